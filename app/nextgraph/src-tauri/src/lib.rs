@@ -24,9 +24,10 @@ use zeroize::Zeroize;
 use ng_repo::errors::NgError;
 use ng_repo::log::*;
 use ng_repo::types::*;
-use ng_repo::utils::decode_key;
+use ng_repo::utils::{decode_digest, decode_key};
 
 use ng_net::app_protocol::*;
+use ng_net::permissions::{AccessGrantV0, AppManifest};
 use ng_net::types::{ClientInfo, CreateAccountBSP, Invitation};
 use ng_net::utils::{decode_invitation_string, spawn_and_log_error, Receiver, ResultSend};
 
@@ -621,6 +622,41 @@ async fn new_orm_start_graph(
     Ok(req)
 }
 
+#[tauri::command(rename_all = "snake_case")]
+async fn new_orm_resume_graph(
+    graph_scope: Vec<String>,
+    subject_scope: Vec<String>,
+    shape_type: ng_net::orm::OrmShapeType,
+    known_heads: Vec<String>,
+    session_id: u64,
+) -> Result<AppRequest, String> {
+    let graph_nuris: Vec<NuriV0> = if graph_scope.is_empty() {
+        vec![NuriV0::new_entire_user_site()]
+    } else {
+        let mut graph_nuris = vec![];
+        for gs in graph_scope {
+            if gs.is_empty() {
+                graph_nuris = vec![NuriV0::new_entire_user_site()];
+                break;
+            }
+            graph_nuris.push(
+                NuriV0::new_from(&gs).map_err(|_| "Deserialization error of scope".to_string())?,
+            );
+        }
+        graph_nuris
+    };
+    let known_heads = known_heads
+        .iter()
+        .map(|h| decode_digest(h))
+        .collect::<Result<Vec<ObjectId>, NgError>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut req =
+        AppRequest::new_orm_resume_graph(graph_nuris, subject_scope, shape_type, known_heads);
+    req.set_session_id(session_id);
+    Ok(req)
+}
+
 #[tauri::command(rename_all = "snake_case")]
 async fn new_orm_start_discrete(nuri: String, session_id: u64) -> Result<AppRequest, String> {
     let nuri = NuriV0::new_from(&nuri).map_err(|_| "Deserialization error of nuri".to_string())?;
@@ -1091,6 +1127,123 @@ async fn wallet_close(wallet_name: String) -> Result<(), String> {
         .map_err(|e: NgError| e.to_string())
 }
 
+#[tauri::command(rename_all = "snake_case")]
+async fn wallet_set_password(wallet_name: String, password: String) -> Result<(), String> {
+    nextgraph::local_broker::wallet_set_password(&wallet_name, password)
+        .await
+        .map_err(|e: NgError| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+async fn wallet_regenerate_mnemonic(
+    wallet_name: String,
+    pin: [u8; 4],
+) -> Result<Vec<String>, String> {
+    nextgraph::local_broker::wallet_regenerate_mnemonic(&wallet_name, pin)
+        .await
+        .map_err(|e: NgError| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+async fn wallet_regenerate_pazzle(wallet_name: String, pin: [u8; 4]) -> Result<Vec<u8>, String> {
+    nextgraph::local_broker::wallet_regenerate_pazzle(&wallet_name, pin)
+        .await
+        .map_err(|e: NgError| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+async fn wallet_remove_login_method(
+    wallet_name: String,
+    method: LoginMethodType,
+) -> Result<(), String> {
+    nextgraph::local_broker::wallet_remove_login_method(&wallet_name, method)
+        .await
+        .map_err(|e: NgError| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+async fn wallet_social_recovery_setup(
+    session_id: u64,
+    contacts: Vec<(String, String)>,
+    threshold: u8,
+) -> Result<(), String> {
+    nextgraph::local_broker::wallet_social_recovery_setup(session_id, contacts, threshold)
+        .await
+        .map_err(|e: NgError| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+async fn wallet_social_recovery_request(
+    wallet: Wallet,
+) -> Result<(String, String, PrivKey), String> {
+    Ok(nextgraph::local_broker::wallet_social_recovery_request(
+        &wallet,
+    ))
+}
+
+#[tauri::command(rename_all = "snake_case")]
+async fn wallet_social_recovery_answer(
+    session_id: u64,
+    request_code: String,
+    verification_code: String,
+) -> Result<String, String> {
+    nextgraph::local_broker::wallet_social_recovery_answer(
+        session_id,
+        request_code,
+        verification_code,
+    )
+    .await
+    .map_err(|e: NgError| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+async fn wallet_open_with_recovery_shares(
+    wallet: Wallet,
+    key: PrivKey,
+    response_codes: Vec<String>,
+) -> Result<SensitiveWallet, String> {
+    nextgraph::local_broker::wallet_open_with_recovery_shares(&wallet, key, response_codes)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+async fn app_grant_access(
+    wallet_name: String,
+    manifest: AppManifest,
+    accepted: Vec<String>,
+) -> Result<Vec<AccessGrantV0>, String> {
+    nextgraph::local_broker::app_grant_access(&wallet_name, manifest, accepted)
+        .await
+        .map_err(|e: NgError| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+async fn app_revoke_access(
+    wallet_name: String,
+    app: String,
+    grant_id: String,
+) -> Result<(), String> {
+    nextgraph::local_broker::app_revoke_access(&wallet_name, app, grant_id)
+        .await
+        .map_err(|e: NgError| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+async fn app_session_bind(session_id: u64, app: String) -> Result<(), String> {
+    nextgraph::local_broker::app_session_bind(session_id, app)
+        .await
+        .map_err(|e: NgError| e.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+async fn app_list_access(
+    wallet_name: String,
+) -> Result<HashMap<String, Vec<AccessGrantV0>>, String> {
+    nextgraph::local_broker::app_list_access(&wallet_name)
+        .await
+        .map_err(|e: NgError| e.to_string())
+}
+
 #[derive(Serialize, Deserialize)]
 struct ConnectionInfo {
     pub server_id: String,
@@ -1210,6 +1363,18 @@ impl AppBuilder {
                 wallet_import_rendezvous,
                 wallet_import_from_code,
                 wallet_close,
+                wallet_set_password,
+                wallet_regenerate_mnemonic,
+                wallet_regenerate_pazzle,
+                wallet_remove_login_method,
+                wallet_social_recovery_setup,
+                wallet_social_recovery_request,
+                wallet_social_recovery_answer,
+                wallet_open_with_recovery_shares,
+                app_grant_access,
+                app_revoke_access,
+                app_session_bind,
+                app_list_access,
                 encode_create_account,
                 session_start,
                 session_start_remote,
@@ -1228,6 +1393,7 @@ impl AppBuilder {
                 discrete_update,
                 app_request_stream,
                 new_orm_start_graph,
+                new_orm_resume_graph,
                 new_orm_start_discrete,
                 graph_orm_update,
                 discrete_orm_update,
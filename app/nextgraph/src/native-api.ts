@@ -32,6 +32,18 @@ const mapping = {
     wallet_import_rendezvous: ["size"],
     wallet_import_from_code: ["code"],
     wallet_close: ["wallet_name"],
+    wallet_set_password: ["wallet_name", "password"],
+    wallet_regenerate_mnemonic: ["wallet_name", "pin"],
+    wallet_regenerate_pazzle: ["wallet_name", "pin"],
+    wallet_remove_login_method: ["wallet_name", "method"],
+    wallet_social_recovery_setup: ["session_id", "contacts", "threshold"],
+    wallet_social_recovery_request: ["wallet"],
+    wallet_social_recovery_answer: ["session_id", "request_code", "verification_code"],
+    wallet_open_with_recovery_shares: ["wallet", "key", "response_codes"],
+    app_grant_access: ["wallet_name", "manifest", "accepted"],
+    app_revoke_access: ["wallet_name", "app", "grant_id"],
+    app_session_bind: ["session_id", "app"],
+    app_list_access: ["wallet_name"],
     encode_create_account: ["payload"],
     session_start: ["wallet_name", "user"],
     session_start_remote: ["wallet_name", "user", "peer_id"],
@@ -210,6 +222,7 @@ const tauri_handler = {
                 path[0] === "app_request_stream" ||
                 path[0] === "doc_subscribe" ||
                 path[0] === "orm_start_graph" ||
+                path[0] === "orm_resume_graph" ||
                 path[0] === "orm_start_discrete" ||
                 path[0] === "file_get"
             ) {
@@ -233,6 +246,15 @@ const tauri_handler = {
                         session_id: args[3]
                     });
                     callback = args[4];
+                } else if (path[0] === "orm_resume_graph") {
+                    request = await invoke("new_orm_resume_graph", {
+                        graph_scope: args[0],
+                        subject_scope: args[1],
+                        shape_type: args[2],
+                        known_heads: args[3],
+                        session_id: args[4]
+                    });
+                    callback = args[5];
                 } else if (path[0] === "orm_start_discrete") {
                     request = await invoke("new_orm_start_discrete", {
                         nuri: args[0],
@@ -364,7 +386,9 @@ const tauri_handler = {
             } else if (
                 path[0] === "wallet_open_with_pazzle" ||
                 path[0] === "wallet_open_with_mnemonic_words" ||
-                path[0] === "wallet_open_with_mnemonic"
+                path[0] === "wallet_open_with_mnemonic" ||
+                path[0] === "wallet_open_with_recovery_shares" ||
+                path[0] === "wallet_social_recovery_request"
             ) {
                 let arg: any = {};
                 args.map((el, ix) => (arg[mapping[path[0]][ix]] = el));
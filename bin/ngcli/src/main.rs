@@ -33,9 +33,14 @@ use ng_repo::store::Store;
 use ng_repo::types::*;
 use ng_repo::utils::{decode_priv_key, display_timestamp, generate_keypair, timestamp_after};
 
+use ng_net::actor::SoS;
 use ng_net::actors::admin::*;
-use ng_net::app_protocol::{NuriTargetV0, NuriV0};
+use ng_net::app_protocol::{
+    AppRequest, AppRequestCommandV0, AppRequestPayload, AppRequestV0, AppResponse, AppResponseV0,
+    AppSessionStart, AppSessionStartV0, AppSessionStop, AppSessionStopV0, NuriTargetV0, NuriV0,
+};
 use ng_net::broker::{Broker, BROKER};
+use ng_net::connection::{AppConfig, StartConfig};
 use ng_net::types::*;
 
 use ng_client_ws::remote_ws::ConnectionWebSocket;
@@ -80,6 +85,7 @@ pub enum NgcliError {
     OtherConfigErrorStr(&'static str),
     CannotSaveConfig(String),
     FileError(FileError),
+    QueryError(String),
 }
 
 impl Error for NgcliError {}
@@ -130,6 +136,7 @@ impl fmt::Display for NgcliError {
             Self::ProtocolError(e) => write!(f, "{}", e),
             Self::OtherConfigError(s) => write!(f, "{}", s),
             Self::OtherConfigErrorStr(s) => write!(f, "{}", s),
+            Self::QueryError(s) => write!(f, "query failed. {}", s),
             _ => write!(f, "{:?}", self),
         }
     }
@@ -231,6 +238,102 @@ fn get_random_access_file(
     Ok(())
 }
 
+/// Opens a session on the server, sends the ReadQueryExplain request and closes the session
+async fn explain_in_session(
+    user_id: UserId,
+    server_peer_id: DirectPeerId,
+    sparql: String,
+    nuri: NuriV0,
+) -> Result<String, NgcliError> {
+    let session_id = 1;
+    let broker = BROKER.read().await;
+    let user = Some(user_id);
+    let server = Some(server_peer_id);
+    match broker
+        .request::<AppSessionStart, AppResponse>(
+            &user,
+            &server,
+            AppSessionStart::V0(AppSessionStartV0 {
+                session_id,
+                credentials: None,
+                user_id,
+                detach: true,
+                access: None,
+            }),
+        )
+        .await?
+    {
+        SoS::Single(AppResponse::V0(AppResponseV0::SessionStart(_))) => {}
+        _ => return Err(NgError::InvalidResponse.into()),
+    }
+
+    let request = AppRequest::V0(AppRequestV0 {
+        command: AppRequestCommandV0::new_read_query_explain(),
+        nuri,
+        payload: Some(AppRequestPayload::new_sparql_query(sparql, None)),
+        session_id,
+    });
+    let res = broker
+        .request::<AppRequest, AppResponse>(&user, &server, request)
+        .await;
+
+    let _ = broker
+        .request::<AppSessionStop, EmptyAppResponse>(
+            &user,
+            &server,
+            AppSessionStop::V0(AppSessionStopV0 {
+                session_id,
+                force_close: false,
+            }),
+        )
+        .await;
+
+    match res? {
+        SoS::Single(AppResponse::V0(AppResponseV0::QueryExplanation(json))) => Ok(json),
+        SoS::Single(AppResponse::V0(AppResponseV0::Error(e))) => Err(NgcliError::QueryError(e)),
+        _ => Err(NgError::InvalidResponse.into()),
+    }
+}
+
+/// Sends the query to the verifier of the user on the server, with the ReadQueryExplain request of the app protocol.
+/// The server must have the credentials of the user, as it runs the verifier in headless mode.
+async fn explain_query(
+    peer_privk: PrivKey,
+    config_v0: &CliConfigV0,
+    sparql: String,
+    nuri: NuriV0,
+) -> Result<String, NgcliError> {
+    let user_priv = config_v0.user.to_owned().unwrap();
+    let user_id = user_priv.to_pub();
+    let peer_pubk = peer_privk.to_pub();
+    BROKER
+        .write()
+        .await
+        .connect(
+            Arc::new(Box::new(ConnectionWebSocket {})),
+            peer_privk,
+            peer_pubk,
+            config_v0.peer_id,
+            StartConfig::App(AppConfig {
+                user_priv: Some(user_priv),
+                info: ClientInfo::new(
+                    ClientType::Cli,
+                    "".to_string(),
+                    env!("CARGO_PKG_VERSION").to_string(),
+                ),
+                addr: BindAddress {
+                    port: config_v0.port,
+                    ip: (&config_v0.ip).into(),
+                },
+            }),
+        )
+        .await?;
+
+    let res = explain_in_session(user_id, config_v0.peer_id, sparql, nuri).await;
+    Broker::close_all_connections().await;
+    res
+}
+
 async fn main_inner() -> Result<(), NgcliError> {
     let matches = command!()
             .arg(arg!(
@@ -317,6 +420,12 @@ async fn main_inner() -> Result<(), NgcliError> {
                     .arg(arg!([NURI] "NextGraph URI of the commit(s) or object, containing the ReadCap in the form :c:k :j:k and optionally :s:k and the usual :o:v:l").required(true))
                     .arg(arg!(-s --save "Saves the binary file(s) of the commits that have the type AddFile").required(false))
                     .arg(arg!(-o --output <FILENAME> "Gives a filename for the binary file(s) to be saved locally. only used if no filename is present in metadata").required(false))
+                ).subcommand(
+                Command::new("explain")
+                    .about("evaluates a SPARQL query on the data of the user, with the verifier running on the server, and outputs in JSON the optimized plan with the duration and number of results of each operator. The server must have the credentials of the user (headless mode). Another session of the user opened on the server from a different client is detached")
+                    .arg(arg!([QUERY] "the SPARQL query").required_unless_present("file"))
+                    .arg(arg!(-f --file <FILENAME> "reads the SPARQL query from a file").value_parser(value_parser!(PathBuf)).required(false))
+                    .arg(arg!(-n --nuri <NURI> "NextGraph URI of the document or store to query. the entire user site by default").required(false))
                 )
             .get_matches();
 
@@ -767,6 +876,20 @@ async fn main_inner() -> Result<(), NgcliError> {
 
     //log_debug!("{:?}", config);
     match matches.subcommand() {
+        Some(("explain", sub_matches)) => {
+            let query = match sub_matches.get_one::<PathBuf>("file") {
+                Some(file) => read_to_string(file)?,
+                None => sub_matches.get_one::<String>("QUERY").unwrap().clone(),
+            };
+            let nuri = match sub_matches.get_one::<String>("nuri") {
+                Some(nuri) => NuriV0::new_from(nuri)?,
+                None => NuriV0::new_entire_user_site(),
+            };
+            let explanation =
+                explain_query(PrivKey::Ed25519PrivKey(keys[1]), config_v0, query, nuri).await?;
+            println!("{explanation}");
+            return Ok(());
+        }
         Some(("admin", sub_matches)) => match sub_matches.subcommand() {
             Some(("add-user", sub2_matches)) => {
                 log_debug!("add-user");
@@ -620,6 +620,85 @@ impl RocksDbServerStorage {
         Ok(self.add_block_(overlay, &mut overlay_storage, block)?)
     }
 
+    /// records that this user pinned the object. An object is pinned at most once per user.
+    pub(crate) fn pin_object(
+        &self,
+        overlay: &OverlayId,
+        object_id: &ObjectId,
+        user_id: &UserId,
+    ) -> Result<(), ServerError> {
+        if overlay.is_outer() {
+            return Err(ServerError::OverlayMismatch);
+        }
+        self.check_overlay(overlay)?;
+
+        let mut inner_overlay_storage = OverlayStorage::open(overlay, &self.core_storage)?;
+        let pin = (*object_id, *user_id);
+        match OverlayStorage::OBJECT_PINS.has(&mut inner_overlay_storage, &pin) {
+            Ok(_) => return Ok(()),
+            Err(StorageError::NotFound) => {}
+            Err(e) => return Err(e.into()),
+        }
+        OverlayStorage::OBJECT_PINS.add(&mut inner_overlay_storage, &pin)?;
+        OverlayStorage::OBJECTS.increment(&mut inner_overlay_storage, object_id)?;
+        Ok(())
+    }
+
+    /// removes the pin of this user on the object. Only a user that pinned the object can unpin it.
+    /// When no user pins the object anymore, the refcount of its blocks is decremented,
+    /// and the blocks that are not referenced anymore are removed.
+    /// The children of a block that is still referenced are kept.
+    pub(crate) fn unpin_object(
+        &self,
+        overlay: &OverlayId,
+        object_id: &ObjectId,
+        user_id: &UserId,
+    ) -> Result<(), ServerError> {
+        if overlay.is_outer() {
+            return Err(ServerError::OverlayMismatch);
+        }
+        let outer_overlay = self.check_overlay(overlay)?;
+
+        let mut inner_overlay_storage = OverlayStorage::open(overlay, &self.core_storage)?;
+        let pin = (*object_id, *user_id);
+        OverlayStorage::OBJECT_PINS
+            .has(&mut inner_overlay_storage, &pin)
+            .map_err(|e| match e {
+                StorageError::NotFound => ServerError::AccessDenied,
+                _ => e.into(),
+            })?;
+        OverlayStorage::OBJECT_PINS.remove(&mut inner_overlay_storage, &pin)?;
+        if !OverlayStorage::OBJECTS.decrement(&mut inner_overlay_storage, object_id)? {
+            // other users still pin this object
+            return Ok(());
+        }
+
+        let overlay = &outer_overlay;
+        let mut overlay_storage = OverlayStorage::new(overlay, &self.core_storage);
+
+        let mut to_visit = vec![*object_id];
+        while let Some(block_id) = to_visit.pop() {
+            let released = match OverlayStorage::BLOCKS.decrement(&mut overlay_storage, &block_id) {
+                Ok(released) => released,
+                Err(StorageError::NotFound) => continue,
+                Err(e) => return Err(e.into()),
+            };
+            if released {
+                let block = match self.block_storage.read().unwrap().get(overlay, &block_id) {
+                    Ok(block) => block,
+                    Err(StorageError::NotFound) => continue,
+                    Err(e) => return Err(e.into()),
+                };
+                to_visit.extend(block.children().iter());
+                self.block_storage
+                    .write()
+                    .unwrap()
+                    .del(overlay, &block_id)?;
+            }
+        }
+        Ok(())
+    }
+
     fn add_block_(
         &self,
         overlay_id: &OverlayId,
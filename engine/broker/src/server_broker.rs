@@ -34,14 +34,16 @@ use ng_net::{
     app_protocol::*,
     broker::{ClientPeerId, BROKER},
     connection::NoiseFSM,
+    permissions::AppAccess,
     server_broker::IServerBroker,
     types::*,
     utils::{spawn_and_log_error, Receiver, ResultSend, Sender},
 };
 
 use ng_verifier::{
+    graph_import::GraphImportRun,
     site::SiteV0,
-    types::{BrokerPeerId, VerifierConfig, VerifierConfigType},
+    types::{BrokerPeerId, CancelFn, VerifierConfig, VerifierConfigType},
     verifier::Verifier,
 };
 
@@ -139,6 +141,51 @@ struct DetachableVerifier {
     verifier: Verifier,
 }
 
+/// Commits the batches of a graph import in a spawned task, locking the verifier for one batch at a time,
+/// so the GraphImportProgress responses are sent while the import runs.
+fn spawn_graph_import(
+    session_lock: Arc<RwLock<DetachableVerifier>>,
+    session_id: (DirectPeerId, u64),
+    mut run: GraphImportRun,
+) -> (Receiver<AppResponse>, CancelFn) {
+    let (mut tx, rx) = mpsc::unbounded::<AppResponse>();
+    let mut tx_cancel = tx.clone();
+    spawn_and_log_error(async move {
+        let response = loop {
+            let res = {
+                let mut session = session_lock.write().await;
+                if session.attached != Some(session_id) {
+                    Err(NgError::SessionNotFound)
+                } else {
+                    session
+                        .verifier
+                        .graph_import_next_batch(&mut run)
+                        .await
+                        .map_err(|e| e.into())
+                }
+            };
+            match res {
+                Err(e) => break AppResponse::error(e.to_string()),
+                Ok(false) => break run.into_response(),
+                Ok(true) => {
+                    if tx.send(run.progress()).await.is_err() {
+                        // cancelled
+                        return Ok(());
+                    }
+                }
+            }
+        };
+        let _ = tx.send(response).await;
+        let _ = tx.send(AppResponse::V0(AppResponseV0::EndOfStream)).await;
+        tx.close_channel();
+        Ok(())
+    });
+    let fnonce = Box::new(move || {
+        tx_cancel.close_channel();
+    });
+    (rx, fnonce as CancelFn)
+}
+
 pub struct ServerBrokerState {
     #[allow(dead_code)]
     overlays: HashMap<OverlayId, OverlayInfo>,
@@ -148,7 +195,8 @@ pub struct ServerBrokerState {
     local_subscriptions: HashMap<(OverlayId, TopicId), HashMap<PubKey, Option<UserId>>>,
 
     verifiers: HashMap<UserId, Arc<RwLock<DetachableVerifier>>>,
-    remote_apps: HashMap<(DirectPeerId, u64), UserId>,
+    /// the user of each app session, and the grants of the session when it was opened by a third-party app
+    remote_apps: HashMap<(DirectPeerId, u64), (UserId, Option<AppAccess>)>,
 
     wallet_rendezvous: HashMap<SymKey, Sender<ExportedWallet>>,
     wallet_exports: HashMap<SymKey, ExportedWallet>,
@@ -406,6 +454,24 @@ impl IServerBroker for ServerBroker {
         self.storage.get_block(overlay_id, block_id)
     }
 
+    fn pin_object(
+        &self,
+        overlay_id: &OverlayId,
+        object_id: &ObjectId,
+        user_id: &UserId,
+    ) -> Result<(), ServerError> {
+        self.storage.pin_object(overlay_id, object_id, user_id)
+    }
+
+    fn unpin_object(
+        &self,
+        overlay_id: &OverlayId,
+        object_id: &ObjectId,
+        user_id: &UserId,
+    ) -> Result<(), ServerError> {
+        self.storage.unpin_object(overlay_id, object_id, user_id)
+    }
+
     fn next_seq_for_peer(&self, peer: &PeerId, seq: u64) -> Result<(), ServerError> {
         self.storage.next_seq_for_peer(peer, seq)
     }
@@ -514,18 +580,20 @@ impl IServerBroker for ServerBroker {
         };
 
         let session_id = (remote, req.session_id());
-        let session_lock = {
+        let (session_lock, has_access) = {
             let lock = self.state.read().await;
-            let user_id = lock
+            let (user_id, access) = lock
                 .remote_apps
                 .get(&session_id)
-                .ok_or(ServerError::SessionNotFound)?
-                .to_owned();
+                .ok_or(ServerError::SessionNotFound)?;
 
-            Arc::clone(
-                lock.verifiers
-                    .get(&user_id)
-                    .ok_or(ServerError::SessionNotFound)?,
+            (
+                Arc::clone(
+                    lock.verifiers
+                        .get(user_id)
+                        .ok_or(ServerError::SessionNotFound)?,
+                ),
+                access.is_some(),
             )
         };
 
@@ -535,8 +603,35 @@ impl IServerBroker for ServerBroker {
             return Err(ServerError::SessionDetached);
         }
 
+        if has_access {
+            // the grants are updated in place, as the ones that can only be used once are consumed
+            let mut write_lock = self.state.write().await;
+            if let Some((_, Some(access))) = write_lock.remote_apps.get_mut(&session_id) {
+                session.verifier.check_app_access(access, &req)?;
+            }
+        }
+
         if req.command().is_stream() {
-            let res = session.verifier.app_request_stream(req).await;
+            let res = match req {
+                AppRequest::V0(AppRequestV0 {
+                    command: AppRequestCommandV0::GraphImport,
+                    nuri,
+                    payload,
+                    ..
+                }) => session
+                    .verifier
+                    .graph_import_start(nuri, payload)
+                    .await
+                    .map(|mut run| {
+                        if has_access {
+                            run.restrict_to_target();
+                        }
+                        spawn_graph_import(Arc::clone(&session_lock), session_id, run)
+                    }),
+                _ => session.verifier.app_request_stream(req).await,
+            };
+            // the responses don't need the verifier, and an import locks it for each of its batches
+            drop(session);
 
             match res {
                 Err(e) => {
@@ -598,6 +693,16 @@ impl IServerBroker for ServerBroker {
     ) -> Result<AppSessionStartResponse, ServerError> {
         let user_id = req.user_id();
         let id = (remote, req.session_id());
+        // the grants of a third-party app must have been signed by the user, for the peer of the app
+        let access = match req.access() {
+            Some(signed) => Some(
+                signed
+                    .clone()
+                    .verify(user_id, &remote)
+                    .map_err(|_| ServerError::AccessDenied)?,
+            ),
+            None => None,
+        };
         let verifier_lock_res = {
             let lock = self.state.read().await;
             lock.verifiers.get(user_id).map(|l| Arc::clone(l))
@@ -669,7 +774,7 @@ impl IServerBroker for ServerBroker {
             public_store: *verifier.public_store_id(),
         });
         let mut write_lock = self.state.write().await;
-        if let Some(previous_user) = write_lock.remote_apps.insert(id, *user_id) {
+        if let Some((previous_user, _)) = write_lock.remote_apps.insert(id, (*user_id, access)) {
             // weird. another session was opened for this id.
             // we have to stop it otherwise it would be dangling.
             if previous_user != *user_id {
@@ -701,7 +806,7 @@ impl IServerBroker for ServerBroker {
 
         let mut write_lock = self.state.write().await;
         let must_be_destroyed = {
-            let session_user = write_lock
+            let (session_user, _) = write_lock
                 .remote_apps
                 .remove(&id)
                 .ok_or(ServerError::SessionNotFound)?;
@@ -53,13 +53,20 @@ impl<'a> OverlayStorage<'a> {
     pub const BLOCKS: MultiCounterColumn<Self, BlockId> = MultiCounterColumn::new(b'b');
     // Overlay <-> Object refcount
     pub const OBJECTS: MultiCounterColumn<Self, ObjectId> = MultiCounterColumn::new(b'j');
+    // Overlay <-> (Object, User) pins. OBJECTS counts the users that pinned each object
+    /// BE CAREFUL: the pins and their count are stored on the InnerOverlay
+    pub const OBJECT_PINS: MultiValueColumn<Self, (ObjectId, UserId)> = MultiValueColumn::new(b'p');
 
     pub const CLASS: Class<'a> = Class::new(
         "Overlay",
         Some(Self::PREFIX),
         Some(&Self::TYPE),
         &[&Self::TOPIC as &dyn ISingleValueColumn],
-        &[&Self::BLOCKS as &dyn IMultiValueColumn, &Self::OBJECTS],
+        &[
+            &Self::BLOCKS as &dyn IMultiValueColumn,
+            &Self::OBJECTS,
+            &Self::OBJECT_PINS,
+        ],
     );
 
     pub fn new(id: &OverlayId, storage: &'a dyn KCVStorage) -> Self {
@@ -86,10 +86,22 @@ impl EActor for Actor<'_, AppSessionStart, AppSessionStartResponse> {
             let lock = fsm.lock().await;
             let remote = lock.remote_peer();
 
-            //TODO: if fsm.get_user_id is some, check that user_priv_key in credentials matches.
+            // if the connection is authenticated with a user, the session can only be opened for that user
+            let user_matches = match lock.user_id() {
+                Ok(user) => {
+                    user == *req.user_id()
+                        && req
+                            .credentials()
+                            .as_ref()
+                            .map_or(true, |c| c.user_key.to_pub() == user)
+                }
+                Err(_) => true,
+            };
             //TODO: if no user in fsm (headless), check user in request is allowed
             if remote.is_none() {
                 Err(ServerError::BrokerError)
+            } else if !user_matches {
+                Err(ServerError::AccessDenied)
             } else {
                 let (sb, broker_id) = {
                     let b = BROKER.read().await;
@@ -16,6 +16,10 @@ pub mod blocks_exist;
 
 pub mod blocks_get;
 
+pub mod object_pin;
+
+pub mod object_unpin;
+
 pub mod wallet_put_export;
 
 pub mod inbox_post;
@@ -14,13 +14,14 @@ use serde::{Deserialize, Serialize};
 use ng_repo::errors::NgError;
 #[allow(unused_imports)]
 use ng_repo::log::*;
-use ng_repo::repo::CommitInfo;
+use ng_repo::repo::{CommitInfo, UserInfo};
 use ng_repo::types::*;
 use ng_repo::utils::{decode_digest, decode_key, decode_sym_key};
 use ng_repo::utils::{decode_overlayid, display_timestamp_local};
 use serde_json::Value;
 
-use crate::orm::{OrmPatches, OrmShapeType};
+use crate::orm::{OrmPatches, OrmShapeType, OrmValidationReport};
+use crate::permissions::SignedAppAccess;
 use crate::types::*;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -30,14 +31,18 @@ pub enum AppFetchContentV0 {
     Update,
     ReadQuery,
     WriteQuery,
-    RdfDump,
+    RdfExport, // needs the Nuri of doc/branch/commits/store or entire user site
     History,
     SignatureStatus,
     SignatureRequest,
     SignedSnapshotRequest,
     Header,
     CurrentHeads,
-    //Invoke,
+    Validate,
+    TextSearch, // needs the Nuri of a store, or of the entire user site
+    ReadQueryStream,
+    ReadQueryExplain, // evaluates the query and returns the plan with the statistics of each operator
+                      //Invoke,
 }
 
 impl AppFetchContentV0 {
@@ -757,29 +762,53 @@ pub enum AppRequestCommandV0 {
     OrmGraphUpdate,
     OrmDiscreteUpdate,
     OrmStop,
+    GraphImport,  // needs the Nuri of branch/doc/store
+    RefreshCaps,  // needs the Nuri of doc
+    MemberUpdate, // needs the Nuri of doc
 }
 
 impl AppRequestCommandV0 {
     pub fn is_stream(&self) -> bool {
         match self {
             Self::Fetch(AppFetchContentV0::Subscribe)
+            | Self::Fetch(AppFetchContentV0::ReadQueryStream)
+            | Self::Fetch(AppFetchContentV0::RdfExport)
             | Self::FileGet
             | Self::OrmStartGraph
-            | Self::OrmStartDiscrete => true,
+            | Self::OrmStartDiscrete
+            | Self::GraphImport => true,
             _ => false,
         }
     }
     pub fn new_read_query() -> Self {
         AppRequestCommandV0::Fetch(AppFetchContentV0::ReadQuery)
     }
+    pub fn new_read_query_stream() -> Self {
+        AppRequestCommandV0::Fetch(AppFetchContentV0::ReadQueryStream)
+    }
+    pub fn new_read_query_explain() -> Self {
+        AppRequestCommandV0::Fetch(AppFetchContentV0::ReadQueryExplain)
+    }
     pub fn new_write_query() -> Self {
         AppRequestCommandV0::Fetch(AppFetchContentV0::WriteQuery)
     }
     pub fn new_update() -> Self {
         AppRequestCommandV0::Fetch(AppFetchContentV0::Update)
     }
-    pub fn new_rdf_dump() -> Self {
-        AppRequestCommandV0::Fetch(AppFetchContentV0::RdfDump)
+    pub fn new_rdf_export() -> Self {
+        AppRequestCommandV0::Fetch(AppFetchContentV0::RdfExport)
+    }
+    pub fn new_text_search() -> Self {
+        AppRequestCommandV0::Fetch(AppFetchContentV0::TextSearch)
+    }
+    pub fn new_graph_import() -> Self {
+        AppRequestCommandV0::GraphImport
+    }
+    pub fn new_refresh_caps() -> Self {
+        AppRequestCommandV0::RefreshCaps
+    }
+    pub fn new_member_update() -> Self {
+        AppRequestCommandV0::MemberUpdate
     }
     pub fn new_history() -> Self {
         AppRequestCommandV0::Fetch(AppFetchContentV0::History)
@@ -808,6 +837,9 @@ impl AppRequestCommandV0 {
     pub fn new_fetch_header() -> Self {
         AppRequestCommandV0::Fetch(AppFetchContentV0::Header)
     }
+    pub fn new_validate() -> Self {
+        AppRequestCommandV0::Fetch(AppFetchContentV0::Validate)
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -871,6 +903,26 @@ impl AppRequest {
         )
     }
 
+    /// Like `new_orm_start_graph`, but the client already holds the state of the documents at `known_heads`.
+    /// Only the changes since those heads are sent back, with a `GraphOrmResumed`.
+    pub fn new_orm_resume_graph(
+        graph_scope: Vec<NuriV0>,
+        subject_scope: Vec<String>,
+        shape_type: OrmShapeType,
+        known_heads: Vec<ObjectId>,
+    ) -> Self {
+        AppRequest::new(
+            AppRequestCommandV0::OrmStartGraph,
+            NuriV0::new_empty(),
+            Some(AppRequestPayload::V0(AppRequestPayloadV0::OrmResume((
+                shape_type,
+                graph_scope,
+                subject_scope,
+                known_heads,
+            )))),
+        )
+    }
+
     pub fn new_orm_start_discrete(nuri: NuriV0) -> Self {
         AppRequest::new(AppRequestCommandV0::OrmStartDiscrete, nuri, None)
     }
@@ -967,6 +1019,9 @@ pub struct AppSessionStartV0 {
     pub user_id: UserId,
 
     pub detach: bool,
+
+    /// the grants of the third-party app that opens the session, signed by the user. None when the session is opened by the user themselves
+    pub access: Option<SignedAppAccess>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -1002,6 +1057,11 @@ impl AppSessionStart {
             Self::V0(v0) => &v0.user_id,
         }
     }
+    pub fn access(&self) -> &Option<SignedAppAccess> {
+        match self {
+            Self::V0(v0) => &v0.access,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -1010,6 +1070,35 @@ pub enum DocQuery {
         sparql: String,
         base: Option<String>,
     },
+    V1 {
+        sparql: String,
+        base: Option<String>,
+        /// iana media type of the results. SPARQL results formats (JSON, XML, CSV, TSV) for SELECT and ASK,
+        /// RDF formats for CONSTRUCT and DESCRIBE. None for the default JSON results and serde_bare serialized triples.
+        format: Option<String>,
+    },
+}
+
+impl DocQuery {
+    /// Returns the query, its base, and the media type of the results (always None for V0)
+    pub fn into_parts(self) -> (String, Option<String>, Option<String>) {
+        match self {
+            Self::V0 { sparql, base } => (sparql, base, None),
+            Self::V1 {
+                sparql,
+                base,
+                format,
+            } => (sparql, base, format),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DocTextSearch {
+    /// words to search for. Documents must contain all of them
+    pub query: String,
+    /// maximum number of results. None for the default limit
+    pub limit: Option<u32>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -1058,6 +1147,23 @@ pub struct DocUpdate {
     pub discrete: Option<DiscreteUpdate>,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum DocGraphImportSource {
+    /// The whole serialization, sent along with the request
+    #[serde(with = "serde_bytes")]
+    Inline(Vec<u8>),
+    /// A file previously uploaded with RandomAccessFilePut, read block by block
+    File(ObjectRef),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DocGraphImport {
+    /// iana media type of the serialization (text/turtle, application/trig, application/n-quads, application/rdf+xml,...)
+    pub content_type: String,
+    pub base: Option<String>,
+    pub source: DocGraphImportSource,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DocAddFile {
     pub filename: Option<String>,
@@ -1094,6 +1200,26 @@ pub struct DocCreate {
     pub destination: DocCreateDestination,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DocRefreshCaps {
+    /// users that will not receive the new capabilities of the document
+    pub exclude: Vec<UserId>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DocAddMember {
+    pub member: UserId,
+    /// without permissions, the member is a reader
+    pub permissions: Vec<PermissionV0>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DocRemoveMember {
+    pub member: UserId,
+    /// prevents the user from being invited again
+    pub banned: bool,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DocDelete {
     /// Nuri of doc to delete
@@ -1128,6 +1254,17 @@ pub enum AppRequestPayloadV0 {
     OrmStart((OrmShapeType, Vec<NuriV0>, Vec<String>)),
     OrmUpdate((OrmPatches, u64)),         // subscription id,
     OrmDiscreteUpdate((OrmPatches, u64)), // subscription id
+    Validate(OrmShapeType),
+    GraphImport(DocGraphImport),
+    RdfExport(String), // content_type (iana media type) of the serialization
+    TextSearch(DocTextSearch),
+    RefreshCaps(DocRefreshCaps),
+    AddMember(DocAddMember),
+    RemoveMember(DocRemoveMember),
+    RandomAccessFileResume(u32), // upload_id of an interrupted upload
+    FileGetRange((u64, u64)),    // offset and length of the range of the file to get
+    RandomAccessFilePutContentDefined(String), // content_type. new versions of the file will share most of their blocks
+    OrmResume((OrmShapeType, Vec<NuriV0>, Vec<String>, Vec<ObjectId>)), // known heads
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -1136,12 +1273,64 @@ pub enum AppRequestPayload {
 }
 
 impl AppRequestPayload {
+    pub fn new_refresh_caps(exclude: Vec<UserId>) -> Self {
+        AppRequestPayload::V0(AppRequestPayloadV0::RefreshCaps(DocRefreshCaps { exclude }))
+    }
+    pub fn new_add_member(member: UserId, permissions: Vec<PermissionV0>) -> Self {
+        AppRequestPayload::V0(AppRequestPayloadV0::AddMember(DocAddMember {
+            member,
+            permissions,
+        }))
+    }
+    pub fn new_remove_member(member: UserId, banned: bool) -> Self {
+        AppRequestPayload::V0(AppRequestPayloadV0::RemoveMember(DocRemoveMember {
+            member,
+            banned,
+        }))
+    }
+    pub fn new_rdf_export(content_type: String) -> Self {
+        AppRequestPayload::V0(AppRequestPayloadV0::RdfExport(content_type))
+    }
+    pub fn new_file_get_range(offset: u64, length: u64) -> Self {
+        AppRequestPayload::V0(AppRequestPayloadV0::FileGetRange((offset, length)))
+    }
     pub fn new_sparql_query(sparql: String, base: Option<String>) -> Self {
         AppRequestPayload::V0(AppRequestPayloadV0::Query(DocQuery::V0 { sparql, base }))
     }
+    pub fn new_sparql_query_with_format(
+        sparql: String,
+        base: Option<String>,
+        format: Option<String>,
+    ) -> Self {
+        AppRequestPayload::V0(AppRequestPayloadV0::Query(DocQuery::V1 {
+            sparql,
+            base,
+            format,
+        }))
+    }
     pub fn new_header(title: Option<String>, about: Option<String>) -> Self {
         AppRequestPayload::V0(AppRequestPayloadV0::Header(DocHeader { title, about }))
     }
+    pub fn new_text_search(query: String, limit: Option<u32>) -> Self {
+        AppRequestPayload::V0(AppRequestPayloadV0::TextSearch(DocTextSearch {
+            query,
+            limit,
+        }))
+    }
+    pub fn new_validate(shape_type: OrmShapeType) -> Self {
+        AppRequestPayload::V0(AppRequestPayloadV0::Validate(shape_type))
+    }
+    pub fn new_graph_import(
+        content_type: String,
+        base: Option<String>,
+        source: DocGraphImportSource,
+    ) -> Self {
+        AppRequestPayload::V0(AppRequestPayloadV0::GraphImport(DocGraphImport {
+            content_type,
+            base,
+            source,
+        }))
+    }
     pub fn new_discrete_update(
         head_strings: Vec<String>,
         crdt: String,
@@ -1296,6 +1485,23 @@ pub struct AppTabStoreInfo {
     pub description: Option<String>,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AppTabDocMember {
+    pub id: String,
+    pub is_owner: bool,
+    pub can_edit: bool,
+}
+
+impl From<&UserInfo> for AppTabDocMember {
+    fn from(info: &UserInfo) -> Self {
+        AppTabDocMember {
+            id: info.id.to_string(),
+            is_owner: info.has_perm(&PermissionV0::Owner).is_ok(),
+            can_edit: info.can_edit(),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AppTabDocInfo {
     pub nuri: Option<String>,      //+
@@ -1307,9 +1513,10 @@ pub struct AppTabDocInfo {
     pub authors: Option<Vec<String>>,
     pub inbox: Option<String>,
     pub can_edit: Option<bool>, //+
-                                //TODO stream
-                                //TODO live_editors
-                                //TODO branches
+    //TODO stream
+    //TODO live_editors
+    //TODO branches
+    pub members: Option<Vec<AppTabDocMember>>,
 }
 
 impl AppTabDocInfo {
@@ -1324,6 +1531,7 @@ impl AppTabDocInfo {
             authors: None,
             inbox: None,
             can_edit: None,
+            members: None,
         }
     }
 }
@@ -1350,6 +1558,15 @@ pub struct AppHeader {
     pub class: Option<String>,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TextSearchResult {
+    /// Nuri of the graph of the document
+    pub nuri: String,
+    pub score: f64,
+    /// an excerpt of the text around the first matching word
+    pub snippet: String,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum AppResponseV0 {
     SessionStart(AppSessionStartResponse),
@@ -1366,7 +1583,7 @@ pub enum AppResponseV0 {
     FileBinary(Vec<u8>),
     FileMeta(FileMetaV0),
     #[serde(with = "serde_bytes")]
-    QueryResult(Vec<u8>), // a serialized [SPARQL Query Results JSON Format](https://www.w3.org/TR/sparql11-results-json/)
+    QueryResult(Vec<u8>), // a serialized [SPARQL Query Results JSON Format](https://www.w3.org/TR/sparql11-results-json/), or the results in the format requested in DocQuery
     #[serde(with = "serde_bytes")]
     Graph(Vec<u8>), // a serde serialization of a list of triples. can be transformed on the client side to RDF-JS data model, or JSON-LD, or else (Turtle,...) http://rdf.js.org/data-model-spec/
     Ok,
@@ -1382,6 +1599,12 @@ pub enum AppResponseV0 {
     DiscreteOrmInitial(Value, u64), // Initial JSON object and subscription id for communication
     DiscreteOrmUpdate(OrmPatches),
     OrmError(String),
+    ValidationReport(OrmValidationReport),
+    GraphImportProgress(u64), // number of quads committed so far
+    TextSearchResults(Vec<TextSearchResult>), // ranked by decreasing score
+    QueryExplanation(String), // JSON of the optimized plan, with the duration and number of results of each operator
+    FileUploadResumed(u64),   // position in the file from which the chunks must be sent again
+    GraphOrmResumed(OrmPatches, u64), // Patches since the known heads and subscription id
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
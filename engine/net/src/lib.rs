@@ -18,6 +18,8 @@ pub mod broker;
 
 pub mod orm;
 
+pub mod permissions;
+
 pub mod server_broker;
 
 #[doc(hidden)]
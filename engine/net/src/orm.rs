@@ -33,6 +33,13 @@ pub enum OrmPatchOp {
 #[allow(non_camel_case_types)]
 pub enum OrmPatchType {
     set,
+    /// Positional operation on an ordered collection (`rdf:Seq` or `rdf:List`).
+    /// The last path segment is the zero-based index to insert at or remove from.
+    list,
+    /// Text delta on a `YText` document or an XML text node.
+    /// The last path segment is the (UTF-16) offset in the text. `add` inserts the string `value`
+    /// at the offset, `remove` deletes `value` characters from it.
+    text,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -101,6 +108,52 @@ impl OrmSchemaPredicate {
     }
 }
 
+/* == Validation Report Types == */
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum OrmViolationKind {
+    /// Less values than `minCardinality`.
+    minCardinality,
+    /// More values than `maxCardinality`, and `extra` is not allowed.
+    maxCardinality,
+    /// The required literals are not present.
+    literals,
+    /// A value does not have one of the allowed `dataTypes`.
+    dataType,
+    /// A value does not conform to the nested shape.
+    shape,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OrmPredicateViolation {
+    pub predicate: String,
+    pub readablePredicate: String,
+    pub kind: OrmViolationKind,
+    /// Number of values found for the predicate.
+    pub count: i32,
+    pub minCardinality: i32,
+    pub maxCardinality: i32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OrmSubjectValidation {
+    pub graph: String,
+    pub subject: String,
+    pub shape: String,
+    pub valid: bool,
+    pub violations: Vec<OrmPredicateViolation>,
+}
+
+/// Result of a stand-alone validation of a graph against a shape.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OrmValidationReport {
+    pub shape: String,
+    /// true if all the subjects of the root shape are valid
+    pub conforms: bool,
+    /// The subjects of the root shape come first, followed by the nested subjects.
+    pub subjects: Vec<OrmSubjectValidation>,
+}
+
 pub type WhereConfig = serde_json::Value;
 pub type SelectConfig = serde_json::Value;
 type IsAscending = bool;
@@ -0,0 +1,581 @@
+// Copyright (c) 2022-2025 Niko Bonnieure, Par le Peuple, NextGraph.org developers
+// All rights reserved.
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE2 or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Permissions of the apps: AppManifest, AccessRequest and AccessGrant
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+
+use ng_repo::errors::NgError;
+use ng_repo::types::{DirectPeerId, PrivKey, PubKey, Sig, UserId};
+use ng_repo::utils::{random_key, sign, verify};
+
+use crate::app_protocol::{AppFetchContentV0, AppRequestCommandV0};
+
+/// Access Mode
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AccessMode {
+    Read,
+    Write,
+    Create,
+    HookCreate,
+    HookDelete,
+    Control,
+    Sign,
+    Run,
+    Cron,
+    Query,
+    SocialQuery,
+    Share,
+    DeviceCapability,
+}
+
+impl AccessMode {
+    /// the access mode needed by an AppRequest command
+    pub fn required_by(command: &AppRequestCommandV0) -> Self {
+        match command {
+            AppRequestCommandV0::Fetch(fetch) => match fetch {
+                AppFetchContentV0::Update | AppFetchContentV0::WriteQuery => AccessMode::Write,
+                AppFetchContentV0::SignatureRequest | AppFetchContentV0::SignedSnapshotRequest => {
+                    AccessMode::Sign
+                }
+                _ => AccessMode::Read,
+            },
+            AppRequestCommandV0::Create => AccessMode::Create,
+            AppRequestCommandV0::Delete
+            | AppRequestCommandV0::FilePut
+            | AppRequestCommandV0::Header
+            | AppRequestCommandV0::GraphImport
+            | AppRequestCommandV0::OrmGraphUpdate
+            | AppRequestCommandV0::OrmDiscreteUpdate => AccessMode::Write,
+            AppRequestCommandV0::InboxPost
+            | AppRequestCommandV0::QrCodeProfile
+            | AppRequestCommandV0::QrCodeProfileImport => AccessMode::Share,
+            AppRequestCommandV0::SocialQueryStart | AppRequestCommandV0::SocialQueryCancel => {
+                AccessMode::SocialQuery
+            }
+            AppRequestCommandV0::RefreshCaps | AppRequestCommandV0::MemberUpdate => {
+                AccessMode::Control
+            }
+            AppRequestCommandV0::Pin
+            | AppRequestCommandV0::UnPin
+            | AppRequestCommandV0::FileGet
+            | AppRequestCommandV0::OrmStartGraph
+            | AppRequestCommandV0::OrmStartDiscrete
+            | AppRequestCommandV0::OrmStop => AccessMode::Read,
+        }
+    }
+
+    /// a Write access also gives Read access
+    pub fn includes(&self, mode: &AccessMode) -> bool {
+        self == mode || (*self == AccessMode::Write && *mode == AccessMode::Read)
+    }
+}
+
+/// Access Scope
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AccessScope {
+    Once,
+    OnceSub,
+    OnceMany,
+    OnceManySub,
+    Permanent,
+    Foreground,
+    Background,
+}
+
+impl AccessScope {
+    /// the grant is only valid for the session it is bound to. It is removed from the wallet when the session is bound.
+    pub fn is_once(&self) -> bool {
+        match self {
+            Self::Once | Self::OnceSub | Self::OnceMany | Self::OnceManySub => true,
+            _ => false,
+        }
+    }
+
+    /// the grant is used up by the first request it allows
+    pub fn is_single_request(&self) -> bool {
+        match self {
+            Self::Once | Self::OnceSub => true,
+            _ => false,
+        }
+    }
+
+    pub fn allows_subscription(&self) -> bool {
+        match self {
+            Self::Once | Self::OnceMany => false,
+            _ => true,
+        }
+    }
+}
+
+/// Access Request Version 0
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AccessRequestV0 {
+    /// ID of the Access Request. Should be the tokenized CommitID of the RDF AccessRequest in the App's manifest Document.
+    pub id: String,
+
+    pub mode: AccessMode,
+
+    /// allowed types for this access mode. Usually a PrimaryClass. can be "any".
+    /// for Runs: name of the service
+    /// for Queries: Nuri of the Sparql, Fragment, ShapeTree or GraphQL
+    /// for Cron: the time interval
+    /// for Share: Stream, e:mail, e:xxx, Contact, Document
+    /// for DeviceCapability: camera, microphone, location, receiveSMS, scanQR, internet
+    pub types: Vec<String>,
+
+    /// allowed scopes for this access mode
+    pub scopes: Vec<AccessScope>,
+
+    /// is this access request optional?
+    pub optional: bool,
+
+    /// request depends on another request (only if optional)
+    pub depends_on: Option<String>,
+}
+
+impl AccessRequestV0 {
+    pub fn new_access_all() -> Self {
+        Self {
+            id: "".to_string(),
+            mode: AccessMode::Read,
+            types: vec!["any".to_string()],
+            scopes: vec![AccessScope::Permanent],
+            optional: false,
+            depends_on: None,
+        }
+    }
+}
+
+/// App Component type
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum AppComponentType {
+    Viewer,
+    Editor,
+    ReadService,
+    WriteService,
+    Model,
+}
+
+/// AppComponentV0 Version 0
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AppComponentV0 {
+    /// Name of the component, can be an official component of the for n:g:z, or custom ones n:xxx:z:yyy or o:xxx
+    pub name: String,
+
+    pub component_type: AppComponentType,
+}
+
+/// Primary Class Install Version 0
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PrimaryClassInstallV0 {
+    /// Primary Class name, can be an official name or a custom name of the form app:n... or app:o:...
+    pub primary_class: String,
+
+    pub components: Vec<AppComponentV0>,
+}
+
+/// App Manifest Version 0
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AppManifestV0 {
+    /// Nuri
+    pub nuri: Option<String>,
+
+    /// Origin (for webapps only)
+    pub origin: Option<String>,
+
+    /// cannot create Documents?
+    pub singleton: bool,
+
+    /// list of Access Requests
+    pub access_requests: Vec<AccessRequestV0>,
+
+    /// installs: list of Viewers, Editors, Services and Models, by PrimaryClass, that will be installed by this app
+    pub installs: HashMap<String, PrimaryClassInstallV0>,
+
+    /// dependencies: list of other apps (Nuri) that needs to be installed before this app can be installed
+    pub dependencies: Vec<String>,
+
+    /// optional name. Only for registered or official apps
+    pub name: Option<String>,
+
+    /// optional title. Broker will enter the domain's homepage title here, if any
+    pub title: Option<String>,
+
+    /// optional description. Broker will enter the domain's homepage description here, if any
+    pub description: Option<String>,
+
+    /// optional icon. Broker will enter the domain's homepage favicon here, if any
+    #[serde(with = "serde_bytes")]
+    pub icon: Vec<u8>,
+
+    /// optional image. Broker will enter the domain's homepage main image here, if any
+    #[serde(with = "serde_bytes")]
+    pub image: Vec<u8>,
+}
+
+/// Web App Manifest
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum AppManifest {
+    V0(AppManifestV0),
+}
+
+impl AppManifest {
+    pub fn new_for_origin_all_access_v0(origin: String) -> Self {
+        AppManifest::V0(AppManifestV0 {
+            nuri: None,
+            origin: Some(origin),
+            singleton: true,
+            access_requests: vec![AccessRequestV0::new_access_all()],
+            installs: HashMap::new(),
+            dependencies: vec![],
+            name: None,
+            title: None,
+            description: None,
+            icon: vec![],
+            image: vec![],
+        })
+    }
+    pub fn new_v0(origin: String, singleton: bool, access_requests: Vec<AccessRequestV0>) -> Self {
+        AppManifest::V0(AppManifestV0 {
+            nuri: None,
+            origin: Some(origin),
+            singleton,
+            access_requests,
+            installs: HashMap::new(),
+            dependencies: vec![],
+            name: None,
+            title: None,
+            description: None,
+            icon: vec![],
+            image: vec![],
+        })
+    }
+    pub fn to_url_param(&self) -> String {
+        let ser = serde_bare::to_vec(self).unwrap();
+        base64_url::encode(&ser)
+    }
+
+    /// the Nuri of the app, or its origin for webapps. The grants are saved in the wallet under this ID.
+    pub fn app_id(&self) -> Result<String, NgError> {
+        match self {
+            AppManifest::V0(v0) => v0
+                .nuri
+                .as_ref()
+                .or(v0.origin.as_ref())
+                .cloned()
+                .ok_or(NgError::InvalidArgument),
+        }
+    }
+
+    /// Grants all the mandatory access requests of the manifest, and the optional ones whose ID is in `accepted`.
+    ///
+    /// Each type of an access request gets its own grant, with the first scope of the request.
+    pub fn grant(
+        &self,
+        accepted: &[String],
+        grantee: UserId,
+    ) -> Result<Vec<AccessGrantV0>, NgError> {
+        let AppManifest::V0(v0) = self;
+        let granted: Vec<&AccessRequestV0> = v0
+            .access_requests
+            .iter()
+            .filter(|r| !r.optional || accepted.contains(&r.id))
+            .collect();
+        let mut grants = vec![];
+        for request in granted.iter() {
+            if let Some(depends_on) = &request.depends_on {
+                if !granted.iter().any(|r| r.id == *depends_on) {
+                    return Err(NgError::InvalidArgument);
+                }
+            }
+            let scope = request.scopes.first().ok_or(NgError::InvalidArgument)?;
+            for access_type in request.types.iter() {
+                grants.push(AccessGrantV0::new(
+                    request,
+                    access_type.clone(),
+                    scope.clone(),
+                    None,
+                    grantee,
+                ));
+            }
+        }
+        Ok(grants)
+    }
+}
+
+/// Access Grant Version 0
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AccessGrantV0 {
+    /// Nuri of tokenized commitID of this grant
+    pub id: String,
+
+    /// reference to the AccessRequest. can be None for PermaCaps
+    pub request: Option<String>,
+
+    pub mode: AccessMode,
+
+    /// Usually a PrimaryClass.
+    /// for Runs: name of the service
+    /// for Queries: Nuri of the Sparql, Fragment, ShapeTree or GraphQL
+    /// for Cron: the time interval
+    /// for Share: Stream, e:mail, e:xxx, Contact, Document
+    /// for DeviceCapability: camera, microphone, location, receiveSMS, scanQR, internet
+    pub access_type: String,
+
+    pub scope: AccessScope,
+
+    /// Nuri of target. Can be None for services
+    pub target: Option<String>,
+
+    /// UserId of grantee (a user or a robot)
+    pub grantee: UserId,
+
+    /// grant depends on another grant
+    pub depends_on: Option<String>,
+}
+
+impl AccessGrantV0 {
+    pub fn new(
+        request: &AccessRequestV0,
+        access_type: String,
+        scope: AccessScope,
+        target: Option<String>,
+        grantee: UserId,
+    ) -> Self {
+        Self {
+            id: base64_url::encode(&random_key()),
+            request: Some(request.id.clone()),
+            mode: request.mode.clone(),
+            access_type,
+            scope,
+            target,
+            grantee,
+            depends_on: request.depends_on.clone(),
+        }
+    }
+
+    /// `access_type` is None when the type of the request is not known, and then only a grant for "any" type allows it.
+    /// `target` is None when the request is not about one document, and then only a grant without target allows it.
+    pub fn allows(
+        &self,
+        mode: &AccessMode,
+        access_type: Option<&String>,
+        target: Option<&String>,
+        subscription: bool,
+    ) -> bool {
+        self.mode.includes(mode)
+            && (self.access_type == "any" || access_type == Some(&self.access_type))
+            && (self.target.is_none() || self.target.as_ref() == target)
+            && (!subscription || self.scope.allows_subscription())
+    }
+}
+
+/// The grants of an app session. Every AppRequest of the session is checked against them.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AppAccess {
+    /// Nuri or origin of the app
+    pub app: String,
+
+    pub grants: Vec<AccessGrantV0>,
+}
+
+impl AppAccess {
+    pub fn new(app: String, grants: Vec<AccessGrantV0>) -> Self {
+        Self { app, grants }
+    }
+
+    /// Checks a request against the grants. The grants that are used up by one request (Once and OnceSub) are removed,
+    /// and the other grants are used first.
+    pub fn check(
+        &mut self,
+        mode: &AccessMode,
+        access_type: Option<&String>,
+        target: Option<&String>,
+        subscription: bool,
+    ) -> Result<(), NgError> {
+        let valid = |grant: &AccessGrantV0| {
+            grant.depends_on.as_ref().map_or(true, |depends_on| {
+                self.grants
+                    .iter()
+                    .any(|g| g.request.as_ref() == Some(depends_on))
+            }) && grant.allows(mode, access_type, target, subscription)
+        };
+        let pos = self
+            .grants
+            .iter()
+            .position(|g| !g.scope.is_single_request() && valid(g))
+            .or_else(|| self.grants.iter().position(|g| valid(g)))
+            .ok_or(NgError::PermissionDenied)?;
+        if self.grants[pos].scope.is_single_request() {
+            self.grants.remove(pos);
+        }
+        Ok(())
+    }
+
+    /// removes a grant that was revoked. It stops applying to the session right away.
+    pub fn revoke(&mut self, grant_id: &String) {
+        self.grants.retain(|g| g.id != *grant_id);
+    }
+}
+
+/// The grants of an app, signed by the user who gave them, for the peer of the app.
+///
+/// Sent in an AppSessionStart, so the broker binds the remote app session to the grants that are in the wallet of the user.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SignedAppAccess {
+    pub access: AppAccess,
+
+    /// the peer that can open sessions with these grants
+    pub peer: DirectPeerId,
+
+    /// Signature over access and peer, by the private key of the user
+    pub sig: Sig,
+}
+
+impl SignedAppAccess {
+    fn content_as_bytes(access: &AppAccess, peer: &DirectPeerId) -> Vec<u8> {
+        serde_bare::to_vec(&(access, peer)).unwrap()
+    }
+
+    pub fn new(
+        access: AppAccess,
+        peer: DirectPeerId,
+        user_privkey: &PrivKey,
+    ) -> Result<Self, NgError> {
+        let user_id = user_privkey.to_pub();
+        if access.grants.iter().any(|g| g.grantee != user_id) {
+            return Err(NgError::InvalidArgument);
+        }
+        let sig = sign(
+            user_privkey,
+            &user_id,
+            &Self::content_as_bytes(&access, &peer),
+        )?;
+        Ok(Self { access, peer, sig })
+    }
+
+    /// Checks that the grants were given by `user_id` to the app of `peer`, and returns them
+    pub fn verify(self, user_id: &UserId, peer: &DirectPeerId) -> Result<AppAccess, NgError> {
+        if self.peer != *peer
+            || !matches!(user_id, PubKey::Ed25519PubKey(_))
+            || self.access.grants.iter().any(|g| g.grantee != *user_id)
+        {
+            return Err(NgError::PermissionDenied);
+        }
+        verify(
+            &Self::content_as_bytes(&self.access, &self.peer),
+            self.sig,
+            *user_id,
+        )
+        .map_err(|_| NgError::PermissionDenied)?;
+        Ok(self.access)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ng_repo::types::PubKey;
+
+    #[test]
+    pub fn check_app_access() {
+        let mut read = AccessRequestV0::new_access_all();
+        read.id = "read".to_string();
+        read.types = vec!["data:graph".to_string()];
+        let write_once = AccessRequestV0 {
+            id: "write".to_string(),
+            mode: AccessMode::Write,
+            types: vec!["any".to_string()],
+            scopes: vec![AccessScope::Once],
+            optional: true,
+            depends_on: Some("read".to_string()),
+        };
+        let manifest = AppManifest::new_v0(
+            "https://app.example".to_string(),
+            true,
+            vec![read, write_once],
+        );
+        assert_eq!(manifest.app_id().unwrap(), "https://app.example");
+
+        let grants = manifest.grant(&[], PubKey::nil()).unwrap();
+        assert_eq!(grants.len(), 1);
+        let mut access = AppAccess::new(manifest.app_id().unwrap(), grants);
+
+        let graph = "data:graph".to_string();
+        let text = "post:text".to_string();
+        assert!(access
+            .check(&AccessMode::Read, Some(&graph), None, true)
+            .is_ok());
+        assert!(access
+            .check(&AccessMode::Read, Some(&text), None, false)
+            .is_err());
+        assert!(access.check(&AccessMode::Read, None, None, false).is_err());
+        assert!(access
+            .check(&AccessMode::Write, Some(&graph), None, false)
+            .is_err());
+
+        let grants = manifest
+            .grant(&["write".to_string()], PubKey::nil())
+            .unwrap();
+        let mut access = AppAccess::new(manifest.app_id().unwrap(), grants);
+        assert!(access
+            .check(&AccessMode::Write, Some(&text), None, true)
+            .is_err());
+        assert!(access
+            .check(&AccessMode::Write, Some(&text), None, false)
+            .is_ok());
+        // the Once grant was used up
+        assert!(access
+            .check(&AccessMode::Write, Some(&text), None, false)
+            .is_err());
+        assert!(access
+            .check(&AccessMode::Read, Some(&graph), None, false)
+            .is_ok());
+
+        let read_grant = access.grants[0].id.clone();
+        access.revoke(&read_grant);
+        assert!(access
+            .check(&AccessMode::Read, Some(&graph), None, false)
+            .is_err());
+    }
+
+    #[test]
+    pub fn signed_app_access() {
+        let (user_privkey, user_id) = ng_repo::utils::generate_keypair();
+        let peer = ng_repo::utils::generate_keypair().1;
+        let manifest = AppManifest::new_for_origin_all_access_v0("https://app.example".to_string());
+        let access = AppAccess::new(
+            manifest.app_id().unwrap(),
+            manifest.grant(&[], user_id).unwrap(),
+        );
+
+        let signed = SignedAppAccess::new(access.clone(), peer, &user_privkey).unwrap();
+        assert_eq!(
+            signed.clone().verify(&user_id, &peer).unwrap().grants.len(),
+            1
+        );
+
+        // the grants cannot be used by another peer, or for another user
+        let other = ng_repo::utils::generate_keypair().1;
+        assert!(signed.clone().verify(&user_id, &other).is_err());
+        assert!(signed.clone().verify(&other, &peer).is_err());
+
+        // nor be changed
+        let mut changed = signed.clone();
+        changed.access.grants[0].target = Some("did:ng:o:a".to_string());
+        assert!(changed.verify(&user_id, &peer).is_err());
+
+        // the grants given to another user cannot be signed
+        let other_privkey = ng_repo::utils::generate_keypair().0;
+        assert!(SignedAppAccess::new(access, peer, &other_privkey).is_err());
+    }
+}
@@ -52,6 +52,18 @@ pub trait IServerBroker: Send + Sync {
     fn put_block(&self, overlay_id: &OverlayId, block: Block) -> Result<(), ServerError>;
     fn has_block(&self, overlay_id: &OverlayId, block_id: &BlockId) -> Result<(), ServerError>;
     fn get_block(&self, overlay_id: &OverlayId, block_id: &BlockId) -> Result<Block, ServerError>;
+    fn pin_object(
+        &self,
+        overlay_id: &OverlayId,
+        object_id: &ObjectId,
+        user_id: &UserId,
+    ) -> Result<(), ServerError>;
+    fn unpin_object(
+        &self,
+        overlay_id: &OverlayId,
+        object_id: &ObjectId,
+        user_id: &UserId,
+    ) -> Result<(), ServerError>;
     async fn create_user(&self, broker_id: &DirectPeerId) -> Result<UserId, ProtocolError>;
     fn get_user(&self, user_id: PubKey) -> Result<bool, ProtocolError>;
     fn has_no_user(&self) -> Result<bool, ProtocolError>;
@@ -28,8 +28,8 @@ use ng_repo::log::*;
 use ng_repo::store::Store;
 use ng_repo::types::*;
 use ng_repo::utils::{
-    decode_digest, decode_key, decode_overlayid, decode_priv_key, decode_sym_key, random_key, sign,
-    verify,
+    decode_digest, decode_key, decode_overlayid, decode_priv_key, decode_sym_key, derive_key,
+    random_key, sign, verify,
 };
 
 use crate::app_protocol::*;
@@ -3576,6 +3576,9 @@ impl BlocksExist {
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct ObjectPinV0 {
     pub id: ObjectId,
+
+    #[serde(skip)]
+    pub overlay: Option<OverlayId>,
 }
 
 /// Request to pin an object
@@ -3590,12 +3593,28 @@ impl ObjectPin {
             ObjectPin::V0(o) => o.id,
         }
     }
+    pub fn overlay(&self) -> &OverlayId {
+        match self {
+            Self::V0(v0) => v0.overlay.as_ref().unwrap(),
+        }
+    }
+    pub fn set_overlay(&mut self, overlay: OverlayId) {
+        match self {
+            Self::V0(v0) => v0.overlay = Some(overlay),
+        }
+    }
 }
 
 /// Request to unpin an object
+///
+/// only a user that pinned the object can unpin it. When no user pins the object anymore,
+/// the blocks of the object whose refcount reaches zero are removed from the broker
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct ObjectUnpinV0 {
     pub id: ObjectId,
+
+    #[serde(skip)]
+    pub overlay: Option<OverlayId>,
 }
 
 /// Request to unpin an object
@@ -3610,6 +3629,16 @@ impl ObjectUnpin {
             ObjectUnpin::V0(o) => o.id,
         }
     }
+    pub fn overlay(&self) -> &OverlayId {
+        match self {
+            Self::V0(v0) => v0.overlay.as_ref().unwrap(),
+        }
+    }
+    pub fn set_overlay(&mut self, overlay: OverlayId) {
+        match self {
+            Self::V0(v0) => v0.overlay = Some(overlay),
+        }
+    }
 }
 
 /// Request to delete an object
@@ -3800,6 +3829,44 @@ impl InboxPost {
             to_broker,
         )?);
     }
+
+    /// to_profile_nuri = did:ng:[ab]
+    /// to_inbox_nuri = did:ng:d
+    pub fn new_wallet_recovery_share(
+        from_profile_store_repo: StoreRepo,
+        from_inbox: PrivKey,
+        to_profile_nuri: &String,
+        to_inbox_nuri: &String,
+        to_broker: Option<Locator>,
+        share: WalletRecoveryShare,
+    ) -> Result<Self, NgError> {
+        let c = RE_PROFILE.captures(to_profile_nuri);
+        if c.is_some() && c.as_ref().unwrap().get(1).is_some() {
+            let cap = c.unwrap();
+            let o = cap.get(1).unwrap().as_str();
+            let to_profile_id = decode_key(o)?;
+            let to_overlay = OverlayId::outer(&to_profile_id);
+
+            let c = RE_INBOX.captures(to_inbox_nuri);
+            if c.is_some() && c.as_ref().unwrap().get(1).is_some() {
+                let cap = c.unwrap();
+                let d = cap.get(1).unwrap().as_str();
+                let to_inbox = decode_key(d)?;
+                let from_overlay = from_profile_store_repo.outer_overlay();
+                let content = InboxMsgContent::WalletRecoveryShare(share);
+
+                return Ok(InboxPost::new(
+                    to_overlay,
+                    to_inbox,
+                    Some((from_overlay, from_inbox)),
+                    &content,
+                    vec![],
+                    to_broker,
+                )?);
+            }
+        }
+        Err(NgError::InvalidNuri)
+    }
 }
 
 /// Request to publish an event in pubsub
@@ -3852,6 +3919,8 @@ impl ClientRequestContentV0 {
             ClientRequestContentV0::BlocksPut(a) => a.set_overlay(overlay),
             ClientRequestContentV0::BlocksExist(a) => a.set_overlay(overlay),
             ClientRequestContentV0::BlocksGet(a) => a.set_overlay(overlay),
+            ClientRequestContentV0::ObjectPin(a) => a.set_overlay(overlay),
+            ClientRequestContentV0::ObjectUnpin(a) => a.set_overlay(overlay),
             ClientRequestContentV0::WalletPutExport(_a) => {}
             _ => unimplemented!(),
         }
@@ -3904,6 +3973,8 @@ impl ClientRequest {
                 ClientRequestContentV0::BlocksPut(r) => r.get_actor(self.id()),
                 ClientRequestContentV0::BlocksExist(r) => r.get_actor(self.id()),
                 ClientRequestContentV0::BlocksGet(r) => r.get_actor(self.id()),
+                ClientRequestContentV0::ObjectPin(r) => r.get_actor(self.id()),
+                ClientRequestContentV0::ObjectUnpin(r) => r.get_actor(self.id()),
                 ClientRequestContentV0::WalletPutExport(r) => r.get_actor(self.id()),
                 ClientRequestContentV0::InboxRegister(r) => r.get_actor(self.id()),
                 ClientRequestContentV0::InboxPost(r) => r.get_actor(self.id()),
@@ -4237,6 +4308,63 @@ pub struct ContactDetails {
     pub email: Option<String>,
 }
 
+/// A Shamir share of the master key of a wallet, given by the owner of the wallet to one of their contacts.
+///
+/// `threshold` shares are needed to rebuild the master key, when the owner lost all the login methods of the wallet.
+#[derive(Clone, Debug, Zeroize, ZeroizeOnDrop, Serialize, Deserialize)]
+pub struct WalletRecoveryShare {
+    #[zeroize(skip)]
+    pub wallet_id: PubKey,
+
+    pub threshold: u8,
+
+    /// x coordinate of the share, starting at 1
+    pub index: u8,
+
+    pub share: [u8; 32],
+
+    /// Signature over the other fields by the wallet's private key
+    #[zeroize(skip)]
+    pub sig: Sig,
+}
+
+impl WalletRecoveryShare {
+    fn content_as_bytes(&self) -> Vec<u8> {
+        serde_bare::to_vec(&(&self.wallet_id, self.threshold, self.index, &self.share)).unwrap()
+    }
+
+    pub fn new(
+        wallet_privkey: &PrivKey,
+        threshold: u8,
+        index: u8,
+        share: [u8; 32],
+    ) -> Result<Self, NgError> {
+        let mut res = Self {
+            wallet_id: wallet_privkey.to_pub(),
+            threshold,
+            index,
+            share,
+            sig: Sig::nil(),
+        };
+        let mut ser = res.content_as_bytes();
+        let sig = sign(wallet_privkey, &res.wallet_id, &ser);
+        ser.zeroize();
+        res.sig = sig?;
+        Ok(res)
+    }
+
+    /// checks that the share was made by the owner of the wallet
+    pub fn verify(&self) -> Result<(), NgError> {
+        if !matches!(self.wallet_id, PubKey::Ed25519PubKey(_)) {
+            return Err(NgError::InvalidSignature);
+        }
+        let mut ser = self.content_as_bytes();
+        let res = verify(&ser, self.sig, self.wallet_id);
+        ser.zeroize();
+        res
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum SocialQuery {
     Request(SocialQueryRequest),
@@ -4255,6 +4383,7 @@ pub enum InboxMsgContent {
     ExtRequest,
     RemoteQuery,
     SocialQuery(SocialQuery),
+    WalletRecoveryShare(WalletRecoveryShare),
     //Transaction
     //Comment
     //BackLink
@@ -5241,11 +5370,92 @@ pub struct NgQRCodeWalletRecoveryV0 {
     pub pin: [u8; 4],
 }
 
+/// Request for the WalletRecoveryShare held by a contact, made by the owner of a wallet who lost all its login methods.
+///
+/// The share will be sealed to `key`, of which the private key stays on the device of the requester.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NgQRCodeWalletShareRequestV0 {
+    pub wallet_id: PubKey,
+    pub key: PubKey,
+}
+
+impl NgQRCodeWalletShareRequestV0 {
+    /// 20 digits (in 4 groups of 5) that the requester reads to the contact by another channel (in person, or on the phone),
+    /// so the contact can verify that the request they received is really the one of the owner of the wallet.
+    /// The code is long enough that nobody can generate another request key with the same code.
+    pub fn verification_code(&self) -> String {
+        let ser = serde_bare::to_vec(self).unwrap();
+        let hash = derive_key("NextGraph Wallet Recovery Request BLAKE3 key", &ser);
+        let code = format!(
+            "{:020}",
+            u128::from_be_bytes(hash[0..16].try_into().unwrap()) % 10u128.pow(20)
+        );
+        code.as_bytes()
+            .chunks(5)
+            .map(|group| std::str::from_utf8(group).unwrap())
+            .collect::<Vec<&str>>()
+            .join("-")
+    }
+
+    /// checks a verification code typed by the contact. The spaces and dashes between the digits are ignored.
+    pub fn check_verification_code(&self, code: &str) -> bool {
+        let digits: String = code
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-')
+            .collect();
+        let expected: String = self
+            .verification_code()
+            .chars()
+            .filter(|c| *c != '-')
+            .collect();
+        digits == expected
+    }
+}
+
+/// WalletRecoveryShare returned by a contact, sealed to the key of the NgQRCodeWalletShareRequestV0
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NgQRCodeWalletShareResponseV0 {
+    #[serde(with = "serde_bytes")]
+    pub sealed_share: Vec<u8>,
+}
+
+impl NgQRCodeWalletShareResponseV0 {
+    pub fn new(
+        share: &WalletRecoveryShare,
+        request: &NgQRCodeWalletShareRequestV0,
+    ) -> Result<Self, NgError> {
+        if share.wallet_id != request.wallet_id {
+            return Err(NgError::InvalidArgument);
+        }
+        let mut ser = serde_bare::to_vec(share).unwrap();
+        let mut rng = crypto_box::aead::OsRng {};
+        let sealed_share = crypto_box::seal(&mut rng, &request.key.to_dh_slice().into(), &ser)
+            .map_err(|_| NgError::EncryptionError);
+        ser.zeroize();
+        Ok(Self {
+            sealed_share: sealed_share?,
+        })
+    }
+
+    pub fn open(&self, key: &PrivKey) -> Result<WalletRecoveryShare, NgError> {
+        let mut ser = crypto_box::seal_open(&(*key.to_dh().slice()).into(), &self.sealed_share)
+            .map_err(|_| NgError::DecryptionError)?;
+        let share: Result<WalletRecoveryShare, NgError> =
+            serde_bare::from_slice(&ser).map_err(|_| NgError::SerializationError);
+        ser.zeroize();
+        let share = share?;
+        share.verify()?;
+        Ok(share)
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum NgQRCode {
     WalletTransferV0(NgQRCodeWalletTransferV0),
     WalletRecoveryV0(NgQRCodeWalletRecoveryV0),
     ProfileSharingV0(NgQRCodeProfileSharingV0),
+    WalletShareRequestV0(NgQRCodeWalletShareRequestV0),
+    WalletShareResponseV0(NgQRCodeWalletShareResponseV0),
 }
 
 impl NgQRCode {
@@ -5264,8 +5474,12 @@ impl NgQRCode {
 #[cfg(test)]
 mod test {
 
-    use crate::types::{BootstrapContentV0, BrokerServerTypeV0, BrokerServerV0, Invitation};
+    use crate::types::{
+        BootstrapContentV0, BrokerServerTypeV0, BrokerServerV0, Invitation,
+        NgQRCodeWalletShareRequestV0, NgQRCodeWalletShareResponseV0, WalletRecoveryShare,
+    };
     use ng_repo::types::PubKey;
+    use ng_repo::utils::generate_keypair;
 
     #[test]
     pub fn invitation() {
@@ -5287,4 +5501,41 @@ mod test {
 
         println!("{:?}", inv.get_urls());
     }
+
+    #[test]
+    pub fn wallet_share_request() {
+        let (wallet_privkey, wallet_id) = generate_keypair();
+        let (request_privkey, request_key) = generate_keypair();
+        let request = NgQRCodeWalletShareRequestV0 {
+            wallet_id,
+            key: request_key,
+        };
+
+        let code = request.verification_code();
+        assert_eq!(code.len(), 23);
+        assert!(request.check_verification_code(&code));
+        assert!(request.check_verification_code(&format!(" {} ", code.replace("-", " "))));
+        assert!(!request.check_verification_code(&code[0..17]));
+
+        // another key gives another code
+        let other_request = NgQRCodeWalletShareRequestV0 {
+            wallet_id,
+            key: generate_keypair().1,
+        };
+        assert!(!other_request.check_verification_code(&code));
+
+        let share = WalletRecoveryShare::new(&wallet_privkey, 2, 1, [7; 32]).unwrap();
+        share.verify().unwrap();
+        let response = NgQRCodeWalletShareResponseV0::new(&share, &request).unwrap();
+        let opened = response.open(&request_privkey).unwrap();
+        assert_eq!(opened.share, share.share);
+
+        // a share that was not made by the owner of the wallet is rejected
+        let mut forged = share.clone();
+        forged.index = 2;
+        assert!(forged.verify().is_err());
+        let mut forged = WalletRecoveryShare::new(&generate_keypair().0, 2, 1, [7; 32]).unwrap();
+        forged.wallet_id = wallet_id;
+        assert!(forged.verify().is_err());
+    }
 }
@@ -15,6 +15,8 @@ pub mod oxttl;
 
 pub mod oxrdfxml;
 
+pub mod oxjsonld;
+
 pub mod sparesults;
 
 pub mod spargebra;
@@ -3,6 +3,7 @@ use crate::oxigraph::model::{BlankNode, LiteralRef, NamedNodeRef, Term, Triple};
 use crate::oxigraph::sparql::algebra::{Query, QueryDataset};
 use crate::oxigraph::sparql::dataset::DatasetView;
 use crate::oxigraph::sparql::error::EvaluationError;
+use crate::oxigraph::sparql::geo;
 use crate::oxigraph::sparql::model::*;
 use crate::oxigraph::sparql::service::ServiceHandler;
 use crate::oxigraph::sparql::CustomFunctionRegistry;
@@ -2443,6 +2444,22 @@ impl SimpleEvaluator {
                                 Some(dataset.encode_term(&function(&args)?))
                             });
                         }
+                        if let Some(function) = geo::function(function_name.as_ref()) {
+                            let args = parameters
+                                .iter()
+                                .map(|e| {
+                                    self.expression_evaluator(e, encoded_variables, stat_children)
+                                })
+                                .collect::<Vec<_>>();
+                            let dataset = Rc::clone(&self.dataset);
+                            return Rc::new(move |tuple| {
+                                let args = args
+                                    .iter()
+                                    .map(|f| dataset.decode_term(&f(tuple)?).ok())
+                                    .collect::<Option<Vec<_>>>()?;
+                                Some(dataset.encode_term(&function(&args)?))
+                            });
+                        }
                         match function_name.as_ref() {
                             xsd::STRING => {
                                 let e = self.expression_evaluator(
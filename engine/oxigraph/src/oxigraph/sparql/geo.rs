@@ -0,0 +1,503 @@
+//! [GeoSPARQL](https://opengeospatial.github.io/ogc-geosparql/) filter functions on `geo:wktLiteral`.
+//!
+//! Only points and polygons (with holes) are supported. Coordinates are longitude/latitude in degrees,
+//! as in the default `CRS84` reference system. Topological relations are computed in the plane of
+//! the coordinates, while distances and buffers are computed on a sphere of the mean Earth radius.
+
+use crate::oxigraph::model::vocab::xsd;
+use crate::oxigraph::model::{Literal, NamedNodeRef, Term};
+use crate::oxrdf::vocab::geosparql;
+use std::f64::consts::PI;
+use std::fmt::Write;
+
+const GEOF: &str = "http://www.opengis.net/def/function/geosparql/";
+const UOM: &str = "http://www.opengis.net/def/uom/OGC/1.0/";
+const CRS84: &str = "http://www.opengis.net/def/crs/OGC/1.3/CRS84";
+
+const EARTH_RADIUS: f64 = 6_371_008.8;
+const BUFFER_SEGMENTS: usize = 32;
+const EPSILON: f64 = 1e-12;
+
+/// Returns the implementation of the GeoSPARQL function with the given IRI, if it is supported
+pub fn function(name: NamedNodeRef<'_>) -> Option<fn(&[Term]) -> Option<Term>> {
+    let function: fn(&[Term]) -> Option<Term> = match name.as_str().strip_prefix(GEOF)? {
+        "distance" => distance,
+        "sfWithin" => sf_within,
+        "sfContains" => sf_contains,
+        "sfIntersects" => sf_intersects,
+        "sfDisjoint" => sf_disjoint,
+        "buffer" => buffer,
+        _ => return None,
+    };
+    Some(function)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Coord {
+    x: f64,
+    y: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Geometry {
+    Point(Coord),
+    /// The exterior ring followed by the holes, each ring being closed
+    Polygon(Vec<Vec<Coord>>),
+}
+
+impl Geometry {
+    fn from_term(term: &Term) -> Option<Self> {
+        let Term::Literal(literal) = term else {
+            return None;
+        };
+        if literal.datatype() != geosparql::WKT_LITERAL {
+            return None;
+        }
+        Self::parse_wkt(literal.value())
+    }
+
+    /// Parses the WKT serialization of a point or a polygon, optionally prefixed by the CRS84 IRI
+    fn parse_wkt(wkt: &str) -> Option<Self> {
+        let mut wkt = wkt.trim();
+        if let Some(rest) = wkt.strip_prefix('<') {
+            let (crs, rest) = rest.split_once('>')?;
+            if crs != CRS84 {
+                return None;
+            }
+            wkt = rest.trim_start();
+        }
+        let (keyword, body) = wkt.split_at(wkt.find('(')?);
+        let keyword = keyword.trim().to_ascii_uppercase();
+        let keyword = keyword
+            .strip_suffix(" ZM")
+            .or_else(|| keyword.strip_suffix(" Z"))
+            .or_else(|| keyword.strip_suffix(" M"))
+            .unwrap_or(&keyword)
+            .trim_end();
+        let body = body.trim().strip_prefix('(')?.strip_suffix(')')?;
+        match keyword {
+            "POINT" => Some(Self::Point(parse_coord(body)?)),
+            "POLYGON" => {
+                let mut rings = Vec::new();
+                let mut rest = body.trim();
+                while !rest.is_empty() {
+                    let (ring, tail) = rest.strip_prefix('(')?.split_once(')')?;
+                    let ring = ring
+                        .split(',')
+                        .map(parse_coord)
+                        .collect::<Option<Vec<_>>>()?;
+                    if ring.len() < 4 || ring.first() != ring.last() {
+                        return None;
+                    }
+                    rings.push(ring);
+                    rest = tail.trim_start();
+                    rest = rest.strip_prefix(',').unwrap_or(rest).trim_start();
+                }
+                (!rings.is_empty()).then_some(Self::Polygon(rings))
+            }
+            _ => None,
+        }
+    }
+
+    fn to_term(&self) -> Term {
+        let mut wkt = String::new();
+        match self {
+            Self::Point(c) => {
+                let _ = write!(wkt, "POINT({} {})", c.x, c.y);
+            }
+            Self::Polygon(rings) => {
+                wkt.push_str("POLYGON(");
+                for (i, ring) in rings.iter().enumerate() {
+                    if i > 0 {
+                        wkt.push_str(", ");
+                    }
+                    wkt.push('(');
+                    for (j, c) in ring.iter().enumerate() {
+                        if j > 0 {
+                            wkt.push_str(", ");
+                        }
+                        let _ = write!(wkt, "{} {}", c.x, c.y);
+                    }
+                    wkt.push(')');
+                }
+                wkt.push(')');
+            }
+        }
+        Literal::new_typed_literal(wkt, geosparql::WKT_LITERAL).into()
+    }
+
+    fn vertices(&self) -> Vec<Coord> {
+        match self {
+            Self::Point(c) => vec![*c],
+            Self::Polygon(rings) => rings.iter().flatten().copied().collect(),
+        }
+    }
+
+    fn segments(&self) -> Vec<(Coord, Coord)> {
+        match self {
+            Self::Point(_) => Vec::new(),
+            Self::Polygon(rings) => rings
+                .iter()
+                .flat_map(|ring| ring.windows(2).map(|w| (w[0], w[1])))
+                .collect(),
+        }
+    }
+
+    /// Returns true if the point is inside the geometry or on its boundary
+    fn covers_point(&self, p: Coord) -> bool {
+        match self {
+            Self::Point(c) => *c == p,
+            Self::Polygon(rings) => {
+                if self.segments().iter().any(|(a, b)| on_segment(p, *a, *b)) {
+                    return true;
+                }
+                ring_contains(&rings[0], p) && !rings[1..].iter().any(|hole| ring_contains(hole, p))
+            }
+        }
+    }
+
+    fn intersects(&self, other: &Self) -> bool {
+        if self.segments().iter().any(|(a, b)| {
+            other
+                .segments()
+                .iter()
+                .any(|(c, d)| segments_intersect(*a, *b, *c, *d))
+        }) {
+            return true;
+        }
+        self.vertices().iter().any(|p| other.covers_point(*p))
+            || other.vertices().iter().any(|p| self.covers_point(*p))
+    }
+
+    /// Returns true if the point is strictly inside the exterior ring of the polygon
+    fn surrounds_point(&self, p: Coord) -> bool {
+        match self {
+            Self::Point(_) => false,
+            Self::Polygon(rings) => {
+                ring_contains(&rings[0], p)
+                    && !rings[0].windows(2).any(|w| on_segment(p, w[0], w[1]))
+            }
+        }
+    }
+
+    /// Returns true if self is within other, approximated as all the vertices of self being covered
+    /// by other, without any edge of self crossing an edge of other, nor any hole of other inside self
+    fn within(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Point(p), Self::Point(q)) => p == q,
+            (Self::Polygon(_), Self::Point(_)) => false,
+            (Self::Point(p), Self::Polygon(_)) => {
+                other.covers_point(*p)
+                    && !other.segments().iter().any(|(a, b)| on_segment(*p, *a, *b))
+            }
+            (Self::Polygon(_), Self::Polygon(_)) => {
+                self.vertices().iter().all(|p| other.covers_point(*p))
+                    && !self.segments().iter().any(|(a, b)| {
+                        other
+                            .segments()
+                            .iter()
+                            .any(|(c, d)| segments_cross(*a, *b, *c, *d))
+                    })
+                    && !matches!(other, Self::Polygon(rings) if rings[1..]
+                        .iter()
+                        .flatten()
+                        .any(|p| self.surrounds_point(*p)))
+            }
+        }
+    }
+
+    /// Returns the shortest distance in metres between the two geometries
+    fn distance(&self, other: &Self) -> f64 {
+        if self.intersects(other) {
+            return 0.;
+        }
+        let from_vertices = |vertices: Vec<Coord>, geometry: &Self| {
+            vertices
+                .into_iter()
+                .map(|p| match geometry {
+                    Self::Point(q) => haversine(p, *q),
+                    Self::Polygon(_) => geometry
+                        .segments()
+                        .iter()
+                        .map(|(a, b)| haversine(p, closest_on_segment(p, *a, *b)))
+                        .fold(f64::INFINITY, f64::min),
+                })
+                .fold(f64::INFINITY, f64::min)
+        };
+        from_vertices(self.vertices(), other).min(from_vertices(other.vertices(), self))
+    }
+
+    /// Returns the polygon covering all the points at less than the radius (in metres) from the geometry.
+    ///
+    /// The buffer of a polygon is computed as the convex hull of the buffers of its vertices,
+    /// which is exact for convex polygons and covers a bit more than the buffer for the others.
+    fn buffer(&self, radius: f64) -> Option<Self> {
+        if !radius.is_finite() || radius < 0. {
+            return None;
+        }
+        if radius == 0. {
+            return Some(self.clone());
+        }
+        let mut points = Vec::new();
+        let centers = match self {
+            Self::Point(c) => vec![*c],
+            Self::Polygon(rings) => rings[0].clone(),
+        };
+        for center in centers {
+            for i in 0..BUFFER_SEGMENTS {
+                let bearing = 2. * PI * i as f64 / BUFFER_SEGMENTS as f64;
+                points.push(destination(center, bearing, radius));
+            }
+        }
+        let mut ring = convex_hull(points);
+        ring.push(*ring.first()?);
+        Some(Self::Polygon(vec![ring]))
+    }
+}
+
+fn parse_coord(value: &str) -> Option<Coord> {
+    let numbers = value
+        .split_whitespace()
+        .map(|n| n.parse::<f64>().ok().filter(|n| n.is_finite()))
+        .collect::<Option<Vec<_>>>()?;
+    // the optional Z and M ordinates are ignored
+    match numbers.as_slice() {
+        [x, y] | [x, y, _] | [x, y, _, _] => Some(Coord { x: *x, y: *y }),
+        _ => None,
+    }
+}
+
+fn cross(o: Coord, a: Coord, b: Coord) -> f64 {
+    (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+}
+
+fn on_segment(p: Coord, a: Coord, b: Coord) -> bool {
+    cross(a, b, p).abs() <= EPSILON
+        && p.x >= a.x.min(b.x) - EPSILON
+        && p.x <= a.x.max(b.x) + EPSILON
+        && p.y >= a.y.min(b.y) - EPSILON
+        && p.y <= a.y.max(b.y) + EPSILON
+}
+
+/// Returns true if the segments cross each other at a point that is interior to both of them
+fn segments_cross(a: Coord, b: Coord, c: Coord, d: Coord) -> bool {
+    let d1 = cross(c, d, a);
+    let d2 = cross(c, d, b);
+    let d3 = cross(a, b, c);
+    let d4 = cross(a, b, d);
+    ((d1 > EPSILON && d2 < -EPSILON) || (d1 < -EPSILON && d2 > EPSILON))
+        && ((d3 > EPSILON && d4 < -EPSILON) || (d3 < -EPSILON && d4 > EPSILON))
+}
+
+fn segments_intersect(a: Coord, b: Coord, c: Coord, d: Coord) -> bool {
+    segments_cross(a, b, c, d)
+        || on_segment(a, c, d)
+        || on_segment(b, c, d)
+        || on_segment(c, a, b)
+        || on_segment(d, a, b)
+}
+
+/// Even-odd rule on a closed ring
+fn ring_contains(ring: &[Coord], p: Coord) -> bool {
+    let mut inside = false;
+    for w in ring.windows(2) {
+        let (a, b) = (w[0], w[1]);
+        if (a.y > p.y) != (b.y > p.y) && p.x < (b.x - a.x) * (p.y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Closest point of the segment, computed in an equirectangular projection around p
+fn closest_on_segment(p: Coord, a: Coord, b: Coord) -> Coord {
+    let scale = p.y.to_radians().cos();
+    let (ax, ay) = ((a.x - p.x) * scale, a.y - p.y);
+    let (bx, by) = ((b.x - p.x) * scale, b.y - p.y);
+    let (dx, dy) = (bx - ax, by - ay);
+    let length = dx * dx + dy * dy;
+    let t = if length == 0. {
+        0.
+    } else {
+        (-(ax * dx + ay * dy) / length).clamp(0., 1.)
+    };
+    Coord {
+        x: a.x + (b.x - a.x) * t,
+        y: a.y + (b.y - a.y) * t,
+    }
+}
+
+fn haversine(a: Coord, b: Coord) -> f64 {
+    let (lat1, lat2) = (a.y.to_radians(), b.y.to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = (b.x - a.x).to_radians();
+    let h = (dlat / 2.).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.).sin().powi(2);
+    2. * EARTH_RADIUS * h.sqrt().min(1.).asin()
+}
+
+/// Point at the given distance (in metres) and bearing (in radians) from the origin
+fn destination(origin: Coord, bearing: f64, distance: f64) -> Coord {
+    let angle = distance / EARTH_RADIUS;
+    let lat1 = origin.y.to_radians();
+    let lon1 = origin.x.to_radians();
+    let lat2 = (lat1.sin() * angle.cos() + lat1.cos() * angle.sin() * bearing.cos()).asin();
+    let lon2 = lon1
+        + (bearing.sin() * angle.sin() * lat1.cos()).atan2(angle.cos() - lat1.sin() * lat2.sin());
+    Coord {
+        x: (lon2.to_degrees() + 540.) % 360. - 180.,
+        y: lat2.to_degrees(),
+    }
+}
+
+/// Andrew's monotone chain, returning the hull in counter-clockwise order
+fn convex_hull(mut points: Vec<Coord>) -> Vec<Coord> {
+    points.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+    let mut hull: Vec<Coord> = Vec::with_capacity(points.len() * 2);
+    for pass in [points.clone(), points.into_iter().rev().collect()] {
+        let start = hull.len();
+        for p in pass {
+            while hull.len() >= start + 2
+                && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.
+            {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        hull.pop();
+    }
+    hull
+}
+
+fn to_f64(term: &Term) -> Option<f64> {
+    let Term::Literal(literal) = term else {
+        return None;
+    };
+    let datatype = literal.datatype();
+    if datatype == xsd::DOUBLE
+        || datatype == xsd::FLOAT
+        || datatype == xsd::DECIMAL
+        || datatype == xsd::INTEGER
+    {
+        literal.value().parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Number of metres in the unit of measure
+fn unit_in_metres(term: &Term) -> Option<f64> {
+    let Term::NamedNode(unit) = term else {
+        return None;
+    };
+    match unit.as_str().strip_prefix(UOM)? {
+        "metre" | "meter" => Some(1.),
+        "kilometre" | "kilometer" => Some(1000.),
+        "mile" | "statuteMile" => Some(1609.344),
+        "radian" => Some(EARTH_RADIUS),
+        "degree" => Some(EARTH_RADIUS * PI / 180.),
+        _ => None,
+    }
+}
+
+fn distance(args: &[Term]) -> Option<Term> {
+    let [a, b, unit] = args else {
+        return None;
+    };
+    let a = Geometry::from_term(a)?;
+    let b = Geometry::from_term(b)?;
+    Some(Literal::from(a.distance(&b) / unit_in_metres(unit)?).into())
+}
+
+fn sf_within(args: &[Term]) -> Option<Term> {
+    let [a, b] = args else {
+        return None;
+    };
+    Some(Literal::from(Geometry::from_term(a)?.within(&Geometry::from_term(b)?)).into())
+}
+
+fn sf_contains(args: &[Term]) -> Option<Term> {
+    let [a, b] = args else {
+        return None;
+    };
+    sf_within(&[b.clone(), a.clone()])
+}
+
+fn sf_intersects(args: &[Term]) -> Option<Term> {
+    let [a, b] = args else {
+        return None;
+    };
+    Some(Literal::from(Geometry::from_term(a)?.intersects(&Geometry::from_term(b)?)).into())
+}
+
+fn sf_disjoint(args: &[Term]) -> Option<Term> {
+    let [a, b] = args else {
+        return None;
+    };
+    Some(Literal::from(!Geometry::from_term(a)?.intersects(&Geometry::from_term(b)?)).into())
+}
+
+fn buffer(args: &[Term]) -> Option<Term> {
+    let [geometry, radius, unit] = args else {
+        return None;
+    };
+    let radius = to_f64(radius)? * unit_in_metres(unit)?;
+    Some(Geometry::from_term(geometry)?.buffer(radius)?.to_term())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wkt(value: &str) -> Term {
+        Literal::new_typed_literal(value, geosparql::WKT_LITERAL).into()
+    }
+
+    fn boolean(value: bool) -> Option<Term> {
+        Some(Literal::from(value).into())
+    }
+
+    fn metre() -> Term {
+        crate::oxigraph::model::NamedNode::new_unchecked(format!("{UOM}metre")).into()
+    }
+
+    #[test]
+    fn test_geo_functions() {
+        let paris = wkt("POINT(2.3522 48.8566)");
+        let london = wkt("<http://www.opengis.net/def/crs/OGC/1.3/CRS84> Point(-0.1276 51.5072)");
+        let km = distance(&[paris.clone(), london.clone(), metre()]).unwrap();
+        let Term::Literal(km) = km else { panic!() };
+        let km = km.value().parse::<f64>().unwrap() / 1000.;
+        assert!((343. ..345.).contains(&km), "{km}");
+
+        let square = wkt("POLYGON((2 48, 3 48, 3 49, 2 49, 2 48), (2.1 48.1, 2.2 48.1, 2.2 48.2, 2.1 48.2, 2.1 48.1))");
+        assert_eq!(sf_within(&[paris.clone(), square.clone()]), boolean(true));
+        assert_eq!(sf_within(&[london.clone(), square.clone()]), boolean(false));
+        assert_eq!(
+            sf_within(&[wkt("POINT(2.15 48.15)"), square.clone()]),
+            boolean(false)
+        );
+        assert_eq!(
+            sf_intersects(&[
+                wkt("POLYGON((2.5 48.5, 4 48.5, 4 50, 2.5 48.5))"),
+                square.clone()
+            ]),
+            boolean(true)
+        );
+
+        let radius: Term = Literal::from(5000.).into();
+        let circle = buffer(&[paris.clone(), radius, metre()]).unwrap();
+        assert_eq!(
+            sf_within(&[wkt("POINT(2.36 48.86)"), circle.clone()]),
+            boolean(true)
+        );
+        assert_eq!(
+            sf_within(&[wkt("POINT(2.5 48.86)"), circle]),
+            boolean(false)
+        );
+        assert_eq!(sf_within(&[wkt("LINESTRING(0 0, 1 1)"), square]), None);
+    }
+}
@@ -16,6 +16,7 @@ mod algebra;
 mod dataset;
 mod error;
 mod eval;
+mod geo;
 mod http;
 mod model;
 pub mod results;
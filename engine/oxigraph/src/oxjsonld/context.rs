@@ -0,0 +1,683 @@
+//! JSON values and [JSON-LD contexts](https://www.w3.org/TR/json-ld11/#the-context).
+
+use crate::oxjsonld::error::{JsonLdParseError, JsonLdSyntaxError, SyntaxErrorKind};
+use json_event_parser::{FromReadJsonReader, JsonEvent, ToWriteJsonWriter};
+use oxiri::Iri;
+use std::collections::HashMap;
+
+/// Above this depth, contexts referencing each other are considered as a cycle.
+const MAX_CONTEXT_DEPTH: usize = 32;
+
+const KEYWORDS: [&str; 23] = [
+    "@base",
+    "@container",
+    "@context",
+    "@direction",
+    "@graph",
+    "@id",
+    "@import",
+    "@included",
+    "@index",
+    "@json",
+    "@language",
+    "@list",
+    "@nest",
+    "@none",
+    "@prefix",
+    "@propagate",
+    "@protected",
+    "@reverse",
+    "@set",
+    "@type",
+    "@value",
+    "@version",
+    "@vocab",
+];
+
+pub fn is_keyword(value: &str) -> bool {
+    KEYWORDS.contains(&value)
+}
+
+/// Values that look like keywords but are not are ignored by JSON-LD processors.
+fn has_keyword_form(value: &str) -> bool {
+    value
+        .strip_prefix('@')
+        .is_some_and(|v| !v.is_empty() && v.bytes().all(|b| b.is_ascii_alphabetic()))
+}
+
+/// An in-memory JSON value, keeping the order of object keys.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Boolean(bool),
+    /// The number lexical representation
+    Number(String),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    pub fn get(&self, key: &str) -> Option<&Self> {
+        if let Self::Object(entries) = self {
+            entries.iter().find_map(|(k, v)| (k == key).then_some(v))
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        if let Self::String(value) = self {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    /// Iterates on the value if it is an array, or on the value itself otherwise.
+    pub fn as_slice(&self) -> &[Self] {
+        match self {
+            Self::Array(values) => values,
+            Self::Null => &[],
+            other => std::slice::from_ref(other),
+        }
+    }
+
+    /// Serializes the value to a JSON string, used for `@json` literals.
+    pub fn to_json_string(&self) -> String {
+        let mut events = Vec::new();
+        self.to_events(&mut events);
+        let mut writer = ToWriteJsonWriter::new(Vec::new());
+        for event in events {
+            // Writing to a Vec never fails and the events are well-formed
+            if writer.write_event(event).is_err() {
+                return String::new();
+            }
+        }
+        writer
+            .finish()
+            .ok()
+            .and_then(|buffer| String::from_utf8(buffer).ok())
+            .unwrap_or_default()
+    }
+
+    /// Parses a complete JSON document.
+    pub fn parse(slice: &[u8]) -> Result<Self, JsonLdParseError> {
+        let mut reader = FromReadJsonReader::new(slice);
+        let mut builder = JsonTreeBuilder::default();
+        loop {
+            if let Some(value) = builder.push(reader.read_next_event()?)? {
+                if reader.read_next_event()? != JsonEvent::Eof {
+                    return Err(
+                        JsonLdSyntaxError::msg("Unexpected content after the JSON value").into(),
+                    );
+                }
+                return Ok(value);
+            }
+        }
+    }
+
+    pub fn to_events(&self, events: &mut Vec<JsonEvent<'static>>) {
+        match self {
+            Self::Null => events.push(JsonEvent::Null),
+            Self::Boolean(value) => events.push(JsonEvent::Boolean(*value)),
+            Self::Number(value) => events.push(JsonEvent::Number(value.clone().into())),
+            Self::String(value) => events.push(JsonEvent::String(value.clone().into())),
+            Self::Array(values) => {
+                events.push(JsonEvent::StartArray);
+                for value in values {
+                    value.to_events(events);
+                }
+                events.push(JsonEvent::EndArray);
+            }
+            Self::Object(entries) => {
+                events.push(JsonEvent::StartObject);
+                for (key, value) in entries {
+                    events.push(JsonEvent::ObjectKey(key.clone().into()));
+                    value.to_events(events);
+                }
+                events.push(JsonEvent::EndObject);
+            }
+        }
+    }
+}
+
+/// Builds [`JsonValue`]s from a stream of JSON events.
+#[derive(Default)]
+pub struct JsonTreeBuilder {
+    stack: Vec<PartialValue>,
+}
+
+enum PartialValue {
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>, Option<String>),
+}
+
+impl JsonTreeBuilder {
+    /// Returns `true` if no value is being built.
+    pub fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    /// Consumes an event and returns the value if it is now complete.
+    pub fn push(&mut self, event: JsonEvent<'_>) -> Result<Option<JsonValue>, JsonLdSyntaxError> {
+        let value = match event {
+            JsonEvent::Null => JsonValue::Null,
+            JsonEvent::Boolean(value) => JsonValue::Boolean(value),
+            JsonEvent::Number(value) => JsonValue::Number(value.into()),
+            JsonEvent::String(value) => JsonValue::String(value.into()),
+            JsonEvent::StartArray => {
+                self.stack.push(PartialValue::Array(Vec::new()));
+                return Ok(None);
+            }
+            JsonEvent::StartObject => {
+                self.stack.push(PartialValue::Object(Vec::new(), None));
+                return Ok(None);
+            }
+            JsonEvent::ObjectKey(key) => {
+                let Some(PartialValue::Object(_, current_key)) = self.stack.last_mut() else {
+                    return Err(JsonLdSyntaxError::msg("Unexpected object key"));
+                };
+                *current_key = Some(key.into());
+                return Ok(None);
+            }
+            JsonEvent::EndArray => match self.stack.pop() {
+                Some(PartialValue::Array(values)) => JsonValue::Array(values),
+                _ => return Err(JsonLdSyntaxError::msg("Unexpected end of array")),
+            },
+            JsonEvent::EndObject => match self.stack.pop() {
+                Some(PartialValue::Object(entries, _)) => JsonValue::Object(entries),
+                _ => return Err(JsonLdSyntaxError::msg("Unexpected end of object")),
+            },
+            JsonEvent::Eof => return Err(JsonLdSyntaxError::msg("Unexpected end of file")),
+        };
+        match self.stack.last_mut() {
+            None => Ok(Some(value)),
+            Some(PartialValue::Array(values)) => {
+                values.push(value);
+                Ok(None)
+            }
+            Some(PartialValue::Object(entries, key)) => {
+                let key = key
+                    .take()
+                    .ok_or_else(|| JsonLdSyntaxError::msg("Object value without key"))?;
+                entries.push((key, value));
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// The set of containers a term is mapped to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Container(u8);
+
+impl Container {
+    pub const LIST: Self = Self(1);
+    pub const SET: Self = Self(1 << 1);
+    pub const LANGUAGE: Self = Self(1 << 2);
+    pub const INDEX: Self = Self(1 << 3);
+    pub const GRAPH: Self = Self(1 << 4);
+    pub const ID: Self = Self(1 << 5);
+    pub const TYPE: Self = Self(1 << 6);
+
+    fn parse(value: &JsonValue) -> Result<Self, JsonLdSyntaxError> {
+        let mut container = Self::default();
+        for value in value.as_slice() {
+            container.0 |= match value.as_str() {
+                Some("@list") => Self::LIST,
+                Some("@set") => Self::SET,
+                Some("@language") => Self::LANGUAGE,
+                Some("@index") => Self::INDEX,
+                Some("@graph") => Self::GRAPH,
+                Some("@id") => Self::ID,
+                Some("@type") => Self::TYPE,
+                _ => return Err(JsonLdSyntaxError::msg("Invalid @container mapping")),
+            }
+            .0;
+        }
+        Ok(container)
+    }
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// No container, or only `@set` that does not change anything to the values.
+    pub fn is_plain(self) -> bool {
+        self.0 & !Self::SET.0 == 0
+    }
+}
+
+/// A [term definition](https://www.w3.org/TR/json-ld11/#term-definitions).
+#[derive(Debug, Clone, Default)]
+pub struct TermDefinition {
+    /// The IRI, blank node or keyword the term maps to. `None` if the term is explicitly ignored.
+    pub iri: Option<String>,
+    pub reverse: bool,
+    /// `@id`, `@vocab`, `@json`, `@none` or a datatype IRI
+    pub type_mapping: Option<String>,
+    /// `Some(None)` if the language is explicitly removed
+    pub language: Option<Option<String>>,
+    pub container: Container,
+    /// A property-scoped context
+    pub context: Option<JsonValue>,
+    /// If the term can be used as a prefix in compact IRIs
+    pub prefix: bool,
+}
+
+/// An [active context](https://www.w3.org/TR/json-ld11-api/#dfn-active-context).
+#[derive(Debug, Clone, Default)]
+pub struct JsonLdContext {
+    pub base_iri: Option<Iri<String>>,
+    pub vocab: Option<String>,
+    pub default_language: Option<String>,
+    pub terms: HashMap<String, TermDefinition>,
+}
+
+impl JsonLdContext {
+    pub fn new(base_iri: Option<Iri<String>>) -> Self {
+        Self {
+            base_iri,
+            ..Self::default()
+        }
+    }
+
+    /// Processes a local context on top of this one, following the
+    /// [context processing algorithm](https://www.w3.org/TR/json-ld11-api/#context-processing-algorithm).
+    ///
+    /// Remote contexts are not supported, they should be inlined.
+    pub fn process(&self, local_context: &JsonValue) -> Result<Self, JsonLdSyntaxError> {
+        self.process_with_depth(local_context, 0)
+    }
+
+    fn process_with_depth(
+        &self,
+        local_context: &JsonValue,
+        depth: usize,
+    ) -> Result<Self, JsonLdSyntaxError> {
+        if depth > MAX_CONTEXT_DEPTH {
+            return Err(JsonLdSyntaxError::msg("Too deeply nested contexts"));
+        }
+        let mut result = self.clone();
+        let contexts = match local_context {
+            // A bare null is a context on its own, not an empty array
+            JsonValue::Null => std::slice::from_ref(local_context),
+            _ => local_context.as_slice(),
+        };
+        for context in contexts {
+            match context {
+                JsonValue::Null => {
+                    result = Self::new(self.base_iri.clone());
+                }
+                JsonValue::String(iri) => {
+                    return Err(JsonLdSyntaxError::msg(format!(
+                        "Remote context {iri} is not supported, it should be inlined"
+                    )));
+                }
+                JsonValue::Object(entries) => {
+                    result.process_object(entries)?;
+                }
+                _ => return Err(JsonLdSyntaxError::msg("Invalid local context")),
+            }
+        }
+        Ok(result)
+    }
+
+    fn process_object(&mut self, entries: &[(String, JsonValue)]) -> Result<(), JsonLdSyntaxError> {
+        for (key, value) in entries {
+            match key.as_str() {
+                "@version" => {
+                    if value != &JsonValue::Number("1.1".into()) {
+                        return Err(JsonLdSyntaxError::msg("Invalid @version value"));
+                    }
+                }
+                "@import" => {
+                    return Err(JsonLdSyntaxError::msg("@import is not supported"));
+                }
+                "@base" => {
+                    self.base_iri = match value {
+                        JsonValue::Null => None,
+                        JsonValue::String(iri) => Some(self.resolve_base(iri)?),
+                        _ => return Err(JsonLdSyntaxError::msg("Invalid @base value")),
+                    }
+                }
+                "@vocab" => {
+                    self.vocab = match value {
+                        JsonValue::Null => None,
+                        JsonValue::String(vocab) => Some(
+                            self.expand_iri(vocab, true, true)
+                                .ok_or_else(|| JsonLdSyntaxError::msg("Invalid @vocab value"))?,
+                        ),
+                        _ => return Err(JsonLdSyntaxError::msg("Invalid @vocab value")),
+                    }
+                }
+                "@language" => {
+                    self.default_language = match value {
+                        JsonValue::Null => None,
+                        JsonValue::String(language) => Some(language.to_ascii_lowercase()),
+                        _ => return Err(JsonLdSyntaxError::msg("Invalid @language value")),
+                    }
+                }
+                "@direction" | "@propagate" | "@protected" => {
+                    // Not relevant for RDF conversion
+                }
+                _ => {}
+            }
+        }
+        let mut defined = HashMap::new();
+        for (term, _) in entries {
+            if !term.starts_with('@') {
+                self.create_term_definition(entries, term, &mut defined)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn resolve_base(&self, iri: &str) -> Result<Iri<String>, JsonLdSyntaxError> {
+        let resolved = match &self.base_iri {
+            Some(base) => base.resolve(iri),
+            None => Iri::parse(iri.to_owned()),
+        };
+        resolved.map_err(|error| {
+            SyntaxErrorKind::InvalidIri {
+                iri: iri.to_owned(),
+                error,
+            }
+            .into()
+        })
+    }
+
+    /// The [create term definition algorithm](https://www.w3.org/TR/json-ld11-api/#create-term-definition).
+    fn create_term_definition(
+        &mut self,
+        local_context: &[(String, JsonValue)],
+        term: &str,
+        defined: &mut HashMap<String, bool>,
+    ) -> Result<(), JsonLdSyntaxError> {
+        match defined.get(term) {
+            Some(true) => return Ok(()),
+            Some(false) => {
+                return Err(JsonLdSyntaxError::msg(format!(
+                    "Cyclic IRI mapping for term {term}"
+                )))
+            }
+            None => {}
+        }
+        let Some(value) = local_context
+            .iter()
+            .find_map(|(k, v)| (k == term).then_some(v))
+        else {
+            return Ok(());
+        };
+        defined.insert(term.to_owned(), false);
+        if is_keyword(term) || has_keyword_form(term) {
+            defined.insert(term.to_owned(), true);
+            return Ok(());
+        }
+
+        let mut definition = TermDefinition::default();
+        let (id, entries): (Option<&JsonValue>, &[(String, JsonValue)]) = match value {
+            JsonValue::Null | JsonValue::String(_) => (Some(value), &[]),
+            JsonValue::Object(entries) => (
+                entries
+                    .iter()
+                    .find_map(|(k, v)| (k == "@id" || k == "@reverse").then_some(v)),
+                entries,
+            ),
+            _ => return Err(JsonLdSyntaxError::msg("Invalid term definition")),
+        };
+        for (key, value) in entries {
+            match key.as_str() {
+                "@id" => {}
+                "@reverse" => definition.reverse = true,
+                "@type" => {
+                    let Some(r#type) = value.as_str() else {
+                        return Err(JsonLdSyntaxError::msg("Invalid type mapping"));
+                    };
+                    definition.type_mapping =
+                        Some(if matches!(r#type, "@id" | "@vocab" | "@json" | "@none") {
+                            r#type.to_owned()
+                        } else {
+                            self.expand_iri_with_local(r#type, local_context, defined)?
+                                .ok_or_else(|| JsonLdSyntaxError::msg("Invalid type mapping"))?
+                        });
+                }
+                "@container" => definition.container = Container::parse(value)?,
+                "@language" => {
+                    definition.language = Some(match value {
+                        JsonValue::Null => None,
+                        JsonValue::String(language) => Some(language.to_ascii_lowercase()),
+                        _ => return Err(JsonLdSyntaxError::msg("Invalid language mapping")),
+                    })
+                }
+                "@context" => definition.context = Some(value.clone()),
+                "@prefix" => definition.prefix = value == &JsonValue::Boolean(true),
+                "@index" | "@nest" | "@protected" | "@direction" => {}
+                _ => {
+                    return Err(JsonLdSyntaxError::msg(format!(
+                        "Invalid term definition entry {key}"
+                    )))
+                }
+            }
+        }
+
+        definition.iri = match id {
+            Some(JsonValue::Null) => None,
+            Some(JsonValue::String(id)) => {
+                if has_keyword_form(id) && !is_keyword(id) {
+                    None
+                } else {
+                    self.expand_iri_with_local(id, local_context, defined)?
+                }
+            }
+            Some(_) => return Err(JsonLdSyntaxError::msg("Invalid IRI mapping")),
+            None => {
+                if let Some((prefix, suffix)) = term.split_once(':') {
+                    // Compact IRI or absolute IRI used as a term
+                    self.create_term_definition(local_context, prefix, defined)?;
+                    Some(match self.terms.get(prefix).and_then(|d| d.iri.as_ref()) {
+                        Some(prefix_iri) => format!("{prefix_iri}{suffix}"),
+                        None => term.to_owned(),
+                    })
+                } else if let Some(vocab) = &self.vocab {
+                    Some(format!("{vocab}{term}"))
+                } else {
+                    return Err(JsonLdSyntaxError::msg(format!(
+                        "Invalid IRI mapping for term {term}"
+                    )));
+                }
+            }
+        };
+        if !definition.prefix && !term.contains(':') && !term.contains('/') {
+            // Simple terms mapping to an IRI ending with a gen-delim can be used as prefixes
+            definition.prefix = matches!(value, JsonValue::String(_))
+                && definition
+                    .iri
+                    .as_ref()
+                    .is_some_and(|iri| iri.ends_with([':', '/', '?', '#', '[', ']', '@']));
+        }
+        self.terms.insert(term.to_owned(), definition);
+        defined.insert(term.to_owned(), true);
+        Ok(())
+    }
+
+    fn expand_iri_with_local(
+        &mut self,
+        value: &str,
+        local_context: &[(String, JsonValue)],
+        defined: &mut HashMap<String, bool>,
+    ) -> Result<Option<String>, JsonLdSyntaxError> {
+        if local_context.iter().any(|(k, _)| k == value) {
+            self.create_term_definition(local_context, value, defined)?;
+        }
+        if let Some((prefix, _)) = value.split_once(':') {
+            if local_context.iter().any(|(k, _)| k == prefix) {
+                self.create_term_definition(local_context, prefix, defined)?;
+            }
+        }
+        Ok(self.expand_iri(value, false, true))
+    }
+
+    /// The [IRI expansion algorithm](https://www.w3.org/TR/json-ld11-api/#iri-expansion).
+    ///
+    /// Returns a keyword, an absolute IRI or a blank node identifier (starting with `_:`).
+    /// Returns `None` if the value must be ignored.
+    pub fn expand_iri(&self, value: &str, document_relative: bool, vocab: bool) -> Option<String> {
+        if is_keyword(value) {
+            return Some(value.to_owned());
+        }
+        if has_keyword_form(value) {
+            return None;
+        }
+        if vocab {
+            if let Some(definition) = self.terms.get(value) {
+                return definition.iri.clone();
+            }
+        }
+        if let Some((prefix, suffix)) = value.split_once(':') {
+            if prefix == "_" || suffix.starts_with("//") {
+                return Some(value.to_owned());
+            }
+            if let Some(definition) = self.terms.get(prefix) {
+                if definition.prefix || !prefix.contains(['/', ':']) {
+                    if let Some(iri) = &definition.iri {
+                        return Some(format!("{iri}{suffix}"));
+                    }
+                }
+            }
+            if Iri::parse(value).is_ok() {
+                return Some(value.to_owned());
+            }
+        }
+        if vocab {
+            if let Some(vocab) = &self.vocab {
+                return Some(format!("{vocab}{value}"));
+            }
+        }
+        if document_relative {
+            if let Some(base) = &self.base_iri {
+                return base.resolve(value).ok().map(Iri::into_inner);
+            }
+        }
+        Iri::parse(value).ok().map(|_| value.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(json: &str) -> Result<JsonLdContext, JsonLdSyntaxError> {
+        JsonLdContext::default().process(&JsonValue::parse(json.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn test_terms_and_compact_iris() {
+        let context = context(
+            r#"{
+                "ex": "http://example.com/",
+                "name": "http://schema.org/name",
+                "knows": {"@id": "ex:knows", "@type": "@id", "@container": "@set"},
+                "ignored": null
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            context.expand_iri("name", false, true).as_deref(),
+            Some("http://schema.org/name")
+        );
+        assert_eq!(
+            context.expand_iri("ex:foo", true, false).as_deref(),
+            Some("http://example.com/foo")
+        );
+        let knows = context.terms.get("knows").unwrap();
+        assert_eq!(knows.iri.as_deref(), Some("http://example.com/knows"));
+        assert_eq!(knows.type_mapping.as_deref(), Some("@id"));
+        assert!(knows.container.contains(Container::SET));
+        assert!(context.terms.get("ex").unwrap().prefix);
+        assert!(!context.terms.get("name").unwrap().prefix);
+        assert_eq!(context.expand_iri("ignored", false, true), None);
+        // Terms only apply to vocabulary positions.
+        assert_eq!(context.expand_iri("name", true, false), None);
+    }
+
+    #[test]
+    fn test_keywords_blank_nodes_and_absolute_iris() {
+        let context = context(r#"{"@vocab": "http://schema.org/"}"#).unwrap();
+        assert_eq!(
+            context.expand_iri("@type", false, true).as_deref(),
+            Some("@type")
+        );
+        assert_eq!(context.expand_iri("@unknown", false, true), None);
+        assert_eq!(
+            context.expand_iri("_:b0", true, false).as_deref(),
+            Some("_:b0")
+        );
+        assert_eq!(
+            context
+                .expand_iri("http://example.com/x", true, true)
+                .as_deref(),
+            Some("http://example.com/x")
+        );
+        assert_eq!(
+            context.expand_iri("Person", false, true).as_deref(),
+            Some("http://schema.org/Person")
+        );
+        assert_eq!(context.expand_iri("Person", true, false), None);
+    }
+
+    #[test]
+    fn test_base_and_vocab() {
+        let context =
+            context(r#"{"@base": "http://example.com/dir/", "@vocab": "ns#", "@language": "EN"}"#)
+                .unwrap();
+        assert_eq!(
+            context.expand_iri("../a", true, false).as_deref(),
+            Some("http://example.com/a")
+        );
+        // A relative vocabulary is resolved against the base.
+        assert_eq!(
+            context.expand_iri("p", false, true).as_deref(),
+            Some("http://example.com/dir/ns#p")
+        );
+        assert_eq!(context.default_language.as_deref(), Some("en"));
+    }
+
+    #[test]
+    fn test_nested_contexts() {
+        let outer =
+            context(r#"{"a": "http://example.com/a", "b": "http://example.com/b"}"#).unwrap();
+        let inner = outer
+            .process(&JsonValue::parse(br#"{"b": "http://example.org/b"}"#).unwrap())
+            .unwrap();
+        assert_eq!(
+            inner.expand_iri("a", false, true).as_deref(),
+            Some("http://example.com/a")
+        );
+        assert_eq!(
+            inner.expand_iri("b", false, true).as_deref(),
+            Some("http://example.org/b")
+        );
+        // A null context resets the active context.
+        let reset = outer.process(&JsonValue::Null).unwrap();
+        assert_eq!(reset.expand_iri("a", false, true), None);
+        let reset = outer
+            .process(&JsonValue::parse(br#"[null, {"c": "http://example.com/c"}]"#).unwrap())
+            .unwrap();
+        assert_eq!(reset.expand_iri("a", false, true), None);
+        assert_eq!(
+            reset.expand_iri("c", false, true).as_deref(),
+            Some("http://example.com/c")
+        );
+    }
+
+    #[test]
+    fn test_invalid_contexts() {
+        assert!(context(r#"{"a": "b:x", "b": "a:y"}"#).is_err());
+        assert!(context(r#""https://schema.org/""#).is_err());
+        assert!(context(r#"{"@import": "https://schema.org/"}"#).is_err());
+        assert!(context(r#"{"a": {"@id": "http://example.com/a", "@foo": 1}}"#).is_err());
+        assert!(context(r#"{"a": 1}"#).is_err());
+    }
+}
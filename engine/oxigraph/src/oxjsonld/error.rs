@@ -0,0 +1,75 @@
+use oxiri::IriParseError;
+use std::io;
+
+/// Error returned during JSON-LD parsing.
+#[derive(Debug, thiserror::Error)]
+pub enum JsonLdParseError {
+    /// I/O error during parsing (file not found...).
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// An error in the file syntax.
+    #[error(transparent)]
+    Syntax(#[from] JsonLdSyntaxError),
+}
+
+impl From<JsonLdParseError> for io::Error {
+    #[inline]
+    fn from(error: JsonLdParseError) -> Self {
+        match error {
+            JsonLdParseError::Io(error) => error,
+            JsonLdParseError::Syntax(error) => error.into(),
+        }
+    }
+}
+
+impl From<json_event_parser::ParseError> for JsonLdParseError {
+    #[inline]
+    fn from(error: json_event_parser::ParseError) -> Self {
+        match error {
+            json_event_parser::ParseError::Syntax(error) => {
+                Self::Syntax(JsonLdSyntaxError(SyntaxErrorKind::Json(error)))
+            }
+            json_event_parser::ParseError::Io(error) => Self::Io(error),
+        }
+    }
+}
+
+/// An error in the syntax of the parsed file.
+#[derive(Debug, thiserror::Error)]
+#[error(transparent)]
+pub struct JsonLdSyntaxError(#[from] pub(crate) SyntaxErrorKind);
+
+#[derive(Debug, thiserror::Error)]
+pub enum SyntaxErrorKind {
+    #[error(transparent)]
+    Json(#[from] json_event_parser::SyntaxError),
+    #[error("error while parsing IRI '{iri}': {error}")]
+    InvalidIri {
+        iri: String,
+        #[source]
+        error: IriParseError,
+    },
+    #[error("{0}")]
+    Msg(String),
+}
+
+impl JsonLdSyntaxError {
+    /// Builds an error from a printable error message.
+    #[inline]
+    pub(crate) fn msg(msg: impl Into<String>) -> Self {
+        Self(SyntaxErrorKind::Msg(msg.into()))
+    }
+}
+
+impl From<JsonLdSyntaxError> for io::Error {
+    #[inline]
+    fn from(error: JsonLdSyntaxError) -> Self {
+        match error.0 {
+            SyntaxErrorKind::Json(error) => Self::new(io::ErrorKind::InvalidData, error),
+            SyntaxErrorKind::Msg(msg) => Self::new(io::ErrorKind::InvalidData, msg),
+            error @ SyntaxErrorKind::InvalidIri { .. } => {
+                Self::new(io::ErrorKind::InvalidData, error)
+            }
+        }
+    }
+}
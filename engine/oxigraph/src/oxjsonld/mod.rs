@@ -0,0 +1,12 @@
+mod context;
+mod error;
+mod parser;
+mod serializer;
+
+pub use error::{JsonLdParseError, JsonLdSyntaxError};
+#[cfg(feature = "async-tokio")]
+pub use parser::FromTokioAsyncReadJsonLdReader;
+pub use parser::{FromReadJsonLdReader, JsonLdParser};
+#[cfg(feature = "async-tokio")]
+pub use serializer::ToTokioAsyncWriteJsonLdWriter;
+pub use serializer::{JsonLdSerializer, ToWriteJsonLdWriter};
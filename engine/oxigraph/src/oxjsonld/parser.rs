@@ -0,0 +1,1089 @@
+use crate::oxjsonld::context::{
+    Container, JsonLdContext, JsonTreeBuilder, JsonValue, TermDefinition,
+};
+use crate::oxjsonld::error::{JsonLdParseError, JsonLdSyntaxError};
+use crate::oxrdf::vocab::{rdf, xsd};
+use crate::oxrdf::{BlankNode, GraphName, Literal, NamedNode, Quad, Subject, Term};
+#[cfg(feature = "async-tokio")]
+use json_event_parser::FromTokioAsyncReadJsonReader;
+use json_event_parser::{FromReadJsonReader, JsonEvent};
+use oxiri::{Iri, IriParseError};
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::io::Read;
+#[cfg(feature = "async-tokio")]
+use tokio::io::AsyncRead;
+
+const RDF_JSON: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#JSON";
+
+/// Above this nesting of JSON-LD objects, the document is rejected to avoid stack overflows.
+const MAX_NESTING: usize = 128;
+
+/// A [JSON-LD 1.1](https://www.w3.org/TR/json-ld11/) streaming parser.
+///
+/// It converts the document to RDF following the [Deserialize JSON-LD to RDF algorithm](https://www.w3.org/TR/json-ld11-api/#deserialize-json-ld-to-rdf-algorithm).
+///
+/// The elements of a top-level array are converted as soon as they are read.
+/// The `@graph` array of a top-level object is streamed too if the object starts with its
+/// `@context` (and `@id`, if any), as recommended by the
+/// [streaming profile](https://www.w3.org/TR/json-ld11-streaming/). Such an object must not have
+/// an `@id` or other properties after its `@graph`.
+/// Otherwise, and for all other objects, the object is kept in memory until it is complete.
+///
+/// Remote contexts are not fetched: they must be inlined in the document.
+///
+/// Count the number of people:
+/// ```
+/// use ng_oxigraph::oxjsonld::JsonLdParser;
+/// use ng_oxigraph::oxrdf::vocab::rdf;
+/// use ng_oxigraph::oxrdf::NamedNodeRef;
+///
+/// let file = br#"{
+///     "@context": {"schema": "http://schema.org/"},
+///     "@graph": [
+///         {"@id": "http://example.com/foo", "@type": "schema:Person", "schema:name": "Foo"},
+///         {"@id": "http://example.com/bar", "@type": "schema:Person", "schema:name": "Bar"}
+///     ]
+/// }"#;
+///
+/// let schema_person = NamedNodeRef::new("http://schema.org/Person")?;
+/// let mut count = 0;
+/// for quad in JsonLdParser::new().parse_read(file.as_ref()) {
+///     let quad = quad?;
+///     if quad.predicate == rdf::TYPE && quad.object == schema_person.into() {
+///         count += 1;
+///     }
+/// }
+/// assert_eq!(2, count);
+/// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+/// ```
+#[derive(Default)]
+#[must_use]
+pub struct JsonLdParser {
+    unchecked: bool,
+    base: Option<Iri<String>>,
+}
+
+impl JsonLdParser {
+    /// Builds a new [`JsonLdParser`].
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Assumes the file is valid to make parsing faster.
+    ///
+    /// It will skip some validations.
+    ///
+    /// Note that if the file is actually not valid, then broken RDF might be emitted by the parser.
+    #[inline]
+    pub fn unchecked(mut self) -> Self {
+        self.unchecked = true;
+        self
+    }
+
+    #[inline]
+    pub fn with_base_iri(mut self, base_iri: impl Into<String>) -> Result<Self, IriParseError> {
+        self.base = Some(Iri::parse(base_iri.into())?);
+        Ok(self)
+    }
+
+    /// Parses a JSON-LD file from a [`Read`] implementation.
+    pub fn parse_read<R: Read>(self, read: R) -> FromReadJsonLdReader<R> {
+        FromReadJsonLdReader {
+            reader: FromReadJsonReader::new(read),
+            inner: self.inner_reader(),
+        }
+    }
+
+    /// Parses a JSON-LD file from a [`AsyncRead`] implementation.
+    #[cfg(feature = "async-tokio")]
+    pub fn parse_tokio_async_read<R: AsyncRead + Unpin>(
+        self,
+        read: R,
+    ) -> FromTokioAsyncReadJsonLdReader<R> {
+        FromTokioAsyncReadJsonLdReader {
+            reader: FromTokioAsyncReadJsonReader::new(read),
+            inner: self.inner_reader(),
+        }
+    }
+
+    fn inner_reader(self) -> InnerJsonLdReader {
+        InnerJsonLdReader {
+            state: ReaderState::Start,
+            builder: JsonTreeBuilder::default(),
+            context: JsonLdContext::new(self.base),
+            converter: RdfConverter {
+                unchecked: self.unchecked,
+                blank_nodes: HashMap::new(),
+                depth: 0,
+                results: VecDeque::new(),
+            },
+        }
+    }
+}
+
+/// Parses a JSON-LD file from a [`Read`] implementation. Can be built using [`JsonLdParser::parse_read`].
+#[must_use]
+pub struct FromReadJsonLdReader<R: Read> {
+    reader: FromReadJsonReader<R>,
+    inner: InnerJsonLdReader,
+}
+
+impl<R: Read> Iterator for FromReadJsonLdReader<R> {
+    type Item = Result<Quad, JsonLdParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(quad) = self.inner.converter.results.pop_front() {
+                return Some(Ok(quad));
+            }
+            if matches!(self.inner.state, ReaderState::End) {
+                return None;
+            }
+            let result = match self.reader.read_next_event() {
+                Ok(event) => self.inner.read_event(event).map_err(JsonLdParseError::from),
+                Err(error) => Err(error.into()),
+            };
+            if let Err(error) = result {
+                self.inner.state = ReaderState::End;
+                return Some(Err(error));
+            }
+        }
+    }
+}
+
+/// Parses a JSON-LD file from a [`AsyncRead`] implementation. Can be built using [`JsonLdParser::parse_tokio_async_read`].
+#[cfg(feature = "async-tokio")]
+#[must_use]
+pub struct FromTokioAsyncReadJsonLdReader<R: AsyncRead + Unpin> {
+    reader: FromTokioAsyncReadJsonReader<R>,
+    inner: InnerJsonLdReader,
+}
+
+#[cfg(feature = "async-tokio")]
+impl<R: AsyncRead + Unpin> FromTokioAsyncReadJsonLdReader<R> {
+    /// Reads the next quad or returns `None` if the file is finished.
+    pub async fn next(&mut self) -> Option<Result<Quad, JsonLdParseError>> {
+        loop {
+            if let Some(quad) = self.inner.converter.results.pop_front() {
+                return Some(Ok(quad));
+            }
+            if matches!(self.inner.state, ReaderState::End) {
+                return None;
+            }
+            let result = match self.reader.read_next_event().await {
+                Ok(event) => self.inner.read_event(event).map_err(JsonLdParseError::from),
+                Err(error) => Err(error.into()),
+            };
+            if let Err(error) = result {
+                self.inner.state = ReaderState::End;
+                return Some(Err(error));
+            }
+        }
+    }
+}
+
+enum ReaderState {
+    Start,
+    /// Inside of the top-level array
+    TopArray,
+    /// Inside of the top-level object
+    TopObject {
+        entries: Vec<(String, JsonValue)>,
+        current_key: Option<String>,
+        /// True once the `@context` of the object is processed
+        has_context: bool,
+        /// The graph the `@graph` array has already been streamed to
+        streamed_graph: Option<GraphName>,
+    },
+    /// Inside of the `@graph` array of the top-level object
+    TopGraph {
+        entries: Vec<(String, JsonValue)>,
+        graph: GraphName,
+    },
+    AfterDocument,
+    End,
+}
+
+struct InnerJsonLdReader {
+    state: ReaderState,
+    builder: JsonTreeBuilder,
+    /// The context of the top-level object
+    context: JsonLdContext,
+    converter: RdfConverter,
+}
+
+impl InnerJsonLdReader {
+    fn read_event(&mut self, event: JsonEvent<'_>) -> Result<(), JsonLdSyntaxError> {
+        match &mut self.state {
+            ReaderState::Start => match event {
+                JsonEvent::StartArray => self.state = ReaderState::TopArray,
+                JsonEvent::StartObject => {
+                    self.state = ReaderState::TopObject {
+                        entries: Vec::new(),
+                        current_key: None,
+                        has_context: false,
+                        streamed_graph: None,
+                    }
+                }
+                _ => {
+                    return Err(JsonLdSyntaxError::msg(
+                        "A JSON-LD document must be an object or an array",
+                    ))
+                }
+            },
+            ReaderState::TopArray => {
+                if self.builder.is_empty() && event == JsonEvent::EndArray {
+                    self.state = ReaderState::AfterDocument;
+                } else if let Some(value) = self.builder.push(event)? {
+                    self.converter.convert_top_level(&value, &self.context)?;
+                }
+            }
+            ReaderState::TopObject {
+                entries,
+                current_key,
+                has_context,
+                streamed_graph,
+            } => {
+                if self.builder.is_empty() {
+                    match event {
+                        JsonEvent::ObjectKey(key) => {
+                            if let Some(graph) = streamed_graph {
+                                let expanded = self.context.expand_iri(&key, false, true);
+                                if matches!(expanded.as_deref(), Some("@context" | "@id"))
+                                    || graph.is_default_graph()
+                                {
+                                    return Err(JsonLdSyntaxError::msg(
+                                        "The @id and properties of a top-level object starting with its @context must be before @graph",
+                                    ));
+                                }
+                            }
+                            *current_key = Some(key.into());
+                            return Ok(());
+                        }
+                        // Without a context yet, a later one could change the meaning of the graph,
+                        // so the `@graph` is buffered like any other value.
+                        JsonEvent::StartArray
+                            if *has_context
+                                && streamed_graph.is_none()
+                                && current_key.as_deref().is_some_and(|key| {
+                                    self.context.expand_iri(key, false, true).as_deref()
+                                        == Some("@graph")
+                                })
+                                && entries.iter().all(|(key, _)| {
+                                    self.context.expand_iri(key, false, true).as_deref()
+                                        == Some("@id")
+                                }) =>
+                        {
+                            let graph = match entries.first().and_then(|(_, id)| id.as_str()) {
+                                Some(id) => self
+                                    .context
+                                    .expand_iri(id, true, false)
+                                    .and_then(|id| self.converter.subject(&id))
+                                    .map_or_else(
+                                        || GraphName::from(BlankNode::default()),
+                                        subject_to_graph_name,
+                                    ),
+                                None => GraphName::DefaultGraph,
+                            };
+                            self.state = ReaderState::TopGraph {
+                                entries: std::mem::take(entries),
+                                graph,
+                            };
+                            return Ok(());
+                        }
+                        JsonEvent::EndObject => {
+                            let entries = std::mem::take(entries);
+                            if streamed_graph.is_none() {
+                                self.converter.convert_top_level(
+                                    &JsonValue::Object(entries),
+                                    &self.context,
+                                )?;
+                            } else if entries.len() > 1 {
+                                // The properties of the named graph node
+                                self.converter.convert_node(
+                                    &JsonValue::Object(entries),
+                                    &self.context,
+                                    &GraphName::DefaultGraph,
+                                )?;
+                            }
+                            self.state = ReaderState::AfterDocument;
+                            return Ok(());
+                        }
+                        _ => {}
+                    }
+                }
+                if let Some(value) = self.builder.push(event)? {
+                    let key = current_key
+                        .take()
+                        .ok_or_else(|| JsonLdSyntaxError::msg("Object value without key"))?;
+                    if key == "@context" {
+                        self.context = self.context.process(&value)?;
+                        *has_context = true;
+                    } else {
+                        entries.push((key, value));
+                    }
+                }
+            }
+            ReaderState::TopGraph { entries, graph } => {
+                if self.builder.is_empty() && event == JsonEvent::EndArray {
+                    self.state = ReaderState::TopObject {
+                        entries: std::mem::take(entries),
+                        current_key: None,
+                        has_context: true,
+                        streamed_graph: Some(graph.clone()),
+                    };
+                } else if let Some(value) = self.builder.push(event)? {
+                    self.converter.convert_node(&value, &self.context, graph)?;
+                }
+            }
+            ReaderState::AfterDocument => {
+                if event != JsonEvent::Eof {
+                    return Err(JsonLdSyntaxError::msg(
+                        "Unexpected content after the end of the document",
+                    ));
+                }
+                self.state = ReaderState::End;
+            }
+            ReaderState::End => {}
+        }
+        Ok(())
+    }
+}
+
+fn subject_to_graph_name(subject: Subject) -> GraphName {
+    match subject {
+        Subject::NamedNode(node) => node.into(),
+        Subject::BlankNode(node) => node.into(),
+        #[cfg(feature = "rdf-star")]
+        Subject::Triple(_) => BlankNode::default().into(),
+    }
+}
+
+/// Converts expanded JSON-LD values to RDF quads.
+struct RdfConverter {
+    unchecked: bool,
+    /// The blank nodes of the document, by identifier
+    blank_nodes: HashMap<String, BlankNode>,
+    depth: usize,
+    results: VecDeque<Quad>,
+}
+
+impl RdfConverter {
+    /// Converts an element of the top-level array, or the top-level object.
+    fn convert_top_level(
+        &mut self,
+        value: &JsonValue,
+        context: &JsonLdContext,
+    ) -> Result<(), JsonLdSyntaxError> {
+        for item in value.as_slice() {
+            let JsonValue::Object(entries) = item else {
+                // Free-floating values are dropped
+                continue;
+            };
+            let context = match item.get("@context") {
+                Some(local_context) => Cow::Owned(context.process(local_context)?),
+                None => Cow::Borrowed(context),
+            };
+            let only_graph = entries.iter().all(|(key, _)| {
+                matches!(
+                    context.expand_iri(key, false, true).as_deref(),
+                    Some("@context" | "@graph")
+                )
+            });
+            if only_graph {
+                // Not a node object but a wrapper around the nodes of the default graph
+                for (key, value) in entries {
+                    if key != "@context" {
+                        for node in value.as_slice() {
+                            self.convert_node(node, &context, &GraphName::DefaultGraph)?;
+                        }
+                    }
+                }
+            } else {
+                self.convert_node(item, context.as_ref(), &GraphName::DefaultGraph)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Converts a node object in the given graph.
+    fn convert_node(
+        &mut self,
+        value: &JsonValue,
+        context: &JsonLdContext,
+        graph: &GraphName,
+    ) -> Result<(), JsonLdSyntaxError> {
+        let mut terms = Vec::new();
+        self.convert_item(value, None, context, graph, None, &mut terms)
+    }
+
+    /// Converts a value, and pushes the resulting terms to `terms`.
+    fn convert_item(
+        &mut self,
+        value: &JsonValue,
+        definition: Option<&TermDefinition>,
+        context: &JsonLdContext,
+        graph: &GraphName,
+        default_id: Option<&str>,
+        terms: &mut Vec<Term>,
+    ) -> Result<(), JsonLdSyntaxError> {
+        let type_mapping = definition.and_then(|d| d.type_mapping.as_deref());
+        match value {
+            JsonValue::Null => {}
+            JsonValue::Array(values) => {
+                for value in values {
+                    self.convert_item(value, definition, context, graph, default_id, terms)?;
+                }
+            }
+            JsonValue::Object(_) | JsonValue::String(_) if type_mapping == Some("@json") => {
+                terms.push(json_literal(value).into());
+            }
+            JsonValue::Object(_) => {
+                self.depth += 1;
+                if self.depth > MAX_NESTING {
+                    return Err(JsonLdSyntaxError::msg("Too deeply nested JSON-LD objects"));
+                }
+                let result =
+                    self.convert_object(value, definition, context, graph, default_id, terms);
+                self.depth -= 1;
+                result?;
+            }
+            JsonValue::String(value) => match type_mapping {
+                Some("@id") => {
+                    if let Some(subject) = context
+                        .expand_iri(value, true, false)
+                        .and_then(|id| self.subject(&id))
+                    {
+                        terms.push(subject.into());
+                    }
+                }
+                Some("@vocab") => {
+                    if let Some(subject) = context
+                        .expand_iri(value, true, true)
+                        .and_then(|id| self.subject(&id))
+                    {
+                        terms.push(subject.into());
+                    }
+                }
+                _ => {
+                    let language = match definition.and_then(|d| d.language.as_ref()) {
+                        Some(language) => language.as_deref(),
+                        None => context.default_language.as_deref(),
+                    };
+                    if let Some(literal) = self.literal(
+                        value,
+                        type_mapping,
+                        language.filter(|_| type_mapping.is_none()),
+                    )? {
+                        terms.push(literal.into());
+                    }
+                }
+            },
+            JsonValue::Boolean(_) | JsonValue::Number(_) => {
+                if let Some(literal) = self.native_literal(value, type_mapping) {
+                    terms.push(literal.into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Converts a value object, a list object, a set object or a node object.
+    fn convert_object(
+        &mut self,
+        value: &JsonValue,
+        definition: Option<&TermDefinition>,
+        context: &JsonLdContext,
+        graph: &GraphName,
+        default_id: Option<&str>,
+        terms: &mut Vec<Term>,
+    ) -> Result<(), JsonLdSyntaxError> {
+        let JsonValue::Object(entries) = value else {
+            return Ok(());
+        };
+        let mut context = match value.get("@context") {
+            Some(local_context) => Cow::Owned(context.process(local_context)?),
+            None => Cow::Borrowed(context),
+        };
+        let expanded_entries = entries
+            .iter()
+            .filter(|(key, _)| key != "@context")
+            .filter_map(|(key, value)| {
+                Some((context.expand_iri(key, false, true)?, key.as_str(), value))
+            })
+            .collect::<Vec<_>>();
+        let get = |keyword: &str| {
+            expanded_entries
+                .iter()
+                .find_map(|(k, _, v)| (k == keyword).then_some(*v))
+        };
+
+        if let Some(value) = get("@value") {
+            let r#type = get("@type")
+                .and_then(JsonValue::as_str)
+                .and_then(|t| context.expand_iri(t, true, true));
+            if r#type.as_deref() == Some("@json") {
+                terms.push(json_literal(value).into());
+                return Ok(());
+            }
+            let literal = match value {
+                JsonValue::Null => None,
+                JsonValue::String(value) => {
+                    let language = get("@language").and_then(JsonValue::as_str);
+                    self.literal(value, r#type.as_deref(), language)?
+                }
+                JsonValue::Boolean(_) | JsonValue::Number(_) => {
+                    self.native_literal(value, r#type.as_deref())
+                }
+                JsonValue::Array(_) | JsonValue::Object(_) => {
+                    return Err(JsonLdSyntaxError::msg("Invalid @value value"))
+                }
+            };
+            terms.extend(literal.map(Term::from));
+            return Ok(());
+        }
+        if let Some(list) = get("@list") {
+            let list = self.convert_list(list.as_slice(), definition, &context, graph)?;
+            terms.push(list);
+            return Ok(());
+        }
+        if let Some(set) = get("@set") {
+            return self.convert_item(set, definition, &context, graph, None, terms);
+        }
+
+        // Node object
+        let subject = match get("@id") {
+            Some(JsonValue::String(id)) => context
+                .expand_iri(id, true, false)
+                .and_then(|id| self.subject(&id)),
+            Some(_) => return Err(JsonLdSyntaxError::msg("@id value must be a string")),
+            None => match default_id {
+                Some(id) => context
+                    .expand_iri(id, true, false)
+                    .and_then(|id| self.subject(&id)),
+                None => Some(BlankNode::default().into()),
+            },
+        };
+
+        let mut types = get("@type")
+            .map(JsonValue::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(JsonValue::as_str)
+            .collect::<Vec<_>>();
+        // Type-scoped contexts are applied in lexicographical order
+        types.sort_unstable();
+        let type_context = types
+            .iter()
+            .filter_map(|t| context.terms.get(*t).and_then(|d| d.context.as_ref()))
+            .cloned()
+            .collect::<Vec<_>>();
+        for type_iri in types
+            .iter()
+            .filter_map(|t| context.expand_iri(t, true, true))
+            .collect::<Vec<_>>()
+        {
+            if let (Some(subject), Some(object)) = (&subject, self.subject(&type_iri)) {
+                self.emit(
+                    subject.clone(),
+                    rdf::TYPE.into_owned(),
+                    object.into(),
+                    graph,
+                );
+            }
+        }
+        for local_context in &type_context {
+            context = Cow::Owned(context.process(local_context)?);
+        }
+
+        if let Some(nodes) = get("@graph") {
+            let graph_name = subject
+                .clone()
+                .map_or_else(|| BlankNode::default().into(), subject_to_graph_name);
+            for node in nodes.as_slice() {
+                self.convert_node(node, &context, &graph_name)?;
+            }
+        }
+        if let Some(nodes) = get("@included") {
+            for node in nodes.as_slice() {
+                self.convert_node(node, &context, graph)?;
+            }
+        }
+        let subject_term = subject.as_ref().map(|s| Term::from(s.clone()));
+        if let Some(JsonValue::Object(reverse_entries)) = get("@reverse") {
+            for (key, values) in reverse_entries {
+                self.convert_property(key, values, true, subject_term.as_ref(), &context, graph)?;
+            }
+        }
+        let mut properties = expanded_entries
+            .iter()
+            .filter(|(expanded, _, _)| !expanded.starts_with('@'))
+            .map(|(_, key, value)| (*key, *value))
+            .collect::<Vec<_>>();
+        // Nested properties are properties of this node
+        let mut nests = expanded_entries
+            .iter()
+            .filter(|(expanded, _, _)| expanded == "@nest")
+            .flat_map(|(_, _, value)| value.as_slice())
+            .collect::<Vec<_>>();
+        while let Some(nest) = nests.pop() {
+            let JsonValue::Object(nest_entries) = nest else {
+                return Err(JsonLdSyntaxError::msg("Invalid @nest value"));
+            };
+            for (key, value) in nest_entries {
+                match context.expand_iri(key, false, true).as_deref() {
+                    Some("@nest") => nests.extend(value.as_slice()),
+                    Some(expanded) if !expanded.starts_with('@') => {
+                        properties.push((key.as_str(), value));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        for (key, values) in properties {
+            self.convert_property(key, values, false, subject_term.as_ref(), &context, graph)?;
+        }
+        terms.extend(subject_term);
+        Ok(())
+    }
+
+    /// Converts the values of a property of the node `subject`.
+    fn convert_property(
+        &mut self,
+        key: &str,
+        values: &JsonValue,
+        reverse: bool,
+        subject: Option<&Term>,
+        context: &JsonLdContext,
+        graph: &GraphName,
+    ) -> Result<(), JsonLdSyntaxError> {
+        let Some(predicate) = context.expand_iri(key, false, true) else {
+            return Ok(());
+        };
+        if predicate.starts_with("_:") {
+            // Blank node predicates are not allowed in RDF
+            return Ok(());
+        }
+        let Some(predicate) = self.named_node(predicate) else {
+            return Ok(());
+        };
+        let definition = context.terms.get(key);
+        let reverse = reverse != definition.is_some_and(|d| d.reverse);
+        let context = match definition.and_then(|d| d.context.as_ref()) {
+            Some(local_context) => Cow::Owned(context.process(local_context)?),
+            None => Cow::Borrowed(context),
+        };
+        let mut objects = Vec::new();
+        self.convert_property_values(values, definition, &context, graph, &mut objects)?;
+        let Some(subject) = subject else {
+            return Ok(());
+        };
+        for object in objects {
+            if reverse {
+                let object = match object {
+                    Term::NamedNode(node) => Subject::from(node),
+                    Term::BlankNode(node) => Subject::from(node),
+                    _ => continue,
+                };
+                self.emit(object, predicate.clone(), subject.clone(), graph);
+            } else if let Some(subject) = term_to_subject(subject) {
+                self.emit(subject, predicate.clone(), object, graph);
+            }
+        }
+        Ok(())
+    }
+
+    /// Converts the values of a property according to the container of its term definition.
+    fn convert_property_values(
+        &mut self,
+        values: &JsonValue,
+        definition: Option<&TermDefinition>,
+        context: &JsonLdContext,
+        graph: &GraphName,
+        terms: &mut Vec<Term>,
+    ) -> Result<(), JsonLdSyntaxError> {
+        let container = definition.map(|d| d.container).unwrap_or_default();
+        if container.is_plain() {
+            return self.convert_item(values, definition, context, graph, None, terms);
+        }
+        if container.contains(Container::LIST) {
+            if values.get("@list").is_some() {
+                return self.convert_item(values, definition, context, graph, None, terms);
+            }
+            let list = self.convert_list(values.as_slice(), definition, context, graph)?;
+            terms.push(list);
+            return Ok(());
+        }
+        let JsonValue::Object(map) = values else {
+            return self.convert_item(values, definition, context, graph, None, terms);
+        };
+        if map
+            .iter()
+            .any(|(key, _)| key.starts_with('@') && is_value_key(key))
+        {
+            // Not a map, but a regular value
+            return self.convert_item(values, definition, context, graph, None, terms);
+        }
+        if container.contains(Container::LANGUAGE) {
+            for (language, values) in map {
+                let language = (context.expand_iri(language, false, true).as_deref()
+                    != Some("@none"))
+                .then_some(language.as_str());
+                for value in values.as_slice() {
+                    match value {
+                        JsonValue::Null => {}
+                        JsonValue::String(value) => {
+                            terms.extend(self.literal(value, None, language)?.map(Term::from));
+                        }
+                        _ => return Err(JsonLdSyntaxError::msg("Invalid language map value")),
+                    }
+                }
+            }
+        } else if container.contains(Container::GRAPH) {
+            for (key, nodes) in map {
+                let graph_name = if container.contains(Container::ID) {
+                    context
+                        .expand_iri(key, true, false)
+                        .filter(|id| id != "@none")
+                        .and_then(|id| self.subject(&id))
+                        .map_or_else(|| BlankNode::default().into(), subject_to_graph_name)
+                } else {
+                    GraphName::from(BlankNode::default())
+                };
+                for node in nodes.as_slice() {
+                    self.convert_node(node, context, &graph_name)?;
+                }
+                match graph_name {
+                    GraphName::NamedNode(node) => terms.push(node.into()),
+                    GraphName::BlankNode(node) => terms.push(node.into()),
+                    GraphName::DefaultGraph => {}
+                }
+            }
+        } else if container.contains(Container::ID) {
+            for (id, nodes) in map {
+                let id = (context.expand_iri(id, false, true).as_deref() != Some("@none"))
+                    .then_some(id.as_str());
+                for node in nodes.as_slice() {
+                    self.convert_item(node, definition, context, graph, id, terms)?;
+                }
+            }
+        } else if container.contains(Container::TYPE) {
+            for (r#type, nodes) in map {
+                let type_iri = context
+                    .expand_iri(r#type, true, true)
+                    .filter(|t| t != "@none")
+                    .and_then(|t| self.subject(&t));
+                let mut node_terms = Vec::new();
+                for node in nodes.as_slice() {
+                    if let JsonValue::String(id) = node {
+                        // Strings in type maps are node references
+                        if let Some(subject) = context
+                            .expand_iri(id, true, false)
+                            .and_then(|id| self.subject(&id))
+                        {
+                            node_terms.push(subject.into());
+                        }
+                    } else {
+                        self.convert_item(node, definition, context, graph, None, &mut node_terms)?;
+                    }
+                }
+                for node in node_terms {
+                    if let (Some(subject), Some(type_iri)) = (term_to_subject(&node), &type_iri) {
+                        self.emit(
+                            subject,
+                            rdf::TYPE.into_owned(),
+                            type_iri.clone().into(),
+                            graph,
+                        );
+                    }
+                    terms.push(node);
+                }
+            }
+        } else {
+            // Index maps: the keys are not represented in RDF
+            for (_, values) in map {
+                self.convert_item(values, definition, context, graph, None, terms)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds a `rdf:List` from the values and returns its head.
+    fn convert_list(
+        &mut self,
+        values: &[JsonValue],
+        definition: Option<&TermDefinition>,
+        context: &JsonLdContext,
+        graph: &GraphName,
+    ) -> Result<Term, JsonLdSyntaxError> {
+        let mut items = Vec::new();
+        for value in values {
+            if let JsonValue::Array(nested) = value {
+                // Lists of lists
+                items.push(self.convert_list(nested, definition, context, graph)?);
+            } else {
+                self.convert_item(value, definition, context, graph, None, &mut items)?;
+            }
+        }
+        let mut head = Term::from(rdf::NIL.into_owned());
+        for item in items.into_iter().rev() {
+            let node = BlankNode::default();
+            self.emit(node.clone().into(), rdf::FIRST.into_owned(), item, graph);
+            self.emit(node.clone().into(), rdf::REST.into_owned(), head, graph);
+            head = node.into();
+        }
+        Ok(head)
+    }
+
+    fn emit(&mut self, subject: Subject, predicate: NamedNode, object: Term, graph: &GraphName) {
+        self.results
+            .push_back(Quad::new(subject, predicate, object, graph.clone()));
+    }
+
+    /// Builds a subject from an expanded IRI or blank node identifier.
+    fn subject(&mut self, id: &str) -> Option<Subject> {
+        if let Some(id) = id.strip_prefix("_:") {
+            Some(
+                self.blank_nodes
+                    .entry(id.to_owned())
+                    .or_default()
+                    .clone()
+                    .into(),
+            )
+        } else if id.starts_with('@') {
+            None
+        } else {
+            self.named_node(id.to_owned()).map(Subject::from)
+        }
+    }
+
+    /// Invalid IRIs are dropped, as required by the JSON-LD to RDF algorithm.
+    fn named_node(&self, iri: String) -> Option<NamedNode> {
+        if self.unchecked {
+            Some(NamedNode::new_unchecked(iri))
+        } else {
+            NamedNode::new(iri).ok()
+        }
+    }
+
+    fn literal(
+        &self,
+        value: &str,
+        datatype: Option<&str>,
+        language: Option<&str>,
+    ) -> Result<Option<Literal>, JsonLdSyntaxError> {
+        Ok(Some(match (datatype, language) {
+            (Some(datatype), _) if datatype != "@none" => {
+                let Some(datatype) = self.named_node(datatype.to_owned()) else {
+                    return Ok(None);
+                };
+                Literal::new_typed_literal(value, datatype)
+            }
+            (_, Some(language)) => {
+                if self.unchecked {
+                    Literal::new_language_tagged_literal_unchecked(
+                        value,
+                        language.to_ascii_lowercase(),
+                    )
+                } else {
+                    Literal::new_language_tagged_literal(value, language).map_err(|_| {
+                        JsonLdSyntaxError::msg(format!("Invalid language tag {language}"))
+                    })?
+                }
+            }
+            _ => Literal::new_simple_literal(value),
+        }))
+    }
+
+    /// Converts a JSON boolean or number, following the JSON-LD rules for native types.
+    fn native_literal(&self, value: &JsonValue, datatype: Option<&str>) -> Option<Literal> {
+        let datatype = datatype.filter(|d| *d != "@none" && *d != "@id" && *d != "@vocab");
+        if datatype == Some("@json") {
+            return Some(json_literal(value));
+        }
+        let (lexical, default_datatype) = match value {
+            JsonValue::Boolean(value) => (value.to_string(), xsd::BOOLEAN),
+            JsonValue::Number(value) => {
+                let is_integer = !value.contains(['.', 'e', 'E']);
+                if is_integer && datatype != Some(xsd::DOUBLE.as_str()) {
+                    (value.clone(), xsd::INTEGER)
+                } else {
+                    (canonical_double(value)?, xsd::DOUBLE)
+                }
+            }
+            _ => return None,
+        };
+        let datatype = match datatype {
+            Some(datatype) => self.named_node(datatype.to_owned())?,
+            None => default_datatype.into_owned(),
+        };
+        Some(Literal::new_typed_literal(lexical, datatype))
+    }
+}
+
+/// Keys that make an object a regular JSON-LD value and not a container map.
+fn is_value_key(key: &str) -> bool {
+    matches!(
+        key,
+        "@value" | "@list" | "@set" | "@id" | "@type" | "@graph"
+    )
+}
+
+fn term_to_subject(term: &Term) -> Option<Subject> {
+    match term {
+        Term::NamedNode(node) => Some(node.clone().into()),
+        Term::BlankNode(node) => Some(node.clone().into()),
+        _ => None,
+    }
+}
+
+fn json_literal(value: &JsonValue) -> Literal {
+    Literal::new_typed_literal(value.to_json_string(), NamedNode::new_unchecked(RDF_JSON))
+}
+
+/// The canonical `xsd:double` representation of a JSON number, like `1.5E1`.
+fn canonical_double(value: &str) -> Option<String> {
+    let value: f64 = value.parse().ok()?;
+    if !value.is_finite() {
+        return None;
+    }
+    let formatted = format!("{value:E}");
+    let (mantissa, exponent) = formatted.split_once('E')?;
+    Some(if mantissa.contains('.') {
+        formatted
+    } else {
+        format!("{mantissa}.0E{exponent}")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(file: &str) -> Vec<String> {
+        let mut quads = JsonLdParser::new()
+            .parse_read(file.as_bytes())
+            .map(|quad| quad.unwrap().to_string())
+            .collect::<Vec<_>>();
+        quads.sort();
+        quads
+    }
+
+    #[test]
+    fn test_context_and_values() {
+        let quads = parse(
+            r#"{
+                "@context": {
+                    "@vocab": "http://schema.org/",
+                    "ex": "http://example.com/",
+                    "knows": {"@type": "@id"},
+                    "age": {"@id": "ex:age", "@type": "http://www.w3.org/2001/XMLSchema#integer"}
+                },
+                "@id": "ex:alice",
+                "@type": "Person",
+                "name": {"@value": "Alice", "@language": "en"},
+                "age": "42",
+                "height": 1.5,
+                "knows": "ex:bob"
+            }"#,
+        );
+        assert_eq!(
+            quads,
+            vec![
+                "<http://example.com/alice> <http://example.com/age> \"42\"^^<http://www.w3.org/2001/XMLSchema#integer>",
+                "<http://example.com/alice> <http://schema.org/height> \"1.5E0\"^^<http://www.w3.org/2001/XMLSchema#double>",
+                "<http://example.com/alice> <http://schema.org/knows> <http://example.com/bob>",
+                "<http://example.com/alice> <http://schema.org/name> \"Alice\"@en",
+                "<http://example.com/alice> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://schema.org/Person>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_graphs_and_lists() {
+        let quads = parse(
+            r#"{
+                "@context": {"ex": "http://example.com/"},
+                "@graph": [
+                    {"@id": "ex:g", "@graph": {"@id": "ex:s", "ex:p": {"@list": ["a"]}}},
+                    {"@id": "ex:s", "ex:q": {"@id": "_:b"}}
+                ]
+            }"#,
+        );
+        assert_eq!(quads.len(), 4);
+        assert!(quads.iter().any(|q| q
+            .starts_with("<http://example.com/s> <http://example.com/p> _:")
+            && q.ends_with("<http://example.com/g>")));
+        assert!(quads.iter().any(|q| q.contains(
+            "<http://www.w3.org/1999/02/22-rdf-syntax-ns#first> \"a\" <http://example.com/g>"
+        )));
+        assert!(quads.iter().any(|q| q
+            .starts_with("<http://example.com/s> <http://example.com/q> _:")
+            && !q.ends_with("<http://example.com/g>")));
+    }
+
+    #[test]
+    fn test_context_after_graph() {
+        let quads = parse(
+            r#"{
+                "@graph": [{"@id": "ex:s", "p": "v"}],
+                "@context": {"ex": "http://example.com/", "p": "http://example.com/p"}
+            }"#,
+        );
+        assert_eq!(
+            quads,
+            vec!["<http://example.com/s> <http://example.com/p> \"v\""]
+        );
+    }
+
+    #[test]
+    fn test_id_after_graph() {
+        let quads = parse(
+            r#"{
+                "@graph": [{"@id": "http://example.com/s", "http://example.com/p": "v"}],
+                "@id": "http://example.com/g"
+            }"#,
+        );
+        assert_eq!(
+            quads,
+            vec!["<http://example.com/s> <http://example.com/p> \"v\" <http://example.com/g>"]
+        );
+    }
+
+    #[test]
+    fn test_graph_is_streamed_after_context() {
+        // The first node is converted before the end of the document is read.
+        let mut reader = JsonLdParser::new().parse_read(
+            br#"{
+                "@context": {"ex": "http://example.com/"},
+                "@graph": [{"@id": "ex:a", "ex:p": "x"}, {"@id""#
+                .as_ref(),
+        );
+        assert_eq!(
+            reader.next().unwrap().unwrap().to_string(),
+            "<http://example.com/a> <http://example.com/p> \"x\""
+        );
+        assert!(reader.next().unwrap().is_err());
+
+        // The graph name is only known from an @id before the @graph.
+        assert!(JsonLdParser::new()
+            .parse_read(
+                br#"{
+                    "@context": {"ex": "http://example.com/"},
+                    "@graph": [{"@id": "ex:a", "ex:p": "x"}],
+                    "@id": "ex:g"
+                }"#
+                .as_ref()
+            )
+            .any(|quad| quad.is_err()));
+    }
+
+    #[test]
+    fn test_remote_context_is_rejected() {
+        assert!(JsonLdParser::new()
+            .parse_read(br#"{"@context": "https://schema.org/", "name": "a"}"#.as_ref())
+            .any(|quad| quad.is_err()));
+    }
+}
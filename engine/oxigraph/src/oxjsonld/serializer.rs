@@ -0,0 +1,629 @@
+use crate::oxjsonld::context::{JsonLdContext, JsonValue, TermDefinition};
+use crate::oxjsonld::error::JsonLdParseError;
+use crate::oxrdf::vocab::{rdf, xsd};
+use crate::oxrdf::{GraphName, GraphNameRef, QuadRef, SubjectRef, TermRef};
+#[cfg(feature = "async-tokio")]
+use json_event_parser::ToTokioAsyncWriteJsonWriter;
+use json_event_parser::{JsonEvent, ToWriteJsonWriter};
+use oxiri::{Iri, IriParseError};
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+use std::io::Write;
+#[cfg(feature = "async-tokio")]
+use tokio::io::AsyncWrite;
+
+/// A [JSON-LD 1.1](https://www.w3.org/TR/json-ld11/) serializer.
+///
+/// The quads are written in the `@graph` array of the top-level object, one node object per
+/// subject. Consecutive quads with the same subject and graph are grouped in the same node object.
+/// Named graphs are written as graph objects.
+///
+/// The IRIs are compacted using the prefixes and the terms of the context given with
+/// [`with_context`](Self::with_context).
+///
+/// ```
+/// use ng_oxigraph::oxjsonld::JsonLdSerializer;
+/// use ng_oxigraph::oxrdf::{GraphNameRef, LiteralRef, NamedNodeRef, QuadRef};
+///
+/// let mut writer = JsonLdSerializer::new()
+///     .with_prefix("schema", "http://schema.org/")?
+///     .serialize_to_write(Vec::new());
+/// writer.write_quad(QuadRef::new(
+///     NamedNodeRef::new("http://example.com#me")?,
+///     NamedNodeRef::new("http://www.w3.org/1999/02/22-rdf-syntax-ns#type")?,
+///     NamedNodeRef::new("http://schema.org/Person")?,
+///     GraphNameRef::DefaultGraph,
+/// ))?;
+/// writer.write_quad(QuadRef::new(
+///     NamedNodeRef::new("http://example.com#me")?,
+///     NamedNodeRef::new("http://schema.org/name")?,
+///     LiteralRef::new_language_tagged_literal_unchecked("Foo Bar", "en"),
+///     GraphNameRef::DefaultGraph,
+/// ))?;
+/// assert_eq!(
+///     r#"{"@context":{"schema":"http://schema.org/"},"@graph":[{"@id":"http://example.com#me","@type":["schema:Person"],"schema:name":[{"@value":"Foo Bar","@language":"en"}]}]}"#,
+///     String::from_utf8(writer.finish()?)?
+/// );
+/// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+/// ```
+#[derive(Default)]
+#[must_use]
+pub struct JsonLdSerializer {
+    prefixes: BTreeMap<String, String>,
+    context: Option<JsonValue>,
+}
+
+impl JsonLdSerializer {
+    /// Builds a new [`JsonLdSerializer`].
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a prefix to the `@context` of the document, it is used to compact the IRIs.
+    #[inline]
+    pub fn with_prefix(
+        mut self,
+        prefix_name: impl Into<String>,
+        prefix_iri: impl Into<String>,
+    ) -> Result<Self, IriParseError> {
+        self.prefixes.insert(
+            prefix_name.into(),
+            Iri::parse(prefix_iri.into())?.into_inner(),
+        );
+        Ok(self)
+    }
+
+    /// Sets the `@context` of the document, used to compact the IRIs to terms and compact IRIs.
+    ///
+    /// Either the context itself or a document with a `@context` key is accepted.
+    /// Remote contexts are not supported.
+    ///
+    /// ```
+    /// use ng_oxigraph::oxjsonld::JsonLdSerializer;
+    /// use ng_oxigraph::oxrdf::{GraphNameRef, LiteralRef, NamedNodeRef, QuadRef};
+    ///
+    /// let mut writer = JsonLdSerializer::new()
+    ///     .with_context(r#"{"@vocab": "http://schema.org/"}"#)?
+    ///     .serialize_to_write(Vec::new());
+    /// writer.write_quad(QuadRef::new(
+    ///     NamedNodeRef::new("http://example.com#me")?,
+    ///     NamedNodeRef::new("http://schema.org/name")?,
+    ///     LiteralRef::new_simple_literal("Foo"),
+    ///     GraphNameRef::DefaultGraph,
+    /// ))?;
+    /// assert_eq!(
+    ///     r#"{"@context":{"@vocab":"http://schema.org/"},"@graph":[{"@id":"http://example.com#me","name":["Foo"]}]}"#,
+    ///     String::from_utf8(writer.finish()?)?
+    /// );
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn with_context(mut self, context: &str) -> Result<Self, JsonLdParseError> {
+        let context = JsonValue::parse(context.as_bytes())?;
+        let context = match context.get("@context") {
+            Some(inner) => inner.clone(),
+            None => context,
+        };
+        // Validates the context
+        JsonLdContext::default().process(&context)?;
+        self.context = Some(context);
+        Ok(self)
+    }
+
+    /// Writes a JSON-LD file to a [`Write`] implementation.
+    ///
+    /// This writer does unbuffered writes.
+    pub fn serialize_to_write<W: Write>(self, write: W) -> ToWriteJsonLdWriter<W> {
+        ToWriteJsonLdWriter {
+            writer: ToWriteJsonWriter::new(write),
+            inner: self.inner_writer(),
+        }
+    }
+
+    /// Writes a JSON-LD file to a [`AsyncWrite`] implementation.
+    ///
+    /// This writer does unbuffered writes.
+    #[cfg(feature = "async-tokio")]
+    pub fn serialize_to_tokio_async_write<W: AsyncWrite + Unpin>(
+        self,
+        write: W,
+    ) -> ToTokioAsyncWriteJsonLdWriter<W> {
+        ToTokioAsyncWriteJsonLdWriter {
+            writer: ToTokioAsyncWriteJsonWriter::new(write),
+            inner: self.inner_writer(),
+        }
+    }
+
+    fn inner_writer(self) -> InnerJsonLdWriter {
+        let mut context = self
+            .context
+            .as_ref()
+            .and_then(|c| JsonLdContext::default().process(c).ok())
+            .unwrap_or_default();
+        for (name, iri) in &self.prefixes {
+            context.terms.insert(
+                name.clone(),
+                TermDefinition {
+                    iri: Some(iri.clone()),
+                    prefix: true,
+                    ..TermDefinition::default()
+                },
+            );
+        }
+        InnerJsonLdWriter {
+            prefixes: self.prefixes,
+            context_value: self.context,
+            context,
+            started: false,
+            current_graph: None,
+            current_subject: None,
+            current_key: None,
+            written_keys: HashSet::new(),
+        }
+    }
+}
+
+/// Writes a JSON-LD file to a [`Write`] implementation. Can be built using [`JsonLdSerializer::serialize_to_write`].
+#[must_use]
+pub struct ToWriteJsonLdWriter<W: Write> {
+    writer: ToWriteJsonWriter<W>,
+    inner: InnerJsonLdWriter,
+}
+
+impl<W: Write> ToWriteJsonLdWriter<W> {
+    /// Writes an extra quad.
+    pub fn write_quad<'a>(&mut self, q: impl Into<QuadRef<'a>>) -> io::Result<()> {
+        let mut buffer = Vec::new();
+        self.inner.write_quad(q.into(), &mut buffer)?;
+        self.flush_buffer(buffer)
+    }
+
+    /// Ends the write process and returns the underlying [`Write`].
+    pub fn finish(mut self) -> io::Result<W> {
+        let mut buffer = Vec::new();
+        self.inner.finish(&mut buffer);
+        self.flush_buffer(buffer)?;
+        self.writer.finish()
+    }
+
+    fn flush_buffer(&mut self, buffer: Vec<JsonEvent<'_>>) -> io::Result<()> {
+        for event in buffer {
+            self.writer.write_event(event)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes a JSON-LD file to a [`AsyncWrite`] implementation. Can be built using [`JsonLdSerializer::serialize_to_tokio_async_write`].
+#[cfg(feature = "async-tokio")]
+#[must_use]
+pub struct ToTokioAsyncWriteJsonLdWriter<W: AsyncWrite + Unpin> {
+    writer: ToTokioAsyncWriteJsonWriter<W>,
+    inner: InnerJsonLdWriter,
+}
+
+#[cfg(feature = "async-tokio")]
+impl<W: AsyncWrite + Unpin> ToTokioAsyncWriteJsonLdWriter<W> {
+    /// Writes an extra quad.
+    pub async fn write_quad<'a>(&mut self, q: impl Into<QuadRef<'a>>) -> io::Result<()> {
+        let mut buffer = Vec::new();
+        self.inner.write_quad(q.into(), &mut buffer)?;
+        self.flush_buffer(buffer).await
+    }
+
+    /// Ends the write process and returns the underlying [`AsyncWrite`].
+    pub async fn finish(mut self) -> io::Result<W> {
+        let mut buffer = Vec::new();
+        self.inner.finish(&mut buffer);
+        self.flush_buffer(buffer).await?;
+        self.writer.finish()
+    }
+
+    async fn flush_buffer(&mut self, buffer: Vec<JsonEvent<'_>>) -> io::Result<()> {
+        for event in buffer {
+            self.writer.write_event(event).await?;
+        }
+        Ok(())
+    }
+}
+
+struct InnerJsonLdWriter {
+    prefixes: BTreeMap<String, String>,
+    /// The `@context` given by the user
+    context_value: Option<JsonValue>,
+    /// The context used to compact IRIs
+    context: JsonLdContext,
+    started: bool,
+    /// The graph of the currently open graph object, or the default graph
+    current_graph: Option<GraphName>,
+    current_subject: Option<String>,
+    current_key: Option<String>,
+    /// The keys already written in the current node object
+    written_keys: HashSet<String>,
+}
+
+impl InnerJsonLdWriter {
+    fn write_quad<'a>(
+        &mut self,
+        q: QuadRef<'a>,
+        output: &mut Vec<JsonEvent<'a>>,
+    ) -> io::Result<()> {
+        #[cfg(feature = "rdf-star")]
+        if let TermRef::Triple(_) = q.object {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "JSON-LD does not support RDF-star quoted triples",
+            ));
+        }
+        let object = self.term_value(q.object);
+        let subject = match q.subject {
+            SubjectRef::NamedNode(node) => self.compact_iri(node.as_str(), false),
+            SubjectRef::BlankNode(node) => node.to_string(),
+            #[cfg(feature = "rdf-star")]
+            SubjectRef::Triple(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "JSON-LD does not support RDF-star quoted triples",
+                ))
+            }
+        };
+        let (key, object) = match (q.predicate == rdf::TYPE, object) {
+            (true, ObjectValue::Id(_)) => (
+                "@type".to_owned(),
+                ObjectValue::Type(match q.object {
+                    TermRef::NamedNode(node) => self.compact_iri(node.as_str(), true),
+                    _ => q.object.to_string(),
+                }),
+            ),
+            (_, object) => (self.compact_iri(q.predicate.as_str(), true), object),
+        };
+        self.start(output);
+
+        if self.current_graph.as_ref().map(GraphName::as_ref) != Some(q.graph_name) {
+            self.close_node(output);
+            self.close_graph(output);
+            if let GraphNameRef::NamedNode(_) | GraphNameRef::BlankNode(_) = q.graph_name {
+                let id = match q.graph_name {
+                    GraphNameRef::NamedNode(node) => self.compact_iri(node.as_str(), false),
+                    _ => q.graph_name.to_string(),
+                };
+                output.push(JsonEvent::StartObject);
+                output.push(JsonEvent::ObjectKey("@id".into()));
+                output.push(JsonEvent::String(id.into()));
+                output.push(JsonEvent::ObjectKey("@graph".into()));
+                output.push(JsonEvent::StartArray);
+            }
+            self.current_graph = Some(q.graph_name.into_owned());
+        }
+        if self.current_subject.as_ref() != Some(&subject)
+            || (self.current_key.as_ref() != Some(&key) && self.written_keys.contains(&key))
+        {
+            // A key can't be repeated in a node object, a new one is opened for the same subject
+            self.close_node(output);
+            output.push(JsonEvent::StartObject);
+            output.push(JsonEvent::ObjectKey("@id".into()));
+            output.push(JsonEvent::String(subject.clone().into()));
+            self.current_subject = Some(subject);
+        }
+        if self.current_key.as_ref() != Some(&key) {
+            if self.current_key.take().is_some() {
+                output.push(JsonEvent::EndArray);
+            }
+            output.push(JsonEvent::ObjectKey(key.clone().into()));
+            output.push(JsonEvent::StartArray);
+            self.written_keys.insert(key.clone());
+            self.current_key = Some(key);
+        }
+        object.write(output);
+        Ok(())
+    }
+
+    fn finish(&mut self, output: &mut Vec<JsonEvent<'_>>) {
+        self.start(output);
+        self.close_node(output);
+        self.close_graph(output);
+        output.push(JsonEvent::EndArray);
+        output.push(JsonEvent::EndObject);
+    }
+
+    /// Writes the top-level object start, with the `@context`.
+    fn start(&mut self, output: &mut Vec<JsonEvent<'_>>) {
+        if self.started {
+            return;
+        }
+        self.started = true;
+        output.push(JsonEvent::StartObject);
+        let prefixes = (!self.prefixes.is_empty()).then(|| {
+            JsonValue::Object(
+                self.prefixes
+                    .iter()
+                    .map(|(name, iri)| (name.clone(), JsonValue::String(iri.clone())))
+                    .collect(),
+            )
+        });
+        let context = match (self.context_value.clone(), prefixes) {
+            (Some(context), Some(prefixes)) => {
+                let mut contexts = context.as_slice().to_vec();
+                contexts.push(prefixes);
+                Some(JsonValue::Array(contexts))
+            }
+            (context, prefixes) => context.or(prefixes),
+        };
+        if let Some(context) = context {
+            output.push(JsonEvent::ObjectKey("@context".into()));
+            let mut events = Vec::new();
+            context.to_events(&mut events);
+            output.extend(events);
+        }
+        output.push(JsonEvent::ObjectKey("@graph".into()));
+        output.push(JsonEvent::StartArray);
+    }
+
+    fn close_node(&mut self, output: &mut Vec<JsonEvent<'_>>) {
+        if self.current_key.take().is_some() {
+            output.push(JsonEvent::EndArray);
+        }
+        if self.current_subject.take().is_some() {
+            output.push(JsonEvent::EndObject);
+        }
+        self.written_keys.clear();
+    }
+
+    fn close_graph(&mut self, output: &mut Vec<JsonEvent<'_>>) {
+        if let Some(GraphName::NamedNode(_) | GraphName::BlankNode(_)) = self.current_graph.take() {
+            output.push(JsonEvent::EndArray);
+            output.push(JsonEvent::EndObject);
+        }
+    }
+
+    /// Compacts an IRI to a term, a vocabulary relative IRI or a compact IRI if possible.
+    ///
+    /// Terms are only used if they do not change how the values are interpreted.
+    fn compact_iri(&self, iri: &str, vocab: bool) -> String {
+        if vocab {
+            let term = self
+                .context
+                .terms
+                .iter()
+                .filter(|(_, d)| {
+                    d.iri.as_deref() == Some(iri)
+                        && !d.reverse
+                        && d.type_mapping.is_none()
+                        && d.language.is_none()
+                        && d.container.is_plain()
+                        && d.context.is_none()
+                })
+                .map(|(term, _)| term)
+                .min_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+            if let Some(term) = term {
+                return term.clone();
+            }
+            if let Some(local) = self
+                .context
+                .vocab
+                .as_deref()
+                .and_then(|vocab| iri.strip_prefix(vocab))
+            {
+                if !local.is_empty()
+                    && !local.contains(':')
+                    && !local.starts_with('@')
+                    && !self.context.terms.contains_key(local)
+                {
+                    return local.to_owned();
+                }
+            }
+        }
+        self.context
+            .terms
+            .iter()
+            .filter(|(name, d)| d.prefix && !name.contains(':'))
+            .filter_map(|(name, d)| {
+                let local = iri.strip_prefix(d.iri.as_deref()?)?;
+                (!local.is_empty() && !local.starts_with("//")).then(|| format!("{name}:{local}"))
+            })
+            .filter(|compact| !self.context.terms.contains_key(compact))
+            .min_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)))
+            .unwrap_or_else(|| iri.to_owned())
+    }
+
+    fn term_value(&self, term: TermRef<'_>) -> ObjectValue {
+        match term {
+            TermRef::NamedNode(node) => ObjectValue::Id(self.compact_iri(node.as_str(), false)),
+            TermRef::BlankNode(node) => ObjectValue::Id(node.to_string()),
+            TermRef::Literal(literal) => {
+                if let Some(language) = literal.language() {
+                    ObjectValue::LanguageString(literal.value().to_owned(), language.to_owned())
+                } else if literal.datatype() == xsd::STRING {
+                    ObjectValue::String(literal.value().to_owned())
+                } else {
+                    ObjectValue::Typed(
+                        literal.value().to_owned(),
+                        self.compact_iri(literal.datatype().as_str(), true),
+                    )
+                }
+            }
+            #[cfg(feature = "rdf-star")]
+            TermRef::Triple(_) => ObjectValue::Id(String::new()),
+        }
+    }
+}
+
+enum ObjectValue {
+    Id(String),
+    /// A value of `@type`
+    Type(String),
+    String(String),
+    LanguageString(String, String),
+    Typed(String, String),
+}
+
+impl ObjectValue {
+    fn write(self, output: &mut Vec<JsonEvent<'_>>) {
+        match self {
+            Self::Type(id) | Self::String(id) => output.push(JsonEvent::String(id.into())),
+            Self::Id(id) => {
+                output.push(JsonEvent::StartObject);
+                output.push(JsonEvent::ObjectKey("@id".into()));
+                output.push(JsonEvent::String(id.into()));
+                output.push(JsonEvent::EndObject);
+            }
+            Self::LanguageString(value, language) => {
+                output.push(JsonEvent::StartObject);
+                output.push(JsonEvent::ObjectKey("@value".into()));
+                output.push(JsonEvent::String(value.into()));
+                output.push(JsonEvent::ObjectKey("@language".into()));
+                output.push(JsonEvent::String(language.into()));
+                output.push(JsonEvent::EndObject);
+            }
+            Self::Typed(value, datatype) => {
+                output.push(JsonEvent::StartObject);
+                output.push(JsonEvent::ObjectKey("@value".into()));
+                output.push(JsonEvent::String(value.into()));
+                output.push(JsonEvent::ObjectKey("@type".into()));
+                output.push(JsonEvent::String(datatype.into()));
+                output.push(JsonEvent::EndObject);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oxjsonld::JsonLdParser;
+    use crate::oxrdf::{BlankNode, Literal, NamedNode, Quad, Subject, Term};
+
+    fn ex(name: &str) -> NamedNode {
+        NamedNode::new_unchecked(format!("http://example.com/{name}"))
+    }
+
+    fn serialize(serializer: JsonLdSerializer, quads: &[Quad]) -> String {
+        let mut writer = serializer.serialize_to_write(Vec::new());
+        for quad in quads {
+            writer.write_quad(quad).unwrap();
+        }
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    }
+
+    fn parse(file: &str) -> Vec<Quad> {
+        JsonLdParser::new()
+            .parse_read(file.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    fn sorted(quads: &[Quad]) -> Vec<String> {
+        let mut quads = quads.iter().map(Quad::to_string).collect::<Vec<_>>();
+        quads.sort();
+        quads
+    }
+
+    fn quads() -> Vec<Quad> {
+        let g = GraphName::from(ex("g"));
+        vec![
+            Quad::new(
+                ex("alice"),
+                rdf::TYPE,
+                NamedNode::new_unchecked("http://schema.org/Person"),
+                GraphName::DefaultGraph,
+            ),
+            Quad::new(
+                ex("alice"),
+                NamedNode::new_unchecked("http://schema.org/name"),
+                Literal::new_language_tagged_literal_unchecked("Alice", "en"),
+                GraphName::DefaultGraph,
+            ),
+            Quad::new(
+                ex("alice"),
+                ex("age"),
+                Literal::new_typed_literal("42", xsd::INTEGER),
+                GraphName::DefaultGraph,
+            ),
+            Quad::new(ex("alice"), ex("knows"), ex("bob"), GraphName::DefaultGraph),
+            Quad::new(
+                ex("bob"),
+                NamedNode::new_unchecked("http://schema.org/name"),
+                Literal::new_simple_literal("Bob"),
+                GraphName::DefaultGraph,
+            ),
+            // A key already written for the subject, after another key
+            Quad::new(
+                ex("alice"),
+                ex("knows"),
+                ex("carol"),
+                GraphName::DefaultGraph,
+            ),
+            Quad::new(
+                ex("alice"),
+                ex("age"),
+                Literal::from(43),
+                GraphName::DefaultGraph,
+            ),
+            Quad::new(
+                ex("alice"),
+                ex("knows"),
+                ex("dave"),
+                GraphName::DefaultGraph,
+            ),
+            Quad::new(
+                ex("s"),
+                ex("p"),
+                Literal::new_simple_literal("in g"),
+                g.clone(),
+            ),
+            Quad::new(ex("s"), ex("p"), ex("o"), g),
+        ]
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let quads = quads();
+        let file = serialize(JsonLdSerializer::new(), &quads);
+        assert_eq!(sorted(&parse(&file)), sorted(&quads));
+    }
+
+    #[test]
+    fn test_round_trip_with_context() {
+        let quads = quads();
+        let file = serialize(
+            JsonLdSerializer::new()
+                .with_context(r#"{"@context": {"@vocab": "http://schema.org/"}}"#)
+                .unwrap()
+                .with_prefix("ex", "http://example.com/")
+                .unwrap(),
+            &quads,
+        );
+        // The IRIs are compacted with the context.
+        assert!(file.contains(r#""@id":"ex:alice""#));
+        assert!(file.contains(r#""@type":["Person"]"#));
+        assert!(file.contains(r#""name":[{"@value":"Alice","@language":"en"}]"#));
+        assert_eq!(sorted(&parse(&file)), sorted(&quads));
+    }
+
+    #[test]
+    fn test_round_trip_blank_nodes() {
+        let b = BlankNode::default();
+        let quads = vec![
+            Quad::new(ex("s"), ex("p"), b.clone(), GraphName::DefaultGraph),
+            Quad::new(b.clone(), ex("q"), ex("o"), GraphName::DefaultGraph),
+            Quad::new(ex("s"), ex("p"), ex("o"), b.clone()),
+        ];
+        let parsed = parse(&serialize(JsonLdSerializer::new(), &quads));
+        assert_eq!(parsed.len(), 3);
+        let object = parsed
+            .iter()
+            .find(|q| q.subject == Subject::from(ex("s")) && q.graph_name.is_default_graph())
+            .map(|q| q.object.clone())
+            .unwrap();
+        let Term::BlankNode(node) = object else {
+            panic!("expected a blank node, got {object}");
+        };
+        // The same blank node is used as subject and as graph name.
+        assert!(parsed
+            .iter()
+            .any(|q| q.subject == Subject::from(node.clone()) && q.predicate == ex("q")));
+        assert!(parsed
+            .iter()
+            .any(|q| q.graph_name == GraphName::from(node.clone())));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::oxjsonld;
 use crate::oxrdfxml;
 use crate::oxttl;
 use std::io;
@@ -54,6 +55,23 @@ impl From<oxrdfxml::RdfXmlParseError> for RdfParseError {
     }
 }
 
+impl From<oxjsonld::JsonLdSyntaxError> for RdfSyntaxError {
+    #[inline]
+    fn from(error: oxjsonld::JsonLdSyntaxError) -> Self {
+        Self(SyntaxErrorKind::JsonLd(error))
+    }
+}
+
+impl From<oxjsonld::JsonLdParseError> for RdfParseError {
+    #[inline]
+    fn from(error: oxjsonld::JsonLdParseError) -> Self {
+        match error {
+            oxjsonld::JsonLdParseError::Syntax(e) => Self::Syntax(e.into()),
+            oxjsonld::JsonLdParseError::Io(e) => Self::Io(e),
+        }
+    }
+}
+
 impl From<RdfParseError> for io::Error {
     #[inline]
     fn from(error: RdfParseError) -> Self {
@@ -76,6 +94,8 @@ enum SyntaxErrorKind {
     Turtle(#[from] oxttl::TurtleSyntaxError),
     #[error(transparent)]
     RdfXml(#[from] oxrdfxml::RdfXmlSyntaxError),
+    #[error(transparent)]
+    JsonLd(#[from] oxjsonld::JsonLdSyntaxError),
     #[error("{0}")]
     Msg(&'static str),
 }
@@ -99,7 +119,9 @@ impl RdfSyntaxError {
                     },
                 )
             }
-            SyntaxErrorKind::RdfXml(_) | SyntaxErrorKind::Msg(_) => None,
+            SyntaxErrorKind::RdfXml(_) | SyntaxErrorKind::JsonLd(_) | SyntaxErrorKind::Msg(_) => {
+                None
+            }
         }
     }
 }
@@ -110,6 +132,7 @@ impl From<RdfSyntaxError> for io::Error {
         match error.0 {
             SyntaxErrorKind::Turtle(error) => error.into(),
             SyntaxErrorKind::RdfXml(error) => error.into(),
+            SyntaxErrorKind::JsonLd(error) => error.into(),
             SyntaxErrorKind::Msg(msg) => Self::new(io::ErrorKind::InvalidData, msg),
         }
     }
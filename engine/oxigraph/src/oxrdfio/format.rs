@@ -2,10 +2,12 @@ use std::fmt;
 
 /// RDF serialization formats.
 ///
-/// This enumeration is non exhaustive. New formats like JSON-LD might be added in the future.
+/// This enumeration is non exhaustive. New formats might be added in the future.
 #[derive(Eq, PartialEq, Debug, Clone, Copy, Hash)]
 #[non_exhaustive]
 pub enum RdfFormat {
+    /// [JSON-LD](https://www.w3.org/TR/json-ld11/)
+    JsonLd,
     /// [N3](https://w3c.github.io/N3/spec/)
     N3,
     /// [N-Quads](https://www.w3.org/TR/n-quads/)
@@ -34,6 +36,7 @@ impl RdfFormat {
     #[inline]
     pub const fn iri(self) -> &'static str {
         match self {
+            Self::JsonLd => "http://www.w3.org/ns/formats/JSON-LD",
             Self::N3 => "http://www.w3.org/ns/formats/N3",
             Self::NQuads => "http://www.w3.org/ns/formats/N-Quads",
             Self::NTriples => "http://www.w3.org/ns/formats/N-Triples",
@@ -53,6 +56,7 @@ impl RdfFormat {
     #[inline]
     pub const fn media_type(self) -> &'static str {
         match self {
+            Self::JsonLd => "application/ld+json",
             Self::N3 => "text/n3",
             Self::NQuads => "application/n-quads",
             Self::NTriples => "application/n-triples",
@@ -72,6 +76,7 @@ impl RdfFormat {
     #[inline]
     pub const fn file_extension(self) -> &'static str {
         match self {
+            Self::JsonLd => "jsonld",
             Self::N3 => "n3",
             Self::NQuads => "nq",
             Self::NTriples => "nt",
@@ -91,6 +96,7 @@ impl RdfFormat {
     #[inline]
     pub const fn name(self) -> &'static str {
         match self {
+            Self::JsonLd => "JSON-LD",
             Self::N3 => "N3",
             Self::NQuads => "N-Quads",
             Self::NTriples => "N-Triples",
@@ -110,7 +116,7 @@ impl RdfFormat {
     /// ```
     #[inline]
     pub const fn supports_datasets(self) -> bool {
-        matches!(self, Self::NQuads | Self::TriG)
+        matches!(self, Self::JsonLd | Self::NQuads | Self::TriG)
     }
 
     /// Checks if the formats supports [RDF-star quoted triples](https://w3c.github.io/rdf-star/cg-spec/2021-12-17.html#dfn-quoted).
@@ -146,7 +152,8 @@ impl RdfFormat {
     /// ```
     #[inline]
     pub fn from_media_type(media_type: &str) -> Option<Self> {
-        const MEDIA_SUBTYPES: [(&str, RdfFormat); 10] = [
+        const MEDIA_SUBTYPES: [(&str, RdfFormat); 11] = [
+            ("ld+json", RdfFormat::JsonLd),
             ("n-quads", RdfFormat::NQuads),
             ("n-triples", RdfFormat::NTriples),
             ("n3", RdfFormat::N3),
@@ -190,7 +197,8 @@ impl RdfFormat {
     /// ```
     #[inline]
     pub fn from_extension(extension: &str) -> Option<Self> {
-        const MEDIA_TYPES: [(&str, RdfFormat); 8] = [
+        const MEDIA_TYPES: [(&str, RdfFormat); 9] = [
+            ("jsonld", RdfFormat::JsonLd),
             ("n3", RdfFormat::N3),
             ("nq", RdfFormat::NQuads),
             ("nt", RdfFormat::NTriples),
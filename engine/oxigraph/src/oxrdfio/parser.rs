@@ -1,5 +1,8 @@
 //! Utilities to read RDF graphs and datasets.
 
+#[cfg(feature = "async-tokio")]
+use crate::oxjsonld::FromTokioAsyncReadJsonLdReader;
+use crate::oxjsonld::{FromReadJsonLdReader, JsonLdParser};
 use crate::oxrdf::{BlankNode, GraphName, IriParseError, Quad, Subject, Term, Triple};
 pub use crate::oxrdfio::error::RdfParseError;
 use crate::oxrdfio::format::RdfFormat;
@@ -17,6 +20,7 @@ use tokio::io::AsyncRead;
 /// Parsers for RDF serialization formats.
 ///
 /// It currently supports the following formats:
+/// * [JSON-LD](https://www.w3.org/TR/json-ld11/) ([`RdfFormat::JsonLd`])
 /// * [N3](https://w3c.github.io/N3/spec/) ([`RdfFormat::N3`])
 /// * [N-Quads](https://www.w3.org/TR/n-quads/) ([`RdfFormat::NQuads`])
 /// * [N-Triples](https://www.w3.org/TR/n-triples/) ([`RdfFormat::NTriples`])
@@ -53,6 +57,7 @@ pub struct RdfParser {
 }
 
 enum RdfParserKind {
+    JsonLd(JsonLdParser),
     N3(N3Parser),
     NQuads(NQuadsParser),
     NTriples(NTriplesParser),
@@ -67,6 +72,7 @@ impl RdfParser {
     pub fn from_format(format: RdfFormat) -> Self {
         Self {
             inner: match format {
+                RdfFormat::JsonLd => RdfParserKind::JsonLd(JsonLdParser::new()),
                 RdfFormat::N3 => RdfParserKind::N3(N3Parser::new()),
                 RdfFormat::NQuads => RdfParserKind::NQuads({
                     #[cfg(feature = "rdf-star")]
@@ -128,6 +134,7 @@ impl RdfParser {
     /// ```
     pub fn format(&self) -> RdfFormat {
         match &self.inner {
+            RdfParserKind::JsonLd(_) => RdfFormat::JsonLd,
             RdfParserKind::N3(_) => RdfFormat::N3,
             RdfParserKind::NQuads(_) => RdfFormat::NQuads,
             RdfParserKind::NTriples(_) => RdfFormat::NTriples,
@@ -156,6 +163,7 @@ impl RdfParser {
     #[inline]
     pub fn with_base_iri(mut self, base_iri: impl Into<String>) -> Result<Self, IriParseError> {
         self.inner = match self.inner {
+            RdfParserKind::JsonLd(p) => RdfParserKind::JsonLd(p.with_base_iri(base_iri)?),
             RdfParserKind::N3(p) => RdfParserKind::N3(p),
             RdfParserKind::NTriples(p) => RdfParserKind::NTriples(p),
             RdfParserKind::NQuads(p) => RdfParserKind::NQuads(p),
@@ -242,6 +250,7 @@ impl RdfParser {
     #[inline]
     pub fn unchecked(mut self) -> Self {
         self.inner = match self.inner {
+            RdfParserKind::JsonLd(p) => RdfParserKind::JsonLd(p.unchecked()),
             RdfParserKind::N3(p) => RdfParserKind::N3(p.unchecked()),
             RdfParserKind::NTriples(p) => RdfParserKind::NTriples(p.unchecked()),
             RdfParserKind::NQuads(p) => RdfParserKind::NQuads(p.unchecked()),
@@ -273,6 +282,7 @@ impl RdfParser {
     pub fn parse_read<R: Read>(self, reader: R) -> FromReadQuadReader<R> {
         FromReadQuadReader {
             parser: match self.inner {
+                RdfParserKind::JsonLd(p) => FromReadQuadReaderKind::JsonLd(p.parse_read(reader)),
                 RdfParserKind::N3(p) => FromReadQuadReaderKind::N3(p.parse_read(reader)),
                 RdfParserKind::NQuads(p) => FromReadQuadReaderKind::NQuads(p.parse_read(reader)),
                 RdfParserKind::NTriples(p) => {
@@ -316,6 +326,9 @@ impl RdfParser {
     ) -> FromTokioAsyncReadQuadReader<R> {
         FromTokioAsyncReadQuadReader {
             parser: match self.inner {
+                RdfParserKind::JsonLd(p) => {
+                    FromTokioAsyncReadQuadReaderKind::JsonLd(p.parse_tokio_async_read(reader))
+                }
                 RdfParserKind::N3(p) => {
                     FromTokioAsyncReadQuadReaderKind::N3(p.parse_tokio_async_read(reader))
                 }
@@ -375,6 +388,7 @@ pub struct FromReadQuadReader<R: Read> {
 }
 
 enum FromReadQuadReaderKind<R: Read> {
+    JsonLd(FromReadJsonLdReader<R>),
     N3(FromReadN3Reader<R>),
     NQuads(FromReadNQuadsReader<R>),
     NTriples(FromReadNTriplesReader<R>),
@@ -388,6 +402,10 @@ impl<R: Read> Iterator for FromReadQuadReader<R> {
 
     fn next(&mut self) -> Option<Self::Item> {
         Some(match &mut self.parser {
+            FromReadQuadReaderKind::JsonLd(parser) => match parser.next()? {
+                Ok(quad) => self.mapper.map_quad(quad),
+                Err(e) => Err(e.into()),
+            },
             FromReadQuadReaderKind::N3(parser) => match parser.next()? {
                 Ok(quad) => self.mapper.map_n3_quad(quad),
                 Err(e) => Err(e.into()),
@@ -449,7 +467,8 @@ impl<R: Read> FromReadQuadReader<R> {
                 FromReadQuadReaderKind::N3(p) => PrefixesIterKind::N3(p.prefixes()),
                 FromReadQuadReaderKind::TriG(p) => PrefixesIterKind::TriG(p.prefixes()),
                 FromReadQuadReaderKind::Turtle(p) => PrefixesIterKind::Turtle(p.prefixes()),
-                FromReadQuadReaderKind::NQuads(_)
+                FromReadQuadReaderKind::JsonLd(_)
+                | FromReadQuadReaderKind::NQuads(_)
                 | FromReadQuadReaderKind::NTriples(_)
                 | FromReadQuadReaderKind::RdfXml(_) => PrefixesIterKind::None, /* TODO: implement for RDF/XML */
             },
@@ -480,7 +499,8 @@ impl<R: Read> FromReadQuadReader<R> {
             FromReadQuadReaderKind::N3(p) => p.base_iri(),
             FromReadQuadReaderKind::TriG(p) => p.base_iri(),
             FromReadQuadReaderKind::Turtle(p) => p.base_iri(),
-            FromReadQuadReaderKind::NQuads(_)
+            FromReadQuadReaderKind::JsonLd(_)
+            | FromReadQuadReaderKind::NQuads(_)
             | FromReadQuadReaderKind::NTriples(_)
             | FromReadQuadReaderKind::RdfXml(_) => None, // TODO: implement for RDF/XML
         }
@@ -515,6 +535,7 @@ pub struct FromTokioAsyncReadQuadReader<R: AsyncRead + Unpin> {
 
 #[cfg(feature = "async-tokio")]
 enum FromTokioAsyncReadQuadReaderKind<R: AsyncRead + Unpin> {
+    JsonLd(FromTokioAsyncReadJsonLdReader<R>),
     N3(FromTokioAsyncReadN3Reader<R>),
     NQuads(FromTokioAsyncReadNQuadsReader<R>),
     NTriples(FromTokioAsyncReadNTriplesReader<R>),
@@ -527,6 +548,10 @@ enum FromTokioAsyncReadQuadReaderKind<R: AsyncRead + Unpin> {
 impl<R: AsyncRead + Unpin> FromTokioAsyncReadQuadReader<R> {
     pub async fn next(&mut self) -> Option<Result<Quad, RdfParseError>> {
         Some(match &mut self.parser {
+            FromTokioAsyncReadQuadReaderKind::JsonLd(parser) => match parser.next().await? {
+                Ok(quad) => self.mapper.map_quad(quad),
+                Err(e) => Err(e.into()),
+            },
             FromTokioAsyncReadQuadReaderKind::N3(parser) => match parser.next().await? {
                 Ok(quad) => self.mapper.map_n3_quad(quad),
                 Err(e) => Err(e.into()),
@@ -591,7 +616,8 @@ impl<R: AsyncRead + Unpin> FromTokioAsyncReadQuadReader<R> {
                 FromTokioAsyncReadQuadReaderKind::Turtle(p) => {
                     PrefixesIterKind::Turtle(p.prefixes())
                 }
-                FromTokioAsyncReadQuadReaderKind::NQuads(_)
+                FromTokioAsyncReadQuadReaderKind::JsonLd(_)
+                | FromTokioAsyncReadQuadReaderKind::NQuads(_)
                 | FromTokioAsyncReadQuadReaderKind::NTriples(_)
                 | FromTokioAsyncReadQuadReaderKind::RdfXml(_) => PrefixesIterKind::None, /* TODO: implement for RDF/XML */
             },
@@ -626,7 +652,8 @@ impl<R: AsyncRead + Unpin> FromTokioAsyncReadQuadReader<R> {
             FromTokioAsyncReadQuadReaderKind::N3(p) => p.base_iri(),
             FromTokioAsyncReadQuadReaderKind::TriG(p) => p.base_iri(),
             FromTokioAsyncReadQuadReaderKind::Turtle(p) => p.base_iri(),
-            FromTokioAsyncReadQuadReaderKind::NQuads(_)
+            FromTokioAsyncReadQuadReaderKind::JsonLd(_)
+            | FromTokioAsyncReadQuadReaderKind::NQuads(_)
             | FromTokioAsyncReadQuadReaderKind::NTriples(_)
             | FromTokioAsyncReadQuadReaderKind::RdfXml(_) => None, // TODO: implement for RDF/XML
         }
@@ -1,5 +1,8 @@
 //! Utilities to write RDF graphs and datasets.
 
+#[cfg(feature = "async-tokio")]
+use crate::oxjsonld::ToTokioAsyncWriteJsonLdWriter;
+use crate::oxjsonld::{JsonLdSerializer, ToWriteJsonLdWriter};
 use crate::oxrdf::{GraphNameRef, IriParseError, QuadRef, TripleRef};
 use crate::oxrdfio::format::RdfFormat;
 
@@ -26,6 +29,7 @@ use tokio::io::AsyncWrite;
 /// A serializer for RDF serialization formats.
 ///
 /// It currently supports the following formats:
+/// * [JSON-LD](https://www.w3.org/TR/json-ld11/) ([`RdfFormat::JsonLd`])
 /// * [N3](https://w3c.github.io/N3/spec/) ([`RdfFormat::N3`])
 /// * [N-Quads](https://www.w3.org/TR/n-quads/) ([`RdfFormat::NQuads`])
 /// * [canonical](https://www.w3.org/TR/n-triples/#canonical-ntriples) [N-Triples](https://www.w3.org/TR/n-triples/) ([`RdfFormat::NTriples`])
@@ -53,6 +57,7 @@ pub struct RdfSerializer {
 }
 
 enum RdfSerializerKind {
+    JsonLd(JsonLdSerializer),
     NQuads(NQuadsSerializer),
     NTriples(NTriplesSerializer),
     RdfXml(RdfXmlSerializer),
@@ -66,6 +71,7 @@ impl RdfSerializer {
     pub fn from_format(format: RdfFormat) -> Self {
         Self {
             inner: match format {
+                RdfFormat::JsonLd => RdfSerializerKind::JsonLd(JsonLdSerializer::new()),
                 RdfFormat::NQuads => RdfSerializerKind::NQuads(NQuadsSerializer::new()),
                 RdfFormat::NTriples => RdfSerializerKind::NTriples(NTriplesSerializer::new()),
                 RdfFormat::RdfXml => RdfSerializerKind::RdfXml(RdfXmlSerializer::new()),
@@ -89,6 +95,7 @@ impl RdfSerializer {
     /// ```
     pub fn format(&self) -> RdfFormat {
         match &self.inner {
+            RdfSerializerKind::JsonLd(_) => RdfFormat::JsonLd,
             RdfSerializerKind::NQuads(_) => RdfFormat::NQuads,
             RdfSerializerKind::NTriples(_) => RdfFormat::NTriples,
             RdfSerializerKind::RdfXml(_) => RdfFormat::RdfXml,
@@ -125,6 +132,9 @@ impl RdfSerializer {
         prefix_iri: impl Into<String>,
     ) -> Result<Self, IriParseError> {
         self.inner = match self.inner {
+            RdfSerializerKind::JsonLd(s) => {
+                RdfSerializerKind::JsonLd(s.with_prefix(prefix_name, prefix_iri)?)
+            }
             RdfSerializerKind::NQuads(s) => RdfSerializerKind::NQuads(s),
             RdfSerializerKind::NTriples(s) => RdfSerializerKind::NTriples(s),
             RdfSerializerKind::RdfXml(s) => {
@@ -167,6 +177,9 @@ impl RdfSerializer {
     pub fn serialize_to_write<W: Write>(self, write: W) -> ToWriteQuadWriter<W> {
         ToWriteQuadWriter {
             formatter: match self.inner {
+                RdfSerializerKind::JsonLd(s) => {
+                    ToWriteQuadWriterKind::JsonLd(s.serialize_to_write(write))
+                }
                 RdfSerializerKind::NQuads(s) => {
                     ToWriteQuadWriterKind::NQuads(s.serialize_to_write(write))
                 }
@@ -220,6 +233,9 @@ impl RdfSerializer {
     ) -> ToTokioAsyncWriteQuadWriter<W> {
         ToTokioAsyncWriteQuadWriter {
             formatter: match self.inner {
+                RdfSerializerKind::JsonLd(s) => {
+                    ToTokioAsyncWriteQuadWriterKind::JsonLd(s.serialize_to_tokio_async_write(write))
+                }
                 RdfSerializerKind::NQuads(s) => {
                     ToTokioAsyncWriteQuadWriterKind::NQuads(s.serialize_to_tokio_async_write(write))
                 }
@@ -278,6 +294,7 @@ pub struct ToWriteQuadWriter<W: Write> {
 }
 
 enum ToWriteQuadWriterKind<W: Write> {
+    JsonLd(ToWriteJsonLdWriter<W>),
     NQuads(ToWriteNQuadsWriter<W>),
     NTriples(ToWriteNTriplesWriter<W>),
     RdfXml(ToWriteRdfXmlWriter<W>),
@@ -289,6 +306,7 @@ impl<W: Write> ToWriteQuadWriter<W> {
     /// Writes a [`QuadRef`]
     pub fn write_quad<'a>(&mut self, quad: impl Into<QuadRef<'a>>) -> io::Result<()> {
         match &mut self.formatter {
+            ToWriteQuadWriterKind::JsonLd(writer) => writer.write_quad(quad),
             ToWriteQuadWriterKind::NQuads(writer) => writer.write_quad(quad),
             ToWriteQuadWriterKind::NTriples(writer) => writer.write_triple(to_triple(quad)?),
             ToWriteQuadWriterKind::RdfXml(writer) => writer.write_triple(to_triple(quad)?),
@@ -307,6 +325,7 @@ impl<W: Write> ToWriteQuadWriter<W> {
     /// Note that this function does not flush the writer. You need to do that if you are using a [`BufWriter`](io::BufWriter).
     pub fn finish(self) -> io::Result<W> {
         Ok(match self.formatter {
+            ToWriteQuadWriterKind::JsonLd(writer) => writer.finish()?,
             ToWriteQuadWriterKind::NQuads(writer) => writer.finish(),
             ToWriteQuadWriterKind::NTriples(writer) => writer.finish(),
             ToWriteQuadWriterKind::RdfXml(writer) => writer.finish()?,
@@ -353,6 +372,7 @@ pub struct ToTokioAsyncWriteQuadWriter<W: AsyncWrite + Unpin> {
 
 #[cfg(feature = "async-tokio")]
 enum ToTokioAsyncWriteQuadWriterKind<W: AsyncWrite + Unpin> {
+    JsonLd(ToTokioAsyncWriteJsonLdWriter<W>),
     NQuads(ToTokioAsyncWriteNQuadsWriter<W>),
     NTriples(ToTokioAsyncWriteNTriplesWriter<W>),
     RdfXml(ToTokioAsyncWriteRdfXmlWriter<W>),
@@ -365,6 +385,7 @@ impl<W: AsyncWrite + Unpin> ToTokioAsyncWriteQuadWriter<W> {
     /// Writes a [`QuadRef`]
     pub async fn write_quad<'a>(&mut self, quad: impl Into<QuadRef<'a>>) -> io::Result<()> {
         match &mut self.formatter {
+            ToTokioAsyncWriteQuadWriterKind::JsonLd(writer) => writer.write_quad(quad).await,
             ToTokioAsyncWriteQuadWriterKind::NQuads(writer) => writer.write_quad(quad).await,
             ToTokioAsyncWriteQuadWriterKind::NTriples(writer) => {
                 writer.write_triple(to_triple(quad)?).await
@@ -390,6 +411,7 @@ impl<W: AsyncWrite + Unpin> ToTokioAsyncWriteQuadWriter<W> {
     /// Note that this function does not flush the writer. You need to do that if you are using a [`BufWriter`](io::BufWriter).
     pub async fn finish(self) -> io::Result<W> {
         Ok(match self.formatter {
+            ToTokioAsyncWriteQuadWriterKind::JsonLd(writer) => writer.finish().await?,
             ToTokioAsyncWriteQuadWriterKind::NQuads(writer) => writer.finish(),
             ToTokioAsyncWriteQuadWriterKind::NTriples(writer) => writer.finish(),
             ToTokioAsyncWriteQuadWriterKind::RdfXml(writer) => writer.finish().await?,
@@ -687,6 +687,17 @@ impl Commit {
         res
     }
 
+    /// Get nfiles (the IDs of the files that are not referenced anymore after this commit)
+    /// if there is no header, returns an empty vec
+    pub fn nfiles(&self) -> Vec<ObjectId> {
+        match self {
+            Commit::V0(c) => match &c.header {
+                Some(h) => h.nfiles().clone(),
+                None => vec![],
+            },
+        }
+    }
+
     /// Get deps (that have both an ID in the header and a key in the header_keys)
     pub fn deps(&self) -> Vec<ObjectRef> {
         let mut res: Vec<ObjectRef> = vec![];
@@ -1255,6 +1266,11 @@ impl CommitHeader {
             CommitHeader::V0(v0) => &v0.files,
         }
     }
+    pub fn nfiles(&self) -> &Vec<ObjectId> {
+        match self {
+            CommitHeader::V0(v0) => &v0.nfiles,
+        }
+    }
     pub fn acks_and_nacks(&self) -> Vec<ObjectId> {
         match self {
             CommitHeader::V0(v0) => {
@@ -335,6 +335,7 @@ impl From<NgError> for ServerError {
             NgError::OxiGraphError(_) => ServerError::OxiGraphError,
             NgError::InvalidNuri => ServerError::InvalidNuri,
             NgError::InvalidTarget => ServerError::InvalidTarget,
+            NgError::PermissionDenied => ServerError::AccessDenied,
 
             _ => ServerError::OtherError,
         }
@@ -75,6 +75,26 @@ impl Event {
         )?))
     }
 
+    /// the key of the commit is encrypted with a key derived from the given secret instead of the read cap of the branch.
+    /// Used for the SyncSignature commits of a capabilities refresh, that are encrypted with the refresh secret
+    pub fn new_with_secret(
+        publisher: &PrivKey,
+        seq: u64,
+        commit: &Commit,
+        additional_blocks: &Vec<BlockId>,
+        repo: &Repo,
+        branch_secret: &ReadCapSecret,
+    ) -> Result<Event, NgError> {
+        Ok(Event::V0(EventV0::new_with_secret(
+            publisher,
+            seq,
+            commit,
+            additional_blocks,
+            repo,
+            branch_secret,
+        )?))
+    }
+
     pub fn seq_num(&self) -> u64 {
         match self {
             Event::V0(v0) => v0.content.seq,
@@ -204,6 +224,25 @@ impl EventV0 {
         commit: &Commit,
         additional_blocks: &Vec<BlockId>,
         repo: &Repo,
+    ) -> Result<EventV0, NgError> {
+        let branch = repo.branch(commit.branch())?;
+        Self::new_with_secret(
+            publisher,
+            seq,
+            commit,
+            additional_blocks,
+            repo,
+            &branch.read_cap.as_ref().unwrap().key,
+        )
+    }
+
+    pub fn new_with_secret(
+        publisher: &PrivKey,
+        seq: u64,
+        commit: &Commit,
+        additional_blocks: &Vec<BlockId>,
+        repo: &Repo,
+        branch_secret: &ReadCapSecret,
     ) -> Result<EventV0, NgError> {
         let branch_id = commit.branch();
         let repo_id = repo.id;
@@ -215,12 +254,7 @@ impl EventV0 {
             .as_ref()
             .ok_or(NgError::PermissionDenied)?;
         let publisher_pubkey = publisher.to_pub();
-        let key = Self::derive_key(
-            &repo_id,
-            branch_id,
-            &branch.read_cap.as_ref().unwrap().key,
-            &publisher_pubkey,
-        );
+        let key = Self::derive_key(&repo_id, branch_id, branch_secret, &publisher_pubkey);
         let commit_key = commit.key().unwrap();
         let mut encrypted_commit_key = Vec::from(commit_key.slice());
         let mut nonce = seq.to_le_bytes().to_vec();
@@ -10,9 +10,11 @@
 //! SmallFile and RandomAccessFile objects
 
 use core::fmt;
-use std::cmp::min;
+use std::cmp::{max, min};
 use std::collections::HashMap;
 use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread::available_parallelism;
 
 use chacha20::cipher::{KeyIvInit, StreamCipher};
 use chacha20::ChaCha20;
@@ -148,6 +150,59 @@ impl ReadFile for SmallFileV0 {
     }
 }
 
+/// Gear table of the rolling hash used for content defined chunking.
+/// Generated with splitmix64 from a zero seed, so that the cut points never change from one version to another.
+const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut seed: u64 = 0;
+    let mut i = 0;
+    while i < 256 {
+        seed = seed.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = seed;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// Returns the size of the next content defined chunk at the start of `data` (FastCDC with normalized chunking).
+///
+/// The chunk is at most `max_size`, averages `max_size / 4` and is at least `max_size / 16`, unless `data` is shorter.
+fn content_defined_cut(data: &[u8], max_size: usize) -> usize {
+    let avg_size = max_size / 4;
+    let min_size = avg_size / 4;
+    if data.len() <= min_size {
+        return data.len();
+    }
+    let end = min(data.len(), max_size);
+    let bits = max(avg_size, 16).ilog2();
+    // a stricter mask before the average size, and a looser one after, so the sizes stay close to the average
+    let mask_small = u64::MAX << (64 - (bits + 2));
+    let mask_large = u64::MAX << (64 - (bits - 2));
+
+    let mut hash: u64 = 0;
+    let mut i = min_size;
+    while i < min(end, avg_size) {
+        hash = (hash << 1).wrapping_add(GEAR[data[i] as usize]);
+        if hash & mask_small == 0 {
+            return i + 1;
+        }
+        i += 1;
+    }
+    while i < end {
+        hash = (hash << 1).wrapping_add(GEAR[data[i] as usize]);
+        if hash & mask_large == 0 {
+            return i + 1;
+        }
+        i += 1;
+    }
+    end
+}
+
 /// A RandomAccessFile in memory. This is not used to serialize data
 pub struct RandomAccessFile {
     //storage: Arc<&'a dyn BlockStorage>,
@@ -169,6 +224,9 @@ pub struct RandomAccessFile {
 
     content_block: Option<(BlockId, BlockKey)>,
 
+    /// start position of each leaf, only used when the chunking is content defined
+    leaf_offsets: Vec<usize>,
+
     // used for writes
     conv_key: Option<[u8; 32]>,
     remainder: Vec<u8>,
@@ -219,14 +277,14 @@ impl ReadFile for RandomAccessFile {
 
     /// reads at most one block from the file. the returned vector should be tested for size. it might be smaller than what you asked for.
     /// `pos`ition can be anywhere in the file.
-    //TODO: parallelize decryption on multi threads (cores)
+    /// To read many blocks at once, with parallel decryption, use RandomAccessFile::read_range()
     fn read(&self, pos: usize, mut size: usize) -> Result<Vec<u8>, FileError> {
         if size == 0 {
             return Err(FileError::InvalidArgument);
         }
         if self.id.is_some() {
             let total = self.meta.total_size() as usize;
-            if pos > total {
+            if pos >= total {
                 return Err(FileError::EndOfFile);
             }
             size = min(total - pos, size);
@@ -235,21 +293,20 @@ impl ReadFile for RandomAccessFile {
             let depth = self.meta.depth();
             let arity = self.meta.arity();
 
-            let mut level_pos = pos;
+            let (mut leaf_index, level_pos) = self.leaf_at(pos);
             for level in 0..depth {
                 let tree_block = self.store.get(&current_block_id_key.0)?;
                 let (children, content) = tree_block.read(&current_block_id_key.1)?;
                 if children.is_empty() || content.len() > 0 {
                     return Err(FileError::BlockDeserializeError);
                 }
-                let factor = (arity as usize).pow(depth as u32 - level as u32 - 1)
-                    * self.meta.chunk_size() as usize;
-                let level_index = pos / factor;
+                let factor = (arity as usize).pow(depth as u32 - level as u32 - 1);
+                let level_index = leaf_index / factor;
                 if level_index >= children.len() {
                     return Err(FileError::EndOfFile);
                 }
                 current_block_id_key = (children[level_index]).clone();
-                level_pos = pos as usize % factor;
+                leaf_index = leaf_index % factor;
             }
 
             let content_block = self.store.get(&current_block_id_key.0)?;
@@ -270,10 +327,7 @@ impl ReadFile for RandomAccessFile {
             }
         } else {
             // hasn't been saved yet, we can use the self.blocks as a flat array and the remainder too
-            let factor = self.meta.chunk_size() as usize;
-            let index = pos / factor as usize;
-            let level_pos = pos % factor as usize;
-            let remainder_pos = self.blocks.len() * factor;
+            let remainder_pos = self.size;
             if pos >= remainder_pos {
                 let pos_in_remainder = pos - remainder_pos;
                 if self.remainder.len() > 0 && pos_in_remainder < self.remainder.len() {
@@ -283,7 +337,8 @@ impl ReadFile for RandomAccessFile {
                     return Err(FileError::EndOfFile);
                 }
             }
-            //log_debug!("{} {} {} {}", index, self.blocks.len(), factor, level_pos);
+            let (index, level_pos) = self.leaf_at(pos);
+            //log_debug!("{} {} {}", index, self.blocks.len(), level_pos);
             if index >= self.blocks.len() {
                 return Err(FileError::EndOfFile);
             }
@@ -305,6 +360,62 @@ impl ReadFile for RandomAccessFile {
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+type DecryptJob = Box<dyn FnOnce() + Send>;
+
+/// Threads that decrypt the blocks of the files. They are started once, and reused by all the reads.
+#[cfg(not(target_arch = "wasm32"))]
+struct DecryptPool {
+    jobs: std::sync::Mutex<std::sync::mpsc::Sender<DecryptJob>>,
+    threads: usize,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl DecryptPool {
+    fn new() -> Self {
+        let (sender, receiver) = std::sync::mpsc::channel::<DecryptJob>();
+        let receiver = Arc::new(std::sync::Mutex::new(receiver));
+        let wanted = available_parallelism().map(|n| n.get()).unwrap_or(1);
+        let mut threads = 0;
+        if wanted > 1 {
+            for i in 0..wanted {
+                let receiver = Arc::clone(&receiver);
+                let spawned = std::thread::Builder::new()
+                    .name(format!("ng-decrypt-{i}"))
+                    .spawn(move || loop {
+                        let job = receiver.lock().unwrap().recv();
+                        match job {
+                            Ok(job) => {
+                                // a panicking job does not stop the worker
+                                let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(job));
+                            }
+                            Err(_) => break,
+                        }
+                    });
+                if spawned.is_ok() {
+                    threads += 1;
+                }
+            }
+        }
+        DecryptPool {
+            jobs: std::sync::Mutex::new(sender),
+            threads,
+        }
+    }
+
+    fn execute(&self, job: DecryptJob) {
+        if let Err(std::sync::mpsc::SendError(job)) = self.jobs.lock().unwrap().send(job) {
+            // no worker is left
+            job();
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+lazy_static::lazy_static! {
+    static ref DECRYPT_POOL: DecryptPool = DecryptPool::new();
+}
+
 impl RandomAccessFile {
     pub fn meta(&self) -> &RandomAccessFileMeta {
         &self.meta
@@ -318,6 +429,38 @@ impl RandomAccessFile {
         &self.key
     }
 
+    /// Returns the index of the leaf that contains the position `pos`, and the position inside that leaf.
+    fn leaf_at(&self, pos: usize) -> (usize, usize) {
+        if self.meta.is_content_defined() {
+            let index = self.leaf_offsets.partition_point(|o| *o <= pos).max(1) - 1;
+            (index, pos - self.leaf_offsets.get(index).unwrap_or(&0))
+        } else {
+            let chunk_size = self.meta.chunk_size() as usize;
+            (pos / chunk_size, pos % chunk_size)
+        }
+    }
+
+    /// Returns the position in the file of the first byte of a leaf
+    fn leaf_start(&self, index: usize) -> usize {
+        if self.meta.is_content_defined() {
+            self.leaf_offsets[index]
+        } else {
+            index * self.meta.chunk_size() as usize
+        }
+    }
+
+    fn leaf_offsets_from_sizes(sizes: &[u32]) -> Vec<usize> {
+        let mut pos = 0;
+        sizes
+            .iter()
+            .map(|size| {
+                let start = pos;
+                pos += *size as usize;
+                start
+            })
+            .collect()
+    }
+
     fn make_block(
         mut content: Vec<u8>,
         conv_key: &[u8; blake3::OUT_LEN],
@@ -345,8 +488,12 @@ impl RandomAccessFile {
 
         let id = block.get_and_save_id();
         already_existing.insert(key.clone(), id);
-        //log_debug!("putting *** {}", id);
-        store.put(&block)?;
+        // thanks to the convergence key, a block with the same content has the same id.
+        // It can already be in storage, for example when resuming a previously aborted write.
+        if store.has(&id).is_err() {
+            //log_debug!("putting *** {}", id);
+            store.put(&block)?;
+        }
         Ok((id, key))
     }
 
@@ -513,9 +660,10 @@ impl RandomAccessFile {
             id: Some(root_block.0.clone()),
             key: Some(root_block.1.clone()),
             content_block: Some(content_block),
-            conv_key: None,    // not used in this case
-            remainder: vec![], // not used in this case
-            size: 0,           // not used in this case
+            leaf_offsets: vec![], // not used in this case
+            conv_key: None,       // not used in this case
+            remainder: vec![],    // not used in this case
+            size: 0,              // not used in this case
         })
     }
 
@@ -524,12 +672,46 @@ impl RandomAccessFile {
         content_type: String,
         metadata: Vec<u8>,
         store: Arc<Store>,
+    ) -> Self {
+        Self::new_empty_(
+            block_size,
+            content_type,
+            metadata,
+            FileChunking::Fixed,
+            store,
+        )
+    }
+
+    /// Same as new_empty(), but the content is split with content defined chunking, and `block_size` is the maximum size of a leaf.
+    ///
+    /// The successive versions of a file, that are mostly identical, will then share most of their blocks.
+    pub fn new_empty_content_defined(
+        block_size: usize,
+        content_type: String,
+        metadata: Vec<u8>,
+        store: Arc<Store>,
+    ) -> Self {
+        Self::new_empty_(
+            block_size,
+            content_type,
+            metadata,
+            FileChunking::ContentDefined(vec![]),
+            store,
+        )
+    }
+
+    fn new_empty_(
+        block_size: usize,
+        content_type: String,
+        metadata: Vec<u8>,
+        chunking: FileChunking,
+        store: Arc<Store>,
     ) -> Self {
         let valid_block_size = store_valid_value_size(block_size) - BLOCK_EXTRA;
 
         let arity = ((valid_block_size) / CHILD_SIZE) as u16;
 
-        let meta = RandomAccessFileMeta::V0(RandomAccessFileMetaV0 {
+        let mut meta = RandomAccessFileMeta::V0(RandomAccessFileMetaV0 {
             content_type,
             metadata,
             chunk_size: valid_block_size as u32,
@@ -537,6 +719,8 @@ impl RandomAccessFile {
             total_size: 0, // will be filled in later, during save
             depth: 0,      // will be filled in later, during save
         });
+        // the sizes of the leaves are filled in later, during save
+        meta.set_chunking(chunking);
 
         Self {
             store: Arc::clone(&store),
@@ -546,23 +730,262 @@ impl RandomAccessFile {
             id: None,
             key: None,
             content_block: None,
+            leaf_offsets: vec![],
             conv_key: Some(Object::convergence_key(&store)),
             remainder: vec![],
             size: 0,
         }
     }
 
+    /// Decrypts the blocks, in parallel on native targets, with the threads of the decryption pool.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn decrypt_blocks(
+        blocks: Vec<(Block, BlockKey)>,
+    ) -> Vec<Result<(Vec<(BlockId, BlockKey)>, Vec<u8>), ObjectParseError>> {
+        let threads = DECRYPT_POOL.threads;
+        if threads < 2 || blocks.len() < 2 {
+            return blocks.iter().map(|(block, key)| block.read(key)).collect();
+        }
+        let per_thread = (blocks.len() + threads - 1) / threads;
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut chunk_lens = vec![];
+        let mut blocks = blocks.into_iter();
+        loop {
+            let chunk: Vec<(Block, BlockKey)> = blocks.by_ref().take(per_thread).collect();
+            if chunk.is_empty() {
+                break;
+            }
+            let index = chunk_lens.len();
+            chunk_lens.push(chunk.len());
+            let sender = sender.clone();
+            DECRYPT_POOL.execute(Box::new(move || {
+                let res = chunk
+                    .iter()
+                    .map(|(block, key)| block.read(key))
+                    .collect::<Vec<_>>();
+                let _ = sender.send((index, res));
+            }));
+        }
+        drop(sender);
+
+        let mut results: Vec<Option<Vec<_>>> = chunk_lens.iter().map(|_| None).collect();
+        for (index, res) in receiver.iter() {
+            results[index] = Some(res);
+        }
+        results
+            .into_iter()
+            .zip(chunk_lens)
+            .flat_map(|(res, len)| {
+                // a chunk whose job panicked has no result
+                res.unwrap_or_else(|| {
+                    (0..len)
+                        .map(|_| Err(ObjectParseError::BlockDeserializeError))
+                        .collect()
+                })
+            })
+            .collect()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn decrypt_blocks(
+        blocks: Vec<(Block, BlockKey)>,
+    ) -> Vec<Result<(Vec<(BlockId, BlockKey)>, Vec<u8>), ObjectParseError>> {
+        blocks.iter().map(|(block, key)| block.read(key)).collect()
+    }
+
+    /// Reads the range `pos..pos+size` of a file that was saved or opened, across as many blocks as needed.
+    ///
+    /// The tree is walked level by level, and the sibling nodes of each level (and then the leaves) are decrypted in parallel.
+    /// The returned vector is smaller than `size` only if the end of the file was reached.
+    pub fn read_range(&self, pos: usize, size: usize) -> Result<Vec<u8>, FileError> {
+        if size == 0 {
+            return Err(FileError::InvalidArgument);
+        }
+        let content_block = self.content_block.to_owned().ok_or(FileError::NotFound)?;
+        let total = self.meta.total_size() as usize;
+        if pos >= total {
+            return Err(FileError::EndOfFile);
+        }
+        let end = min(total, pos + size);
+        let first_leaf = self.leaf_at(pos).0;
+        let last_leaf = self.leaf_at(end - 1).0;
+
+        let depth = self.meta.depth();
+        let arity = self.meta.arity() as usize;
+
+        // the nodes of the current level that cover the range, with the index of the first leaf they cover
+        let mut nodes: Vec<(usize, (BlockId, BlockKey))> = vec![(0, content_block)];
+        for level in 0..depth {
+            let factor = arity.pow(depth as u32 - level as u32 - 1);
+            let blocks = nodes
+                .iter()
+                .map(|n| Ok((self.store.get(&n.1 .0)?, n.1 .1.clone())))
+                .collect::<Result<Vec<_>, StorageError>>()?;
+            let mut next_level = vec![];
+            for ((first, _), decrypted) in nodes.iter().zip(Self::decrypt_blocks(blocks)) {
+                let (children, content) = decrypted?;
+                if children.is_empty() || content.len() > 0 {
+                    return Err(FileError::BlockDeserializeError);
+                }
+                for (i, child) in children.into_iter().enumerate() {
+                    let child_first = first + i * factor;
+                    if child_first <= last_leaf && child_first + factor > first_leaf {
+                        next_level.push((child_first, child));
+                    }
+                }
+            }
+            nodes = next_level;
+        }
+
+        let blocks = nodes
+            .iter()
+            .map(|n| Ok((self.store.get(&n.1 .0)?, n.1 .1.clone())))
+            .collect::<Result<Vec<_>, StorageError>>()?;
+        let mut res = Vec::with_capacity(end - pos);
+        for ((first, _), decrypted) in nodes.iter().zip(Self::decrypt_blocks(blocks)) {
+            let (children, content) = decrypted?;
+            if !children.is_empty() || content.is_empty() {
+                return Err(FileError::BlockDeserializeError);
+            }
+            let leaf_start = self.leaf_start(*first);
+            let from = min(content.len(), pos.saturating_sub(leaf_start));
+            let to = min(content.len(), end - leaf_start);
+            res.extend_from_slice(&content[from..to]);
+        }
+        Ok(res)
+    }
+
+    /// Returns the IDs of the blocks of the tree that are needed to read the range `pos..pos+size`, and that are missing in storage.
+    ///
+    /// The file must have been opened or saved. As the children of a node are only known once the node is in storage,
+    /// the missing blocks are returned level by level: call it again once the returned blocks have been put in storage,
+    /// until it returns an empty vec.
+    pub fn missing_blocks_in_range(
+        &self,
+        pos: usize,
+        size: usize,
+    ) -> Result<Vec<BlockId>, FileError> {
+        let content_block = self.content_block.to_owned().ok_or(FileError::NotFound)?;
+        let total = self.meta.total_size() as usize;
+        if size == 0 || pos >= total {
+            return Ok(vec![]);
+        }
+        let first_leaf = self.leaf_at(pos).0;
+        let last_leaf = self.leaf_at(min(total, pos + size) - 1).0;
+
+        let depth = self.meta.depth();
+        let arity = self.meta.arity() as usize;
+
+        // the nodes of the current level that cover the range, with the index of the first leaf they cover
+        let mut nodes: Vec<(usize, (BlockId, BlockKey))> = vec![(0, content_block)];
+        for level in 0..depth {
+            let missing: Vec<BlockId> = nodes
+                .iter()
+                .filter(|n| self.store.has(&n.1 .0).is_err())
+                .map(|n| n.1 .0)
+                .collect();
+            if !missing.is_empty() {
+                return Ok(missing);
+            }
+            let factor = arity.pow(depth as u32 - level as u32 - 1);
+            let mut next_level = vec![];
+            for (first, node) in nodes {
+                let tree_block = self.store.get(&node.0)?;
+                let (children, content) = tree_block.read(&node.1)?;
+                if children.is_empty() || content.len() > 0 {
+                    return Err(FileError::BlockDeserializeError);
+                }
+                for (i, child) in children.into_iter().enumerate() {
+                    let child_first = first + i * factor;
+                    if child_first <= last_leaf && child_first + factor > first_leaf {
+                        next_level.push((child_first, child));
+                    }
+                }
+            }
+            nodes = next_level;
+        }
+        Ok(nodes
+            .iter()
+            .filter(|n| self.store.has(&n.1 .0).is_err())
+            .map(|n| n.1 .0)
+            .collect())
+    }
+
+    /// Resumes the write of a file that was created with new_empty() and interrupted before save().
+    ///
+    /// `blocks` are the leaves that were returned by written_blocks() before the interruption.
+    /// The ones that are not in storage anymore are dropped, together with all the ones that follow.
+    /// The write must continue at the position given by written_size().
+    pub fn resume(
+        meta: RandomAccessFileMeta,
+        mut blocks: Vec<(BlockId, BlockKey)>,
+        store: Arc<Store>,
+    ) -> Self {
+        if let Some(missing) = blocks.iter().position(|b| store.has(&b.0).is_err()) {
+            blocks.truncate(missing);
+        }
+        let mut leaf_offsets = vec![];
+        let size = if meta.is_content_defined() {
+            // the leaves have different sizes, that we find by reading them
+            let mut size = 0;
+            for (id, key) in blocks.iter() {
+                match store.get(id).map(|block| block.read(key)) {
+                    Ok(Ok((children, content))) if children.is_empty() && content.len() > 0 => {
+                        leaf_offsets.push(size);
+                        size += content.len();
+                    }
+                    _ => break,
+                }
+            }
+            blocks.truncate(leaf_offsets.len());
+            size
+        } else {
+            blocks.len() * meta.chunk_size() as usize
+        };
+        Self {
+            conv_key: Some(Object::convergence_key(&store)),
+            store,
+            meta,
+            block_contents: HashMap::new(),
+            blocks,
+            id: None,
+            key: None,
+            content_block: None,
+            leaf_offsets,
+            remainder: vec![],
+            size,
+        }
+    }
+
+    /// Drops the data written after the last complete chunk, so that the write continues at written_size(),
+    /// as after resume(). Used to resume an upload that is still in memory.
+    pub fn discard_remainder(&mut self) {
+        self.remainder.clear();
+    }
+
+    /// The leaves that have been written so far by write(), not including the remainder. Empty once the file is saved.
+    pub fn written_blocks(&self) -> &Vec<(BlockId, BlockKey)> {
+        &self.blocks
+    }
+
+    /// The size of the content that has been written so far in complete chunks, not including the remainder.
+    pub fn written_size(&self) -> usize {
+        self.size
+    }
+
     /// Appends some data at the end of the file currently created with new_empty() and not saved yet.
     /// you can call it many times. Don't forget to eventually call save()
     pub fn write(&mut self, data: &[u8]) -> Result<(), FileError> {
         if self.id.is_some() {
             return Err(FileError::AlreadySaved);
         }
+        if self.meta.is_content_defined() {
+            return self.write_content_defined(data);
+        }
         let remainder = self.remainder.len();
         let chunk_size = self.meta.chunk_size() as usize;
         let mut pos: usize = 0;
         let conv_key = self.conv_key.unwrap();
-        // TODO: provide an option to search in storage for already existing, when doing a resume of previously aborted write
         let mut already_existing: HashMap<BlockKey, BlockId> = HashMap::new();
 
         if remainder > 0 {
@@ -614,12 +1037,76 @@ impl RandomAccessFile {
         Ok(())
     }
 
+    /// Adds a leaf of a file with content defined chunking
+    fn add_leaf(
+        &mut self,
+        chunk: Vec<u8>,
+        already_existing: &mut HashMap<BlockKey, BlockId>,
+    ) -> Result<(), FileError> {
+        self.leaf_offsets.push(self.size);
+        self.size += chunk.len();
+        let data_chunk = ChunkContentV0::DataChunk(chunk);
+        let content_ser = serde_bare::to_vec(&data_chunk).unwrap();
+        self.blocks.push(Self::make_block(
+            content_ser,
+            self.conv_key.as_ref().unwrap(),
+            vec![],
+            already_existing,
+            &self.store,
+        )?);
+        Ok(())
+    }
+
+    /// The cut points only depend on the content, so a leaf is made only once we have `chunk_size` bytes ahead of it,
+    /// or when the file is saved.
+    fn write_content_defined(&mut self, data: &[u8]) -> Result<(), FileError> {
+        let chunk_size = self.meta.chunk_size() as usize;
+        let mut already_existing: HashMap<BlockKey, BlockId> = HashMap::new();
+        let mut remainder = std::mem::take(&mut self.remainder);
+        remainder.extend_from_slice(data);
+        let mut start = 0;
+        while remainder.len() - start >= chunk_size {
+            let cut = content_defined_cut(&remainder[start..], chunk_size);
+            self.add_leaf(
+                remainder[start..start + cut].to_vec(),
+                &mut already_existing,
+            )?;
+            start += cut;
+        }
+        remainder.drain(..start);
+        self.remainder = remainder;
+        Ok(())
+    }
+
     pub fn save(&mut self) -> Result<ObjectId, FileError> {
         if self.id.is_some() {
             return Err(FileError::AlreadySaved);
         }
-        // save the remainder, if any.
-        if self.remainder.len() > 0 {
+        if self.meta.is_content_defined() {
+            // the remainder is cut in as many leaves as needed
+            let chunk_size = self.meta.chunk_size() as usize;
+            let mut already_existing: HashMap<BlockKey, BlockId> = HashMap::new();
+            let remainder = std::mem::take(&mut self.remainder);
+            let mut start = 0;
+            while start < remainder.len() {
+                let cut = content_defined_cut(&remainder[start..], chunk_size);
+                self.add_leaf(
+                    remainder[start..start + cut].to_vec(),
+                    &mut already_existing,
+                )?;
+                start += cut;
+            }
+            let mut sizes: Vec<u32> = self
+                .leaf_offsets
+                .windows(2)
+                .map(|w| (w[1] - w[0]) as u32)
+                .collect();
+            if let Some(last) = self.leaf_offsets.last() {
+                sizes.push((self.size - last) as u32);
+            }
+            self.meta.set_chunking(FileChunking::ContentDefined(sizes));
+        } else if self.remainder.len() > 0 {
+            // save the remainder, if any.
             self.size += self.remainder.len();
             //log_debug!("size += remainder {} {}", self.size, self.remainder.len());
             let mut remainder = Vec::with_capacity(self.remainder.len());
@@ -698,6 +1185,10 @@ impl RandomAccessFile {
             ObjectContentV0::RandomAccessFileMeta(meta) => meta,
             _ => return Err(FileError::InvalidChildren),
         };
+        let leaf_offsets = match meta.chunking() {
+            FileChunking::ContentDefined(sizes) => Self::leaf_offsets_from_sizes(sizes),
+            FileChunking::Fixed => vec![],
+        };
 
         Ok(RandomAccessFile {
             store,
@@ -707,6 +1198,7 @@ impl RandomAccessFile {
             id: Some(id),
             key: Some(key),
             content_block: Some(root_sub_blocks[1].clone()),
+            leaf_offsets,
             conv_key: None,
             remainder: vec![],
             size: 0,
@@ -772,6 +1264,15 @@ impl fmt::Display for RandomAccessFile {
         writeln!(f, "== depth:        {}", self.meta.depth())?;
         writeln!(f, "== arity:        {}", self.meta.arity())?;
         writeln!(f, "== chunk_size:   {}", self.meta.chunk_size())?;
+        writeln!(
+            f,
+            "== chunking:     {}",
+            if self.meta.is_content_defined() {
+                "content defined"
+            } else {
+                "fixed"
+            }
+        )?;
         writeln!(f, "== total_size:   {}", self.meta.total_size())?;
         writeln!(f, "== content_type: {}", self.meta.content_type())?;
         writeln!(f, "== metadata len: {}", self.meta.metadata().len())?;
@@ -1249,6 +1750,385 @@ mod test {
         assert_eq!(file.read(29454, 0), Err(FileError::InvalidArgument));
     }
 
+    /// Test resuming an interrupted write to a file
+    #[test]
+    pub fn test_resume_write() {
+        let f = std::fs::File::open("tests/test.jpg").expect("open of tests/test.jpg");
+        let mut reader = BufReader::new(f);
+        let mut img_buffer: Vec<u8> = Vec::new();
+        reader
+            .read_to_end(&mut img_buffer)
+            .expect("read of test.jpg");
+
+        let store = Store::dummy_public_v0();
+
+        let mut file: RandomAccessFile = RandomAccessFile::new_empty(
+            store_valid_value_size(0),
+            "image/jpeg".to_string(),
+            vec![],
+            Arc::clone(&store),
+        );
+        for chunk in img_buffer[0..15000].chunks(1000) {
+            file.write(chunk).expect("write a chunk");
+        }
+        let meta = file.meta().clone();
+        let blocks = file.written_blocks().clone();
+        let chunk_size = meta.chunk_size() as usize;
+        assert_eq!(file.written_size(), blocks.len() * chunk_size);
+        // the remainder is lost
+        drop(file);
+
+        let mut resumed = RandomAccessFile::resume(meta, blocks, Arc::clone(&store));
+        let pos = resumed.written_size();
+        assert_eq!(pos, 15000 / chunk_size * chunk_size);
+        for chunk in img_buffer[pos..].chunks(1000) {
+            resumed.write(chunk).expect("write a chunk");
+        }
+        resumed.save().expect("save");
+        let stored_blocks = store.len().expect("len");
+
+        let mut file: RandomAccessFile = RandomAccessFile::new_empty(
+            store_valid_value_size(0),
+            "image/jpeg".to_string(),
+            vec![],
+            Arc::clone(&store),
+        );
+        file.write(&img_buffer).expect("write all");
+        file.save().expect("save");
+
+        // same content gives the same file, and the blocks were not stored twice
+        assert_eq!(resumed.reference(), file.reference());
+        assert_eq!(stored_blocks, store.len().expect("len"));
+        assert_eq!(
+            resumed.read(0, img_buffer.len()).expect("read all"),
+            img_buffer[0..chunk_size].to_vec()
+        );
+    }
+
+    /// Test that two versions of a file with content defined chunking share most of their blocks
+    #[test]
+    pub fn test_content_defined_chunking() {
+        // pseudo random content, so that the rolling hash finds cut points
+        let mut state: u64 = 42;
+        let content: Vec<u8> = (0..300000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        let mut new_version = content.clone();
+        new_version.insert(100, 7);
+
+        let store = Store::dummy_public_v0();
+
+        let mut file = RandomAccessFile::new_empty_content_defined(
+            store_valid_value_size(0),
+            "application/octet-stream".to_string(),
+            vec![],
+            Arc::clone(&store),
+        );
+        for chunk in content.chunks(5000) {
+            file.write(chunk).expect("write a chunk");
+        }
+        file.save().expect("save");
+        let leaves = match file.meta().chunking() {
+            FileChunking::ContentDefined(sizes) => sizes.len(),
+            FileChunking::Fixed => panic!("chunking should be content defined"),
+        };
+        assert!(leaves > content.len() / file.meta().chunk_size() as usize);
+        let stored_blocks = store.len().expect("len");
+
+        let mut file2 = RandomAccessFile::new_empty_content_defined(
+            store_valid_value_size(0),
+            "application/octet-stream".to_string(),
+            vec![],
+            Arc::clone(&store),
+        );
+        file2.write(&new_version).expect("write all");
+        file2.save().expect("save");
+
+        // only the leaf with the insertion, the tree above it, and the meta are new
+        let new_blocks = store.len().expect("len") - stored_blocks;
+        log_debug!("leaves {} new blocks {}", leaves, new_blocks);
+        assert!(new_blocks < leaves / 4);
+
+        let file2 = RandomAccessFile::open(
+            file2.id().unwrap(),
+            file2.key().clone().unwrap(),
+            Arc::clone(&store),
+        )
+        .expect("open");
+        assert_eq!(
+            file2.read_range(0, new_version.len()).expect("read all"),
+            new_version
+        );
+        assert_eq!(
+            file2.read_range(150000, 20000).expect("read range"),
+            new_version[150000..170000].to_vec()
+        );
+        let (index, pos) = file2.leaf_at(150000);
+        let res = file2.read(150000, 20000).expect("read");
+        assert_eq!(res, new_version[150000..150000 + res.len()].to_vec());
+        assert_eq!(
+            pos + res.len(),
+            file2.leaf_offsets[index + 1] - file2.leaf_offsets[index]
+        );
+
+        // with fixed chunking, all the leaves would have changed
+        let mut file3 = RandomAccessFile::new_empty(
+            store_valid_value_size(0),
+            "application/octet-stream".to_string(),
+            vec![],
+            Arc::clone(&store),
+        );
+        file3.write(&content).expect("write all");
+        file3.save().expect("save");
+        let stored_blocks = store.len().expect("len");
+        let mut file4 = RandomAccessFile::new_empty(
+            store_valid_value_size(0),
+            "application/octet-stream".to_string(),
+            vec![],
+            Arc::clone(&store),
+        );
+        file4.write(&new_version).expect("write all");
+        file4.save().expect("save");
+        assert!(
+            store.len().expect("len") - stored_blocks
+                >= content.len() / file4.meta().chunk_size() as usize
+        );
+    }
+
+    /// Test that a file with the V0 meta (saved before content defined chunking existed) can still be opened and read
+    #[test]
+    pub fn test_open_v0_meta() {
+        // the layout of the V0 meta did not change
+        let old_meta = serde_bare::to_vec(&(
+            0u8,
+            "text/plain".to_string(),
+            vec![1u8, 2, 3],
+            20000u64,
+            4084u32,
+            61u16,
+            1u8,
+        ))
+        .unwrap();
+        let meta: RandomAccessFileMeta = serde_bare::from_slice(&old_meta).expect("deserialize");
+        assert_eq!(
+            meta,
+            RandomAccessFileMeta::V0(RandomAccessFileMetaV0 {
+                content_type: "text/plain".to_string(),
+                metadata: vec![1, 2, 3],
+                total_size: 20000,
+                chunk_size: 4084,
+                arity: 61,
+                depth: 1,
+            })
+        );
+        assert_eq!(meta.chunking(), &FileChunking::Fixed);
+
+        // files with fixed chunking are still saved with the V0 meta
+        let content: Vec<u8> = (0..20000).map(|i| i as u8).collect();
+        let store = Store::dummy_public_v0();
+        let mut file = RandomAccessFile::new_empty(
+            store_valid_value_size(0),
+            "text/plain".to_string(),
+            vec![],
+            Arc::clone(&store),
+        );
+        file.write(&content).expect("write all");
+        file.save().expect("save");
+        assert!(matches!(file.meta(), RandomAccessFileMeta::V0(_)));
+
+        let file = RandomAccessFile::open(
+            file.id().unwrap(),
+            file.key().clone().unwrap(),
+            Arc::clone(&store),
+        )
+        .expect("open");
+        assert!(matches!(file.meta(), RandomAccessFileMeta::V0(_)));
+        assert!(!file.meta().is_content_defined());
+        assert_eq!(
+            file.read_range(0, content.len()).expect("read all"),
+            content
+        );
+        assert_eq!(
+            file.read_range(5000, 10000).expect("read range"),
+            content[5000..15000].to_vec()
+        );
+
+        // and the files with content defined chunking with the V1 meta
+        let mut file = RandomAccessFile::new_empty_content_defined(
+            store_valid_value_size(0),
+            "text/plain".to_string(),
+            vec![],
+            Arc::clone(&store),
+        );
+        file.write(&content).expect("write all");
+        file.save().expect("save");
+        assert!(matches!(file.meta(), RandomAccessFileMeta::V1(_)));
+    }
+
+    /// Test resuming a write with content defined chunking
+    #[test]
+    pub fn test_resume_write_content_defined() {
+        let f = std::fs::File::open("tests/test.jpg").expect("open of tests/test.jpg");
+        let mut reader = BufReader::new(f);
+        let mut img_buffer: Vec<u8> = Vec::new();
+        reader
+            .read_to_end(&mut img_buffer)
+            .expect("read of test.jpg");
+
+        let store = Store::dummy_public_v0();
+
+        let mut file = RandomAccessFile::new_empty_content_defined(
+            store_valid_value_size(0),
+            "image/jpeg".to_string(),
+            vec![],
+            Arc::clone(&store),
+        );
+        for chunk in img_buffer[0..20000].chunks(1000) {
+            file.write(chunk).expect("write a chunk");
+        }
+        let meta = file.meta().clone();
+        let blocks = file.written_blocks().clone();
+        let written = file.written_size();
+        assert!(blocks.len() > 0);
+        drop(file);
+
+        let mut resumed = RandomAccessFile::resume(meta, blocks, Arc::clone(&store));
+        let pos = resumed.written_size();
+        assert_eq!(pos, written);
+        resumed.write(&img_buffer[pos..]).expect("write the rest");
+        resumed.save().expect("save");
+
+        let mut file = RandomAccessFile::new_empty_content_defined(
+            store_valid_value_size(0),
+            "image/jpeg".to_string(),
+            vec![],
+            Arc::clone(&store),
+        );
+        file.write(&img_buffer).expect("write all");
+        file.save().expect("save");
+
+        assert_eq!(resumed.reference(), file.reference());
+        assert_eq!(
+            resumed.read_range(0, img_buffer.len()).expect("read all"),
+            img_buffer
+        );
+    }
+
+    /// Test reading ranges of a file across several blocks
+    #[test]
+    pub fn test_read_range() {
+        let f = std::fs::File::open("tests/test.jpg").expect("open of tests/test.jpg");
+        let mut reader = BufReader::new(f);
+        let mut img_buffer: Vec<u8> = Vec::new();
+        reader
+            .read_to_end(&mut img_buffer)
+            .expect("read of test.jpg");
+
+        let store = Store::dummy_public_v0();
+
+        let file: RandomAccessFile = RandomAccessFile::new_from_slice(
+            &img_buffer,
+            store_valid_value_size(0),
+            "image/jpeg".to_string(),
+            vec![],
+            Arc::clone(&store),
+        )
+        .expect("new_from_slice");
+        assert_eq!(file.meta.depth(), 1);
+
+        let file = RandomAccessFile::open(
+            file.id().unwrap(),
+            file.key().clone().unwrap(),
+            Arc::clone(&store),
+        )
+        .expect("open");
+
+        assert_eq!(
+            file.read_range(0, img_buffer.len()).expect("read all"),
+            img_buffer
+        );
+        assert_eq!(
+            file.read_range(5000, 15000).expect("read range"),
+            img_buffer[5000..20000].to_vec()
+        );
+        // asking too much, receiving just enough
+        assert_eq!(
+            file.read_range(29000, 1000).expect("read end"),
+            img_buffer[29000..].to_vec()
+        );
+        assert_eq!(
+            file.read_range(img_buffer.len(), 1),
+            Err(FileError::EndOfFile)
+        );
+        assert_eq!(file.read_range(0, 0), Err(FileError::InvalidArgument));
+    }
+
+    /// Test finding the missing blocks of a range of a file
+    #[test]
+    pub fn test_missing_blocks_in_range() {
+        let f = std::fs::File::open("tests/test.jpg").expect("open of tests/test.jpg");
+        let mut reader = BufReader::new(f);
+        let mut img_buffer: Vec<u8> = Vec::new();
+        reader
+            .read_to_end(&mut img_buffer)
+            .expect("read of test.jpg");
+
+        let store = Store::dummy_public_v0();
+
+        let file: RandomAccessFile = RandomAccessFile::new_from_slice(
+            &img_buffer,
+            store_valid_value_size(0),
+            "image/jpeg".to_string(),
+            vec![],
+            Arc::clone(&store),
+        )
+        .expect("new_from_slice");
+        assert_eq!(file.meta.depth(), 1);
+        let chunk_size = file.meta.chunk_size() as usize;
+
+        assert_eq!(
+            file.missing_blocks_in_range(0, img_buffer.len())
+                .expect("missing"),
+            vec![]
+        );
+
+        let content_block = file.content_block.clone().unwrap();
+        let (leaves, _) = store
+            .get(&content_block.0)
+            .expect("get content block")
+            .read(&content_block.1)
+            .expect("read content block");
+        store.del(&leaves[2].0).expect("del leaf");
+
+        assert_eq!(
+            file.missing_blocks_in_range(0, chunk_size)
+                .expect("missing"),
+            vec![]
+        );
+        assert_eq!(
+            file.missing_blocks_in_range(2 * chunk_size + 10, 10)
+                .expect("missing"),
+            vec![leaves[2].0]
+        );
+        assert_eq!(
+            file.missing_blocks_in_range(0, img_buffer.len())
+                .expect("missing"),
+            vec![leaves[2].0]
+        );
+
+        store.del(&content_block.0).expect("del content block");
+        assert_eq!(
+            file.missing_blocks_in_range(0, chunk_size)
+                .expect("missing"),
+            vec![content_block.0]
+        );
+    }
+
     /// Test async write to a file all at once
     #[test]
     pub fn test_write_all_at_once_small_blocks() {
@@ -54,7 +54,7 @@ impl Repository {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserInfo {
     /// list of permissions granted to user, with optional metadata
     pub permissions: HashMap<PermissionV0, Vec<u8>>,
@@ -85,6 +85,14 @@ impl UserInfo {
     pub fn has_perm(&self, perm: &PermissionV0) -> Result<&Vec<u8>, NgError> {
         self.permissions.get(perm).ok_or(NgError::PermissionDenied)
     }
+    /// owners and members with a write permission
+    pub fn can_edit(&self) -> bool {
+        self.has_any_perm(&HashSet::from([
+            PermissionV0::WriteAsync,
+            PermissionV0::WriteSync,
+        ]))
+        .is_ok()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -110,6 +118,21 @@ pub struct BranchInfo {
     pub commits_nbr: u64,
 }
 
+/// The commits of a refresh of the capabilities of a repo, prepared by Store::refresh_repo_caps
+pub struct RepoCapRefresh {
+    /// the new RepoWriteCapSecret
+    pub write_cap: RepoWriteCapSecret,
+
+    pub refresh_secret: RefreshSecretV0,
+
+    /// events to send in the old topics, in order. The ones flagged with true
+    /// have their commit key encrypted with the read secret of refresh_secret
+    pub events: Vec<(Commit, Vec<Digest>, bool)>,
+
+    /// the CapRefreshed singletons, to send in the new topics once the events have been verified
+    pub refreshed: Vec<Commit>,
+}
+
 /// In memory Repository representation. With helper functions that access the underlying UserStorage and keeps proxy of the values
 #[derive(Debug)]
 pub struct Repo {
@@ -581,6 +604,62 @@ impl Repo {
         }
     }
 
+    /// adds a member to the repo, or gives additional permissions to an existing member.
+    /// Without permissions, the member is a reader.
+    pub fn add_member(&mut self, member: &UserId, perms: &[PermissionV0]) {
+        let overlay = self.store.get_store_repo().overlay_id_for_read_purpose();
+        let member_hash = CommitContent::author_digest(member, overlay);
+        let info = self.members.entry(member_hash).or_insert_with(|| UserInfo {
+            id: *member,
+            permissions: HashMap::new(),
+        });
+        for perm in perms {
+            info.permissions.entry(*perm).or_insert_with(Vec::new);
+        }
+    }
+
+    /// removes a member from the repo. Returns its info if it was a member.
+    pub fn remove_member(&mut self, member: &UserId) -> Option<UserInfo> {
+        let overlay = self.store.get_store_repo().overlay_id_for_read_purpose();
+        let member_hash = CommitContent::author_digest(member, overlay);
+        self.members.remove(&member_hash)
+    }
+
+    /// sets the Owner permission on the owners of the RootBranch (and removes it from the previous owners)
+    pub fn set_owners(&mut self, owners: &[UserId]) {
+        for info in self.members.values_mut() {
+            if !owners.contains(&info.id) {
+                info.permissions.remove(&PermissionV0::Owner);
+            }
+        }
+        for owner in owners {
+            self.add_member(owner, &[PermissionV0::Owner]);
+        }
+    }
+
+    /// sets the owners from the latest RootBranch commit. Used for the repos saved before their members were tracked
+    pub fn load_owners(&mut self) -> Result<(), NgError> {
+        let read_cap = self.read_cap.clone().ok_or(NgError::RepoNotFound)?;
+        let owners = match Commit::load(read_cap, &self.store, true)?
+            .body()
+            .ok_or(NgError::CommitNotFound)?
+        {
+            CommitBody::V0(CommitBodyV0::RootBranch(RootBranch::V0(v0)))
+            | CommitBody::V0(CommitBodyV0::UpdateRootBranch(RootBranch::V0(v0))) => {
+                v0.owners.clone()
+            }
+            _ => return Err(NgError::InvalidValue),
+        };
+        self.set_owners(&owners);
+        Ok(())
+    }
+
+    pub fn is_owner(&self, user: &UserId) -> bool {
+        self.members
+            .values()
+            .any(|info| info.id == *user && info.has_perm(&PermissionV0::Owner).is_ok())
+    }
+
     pub fn verify_permission(&self, commit: &Commit) -> Result<(), NgError> {
         let content_author = commit.content_v0().author;
         let body = commit.load_body(&self.store)?;
@@ -15,14 +15,14 @@ use core::fmt;
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 
-use ng_threshold_crypto::SecretKeySet;
+use ng_threshold_crypto::{PublicKeySet, SecretKeySet};
 
 use crate::block_storage::{BlockStorage, HashMapBlockStorage};
 use crate::errors::{NgError, StorageError};
 #[allow(unused_imports)]
 use crate::log::*;
 use crate::object::Object;
-use crate::repo::{BranchInfo, Repo};
+use crate::repo::{BranchInfo, Repo, RepoCapRefresh};
 use crate::types::*;
 use crate::utils::{generate_keypair, sign};
 
@@ -684,7 +684,7 @@ impl Store {
 
         branches.push((root_branch.id, root_branch));
 
-        let repo = Repo {
+        let mut repo = Repo {
             id: repo_pub_key,
             repo_def: repository,
             signer: Some(signer_cap),
@@ -697,10 +697,352 @@ impl Store {
             opened_branches: HashMap::new(),
             certificate_ref: Some(certificate_ref),
         };
+        repo.add_member(creator, &[PermissionV0::Owner]);
 
         Ok((repo, events))
     }
 
+    /// Renews the capabilities of the repo: each branch gets a new topic and a new read cap,
+    /// and the repo gets a new RepoWriteCapSecret.
+    ///
+    /// The recipients (with a flag telling if they are editors) receive the refresh secret in a RefreshCap.
+    /// Anybody else, like a removed member, keeps access to the history until the refresh, but not after.
+    ///
+    /// The removed members are removed with RemoveMember commits, that are part of the chain signed by the owners.
+    ///
+    /// On the root branch, the chain is: RootCapRefresh -> RemoveMember(s) -> UpdateRootBranch -> AddBranch for each branch -> SyncSignature,
+    /// and on each transactional branch: BranchCapRefresh -> UpdateBranch -> SyncSignature.
+    /// Then each new topic starts with a CapRefreshed.
+    pub fn refresh_repo_caps(
+        &self,
+        repo: &Repo,
+        author: &UserId,
+        author_priv_key: &PrivKey,
+        recipients: &[(UserId, bool)],
+        removed_members: &[RemoveMemberV0],
+    ) -> Result<RepoCapRefresh, NgError> {
+        let signer = repo.signer.as_ref().ok_or(NgError::PermissionDenied)?;
+        let certificate_ref = repo
+            .certificate_ref
+            .clone()
+            .ok_or(NgError::PermissionDenied)?;
+        let root_read_cap = repo.read_cap.clone().ok_or(NgError::RepoNotFound)?;
+        let root_info = repo.root_branch().ok_or(NgError::BranchNotFound)?;
+        let root_branch = match Commit::load(root_read_cap.clone(), self, true)?
+            .body()
+            .ok_or(NgError::CommitNotFound)?
+        {
+            CommitBody::V0(CommitBodyV0::RootBranch(RootBranch::V0(v0)))
+            | CommitBody::V0(CommitBodyV0::UpdateRootBranch(RootBranch::V0(v0))) => v0.clone(),
+            _ => return Err(NgError::InvalidValue),
+        };
+        // the threshold signature of the owners is combined below from the share of the author alone,
+        // which is only valid when the repo has a single owner
+        if root_branch.owners.len() != 1 {
+            return Err(NgError::NotImplemented);
+        }
+
+        let write_cap = SymKey::random();
+        let refresh_secret = RefreshSecretV0::new(SymKey::random(), Some(SymKey::random()));
+        let refresh_cap = RefreshCap::new(
+            recipients,
+            refresh_secret.read_secret(),
+            refresh_secret.write_secret(),
+            self.store_repo.overlay_id_for_read_purpose(),
+        )?;
+        let refresh_object = Object::new(
+            ObjectContent::V0(ObjectContentV0::RefreshCap(refresh_cap)),
+            None,
+            0,
+            self,
+        );
+        let refresh_blocks = refresh_object.save(self)?;
+        let refresh_ref = refresh_object.reference().unwrap();
+
+        let mut events = vec![];
+        let mut signed_commits = vec![];
+
+        // the RootCapRefresh, RemoveMember and UpdateRootBranch commits
+
+        let root_cap_refresh_commit = Commit::new_with_body_acks_deps_and_save(
+            author_priv_key,
+            author,
+            repo.id,
+            QuorumType::Owners,
+            vec![root_read_cap.clone()],
+            root_info.current_heads.clone(),
+            CommitBody::V0(CommitBodyV0::RootCapRefresh(RootCapRefresh::V0(
+                RootCapRefreshV0 {
+                    refresh_ref: refresh_ref.clone(),
+                    write_cap: Some(RootCapRefresh::encrypt_write_cap(
+                        &write_cap,
+                        refresh_secret.write_secret().unwrap(),
+                    )),
+                },
+            ))),
+            self,
+        )?;
+        let root_cap_refresh_ref = root_cap_refresh_commit.reference().unwrap();
+        // it is sent in its own event, so the recipients can open the events of the SyncSignatures, but only applied once the signature is verified
+        signed_commits.push(root_cap_refresh_ref.id);
+        events.push((root_cap_refresh_commit, refresh_blocks.clone(), false));
+
+        let mut root_chain_blocks = vec![];
+        let mut root_chain_head = root_cap_refresh_ref.clone();
+        for removed in removed_members {
+            let remove_member_commit = Commit::new_with_body_acks_deps_and_save(
+                author_priv_key,
+                author,
+                repo.id,
+                QuorumType::Owners,
+                vec![root_chain_head.clone()],
+                vec![root_chain_head.clone()],
+                CommitBody::V0(CommitBodyV0::RemoveMember(RemoveMember::V0(
+                    removed.clone(),
+                ))),
+                self,
+            )?;
+            root_chain_head = remove_member_commit.reference().unwrap();
+            signed_commits.push(root_chain_head.id);
+            root_chain_blocks.extend(remove_member_commit.blocks().iter());
+        }
+
+        let (root_topic_priv_key, root_topic) = generate_keypair();
+        let mut owners_write_cap = Vec::with_capacity(root_branch.owners.len());
+        for owner in root_branch.owners.iter() {
+            owners_write_cap.push(serde_bytes::ByteBuf::from(RootBranch::encrypt_write_cap(
+                owner, &write_cap,
+            )?));
+        }
+        let update_root_branch_commit = Commit::new_with_body_acks_deps_and_save(
+            author_priv_key,
+            author,
+            repo.id,
+            QuorumType::Owners,
+            vec![root_chain_head.clone()],
+            vec![root_chain_head],
+            CommitBody::V0(CommitBodyV0::UpdateRootBranch(RootBranch::V0(
+                RootBranchV0 {
+                    topic: root_topic,
+                    topic_privkey: Branch::encrypt_branch_write_cap_secret(
+                        &root_topic_priv_key,
+                        root_topic,
+                        repo.id,
+                        &write_cap,
+                    ),
+                    owners_write_cap,
+                    ..root_branch
+                },
+            ))),
+            self,
+        )?;
+        let new_root_read_cap = update_root_branch_commit.reference().unwrap();
+        signed_commits.push(new_root_read_cap.id);
+        root_chain_blocks.extend(update_root_branch_commit.blocks().iter());
+        let mut root_chain_head = new_root_read_cap.clone();
+
+        // the BranchCapRefresh and UpdateBranch commits of each transactional branch, and their AddBranch on the root branch
+
+        let mut branch_chains = vec![];
+        for branch_info in repo.branches.values() {
+            if branch_info.branch_type == BranchType::Root {
+                continue;
+            }
+            let read_cap = branch_info
+                .read_cap
+                .clone()
+                .ok_or(NgError::BranchNotFound)?;
+            let branch = match Commit::load(read_cap.clone(), self, true)?
+                .body()
+                .ok_or(NgError::CommitNotFound)?
+            {
+                CommitBody::V0(CommitBodyV0::Branch(Branch::V0(v0)))
+                | CommitBody::V0(CommitBodyV0::UpdateBranch(Branch::V0(v0))) => v0.clone(),
+                _ => return Err(NgError::InvalidValue),
+            };
+
+            let branch_cap_refresh_commit = Commit::new_with_body_acks_deps_and_save(
+                author_priv_key,
+                author,
+                branch_info.id,
+                QuorumType::Owners,
+                vec![read_cap.clone()],
+                branch_info.current_heads.clone(),
+                CommitBody::V0(CommitBodyV0::BranchCapRefresh(BranchCapRefresh::V0(
+                    BranchCapRefreshV0 {
+                        refresh_ref: refresh_ref.clone(),
+                    },
+                ))),
+                self,
+            )?;
+            let branch_cap_refresh_ref = branch_cap_refresh_commit.reference().unwrap();
+            events.push((branch_cap_refresh_commit, refresh_blocks.clone(), false));
+
+            let (topic_priv_key, topic) = generate_keypair();
+            let update_branch_commit = Commit::new_with_body_acks_deps_and_save(
+                author_priv_key,
+                author,
+                branch_info.id,
+                QuorumType::Owners,
+                vec![branch_cap_refresh_ref.clone()],
+                vec![branch_cap_refresh_ref.clone()],
+                CommitBody::V0(CommitBodyV0::UpdateBranch(Branch::V0(BranchV0 {
+                    root_branch_readcap_id: new_root_read_cap.id,
+                    topic,
+                    topic_privkey: Branch::encrypt_branch_write_cap_secret(
+                        &topic_priv_key,
+                        topic,
+                        branch_info.id,
+                        &write_cap,
+                    ),
+                    ..branch
+                }))),
+                self,
+            )?;
+            let new_read_cap = update_branch_commit.reference().unwrap();
+            signed_commits.push(new_read_cap.id);
+            // so that members who did not open the branch can still learn its new topic_priv_key from the AddBranch
+            root_chain_blocks.extend(update_branch_commit.blocks().iter());
+
+            let add_branch_commit = Commit::new_with_body_acks_deps_and_save(
+                author_priv_key,
+                author,
+                repo.id,
+                QuorumType::Owners,
+                vec![root_chain_head.clone()],
+                vec![root_chain_head.clone()],
+                CommitBody::V0(CommitBodyV0::AddBranch(AddBranch::V0(AddBranchV0 {
+                    branch_type: branch_info.branch_type.clone(),
+                    branch_id: branch_info.id,
+                    topic_id: Some(topic),
+                    branch_read_cap: Some(new_read_cap.clone()),
+                    fork_of: branch_info.fork_of,
+                    merged_in: branch_info.merged_in,
+                    crdt: branch_info.crdt.clone(),
+                }))),
+                self,
+            )?;
+            root_chain_head = add_branch_commit.reference().unwrap();
+            signed_commits.push(root_chain_head.id);
+            root_chain_blocks.extend(add_branch_commit.blocks().iter());
+
+            branch_chains.push((
+                branch_info.id,
+                read_cap,
+                branch_cap_refresh_ref,
+                update_branch_commit,
+            ));
+        }
+
+        // one signature by the owners for all the chains
+
+        let signature_content = SignatureContent::V0(SignatureContentV0 {
+            commits: signed_commits,
+        });
+        let signature_content_ser = serde_bare::to_vec(&signature_content).unwrap();
+        let sig_share = signer.sign_with_owner(&signature_content_ser)?;
+        let sig = PublicKeySet::combine_signatures_with_threshold(0, [(0, &sig_share)])
+            .map_err(|_| NgError::IncompleteSignature)?;
+        let signature = Signature::V0(SignatureV0 {
+            content: signature_content,
+            threshold_sig: ThresholdSignatureV0::Owners(sig),
+            certificate_ref,
+        });
+        let sig_object = Object::new(
+            ObjectContent::V0(ObjectContentV0::Signature(signature)),
+            None,
+            0,
+            self,
+        );
+        let sig_obj_blocks = sig_object.save(self)?;
+        let sync_sig_commit_body = CommitBody::V0(CommitBodyV0::SyncSignature(SyncSignature::V0(
+            sig_object.reference().unwrap(),
+        )));
+
+        let mut refreshed = vec![];
+
+        let root_sync_sig_commit = Commit::new_with_body_acks_deps_and_save(
+            author_priv_key,
+            author,
+            repo.id,
+            QuorumType::IamTheSignature,
+            vec![root_chain_head],
+            vec![root_cap_refresh_ref],
+            sync_sig_commit_body.clone(),
+            self,
+        )?;
+        refreshed.push(self.new_cap_refreshed(
+            author,
+            author_priv_key,
+            repo.id,
+            root_read_cap,
+            root_sync_sig_commit.reference().unwrap(),
+            new_root_read_cap,
+        )?);
+        root_chain_blocks.extend(sig_obj_blocks.iter());
+        events.push((root_sync_sig_commit, root_chain_blocks, true));
+
+        for (branch_id, read_cap, branch_cap_refresh_ref, update_branch_commit) in branch_chains {
+            let new_read_cap = update_branch_commit.reference().unwrap();
+            let sync_sig_commit = Commit::new_with_body_acks_deps_and_save(
+                author_priv_key,
+                author,
+                branch_id,
+                QuorumType::IamTheSignature,
+                vec![new_read_cap.clone()],
+                vec![branch_cap_refresh_ref],
+                sync_sig_commit_body.clone(),
+                self,
+            )?;
+            refreshed.push(self.new_cap_refreshed(
+                author,
+                author_priv_key,
+                branch_id,
+                read_cap,
+                sync_sig_commit.reference().unwrap(),
+                new_read_cap,
+            )?);
+            let mut blocks = update_branch_commit.blocks().clone();
+            blocks.extend(sig_obj_blocks.iter());
+            events.push((sync_sig_commit, blocks, true));
+        }
+
+        Ok(RepoCapRefresh {
+            write_cap,
+            refresh_secret,
+            events,
+            refreshed,
+        })
+    }
+
+    /// the singleton that starts the new topic of a refreshed branch. It has no ACKS nor DEPS.
+    fn new_cap_refreshed(
+        &self,
+        author: &UserId,
+        author_priv_key: &PrivKey,
+        branch_id: BranchId,
+        continuation_of: ReadCap,
+        refresh: ObjectRef,
+        new_read_cap: ReadCap,
+    ) -> Result<Commit, NgError> {
+        Commit::new_with_body_acks_deps_and_save(
+            author_priv_key,
+            author,
+            branch_id,
+            QuorumType::NoSigning,
+            vec![],
+            vec![],
+            CommitBody::V0(CommitBodyV0::CapRefreshed(BranchCapRefreshed::V0(
+                BranchCapRefreshedV0 {
+                    continuation_of,
+                    refresh,
+                    new_read_cap,
+                },
+            ))),
+            self,
+        )
+    }
+
     pub fn new(
         store_repo: StoreRepo,
         store_readcap: ReadCap,
@@ -762,3 +1104,189 @@ impl Store {
         ))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::event::Event;
+    #[allow(unused_imports)]
+    use crate::log::*;
+    use crate::store::*;
+
+    fn new_transaction(
+        author_priv_key: &PrivKey,
+        author: &UserId,
+        branch_info: &BranchInfo,
+        store: &Store,
+    ) -> Commit {
+        Commit::new_with_body_acks_deps_and_save(
+            author_priv_key,
+            author,
+            branch_info.id,
+            QuorumType::NoSigning,
+            vec![],
+            branch_info.current_heads.clone(),
+            CommitBody::V0(CommitBodyV0::AsyncTransaction(Transaction::V0(vec![
+                1, 2, 3,
+            ]))),
+            store,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    pub fn test_refresh_repo_caps() {
+        let (owner_priv, owner) = generate_keypair();
+        let (editor_priv, editor) = generate_keypair();
+        let (reader_priv, reader) = generate_keypair();
+        let (removed_priv, removed) = generate_keypair();
+        let (peer_priv, _) = generate_keypair();
+        let store = Store::dummy_public_v0();
+        let overlay = store.get_store_repo().overlay_id_for_read_purpose();
+
+        let (mut repo, _) = Arc::clone(&store)
+            .create_repo_default(
+                &owner,
+                &owner_priv,
+                SymKey::random(),
+                BranchCrdt::Graph("data:graph".to_string()),
+            )
+            .unwrap();
+        repo.add_member(&editor, &[PermissionV0::WriteAsync]);
+        repo.add_member(&reader, &[]);
+        repo.add_member(&removed, &[PermissionV0::WriteAsync]);
+
+        let main_branch = repo.main_branch().unwrap().clone();
+        let old_read_cap = main_branch.read_cap.clone().unwrap();
+        let before = new_transaction(&owner_priv, &owner, &main_branch, &store);
+        let event_before = Event::new(&peer_priv, 1, &before, &vec![], &repo).unwrap();
+
+        let recipients = vec![(owner, true), (editor, true), (reader, false)];
+        let refresh = store
+            .refresh_repo_caps(&repo, &owner, &owner_priv, &recipients, &[])
+            .unwrap();
+
+        // the RefreshCap is only readable by the recipients, and only the editors get the renewed write cap
+        let root_cap_refresh = match refresh.events[0].0.body() {
+            Some(CommitBody::V0(CommitBodyV0::RootCapRefresh(r))) => r.clone(),
+            _ => panic!("the first event should be the RootCapRefresh"),
+        };
+        let refresh_cap = match Object::load_ref(root_cap_refresh.refresh_ref(), &store)
+            .unwrap()
+            .content_v0()
+            .unwrap()
+        {
+            ObjectContentV0::RefreshCap(refresh_cap) => refresh_cap,
+            _ => panic!("expected a RefreshCap"),
+        };
+        assert!(matches!(
+            refresh_cap.open(&removed, &removed_priv, overlay),
+            Err(NgError::NotFound)
+        ));
+        let reader_secret = refresh_cap.open(&reader, &reader_priv, overlay).unwrap();
+        assert_eq!(
+            reader_secret.read_secret(),
+            refresh.refresh_secret.read_secret()
+        );
+        assert!(reader_secret.write_secret().is_none());
+
+        let editor_secret = refresh_cap.open(&editor, &editor_priv, overlay).unwrap();
+        let editor_write_cap = RootCapRefresh::decrypt_write_cap(
+            root_cap_refresh.write_cap().unwrap(),
+            editor_secret.write_secret().unwrap(),
+        );
+        assert_eq!(editor_write_cap, refresh.write_cap);
+
+        // the new read cap of the main branch, and the new topic that the editor can publish in
+        let new_read_cap = refresh
+            .refreshed
+            .iter()
+            .find_map(|commit| match commit.body() {
+                Some(CommitBody::V0(CommitBodyV0::CapRefreshed(BranchCapRefreshed::V0(v0))))
+                    if *commit.branch() == main_branch.id =>
+                {
+                    assert_eq!(v0.continuation_of, old_read_cap);
+                    Some(v0.new_read_cap.clone())
+                }
+                _ => None,
+            })
+            .unwrap();
+        assert_ne!(new_read_cap.key, old_read_cap.key);
+        let new_branch = match Commit::load(new_read_cap.clone(), &store, true)
+            .unwrap()
+            .body()
+        {
+            Some(CommitBody::V0(CommitBodyV0::UpdateBranch(Branch::V0(v0)))) => v0.clone(),
+            _ => panic!("the new read cap should point to an UpdateBranch"),
+        };
+        let new_topic_priv_key = Branch::decrypt_branch_write_cap_secret(
+            new_branch.topic_privkey.clone(),
+            new_branch.topic,
+            main_branch.id,
+            &editor_write_cap,
+        )
+        .unwrap();
+        assert_eq!(new_topic_priv_key.to_pub(), new_branch.topic);
+
+        // the SyncSignatures sent in the old topics can only be opened with the refresh secret
+        let (root_sync_sig, blocks, with_refresh_secret) = refresh
+            .events
+            .iter()
+            .find(|(commit, _, with_secret)| *with_secret && *commit.branch() == repo.id)
+            .unwrap();
+        assert!(*with_refresh_secret);
+        let event = Event::new_with_secret(
+            &peer_priv,
+            2,
+            root_sync_sig,
+            blocks,
+            &repo,
+            refresh.refresh_secret.read_secret(),
+        )
+        .unwrap();
+        let old_root_key = repo.read_cap.as_ref().unwrap().key.clone();
+        assert!(event
+            .open(&store, &repo.id, &repo.id, &old_root_key)
+            .is_err());
+        assert_eq!(
+            event
+                .open(
+                    &store,
+                    &repo.id,
+                    &repo.id,
+                    refresh.refresh_secret.read_secret()
+                )
+                .unwrap()
+                .id(),
+            root_sync_sig.id()
+        );
+
+        // the history before the refresh is still readable with the old read cap
+        assert_eq!(
+            event_before
+                .open(&store, &repo.id, &main_branch.id, &old_read_cap.key)
+                .unwrap()
+                .id(),
+            before.id()
+        );
+
+        // the events after the refresh cannot be read by the removed member, who only knows the old read cap
+        {
+            let branch = repo.branch_mut(&main_branch.id).unwrap();
+            branch.read_cap = Some(new_read_cap.clone());
+            branch.topic = Some(new_branch.topic);
+            branch.topic_priv_key = Some(new_topic_priv_key);
+        }
+        let after = new_transaction(&editor_priv, &editor, &main_branch, &store);
+        let event_after = Event::new(&peer_priv, 3, &after, &vec![], &repo).unwrap();
+        assert!(event_after
+            .open(&store, &repo.id, &main_branch.id, &old_read_cap.key)
+            .is_err());
+        assert_eq!(
+            event_after
+                .open(&store, &repo.id, &main_branch.id, &new_read_cap.key)
+                .unwrap()
+                .id(),
+            after.id()
+        );
+    }
+}
@@ -16,6 +16,8 @@ use std::cmp::Ordering;
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use chacha20::ChaCha20;
 use ng_threshold_crypto::serde_impl::SerdeSecret;
 use ng_threshold_crypto::SignatureShare;
 use once_cell::sync::OnceCell;
@@ -2350,6 +2352,20 @@ impl fmt::Display for SyncSignature {
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct RefreshSecretV0(SymKey, Option<SymKey>);
 
+impl RefreshSecretV0 {
+    pub fn new(read_secret: SymKey, write_secret: Option<SymKey>) -> Self {
+        Self(read_secret, write_secret)
+    }
+    /// the key used to encrypt the key of the SyncSignature commits in the events sent in the old topics
+    pub fn read_secret(&self) -> &SymKey {
+        &self.0
+    }
+    /// the key used to encrypt the renewed RepoWriteCapSecret
+    pub fn write_secret(&self) -> Option<&SymKey> {
+        self.1.as_ref()
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct RefreshCapV0 {
     /// an ordered list of user IDs, with their corresponding crypto_box of a RefreshSecretV0.
@@ -2365,6 +2381,62 @@ pub enum RefreshCap {
     V0(RefreshCapV0),
 }
 
+impl RefreshCap {
+    /// crypto_boxes the refresh secret for each recipient. The write secret is only given to the editors (recipients flagged with true).
+    pub fn new(
+        recipients: &[(UserId, bool)],
+        read_secret: &SymKey,
+        write_secret: Option<&SymKey>,
+        overlay: OverlayId,
+    ) -> Result<Self, NgError> {
+        let mut refresh_secret = Vec::with_capacity(recipients.len());
+        let mut rng = crypto_box::aead::OsRng {};
+        for (user, is_editor) in recipients {
+            let secret = RefreshSecretV0(
+                read_secret.clone(),
+                if *is_editor {
+                    write_secret.cloned()
+                } else {
+                    None
+                },
+            );
+            let ser = serde_bare::to_vec(&secret).unwrap();
+            let cipher = crypto_box::seal(&mut rng, &user.to_dh_slice().into(), &ser)
+                .map_err(|_| NgError::EncryptionError)?;
+            refresh_secret.push((
+                CommitContent::author_digest(user, overlay),
+                serde_bytes::ByteBuf::from(cipher),
+            ));
+        }
+        refresh_secret.sort_by(|a, b| a.0.slice().cmp(b.0.slice()));
+        Ok(Self::V0(RefreshCapV0 { refresh_secret }))
+    }
+
+    /// finds the crypto_box of the user and opens it. Fails with NotFound if the user is not a recipient anymore
+    pub fn open(
+        &self,
+        user: &UserId,
+        user_priv: &PrivKey,
+        overlay: OverlayId,
+    ) -> Result<RefreshSecretV0, NgError> {
+        match self {
+            Self::V0(v0) => {
+                let digest = CommitContent::author_digest(user, overlay);
+                let pos = v0
+                    .refresh_secret
+                    .binary_search_by(|(d, _)| d.slice().cmp(digest.slice()))
+                    .map_err(|_| NgError::NotFound)?;
+                let ser = crypto_box::seal_open(
+                    &(*user_priv.to_dh().slice()).into(),
+                    &v0.refresh_secret[pos].1,
+                )
+                .map_err(|_| NgError::DecryptionError)?;
+                serde_bare::from_slice(&ser).map_err(|_| NgError::SerializationError)
+            }
+        }
+    }
+}
+
 /// RootCapRefresh. renew the capabilities of the root branch, or all transactional branches and the root_branch.
 ///
 /// Each branch forms its separate chain for that purpose.
@@ -2393,6 +2465,40 @@ pub enum RootCapRefresh {
     V0(RootCapRefreshV0),
 }
 
+impl RootCapRefresh {
+    pub fn refresh_ref(&self) -> &ObjectRef {
+        match self {
+            Self::V0(v0) => &v0.refresh_ref,
+        }
+    }
+    pub fn write_cap(&self) -> Option<&RepoWriteCapSecret> {
+        match self {
+            Self::V0(v0) => v0.write_cap.as_ref(),
+        }
+    }
+    /// encrypts the renewed write_cap with the write secret of the RefreshSecretV0
+    pub fn encrypt_write_cap(
+        write_cap: &RepoWriteCapSecret,
+        write_secret: &SymKey,
+    ) -> RepoWriteCapSecret {
+        Self::apply_write_secret(write_cap, write_secret)
+    }
+    /// decrypts the write_cap of a RootCapRefresh with the write secret of the RefreshSecretV0
+    pub fn decrypt_write_cap(
+        encrypted_write_cap: &RepoWriteCapSecret,
+        write_secret: &SymKey,
+    ) -> RepoWriteCapSecret {
+        Self::apply_write_secret(encrypted_write_cap, write_secret)
+    }
+    // the ChaCha20 keystream is xored, so encrypting and decrypting are the same operation
+    fn apply_write_secret(key: &RepoWriteCapSecret, write_secret: &SymKey) -> RepoWriteCapSecret {
+        let mut key = *key.slice();
+        let mut cipher = ChaCha20::new(write_secret.slice().into(), &[0u8; 12].into());
+        cipher.apply_keystream(&mut key);
+        SymKey::ChaCha20Key(key)
+    }
+}
+
 /// BranchCapRefresh renew the capabilities of one specific transactional branch
 ///
 /// ACKS: current HEADS in the branch at the moment of refresh.  DEPS to the previous Branch commit that will be superseded.
@@ -2415,6 +2521,14 @@ pub enum BranchCapRefresh {
     V0(BranchCapRefreshV0),
 }
 
+impl BranchCapRefresh {
+    pub fn refresh_ref(&self) -> &ObjectRef {
+        match self {
+            Self::V0(v0) => &v0.refresh_ref,
+        }
+    }
+}
+
 /// BranchCapRefreshed is a singleton in a new topic. it has no ACKS nor DEPS.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct BranchCapRefreshedV0 {
@@ -2434,6 +2548,19 @@ pub enum BranchCapRefreshed {
     V0(BranchCapRefreshedV0),
 }
 
+impl BranchCapRefreshed {
+    pub fn continuation_of(&self) -> &ReadCap {
+        match self {
+            Self::V0(v0) => &v0.continuation_of,
+        }
+    }
+    pub fn new_read_cap(&self) -> &ReadCap {
+        match self {
+            Self::V0(v0) => &v0.new_read_cap,
+        }
+    }
+}
+
 /// A Threshold Signature content
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct SignatureContentV0 {
@@ -2929,6 +3056,20 @@ pub enum SmallFile {
     V0(SmallFileV0),
 }
 
+/// How the content of a RandomAccessFile is split into the leaves of its tree
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum FileChunking {
+    /// All the leaves have the size `chunk_size`, except the last one
+    Fixed,
+
+    /// The leaves are cut where a rolling hash (FastCDC) of the content matches, so that an insertion or a deletion
+    /// in a new version of the file only changes the leaves around it. `chunk_size` is then the maximum size of a leaf.
+    /// Contains the size of each leaf, in order, so that positions can be found for random access.
+    /// This list is stored in the metadata of the file, and grows linearly with the size of the file
+    /// (4 bytes per leaf, that is at least 4 bytes per `chunk_size` bytes of content).
+    ContentDefined(Vec<u32>),
+}
+
 /// Random Access File Object
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct RandomAccessFileMetaV0 {
@@ -2946,22 +3087,48 @@ pub struct RandomAccessFileMetaV0 {
     pub depth: u8,
 }
 
+/// Random Access File Object, with the chunking of its content
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RandomAccessFileMetaV1 {
+    pub content_type: String,
+
+    #[serde(with = "serde_bytes")]
+    pub metadata: Vec<u8>,
+
+    pub total_size: u64,
+
+    pub chunk_size: u32,
+
+    pub arity: u16,
+
+    pub depth: u8,
+
+    pub chunking: FileChunking,
+}
+
 /// A Random Access file stored in an Object
+///
+/// The files with fixed chunking keep the V0 meta, so they can still be read by older versions.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub enum RandomAccessFileMeta {
     V0(RandomAccessFileMetaV0),
+    V1(RandomAccessFileMetaV1),
 }
 
+static FIXED_CHUNKING: FileChunking = FileChunking::Fixed;
+
 impl RandomAccessFileMeta {
     pub fn arity(&self) -> u16 {
         match self {
             Self::V0(v0) => v0.arity,
+            Self::V1(v1) => v1.arity,
         }
     }
 
     pub fn depth(&self) -> u8 {
         match self {
             Self::V0(v0) => v0.depth,
+            Self::V1(v1) => v1.depth,
         }
     }
 
@@ -2970,18 +3137,23 @@ impl RandomAccessFileMeta {
             Self::V0(v0) => {
                 v0.depth = depth;
             }
+            Self::V1(v1) => {
+                v1.depth = depth;
+            }
         }
     }
 
     pub fn chunk_size(&self) -> u32 {
         match self {
             Self::V0(v0) => v0.chunk_size,
+            Self::V1(v1) => v1.chunk_size,
         }
     }
 
     pub fn total_size(&self) -> u64 {
         match self {
             Self::V0(v0) => v0.total_size,
+            Self::V1(v1) => v1.total_size,
         }
     }
 
@@ -2990,20 +3162,59 @@ impl RandomAccessFileMeta {
             Self::V0(v0) => {
                 v0.total_size = size;
             }
+            Self::V1(v1) => {
+                v1.total_size = size;
+            }
         }
     }
 
     pub fn metadata(&self) -> &Vec<u8> {
         match self {
             Self::V0(v0) => &v0.metadata,
+            Self::V1(v1) => &v1.metadata,
         }
     }
 
     pub fn content_type(&self) -> &String {
         match self {
             Self::V0(v0) => &v0.content_type,
+            Self::V1(v1) => &v1.content_type,
+        }
+    }
+
+    /// the V0 meta is always fixed chunking
+    pub fn chunking(&self) -> &FileChunking {
+        match self {
+            Self::V0(_) => &FIXED_CHUNKING,
+            Self::V1(v1) => &v1.chunking,
         }
     }
+
+    /// a V0 meta is upgraded to V1 when the chunking is content defined
+    pub fn set_chunking(&mut self, chunking: FileChunking) {
+        match self {
+            Self::V0(v0) => {
+                if chunking != FileChunking::Fixed {
+                    *self = Self::V1(RandomAccessFileMetaV1 {
+                        content_type: v0.content_type.clone(),
+                        metadata: v0.metadata.clone(),
+                        total_size: v0.total_size,
+                        chunk_size: v0.chunk_size,
+                        arity: v0.arity,
+                        depth: v0.depth,
+                        chunking,
+                    });
+                }
+            }
+            Self::V1(v1) => {
+                v1.chunking = chunking;
+            }
+        }
+    }
+
+    pub fn is_content_defined(&self) -> bool {
+        matches!(self.chunking(), FileChunking::ContentDefined(_))
+    }
 }
 
 /// Immutable data stored encrypted in a Merkle tree V0
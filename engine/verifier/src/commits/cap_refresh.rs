@@ -0,0 +1,363 @@
+// Copyright (c) 2022-2025 Niko Bonnieure, Par le Peuple, NextGraph.org developers
+// All rights reserved.
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE2 or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Verifiers for the commits of a refresh of capabilities (RootCapRefresh, BranchCapRefresh, UpdateRootBranch, UpdateBranch)
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use ng_net::broker::BROKER;
+use ng_repo::errors::{NgError, VerifierError};
+use ng_repo::log::*;
+use ng_repo::object::Object;
+use ng_repo::repo::Repo;
+use ng_repo::store::Store;
+use ng_repo::types::*;
+
+use crate::verifier::Verifier;
+
+impl Verifier {
+    /// opens the RefreshCap of a refresh. Returns None if we are not a recipient of the refresh.
+    fn open_refresh_cap(
+        &self,
+        refresh_ref: &ObjectRef,
+        repo_id: &RepoId,
+        store: &Store,
+    ) -> Result<Option<RefreshSecretV0>, VerifierError> {
+        let refresh_cap = match Object::load_ref(refresh_ref, store)?.content_v0()? {
+            ObjectContentV0::RefreshCap(refresh_cap) => refresh_cap,
+            _ => return Err(VerifierError::InvalidCommit),
+        };
+        match refresh_cap.open(
+            self.user_id(),
+            self.user_privkey(),
+            store.get_store_repo().overlay_id_for_read_purpose(),
+        ) {
+            Ok(secret) => Ok(Some(secret)),
+            Err(NgError::NotFound) => {
+                log_info!(
+                    "we are not a recipient of the cap refresh of repo {}",
+                    repo_id
+                );
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// opens the RefreshCap of a signed RootCapRefresh or of a BranchCapRefresh, and keeps the refresh secret
+    /// (and the renewed RepoWriteCapSecret if we are an editor) until the refresh has been verified.
+    pub(crate) fn receive_refresh_cap(
+        &mut self,
+        refresh_ref: &ObjectRef,
+        write_cap: Option<&RepoWriteCapSecret>,
+        repo_id: &RepoId,
+        store: &Store,
+    ) -> Result<(), VerifierError> {
+        if let Some(secret) = self.open_refresh_cap(refresh_ref, repo_id, store)? {
+            let write_cap = match (write_cap, secret.write_secret()) {
+                (Some(write_cap), Some(write_secret)) => {
+                    Some(RootCapRefresh::decrypt_write_cap(write_cap, write_secret))
+                }
+                _ => None,
+            };
+            self.save_refresh_secret(repo_id, &refresh_ref.id, secret, write_cap)?;
+        }
+        Ok(())
+    }
+
+    /// opens the RefreshCap of a RootCapRefresh that arrived before the SyncSignature that signs it.
+    /// Its refresh secret is only used to open the events of the refresh, and nothing is applied nor saved.
+    pub(crate) fn receive_unsigned_refresh_cap(
+        &mut self,
+        refresh_ref: &ObjectRef,
+        repo_id: &RepoId,
+        store: &Store,
+    ) -> Result<(), VerifierError> {
+        if let Some(secret) = self.open_refresh_cap(refresh_ref, repo_id, store)? {
+            self.pending_refresh_secrets.insert(*repo_id, secret);
+        }
+        Ok(())
+    }
+
+    /// keeps the refresh secret of the last refresh of the repo, also in user storage, so that the events
+    /// of the refresh can still be opened, and the renewed write cap applied, after the verifier is reloaded.
+    fn save_refresh_secret(
+        &mut self,
+        repo_id: &RepoId,
+        refresh_id: &ObjectId,
+        refresh_secret: RefreshSecretV0,
+        write_cap: Option<RepoWriteCapSecret>,
+    ) -> Result<(), VerifierError> {
+        if let Some(user_storage) = self.user_storage_if_persistent() {
+            user_storage.update_repo_refresh_secret(
+                repo_id,
+                refresh_id,
+                &refresh_secret,
+                write_cap.as_ref(),
+            )?;
+        }
+        self.pending_refresh_secrets.remove(repo_id);
+        self.refresh_secrets
+            .insert(*repo_id, (*refresh_id, refresh_secret, write_cap));
+        Ok(())
+    }
+
+    pub(crate) async fn verify_update_root_branch(
+        &mut self,
+        root_branch: &RootBranch,
+        commit: &Commit,
+        branch_id: &BranchId,
+        repo_id: &RepoId,
+        store: Arc<Store>,
+    ) -> Result<(), VerifierError> {
+        let RootBranch::V0(root_branch) = root_branch;
+        if root_branch.id != *repo_id || branch_id != repo_id {
+            return Err(VerifierError::InvalidBranch);
+        }
+        // an UpdateRootBranch is always in the chain of a SyncSignature, which has been verified before its chain
+        let id = commit.id().ok_or(VerifierError::InvalidCommit)?;
+        if !self.temporary_signed_commits.contains(&id) {
+            return Err(VerifierError::InvalidSignatureObject);
+        }
+
+        let user_id = self.user_id().clone();
+        let write_cap = if store.id() == &root_branch.id && store.is_private() {
+            Some(SymKey::nil())
+        } else if let Some(pos) = root_branch.owners.iter().position(|o| *o == user_id) {
+            Some(RootBranch::decrypt_write_cap(
+                self.user_privkey(),
+                &root_branch.owners_write_cap[pos],
+            )?)
+        } else {
+            // editors that are not owners received the renewed write cap in the RootCapRefresh
+            self.refresh_secrets
+                .get(repo_id)
+                .and_then(|(_, _, write_cap)| write_cap.clone())
+        };
+        let topic_priv_key = write_cap.as_ref().and_then(|rwcs| {
+            Branch::decrypt_branch_write_cap_secret(
+                root_branch.topic_privkey.clone(),
+                root_branch.topic,
+                root_branch.id,
+                rwcs,
+            )
+            .ok()
+        });
+        let reference = commit.reference().unwrap();
+
+        let repo = self.get_repo_mut(repo_id, store.get_store_repo())?;
+        repo.read_cap = Some(reference.clone());
+        repo.write_cap = write_cap.clone();
+        repo.set_owners(&root_branch.owners);
+        let overlay_id = repo.store.inner_overlay();
+        let root_info = repo.branch_mut(branch_id)?;
+        root_info.topic = Some(root_branch.topic);
+        root_info.topic_priv_key = topic_priv_key;
+        root_info.read_cap = Some(reference.clone());
+
+        // the old topic stays in self.topics, so that the events that are still published there can be received
+        self.topics
+            .insert((overlay_id, root_branch.topic), (*repo_id, *branch_id));
+
+        if let Some(user_storage) = self.user_storage_if_persistent() {
+            user_storage.update_repo_caps(repo_id, &reference, write_cap.as_ref())?;
+        }
+        self.update_repo_members(repo_id, store.get_store_repo())?;
+        self.update_branch(repo_id, branch_id, store.get_store_repo())
+    }
+
+    pub(crate) async fn verify_update_branch(
+        &mut self,
+        branch: &Branch,
+        commit: &Commit,
+        branch_id: &BranchId,
+        repo_id: &RepoId,
+        store: Arc<Store>,
+    ) -> Result<(), VerifierError> {
+        let Branch::V0(branch) = branch;
+        if branch.id != *branch_id {
+            return Err(VerifierError::InvalidBranch);
+        }
+        //TODO: deal with quorum_type (verify signature)
+
+        self.refresh_branch_info(
+            repo_id,
+            store.get_store_repo(),
+            branch,
+            commit.reference().unwrap(),
+        )
+    }
+
+    /// applies the new topic and read cap of a transactional branch, received in an UpdateBranch, or in the AddBranch of a RootCapRefresh.
+    /// The current heads are kept, as the history before the refresh is still valid.
+    pub(crate) fn refresh_branch_info(
+        &mut self,
+        repo_id: &RepoId,
+        store_repo: &StoreRepo,
+        branch: &BranchV0,
+        read_cap: ReadCap,
+    ) -> Result<(), VerifierError> {
+        let repo = self.get_repo_mut(repo_id, store_repo)?;
+        let topic_priv_key = repo.write_cap.as_ref().and_then(|rwcs| {
+            Branch::decrypt_branch_write_cap_secret(
+                branch.topic_privkey.clone(),
+                branch.topic,
+                branch.id,
+                rwcs,
+            )
+            .ok()
+        });
+        let overlay_id = repo.store.inner_overlay();
+        let branch_info = repo.branch_mut(&branch.id)?;
+        branch_info.topic = Some(branch.topic);
+        branch_info.topic_priv_key = topic_priv_key;
+        branch_info.read_cap = Some(read_cap);
+
+        self.topics
+            .insert((overlay_id, branch.topic), (*repo_id, branch.id));
+
+        self.update_branch(repo_id, &branch.id, store_repo)
+    }
+
+    /// subscribes to the new topic of a refreshed branch, if the branch was opened.
+    pub(crate) async fn reopen_refreshed_branch(
+        &mut self,
+        repo_id: &RepoId,
+        branch_id: &BranchId,
+    ) -> Result<(), NgError> {
+        let as_publisher = {
+            let repo = self.repos.get(repo_id).ok_or(NgError::RepoNotFound)?;
+            match repo.opened_branches.get(branch_id) {
+                Some(as_publisher) => {
+                    *as_publisher && repo.branch(branch_id)?.topic_priv_key.is_some()
+                }
+                None => return Ok(()),
+            }
+        };
+        if !self.connected_broker.is_some() {
+            let repo = self.repos.get_mut(repo_id).ok_or(NgError::RepoNotFound)?;
+            repo.opened_branches.insert(*branch_id, as_publisher);
+            return Ok(());
+        }
+        let user = self.user_id().clone();
+        let connected_broker = self.connected_broker.clone();
+        self.open_branch_(
+            repo_id,
+            branch_id,
+            as_publisher,
+            &BROKER.read().await,
+            &Some(user),
+            &connected_broker,
+            true,
+        )
+        .await
+    }
+
+    /// the users that will receive the refresh secret: the owners, the members and ourselves, minus the excluded users.
+    /// Editors (flagged with true) also receive the renewed RepoWriteCapSecret.
+    fn cap_refresh_recipients(
+        &self,
+        repo: &Repo,
+        excluded: &[UserId],
+    ) -> Result<Vec<(UserId, bool)>, VerifierError> {
+        let read_cap = repo
+            .read_cap
+            .clone()
+            .ok_or(VerifierError::RootBranchNotFound)?;
+        let root_branch = Commit::load(read_cap, &repo.store, true)?;
+        let owners = match root_branch.body() {
+            Some(CommitBody::V0(CommitBodyV0::RootBranch(RootBranch::V0(v0))))
+            | Some(CommitBody::V0(CommitBodyV0::UpdateRootBranch(RootBranch::V0(v0)))) => {
+                &v0.owners
+            }
+            _ => return Err(VerifierError::RootBranchNotFound),
+        };
+        let mut recipients: HashMap<UserId, bool> = HashMap::new();
+        for owner in owners.iter() {
+            recipients.insert(*owner, true);
+        }
+        for member in repo.members.values() {
+            recipients.entry(member.id).or_insert(member.can_edit());
+        }
+        recipients.insert(*self.user_id(), true);
+
+        for user in excluded {
+            if user == self.user_id() || owners.contains(user) {
+                return Err(VerifierError::PermissionDenied);
+            }
+            recipients.remove(user);
+        }
+        Ok(recipients.into_iter().collect())
+    }
+
+    /// Renews the read caps, the topics and the RepoWriteCapSecret of a repo, so that the excluded users cannot
+    /// read nor write the commits that will be added after the refresh.
+    /// The removed members are removed in the same chain, which is signed by the owners.
+    pub(crate) async fn refresh_caps(
+        &mut self,
+        repo_id: &RepoId,
+        store_repo: &StoreRepo,
+        excluded: &[UserId],
+        removed_members: &[RemoveMemberV0],
+    ) -> Result<(), VerifierError> {
+        let (refresh, store) = {
+            let repo = self.get_repo(repo_id, store_repo)?;
+            let recipients = self.cap_refresh_recipients(repo, excluded)?;
+            let refresh = repo.store.refresh_repo_caps(
+                repo,
+                self.user_id(),
+                self.user_privkey(),
+                &recipients,
+                removed_members,
+            )?;
+            (refresh, Arc::clone(&repo.store))
+        };
+        let refresh_id = match refresh.events[0].0.body() {
+            Some(CommitBody::V0(CommitBodyV0::RootCapRefresh(root_cap_refresh))) => {
+                root_cap_refresh.refresh_ref().id
+            }
+            _ => return Err(VerifierError::InvalidCommit),
+        };
+        self.save_refresh_secret(
+            repo_id,
+            &refresh_id,
+            refresh.refresh_secret.clone(),
+            Some(refresh.write_cap),
+        )?;
+
+        // all the events are sent in the old topics, before we switch to the new ones
+        let secret = refresh.refresh_secret.read_secret().clone();
+        for (commit, blocks, with_refresh_secret) in refresh.events.iter() {
+            if *with_refresh_secret {
+                self.new_event_with_secret(commit, blocks, *repo_id, store_repo, &secret)
+                    .await?;
+            } else {
+                self.new_event(commit, blocks, *repo_id, store_repo).await?;
+            }
+        }
+        for (commit, _, _) in refresh.events.iter() {
+            // the RootCapRefresh is verified with the SyncSignature that signs it
+            if let Some(CommitBody::V0(CommitBodyV0::RootCapRefresh(_))) = commit.body() {
+                continue;
+            }
+            self.verify_commit(commit, commit.branch(), repo_id, Arc::clone(&store))
+                .await?;
+        }
+
+        for commit in refresh.refreshed.iter() {
+            let branch_id = *commit.branch();
+            self.verify_commit_(commit, &branch_id, repo_id, Arc::clone(&store), true)
+                .await?;
+            self.new_event(commit, &vec![], *repo_id, store_repo)
+                .await?;
+        }
+        Ok(())
+    }
+}
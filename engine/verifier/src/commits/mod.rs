@@ -13,11 +13,13 @@ pub mod transaction;
 
 pub mod snapshot;
 
+pub mod cap_refresh;
+
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use ng_net::broker::BROKER;
-use ng_repo::errors::VerifierError;
+use ng_repo::errors::{StorageError, VerifierError};
 #[allow(unused_imports)]
 use ng_repo::log::*;
 use ng_repo::object::Object;
@@ -111,6 +113,7 @@ impl CommitVerifier for RootBranch {
                     None
                 };
                 let reference = commit.reference().unwrap();
+                let owners = root_branch.owners.clone();
                 let root_branch = BranchInfo {
                     id: root_branch.id.clone(),
                     branch_type: BranchType::Root,
@@ -131,7 +134,7 @@ impl CommitVerifier for RootBranch {
                 let inbox = verifier
                     .user_storage()
                     .and_then(|storage| storage.get_inbox_cap(&id).ok());
-                let repo = Repo {
+                let mut repo = Repo {
                     id,
                     repo_def: repository.clone(),
                     signer,
@@ -144,6 +147,7 @@ impl CommitVerifier for RootBranch {
                     opened_branches: HashMap::new(),
                     certificate_ref: verifier.temporary_repo_certificates.remove(&id),
                 };
+                repo.set_owners(&owners);
                 verifier.populate_topics(&repo);
                 let _repo_ref = verifier.add_repo_and_save(repo);
             }
@@ -207,6 +211,28 @@ impl CommitVerifier for Branch {
         }
     }
 }
+/// verifies a threshold signature of the owners against the root certificate of the repo
+fn verify_owners_signature(
+    sig: &Signature,
+    repo_id: &RepoId,
+    store: &Store,
+) -> Result<(), VerifierError> {
+    let cert = match Object::load_ref(sig.certificate_ref(), store)?.content_v0()? {
+        ObjectContentV0::Certificate(Certificate::V0(cert)) => cert,
+        _ => return Err(VerifierError::InvalidSignatureObject),
+    };
+    //TODO: verify the chain of certificates
+    cert.verify_with_repo_id(repo_id)
+        .map_err(|_| VerifierError::InvalidSignatureObject)?;
+    match sig {
+        Signature::V0(v0) => match v0.threshold_sig {
+            ThresholdSignatureV0::Owners(_) => v0
+                .verify(&cert)
+                .map_err(|_| VerifierError::InvalidSignatureObject),
+            _ => Err(VerifierError::NotImplemented),
+        },
+    }
+}
 #[async_trait::async_trait]
 impl CommitVerifier for SyncSignature {
     async fn verify(
@@ -220,13 +246,14 @@ impl CommitVerifier for SyncSignature {
         match self {
             SyncSignature::V0(signature_ref) => {
                 let sign = Object::load_ref(signature_ref, &store)?;
-                match sign.content_v0()? {
+                let signed_commits = match sign.content_v0()? {
                     ObjectContentV0::Signature(sig) => {
-                        //TODO: verify signature
+                        verify_owners_signature(&sig, repo_id, &store)?;
                         verifier.update_repo_certificate(repo_id, sig.certificate_ref());
+                        sig.signed_commits().to_vec()
                     }
                     _ => return Err(VerifierError::InvalidSignatureObject),
-                }
+                };
                 // process each deps
                 let acks = commit.acks();
                 if acks.len() != 1 {
@@ -237,11 +264,42 @@ impl CommitVerifier for SyncSignature {
                 if deps.len() != 1 {
                     return Err(VerifierError::MalformedSyncSignatureDeps);
                 }
-                let commits = list_dep_chain_until(deps[0].clone(), &ack.id, &store, true)?;
+                let mut commits = list_dep_chain_until(deps[0].clone(), &ack.id, &store, true)?;
+                // the RootCapRefresh that starts the chain of a cap refresh is covered by the signature too
+                if let Ok(ack_commit) = Commit::load(ack.clone(), &store, true) {
+                    if let Some(CommitBody::V0(CommitBodyV0::RootCapRefresh(_))) = ack_commit.body()
+                    {
+                        commits.insert(0, ack_commit);
+                    }
+                }
+                let mut refreshed = false;
+                // the commits that need the quorum (like RemoveMember) check that they are covered by the signature
+                verifier.temporary_signed_commits = signed_commits.into_iter().collect();
+                let mut res = Ok(());
                 for commit in commits {
-                    verifier
+                    res = verifier
                         .verify_commit(&commit, branch_id, repo_id, Arc::clone(&store))
-                        .await?;
+                        .await;
+                    if res.is_err() {
+                        break;
+                    }
+                    refreshed |= matches!(
+                        commit.body(),
+                        Some(CommitBody::V0(CommitBodyV0::UpdateRootBranch(_)))
+                            | Some(CommitBody::V0(CommitBodyV0::UpdateBranch(_)))
+                    );
+                }
+                verifier.temporary_signed_commits.clear();
+                res?;
+                if refreshed {
+                    // the branch has a new topic
+                    if let Err(e) = verifier.reopen_refreshed_branch(repo_id, branch_id).await {
+                        log_err!(
+                            "could not subscribe to the new topic of branch {}: {}",
+                            branch_id,
+                            e
+                        );
+                    }
                 }
             }
         }
@@ -264,6 +322,31 @@ impl CommitVerifier for AddBranch {
                     return Err(VerifierError::InvalidBranch);
                 }
 
+                // if the branch has been refreshed by a RootCapRefresh, the UpdateBranch is included in the event.
+                // we keep the current heads of the branch, as its history is still valid.
+                let exists = verifier
+                    .get_repo(commit.branch(), store.get_store_repo())?
+                    .branches
+                    .contains_key(&v0.branch_id);
+                if let (true, Some(read_cap)) = (exists, v0.branch_read_cap.as_ref()) {
+                    if let Ok(update_branch) = Commit::load(read_cap.clone(), &store, true) {
+                        if let Some(CommitBody::V0(CommitBodyV0::UpdateBranch(Branch::V0(
+                            branch,
+                        )))) = update_branch.body()
+                        {
+                            if branch.id != v0.branch_id {
+                                return Err(VerifierError::InvalidBranch);
+                            }
+                            return verifier.refresh_branch_info(
+                                commit.branch(),
+                                store.get_store_repo(),
+                                branch,
+                                read_cap.clone(),
+                            );
+                        }
+                    }
+                }
+
                 // TODO fetch the readcap and verify that crdt and other infos in Branch definition are the same as in AddBranch commit
                 let branch_info = BranchInfo {
                     id: v0.branch_id,
@@ -347,7 +430,6 @@ impl CommitVerifier for AddSignerCap {
 }
 #[async_trait::async_trait]
 impl CommitVerifier for AddMember {
-    #[allow(unused_variables)]
     async fn verify(
         &self,
         commit: &Commit,
@@ -356,12 +438,22 @@ impl CommitVerifier for AddMember {
         repo_id: &RepoId,
         store: Arc<Store>,
     ) -> Result<(), VerifierError> {
-        Ok(())
+        if branch_id != repo_id {
+            return Err(VerifierError::InvalidBranch);
+        }
+        match self {
+            AddMember::V0(v0) => {
+                let repo = verifier.get_repo_mut(repo_id, store.get_store_repo())?;
+                repo.verify_permission(commit)?;
+                // the additional permissions are given with AddPermission commits
+                repo.add_member(&v0.member, &[]);
+                verifier.update_repo_members(repo_id, store.get_store_repo())
+            }
+        }
     }
 }
 #[async_trait::async_trait]
 impl CommitVerifier for RemoveMember {
-    #[allow(unused_variables)]
     async fn verify(
         &self,
         commit: &Commit,
@@ -370,12 +462,35 @@ impl CommitVerifier for RemoveMember {
         repo_id: &RepoId,
         store: Arc<Store>,
     ) -> Result<(), VerifierError> {
-        Ok(())
+        if branch_id != repo_id {
+            return Err(VerifierError::InvalidBranch);
+        }
+        // a RemoveMember is always behind a SyncSignature, which has been verified before its chain
+        match commit.quorum_type() {
+            QuorumType::Owners | QuorumType::TotalOrder => {}
+            _ => return Err(VerifierError::InvalidCommit),
+        }
+        let id = commit.id().ok_or(VerifierError::InvalidCommit)?;
+        if !verifier.temporary_signed_commits.contains(&id) {
+            return Err(VerifierError::InvalidSignatureObject);
+        }
+        match self {
+            RemoveMember::V0(v0) => {
+                let repo = verifier.get_repo_mut(repo_id, store.get_store_repo())?;
+                repo.verify_permission(commit)?;
+                if repo.is_owner(&v0.member) {
+                    return Err(VerifierError::PermissionDenied);
+                }
+                if repo.remove_member(&v0.member).is_none() {
+                    log_debug!("removed {} was not a member of {}", v0.member, repo_id);
+                }
+                verifier.update_repo_members(repo_id, store.get_store_repo())
+            }
+        }
     }
 }
 #[async_trait::async_trait]
 impl CommitVerifier for AddPermission {
-    #[allow(unused_variables)]
     async fn verify(
         &self,
         commit: &Commit,
@@ -384,7 +499,20 @@ impl CommitVerifier for AddPermission {
         repo_id: &RepoId,
         store: Arc<Store>,
     ) -> Result<(), VerifierError> {
-        Ok(())
+        if branch_id != repo_id {
+            return Err(VerifierError::InvalidBranch);
+        }
+        match self {
+            AddPermission::V0(v0) => {
+                if v0.permission == PermissionV0::Create || v0.permission == PermissionV0::Owner {
+                    return Err(VerifierError::PermissionDenied);
+                }
+                let repo = verifier.get_repo_mut(repo_id, store.get_store_repo())?;
+                repo.verify_permission(commit)?;
+                repo.add_member(&v0.member, &[v0.permission]);
+                verifier.update_repo_members(repo_id, store.get_store_repo())
+            }
+        }
     }
 }
 #[async_trait::async_trait]
@@ -547,6 +675,66 @@ impl CommitVerifier for RemoveFile {
         repo_id: &RepoId,
         store: Arc<Store>,
     ) -> Result<(), VerifierError> {
+        let nfiles = commit.nfiles();
+        if nfiles.is_empty() {
+            return Err(VerifierError::InvalidCommit);
+        }
+        let commit_id = commit.id().ok_or(VerifierError::InvalidCommit)?;
+        let user_storage = verifier
+            .user_storage
+            .as_ref()
+            .ok_or(VerifierError::StorageError(StorageError::NotFound))?;
+        let mut unreferenced = vec![];
+        for file_id in nfiles.iter() {
+            if user_storage
+                .branch_remove_file(branch_id, file_id)?
+                .is_some()
+                && !user_storage.file_is_referenced(file_id)?
+            {
+                unreferenced.push(*file_id);
+            }
+        }
+        let store_repo = store.get_store_repo();
+        let repo = verifier.get_repo(repo_id, store_repo)?;
+        let branch = repo.branch(branch_id)?;
+        let topic = branch.topic.clone().ok_or(VerifierError::TopicNotFound)?;
+        let overlay_id = store_repo.overlay_id_for_storage_purpose();
+        let previous_heads =
+            HashSet::from_iter(branch.current_heads.iter().map(|br| br.id.clone()));
+        let commit_info: CommitInfoJs = (&commit.as_info(repo)).into();
+        for file_id in nfiles {
+            verifier
+                .push_app_response(
+                    branch_id,
+                    AppResponse::V0(AppResponseV0::Patch(AppPatch {
+                        commit_id: commit_id.to_string(),
+                        commit_info: commit_info.clone(),
+                        graph: None,
+                        discrete: None,
+                        other: Some(OtherPatch::FileRemove(file_id)),
+                    })),
+                )
+                .await;
+        }
+        verifier.advance_head_without_graph(&topic, &overlay_id, &commit_id, previous_heads)?;
+
+        // the local blocks of the files that are not referenced anymore in any of our branches are freed.
+        // The broker is asked to unpin them by the device that sends this commit.
+        if unreferenced.is_empty() {
+            return Ok(());
+        }
+        let repo = verifier.get_repo(repo_id, store_repo)?;
+        // the blocks of the files that are still referenced are listed once for all the files of the commit
+        match verifier.referenced_blocks(repo) {
+            Err(e) => log_err!("could not list the blocks of the referenced files: {}", e),
+            Ok(kept) => {
+                for file_id in unreferenced {
+                    if let Err(e) = verifier.free_file(&file_id, repo, &kept) {
+                        log_err!("could not free file {}: {}", file_id, e);
+                    }
+                }
+            }
+        }
         Ok(())
     }
 }
@@ -614,29 +802,62 @@ impl CommitVerifier for AsyncSignature {
 }
 #[async_trait::async_trait]
 impl CommitVerifier for RootCapRefresh {
-    #[allow(unused_variables)]
     async fn verify(
         &self,
         commit: &Commit,
         verifier: &mut Verifier,
-        branch_id: &BranchId,
+        _branch_id: &BranchId,
         repo_id: &RepoId,
         store: Arc<Store>,
     ) -> Result<(), VerifierError> {
-        Ok(())
+        // the RootCapRefresh is sent in its own event, before the SyncSignature that signs it and verifies it again.
+        // Until then, its refresh secret is only kept to open the events of the refresh, which are encrypted with it.
+        let id = commit.id().ok_or(VerifierError::InvalidCommit)?;
+        if !verifier.temporary_signed_commits.contains(&id) {
+            verifier.receive_unsigned_refresh_cap(self.refresh_ref(), repo_id, &store)?;
+            return Err(VerifierError::InvalidSignatureObject);
+        }
+        verifier.receive_refresh_cap(self.refresh_ref(), self.write_cap(), repo_id, &store)
     }
 }
 #[async_trait::async_trait]
 impl CommitVerifier for BranchCapRefresh {
-    #[allow(unused_variables)]
     async fn verify(
         &self,
-        commit: &Commit,
+        _commit: &Commit,
+        verifier: &mut Verifier,
+        _branch_id: &BranchId,
+        repo_id: &RepoId,
+        store: Arc<Store>,
+    ) -> Result<(), VerifierError> {
+        // the RootCapRefresh of the same refresh, if any, has already given us the refresh secret and the renewed write cap
+        let already_received = verifier
+            .refresh_secrets
+            .get(repo_id)
+            .map_or(false, |(refresh_id, ..)| {
+                *refresh_id == self.refresh_ref().id
+            });
+        if !already_received {
+            verifier.receive_refresh_cap(self.refresh_ref(), None, repo_id, &store)?;
+        }
+        Ok(())
+    }
+}
+#[async_trait::async_trait]
+impl CommitVerifier for BranchCapRefreshed {
+    async fn verify(
+        &self,
+        _commit: &Commit,
         verifier: &mut Verifier,
         branch_id: &BranchId,
         repo_id: &RepoId,
         store: Arc<Store>,
     ) -> Result<(), VerifierError> {
+        let repo = verifier.get_repo(repo_id, store.get_store_repo())?;
+        let branch = repo.branch(branch_id)?;
+        if branch.read_cap.as_ref() != Some(self.new_read_cap()) {
+            return Err(VerifierError::InvalidBranch);
+        }
         Ok(())
     }
 }
@@ -733,3 +954,567 @@ impl CommitVerifier for WalletUpdate {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ng_repo::repo::UserInfo;
+    use ng_repo::utils::generate_keypair;
+
+    fn verifier_with_repo(owner: &UserId, owner_priv: &PrivKey) -> (Verifier, RepoId, Arc<Store>) {
+        let store = Store::dummy_public_v0();
+        let (mut repo, _) = Arc::clone(&store)
+            .create_repo_default(
+                owner,
+                owner_priv,
+                SymKey::random(),
+                BranchCrdt::Graph("data:graph".to_string()),
+            )
+            .unwrap();
+        repo.set_owners(&[*owner]);
+        let repo_id = repo.id;
+        let mut verifier = Verifier::new_dummy();
+        verifier.repos.insert(repo_id, repo);
+        (verifier, repo_id, store)
+    }
+
+    fn new_commit(
+        author_priv: &PrivKey,
+        author: &UserId,
+        repo_id: &RepoId,
+        quorum: QuorumType,
+        body: CommitBodyV0,
+        store: &Store,
+    ) -> Commit {
+        Commit::new_with_body_acks_deps_and_save(
+            author_priv,
+            author,
+            *repo_id,
+            quorum,
+            vec![],
+            vec![],
+            CommitBody::V0(body),
+            store,
+        )
+        .unwrap()
+    }
+
+    fn member(verifier: &Verifier, repo_id: &RepoId, user: &UserId) -> Option<UserInfo> {
+        verifier.repos[repo_id]
+            .members
+            .values()
+            .find(|m| m.id == *user)
+            .cloned()
+    }
+
+    #[async_std::test]
+    pub async fn test_add_member_and_permission() {
+        let (owner_priv, owner) = generate_keypair();
+        let (editor_priv, editor) = generate_keypair();
+        let (reader, _) = generate_keypair();
+        let (mut verifier, repo_id, store) = verifier_with_repo(&owner, &owner_priv);
+
+        // only the members with the AddReadMember permission can add members
+        let add_editor = AddMember::V0(AddMemberV0 {
+            member: editor,
+            metadata: vec![],
+        });
+        let commit = new_commit(
+            &editor_priv,
+            &editor,
+            &repo_id,
+            QuorumType::NoSigning,
+            CommitBodyV0::AddMember(add_editor.clone()),
+            &store,
+        );
+        assert!(matches!(
+            add_editor
+                .verify(
+                    &commit,
+                    &mut verifier,
+                    &repo_id,
+                    &repo_id,
+                    Arc::clone(&store)
+                )
+                .await,
+            Err(VerifierError::PermissionDenied)
+        ));
+        assert!(member(&verifier, &repo_id, &editor).is_none());
+
+        let commit = new_commit(
+            &owner_priv,
+            &owner,
+            &repo_id,
+            QuorumType::NoSigning,
+            CommitBodyV0::AddMember(add_editor.clone()),
+            &store,
+        );
+        add_editor
+            .verify(
+                &commit,
+                &mut verifier,
+                &repo_id,
+                &repo_id,
+                Arc::clone(&store),
+            )
+            .await
+            .unwrap();
+        let info = member(&verifier, &repo_id, &editor).unwrap();
+        assert!(info.permissions.is_empty());
+        assert!(!info.can_edit());
+
+        // a reader cannot give permissions
+        let add_write = AddPermission::V0(AddPermissionV0 {
+            member: editor,
+            permission: PermissionV0::WriteAsync,
+            metadata: vec![],
+        });
+        let commit = new_commit(
+            &editor_priv,
+            &editor,
+            &repo_id,
+            QuorumType::NoSigning,
+            CommitBodyV0::AddPermission(add_write.clone()),
+            &store,
+        );
+        assert!(matches!(
+            add_write
+                .verify(
+                    &commit,
+                    &mut verifier,
+                    &repo_id,
+                    &repo_id,
+                    Arc::clone(&store)
+                )
+                .await,
+            Err(VerifierError::PermissionDenied)
+        ));
+
+        let commit = new_commit(
+            &owner_priv,
+            &owner,
+            &repo_id,
+            QuorumType::NoSigning,
+            CommitBodyV0::AddPermission(add_write.clone()),
+            &store,
+        );
+        add_write
+            .verify(
+                &commit,
+                &mut verifier,
+                &repo_id,
+                &repo_id,
+                Arc::clone(&store),
+            )
+            .await
+            .unwrap();
+        let info = member(&verifier, &repo_id, &editor).unwrap();
+        assert!(info.has_perm(&PermissionV0::WriteAsync).is_ok());
+        assert!(info.can_edit());
+
+        // nobody can be made owner with an AddPermission
+        let add_owner = AddPermission::V0(AddPermissionV0 {
+            member: reader,
+            permission: PermissionV0::Owner,
+            metadata: vec![],
+        });
+        let commit = new_commit(
+            &owner_priv,
+            &owner,
+            &repo_id,
+            QuorumType::NoSigning,
+            CommitBodyV0::AddPermission(add_owner.clone()),
+            &store,
+        );
+        assert!(matches!(
+            add_owner
+                .verify(
+                    &commit,
+                    &mut verifier,
+                    &repo_id,
+                    &repo_id,
+                    Arc::clone(&store)
+                )
+                .await,
+            Err(VerifierError::PermissionDenied)
+        ));
+        assert!(member(&verifier, &repo_id, &reader).is_none());
+    }
+
+    #[async_std::test]
+    pub async fn test_remove_member_needs_signature() {
+        let (owner_priv, owner) = generate_keypair();
+        let (removed, _) = generate_keypair();
+        let (mut verifier, repo_id, store) = verifier_with_repo(&owner, &owner_priv);
+        verifier
+            .repos
+            .get_mut(&repo_id)
+            .unwrap()
+            .add_member(&removed, &[PermissionV0::WriteAsync]);
+
+        let remove = RemoveMember::V0(RemoveMemberV0 {
+            member: removed,
+            banned: false,
+            metadata: vec![],
+        });
+        let commit = new_commit(
+            &owner_priv,
+            &owner,
+            &repo_id,
+            QuorumType::Owners,
+            CommitBodyV0::RemoveMember(remove.clone()),
+            &store,
+        );
+
+        // a RemoveMember that is not covered by a verified SyncSignature is rejected
+        assert!(matches!(
+            remove
+                .verify(
+                    &commit,
+                    &mut verifier,
+                    &repo_id,
+                    &repo_id,
+                    Arc::clone(&store)
+                )
+                .await,
+            Err(VerifierError::InvalidSignatureObject)
+        ));
+        assert!(member(&verifier, &repo_id, &removed).is_some());
+
+        verifier
+            .temporary_signed_commits
+            .insert(commit.id().unwrap());
+        remove
+            .verify(
+                &commit,
+                &mut verifier,
+                &repo_id,
+                &repo_id,
+                Arc::clone(&store),
+            )
+            .await
+            .unwrap();
+        assert!(member(&verifier, &repo_id, &removed).is_none());
+
+        // the owners cannot be removed
+        let remove_owner = RemoveMember::V0(RemoveMemberV0 {
+            member: owner,
+            banned: false,
+            metadata: vec![],
+        });
+        let commit = new_commit(
+            &owner_priv,
+            &owner,
+            &repo_id,
+            QuorumType::Owners,
+            CommitBodyV0::RemoveMember(remove_owner.clone()),
+            &store,
+        );
+        verifier
+            .temporary_signed_commits
+            .insert(commit.id().unwrap());
+        assert!(matches!(
+            remove_owner
+                .verify(
+                    &commit,
+                    &mut verifier,
+                    &repo_id,
+                    &repo_id,
+                    Arc::clone(&store)
+                )
+                .await,
+            Err(VerifierError::PermissionDenied)
+        ));
+    }
+
+    #[test]
+    pub fn test_verify_owners_signature() {
+        let (owner_priv, owner) = generate_keypair();
+        let (removed, _) = generate_keypair();
+        let (verifier, repo_id, store) = verifier_with_repo(&owner, &owner_priv);
+        let removed_member = RemoveMemberV0 {
+            member: removed,
+            banned: false,
+            metadata: vec![],
+        };
+        let refresh = store
+            .refresh_repo_caps(
+                &verifier.repos[&repo_id],
+                &owner,
+                &owner_priv,
+                &[(owner, true)],
+                &[removed_member],
+            )
+            .unwrap();
+        let (sync_sig, _, _) = refresh
+            .events
+            .iter()
+            .find(|(commit, _, _)| {
+                *commit.branch() == repo_id
+                    && matches!(
+                        commit.body(),
+                        Some(CommitBody::V0(CommitBodyV0::SyncSignature(_)))
+                    )
+            })
+            .unwrap();
+        let signature = match sync_sig.body() {
+            Some(CommitBody::V0(CommitBodyV0::SyncSignature(s))) => {
+                match Object::load_ref(s.reference(), &store)
+                    .unwrap()
+                    .content_v0()
+                    .unwrap()
+                {
+                    ObjectContentV0::Signature(sig) => sig,
+                    _ => panic!("expected a Signature"),
+                }
+            }
+            _ => unreachable!(),
+        };
+
+        verify_owners_signature(&signature, &repo_id, &store).unwrap();
+
+        // a signature over other commits is rejected
+        let Signature::V0(mut forged) = signature.clone();
+        forged.content = SignatureContent::V0(SignatureContentV0 {
+            commits: vec![Digest::from_slice([0; 32])],
+        });
+        assert!(matches!(
+            verify_owners_signature(&Signature::V0(forged), &repo_id, &store),
+            Err(VerifierError::InvalidSignatureObject)
+        ));
+
+        // and so is a signature verified against another repo
+        let (other_repo, _) = generate_keypair();
+        assert!(verify_owners_signature(&signature, &other_repo, &store).is_err());
+    }
+
+    #[async_std::test]
+    pub async fn test_remove_file() {
+        use crate::user_storage::{InMemoryUserStorage, UserStorage};
+        use ng_repo::file::{RandomAccessFile, ReadFile};
+
+        let (owner_priv, owner) = generate_keypair();
+        let (mut verifier, repo_id, store) = verifier_with_repo(&owner, &owner_priv);
+        verifier.graph_dataset = Some(ng_oxigraph::oxigraph::store::Store::new().unwrap());
+        verifier.user_storage = Some(Arc::new(
+            Box::new(InMemoryUserStorage::new()) as Box<dyn UserStorage>
+        ));
+        let branch_id = verifier.repos[&repo_id].main_branch().unwrap().id;
+
+        // two files that share their first leaves
+        let mut shared = vec![1u8; 3 * 4096];
+        let mut content_a = shared.clone();
+        content_a.extend_from_slice(&[2u8; 10]);
+        shared.extend_from_slice(&[3u8; 10]);
+        let new_file = |content: &[u8]| {
+            let file = RandomAccessFile::new_from_slice(
+                content,
+                0,
+                "text/plain".to_string(),
+                vec![],
+                Arc::clone(&store),
+            )
+            .unwrap();
+            let mut blocks = file.get_all_blocks_ids().unwrap();
+            blocks.push(file.id().unwrap());
+            (file.reference().unwrap(), blocks)
+        };
+        let (file_a, blocks_a) = new_file(&content_a);
+        let (file_b, blocks_b) = new_file(&shared);
+        assert!(blocks_a.iter().any(|b| blocks_b.contains(b)));
+
+        let user_storage = Arc::clone(verifier.user_storage.as_ref().unwrap());
+        for file in [&file_a, &file_b] {
+            user_storage
+                .branch_add_file(
+                    file.id,
+                    branch_id,
+                    FileName {
+                        name: None,
+                        reference: file.clone(),
+                        nuri: NuriV0::object_ref(file),
+                    },
+                )
+                .unwrap();
+        }
+
+        let remove = RemoveFile::V0(());
+        let commit = Commit::new_with_body_and_save(
+            &owner_priv,
+            &owner,
+            branch_id,
+            QuorumType::NoSigning,
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![file_a.clone()],
+            vec![],
+            CommitBody::V0(CommitBodyV0::RemoveFile(remove.clone())),
+            0,
+            &store,
+        )
+        .unwrap();
+        remove
+            .verify(
+                &commit,
+                &mut verifier,
+                &branch_id,
+                &repo_id,
+                Arc::clone(&store),
+            )
+            .await
+            .unwrap();
+
+        let files = user_storage.branch_get_all_files(&branch_id).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].reference.id, file_b.id);
+
+        // the blocks shared with the file that is still referenced are kept, the others are freed
+        for block_id in blocks_b.iter() {
+            assert!(store.get(block_id).is_ok());
+        }
+        for block_id in blocks_a.iter().filter(|b| !blocks_b.contains(b)) {
+            assert!(matches!(store.get(block_id), Err(StorageError::NotFound)));
+        }
+    }
+
+    fn refresh_cap_ref(verifier: &Verifier, secret: &RefreshSecretV0, store: &Store) -> ObjectRef {
+        let refresh_cap = RefreshCap::new(
+            &[(*verifier.user_id(), true)],
+            secret.read_secret(),
+            secret.write_secret(),
+            store.get_store_repo().overlay_id_for_read_purpose(),
+        )
+        .unwrap();
+        let object = Object::new(
+            ObjectContent::V0(ObjectContentV0::RefreshCap(refresh_cap)),
+            None,
+            0,
+            store,
+        );
+        object.save(store).unwrap();
+        object.reference().unwrap()
+    }
+
+    #[async_std::test]
+    pub async fn test_cap_refresh_needs_signature() {
+        let (owner_priv, owner) = generate_keypair();
+        let (mut verifier, repo_id, store) = verifier_with_repo(&owner, &owner_priv);
+
+        let secret = RefreshSecretV0::new(SymKey::random(), Some(SymKey::random()));
+        let refresh_ref = refresh_cap_ref(&verifier, &secret, &store);
+        let write_cap = SymKey::random();
+        let root_cap_refresh = RootCapRefresh::V0(RootCapRefreshV0 {
+            refresh_ref: refresh_ref.clone(),
+            write_cap: Some(RootCapRefresh::encrypt_write_cap(
+                &write_cap,
+                secret.write_secret().unwrap(),
+            )),
+        });
+        let commit = new_commit(
+            &owner_priv,
+            &owner,
+            &repo_id,
+            QuorumType::Owners,
+            CommitBodyV0::RootCapRefresh(root_cap_refresh.clone()),
+            &store,
+        );
+
+        // before its SyncSignature, only the secret to open the events of the refresh is kept
+        assert!(matches!(
+            root_cap_refresh
+                .verify(
+                    &commit,
+                    &mut verifier,
+                    &repo_id,
+                    &repo_id,
+                    Arc::clone(&store)
+                )
+                .await,
+            Err(VerifierError::InvalidSignatureObject)
+        ));
+        assert!(verifier.refresh_secrets.get(&repo_id).is_none());
+        assert_eq!(
+            verifier.pending_refresh_secrets.get(&repo_id),
+            Some(&secret)
+        );
+
+        verifier
+            .temporary_signed_commits
+            .insert(commit.id().unwrap());
+        root_cap_refresh
+            .verify(
+                &commit,
+                &mut verifier,
+                &repo_id,
+                &repo_id,
+                Arc::clone(&store),
+            )
+            .await
+            .unwrap();
+        verifier.temporary_signed_commits.clear();
+        assert_eq!(
+            verifier.refresh_secrets.get(&repo_id),
+            Some(&(refresh_ref.id, secret.clone(), Some(write_cap)))
+        );
+        assert!(verifier.pending_refresh_secrets.get(&repo_id).is_none());
+
+        // an UpdateRootBranch must be signed too
+        let read_cap = verifier.repos[&repo_id].read_cap.clone().unwrap();
+        let root_branch = match Commit::load(read_cap, &store, true).unwrap().body() {
+            Some(CommitBody::V0(CommitBodyV0::RootBranch(root_branch))) => root_branch.clone(),
+            _ => panic!("expected a RootBranch"),
+        };
+        let commit = new_commit(
+            &owner_priv,
+            &owner,
+            &repo_id,
+            QuorumType::Owners,
+            CommitBodyV0::UpdateRootBranch(root_branch.clone()),
+            &store,
+        );
+        assert!(matches!(
+            verifier
+                .verify_update_root_branch(
+                    &root_branch,
+                    &commit,
+                    &repo_id,
+                    &repo_id,
+                    Arc::clone(&store)
+                )
+                .await,
+            Err(VerifierError::InvalidSignatureObject)
+        ));
+
+        // the BranchCapRefresh of a later refresh is not ignored
+        let later_secret = RefreshSecretV0::new(SymKey::random(), None);
+        let later_ref = refresh_cap_ref(&verifier, &later_secret, &store);
+        let branch_cap_refresh = BranchCapRefresh::V0(BranchCapRefreshV0 {
+            refresh_ref: later_ref.clone(),
+        });
+        let commit = new_commit(
+            &owner_priv,
+            &owner,
+            &repo_id,
+            QuorumType::Owners,
+            CommitBodyV0::BranchCapRefresh(branch_cap_refresh.clone()),
+            &store,
+        );
+        branch_cap_refresh
+            .verify(
+                &commit,
+                &mut verifier,
+                &repo_id,
+                &repo_id,
+                Arc::clone(&store),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            verifier.refresh_secrets.get(&repo_id),
+            Some(&(later_ref.id, later_secret, None))
+        );
+    }
+}
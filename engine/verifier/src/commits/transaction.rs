@@ -31,6 +31,63 @@ use ng_repo::types::*;
 use crate::types::*;
 use crate::verifier::Verifier;
 
+fn skolem_iri(
+    blank_node: &BlankNode,
+    repo_id: &RepoId,
+    peer_id: &Vec<u8>,
+) -> Result<NamedNode, VerifierError> {
+    let unique_id = blank_node
+        .as_ref()
+        .unique_id()
+        .ok_or(VerifierError::InvalidTriple)?;
+    let iri = NuriV0::repo_skolem(repo_id, peer_id, unique_id)?;
+    Ok(NamedNode::new_unchecked(iri))
+}
+
+/// Replaces the blank nodes of the subject, including those of a quoted triple, with skolem IRIs.
+/// Returns true if a blank node was replaced.
+fn skolemize_subject(
+    subject: &mut Subject,
+    repo_id: &RepoId,
+    peer_id: &Vec<u8>,
+) -> Result<bool, VerifierError> {
+    match subject {
+        Subject::BlankNode(b) => {
+            *subject = Subject::NamedNode(skolem_iri(b, repo_id, peer_id)?);
+            Ok(true)
+        }
+        Subject::Triple(triple) => skolemize_triple(triple, repo_id, peer_id),
+        Subject::NamedNode(_) => Ok(false),
+    }
+}
+
+/// Replaces the blank nodes of the object, including those of a quoted triple, with skolem IRIs.
+/// Returns true if a blank node was replaced.
+fn skolemize_term(
+    term: &mut Term,
+    repo_id: &RepoId,
+    peer_id: &Vec<u8>,
+) -> Result<bool, VerifierError> {
+    match term {
+        Term::BlankNode(b) => {
+            *term = Term::NamedNode(skolem_iri(b, repo_id, peer_id)?);
+            Ok(true)
+        }
+        Term::Triple(triple) => skolemize_triple(triple, repo_id, peer_id),
+        Term::NamedNode(_) | Term::Literal(_) => Ok(false),
+    }
+}
+
+fn skolemize_triple(
+    triple: &mut Triple,
+    repo_id: &RepoId,
+    peer_id: &Vec<u8>,
+) -> Result<bool, VerifierError> {
+    let subject = skolemize_subject(&mut triple.subject, repo_id, peer_id)?;
+    let object = skolemize_term(&mut triple.object, repo_id, peer_id)?;
+    Ok(subject || object)
+}
+
 #[derive(Debug)]
 struct BranchUpdateInfo {
     branch_id: BranchId,
@@ -175,6 +232,7 @@ impl Verifier {
         patch: DiscreteTransaction,
         crdt: &BranchCrdt,
         branch_id: &BranchId,
+        repo_id: &RepoId,
         commit_id: ObjectId,
         commit_info: CommitInfoJs,
         from_orm: Option<Vec<u8>>,
@@ -239,6 +297,8 @@ impl Verifier {
             }
         };
 
+        self.text_index_discrete_state(repo_id, branch_id, crdt, &new_state);
+
         // SAVING NEW STATE
 
         self.user_storage
@@ -340,8 +400,16 @@ impl Verifier {
                 previous_heads,
             )?;
 
-            self.process_discrete(patch, &crdt, branch_id, commit_id, commit_info, None)
-                .await?;
+            self.process_discrete(
+                patch,
+                &crdt,
+                branch_id,
+                repo_id,
+                commit_id,
+                commit_info,
+                None,
+            )
+            .await?;
         }
 
         Ok(())
@@ -498,29 +566,17 @@ impl Verifier {
 
             // changing blank node to skolemized node
 
+            // also done inside the quoted triples (RDF-STAR)
+
             //log_debug!("INSERTING BN {}", quad);
-            if insert.subject.is_blank_node() {
-                //log_debug!("INSERTING SUBJECT BN {}", insert.subject);
-                if let Subject::BlankNode(b) = &insert.subject {
-                    let iri =
-                        NuriV0::repo_skolem(&repo_id, &peer_id, b.as_ref().unique_id().unwrap())?;
-                    insert.subject = Subject::NamedNode(NamedNode::new_unchecked(iri));
-                }
-            }
-            if insert.object.is_blank_node() {
-                //log_debug!("INSERTING OBJECT BN {}", insert.object);
-                if let Term::BlankNode(b) = &insert.object {
-                    let iri =
-                        NuriV0::repo_skolem(&repo_id, &peer_id, b.as_ref().unique_id().unwrap())?;
-                    insert.object = Term::NamedNode(NamedNode::new_unchecked(iri));
-                    skolemnized_blank_nodes.push(insert.clone());
-                }
+            skolemize_subject(&mut insert.subject, &repo_id, &peer_id)?;
+            if skolemize_term(&mut insert.object, &repo_id, &peer_id)? {
+                skolemnized_blank_nodes.push(insert.clone());
             }
-            // TODO deal with triples in subject and object (RDF-STAR)
 
             set.insert(insert.into());
         }
-        for remove in removes {
+        for mut remove in removes {
             let (repo_id, branch_id, is_publisher) =
                 self.find_branch_and_repo_for_quad(&remove, &mut branches, &mut nuri_branches)?;
             if !is_publisher {
@@ -532,6 +588,12 @@ impl Verifier {
                 removes_len = 1;
                 set
             });
+
+            // the blank nodes were skolemized when inserted, so the removed triple has to
+            // use the same IRIs, also inside the quoted triples
+            skolemize_subject(&mut remove.subject, &repo_id, &peer_id)?;
+            skolemize_term(&mut remove.object, &repo_id, &peer_id)?;
+
             set.insert(remove.into());
         }
 
@@ -842,6 +904,9 @@ impl Verifier {
             Ok(()) => {
                 let mut commit_nuris = Vec::with_capacity(updates.len());
                 for update in updates {
+                    if update.branch_type.is_main() || update.branch_type.is_header() {
+                        self.text_index_graph_transaction(&update.repo_id, &update.transaction);
+                    }
                     if update.branch_type.is_header() {
                         let mut tab_doc_info = AppTabDocInfo::new();
                         for removed in update.transaction.removes {
@@ -898,6 +963,7 @@ impl Verifier {
                             subscription_id,
                             update.repo_id.clone(),
                             update.overlay_id,
+                            update.commit_id,
                             update.transaction.as_quads_patch(graph_nuri),
                         )
                         .await;
@@ -950,10 +1016,59 @@ impl Verifier {
 #[cfg(test)]
 mod test {
 
-    use super::{TransactionBody, TransactionBodyType};
+    use super::{skolemize_subject, skolemize_term, TransactionBody, TransactionBodyType};
+    use ng_net::app_protocol::NuriV0;
+    use ng_oxigraph::oxrdf::{BlankNode, Literal, NamedNode, Subject, Term, Triple};
     use ng_repo::log::*;
+    use ng_repo::types::PubKey;
     use serde_bare::to_vec;
 
+    #[test]
+    pub fn test_skolemize_nested_quoted_triples() {
+        let repo_id = PubKey::Ed25519PubKey([1; 32]);
+        let peer_id = vec![2; 16];
+        let p = NamedNode::new_unchecked("did:ng:x:p");
+        let o = NamedNode::new_unchecked("did:ng:x:o");
+        let sko = |id: u128| {
+            NamedNode::new_unchecked(NuriV0::repo_skolem(&repo_id, &peer_id, id).unwrap())
+        };
+
+        // << << _:b1 p _:b2 >> p "lit" >> p << o p _:b3 >>
+        let inner = Triple::new(
+            BlankNode::new_from_unique_id(1),
+            p.clone(),
+            BlankNode::new_from_unique_id(2),
+        );
+        let middle = Triple::new(inner, p.clone(), Literal::new_simple_literal("lit"));
+        let object = Triple::new(o.clone(), p.clone(), BlankNode::new_from_unique_id(3));
+        let mut subject = Subject::Triple(Box::new(middle));
+        let mut term = Term::Triple(Box::new(object));
+
+        assert!(skolemize_subject(&mut subject, &repo_id, &peer_id).unwrap());
+        assert!(skolemize_term(&mut term, &repo_id, &peer_id).unwrap());
+
+        let expected_inner = Triple::new(sko(1), p.clone(), sko(2));
+        let expected_middle = Triple::new(
+            expected_inner,
+            p.clone(),
+            Literal::new_simple_literal("lit"),
+        );
+        assert_eq!(subject, Subject::Triple(Box::new(expected_middle)));
+        let expected_object = Triple::new(o.clone(), p.clone(), sko(3));
+        assert_eq!(term, Term::Triple(Box::new(expected_object)));
+
+        // skolemizing twice is a no-op, so removes of already skolemized triples are kept as is
+        assert!(!skolemize_subject(&mut subject, &repo_id, &peer_id).unwrap());
+        assert!(!skolemize_term(&mut term, &repo_id, &peer_id).unwrap());
+
+        let mut no_blank = Term::Triple(Box::new(Triple::new(o.clone(), p.clone(), o)));
+        assert!(!skolemize_term(&mut no_blank, &repo_id, &peer_id).unwrap());
+
+        // a blank node without a numeric id cannot be skolemized
+        let mut named_blank = Term::BlankNode(BlankNode::new_unchecked("named"));
+        assert!(skolemize_term(&mut named_blank, &repo_id, &peer_id).is_err());
+    }
+
     #[test]
     pub fn test_transaction_body() {
         let body = TransactionBody {
@@ -0,0 +1,334 @@
+// Copyright (c) 2022-2025 Niko Bonnieure, Par le Peuple, NextGraph.org developers
+// All rights reserved.
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE2 or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Export of the graph of documents, branches, stores or of the whole user site, in an RDF serialization
+
+use std::io::Write;
+
+use ng_oxigraph::oxigraph::sparql::{Query, QueryResults};
+use ng_oxigraph::oxigraph::store::Store;
+use ng_oxigraph::oxrdf::{GraphNameRef, NamedNode, NamedNodeRef, Quad, Triple};
+use ng_oxigraph::oxrdfio::{RdfFormat, RdfSerializer, ToWriteQuadWriter};
+
+use ng_repo::errors::*;
+#[allow(unused_imports)]
+use ng_repo::log::*;
+
+use ng_net::app_protocol::*;
+#[cfg(target_arch = "wasm32")]
+use ng_net::utils::spawn_and_log_error;
+use ng_net::utils::Sender;
+
+use crate::verifier::*;
+
+/// Size of the FileBinary chunks sent while exporting
+const EXPORT_CHUNK_SIZE: usize = 1048564;
+
+/// Buffers the serialized output and sends it as FileBinary chunks on the stream
+pub(crate) struct ChunkSender {
+    tx: Sender<AppResponse>,
+    buffer: Vec<u8>,
+}
+
+impl ChunkSender {
+    pub(crate) fn new(tx: Sender<AppResponse>) -> Self {
+        Self {
+            tx,
+            buffer: Vec::with_capacity(EXPORT_CHUNK_SIZE),
+        }
+    }
+
+    fn send_buffer(&mut self) -> std::io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::replace(&mut self.buffer, Vec::with_capacity(EXPORT_CHUNK_SIZE));
+        self.tx
+            .unbounded_send(AppResponse::V0(AppResponseV0::FileBinary(chunk)))
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "stream closed"))
+    }
+}
+
+impl Write for ChunkSender {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= EXPORT_CHUNK_SIZE {
+            self.send_buffer()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.send_buffer()
+    }
+}
+
+/// Serializes its content one item at a time into a ChunkSender, so the output can be streamed
+pub(crate) trait ChunkWriter {
+    /// Writes the next item. Returns false when everything has been written.
+    fn write_next(&mut self) -> Result<bool, String>;
+
+    fn finish(self) -> Result<ChunkSender, String>;
+}
+
+/// Number of items written between two checks that the stream is still open
+const ITEMS_BETWEEN_CHECKS: usize = 1000;
+
+/// Creates a writer with `new_writer` in a spawned task, and sends all its output on `tx` as FileBinary chunks,
+/// followed by an EndOfStream (preceded by an error response if it failed).
+///
+/// The writer is created inside the task, as the iterators of the graph dataset cannot be sent to another thread.
+/// The task stops early if the stream is closed.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn spawn_chunk_writer<W, F>(new_writer: F, tx: Sender<AppResponse>)
+where
+    W: ChunkWriter,
+    F: FnOnce(ChunkSender) -> Result<W, String> + Send + 'static,
+{
+    async_std::task::spawn_blocking(move || {
+        let res = new_writer(ChunkSender::new(tx.clone())).and_then(|mut writer| {
+            let mut written = 0;
+            while writer.write_next()? {
+                written += 1;
+                if written % ITEMS_BETWEEN_CHECKS == 0 && tx.is_closed() {
+                    return Ok(());
+                }
+            }
+            writer.finish()?.flush().map_err(|e| e.to_string())
+        });
+        end_of_stream(res, tx);
+    });
+}
+
+/// Creates a writer with `new_writer` in a local task, and sends all its output on `tx` as FileBinary chunks,
+/// followed by an EndOfStream (preceded by an error response if it failed).
+///
+/// The task yields regularly, so the chunks are consumed while the rest is being written,
+/// instead of being all buffered in the channel. It stops early if the stream is closed.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn spawn_chunk_writer<W, F>(new_writer: F, tx: Sender<AppResponse>)
+where
+    W: ChunkWriter + 'static,
+    F: FnOnce(ChunkSender) -> Result<W, String> + 'static,
+{
+    spawn_and_log_error(async move {
+        let res = match new_writer(ChunkSender::new(tx.clone())) {
+            Err(e) => Err(e),
+            Ok(mut writer) => {
+                let mut written = 0;
+                loop {
+                    match writer.write_next() {
+                        Err(e) => break Err(e),
+                        Ok(false) => {
+                            break writer
+                                .finish()
+                                .and_then(|mut chunks| chunks.flush().map_err(|e| e.to_string()))
+                        }
+                        Ok(true) => {
+                            written += 1;
+                            if written % ITEMS_BETWEEN_CHECKS == 0 {
+                                if tx.is_closed() {
+                                    return Ok(());
+                                }
+                                async_std::task::yield_now().await;
+                            }
+                        }
+                    }
+                }
+            }
+        };
+        end_of_stream(res, tx);
+        Ok(())
+    });
+}
+
+pub(crate) fn end_of_stream(res: Result<(), String>, tx: Sender<AppResponse>) {
+    if let Err(e) = res {
+        let _ = tx.unbounded_send(AppResponse::error(e));
+    }
+    let _ = tx.unbounded_send(AppResponse::V0(AppResponseV0::EndOfStream));
+    tx.close_channel();
+}
+
+/// What is exported, once the Nuri has been resolved
+pub(crate) enum ExportScope {
+    /// The whole dataset of the user
+    UserSite,
+    /// The current state of a graph of the dataset (the main branch of a document)
+    Graph(String),
+    /// A graph that needs the dataset view to be resolved (a branch, or the state at some heads)
+    View(String),
+    /// Several documents, each one in its own named graph
+    Graphs(Vec<String>),
+}
+
+impl Verifier {
+    pub(crate) fn resolve_export_scope(&self, nuri: &NuriV0) -> Result<ExportScope, VerifierError> {
+        if nuri.target == NuriTargetV0::UserSite {
+            return Ok(ExportScope::UserSite);
+        }
+        let (repo_id, _, store_repo) = self.resolve_target(&nuri.target)?;
+        let repo = self.get_repo(&repo_id, &store_repo)?;
+        let overlay_id = repo.store.overlay_id;
+        let repo_graph = NuriV0::repo_graph_name(&repo_id, &overlay_id);
+
+        if nuri.entire_store {
+            if !repo_id.eq(store_repo.repo_id()) {
+                return Err(VerifierError::InvalidNuri);
+            }
+            let mut graphs: Vec<String> = self
+                .repos
+                .iter()
+                .filter(|(id, r)| {
+                    r.store.get_store_repo() == &store_repo && !id.eq(&store_repo.repo_id())
+                })
+                .map(|(id, r)| NuriV0::repo_graph_name(id, &r.store.overlay_id))
+                .collect();
+            graphs.sort();
+            graphs.insert(0, repo_graph);
+            return Ok(ExportScope::Graphs(graphs));
+        }
+
+        Ok(match &nuri.branch {
+            None => ExportScope::Graph(repo_graph),
+            Some(TargetBranchV0::BranchId(branch_id)) => {
+                repo.branch(branch_id)?;
+                ExportScope::View(NuriV0::branch_repo_graph_name(
+                    branch_id,
+                    &repo_id,
+                    &overlay_id,
+                ))
+            }
+            Some(TargetBranchV0::Commits(heads)) => {
+                if heads.is_empty() {
+                    return Err(VerifierError::InvalidNuri);
+                }
+                let mut graph = repo_graph;
+                for head in heads {
+                    graph.push_str(&format!(":c:{head}"));
+                }
+                ExportScope::View(graph)
+            }
+            Some(_) => return Err(VerifierError::NotImplemented),
+        })
+    }
+
+    /// Serializes the graph targeted by the nuri and sends it on `tx` as FileBinary chunks, followed by an EndOfStream.
+    ///
+    /// A document (main branch, a branch, or a set of heads) is exported as a single graph,
+    /// so any format can be used, and the triples are written in the default graph.
+    /// A store with `entire_store` and the whole user site keep their named graphs, and need a dataset format (TriG, N-Quads).
+    /// The serialization runs in a spawned task, so the chunks can be consumed while the rest is being exported.
+    pub(crate) fn rdf_export(
+        &self,
+        nuri: &NuriV0,
+        content_type: &str,
+        tx: Sender<AppResponse>,
+    ) -> Result<(), VerifierError> {
+        let format =
+            RdfFormat::from_media_type(content_type).ok_or(VerifierError::InvalidArgument)?;
+        let scope = self.resolve_export_scope(nuri)?;
+        if matches!(scope, ExportScope::UserSite | ExportScope::Graphs(_))
+            && !format.supports_datasets()
+        {
+            return Err(VerifierError::InvalidArgument);
+        }
+        let store = self.graph_dataset.as_ref().unwrap().clone();
+        spawn_chunk_writer(
+            move |chunks| RdfExportWriter::new(store, scope, format, chunks),
+            tx,
+        );
+        Ok(())
+    }
+}
+
+/// Writes the triples or quads of an export one at a time
+pub(crate) struct RdfExportWriter {
+    writer: ToWriteQuadWriter<ChunkSender>,
+    items: ExportItems,
+}
+
+enum ExportItems {
+    /// Written in the default graph
+    Triples(Box<dyn Iterator<Item = Result<Triple, String>>>),
+    Quads(Box<dyn Iterator<Item = Result<Quad, String>>>),
+}
+
+impl RdfExportWriter {
+    fn new(
+        store: Store,
+        scope: ExportScope,
+        format: RdfFormat,
+        chunks: ChunkSender,
+    ) -> Result<Self, String> {
+        let items = match scope {
+            ExportScope::UserSite => {
+                ExportItems::Quads(Box::new(store.iter().map(|q| q.map_err(|e| e.to_string()))))
+            }
+            ExportScope::Graph(graph_name) => ExportItems::Triples(Box::new(
+                store
+                    .quads_for_pattern(
+                        None,
+                        None,
+                        None,
+                        Some(GraphNameRef::NamedNode(NamedNodeRef::new_unchecked(
+                            &graph_name,
+                        ))),
+                    )
+                    .map(|q| q.map(Triple::from).map_err(|e| e.to_string())),
+            )),
+            ExportScope::View(graph_name) => {
+                let query = Query::parse("CONSTRUCT WHERE { ?s ?p ?o }", None)
+                    .map_err(|e| e.to_string())?;
+                match store
+                    .query(query, Some(graph_name))
+                    .map_err(|e| e.to_string())?
+                {
+                    QueryResults::Graph(triples) => ExportItems::Triples(Box::new(
+                        triples.map(|t| t.map_err(|e| e.to_string())),
+                    )),
+                    _ => return Err(VerifierError::InternalError.to_string()),
+                }
+            }
+            ExportScope::Graphs(graph_names) => ExportItems::Quads(Box::new(
+                graph_names.into_iter().flat_map(move |graph_name| {
+                    let graph_name = NamedNode::new_unchecked(graph_name);
+                    store
+                        .quads_for_pattern(None, None, None, Some(graph_name.as_ref().into()))
+                        .map(|q| q.map_err(|e| e.to_string()))
+                }),
+            )),
+        };
+        Ok(Self {
+            writer: RdfSerializer::from_format(format).serialize_to_write(chunks),
+            items,
+        })
+    }
+}
+
+impl ChunkWriter for RdfExportWriter {
+    fn write_next(&mut self) -> Result<bool, String> {
+        let res = match &mut self.items {
+            ExportItems::Triples(triples) => match triples.next() {
+                None => return Ok(false),
+                Some(triple) => self.writer.write_triple(&triple?),
+            },
+            ExportItems::Quads(quads) => match quads.next() {
+                None => return Ok(false),
+                Some(quad) => self.writer.write_quad(&quad?),
+            },
+        };
+        res.map_err(|e| e.to_string())?;
+        Ok(true)
+    }
+
+    fn finish(self) -> Result<ChunkSender, String> {
+        self.writer.finish().map_err(|e| e.to_string())
+    }
+}
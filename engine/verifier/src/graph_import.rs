@@ -0,0 +1,415 @@
+// Copyright (c) 2022-2025 Niko Bonnieure, Par le Peuple, NextGraph.org developers
+// All rights reserved.
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE2 or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Import of RDF serializations and GraphUpdates into the graph of a document
+
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use std::sync::Arc;
+
+use futures::SinkExt;
+use futures::StreamExt;
+use ng_oxigraph::oxrdf::{BlankNode, GraphName, NamedNode, Quad, Subject, Term, Triple};
+use ng_oxigraph::oxrdfio::{FromReadQuadReader, RdfFormat, RdfParser};
+
+use ng_repo::errors::*;
+use ng_repo::file::{FileError, RandomAccessFile, ReadFile};
+#[allow(unused_imports)]
+use ng_repo::log::*;
+
+use ng_net::app_protocol::*;
+use ng_net::utils::Sender;
+
+use crate::verifier::*;
+
+/// Maximum number of quads sent in a single AsyncTransaction commit during an import
+const IMPORT_BATCH_SIZE: usize = 10_000;
+
+/// Size of each read done on a RandomAccessFile while parsing it
+const IMPORT_READ_SIZE: usize = 1048564;
+
+/// Exposes a RandomAccessFile as a `Read`, so it can be parsed incrementally
+struct RandomAccessFileReader {
+    file: RandomAccessFile,
+    pos: usize,
+}
+
+impl Read for RandomAccessFileReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        match self.file.read(self.pos, buf.len().min(IMPORT_READ_SIZE)) {
+            Ok(bytes) => {
+                buf[..bytes.len()].copy_from_slice(&bytes);
+                self.pos += bytes.len();
+                Ok(bytes.len())
+            }
+            Err(FileError::EndOfFile) => Ok(0),
+            Err(e) => Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("{:?}", e),
+            )),
+        }
+    }
+}
+
+fn renumber_triple_blank_nodes(
+    subject: &mut Subject,
+    object: &mut Term,
+    map: &mut HashMap<BlankNode, BlankNode>,
+) {
+    match subject {
+        Subject::BlankNode(b) => *b = map.entry(b.clone()).or_default().clone(),
+        Subject::Triple(t) => renumber_triple_blank_nodes(&mut t.subject, &mut t.object, map),
+        Subject::NamedNode(_) => {}
+    }
+    match object {
+        Term::BlankNode(b) => *b = map.entry(b.clone()).or_default().clone(),
+        Term::Triple(t) => renumber_triple_blank_nodes(&mut t.subject, &mut t.object, map),
+        Term::NamedNode(_) | Term::Literal(_) => {}
+    }
+}
+
+/// Replaces all the blank nodes of the quads (including inside quoted triples) with fresh numeric ones,
+/// as only those can be skolemized by prepare_sparql_update.
+/// The same label is always mapped to the same new blank node.
+fn renumber_blank_nodes(quads: &mut [Quad]) {
+    let mut map: HashMap<BlankNode, BlankNode> = HashMap::new();
+    for quad in quads.iter_mut() {
+        renumber_triple_blank_nodes(&mut quad.subject, &mut quad.object, &mut map);
+    }
+}
+
+impl Verifier {
+    fn graph_name_for_update(&self, nuri: &NuriV0) -> Result<NamedNode, VerifierError> {
+        let graph_name = self
+            .resolve_target_for_sparql(&nuri.target, true)?
+            .ok_or(VerifierError::InvalidNuri)?;
+        Ok(NamedNode::new_unchecked(graph_name))
+    }
+
+    /// Applies the `graph` part of a DocUpdate (serialized `Vec<Triple>`) to the graph of the document
+    pub(crate) async fn process_graph_update(
+        &mut self,
+        nuri: &NuriV0,
+        update: GraphUpdate,
+    ) -> Result<Vec<String>, VerifierError> {
+        let graph_name = self.graph_name_for_update(nuri)?;
+        let into_quads = |triples: Vec<Triple>| -> Vec<Quad> {
+            triples
+                .into_iter()
+                .map(|t| t.in_graph(graph_name.clone()))
+                .collect()
+        };
+        let mut inserts = into_quads(serde_bare::from_slice(&update.inserts)?);
+        let removes = into_quads(serde_bare::from_slice(&update.removes)?);
+        if inserts.is_empty() && removes.is_empty() {
+            return Ok(vec![]);
+        }
+        renumber_blank_nodes(&mut inserts);
+
+        let (commits, revert_inserts, revert_removes, _) = self
+            .prepare_sparql_update(inserts, removes, self.get_peer_id_for_skolem(), 0)
+            .await?;
+        if !revert_inserts.is_empty() || !revert_removes.is_empty() {
+            return Err(VerifierError::PermissionDenied);
+        }
+        Ok(commits)
+    }
+
+    async fn open_graph_import_source(
+        &self,
+        nuri: &NuriV0,
+        source: DocGraphImportSource,
+    ) -> Result<Box<dyn Read + Send>, VerifierError> {
+        Ok(match source {
+            DocGraphImportSource::Inline(content) => Box::new(Cursor::new(content)),
+            DocGraphImportSource::File(obj) => {
+                let (repo_id, _, store_repo) = self.resolve_target(&nuri.target)?;
+                let repo = self.get_repo(&repo_id, &store_repo)?;
+                if let Some(mut stream) = self
+                    .fetch_blocks_if_needed(&obj.id, &repo_id, &store_repo)
+                    .await?
+                {
+                    while let Some(block) = stream.next().await {
+                        repo.store.put(&block)?;
+                    }
+                }
+                let file = RandomAccessFile::open(obj.id, obj.key, Arc::clone(&repo.store))
+                    .map_err(|e| VerifierError::OtherError(format!("{:?}", e)))?;
+                Box::new(RandomAccessFileReader { file, pos: 0 })
+            }
+        })
+    }
+
+    /// Prepares the import of an RDF serialization into the graph of a document.
+    ///
+    /// Triples (and quads in the default graph) go into the graph of the document targeted by the nuri.
+    /// Quads in named graphs must use NextGraph graph names.
+    /// Nothing is parsed nor committed yet, this is done batch by batch with [graph_import_next_batch](Self::graph_import_next_batch),
+    /// so the caller can release the verifier between batches.
+    pub async fn graph_import_start(
+        &mut self,
+        nuri: NuriV0,
+        payload: Option<AppRequestPayload>,
+    ) -> Result<GraphImportRun, NgError> {
+        if !nuri.is_valid_for_sparql_update() {
+            return Err(NgError::InvalidNuri);
+        }
+        let import = match payload {
+            Some(AppRequestPayload::V0(AppRequestPayloadV0::GraphImport(import))) => import,
+            _ => return Err(NgError::InvalidPayload),
+        };
+        self.open_for_target(&nuri.target, true).await?;
+
+        let format = RdfFormat::from_media_type(&import.content_type)
+            .ok_or(VerifierError::InvalidArgument)?;
+        let graph_name = self.graph_name_for_update(&nuri)?;
+
+        let mut parser = RdfParser::from_format(format)
+            .rename_blank_nodes()
+            .with_default_graph(GraphName::NamedNode(graph_name.clone()));
+        if let Some(base) = import.base {
+            parser = parser
+                .with_base_iri(base)
+                .map_err(|_| VerifierError::InvalidArgument)?;
+        }
+
+        let reader = self.open_graph_import_source(&nuri, import.source).await?;
+        Ok(GraphImportRun {
+            quads: parser.parse_read(reader),
+            target: GraphName::NamedNode(graph_name),
+            only_target: false,
+            imported: 0,
+            commits: vec![],
+            done: false,
+        })
+    }
+
+    /// Parses and commits the next batch of at most IMPORT_BATCH_SIZE quads of an import.
+    ///
+    /// Blank nodes are skolemized. Returns false when there was nothing left to import.
+    /// If an error occurs in the middle of the import, the batches already committed are kept.
+    pub async fn graph_import_next_batch(
+        &mut self,
+        run: &mut GraphImportRun,
+    ) -> Result<bool, VerifierError> {
+        if run.done {
+            return Ok(false);
+        }
+        let batch = run.next_batch()?;
+        if batch.is_empty() {
+            return Ok(false);
+        }
+        let batch_len = batch.len();
+        let (commits, revert_inserts, ..) = self
+            .prepare_sparql_update(batch, vec![], self.get_peer_id_for_skolem(), 0)
+            .await?;
+        if !revert_inserts.is_empty() {
+            return Err(VerifierError::PermissionDenied);
+        }
+        run.commits.extend(commits);
+        run.imported += batch_len as u64;
+        Ok(true)
+    }
+
+    /// Runs a whole import while holding the verifier, queuing the progress responses in the channel.
+    ///
+    /// Used when the verifier is called directly. The brokers, that keep the verifier behind a lock,
+    /// start the import with graph_import_start and run the batches in a spawned task instead.
+    pub(crate) async fn import_graph(
+        &mut self,
+        mut run: GraphImportRun,
+        progress: &mut Sender<AppResponse>,
+    ) -> AppResponse {
+        loop {
+            match self.graph_import_next_batch(&mut run).await {
+                Err(e) => return AppResponse::error(e.to_string()),
+                Ok(false) => return run.into_response(),
+                Ok(true) => {
+                    let _ = progress.send(run.progress()).await;
+                }
+            }
+        }
+    }
+}
+
+/// An RDF import started with [Verifier::graph_import_start]
+pub struct GraphImportRun {
+    quads: FromReadQuadReader<Box<dyn Read + Send>>,
+    /// the graph of the document targeted by the import
+    target: GraphName,
+    only_target: bool,
+    imported: u64,
+    commits: Vec<String>,
+    done: bool,
+}
+
+impl GraphImportRun {
+    fn next_batch(&mut self) -> Result<Vec<Quad>, VerifierError> {
+        let mut batch = Vec::with_capacity(IMPORT_BATCH_SIZE);
+        for quad in self.quads.by_ref().take(IMPORT_BATCH_SIZE) {
+            let quad = quad.map_err(|e| VerifierError::OxigraphError(e.to_string()))?;
+            if self.only_target && quad.graph_name != self.target {
+                return Err(VerifierError::PermissionDenied);
+            }
+            batch.push(quad);
+        }
+        self.done = batch.len() < IMPORT_BATCH_SIZE;
+        Ok(batch)
+    }
+
+    /// Rejects the quads in a named graph other than the graph of the target document.
+    ///
+    /// Used for the imports of app sessions, as their grants are only checked against the target of the request.
+    pub fn restrict_to_target(&mut self) {
+        self.only_target = true;
+    }
+
+    /// The GraphImportProgress to send after a batch was committed
+    pub fn progress(&self) -> AppResponse {
+        AppResponse::V0(AppResponseV0::GraphImportProgress(self.imported))
+    }
+
+    /// The final response of a successful import, with all its commits
+    pub fn into_response(self) -> AppResponse {
+        AppResponse::commits(self.commits)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run_for_format(content: String, format: RdfFormat) -> GraphImportRun {
+        let reader: Box<dyn Read + Send> = Box::new(Cursor::new(content.into_bytes()));
+        let target = GraphName::NamedNode(NamedNode::new_unchecked("did:ng:o:test"));
+        GraphImportRun {
+            quads: RdfParser::from_format(format)
+                .rename_blank_nodes()
+                .with_default_graph(target.clone())
+                .parse_read(reader),
+            target,
+            only_target: false,
+            imported: 0,
+            commits: vec![],
+            done: false,
+        }
+    }
+
+    fn run_for(content: String) -> GraphImportRun {
+        run_for_format(content, RdfFormat::NTriples)
+    }
+
+    #[test]
+    pub fn test_import_batches() {
+        let count = IMPORT_BATCH_SIZE * 2 + 3;
+        let mut content = String::new();
+        for i in 0..count {
+            content.push_str(&format!(
+                "<http://example.com/s{i}> <http://example.com/p> \"{i}\" .\n"
+            ));
+        }
+        let mut run = run_for(content);
+
+        let first = run.next_batch().unwrap();
+        assert_eq!(first.len(), IMPORT_BATCH_SIZE);
+        assert!(!run.done);
+        assert_eq!(first[0].graph_name.to_string(), "<did:ng:o:test>");
+        assert_eq!(run.next_batch().unwrap().len(), IMPORT_BATCH_SIZE);
+        assert!(!run.done);
+        let last = run.next_batch().unwrap();
+        assert_eq!(last.len(), 3);
+        assert!(run.done);
+        assert_eq!(
+            last[2].subject.to_string(),
+            format!("<http://example.com/s{}>", count - 1)
+        );
+    }
+
+    #[test]
+    pub fn test_import_exact_batch_and_syntax_error() {
+        let mut content = String::new();
+        for i in 0..IMPORT_BATCH_SIZE {
+            content.push_str(&format!(
+                "<http://example.com/s{i}> <http://example.com/p> <http://example.com/o> .\n"
+            ));
+        }
+        let mut run = run_for(content);
+        assert_eq!(run.next_batch().unwrap().len(), IMPORT_BATCH_SIZE);
+        assert!(!run.done);
+        assert!(run.next_batch().unwrap().is_empty());
+        assert!(run.done);
+
+        let mut run = run_for("<http://example.com/s> <http://example.com/p> .\n".to_string());
+        assert!(run.next_batch().is_err());
+    }
+
+    #[test]
+    pub fn test_import_restricted_to_target() {
+        let content = "<http://example.com/s> <http://example.com/p> \"1\" .\n\
+            <http://example.com/s> <http://example.com/p> \"2\" <did:ng:o:test> .\n\
+            <http://example.com/s> <http://example.com/p> \"3\" <did:ng:o:other> .\n"
+            .to_string();
+
+        // without restriction, the quads of other graphs are imported as well
+        let mut run = run_for_format(content.clone(), RdfFormat::NQuads);
+        assert_eq!(run.next_batch().unwrap().len(), 3);
+
+        let mut run = run_for_format(content, RdfFormat::NQuads);
+        run.restrict_to_target();
+        assert_eq!(run.next_batch(), Err(VerifierError::PermissionDenied));
+
+        let mut run = run_for_format(
+            "<http://example.com/s> <http://example.com/p> \"1\" .\n\
+            <http://example.com/s> <http://example.com/p> \"2\" <did:ng:o:test> .\n"
+                .to_string(),
+            RdfFormat::NQuads,
+        );
+        run.restrict_to_target();
+        assert_eq!(run.next_batch().unwrap().len(), 2);
+    }
+
+    #[test]
+    pub fn test_renumber_blank_nodes() {
+        let graph = NamedNode::new_unchecked("did:ng:o:test");
+        let p = NamedNode::new_unchecked("http://example.com/p");
+        let a = BlankNode::new_unchecked("a");
+        let b = BlankNode::new_unchecked("b");
+        let quoted = Triple::new(a.clone(), p.clone(), b.clone());
+        let mut quads = vec![
+            Quad::new(a.clone(), p.clone(), b.clone(), graph.clone()),
+            Quad::new(b.clone(), p.clone(), a.clone(), graph.clone()),
+            Quad::new(quoted, p.clone(), a.clone(), graph.clone()),
+        ];
+        renumber_blank_nodes(&mut quads);
+
+        let Subject::BlankNode(new_a) = &quads[0].subject else {
+            panic!("expected a blank node");
+        };
+        let Term::BlankNode(new_b) = &quads[0].object else {
+            panic!("expected a blank node");
+        };
+        assert_ne!(new_a, new_b);
+        for new in [new_a, new_b] {
+            // fresh blank nodes are numeric, so they can be skolemized
+            assert!(new.as_str().chars().all(|c| c.is_ascii_hexdigit()));
+            assert!(new != &a && new != &b);
+        }
+        assert_eq!(quads[1].subject, Subject::BlankNode(new_b.clone()));
+        assert_eq!(quads[1].object, Term::BlankNode(new_a.clone()));
+        let Subject::Triple(quoted) = &quads[2].subject else {
+            panic!("expected a quoted triple");
+        };
+        assert_eq!(quoted.subject, Subject::BlankNode(new_a.clone()));
+        assert_eq!(quoted.object, Term::BlankNode(new_b.clone()));
+        assert_eq!(quads[2].object, Term::BlankNode(new_a.clone()));
+    }
+}
@@ -25,8 +25,9 @@ use ng_repo::{errors::*, store::Store, types::Commit};
 
 use ng_net::app_protocol::*;
 use ng_net::types::{
-    InboxMsg, InboxMsgContent, InboxPost, SocialQuery, SocialQueryResponse,
-    SocialQueryResponseContent,
+    InboxMsg, InboxMsgContent, InboxPost, NgQRCodeWalletShareRequestV0,
+    NgQRCodeWalletShareResponseV0, SocialQuery, SocialQueryResponse, SocialQueryResponseContent,
+    WalletRecoveryShare,
 };
 
 use crate::verifier::*;
@@ -227,6 +228,52 @@ impl Verifier {
         Ok(())
     }
 
+    /// sends each share of the master key of our wallet to one contact, identified by its profile nuri and inbox nuri.
+    pub async fn send_wallet_recovery_shares(
+        &self,
+        shares: Vec<WalletRecoveryShare>,
+        contacts: Vec<(String, String)>,
+    ) -> Result<(), VerifierError> {
+        if shares.len() != contacts.len() {
+            return Err(VerifierError::InvalidArgument);
+        }
+        let from_profiles = self.get_2_profiles()?;
+        for (share, (to_profile_nuri, to_inbox_nuri)) in shares.into_iter().zip(contacts) {
+            let from_profile = if to_profile_nuri.starts_with("did:ng:b") {
+                &from_profiles.1
+            } else {
+                &from_profiles.0
+            };
+            self.post_to_inbox(InboxPost::new_wallet_recovery_share(
+                from_profile.0,
+                from_profile.1.clone(),
+                &to_profile_nuri,
+                &to_inbox_nuri,
+                None,
+                share,
+            )?)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// returns the share we hold for the wallet of a contact, sealed to the key of the request.
+    /// The verification code must have been given to us by the contact, by another channel.
+    pub fn answer_wallet_recovery_request(
+        &self,
+        request: &NgQRCodeWalletShareRequestV0,
+        verification_code: &String,
+    ) -> Result<NgQRCodeWalletShareResponseV0, VerifierError> {
+        if !request.check_verification_code(verification_code) {
+            return Err(VerifierError::PermissionDenied);
+        }
+        let user_storage = self
+            .user_storage()
+            .ok_or(VerifierError::StorageError(StorageError::NotFound))?;
+        let (share, _) = user_storage.wallet_share_load(&request.wallet_id)?;
+        Ok(NgQRCodeWalletShareResponseV0::new(&share, request)?)
+    }
+
     pub(crate) async fn process_inbox(
         &mut self,
         msg: &InboxMsg,
@@ -844,6 +891,29 @@ impl Verifier {
                 self.update_header(&contact_nuri.target, Some(details.name), None)
                     .await?;
             }
+            InboxMsgContent::WalletRecoveryShare(share) => {
+                if msg.body.from_overlay.is_none() || msg.body.from_inbox.is_none() {
+                    // the share must come from a known profile
+                    return Err(VerifierError::InvalidInboxPost);
+                }
+                // the share must have been made by the owner of the wallet
+                share
+                    .verify()
+                    .map_err(|_| VerifierError::InvalidInboxPost)?;
+                let from_overlay = msg.body.from_overlay.unwrap();
+                let user_storage = self
+                    .user_storage()
+                    .ok_or(VerifierError::StorageError(StorageError::NotFound))?;
+
+                // a share can only be replaced by the profile that sent it first
+                match user_storage.wallet_share_load(&share.wallet_id) {
+                    Ok((_, previous_overlay)) if previous_overlay != from_overlay => {
+                        return Err(VerifierError::PermissionDenied);
+                    }
+                    _ => {}
+                }
+                user_storage.wallet_share_save(&share, &from_overlay)?;
+            }
             _ => return Err(VerifierError::NotImplemented),
         }
         Ok(())
@@ -24,6 +24,16 @@ mod request_processor;
 
 mod inbox_processor;
 
+pub mod graph_import;
+
+mod graph_export;
+
+mod query_results;
+
+mod sparql_service;
+
+mod text_index;
+
 #[cfg(all(not(target_family = "wasm"), not(docsrs)))]
 mod rocksdb_user_storage;
 
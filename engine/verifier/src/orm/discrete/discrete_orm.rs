@@ -16,17 +16,20 @@ use ng_net::orm::{OrmPatchOp, OrmPatches};
 use ng_net::utils::Receiver;
 use ng_net::{app_protocol::*, orm::OrmPatch};
 use ng_repo::errors::{StorageError, VerifierError};
-use ng_repo::log::*;
 use ng_repo::types::*;
 use serde_json::Value;
 use yrs::updates::decoder::Decode;
-use yrs::{Out, Transact};
+use yrs::{GetString, Out, Transact, XmlOut};
 
 use crate::orm::discrete::automerge_orm::{
     automerge_doc_to_json, automerge_handle_frontend_discrete_update,
 };
+use crate::orm::discrete::reconcile::{apply_orm_patches_to_json, diff_orm_json};
 use crate::orm::discrete::types::{BackendDiscreteState, DiscreteOrmSubscription};
-use crate::orm::discrete::yrs_orm::{yrs_handle_frontend_discrete_update, yrs_out_to_json};
+use crate::orm::discrete::yrs_orm::{
+    new_yrs_text_doc, yrs_handle_frontend_discrete_update, yrs_out_to_json, yrs_xml_to_json,
+    YrsRootType, YXML_ROOT,
+};
 use crate::types::{CancelFn, DiscreteTransaction};
 
 use crate::verifier::Verifier;
@@ -104,9 +107,14 @@ impl Verifier {
                         serde_json::Value::Object(serde_json::map::Map::new()),
                         BackendDiscreteState::Automerge(automerge::Automerge::new()),
                     ),
-                    BranchCrdt::YText(_) | BranchCrdt::YXml(_) => {
-                        return Err(VerifierError::NotImplemented)
-                    }
+                    BranchCrdt::YText(_) => (
+                        serde_json::Value::String(String::new()),
+                        BackendDiscreteState::YText(new_yrs_text_doc()),
+                    ),
+                    BranchCrdt::YXml(_) => (
+                        serde_json::Value::Array(vec![]),
+                        BackendDiscreteState::YXml(new_yrs_text_doc()),
+                    ),
                     _ => return Err(VerifierError::InvalidBranch),
                 }
             };
@@ -242,7 +250,7 @@ impl Verifier {
             return Ok(());
         };
 
-        let (transaction, resulting_orm_patches, nuri, branch_id, full_state) = {
+        let (transaction, resulting_orm_patches, nuri, branch_id, full_state, corrective_patches) = {
             let orm_subscription = self
                 .discrete_orm_subscriptions
                 .get_mut(&subscription_id)
@@ -253,24 +261,78 @@ impl Verifier {
                 .get_mut(&orm_subscription.branch_id)
                 .ok_or(VerifierError::OrmStateNotFound)?;
 
-            match backend_state {
-                BackendDiscreteState::YMap(doc) => {
-                    yrs_handle_frontend_discrete_update(patches, orm_subscription, doc, false)?
-                }
-                BackendDiscreteState::YArray(doc) => {
-                    yrs_handle_frontend_discrete_update(patches, orm_subscription, doc, true)?
-                }
-                BackendDiscreteState::Automerge(doc) => {
-                    automerge_handle_frontend_discrete_update(patches, orm_subscription, doc)?
-                }
-            }
+            // The state the client expects to reach, once its patches are applied.
+            let mut intended_state =
+                convert_discrete_state_to_orm_object(backend_state, &orm_subscription.nuri)?;
+            apply_orm_patches_to_json(&mut intended_state, &patches);
+
+            let (transaction, resulting_orm_patches, nuri, branch_id, full_state) =
+                match backend_state {
+                    BackendDiscreteState::YMap(doc) => yrs_handle_frontend_discrete_update(
+                        patches,
+                        orm_subscription,
+                        doc,
+                        YrsRootType::Map,
+                    )?,
+                    BackendDiscreteState::YArray(doc) => yrs_handle_frontend_discrete_update(
+                        patches,
+                        orm_subscription,
+                        doc,
+                        YrsRootType::Array,
+                    )?,
+                    BackendDiscreteState::YText(doc) => yrs_handle_frontend_discrete_update(
+                        patches,
+                        orm_subscription,
+                        doc,
+                        YrsRootType::Text,
+                    )?,
+                    BackendDiscreteState::YXml(doc) => yrs_handle_frontend_discrete_update(
+                        patches,
+                        orm_subscription,
+                        doc,
+                        YrsRootType::Xml,
+                    )?,
+                    BackendDiscreteState::Automerge(doc) => {
+                        automerge_handle_frontend_discrete_update(patches, orm_subscription, doc)?
+                    }
+                };
+
+            // The merge can end up somewhere else, e.g. when a concurrent change removed
+            // the target of a patch. The client then needs to be corrected.
+            let effective_state = convert_discrete_state_to_orm_object(backend_state, &nuri)?;
+            let mut corrective_patches = vec![];
+            diff_orm_json(
+                "",
+                &intended_state,
+                &effective_state,
+                &mut corrective_patches,
+            );
+
+            (
+                transaction,
+                resulting_orm_patches,
+                nuri,
+                branch_id,
+                full_state,
+                corrective_patches,
+            )
         };
 
         // == Send updates to other subscribers ==
         self.push_orm_discrete_update(resulting_orm_patches, subscription_id, &branch_id)
             .await?;
 
-        //TODO: deal with cases when the resulting_orm_patches is different from patches (received). We need to send the diff to subscription_id
+        // == Send the diff between intended and effective state back to the originating subscriber ==
+        if !corrective_patches.is_empty() {
+            if let Some(sub) = self.discrete_orm_subscriptions.get_mut(&subscription_id) {
+                let _ = sub
+                    .sender
+                    .send(AppResponse::V0(AppResponseV0::DiscreteOrmUpdate(
+                        corrective_patches,
+                    )))
+                    .await;
+            }
+        }
 
         // == Record change (create a Commit, and process it) ==
         self.create_discrete_transaction(transaction, &nuri, Some(full_state))
@@ -286,20 +348,12 @@ impl Verifier {
         patch: &DiscreteTransaction,
     ) -> Result<(Vec<u8>, Vec<OrmPatch>), VerifierError> {
         match patch {
-            DiscreteTransaction::YMap(_) => {
+            DiscreteTransaction::YMap(_)
+            | DiscreteTransaction::YArray(_)
+            | DiscreteTransaction::YXml(_)
+            | DiscreteTransaction::YText(_) => {
                 self.apply_discrete_yjs_transaction_gen_orm_patches(branch_id, patch)
             }
-            DiscreteTransaction::YArray(_) => {
-                self.apply_discrete_yjs_transaction_gen_orm_patches(branch_id, patch)
-            }
-            DiscreteTransaction::YXml(_) => {
-                log_warn!("Discrete patch type YXml not implemented.");
-                return Err(VerifierError::NotImplemented);
-            }
-            DiscreteTransaction::YText(_) => {
-                log_warn!("Discrete patch type YText not implemented.");
-                return Err(VerifierError::NotImplemented);
-            }
             DiscreteTransaction::Automerge(_) => {
                 self.apply_discrete_automerge_transaction_gen_orm_patches(branch_id, patch)
             }
@@ -327,6 +381,16 @@ fn convert_discrete_state_to_orm_object(
             drop(txn);
             Ok(val)
         }
+        BackendDiscreteState::YText(doc) => {
+            let root = doc.get_or_insert_text("ng");
+            let txn = doc.transact();
+            Ok(Value::String(root.get_string(&txn)))
+        }
+        BackendDiscreteState::YXml(doc) => {
+            let root = doc.get_or_insert_xml_fragment(YXML_ROOT);
+            let txn = doc.transact();
+            Ok(yrs_xml_to_json(&txn, &XmlOut::Fragment(root)))
+        }
         BackendDiscreteState::Automerge(doc) => Ok(automerge_doc_to_json(doc, nuri)),
     }
 }
@@ -370,13 +434,31 @@ fn convert_discrete_blob_to_orm_object(
             let root_json = automerge_doc_to_json(&doc, nuri);
             return Ok((root_json, BackendDiscreteState::Automerge(doc)));
         }
-        DiscreteState::YXml(_bytes) => {
-            log_warn!("YXml not implemented.");
-            return Err(VerifierError::NotImplemented);
+        DiscreteState::YXml(bytes) => {
+            let doc = new_yrs_text_doc();
+            let root = doc.get_or_insert_xml_fragment(YXML_ROOT);
+            let update = yrs::Update::decode_v1(&bytes)
+                .map_err(|e| VerifierError::YrsError(e.to_string()))?;
+            let mut txn = doc.transact_mut();
+            txn.apply_update(update);
+
+            let root_json = yrs_xml_to_json(&txn, &XmlOut::Fragment(root));
+            drop(txn);
+
+            return Ok((root_json, BackendDiscreteState::YXml(doc)));
         }
-        DiscreteState::YText(_bytes) => {
-            log_warn!("YText not implemented.");
-            return Err(VerifierError::NotImplemented);
+        DiscreteState::YText(bytes) => {
+            let doc = new_yrs_text_doc();
+            let root = doc.get_or_insert_text("ng");
+            let update = yrs::Update::decode_v1(&bytes)
+                .map_err(|e| VerifierError::YrsError(e.to_string()))?;
+            let mut txn = doc.transact_mut();
+            txn.apply_update(update);
+
+            let root_json = Value::String(root.get_string(&txn));
+            drop(txn);
+
+            return Ok((root_json, BackendDiscreteState::YText(doc)));
         }
     }
 }
@@ -10,5 +10,6 @@
 
 pub mod automerge_orm;
 pub mod discrete_orm;
+pub mod reconcile;
 pub mod types;
 pub mod yrs_orm;
@@ -0,0 +1,240 @@
+// Copyright (c) 2026 Laurin Weger, Par le Peuple, NextGraph.org developers
+// All rights reserved.
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE2 or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Reconciliation of the state intended by a discrete orm client with the state effectively
+//! reached once its patches got merged into the CRDT.
+
+use ng_net::orm::{OrmPatch, OrmPatchOp, OrmPatchType};
+use serde_json::Value;
+
+use crate::orm::utils::{decode_json_pointer, escape_json_pointer_segment};
+
+/// Applies the patches sent by a client to the JSON state, the way the client did it locally.
+/// Patches that cannot be applied are ignored, the resulting diff takes care of them.
+pub(crate) fn apply_orm_patches_to_json(state: &mut Value, patches: &[OrmPatch]) {
+    for patch in patches {
+        let path: Vec<String> = patch
+            .path
+            .split('/')
+            .skip(1)
+            .map(|segment| decode_json_pointer(&segment.to_string()))
+            .collect();
+        if patch.valType == Some(OrmPatchType::text) {
+            apply_text_patch(state, &path, patch);
+        } else {
+            apply_patch(state, &path, patch);
+        }
+    }
+}
+
+fn value_at_mut<'a>(value: &'a mut Value, path: &[String]) -> Option<&'a mut Value> {
+    path.iter()
+        .try_fold(value, |current, segment| match current {
+            Value::Object(map) => map.get_mut(segment),
+            Value::Array(arr) => segment.parse::<usize>().ok().and_then(|i| arr.get_mut(i)),
+            _ => None,
+        })
+}
+
+fn apply_patch(state: &mut Value, path: &[String], patch: &OrmPatch) -> Option<()> {
+    let Some((key, parent_path)) = path.split_last() else {
+        match patch.op {
+            OrmPatchOp::add => *state = patch.value.clone()?,
+            OrmPatchOp::remove => match state {
+                Value::Object(map) => map.clear(),
+                Value::Array(arr) => arr.clear(),
+                other => *other = Value::Null,
+            },
+        }
+        return Some(());
+    };
+    match value_at_mut(state, parent_path)? {
+        Value::Object(map) => match patch.op {
+            OrmPatchOp::add => {
+                map.insert(key.clone(), patch.value.clone()?);
+            }
+            OrmPatchOp::remove => {
+                map.remove(key);
+            }
+        },
+        Value::Array(arr) => {
+            let index = if key == "-" {
+                arr.len()
+            } else {
+                key.parse().ok()?
+            };
+            match patch.op {
+                OrmPatchOp::add if index <= arr.len() => arr.insert(index, patch.value.clone()?),
+                OrmPatchOp::remove if index < arr.len() => {
+                    arr.remove(index);
+                }
+                _ => return None,
+            }
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+/// Applies a text delta. Offsets are counted in UTF-16 code units.
+fn apply_text_patch(state: &mut Value, path: &[String], patch: &OrmPatch) -> Option<()> {
+    let Some((offset, text_path)) = path.split_last() else {
+        *state = match patch.op {
+            OrmPatchOp::add => patch.value.clone()?,
+            OrmPatchOp::remove => Value::String(String::new()),
+        };
+        return Some(());
+    };
+    let Value::String(text) = value_at_mut(state, text_path)? else {
+        return None;
+    };
+    let offset: usize = offset.parse().ok()?;
+    let mut units: Vec<u16> = text.encode_utf16().collect();
+    if offset > units.len() {
+        return None;
+    }
+    match patch.op {
+        OrmPatchOp::add => {
+            let inserted = patch.value.as_ref()?.as_str()?;
+            units.splice(offset..offset, inserted.encode_utf16());
+        }
+        OrmPatchOp::remove => {
+            let count = patch.value.as_ref()?.as_u64()? as usize;
+            units.drain(offset..(offset + count).min(units.len()));
+        }
+    }
+    *text = String::from_utf16_lossy(&units);
+    Some(())
+}
+
+/// Computes the patches that turn the `intended` state into the `effective` one, at `path`.
+///
+/// Objects are compared key by key and arrays of the same length item by item,
+/// anything else that differs is replaced as a whole.
+/// `@id`s are skipped, those of new objects are already sent along with the update.
+pub(crate) fn diff_orm_json(
+    path: &str,
+    intended: &Value,
+    effective: &Value,
+    patches: &mut Vec<OrmPatch>,
+) {
+    match (intended, effective) {
+        (Value::Object(intended), Value::Object(effective)) => {
+            for key in intended.keys() {
+                if key != "@id" && !effective.contains_key(key) {
+                    patches.push(OrmPatch {
+                        op: OrmPatchOp::remove,
+                        valType: None,
+                        path: format!("{path}/{}", escape_json_pointer_segment(key)),
+                        value: None,
+                    });
+                }
+            }
+            for (key, value) in effective.iter() {
+                if key == "@id" {
+                    continue;
+                }
+                let child_path = format!("{path}/{}", escape_json_pointer_segment(key));
+                match intended.get(key) {
+                    Some(intended_value) => {
+                        diff_orm_json(&child_path, intended_value, value, patches)
+                    }
+                    None => patches.push(OrmPatch {
+                        op: OrmPatchOp::add,
+                        valType: None,
+                        path: child_path,
+                        value: Some(value.clone()),
+                    }),
+                }
+            }
+        }
+        (Value::Array(intended), Value::Array(effective)) if intended.len() == effective.len() => {
+            for (index, (intended_value, value)) in intended.iter().zip(effective).enumerate() {
+                diff_orm_json(&format!("{path}/{index}"), intended_value, value, patches);
+            }
+        }
+        _ if intended == effective => {}
+        _ => patches.push(OrmPatch {
+            op: OrmPatchOp::add,
+            valType: None,
+            path: path.to_string(),
+            value: Some(effective.clone()),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn patch(op: OrmPatchOp, val_type: Option<OrmPatchType>, path: &str, value: Value) -> OrmPatch {
+        OrmPatch {
+            op,
+            valType: val_type,
+            path: path.to_string(),
+            value: Some(value),
+        }
+    }
+
+    #[test]
+    pub fn test_apply_patches() {
+        let mut state = json!({"list": [{"@id": "a", "n": 1}], "title": "hé llo"});
+        apply_orm_patches_to_json(
+            &mut state,
+            &[
+                patch(OrmPatchOp::add, None, "/list/0", json!({"n": 0})),
+                patch(OrmPatchOp::add, None, "/list/-", json!({"n": 2})),
+                patch(OrmPatchOp::remove, None, "/list/1", Value::Null),
+                patch(
+                    OrmPatchOp::add,
+                    Some(OrmPatchType::text),
+                    "/title/2",
+                    json!(","),
+                ),
+                patch(
+                    OrmPatchOp::remove,
+                    Some(OrmPatchType::text),
+                    "/title/3",
+                    json!(1),
+                ),
+            ],
+        );
+        assert_eq!(
+            state,
+            json!({"list": [{"n": 0}, {"n": 2}], "title": "hé,llo"})
+        );
+    }
+
+    #[test]
+    pub fn test_diff() {
+        let intended = json!({"a": 1, "b": [{"n": 1}], "c": "x", "d": [1, 2]});
+        let effective = json!({"b": [{"@id": "id", "n": 2}], "c": "x", "d": [1], "e": true});
+        let mut patches = vec![];
+        diff_orm_json("", &intended, &effective, &mut patches);
+        assert_eq!(
+            patches
+                .iter()
+                .map(|p| (p.op.clone(), p.path.as_str(), p.value.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (OrmPatchOp::remove, "/a", None),
+                (OrmPatchOp::add, "/b/0/n", Some(json!(2))),
+                (OrmPatchOp::add, "/d", Some(json!([1]))),
+                (OrmPatchOp::add, "/e", Some(json!(true))),
+            ]
+        );
+
+        let mut patches = vec![];
+        diff_orm_json("", &intended, &intended, &mut patches);
+        assert!(patches.is_empty());
+    }
+}
@@ -25,5 +25,7 @@ pub struct DiscreteOrmSubscription {
 pub enum BackendDiscreteState {
     YMap(yrs::Doc),
     YArray(yrs::Doc),
+    YText(yrs::Doc),
+    YXml(yrs::Doc),
     Automerge(automerge::Automerge),
 }
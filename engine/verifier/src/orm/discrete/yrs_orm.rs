@@ -11,7 +11,7 @@
 use std::{cell::RefCell, rc::Rc};
 
 use ng_net::app_protocol::*;
-use ng_net::orm::{OrmPatch, OrmPatchOp, OrmPatches};
+use ng_net::orm::{OrmPatch, OrmPatchOp, OrmPatchType, OrmPatches};
 
 use ng_repo::log::*;
 use ng_repo::{errors::VerifierError, types::BranchId};
@@ -19,14 +19,86 @@ use serde_json::{json, Value};
 use yrs::{updates::decoder::Decode, Any, ArrayPrelim, BranchID, In, MapPrelim, Out};
 
 use serde_json::Map as JsonMap;
-use yrs::types::{Change, EntryChange, Events, PathSegment};
-use yrs::{Array, DeepObservable, Doc, Map, ReadTxn, Transact};
+use yrs::types::{Change, Delta, EntryChange, Events, Path, PathSegment};
+use yrs::{
+    Array, DeepObservable, Doc, GetString, Map, OffsetKind, Options, ReadTxn, Subscription, Text,
+    Transact, XmlElementPrelim, XmlFragment, XmlFragmentRef, XmlOut, XmlTextPrelim,
+};
 
 use crate::orm::discrete::types::{BackendDiscreteState, DiscreteOrmSubscription};
 use crate::orm::utils::decode_json_pointer;
 use crate::types::DiscreteTransaction;
 use crate::verifier::Verifier;
 
+/// Name of the root shared type of a `YXml` document, as used by the prosemirror binding.
+pub(crate) const YXML_ROOT: &str = "prosemirror";
+
+/// The kind of root shared type of a yrs document.
+/// `YMap`, `YArray` and `YText` documents have their root stored under `ng`, `YXml` under `YXML_ROOT`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum YrsRootType {
+    Map,
+    Array,
+    Text,
+    Xml,
+}
+
+impl YrsRootType {
+    /// Returns the root type and the yrs document of a backend state, if it is a yrs one.
+    pub(crate) fn of(state: &BackendDiscreteState) -> Option<(Self, &Doc)> {
+        match state {
+            BackendDiscreteState::YMap(doc) => Some((Self::Map, doc)),
+            BackendDiscreteState::YArray(doc) => Some((Self::Array, doc)),
+            BackendDiscreteState::YText(doc) => Some((Self::Text, doc)),
+            BackendDiscreteState::YXml(doc) => Some((Self::Xml, doc)),
+            BackendDiscreteState::Automerge(_) => None,
+        }
+    }
+
+    fn transaction(&self, update: Vec<u8>) -> DiscreteTransaction {
+        match self {
+            Self::Map => DiscreteTransaction::YMap(update),
+            Self::Array => DiscreteTransaction::YArray(update),
+            Self::Text => DiscreteTransaction::YText(update),
+            Self::Xml => DiscreteTransaction::YXml(update),
+        }
+    }
+}
+
+/// Creates a yrs document for text content.
+/// Offsets are counted in UTF-16 code units, like in JS-land.
+pub(crate) fn new_yrs_text_doc() -> Doc {
+    Doc::with_options(Options {
+        offset_kind: OffsetKind::Utf16,
+        ..Options::default()
+    })
+}
+
+/// Observes the root of the document and collects the generated OrmPatches in `patches`.
+fn observe_root(
+    doc: &Doc,
+    root_type: YrsRootType,
+    patches: &Rc<RefCell<Vec<OrmPatch>>>,
+    nuri: &NuriV0,
+) -> Subscription {
+    let patches = Rc::clone(patches);
+    let nuri = nuri.clone();
+    match root_type {
+        YrsRootType::Map => doc
+            .get_or_insert_map("ng")
+            .observe_deep(move |txn, ev| yrs_mutation_callback(txn, ev, &patches, &nuri)),
+        YrsRootType::Array => doc
+            .get_or_insert_array("ng")
+            .observe_deep(move |txn, ev| yrs_mutation_callback(txn, ev, &patches, &nuri)),
+        YrsRootType::Text => doc
+            .get_or_insert_text("ng")
+            .observe_deep(move |txn, ev| yrs_mutation_callback(txn, ev, &patches, &nuri)),
+        YrsRootType::Xml => doc
+            .get_or_insert_xml_fragment(YXML_ROOT)
+            .observe_deep(move |txn, ev| yrs_mutation_callback(txn, ev, &patches, &nuri)),
+    }
+}
+
 impl Verifier {
     /// Applies blob batches and generates ORM JSON patches.
     pub(crate) fn apply_discrete_yjs_transaction_gen_orm_patches(
@@ -46,27 +118,12 @@ impl Verifier {
             .map(|sub| sub.nuri.clone())
             .ok_or(VerifierError::OrmSubscriptionNotFound)?;
 
-        let (BackendDiscreteState::YMap(doc) | BackendDiscreteState::YArray(doc)) = backend_state
-        else {
+        let Some((root_type, doc)) = YrsRootType::of(backend_state) else {
             return Err(VerifierError::OrmStateNotFound);
         };
 
-        let is_array = matches!(backend_state, BackendDiscreteState::YArray(_));
         let resulting_orm_patches: Rc<RefCell<Vec<OrmPatch>>> = Rc::new(RefCell::new(vec![]));
-        let nuri_clone = nuri.clone();
-        let observation = if is_array {
-            let array_ref = doc.get_or_insert_array("ng");
-            let patches_clone = Rc::clone(&resulting_orm_patches);
-            array_ref.observe_deep(move |mut txn, ev| {
-                yrs_mutation_callback(&mut txn, ev, &patches_clone, &nuri_clone);
-            })
-        } else {
-            let map_ref = doc.get_or_insert_map("ng");
-            let patches_clone = Rc::clone(&resulting_orm_patches);
-            map_ref.observe_deep(move |mut tx, ev| {
-                yrs_mutation_callback(&mut tx, ev, &patches_clone, &nuri_clone);
-            })
-        };
+        let observation = observe_root(doc, root_type, &resulting_orm_patches, &nuri);
         let mut tx = doc.transact_mut();
         let update = yrs::Update::decode_v1(patch.as_slice())
             .map_err(|e| VerifierError::YrsError(e.to_string()))?;
@@ -128,6 +185,13 @@ pub(crate) enum YrsTarget {
     Array(yrs::ArrayRef),
 }
 
+/// The root of a yrs document, which frontend patches are applied to.
+enum YrsRoot {
+    Target(YrsTarget),
+    Text(yrs::TextRef),
+    Xml(XmlFragmentRef),
+}
+
 /// Navigates to the parent container at the given path and returns it along with the final key.
 fn navigate_to_parent_from_target(
     txn: &mut yrs::TransactionMut,
@@ -469,11 +533,430 @@ pub(crate) fn yrs_mutation_callback(
                     }
                 }
             }
-            // TODO: Event::Text ?
+            yrs::types::Event::Text(text_event) => {
+                push_text_delta_patches(&mut patches, &base_path, text_event.delta(txn));
+            }
+            yrs::types::Event::XmlText(text_event) => {
+                let node_path = xml_node_path(&path);
+                push_text_delta_patches(
+                    &mut patches,
+                    &format!("{node_path}/text"),
+                    text_event.delta(txn),
+                );
+            }
+            yrs::types::Event::XmlFragment(xml_event) => {
+                let node_path = xml_node_path(&path);
+                let content_path = if node_path.is_empty() {
+                    node_path.clone()
+                } else {
+                    format!("{node_path}/content")
+                };
+                let mut pos = 0;
+                for delta in xml_event.delta(txn).iter() {
+                    match delta {
+                        Change::Added(added) => {
+                            for new_val in added {
+                                patches.push(OrmPatch {
+                                    op: OrmPatchOp::add,
+                                    path: format!("{content_path}/{pos}"),
+                                    value: Some(yrs_xml_out_to_json(txn, new_val)),
+                                    valType: None,
+                                });
+                                pos += 1;
+                            }
+                        }
+                        Change::Removed(removed) => {
+                            for _i in pos..(pos + removed) {
+                                patches.push(OrmPatch {
+                                    op: OrmPatchOp::remove,
+                                    path: format!("{content_path}/{pos}"),
+                                    valType: None,
+                                    value: None,
+                                });
+                            }
+                        }
+                        Change::Retain(retain) => {
+                            pos += retain;
+                        }
+                    }
+                }
+                for (key, change) in xml_event.keys(txn).iter() {
+                    let path = format!("{node_path}/attrs/{key}");
+                    match change {
+                        EntryChange::Inserted(new_val) | EntryChange::Updated(_, new_val) => {
+                            patches.push(OrmPatch {
+                                op: OrmPatchOp::add,
+                                path,
+                                valType: None,
+                                value: Some(yrs_out_to_json(txn, new_val, nuri, false)),
+                            });
+                        }
+                        EntryChange::Removed(_removed) => {
+                            patches.push(OrmPatch {
+                                op: OrmPatchOp::remove,
+                                path,
+                                valType: None,
+                                value: None,
+                            });
+                        }
+                    }
+                }
+            }
             _other => {
-                log_err!("[yrs_mutation_callback] Expected map or array change");
+                log_err!("[yrs_mutation_callback] Unsupported change type");
+            }
+        };
+    }
+}
+
+/// Converts the path of an XML event to the path of the node in the ORM object.
+/// Nested nodes are found in the `content` of their parent element: `/0/content/2`.
+fn xml_node_path(path: &Path) -> String {
+    path.iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let segment = match p {
+                PathSegment::Index(i) => i.to_string(),
+                PathSegment::Key(key) => key.to_string(),
+            };
+            if i == 0 {
+                format!("/{segment}")
+            } else {
+                format!("/content/{segment}")
+            }
+        })
+        .collect()
+}
+
+/// Translates a text delta to `text` patches, at offsets relative to `base_path`.
+fn push_text_delta_patches(patches: &mut Vec<OrmPatch>, base_path: &str, delta: &[Delta]) {
+    let mut pos: u32 = 0;
+    for change in delta {
+        match change {
+            Delta::Inserted(Out::Any(Any::String(inserted)), _) => {
+                patches.push(OrmPatch {
+                    op: OrmPatchOp::add,
+                    path: format!("{base_path}/{pos}"),
+                    valType: Some(OrmPatchType::text),
+                    value: Some(json!(inserted.as_ref())),
+                });
+                pos += inserted.encode_utf16().count() as u32;
+            }
+            Delta::Inserted(_, _) => {
+                // Embeds take a single position, but have no text representation.
+                log_warn!("[yrs_mutation_callback] Ignoring embed in text");
+                pos += 1;
+            }
+            Delta::Deleted(len) => {
+                patches.push(OrmPatch {
+                    op: OrmPatchOp::remove,
+                    path: format!("{base_path}/{pos}"),
+                    valType: Some(OrmPatchType::text),
+                    value: Some(json!(len)),
+                });
+            }
+            Delta::Retain(len, _) => {
+                pos += len;
+            }
+        }
+    }
+}
+
+/// Converts an XML node to its JSON representation:
+/// `{"type": tag, "attrs": {..}, "content": [..]}` for elements,
+/// `{"type": "text", "text": ".."}` for text nodes.
+/// A fragment is converted to the array of its children.
+pub(crate) fn yrs_xml_to_json<T: ReadTxn>(txn: &T, node: &XmlOut) -> Value {
+    match node {
+        XmlOut::Element(element) => {
+            let attrs: JsonMap<String, Value> = element
+                .attributes(txn)
+                .map(|(key, value)| {
+                    let value = match value {
+                        Out::Any(any) => json!(any),
+                        other => json!(other.to_string(txn)),
+                    };
+                    (key.to_string(), value)
+                })
+                .collect();
+            json!({
+                "type": element.tag().to_string(),
+                "attrs": attrs,
+                "content": element
+                    .children(txn)
+                    .map(|child| yrs_xml_to_json(txn, &child))
+                    .collect::<Vec<_>>(),
+            })
+        }
+        XmlOut::Text(text) => json!({
+            "type": "text",
+            "text": text.get_string(txn),
+        }),
+        XmlOut::Fragment(fragment) => Value::Array(
+            fragment
+                .children(txn)
+                .map(|child| yrs_xml_to_json(txn, &child))
+                .collect(),
+        ),
+    }
+}
+
+fn yrs_xml_out_to_json(txn: &yrs::TransactionMut<'_>, value: &Out) -> Value {
+    let node = match value {
+        Out::YXmlElement(element) => XmlOut::Element(element.clone()),
+        Out::YXmlText(text) => XmlOut::Text(text.clone()),
+        Out::YXmlFragment(fragment) => XmlOut::Fragment(fragment.clone()),
+        _ => {
+            log_err!("[yrs_xml_out_to_json] Expected an XML node");
+            return Value::Null;
+        }
+    };
+    yrs_xml_to_json(txn, &node)
+}
+
+/// Applies a `text` patch to a text, at the offset found in the first segment of `path`.
+/// An empty path replaces (`add`) or clears (`remove`) the whole text.
+fn apply_yrs_text_patch<T: Text>(
+    txn: &mut yrs::TransactionMut,
+    text: &T,
+    path: &[String],
+    patch: &OrmPatch,
+) -> Result<(), VerifierError> {
+    if path.is_empty() {
+        let len = text.len(txn);
+        if len > 0 {
+            text.remove_range(txn, 0, len);
+        }
+        if patch.op == OrmPatchOp::add {
+            let value =
+                patch
+                    .value
+                    .as_ref()
+                    .and_then(|v| v.as_str())
+                    .ok_or(VerifierError::YrsError(
+                        "Text patch value must be a string".into(),
+                    ))?;
+            text.insert(txn, 0, value);
+        }
+        return Ok(());
+    }
+    if path.len() != 1 {
+        return Err(VerifierError::YrsError("Invalid text patch path".into()));
+    }
+    let offset: u32 = path[0]
+        .parse()
+        .map_err(|_| VerifierError::YrsError(format!("Invalid text offset '{}'", path[0])))?;
+    let len = text.len(txn);
+    if offset > len {
+        return Err(VerifierError::YrsError(format!(
+            "Text offset {} out of bounds (len: {})",
+            offset, len
+        )));
+    }
+    if patch.op == OrmPatchOp::add {
+        let value =
+            patch
+                .value
+                .as_ref()
+                .and_then(|v| v.as_str())
+                .ok_or(VerifierError::YrsError(
+                    "Text patch value must be a string".into(),
+                ))?;
+        text.insert(txn, offset, value);
+    } else {
+        let count = patch
+            .value
+            .as_ref()
+            .and_then(|v| v.as_u64())
+            .ok_or(VerifierError::YrsError(
+                "Text removal value must be a length".into(),
+            ))? as u32;
+        text.remove_range(txn, offset, count.min(len - offset));
+    }
+    Ok(())
+}
+
+/// Inserts the XML node described by the JSON `value` in `parent`, at `index`.
+fn insert_xml_json<F: XmlFragment>(
+    txn: &mut yrs::TransactionMut,
+    parent: &F,
+    index: u32,
+    value: &Value,
+) -> Result<(), VerifierError> {
+    let node_type = value
+        .get("type")
+        .and_then(|t| t.as_str())
+        .ok_or(VerifierError::YrsError("XML node without type".into()))?;
+    if node_type == "text" {
+        let text = value.get("text").and_then(|t| t.as_str()).unwrap_or("");
+        parent.insert(txn, index, XmlTextPrelim::new(text));
+        return Ok(());
+    }
+    let element = parent.insert(txn, index, XmlElementPrelim::empty(node_type));
+    if let Some(attrs) = value.get("attrs").and_then(|a| a.as_object()) {
+        for (key, attr) in attrs {
+            let attr = match attr {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            element.insert_attribute(txn, key.as_str(), attr);
+        }
+    }
+    if let Some(content) = value.get("content").and_then(|c| c.as_array()) {
+        for (i, child) in content.iter().enumerate() {
+            insert_xml_json(txn, &element, i as u32, child)?;
+        }
+    }
+    Ok(())
+}
+
+/// Finds the XML node at the given child indices, starting from the root fragment.
+fn xml_node_at(
+    txn: &yrs::TransactionMut,
+    root: &XmlFragmentRef,
+    indices: &[u32],
+) -> Result<XmlOut, VerifierError> {
+    let mut current = XmlOut::Fragment(root.clone());
+    for index in indices {
+        let child = match &current {
+            XmlOut::Fragment(fragment) => fragment.get(txn, *index),
+            XmlOut::Element(element) => element.get(txn, *index),
+            XmlOut::Text(_) => None,
+        };
+        current = child.ok_or(VerifierError::YrsError(format!(
+            "XML node index {} does not exist",
+            index
+        )))?;
+    }
+    Ok(current)
+}
+
+/// Applies a patch to a `YXml` document.
+///
+/// Paths address nodes by their index in the `content` of their parent (`/0/content/2`),
+/// followed by `attrs/<key>` for an attribute, or `text/<offset>` for a text delta.
+fn apply_yrs_xml_patch(
+    txn: &mut yrs::TransactionMut,
+    root: &XmlFragmentRef,
+    path: &[String],
+    patch: &OrmPatch,
+) -> Result<(), VerifierError> {
+    // Split the path into the node indices and the remaining tail.
+    let mut indices: Vec<u32> = vec![];
+    let mut rest = path;
+    loop {
+        let Some(segment) = rest.first() else {
+            break;
+        };
+        if !indices.is_empty() {
+            if segment != "content" {
+                break;
             }
+            rest = &rest[1..];
+        }
+        let Some(segment) = rest.first() else {
+            return Err(VerifierError::YrsError("Invalid XML patch path".into()));
         };
+        let index = if segment == "-" && rest.len() == 1 && patch.op == OrmPatchOp::add {
+            u32::MAX
+        } else {
+            segment.parse().map_err(|_| {
+                VerifierError::YrsError(format!("Invalid XML node index '{}'", segment))
+            })?
+        };
+        indices.push(index);
+        rest = &rest[1..];
+    }
+
+    match rest {
+        [] => {
+            let Some((index, parent_indices)) = indices.split_last() else {
+                // Replace or clear the whole document.
+                let len = root.len(txn);
+                if len > 0 {
+                    root.remove_range(txn, 0, len);
+                }
+                if patch.op == OrmPatchOp::add {
+                    let nodes = patch.value.as_ref().and_then(|v| v.as_array()).ok_or(
+                        VerifierError::YrsError("XML document must be an array".into()),
+                    )?;
+                    for (i, node) in nodes.iter().enumerate() {
+                        insert_xml_json(txn, root, i as u32, node)?;
+                    }
+                }
+                return Ok(());
+            };
+            match xml_node_at(txn, root, parent_indices)? {
+                XmlOut::Fragment(parent) => apply_yrs_xml_node_patch(txn, &parent, *index, patch),
+                XmlOut::Element(parent) => apply_yrs_xml_node_patch(txn, &parent, *index, patch),
+                XmlOut::Text(_) => Err(VerifierError::YrsError(
+                    "XML text nodes have no children".into(),
+                )),
+            }
+        }
+        [attrs, key] if attrs == "attrs" => {
+            let XmlOut::Element(element) = xml_node_at(txn, root, &indices)? else {
+                return Err(VerifierError::YrsError(
+                    "Only XML elements have attributes".into(),
+                ));
+            };
+            if patch.op == OrmPatchOp::add {
+                let value = match patch.value.as_ref() {
+                    Some(Value::String(s)) => s.clone(),
+                    Some(other) => other.to_string(),
+                    None => {
+                        return Err(VerifierError::YrsError(
+                            "Attribute patch without value".into(),
+                        ))
+                    }
+                };
+                element.insert_attribute(txn, key.as_str(), value);
+            } else {
+                element.remove_attribute(txn, key);
+            }
+            Ok(())
+        }
+        [text, offset @ ..] if text == "text" => {
+            let XmlOut::Text(xml_text) = xml_node_at(txn, root, &indices)? else {
+                return Err(VerifierError::YrsError("Not an XML text node".into()));
+            };
+            apply_yrs_text_patch(txn, &xml_text, offset, patch)
+        }
+        _ => Err(VerifierError::YrsError("Invalid XML patch path".into())),
+    }
+}
+
+/// Inserts or removes the child node at `index` of `parent`.
+fn apply_yrs_xml_node_patch<F: XmlFragment>(
+    txn: &mut yrs::TransactionMut,
+    parent: &F,
+    index: u32,
+    patch: &OrmPatch,
+) -> Result<(), VerifierError> {
+    let len = parent.len(txn);
+    if patch.op == OrmPatchOp::add {
+        let value = patch
+            .value
+            .as_ref()
+            .ok_or(VerifierError::YrsError("Add patch without value".into()))?;
+        // `-` means that we append.
+        let index = if index == u32::MAX { len } else { index };
+        if index > len {
+            return Err(VerifierError::YrsError(format!(
+                "XML node index {} out of bounds (len: {})",
+                index, len
+            )));
+        }
+        insert_xml_json(txn, parent, index, value)
+    } else {
+        if index >= len {
+            return Err(VerifierError::YrsError(format!(
+                "XML node index {} out of bounds for removal (len: {})",
+                index, len
+            )));
+        }
+        parent.remove_range(txn, index, 1);
+        Ok(())
     }
 }
 
@@ -481,7 +964,7 @@ pub(crate) fn yrs_handle_frontend_discrete_update(
     patches: OrmPatches,
     orm_subscription: &DiscreteOrmSubscription,
     doc: &mut Doc,
-    is_array: bool,
+    root_type: YrsRootType,
 ) -> Result<
     (
         DiscreteTransaction,
@@ -493,25 +976,17 @@ pub(crate) fn yrs_handle_frontend_discrete_update(
     VerifierError,
 > {
     let resulting_orm_patches: Rc<RefCell<Vec<OrmPatch>>> = Rc::new(RefCell::new(vec![]));
-    let nuri_clone = orm_subscription.nuri.clone();
-    let (observation, root) = if is_array {
-        let array_ref = doc.get_or_insert_array("ng");
-        let patches_clone = Rc::clone(&resulting_orm_patches);
-        (
-            array_ref.observe_deep(move |tx, ev| {
-                yrs_mutation_callback(tx, ev, &patches_clone, &nuri_clone);
-            }),
-            YrsTarget::Array(array_ref),
-        )
-    } else {
-        let map_ref = doc.get_or_insert_map("ng");
-        let patches_clone = Rc::clone(&resulting_orm_patches);
-        (
-            map_ref.observe_deep(move |tx, ev| {
-                yrs_mutation_callback(tx, ev, &patches_clone, &nuri_clone);
-            }),
-            YrsTarget::Map(map_ref),
-        )
+    let observation = observe_root(
+        doc,
+        root_type,
+        &resulting_orm_patches,
+        &orm_subscription.nuri,
+    );
+    let root = match root_type {
+        YrsRootType::Map => YrsRoot::Target(YrsTarget::Map(doc.get_or_insert_map("ng"))),
+        YrsRootType::Array => YrsRoot::Target(YrsTarget::Array(doc.get_or_insert_array("ng"))),
+        YrsRootType::Text => YrsRoot::Text(doc.get_or_insert_text("ng")),
+        YrsRootType::Xml => YrsRoot::Xml(doc.get_or_insert_xml_fragment(YXML_ROOT)),
     };
     let mut tx: yrs::TransactionMut<'_> = doc.transact_mut();
     for patch in patches {
@@ -522,6 +997,18 @@ pub(crate) fn yrs_handle_frontend_discrete_update(
             .map(|segment| decode_json_pointer(&segment.to_string()))
             .collect();
 
+        let root = match &root {
+            YrsRoot::Text(text) => {
+                apply_yrs_text_patch(&mut tx, text, &parsed_path, &patch)?;
+                continue;
+            }
+            YrsRoot::Xml(fragment) => {
+                apply_yrs_xml_patch(&mut tx, fragment, &parsed_path, &patch)?;
+                continue;
+            }
+            YrsRoot::Target(target) => target,
+        };
+        let is_array = root_type == YrsRootType::Array;
         if patch.op == OrmPatchOp::add {
             let value = match &patch.value {
                 Some(v) => json_value_to_yrs_in(v),
@@ -532,10 +1019,10 @@ pub(crate) fn yrs_handle_frontend_discrete_update(
             };
 
             // Navigate to parent and insert/update the final key
-            apply_yrs_add_patch(&mut tx, &parsed_path, value, is_array, &root)?;
+            apply_yrs_add_patch(&mut tx, &parsed_path, value, is_array, root)?;
         } else {
             // patch.op == OrmPatchOp::remove
-            apply_yrs_remove_patch(&mut tx, &parsed_path, is_array, &root)?;
+            apply_yrs_remove_patch(&mut tx, &parsed_path, is_array, root)?;
         }
     }
 
@@ -553,11 +1040,7 @@ pub(crate) fn yrs_handle_frontend_discrete_update(
     let transac = doc.transact();
     let full_state = transac.encode_state_as_update_v1(&empty_state_vector);
 
-    let transaction = if is_array {
-        DiscreteTransaction::YArray(update_bytes)
-    } else {
-        DiscreteTransaction::YMap(update_bytes)
-    };
+    let transaction = root_type.transaction(update_bytes);
 
     let nuri = orm_subscription.nuri.clone();
     let branch_id = orm_subscription.branch_id;
@@ -570,3 +1053,234 @@ pub(crate) fn yrs_handle_frontend_discrete_update(
         full_state,
     ))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn patch(op: OrmPatchOp, val_type: Option<OrmPatchType>, value: Option<Value>) -> OrmPatch {
+        OrmPatch {
+            op,
+            valType: val_type,
+            path: String::new(),
+            value,
+        }
+    }
+
+    fn text_add(value: &str) -> OrmPatch {
+        patch(
+            OrmPatchOp::add,
+            Some(OrmPatchType::text),
+            Some(json!(value)),
+        )
+    }
+
+    fn text_remove(len: u32) -> OrmPatch {
+        patch(
+            OrmPatchOp::remove,
+            Some(OrmPatchType::text),
+            Some(json!(len)),
+        )
+    }
+
+    fn segments(path: &str) -> Vec<String> {
+        path.split('/').skip(1).map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    pub fn test_apply_yrs_text_patch() {
+        let doc = new_yrs_text_doc();
+        let text = doc.get_or_insert_text("ng");
+        let mut txn = doc.transact_mut();
+
+        apply_yrs_text_patch(&mut txn, &text, &[], &text_add("héllo")).unwrap();
+        apply_yrs_text_patch(&mut txn, &text, &segments("/5"), &text_add(" world")).unwrap();
+        apply_yrs_text_patch(&mut txn, &text, &segments("/0"), &text_remove(1)).unwrap();
+        assert_eq!(text.get_string(&txn), "éllo world");
+
+        // Offsets are in UTF-16 code units, the emoji takes two.
+        apply_yrs_text_patch(&mut txn, &text, &segments("/0"), &text_add("😀")).unwrap();
+        apply_yrs_text_patch(&mut txn, &text, &segments("/2"), &text_add("!")).unwrap();
+        assert_eq!(text.get_string(&txn), "😀!éllo world");
+
+        // Removals past the end are clamped.
+        apply_yrs_text_patch(&mut txn, &text, &segments("/8"), &text_remove(100)).unwrap();
+        assert_eq!(text.get_string(&txn), "😀!éllo ");
+
+        assert!(apply_yrs_text_patch(&mut txn, &text, &segments("/9"), &text_add("x")).is_err());
+        assert!(apply_yrs_text_patch(&mut txn, &text, &segments("/a"), &text_add("x")).is_err());
+        assert!(apply_yrs_text_patch(&mut txn, &text, &segments("/0/1"), &text_add("x")).is_err());
+        assert!(apply_yrs_text_patch(
+            &mut txn,
+            &text,
+            &segments("/0"),
+            &patch(OrmPatchOp::add, Some(OrmPatchType::text), Some(json!(1)))
+        )
+        .is_err());
+
+        // An empty path replaces or clears the whole text.
+        apply_yrs_text_patch(&mut txn, &text, &[], &text_add("new")).unwrap();
+        assert_eq!(text.get_string(&txn), "new");
+        apply_yrs_text_patch(&mut txn, &text, &[], &patch(OrmPatchOp::remove, None, None)).unwrap();
+        assert_eq!(text.get_string(&txn), "");
+    }
+
+    #[test]
+    pub fn test_apply_yrs_xml_patch() {
+        let doc = new_yrs_text_doc();
+        let root = doc.get_or_insert_xml_fragment(YXML_ROOT);
+        let mut txn = doc.transact_mut();
+        let add = |value: Value| patch(OrmPatchOp::add, None, Some(value));
+        let remove = patch(OrmPatchOp::remove, None, None);
+
+        apply_yrs_xml_patch(
+            &mut txn,
+            &root,
+            &[],
+            &add(json!([{
+                "type": "paragraph",
+                "attrs": {"align": "left"},
+                "content": [{"type": "text", "text": "Hello"}]
+            }])),
+        )
+        .unwrap();
+        apply_yrs_xml_patch(
+            &mut txn,
+            &root,
+            &segments("/0/content/1"),
+            &add(json!({"type": "hard_break", "attrs": {}, "content": []})),
+        )
+        .unwrap();
+        apply_yrs_xml_patch(
+            &mut txn,
+            &root,
+            &segments("/-"),
+            &add(json!({"type": "heading", "attrs": {"level": "1"}, "content": []})),
+        )
+        .unwrap();
+        apply_yrs_xml_patch(
+            &mut txn,
+            &root,
+            &segments("/0/attrs/align"),
+            &add(json!("right")),
+        )
+        .unwrap();
+        apply_yrs_xml_patch(
+            &mut txn,
+            &root,
+            &segments("/0/content/0/text/5"),
+            &text_add(" world"),
+        )
+        .unwrap();
+        apply_yrs_xml_patch(&mut txn, &root, &segments("/1/attrs/level"), &remove).unwrap();
+
+        assert_eq!(
+            yrs_xml_to_json(&txn, &XmlOut::Fragment(root.clone())),
+            json!([
+                {
+                    "type": "paragraph",
+                    "attrs": {"align": "right"},
+                    "content": [
+                        {"type": "text", "text": "Hello world"},
+                        {"type": "hard_break", "attrs": {}, "content": []}
+                    ]
+                },
+                {"type": "heading", "attrs": {}, "content": []}
+            ])
+        );
+
+        apply_yrs_xml_patch(&mut txn, &root, &segments("/0/content/1"), &remove).unwrap();
+        apply_yrs_xml_patch(&mut txn, &root, &segments("/1"), &remove).unwrap();
+        assert_eq!(
+            yrs_xml_to_json(&txn, &XmlOut::Fragment(root.clone())),
+            json!([{
+                "type": "paragraph",
+                "attrs": {"align": "right"},
+                "content": [{"type": "text", "text": "Hello world"}]
+            }])
+        );
+
+        // Invalid paths and out of bounds indices are rejected.
+        assert!(apply_yrs_xml_patch(&mut txn, &root, &segments("/3"), &remove).is_err());
+        assert!(
+            apply_yrs_xml_patch(&mut txn, &root, &segments("/5"), &add(json!({"type": "p"})))
+                .is_err()
+        );
+        assert!(apply_yrs_xml_patch(
+            &mut txn,
+            &root,
+            &segments("/0/content/0/attrs/x"),
+            &add(json!("y"))
+        )
+        .is_err());
+        assert!(
+            apply_yrs_xml_patch(&mut txn, &root, &segments("/0/text/0"), &text_add("x")).is_err()
+        );
+        assert!(apply_yrs_xml_patch(&mut txn, &root, &segments("/0/foo"), &remove).is_err());
+
+        // An empty path clears the document.
+        apply_yrs_xml_patch(&mut txn, &root, &[], &remove).unwrap();
+        assert_eq!(
+            yrs_xml_to_json(&txn, &XmlOut::Fragment(root.clone())),
+            json!([])
+        );
+    }
+
+    #[test]
+    pub fn test_push_text_delta_patches() {
+        let mut patches = vec![];
+        push_text_delta_patches(
+            &mut patches,
+            "/0/content/1/text",
+            &[
+                Delta::Retain(2, None),
+                Delta::Inserted(Out::Any(Any::String("😀b".into())), None),
+                Delta::Deleted(3),
+                Delta::Retain(1, None),
+                Delta::Inserted(Out::Any(Any::String("c".into())), None),
+            ],
+        );
+        assert_eq!(
+            patches
+                .iter()
+                .map(|p| (p.op.clone(), p.path.as_str(), p.value.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (OrmPatchOp::add, "/0/content/1/text/2", Some(json!("😀b"))),
+                (OrmPatchOp::remove, "/0/content/1/text/5", Some(json!(3))),
+                (OrmPatchOp::add, "/0/content/1/text/6", Some(json!("c"))),
+            ]
+        );
+        assert!(patches
+            .iter()
+            .all(|p| p.valType == Some(OrmPatchType::text)));
+    }
+
+    #[test]
+    pub fn test_text_patches_round_trip() {
+        // Patches emitted for a change, applied to a copy of the text, give the same text.
+        let doc = new_yrs_text_doc();
+        let text = doc.get_or_insert_text("ng");
+        text.insert(&mut doc.transact_mut(), 0, "hello world");
+
+        let patches: Rc<RefCell<Vec<OrmPatch>>> = Rc::new(RefCell::new(vec![]));
+        let nuri = NuriV0::new_empty();
+        let observation = observe_root(&doc, YrsRootType::Text, &patches, &nuri);
+        {
+            let mut txn = doc.transact_mut();
+            text.remove_range(&mut txn, 0, 1);
+            text.insert(&mut txn, 0, "H");
+            text.insert(&mut txn, 11, "!");
+        }
+        drop(observation);
+
+        let copy_doc = new_yrs_text_doc();
+        let copy = copy_doc.get_or_insert_text("ng");
+        let mut txn = copy_doc.transact_mut();
+        copy.insert(&mut txn, 0, "hello world");
+        for patch in patches.borrow().iter() {
+            apply_yrs_text_patch(&mut txn, &copy, &segments(&patch.path), patch).unwrap();
+        }
+        assert_eq!(copy.get_string(&txn), "Hello world!");
+    }
+}
@@ -22,6 +22,7 @@ use crate::orm::utils::escape_json_pointer_segment;
 use crate::types::*;
 use crate::verifier::*;
 use ng_net::types::OverlayLink;
+use ng_repo::types::ObjectId;
 use ng_repo::types::OverlayId;
 use ng_repo::types::RepoId;
 use serde_json::json;
@@ -44,12 +45,13 @@ impl Verifier {
     /// Applies quad patches and
     /// generates and sends JSON patches to JS-land.
     ///
-    /// TODO: How to prevent duplicate application of change data?
+    /// Changes are keyed by `commit_id`: a commit that was already applied to a subscription is skipped for it.
     pub(crate) async fn orm_backend_update(
         &mut self,
         subscription_id: u64,
         repo_id: RepoId,
         overlay_id: OverlayId,
+        commit_id: ObjectId,
         patch: GraphQuadsPatch,
     ) {
         let inserts = patch.inserts;
@@ -73,8 +75,15 @@ impl Verifier {
         // );
 
         // Apply changes to all affected scopes and send patches to clients
-        self.apply_changes_to_all_scopes(repo_id, overlay_id, &inserts, &removes, subscription_id)
-            .await;
+        self.apply_changes_to_all_scopes(
+            repo_id,
+            overlay_id,
+            commit_id,
+            &inserts,
+            &removes,
+            subscription_id,
+        )
+        .await;
     }
 
     /// Processes database quad updates. For each subscription, whose scope is affected:
@@ -84,6 +93,7 @@ impl Verifier {
         &mut self,
         repo_id: RepoId,
         overlay_id: OverlayId,
+        commit_id: ObjectId,
         inserts: &[Quad],
         removes: &[Quad],
         origin_subscription_id: u64,
@@ -111,6 +121,17 @@ impl Verifier {
                 continue;
             }
 
+            // Drop changes of commits this subscription has already seen.
+            if !subscription.applied_commits.insert(commit_id) {
+                log_debug!(
+                    "[orm_backend_update] commit {} already applied to subscription {}",
+                    commit_id,
+                    subscription_id
+                );
+                self.orm_subscriptions.insert(subscription_id, subscription);
+                continue;
+            }
+
             // TODO: Also filter if it's within page-order.
             // Filter quads by subject scope if applicable
             let (inserts, removes) =
@@ -17,6 +17,7 @@ use futures::SinkExt;
 use ng_net::app_protocol::*;
 pub use ng_net::orm::{OrmPatches, OrmShapeType};
 use ng_oxigraph::oxigraph::sparql::QueryResults;
+use ng_repo::errors::NgError;
 use ng_repo::log::*;
 use serde_json::json;
 
@@ -51,7 +52,8 @@ impl Verifier {
             let doc_nuri = graph_subj[0].clone();
 
             let (sparql_update, failed_patches) =
-                create_sparql_update_query_for_patches(orm_subscription, &patches);
+                create_sparql_update_query_for_patches(orm_subscription, &patches)
+                    .map_err(|e| e.to_string())?;
 
             (doc_nuri, sparql_update, failed_patches)
         };
@@ -271,12 +273,228 @@ struct PathTarget {
     child_iri: Option<String>, // IRI of object referenced directly (for link ops)
 }
 
+const RDF: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+const XSD: &str = "http://www.w3.org/2001/XMLSchema#";
+
+/// Pattern matching `seq` only if it is an `rdf:Seq` (typed as such or having membership properties).
+fn seq_guard(seq: &str) -> String {
+    format!(
+        "FILTER(EXISTS {{ {seq} a <{RDF}Seq> }} || EXISTS {{ {seq} {seq}_m {seq}_v FILTER(STRSTARTS(STR({seq}_m), \"{RDF}_\")) }})"
+    )
+}
+
+/// Pattern matching `head` only if it is an `rdf:List` (a list cell or `rdf:nil`).
+fn list_guard(head: &str) -> String {
+    format!("FILTER({head} = <{RDF}nil> || EXISTS {{ {head} <{RDF}first> {head}_f }})")
+}
+
+/// Pattern binding `cell` to the list cell at position `index` of the `rdf:List` linked by `<subj> <pred>`.
+fn list_cell_pattern(subj: &str, pred: &str, index: usize, cell: &str) -> String {
+    if index == 0 {
+        return format!("<{subj}> <{pred}> {cell} . {cell} <{RDF}first> {cell}_f");
+    }
+    let rest_path = vec![format!("<{RDF}rest>"); index].join("/");
+    format!(
+        "<{subj}> <{pred}> {cell}_h . {cell}_h <{RDF}first> {cell}_f . {cell}_h {rest_path} {cell}"
+    )
+}
+
+// ------------------------- Child Lookup Helper ---------------------------
+/// Finds the graph of a tracked child of `parent_subject` (via `pred_iri`) by the child's subject IRI.
+fn find_child_graph(
+    orm_subscription: &OrmSubscription,
+    parent_graph: &str,
+    parent_subject: &str,
+    parent_shape_iri: &str,
+    pred_iri: &str,
+    child_subject: &str,
+) -> Option<String> {
+    let parent_obj =
+        orm_subscription.get_tracked_orm_object(parent_graph, parent_subject, parent_shape_iri)?;
+    let parent_guard = parent_obj.read().ok()?;
+    let tracked_pred = parent_guard.tracked_predicates.get(pred_iri)?;
+    let pred_guard = tracked_pred.read().ok()?;
+    let graph = pred_guard
+        .tracked_children
+        .iter()
+        .filter_map(|w| w.upgrade())
+        .find_map(|child| {
+            let child_guard = child.read().ok()?;
+            (child_guard.subject_iri == child_subject).then(|| child_guard.graph_iri.clone())
+        });
+    graph
+}
+
+// ------------------------- Builder ---------------------------------------
+struct SparqlBuilder {
+    queries: Vec<String>,
+    var_counter: usize,
+}
+impl SparqlBuilder {
+    fn new() -> Self {
+        Self {
+            queries: vec![],
+            var_counter: 0,
+        }
+    }
+    fn next_var(&mut self) -> String {
+        let v = format!("?o{}", self.var_counter);
+        self.var_counter += 1;
+        v
+    }
+    fn overwrite_link(&mut self, graph: &str, subj: &str, pred: &str, child: &str) {
+        let var = self.next_var();
+        let combined = format!(
+            "DELETE {{\n  GRAPH <{}> {{ <{}> <{}> {} }}\n}} INSERT {{\n  GRAPH <{}> {{ <{}> <{}> <{}> }}\n}} WHERE {{\n  OPTIONAL {{ GRAPH <{}> {{ <{}> <{}> {} }} }}\n}}",
+            graph, subj, pred, var,
+            graph, subj, pred, child,
+            graph, subj, pred, var
+        );
+        self.queries.push(combined);
+    }
+    fn add_link(&mut self, graph: &str, subj: &str, pred: &str, child: &str) {
+        // Use INSERT DATA for unconditional addition (engine appears to ignore plain INSERT without WHERE)
+        let insert = format!(
+            "INSERT DATA {{\n  GRAPH <{}> {{ <{}> <{}> <{}> }}\n}}",
+            graph, subj, pred, child
+        );
+        self.queries.push(insert);
+    }
+    fn remove_link(&mut self, graph: &str, subj: &str, pred: &str, child: &str) {
+        let del = format!(
+            "DELETE DATA {{\n  GRAPH <{}> {{ <{}> <{}> <{}> }}\n}}",
+            graph, subj, pred, child
+        );
+        self.queries.push(del);
+    }
+    fn overwrite_value(&mut self, graph: &str, subj: &str, pred: &str, value: &str) {
+        let var = self.next_var();
+        let combined = format!(
+            "DELETE {{\n  GRAPH <{}> {{ <{}> <{}> {} }}\n}} INSERT {{\n  GRAPH <{}> {{ <{}> <{}> {} }}\n}} WHERE {{\n  OPTIONAL {{ GRAPH <{}> {{ <{}> <{}> {} }} }}\n}}",
+            graph, subj, pred, var,
+            graph, subj, pred, value,
+            graph, subj, pred, var
+        );
+        self.queries.push(combined);
+    }
+    fn add_value(&mut self, graph: &str, subj: &str, pred: &str, value: &str) {
+        // Use INSERT DATA to reliably add multi-valued literal/object without needing a WHERE pattern
+        let insert = format!(
+            "INSERT DATA {{\n  GRAPH <{}> {{ <{}> <{}> {} }}\n}}",
+            graph, subj, pred, value
+        );
+        self.queries.push(insert);
+    }
+    fn remove_value(&mut self, graph: &str, subj: &str, pred: &str, value: &str) {
+        let del = format!(
+            "DELETE DATA {{\n  GRAPH <{}> {{ <{}> <{}> {} }}\n}}",
+            graph, subj, pred, value
+        );
+        self.queries.push(del);
+    }
+    fn remove_all_values(&mut self, graph: &str, subj: &str, pred: &str) {
+        let var = self.next_var();
+        let del = format!(
+            "DELETE {{\n  GRAPH <{}> {{ <{}> <{}> {} }}\n}} WHERE {{\n  GRAPH <{}> {{ <{}> <{}> {} }}\n}}",
+            graph, subj, pred, var, graph, subj, pred, var
+        );
+        self.queries.push(del);
+    }
+    fn remove_object(&mut self, graph: &str, subj: &str, schema: &OrmSchemaShape) {
+        for pred_schema in schema.predicates.iter() {
+            self.remove_all_values(graph, subj, &pred_schema.iri);
+        }
+    }
+    /// Inserts `value` at position `index` of the ordered collection linked by `<subj> <pred>`.
+    /// Both `rdf:Seq` and `rdf:List` are handled, only the query matching the stored form has an effect.
+    /// If no collection exists yet, inserting at index 0 creates an `rdf:List`.
+    fn list_insert(&mut self, graph: &str, subj: &str, pred: &str, index: usize, value: &str) {
+        // rdf:Seq: shift members at or after the position, then insert.
+        self.seq_shift(graph, subj, pred, index + 1, 1);
+        let seq = self.next_var();
+        self.queries.push(format!(
+            "INSERT {{\n  GRAPH <{g}> {{ {seq} <{RDF}_{m}> {value} }}\n}} WHERE {{\n  GRAPH <{g}> {{ <{subj}> <{pred}> {seq} . {} }}\n}}",
+            seq_guard(&seq),
+            g = graph,
+            m = index + 1,
+        ));
+
+        // rdf:List: relink the cell before the position to a new cell.
+        let cell = format!("_:c{}", self.var_counter);
+        let (next, prev) = (self.next_var(), self.next_var());
+        if index == 0 {
+            self.queries.push(format!(
+                "DELETE {{\n  GRAPH <{g}> {{ <{subj}> <{pred}> {next} }}\n}} INSERT {{\n  GRAPH <{g}> {{ <{subj}> <{pred}> {cell} . {cell} <{RDF}first> {value} ; <{RDF}rest> {next} }}\n}} WHERE {{\n  GRAPH <{g}> {{ <{subj}> <{pred}> {next} . {} }}\n}}",
+                list_guard(&next),
+                g = graph,
+            ));
+            // No collection yet: create a list with a single cell.
+            self.queries.push(format!(
+                "INSERT {{\n  GRAPH <{g}> {{ <{subj}> <{pred}> {cell} . {cell} <{RDF}first> {value} ; <{RDF}rest> <{RDF}nil> }}\n}} WHERE {{\n  FILTER NOT EXISTS {{ GRAPH <{g}> {{ <{subj}> <{pred}> {prev} }} }}\n}}",
+                g = graph,
+            ));
+        } else {
+            self.queries.push(format!(
+                "DELETE {{\n  GRAPH <{g}> {{ {prev} <{RDF}rest> {next} }}\n}} INSERT {{\n  GRAPH <{g}> {{ {prev} <{RDF}rest> {cell} . {cell} <{RDF}first> {value} ; <{RDF}rest> {next} }}\n}} WHERE {{\n  GRAPH <{g}> {{ {} . {prev} <{RDF}rest> {next} }}\n}}",
+                list_cell_pattern(subj, pred, index - 1, &prev),
+                g = graph,
+            ));
+        }
+    }
+    /// Removes the element at position `index` of the ordered collection linked by `<subj> <pred>`.
+    fn list_remove(&mut self, graph: &str, subj: &str, pred: &str, index: usize) {
+        // rdf:Seq: remove the member, then close the gap.
+        let (seq, val) = (self.next_var(), self.next_var());
+        self.queries.push(format!(
+            "DELETE {{\n  GRAPH <{g}> {{ {seq} <{RDF}_{m}> {val} }}\n}} WHERE {{\n  GRAPH <{g}> {{ <{subj}> <{pred}> {seq} . {seq} <{RDF}_{m}> {val} }}\n}}",
+            g = graph,
+            m = index + 1,
+        ));
+        self.seq_shift(graph, subj, pred, index + 2, -1);
+
+        // rdf:List: unlink the cell.
+        let (cell, next, prev, first) = (
+            self.next_var(),
+            self.next_var(),
+            self.next_var(),
+            self.next_var(),
+        );
+        if index == 0 {
+            self.queries.push(format!(
+                "DELETE {{\n  GRAPH <{g}> {{ <{subj}> <{pred}> {cell} . {cell} <{RDF}first> {first} ; <{RDF}rest> {next} }}\n}} INSERT {{\n  GRAPH <{g}> {{ <{subj}> <{pred}> {next} }}\n}} WHERE {{\n  GRAPH <{g}> {{ <{subj}> <{pred}> {cell} . {cell} <{RDF}first> {first} ; <{RDF}rest> {next} }}\n}}",
+                g = graph,
+            ));
+        } else {
+            self.queries.push(format!(
+                "DELETE {{\n  GRAPH <{g}> {{ {prev} <{RDF}rest> {cell} . {cell} <{RDF}first> {first} ; <{RDF}rest> {next} }}\n}} INSERT {{\n  GRAPH <{g}> {{ {prev} <{RDF}rest> {next} }}\n}} WHERE {{\n  GRAPH <{g}> {{ {} . {prev} <{RDF}rest> {cell} . {cell} <{RDF}first> {first} ; <{RDF}rest> {next} }}\n}}",
+                list_cell_pattern(subj, pred, index - 1, &prev),
+                g = graph,
+            ));
+        }
+    }
+    /// Renumbers the `rdf:_n` members with `n >= from` of an `rdf:Seq` by `delta`.
+    fn seq_shift(&mut self, graph: &str, subj: &str, pred: &str, from: usize, delta: i64) {
+        let (seq, member, val, n, new_member) = (
+            self.next_var(),
+            self.next_var(),
+            self.next_var(),
+            self.next_var(),
+            self.next_var(),
+        );
+        self.queries.push(format!(
+            "DELETE {{\n  GRAPH <{g}> {{ {seq} {member} {val} }}\n}} INSERT {{\n  GRAPH <{g}> {{ {seq} {new_member} {val} }}\n}} WHERE {{\n  GRAPH <{g}> {{ <{subj}> <{pred}> {seq} . {seq} {member} {val} }}\n  FILTER(STRSTARTS(STR({member}), \"{RDF}_\"))\n  BIND(<{XSD}integer>(STRAFTER(STR({member}), \"{RDF}_\")) AS {n})\n  FILTER({n} >= {from})\n  BIND(IRI(CONCAT(\"{RDF}_\", STR({n} + ({delta})))) AS {new_member})\n}}",
+            g = graph,
+        ));
+    }
+    fn finish(self) -> String {
+        self.queries.join(";\n")
+    }
+}
+
 fn create_sparql_update_query_for_patches(
     orm_subscription: &OrmSubscription,
     patches: &OrmPatches,
-) -> (String, Vec<(OrmPatch, PathTarget)>) {
-    // TODO: Support patches by array id.
-
+) -> Result<(String, Vec<(OrmPatch, PathTarget)>), NgError> {
     // ------------------------- Schema Selection Helper ----------------------
     fn select_child_schema(
         subject_iri: Option<&String>,
@@ -328,18 +546,19 @@ fn create_sparql_update_query_for_patches(
         path: &str,
         orm_subscription: &OrmSubscription,
         staged_children: &HashMap<String, (String, String)>,
-    ) -> Option<PathTarget> {
+    ) -> Result<Option<PathTarget>, NgError> {
         let segs: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
         if segs.is_empty() {
-            return None;
+            return Ok(None);
         }
 
         // root composite
         let mut root_split = segs[0].split('|');
-        let raw_graph = root_split.next()?.to_string();
-        let raw_subject = root_split.next()?.to_string();
-        let graph = decode_json_pointer(&raw_graph);
-        let subject = decode_json_pointer(&raw_subject);
+        let (Some(raw_graph), Some(raw_subject)) = (root_split.next(), root_split.next()) else {
+            return Ok(None);
+        };
+        let graph = decode_json_pointer(raw_graph);
+        let subject = decode_json_pointer(raw_subject);
 
         let mut idx = 1;
         let mut current_graph = graph.clone();
@@ -353,15 +572,12 @@ fn create_sparql_update_query_for_patches(
 
         // Path points to root object?
         if idx == segs.len() {
-            let mut cs = segs[0].split('|');
-            let graph = decode_json_pointer(&cs.next()?.to_string());
-            let subject = decode_json_pointer(&cs.next()?.to_string());
-            return Some(PathTarget {
+            return Ok(Some(PathTarget {
                 graph,
                 subject,
                 child_iri: None,
                 pred_schema: None,
-            });
+            }));
         }
 
         while idx < segs.len() {
@@ -373,7 +589,7 @@ fn create_sparql_update_query_for_patches(
                 .find(|p| p.readablePredicate == pred_name)
                 .cloned();
             let Some(pred_schema) = &pred_schema_opt else {
-                return None;
+                return Ok(None);
             };
 
             idx += 1;
@@ -381,54 +597,76 @@ fn create_sparql_update_query_for_patches(
                 // primitive leaf expected
                 // If more segments follow -> invalid path for primitives
                 if idx != segs.len() {
-                    return None;
+                    return Ok(None);
                 }
 
-                return Some(PathTarget {
+                return Ok(Some(PathTarget {
                     graph: current_graph,
                     subject: current_subject,
                     pred_schema: Some(pred_schema.clone()),
                     child_iri: None,
-                });
+                }));
             }
             // object predicate
             if pred_schema.is_multi() {
                 if idx >= segs.len() {
-                    return Some(PathTarget {
+                    return Ok(Some(PathTarget {
                         graph: current_graph,
                         subject: current_subject,
                         pred_schema: Some(pred_schema.clone()),
                         child_iri: None,
-                    });
+                    }));
                 }
                 let composite = segs[idx];
-                if !composite.contains('|') {
-                    log_debug!(
-                        "[resolve_path] invalid composite '{}' for multi-object pred='{}'",
-                        composite,
-                        pred_schema.iri
-                    );
-                    return None;
-                }
                 let parent_graph = current_graph.clone();
                 let parent_subject = current_subject.clone();
-                let mut cs = composite.split('|');
-                let raw_child_graph = cs.next()?.to_string();
-                let raw_child_subj = cs.next()?.to_string();
-                let child_graph = decode_json_pointer(&raw_child_graph);
-                let child_subj_decoded = decode_json_pointer(&raw_child_subj);
+                let (child_graph, child_subj_decoded) = if composite.contains('|') {
+                    let mut cs = composite.split('|');
+                    let (Some(raw_child_graph), Some(raw_child_subj)) = (cs.next(), cs.next())
+                    else {
+                        return Ok(None);
+                    };
+                    (
+                        decode_json_pointer(raw_child_graph),
+                        decode_json_pointer(raw_child_subj),
+                    )
+                } else {
+                    // Element addressed by its stable id (the child's `@id`) only.
+                    let child_subj = decode_json_pointer(composite);
+                    let current_key = format!("/{}", segs[..=idx].join("/"));
+                    let child_graph = find_child_graph(
+                        orm_subscription,
+                        &current_graph,
+                        &current_subject,
+                        &current_schema.iri,
+                        &pred_schema.iri,
+                        &child_subj,
+                    )
+                    .or_else(|| {
+                        staged_children
+                            .get(&current_key)
+                            .filter(|(_, graph)| !graph.is_empty())
+                            .map(|(_, graph)| decode_json_pointer(graph))
+                    })
+                    .ok_or_else(|| {
+                        NgError::OrmError(format!(
+                            "Cannot find the graph of the child {child_subj} in path {path}"
+                        ))
+                    })?;
+                    (child_graph, child_subj)
+                };
                 current_graph = child_graph.clone();
                 current_subject = child_subj_decoded.clone();
                 idx += 1;
                 if idx == segs.len() {
                     // link to child object itself
                     let child_iri = Some(child_subj_decoded);
-                    return Some(PathTarget {
+                    return Ok(Some(PathTarget {
                         graph: parent_graph,
                         subject: parent_subject,
                         pred_schema: Some(pred_schema.clone()),
                         child_iri,
-                    });
+                    }));
                 } else {
                     // continue traversal inside child
                     current_schema =
@@ -438,12 +676,12 @@ fn create_sparql_update_query_for_patches(
             } else {
                 // single-valued object predicate, like `/root/pred/<object>`
                 if idx == segs.len() {
-                    return Some(PathTarget {
+                    return Ok(Some(PathTarget {
                         graph: current_graph.clone(),
                         subject: current_subject.clone(),
                         pred_schema: Some(pred_schema.clone()),
                         child_iri: None,
-                    });
+                    }));
                 }
 
                 // Check if there was a new child created for this path.
@@ -498,97 +736,73 @@ fn create_sparql_update_query_for_patches(
                         path,
                         segs[idx - 1]
                     );
-                    return None;
+                    return Ok(None);
                 }
             }
         }
         // If we exit loop without return and have predicate schema -> treat as leaf primitive reached earlier.
-        None
+        Ok(None)
     }
 
-    // ------------------------- Builder ---------------------------------------
-    struct SparqlBuilder {
-        queries: Vec<String>,
-        var_counter: usize,
-    }
-    impl SparqlBuilder {
-        fn new() -> Self {
-            Self {
-                queries: vec![],
-                var_counter: 0,
-            }
-        }
-        fn next_var(&mut self) -> String {
-            let v = format!("?o{}", self.var_counter);
-            self.var_counter += 1;
-            v
-        }
-        fn overwrite_link(&mut self, graph: &str, subj: &str, pred: &str, child: &str) {
-            let var = self.next_var();
-            let combined = format!(
-                "DELETE {{\n  GRAPH <{}> {{ <{}> <{}> {} }}\n}} INSERT {{\n  GRAPH <{}> {{ <{}> <{}> <{}> }}\n}} WHERE {{\n  OPTIONAL {{ GRAPH <{}> {{ <{}> <{}> {} }} }}\n}}",
-                graph, subj, pred, var,
-                graph, subj, pred, child,
-                graph, subj, pred, var
-            );
-            self.queries.push(combined);
-        }
-        fn add_link(&mut self, graph: &str, subj: &str, pred: &str, child: &str) {
-            // Use INSERT DATA for unconditional addition (engine appears to ignore plain INSERT without WHERE)
-            let insert = format!(
-                "INSERT DATA {{\n  GRAPH <{}> {{ <{}> <{}> <{}> }}\n}}",
-                graph, subj, pred, child
-            );
-            self.queries.push(insert);
-        }
-        fn remove_link(&mut self, graph: &str, subj: &str, pred: &str, child: &str) {
-            let del = format!(
-                "DELETE DATA {{\n  GRAPH <{}> {{ <{}> <{}> <{}> }}\n}}",
-                graph, subj, pred, child
-            );
-            self.queries.push(del);
-        }
-        fn overwrite_value(&mut self, graph: &str, subj: &str, pred: &str, value: &str) {
-            let var = self.next_var();
-            let combined = format!(
-                "DELETE {{\n  GRAPH <{}> {{ <{}> <{}> {} }}\n}} INSERT {{\n  GRAPH <{}> {{ <{}> <{}> {} }}\n}} WHERE {{\n  OPTIONAL {{ GRAPH <{}> {{ <{}> <{}> {} }} }}\n}}",
-                graph, subj, pred, var,
-                graph, subj, pred, value,
-                graph, subj, pred, var
-            );
-            self.queries.push(combined);
-        }
-        fn add_value(&mut self, graph: &str, subj: &str, pred: &str, value: &str) {
-            // Use INSERT DATA to reliably add multi-valued literal/object without needing a WHERE pattern
-            let insert = format!(
-                "INSERT DATA {{\n  GRAPH <{}> {{ <{}> <{}> {} }}\n}}",
-                graph, subj, pred, value
-            );
-            self.queries.push(insert);
-        }
-        fn remove_value(&mut self, graph: &str, subj: &str, pred: &str, value: &str) {
-            let del = format!(
-                "DELETE DATA {{\n  GRAPH <{}> {{ <{}> <{}> {} }}\n}}",
-                graph, subj, pred, value
+    // ------------------------- Ordered Collections ---------------------------
+    /// Handles a patch with `valType: list`, whose path ends with the index in the ordered collection.
+    /// Reordering is expressed as a remove at the old index followed by an add at the new one.
+    fn add_list_patch(
+        builder: &mut SparqlBuilder,
+        p: &OrmPatch,
+        orm_subscription: &OrmSubscription,
+        staged_children: &HashMap<String, (String, String)>,
+    ) -> Result<(), NgError> {
+        let index = p
+            .path
+            .rsplit_once('/')
+            .and_then(|(parent_path, raw_index)| {
+                Some((parent_path, raw_index.parse::<usize>().ok()?))
+            });
+        let Some((parent_path, index)) = index else {
+            log_err!(
+                "[orm_frontend_update] Missing list index in patch path {}",
+                p.path
             );
-            self.queries.push(del);
-        }
-        fn remove_all_values(&mut self, graph: &str, subj: &str, pred: &str) {
-            let var = self.next_var();
-            let del = format!(
-                "DELETE {{\n  GRAPH <{}> {{ <{}> <{}> {} }}\n}} WHERE {{\n  GRAPH <{}> {{ <{}> <{}> {} }}\n}}",
-                graph, subj, pred, var, graph, subj, pred, var
+            return Ok(());
+        };
+        let Some(target) = resolve_path(parent_path, orm_subscription, staged_children)? else {
+            return Ok(());
+        };
+        let (Some(pred_schema), None) = (target.pred_schema.as_ref(), target.child_iri.as_ref())
+        else {
+            log_err!(
+                "[orm_frontend_update] List patch does not point to a predicate: {}",
+                p.path
             );
-            self.queries.push(del);
-        }
-        fn remove_object(&mut self, graph: &str, subj: &str, schema: &OrmSchemaShape) {
-            for pred_schema in schema.predicates.iter() {
-                self.remove_all_values(graph, subj, &pred_schema.iri);
+            return Ok(());
+        };
+        let (graph, subj, pred) = (&target.graph, &target.subject, &pred_schema.iri);
+
+        match p.op {
+            OrmPatchOp::remove => builder.list_remove(graph, subj, pred, index),
+            OrmPatchOp::add => {
+                let Some(val) = &p.value else {
+                    log_err!(
+                        "[orm_frontend_update] List insert without value: {}",
+                        p.path
+                    );
+                    return Ok(());
+                };
+                let sparql_val = if pred_schema.is_object() {
+                    val.as_str()
+                        .map(|iri| format!("<{}>", decode_json_pointer_iri(iri)))
+                        .ok_or_else(|| format!("Expected the @id of a child object, got {val}"))
+                } else {
+                    json_to_sparql_val(val, pred_schema)
+                };
+                match sparql_val {
+                    Ok(sparql_val) => builder.list_insert(graph, subj, pred, index, &sparql_val),
+                    Err(err) => log_err!("Ignoring list patch {:?} due to error: {err}", p),
+                }
             }
         }
-        fn finish(self) -> String {
-            self.queries.join(";\n")
-        }
+        Ok(())
     }
 
     let mut builder = SparqlBuilder::new();
@@ -638,7 +852,7 @@ fn create_sparql_update_query_for_patches(
         if child_id.is_empty() || child_graph.is_empty() {
             continue;
         }
-        if let Some(target) = resolve_path(base, orm_subscription, &staged_children) {
+        if let Some(target) = resolve_path(base, orm_subscription, &staged_children)? {
             if let Some(pred_schema) = target.pred_schema {
                 if pred_schema.is_object() && !pred_schema.is_multi() {
                     let decoded_child = decode_json_pointer_iri(child_id);
@@ -666,7 +880,11 @@ fn create_sparql_update_query_for_patches(
         if p.path.ends_with("/@id") || p.path.ends_with("/@graph") {
             continue;
         }
-        let Some(target) = resolve_path(&p.path, orm_subscription, &staged_children) else {
+        if p.valType == Some(OrmPatchType::list) {
+            add_list_patch(&mut builder, p, orm_subscription, &staged_children)?;
+            continue;
+        }
+        let Some(target) = resolve_path(&p.path, orm_subscription, &staged_children)? else {
             continue;
         };
         let graph = &target.graph;
@@ -748,5 +966,371 @@ fn create_sparql_update_query_for_patches(
 
     let result = builder.finish();
 
-    (result, failed_patches)
+    Ok((result, failed_patches))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::RwLock;
+
+    use ng_net::orm::{OrmConfig, OrmSchemaDataType, OrmSchemaValType};
+    use ng_oxigraph::oxigraph::model::{
+        BlankNode, GraphName, Literal, NamedNode, Quad, Subject, Term as RdfTerm,
+    };
+    use ng_oxigraph::oxigraph::storage_ng::REPO_IN_MAIN;
+    use ng_oxigraph::oxigraph::store::{StorageError, Store};
+    use ng_repo::types::{OverlayId, PubKey};
+
+    const SUBJ: &str = "did:ng:x:subject";
+    const PRED: &str = "did:ng:x:items";
+
+    fn graph() -> String {
+        NuriV0::repo_graph_name(&PubKey::Ed25519PubKey([1; 32]), &OverlayId::Outer([2; 32]))
+    }
+
+    fn quad(subject: impl Into<Subject>, predicate: &str, object: impl Into<RdfTerm>) -> Quad {
+        Quad::new(
+            subject,
+            NamedNode::new_unchecked(predicate),
+            object,
+            GraphName::NamedNode(NamedNode::new_unchecked(graph())),
+        )
+    }
+
+    /// Loads `quads` in the main branch of a new store, runs `update` on it and returns the resulting quads.
+    fn apply(quads: &HashSet<Quad>, update: String) -> HashSet<Quad> {
+        let store = Store::new().unwrap();
+        store
+            .ng_transaction(|mut transaction| {
+                for quad in quads.iter() {
+                    transaction.insert(quad.as_ref(), REPO_IN_MAIN, false)?;
+                }
+                Ok::<_, StorageError>(())
+            })
+            .unwrap();
+        let (inserts, removes) = store.ng_update(update.as_str(), None).unwrap();
+        let mut res: HashSet<Quad> = quads.difference(&removes).cloned().collect();
+        res.extend(inserts);
+        res
+    }
+
+    fn object_of(quads: &HashSet<Quad>, subject: &Subject, predicate: &str) -> Option<RdfTerm> {
+        quads
+            .iter()
+            .find(|q| &q.subject == subject && q.predicate.as_str() == predicate)
+            .map(|q| q.object.clone())
+    }
+
+    fn literal(value: &str) -> RdfTerm {
+        Literal::new_simple_literal(value).into()
+    }
+
+    /// Values of the `rdf:List` linked by `<SUBJ> <PRED>`, in order.
+    fn list_values(quads: &HashSet<Quad>) -> Vec<RdfTerm> {
+        let first = format!("{RDF}first");
+        let rest = format!("{RDF}rest");
+        let mut values = vec![];
+        let mut cell = object_of(quads, &NamedNode::new_unchecked(SUBJ).into(), PRED);
+        while let Some(RdfTerm::BlankNode(node)) = cell {
+            let subject: Subject = node.into();
+            values.push(object_of(quads, &subject, &first).unwrap());
+            cell = object_of(quads, &subject, &rest);
+        }
+        assert_eq!(
+            cell,
+            Some(NamedNode::new_unchecked(format!("{RDF}nil")).into())
+        );
+        values
+    }
+
+    /// Values of the `rdf:Seq` linked by `<SUBJ> <PRED>`, ordered by membership property.
+    fn seq_values(quads: &HashSet<Quad>) -> Vec<(usize, RdfTerm)> {
+        let seq = match object_of(quads, &NamedNode::new_unchecked(SUBJ).into(), PRED) {
+            Some(RdfTerm::NamedNode(node)) => Subject::from(node),
+            other => panic!("no seq found: {:?}", other),
+        };
+        let member_prefix = format!("{RDF}_");
+        let mut values: Vec<(usize, RdfTerm)> = quads
+            .iter()
+            .filter(|q| q.subject == seq)
+            .filter_map(|q| {
+                let n = q.predicate.as_str().strip_prefix(&member_prefix)?;
+                Some((n.parse().unwrap(), q.object.clone()))
+            })
+            .collect();
+        values.sort_by_key(|(n, _)| *n);
+        values
+    }
+
+    fn list_of(values: &[&str]) -> HashSet<Quad> {
+        let mut quads = HashSet::new();
+        let mut next: RdfTerm = NamedNode::new_unchecked(format!("{RDF}nil")).into();
+        for value in values.iter().rev() {
+            let cell = BlankNode::default();
+            quads.insert(quad(cell.clone(), &format!("{RDF}first"), literal(value)));
+            quads.insert(quad(cell.clone(), &format!("{RDF}rest"), next));
+            next = cell.into();
+        }
+        quads.insert(quad(NamedNode::new_unchecked(SUBJ), PRED, next));
+        quads
+    }
+
+    fn seq_of(values: &[&str]) -> HashSet<Quad> {
+        let seq = NamedNode::new_unchecked("did:ng:x:seq");
+        let mut quads = HashSet::new();
+        quads.insert(quad(NamedNode::new_unchecked(SUBJ), PRED, seq.clone()));
+        quads.insert(quad(
+            seq.clone(),
+            &format!("{RDF}type"),
+            NamedNode::new_unchecked(format!("{RDF}Seq")),
+        ));
+        for (i, value) in values.iter().enumerate() {
+            quads.insert(quad(
+                seq.clone(),
+                &format!("{RDF}_{}", i + 1),
+                literal(value),
+            ));
+        }
+        quads
+    }
+
+    fn literals(values: &[&str]) -> Vec<RdfTerm> {
+        values.iter().map(|v| literal(v)).collect()
+    }
+
+    fn numbered(values: &[&str]) -> Vec<(usize, RdfTerm)> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (i + 1, literal(v)))
+            .collect()
+    }
+
+    fn list_insert(quads: &HashSet<Quad>, index: usize, value: &str) -> HashSet<Quad> {
+        let mut builder = SparqlBuilder::new();
+        builder.list_insert(&graph(), SUBJ, PRED, index, &format!("\"{value}\""));
+        apply(quads, builder.finish())
+    }
+
+    fn list_remove(quads: &HashSet<Quad>, index: usize) -> HashSet<Quad> {
+        let mut builder = SparqlBuilder::new();
+        builder.list_remove(&graph(), SUBJ, PRED, index);
+        apply(quads, builder.finish())
+    }
+
+    #[test]
+    pub fn test_list_insert() {
+        let quads = list_of(&["a", "b", "c"]);
+
+        let front = list_insert(&quads, 0, "x");
+        assert_eq!(list_values(&front), literals(&["x", "a", "b", "c"]));
+
+        let middle = list_insert(&quads, 1, "x");
+        assert_eq!(list_values(&middle), literals(&["a", "x", "b", "c"]));
+
+        let end = list_insert(&quads, 3, "x");
+        assert_eq!(list_values(&end), literals(&["a", "b", "c", "x"]));
+
+        let again = list_insert(&middle, 3, "y");
+        assert_eq!(list_values(&again), literals(&["a", "x", "b", "y", "c"]));
+    }
+
+    #[test]
+    pub fn test_list_insert_creates_list() {
+        let created = list_insert(&HashSet::new(), 0, "x");
+        assert_eq!(list_values(&created), literals(&["x"]));
+
+        let appended = list_insert(&created, 1, "y");
+        assert_eq!(list_values(&appended), literals(&["x", "y"]));
+    }
+
+    #[test]
+    pub fn test_list_remove() {
+        let quads = list_of(&["a", "b", "c"]);
+
+        let front = list_remove(&quads, 0);
+        assert_eq!(list_values(&front), literals(&["b", "c"]));
+
+        let middle = list_remove(&quads, 1);
+        assert_eq!(list_values(&middle), literals(&["a", "c"]));
+
+        let end = list_remove(&quads, 2);
+        assert_eq!(list_values(&end), literals(&["a", "b"]));
+
+        // The removed cell is gone entirely, not only unlinked.
+        assert_eq!(middle.len(), quads.len() - 2);
+
+        let emptied = list_remove(&list_remove(&front, 0), 0);
+        assert_eq!(list_values(&emptied), vec![]);
+    }
+
+    #[test]
+    pub fn test_seq_insert_and_remove() {
+        let quads = seq_of(&["a", "b", "c"]);
+
+        let front = list_insert(&quads, 0, "x");
+        assert_eq!(seq_values(&front), numbered(&["x", "a", "b", "c"]));
+
+        let middle = list_insert(&quads, 1, "x");
+        assert_eq!(seq_values(&middle), numbered(&["a", "x", "b", "c"]));
+
+        let end = list_insert(&quads, 3, "x");
+        assert_eq!(seq_values(&end), numbered(&["a", "b", "c", "x"]));
+
+        let removed = list_remove(&quads, 0);
+        assert_eq!(seq_values(&removed), numbered(&["b", "c"]));
+
+        let removed = list_remove(&middle, 2);
+        assert_eq!(seq_values(&removed), numbered(&["a", "x", "c"]));
+
+        // A seq is never turned into a list.
+        let first = format!("{RDF}first");
+        assert!(!middle.iter().any(|q| q.predicate.as_str() == first));
+    }
+
+    #[test]
+    pub fn test_seq_shift() {
+        let quads = seq_of(&["a", "b", "c", "d"]);
+        let mut builder = SparqlBuilder::new();
+        builder.seq_shift(&graph(), SUBJ, PRED, 3, 2);
+        let shifted = apply(&quads, builder.finish());
+        assert_eq!(
+            seq_values(&shifted),
+            vec![
+                (1, literal("a")),
+                (2, literal("b")),
+                (5, literal("c")),
+                (6, literal("d"))
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_find_child_graph() {
+        let child_shape = Arc::new(OrmSchemaShape {
+            iri: "did:ng:x:ChildShape".to_string(),
+            predicates: vec![],
+        });
+        let pred_schema = Arc::new(OrmSchemaPredicate {
+            dataTypes: vec![OrmSchemaDataType {
+                valType: OrmSchemaValType::shape,
+                literals: None,
+                shape: Some(child_shape.iri.clone()),
+            }],
+            iri: PRED.to_string(),
+            readablePredicate: "items".to_string(),
+            maxCardinality: -1,
+            minCardinality: 0,
+            extra: None,
+        });
+        let root_shape = Arc::new(OrmSchemaShape {
+            iri: "did:ng:x:RootShape".to_string(),
+            predicates: vec![pred_schema.clone()],
+        });
+        let mut schema = HashMap::new();
+        schema.insert(root_shape.iri.clone(), root_shape.clone());
+        schema.insert(child_shape.iri.clone(), child_shape.clone());
+
+        let (sender, _receiver) = futures::channel::mpsc::unbounded();
+        let mut orm_subscription = OrmSubscription::new(
+            OrmShapeType {
+                schema,
+                shape: root_shape.iri.clone(),
+            },
+            1,
+            vec![],
+            vec![],
+            sender,
+            OrmConfig {
+                where_: None,
+                order_by: None,
+                select: None,
+                page_size: 0,
+                max_active_pages: 0,
+            },
+        )
+        .unwrap();
+
+        let parent_graph = "did:ng:o:parent";
+        let parent =
+            orm_subscription.get_or_create_tracked_orm_object(parent_graph, SUBJ, &root_shape);
+        // The same child subject in two graphs: the one linked from the parent wins.
+        let _unlinked = orm_subscription.get_or_create_tracked_orm_object(
+            "did:ng:o:other",
+            "did:ng:x:child",
+            &child_shape,
+        );
+        let linked = orm_subscription.get_or_create_tracked_orm_object(
+            "did:ng:o:child",
+            "did:ng:x:child",
+            &child_shape,
+        );
+        let tracked_pred = Arc::new(RwLock::new(TrackedOrmPredicate {
+            schema: Arc::downgrade(&pred_schema),
+            tracked_children: vec![],
+            current_cardinality: 1,
+            current_literals: None,
+        }));
+        tracked_pred.write().unwrap().add_child(&linked);
+        parent
+            .write()
+            .unwrap()
+            .tracked_predicates
+            .insert(PRED.to_string(), tracked_pred);
+
+        assert_eq!(
+            find_child_graph(
+                &orm_subscription,
+                parent_graph,
+                SUBJ,
+                &root_shape.iri,
+                PRED,
+                "did:ng:x:child"
+            ),
+            Some("did:ng:o:child".to_string())
+        );
+        assert_eq!(
+            find_child_graph(
+                &orm_subscription,
+                parent_graph,
+                SUBJ,
+                &root_shape.iri,
+                PRED,
+                "did:ng:x:unknown"
+            ),
+            None
+        );
+        assert_eq!(
+            find_child_graph(
+                &orm_subscription,
+                parent_graph,
+                SUBJ,
+                &root_shape.iri,
+                "did:ng:x:other_pred",
+                "did:ng:x:child"
+            ),
+            None
+        );
+
+        // a child whose graph cannot be found is an error, instead of an update of the parent graph
+        let patch = |child: &str| OrmPatch {
+            op: OrmPatchOp::remove,
+            valType: None,
+            path: format!("/{parent_graph}|{SUBJ}/items/{child}"),
+            value: None,
+        };
+        let (update, _) = create_sparql_update_query_for_patches(
+            &orm_subscription,
+            &vec![patch("did:ng:x:child")],
+        )
+        .unwrap();
+        assert!(update.contains("did:ng:o:parent"));
+        assert!(create_sparql_update_query_for_patches(
+            &orm_subscription,
+            &vec![patch("did:ng:x:unknown")]
+        )
+        .is_err());
+    }
 }
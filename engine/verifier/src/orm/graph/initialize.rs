@@ -31,6 +31,7 @@ use crate::verifier::Verifier;
 use ng_net::app_protocol::{AppResponse, AppResponseV0, NuriV0};
 use ng_net::orm::OrmSchemaShape;
 use ng_repo::errors::NgError;
+use ng_repo::types::ObjectId;
 
 use futures::channel::mpsc;
 
@@ -39,11 +40,14 @@ use crate::orm::graph::types::TrackedOrmObjectChange;
 impl Verifier {
     /// Entry point to create a new orm subscription.
     /// Triggers the creation of an orm object which is sent back to the receiver.
+    /// If `known_heads` are given, the client already holds the state at those heads and
+    /// only the changes since then are sent (see `orm_resume_patches`).
     pub(crate) async fn start_orm(
         &mut self,
         graph_scope: Vec<NuriV0>,
         subject_scope: Vec<String>,
         shape_type: OrmShapeType,
+        known_heads: Option<Vec<ObjectId>>,
     ) -> Result<(Receiver<AppResponse>, CancelFn), NgError> {
         // TODO
         let config =
@@ -75,7 +79,7 @@ impl Verifier {
         };
 
         if let Err(error) = self
-            .create_orm_objects_and_insert_subscription(orm_subscription, &mut tx)
+            .create_orm_objects_and_insert_subscription(orm_subscription, known_heads, &mut tx)
             .await
         {
             log_err!(
@@ -97,8 +101,14 @@ impl Verifier {
     async fn create_orm_objects_and_insert_subscription(
         &mut self,
         mut orm_subscription: OrmSubscription,
+        known_heads: Option<Vec<ObjectId>>,
         tx: &mut UnboundedSender<AppResponse>,
     ) -> Result<(), NgError> {
+        // The commits at the current heads are part of the state we are about to build.
+        for head in self.orm_subscription_heads(&orm_subscription) {
+            orm_subscription.applied_commits.insert(head);
+        }
+
         let materialized_objects = if orm_subscription.config.order_by.is_some() {
             // If ordering is active, make an additional query that queries ordered graph-subject pairs first.
             // If pagination is activated, not all graph-subject pairs are fetched.
@@ -110,17 +120,30 @@ impl Verifier {
                 .await?
         };
 
-        let _ = tx
-            .send(AppResponse::V0(AppResponseV0::GraphOrmInitial(
+        let resume_patches = match known_heads {
+            Some(known_heads) => self
+                .orm_resume_patches(&orm_subscription, &materialized_objects, &known_heads)
+                .unwrap_or_else(|e| {
+                    log_err!("Could not resume orm subscription, sending full state: {e:?}");
+                    None
+                }),
+            None => None,
+        };
+        let response = match resume_patches {
+            Some(patches) => {
+                AppResponseV0::GraphOrmResumed(patches, orm_subscription.subscription_id)
+            }
+            None => AppResponseV0::GraphOrmInitial(
                 materialized_objects,
                 orm_subscription.subscription_id,
-            )))
-            .await;
+            ),
+        };
+        let _ = tx.send(AppResponse::V0(response)).await;
 
         // sync and subscribe to all the graphs found by ORM.
         // This can have the side effect of sending more AppResponses to the stream
         // (in case some new updates have been received while we were building the initial values).
-        // For this reason, it happens AFTER the GraphOrmInitial is sent (just above) because
+        // For this reason, it happens AFTER the GraphOrmInitial (or GraphOrmResumed) is sent (just above) because
         // the client cannot apply OrmPatches if it didn't receive the GraphOrmInitial first.
         for graph in orm_subscription.iter_graphs() {
             let nuri = NuriV0::new_from_repo_graph(graph)?;
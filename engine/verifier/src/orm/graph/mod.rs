@@ -14,6 +14,8 @@ pub mod handle_frontend_update;
 pub mod initialize;
 pub mod process_changes;
 pub mod query;
+pub mod resume;
 pub mod shape_validation;
 pub mod types;
 pub mod utils;
+pub mod validate;
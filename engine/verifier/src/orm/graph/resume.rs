@@ -0,0 +1,361 @@
+// Copyright (c) 2026 Laurin Weger, Par le Peuple, NextGraph.org developers
+// All rights reserved.
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE2 or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Resuming an orm subscription from the heads known by a client, after a reconnect.
+
+use std::collections::HashSet;
+
+use ng_net::app_protocol::NuriV0;
+use ng_net::orm::{OrmPatch, OrmPatchOp, OrmPatches};
+use ng_oxigraph::oxrdf::Subject;
+use ng_repo::branch::Branch;
+use ng_repo::commit::Commit;
+use ng_repo::errors::NgError;
+use ng_repo::store::Store;
+use ng_repo::types::*;
+use serde_json::Value;
+
+use crate::orm::graph::types::*;
+use crate::orm::utils::escape_json_pointer_segment;
+use crate::types::{GraphTransaction, TransactionBody};
+use crate::verifier::Verifier;
+
+/// Above this number of commits since the known heads, sending the full state is cheaper.
+const MAX_RESUME_COMMITS: usize = 256;
+
+impl Verifier {
+    /// Returns the current heads of each repo graph in scope of the subscription.
+    pub(crate) fn orm_subscription_heads(
+        &self,
+        orm_subscription: &OrmSubscription,
+    ) -> Vec<ObjectId> {
+        let mut heads = vec![];
+        for graph in orm_subscription.graph_scope.iter() {
+            let Ok(nuri) = NuriV0::new_from_repo_graph(graph) else {
+                continue;
+            };
+            let Ok((repo_id, branch_id, store_repo)) = self.resolve_target(&nuri.target) else {
+                continue;
+            };
+            if let Ok(branch) = self
+                .get_repo(&repo_id, &store_repo)
+                .and_then(|repo| repo.branch(&branch_id))
+            {
+                heads.extend(branch.current_heads.iter().map(|h| h.id));
+            }
+        }
+        heads
+    }
+
+    /// Computes the patches bringing a client that holds the state at `known_heads` to the
+    /// `materialized` state of the subscription.
+    ///
+    /// Every root object that was touched (directly or through a nested object) by a commit
+    /// since the known heads is replaced as a whole, or removed if it is not valid anymore.
+    ///
+    /// Returns `None` if the subscription cannot be resumed and the full state must be sent.
+    pub(crate) fn orm_resume_patches(
+        &self,
+        orm_subscription: &OrmSubscription,
+        materialized: &Value,
+        known_heads: &[ObjectId],
+    ) -> Result<Option<OrmPatches>, NgError> {
+        // Paginated and ordered subscriptions are not materialized as a map of root objects.
+        if orm_subscription.page_info.is_some() || orm_subscription.config.order_by.is_some() {
+            return Ok(None);
+        }
+        let Some(materialized) = materialized.as_object() else {
+            return Ok(None);
+        };
+        // Collect the subjects changed by commits since the known heads.
+        let mut changed_subjects: HashSet<(GraphIri, SubjectIri)> = HashSet::new();
+        for graph in orm_subscription.graph_scope.iter() {
+            // With a scope that is not a single document, we cannot know which graphs to look at.
+            let Ok(nuri) = NuriV0::new_from_repo_graph(graph) else {
+                return Ok(None);
+            };
+            let (repo_id, branch_id, store_repo) = self.resolve_target(&nuri.target)?;
+            let repo = self.get_repo(&repo_id, &store_repo)?;
+            let branch = repo.branch(&branch_id)?;
+
+            let Some(commits) = commits_since(&repo.store, &branch.current_heads, known_heads)
+            else {
+                return Ok(None);
+            };
+            for commit in commits {
+                let Some(mut transaction) = graph_transaction(&commit) else {
+                    continue;
+                };
+                transaction.tokenize_with_commit_id(commit.id().unwrap(), &repo_id);
+                for triple in transaction.inserts.iter().chain(transaction.removes.iter()) {
+                    if let Subject::NamedNode(subject) = &triple.subject {
+                        changed_subjects.insert((graph.clone(), subject.as_str().to_string()));
+                    }
+                }
+            }
+        }
+
+        // Find the root objects affected by the changed subjects.
+        let root_shape_iri = orm_subscription.shape_type.shape.clone();
+        let mut affected_roots: HashSet<(GraphIri, SubjectIri)> = HashSet::new();
+        for (graph, subject) in changed_subjects.iter() {
+            let mut stack: Vec<_> = orm_subscription
+                .shapes_being_tracked()
+                .iter()
+                .filter_map(|shape| shape.upgrade())
+                .filter_map(|shape| {
+                    orm_subscription.get_tracked_orm_object(graph, subject, &shape.iri)
+                })
+                .collect();
+            if stack.is_empty() {
+                // The changed subject might be a root object that is not tracked anymore.
+                affected_roots.insert((graph.clone(), subject.clone()));
+            }
+            let mut visited: HashSet<(GraphIri, SubjectIri, ShapeIri)> = HashSet::new();
+            while let Some(tracked) = stack.pop() {
+                let tracked = tracked.read().unwrap();
+                let shape_iri = tracked.shape_iri().unwrap_or_default();
+                if !visited.insert((
+                    tracked.graph_iri.clone(),
+                    tracked.subject_iri.clone(),
+                    shape_iri.clone(),
+                )) {
+                    continue;
+                }
+                if shape_iri == root_shape_iri {
+                    affected_roots.insert((tracked.graph_iri.clone(), tracked.subject_iri.clone()));
+                }
+                stack.extend(tracked.live_parents());
+            }
+        }
+
+        let mut patches = vec![];
+        for (graph, subject) in affected_roots {
+            let path = format!(
+                "/{}|{}",
+                escape_json_pointer_segment(&graph),
+                escape_json_pointer_segment(&subject)
+            );
+            match materialized.get(&format!("{graph}|{subject}")) {
+                Some(object) => patches.push(OrmPatch {
+                    op: OrmPatchOp::add,
+                    valType: None,
+                    path,
+                    value: Some(object.clone()),
+                }),
+                // Only changed subjects end up here. Those that were not root objects are unknown
+                // to the client, which ignores removals of missing keys.
+                None => patches.push(OrmPatch {
+                    op: OrmPatchOp::remove,
+                    valType: None,
+                    path,
+                    value: None,
+                }),
+            }
+        }
+        Ok(Some(patches))
+    }
+}
+
+/// Loads the commits reachable from `heads` that are not in the causal past of `known_heads`.
+/// Returns `None` if there are too many of them, or if some could not be loaded.
+fn commits_since(
+    store: &Store,
+    heads: &[ObjectRef],
+    known_heads: &[ObjectId],
+) -> Option<Vec<Commit>> {
+    let missing: HashSet<ObjectId> =
+        Branch::sync_req(heads.iter().map(|h| h.id), known_heads, &None, store)
+            .ok()?
+            .into_iter()
+            .collect();
+    if missing.len() > MAX_RESUME_COMMITS {
+        return None;
+    }
+
+    // Walk again with the references, as the keys are needed to load the commit bodies.
+    let mut commits = Vec::with_capacity(missing.len());
+    let mut visited: HashSet<ObjectId> = HashSet::new();
+    let mut recursor: Vec<ObjectRef> = heads.to_vec();
+    while let Some(commit_ref) = recursor.pop() {
+        if !missing.contains(&commit_ref.id) || !visited.insert(commit_ref.id) {
+            continue;
+        }
+        let commit = Commit::load(commit_ref, store, true).ok()?;
+        recursor.extend(commit.acks());
+        commits.push(commit);
+    }
+    Some(commits)
+}
+
+/// Extracts the graph part of a transaction commit.
+fn graph_transaction(commit: &Commit) -> Option<GraphTransaction> {
+    match commit.body()? {
+        CommitBody::V0(CommitBodyV0::AsyncTransaction(Transaction::V0(v0))) => {
+            let body: TransactionBody = serde_bare::from_slice(v0).ok()?;
+            body.graph
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use ng_net::orm::{OrmConfig, OrmSchemaShape, OrmShapeType};
+    use ng_oxigraph::oxrdf::{Literal, NamedNode, Triple};
+    use ng_repo::utils::generate_keypair;
+    use serde_json::json;
+
+    use crate::types::TransactionBodyType;
+
+    fn add_transaction(
+        store: &Store,
+        branch: BranchId,
+        acks: Vec<ObjectRef>,
+        subject: &str,
+    ) -> ObjectRef {
+        let (author_privkey, author_pubkey) = generate_keypair();
+        let body = TransactionBody {
+            body_type: TransactionBodyType::Graph,
+            graph: Some(GraphTransaction {
+                inserts: vec![Triple::new(
+                    NamedNode::new_unchecked(subject),
+                    NamedNode::new_unchecked("did:ng:x:name"),
+                    Literal::new_simple_literal("value"),
+                )],
+                removes: vec![],
+            }),
+            discrete: None,
+        };
+        let commit = Commit::new_with_body_acks_deps_and_save(
+            &author_privkey,
+            &author_pubkey,
+            branch,
+            QuorumType::NoSigning,
+            vec![],
+            acks,
+            CommitBody::V0(CommitBodyV0::AsyncTransaction(Transaction::V0(
+                serde_bare::to_vec(&body).unwrap(),
+            ))),
+            store,
+        )
+        .unwrap();
+        commit.reference().unwrap()
+    }
+
+    fn ids(commits: &[Commit]) -> HashSet<ObjectId> {
+        commits.iter().map(|c| c.id().unwrap()).collect()
+    }
+
+    fn subscription(graph_scope: Vec<String>, page_size: u64) -> OrmSubscription {
+        let shape = Arc::new(OrmSchemaShape {
+            iri: "did:ng:x:Shape".to_string(),
+            predicates: vec![],
+        });
+        let mut schema = HashMap::new();
+        schema.insert(shape.iri.clone(), shape.clone());
+        let (sender, _) = futures::channel::mpsc::unbounded();
+        OrmSubscription::new(
+            OrmShapeType {
+                schema,
+                shape: shape.iri.clone(),
+            },
+            1,
+            graph_scope,
+            vec![],
+            sender,
+            OrmConfig {
+                where_: None,
+                order_by: None,
+                select: None,
+                page_size,
+                max_active_pages: 0,
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    pub fn test_commits_since() {
+        let store = Store::dummy_public_v0();
+        let branch = BranchId::nil();
+        let c1 = add_transaction(&store, branch, vec![], "did:ng:x:a");
+        let c2 = add_transaction(&store, branch, vec![c1.clone()], "did:ng:x:b");
+        let c3 = add_transaction(&store, branch, vec![c2.clone()], "did:ng:x:c");
+
+        let commits = commits_since(&store, &[c3.clone()], &[c1.id]).unwrap();
+        assert_eq!(ids(&commits), HashSet::from([c2.id, c3.id]));
+
+        let commits = commits_since(&store, &[c3.clone()], &[]).unwrap();
+        assert_eq!(ids(&commits), HashSet::from([c1.id, c2.id, c3.id]));
+
+        let commits = commits_since(&store, &[c3.clone()], &[c3.id]).unwrap();
+        assert!(commits.is_empty());
+
+        // The bodies are loaded, so the changed subjects can be read.
+        let commit = Commit::load(c2, &store, true).unwrap();
+        let transaction = graph_transaction(&commit).unwrap();
+        assert_eq!(
+            transaction.inserts[0].subject,
+            NamedNode::new_unchecked("did:ng:x:b").into()
+        );
+    }
+
+    #[test]
+    pub fn test_commits_since_too_many() {
+        let store = Store::dummy_public_v0();
+        let branch = BranchId::nil();
+        let first = add_transaction(&store, branch, vec![], "did:ng:x:s");
+        let mut head = first.clone();
+        for _ in 0..MAX_RESUME_COMMITS {
+            head = add_transaction(&store, branch, vec![head], "did:ng:x:s");
+        }
+        // One commit over the limit: the full state is sent instead.
+        assert!(commits_since(&store, &[head.clone()], &[]).is_none());
+        assert_eq!(
+            commits_since(&store, &[head], &[first.id]).unwrap().len(),
+            MAX_RESUME_COMMITS
+        );
+    }
+
+    #[test]
+    pub fn test_resume_patches_fallback() {
+        let verifier = Verifier::new_dummy();
+        let materialized = json!({});
+
+        // Paginated subscriptions are always sent in full.
+        let paginated = subscription(vec![], 10);
+        assert!(verifier
+            .orm_resume_patches(&paginated, &materialized, &[])
+            .unwrap()
+            .is_none());
+
+        // So are subscriptions whose scope is not made of documents.
+        let whole_site = subscription(vec!["did:ng:i".to_string()], 0);
+        assert!(verifier
+            .orm_resume_patches(&whole_site, &materialized, &[])
+            .unwrap()
+            .is_none());
+
+        let empty = subscription(vec![], 0);
+        assert!(verifier
+            .orm_resume_patches(&empty, &json!([]), &[])
+            .unwrap()
+            .is_none());
+        let patches = verifier
+            .orm_resume_patches(&empty, &materialized, &[])
+            .unwrap();
+        assert!(patches.unwrap().is_empty());
+    }
+}
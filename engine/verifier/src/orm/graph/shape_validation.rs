@@ -135,194 +135,97 @@ impl Verifier {
                 .as_ref()
                 .map_or_else(|| 0, |tp| tp.current_cardinality);
 
-            // Check 3.1) Cardinality
-            if count < p_schema.minCardinality {
-                // log_info!(
-                //     "  - Invalid: minCardinality not met | predicate: {:?} | count: {} | min: {} | schema: {:?} | changed: {:?}",
-                //     p_schema.iri,
-                //     count,
-                //     p_schema.minCardinality,
-                //     shape.iri,
-                //     p_change
-                // );
-                set_validity(&mut new_validity, TrackedOrmObjectValidity::Invalid);
-                if count <= 0 {
-                    // If cardinality is 0, we can remove the tracked predicate.
-                    // Drop the guard to release the immutable borrow
-                    drop(tracked_pred);
-                    tracked_orm_object.tracked_predicates.remove(&p_schema.iri);
-                }
-                break;
-            // Check 3.2) Cardinality too high and extra values not allowed.
-            } else if count > p_schema.maxCardinality
-                && p_schema.maxCardinality != -1
-                && p_schema.extra != Some(true)
-            {
-                // log_info!(
-                //     "  - Invalid: maxCardinality exceeded | predicate: {:?} | count: {} | max: {} | schema: {:?} | changed: {:?}",
-                //     p_schema.iri,
-                //     count,
-                //     p_schema.maxCardinality,
-                //     shape.iri,
-                //     p_change
-                // );
-                // If cardinality is too high and no extra allowed, invalid.
-                set_validity(&mut new_validity, TrackedOrmObjectValidity::Invalid);
-                break;
-            // Check 3.3) Required literals present.
-            } else if p_schema.dataTypes.iter().any(|dt| dt.literals.is_some()) {
-                // If the predicate is optional and has no values, skip literal validation
-                if p_schema.minCardinality == 0 && count == 0 {
-                    continue;
-                }
-
-                // If we have literals, check if all required literals are present.
-                // At least one datatype must match.
-                let some_valid = p_schema.dataTypes.iter().flat_map(|dt| &dt.literals).any(
-                    |required_literals| {
-                        // Early stop: If no extra values allowed but the sizes
-                        // between required and given values mismatches.
-                        if !p_schema.extra.unwrap_or(false)
-                            && ((required_literals.len() as i32)
-                                != tracked_pred.as_ref().map_or(0, |p| p.current_cardinality))
-                        {
-                            return false;
-                        }
-
-                        // Check that each required literal is present.
-                        for required_literal in required_literals {
-                            // Is tracked predicate present?
-                            if !tracked_pred.as_ref().map_or(false, |t| {
-                                t.current_literals.as_ref().map_or(false, |tt| {
-                                    tt.iter().any(|literal| *literal == *required_literal)
-                                })
-                            }) {
-                                return false;
-                            }
-                        }
-                        // All required literals present.
-                        return true;
-                    },
-                );
-                if !some_valid {
-                    // log_info!(
-                    //     "  - Invalid: required literals missing | predicate: {:?} | schema: {:?} | changed: {:?}",
-                    //     p_schema.iri,
-                    //     shape.iri,
-                    //     p_change
-                    // );
-                    // If there are more valid children than what's allowed, break.
+            // Check 3.1) to 3.3) and 3.5) Cardinality, required literals and data types of the new values.
+            let check = check_predicate(
+                p_schema,
+                count,
+                tracked_pred
+                    .as_ref()
+                    .and_then(|tp| tp.current_literals.as_deref()),
+                p_change.iter().map(|pc| &pc.values_added).flatten(),
+            );
+            match check {
+                PredicateCheck::Valid => {}
+                PredicateCheck::Invalid(kind) => {
                     set_validity(&mut new_validity, TrackedOrmObjectValidity::Invalid);
+                    if kind == OrmViolationKind::minCardinality && count <= 0 {
+                        // If cardinality is 0, we can remove the tracked predicate.
+                        // Drop the guard to release the immutable borrow
+                        drop(tracked_pred);
+                        tracked_orm_object.tracked_predicates.remove(&p_schema.iri);
+                    }
                     break;
                 }
-            // Check 3.4) Nested shape correct.
-            } else if p_schema
-                .dataTypes
-                .iter()
-                .any(|dt| dt.valType == OrmSchemaValType::shape)
-            {
-                // If we have a nested shape, assess children using heuristic and cardinality checks
-                if let Some(tp) = tracked_pred.as_ref() {
-                    let children_upgraded: Vec<_> = tp
-                        .tracked_children
-                        .iter()
-                        .filter_map(|w| w.upgrade())
-                        .collect();
-                    let assessed = assess_and_rank_children(
-                        &tracked_orm_object.graph_iri,
-                        &tracked_orm_object.subject_iri,
-                        p_schema.minCardinality,
-                        p_schema.maxCardinality,
-                        &children_upgraded,
-                    );
-
-                    // log_info!(
-                    //     "  - Nested shape assessment: heuristic={:?}, considered={}, valid={}, pending={}, untracked={}, invalid={}, satisfies={}",
-                    //     assessed.heuristic_used,
-                    //     assessed.considered.len(),
-                    //     assessed.counts.valid,
-                    //     assessed.counts.pending,
-                    //     assessed.counts.untracked,
-                    //     assessed.counts.invalid,
-                    //     assessed.satisfies
-                    // );
-
-                    if assessed.satisfies {
-                        // Cardinality satisfied, predicate is valid
-                        continue;
-                    }
+                // Check 3.4) Nested shape correct.
+                PredicateCheck::NestedShape => {
+                    // If we have a nested shape, assess children using heuristic and cardinality checks
+                    if let Some(tp) = tracked_pred.as_ref() {
+                        let children_upgraded: Vec<_> = tp
+                            .tracked_children
+                            .iter()
+                            .filter_map(|w| w.upgrade())
+                            .collect();
+                        let assessed = assess_and_rank_children(
+                            &tracked_orm_object.graph_iri,
+                            &tracked_orm_object.subject_iri,
+                            p_schema.minCardinality,
+                            p_schema.maxCardinality,
+                            &children_upgraded,
+                        );
 
-                    // Check if we have children that need fetching or re-evaluation
-                    if !assessed.children_to_fetch.is_empty()
-                        || !assessed.children_to_reevaluate.is_empty()
-                    {
-                        set_validity(&mut new_validity, TrackedOrmObjectValidity::Pending);
-                        needs_self_reevaluation = NeedEvalSelf::Reevaluate;
+                        // log_info!(
+                        //     "  - Nested shape assessment: heuristic={:?}, considered={}, valid={}, pending={}, untracked={}, invalid={}, satisfies={}",
+                        //     assessed.heuristic_used,
+                        //     assessed.considered.len(),
+                        //     assessed.counts.valid,
+                        //     assessed.counts.pending,
+                        //     assessed.counts.untracked,
+                        //     assessed.counts.invalid,
+                        //     assessed.satisfies
+                        // );
 
-                        // Schedule children for fetching
-                        for child in assessed.children_to_fetch {
-                            children_to_eval.push((child, true));
+                        if assessed.satisfies {
+                            // Cardinality satisfied, predicate is valid
+                            continue;
                         }
 
-                        // Schedule children for re-evaluation
-                        for child in assessed.children_to_reevaluate {
-                            // log_info!(
-                            //     "  - adding subject {} with graph {} to child evaluation",
-                            //     child.read().unwrap().subject_iri,
-                            //     child.read().unwrap().graph_iri,
-                            // );
-                            children_to_eval.push((child, false));
+                        // Check if we have children that need fetching or re-evaluation
+                        if !assessed.children_to_fetch.is_empty()
+                            || !assessed.children_to_reevaluate.is_empty()
+                        {
+                            set_validity(&mut new_validity, TrackedOrmObjectValidity::Pending);
+                            needs_self_reevaluation = NeedEvalSelf::Reevaluate;
+
+                            // Schedule children for fetching
+                            for child in assessed.children_to_fetch {
+                                children_to_eval.push((child, true));
+                            }
+
+                            // Schedule children for re-evaluation
+                            for child in assessed.children_to_reevaluate {
+                                // log_info!(
+                                //     "  - adding subject {} with graph {} to child evaluation",
+                                //     child.read().unwrap().subject_iri,
+                                //     child.read().unwrap().graph_iri,
+                                // );
+                                children_to_eval.push((child, false));
+                            }
+                            continue;
                         }
-                        continue;
-                    }
 
-                    // Neither satisfied nor pending - invalid
-                    // log_info!(
-                    //     "  - Invalid: nested shape constraint not met | predicate: {:?} | valid_count: {} | min: {} | schema: {:?}",
-                    //     p_schema.iri,
-                    //     assessed.counts.valid,
-                    //     p_schema.minCardinality,
-                    //     shape.iri
-                    // );
-                    set_validity(&mut new_validity, TrackedOrmObjectValidity::Invalid);
-                    break;
-                }
-            // Check 3.5) Data types correct.
-            } else {
-                // Check if the data type is correct.
-                let allowed_types: Vec<&OrmSchemaValType> =
-                    p_schema.dataTypes.iter().map(|dt| &dt.valType).collect();
-                // For each new value, check that data type is in allowed_types.
-                for val_added in p_change.iter().map(|pc| &pc.values_added).flatten() {
-                    let matches = match val_added {
-                        BasicType::Bool(_) => allowed_types
-                            .iter()
-                            .any(|t| **t == OrmSchemaValType::boolean),
-                        BasicType::Num(_) => allowed_types
-                            .iter()
-                            .any(|t| **t == OrmSchemaValType::number),
-                        BasicType::Str(_) => allowed_types.iter().any(|t| {
-                            **t == OrmSchemaValType::string || **t == OrmSchemaValType::iri
-                        }),
-                    };
-                    if !matches {
+                        // Neither satisfied nor pending - invalid
                         // log_info!(
-                        //     "  - Invalid: value type mismatch | predicate: {:?} | value: {:?} | allowed_types: {:?} | schema: {:?} | changed: {:?}",
+                        //     "  - Invalid: nested shape constraint not met | predicate: {:?} | valid_count: {} | min: {} | schema: {:?}",
                         //     p_schema.iri,
-                        //     val_added,
-                        //     allowed_types,
-                        //     shape.iri,
-                        //     p_change
+                        //     assessed.counts.valid,
+                        //     p_schema.minCardinality,
+                        //     shape.iri
                         // );
                         set_validity(&mut new_validity, TrackedOrmObjectValidity::Invalid);
                         break;
                     }
                 }
-                // Break again if validity has become invalid.
-                if new_validity == TrackedOrmObjectValidity::Invalid {
-                    break;
-                }
-            };
+            }
         }
 
         // === End of validation part. Next, process side-effects ===
@@ -388,6 +291,90 @@ impl Verifier {
     }
 }
 
+/// The result of the checks of a predicate that don't depend on the validity of nested objects.
+#[derive(Debug, PartialEq)]
+pub(crate) enum PredicateCheck {
+    Valid,
+    Invalid(OrmViolationKind),
+    /// The values are nested objects. The predicate is valid if enough of them conform to their shape.
+    NestedShape,
+}
+
+/// Checks the values of a predicate of a subject against the cardinality, the required literals
+/// and the data types of the predicate schema.
+/// `literals` are the current values of a predicate with required literals,
+/// and the data type is checked for each value in `values`.
+pub(crate) fn check_predicate<'a>(
+    p_schema: &OrmSchemaPredicate,
+    count: i32,
+    literals: Option<&[BasicType]>,
+    values: impl IntoIterator<Item = &'a BasicType>,
+) -> PredicateCheck {
+    // Cardinality
+    if count < p_schema.minCardinality {
+        return PredicateCheck::Invalid(OrmViolationKind::minCardinality);
+    }
+    // Cardinality too high and extra values not allowed.
+    if count > p_schema.maxCardinality
+        && p_schema.maxCardinality != -1
+        && p_schema.extra != Some(true)
+    {
+        return PredicateCheck::Invalid(OrmViolationKind::maxCardinality);
+    }
+    // Required literals present.
+    if p_schema.dataTypes.iter().any(|dt| dt.literals.is_some()) {
+        // If the predicate is optional and has no values, skip literal validation
+        if p_schema.minCardinality == 0 && count == 0 {
+            return PredicateCheck::Valid;
+        }
+        // At least one datatype must match.
+        let some_valid =
+            p_schema
+                .dataTypes
+                .iter()
+                .flat_map(|dt| &dt.literals)
+                .any(|required_literals| {
+                    // Early stop: If no extra values allowed but the sizes
+                    // between required and given values mismatches.
+                    if !p_schema.extra.unwrap_or(false) && (required_literals.len() as i32) != count
+                    {
+                        return false;
+                    }
+                    // Check that each required literal is present.
+                    required_literals
+                        .iter()
+                        .all(|required| literals.map_or(false, |l| l.contains(required)))
+                });
+        return if some_valid {
+            PredicateCheck::Valid
+        } else {
+            PredicateCheck::Invalid(OrmViolationKind::literals)
+        };
+    }
+    if p_schema.is_object() {
+        return PredicateCheck::NestedShape;
+    }
+    // Data types correct.
+    let allowed_types: Vec<&OrmSchemaValType> =
+        p_schema.dataTypes.iter().map(|dt| &dt.valType).collect();
+    let all_match = values.into_iter().all(|value| match value {
+        BasicType::Bool(_) => allowed_types
+            .iter()
+            .any(|t| **t == OrmSchemaValType::boolean),
+        BasicType::Num(_) => allowed_types
+            .iter()
+            .any(|t| **t == OrmSchemaValType::number),
+        BasicType::Str(_) => allowed_types
+            .iter()
+            .any(|t| **t == OrmSchemaValType::string || **t == OrmSchemaValType::iri),
+    });
+    if all_match {
+        PredicateCheck::Valid
+    } else {
+        PredicateCheck::Invalid(OrmViolationKind::dataType)
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum NeedEvalSelf {
     Reevaluate,
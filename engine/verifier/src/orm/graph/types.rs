@@ -8,12 +8,13 @@
 // according to those terms.
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
 use std::{collections::HashMap, sync::Arc};
 
 use ng_net::app_protocol::AppResponse;
 use ng_net::{orm::*, utils::Sender};
 use ng_repo::errors::NgError;
+use ng_repo::types::ObjectId;
 use std::sync::{RwLock, Weak};
 
 /// A struct for recording the state of subjects and its predicates
@@ -164,6 +165,34 @@ pub struct OrmSubscriptionPageInfo {
     pub backwards_page_offset: u64,
 }
 
+/// Maximum number of commit ids remembered per subscription for deduplication.
+const APPLIED_COMMITS_CAPACITY: usize = 1024;
+
+/// The ids of the latest commits whose graph changes were applied to a subscription.
+/// The same commit can be received more than once (local transaction and broker echo),
+/// so we only keep a bounded window of ids, oldest evicted first.
+#[derive(Debug, Default)]
+pub struct AppliedCommits {
+    ids: HashSet<ObjectId>,
+    order: VecDeque<ObjectId>,
+}
+
+impl AppliedCommits {
+    /// Records `commit_id`. Returns false if it had already been applied.
+    pub fn insert(&mut self, commit_id: ObjectId) -> bool {
+        if !self.ids.insert(commit_id) {
+            return false;
+        }
+        self.order.push_back(commit_id);
+        if self.order.len() > APPLIED_COMMITS_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
 #[derive(Debug)]
 pub struct OrmSubscription {
     pub shape_type: OrmShapeType,
@@ -175,6 +204,8 @@ pub struct OrmSubscription {
     pub page_info: Option<OrmSubscriptionPageInfo>,
 
     pub sender: Sender<AppResponse>,
+    /// Commits already applied to this subscription, see `AppliedCommits`.
+    pub applied_commits: AppliedCommits,
     // Keep private: always use the helper methods below to access/modify
     tracked_orm_objects:
         HashMap<GraphIri, HashMap<SubjectIri, HashMap<ShapeIri, Arc<RwLock<TrackedOrmObject>>>>>,
@@ -251,6 +282,7 @@ impl OrmSubscription {
             graph_scope,
             subject_scope,
             sender,
+            applied_commits: AppliedCommits::default(),
             tracked_orm_objects: HashMap::new(),
             tracked_nested_subjects: HashMap::new(),
             config,
@@ -815,3 +847,38 @@ fn clone_shape(shape: &OrmSchemaShape) -> OrmSchemaShape {
             .collect(),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn commit_id(n: usize) -> ObjectId {
+        let mut digest = [0u8; 32];
+        digest[..8].copy_from_slice(&(n as u64).to_be_bytes());
+        ObjectId::Blake3Digest32(digest)
+    }
+
+    #[test]
+    pub fn test_applied_commits_dedup() {
+        let mut applied = AppliedCommits::default();
+        assert!(applied.insert(commit_id(1)));
+        assert!(applied.insert(commit_id(2)));
+        // The broker echo of a local transaction is ignored.
+        assert!(!applied.insert(commit_id(1)));
+        assert!(!applied.insert(commit_id(2)));
+    }
+
+    #[test]
+    pub fn test_applied_commits_evicts_oldest() {
+        let mut applied = AppliedCommits::default();
+        for n in 0..=APPLIED_COMMITS_CAPACITY {
+            assert!(applied.insert(commit_id(n)));
+        }
+        assert_eq!(applied.ids.len(), APPLIED_COMMITS_CAPACITY);
+        assert_eq!(applied.order.len(), APPLIED_COMMITS_CAPACITY);
+        // The oldest id fell out of the window, the most recent ones are still known.
+        assert!(!applied.insert(commit_id(APPLIED_COMMITS_CAPACITY)));
+        assert!(!applied.insert(commit_id(1)));
+        assert!(applied.insert(commit_id(0)));
+    }
+}
@@ -244,6 +244,15 @@ fn rank_by_validity_stable(
     out
 }
 
+/// Whether the number of valid nested objects of a predicate is within its cardinality.
+pub fn nested_cardinality_satisfied(
+    min_cardinality: i32,
+    max_cardinality: i32,
+    valid_total: i32,
+) -> bool {
+    valid_total >= min_cardinality && (max_cardinality == -1 || valid_total <= max_cardinality)
+}
+
 /// Assess and rank children for a predicate, determining which bucket (same-graph, subject-prefix, or all)
 /// satisfies cardinality constraints or has potential to satisfy them (via pending/untracked children).
 /// Returns the considered children from the selected bucket along with scheduling information for
@@ -280,10 +289,8 @@ pub fn assess_and_rank_children(
         let counts = bucket_counts(&ranked);
 
         // Cardinality satisfaction should consider Valid children.
-        let valid_total = counts.valid as i32;
-        let within_min = valid_total >= min_cardinality;
-        let within_max = max_cardinality == -1 || valid_total <= max_cardinality;
-        let satisfies = within_min && within_max;
+        let satisfies =
+            nested_cardinality_satisfied(min_cardinality, max_cardinality, counts.valid as i32);
 
         // Extract children that need fetching (Untracked) or re-evaluation (Pending)
         let mut children_to_fetch = Vec::new();
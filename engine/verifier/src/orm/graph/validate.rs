@@ -0,0 +1,445 @@
+// Copyright (c) 2025 Laurin Weger, Par le Peuple, NextGraph.org developers
+// All rights reserved.
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE2 or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Stand-alone validation of a graph against an ORM shape, without subscription.
+
+use std::collections::{HashMap, HashSet};
+
+use ng_net::app_protocol::NuriV0;
+use ng_net::orm::*;
+use ng_oxigraph::oxrdf::{GraphName, NamedNodeRef, Quad, Subject, Term};
+use ng_repo::errors::{NgError, VerifierError};
+use ng_repo::log::*;
+
+use crate::orm::graph::add_remove_quads::oxrdf_term_to_orm_basic_type;
+use crate::orm::graph::shape_validation::{check_predicate, PredicateCheck};
+use crate::orm::graph::types::*;
+use crate::orm::graph::utils::{nested_cardinality_satisfied, nuri_to_string};
+use crate::verifier::Verifier;
+
+type ValidationKey = (GraphIri, SubjectIri, ShapeIri);
+
+/// Walks the subjects and their nested objects, memoizing the result for each (graph, subject, shape).
+struct ShapeValidator<'a> {
+    verifier: &'a Verifier,
+    schema: &'a OrmSchema,
+    filter_graphs: Option<Vec<String>>,
+    quads: HashMap<(GraphIri, SubjectIri), Vec<Quad>>,
+    /// A key present with `None` is currently being validated (cycle). It is then assumed valid.
+    results: HashMap<ValidationKey, Option<OrmSubjectValidation>>,
+    order: Vec<ValidationKey>,
+}
+
+impl<'a> ShapeValidator<'a> {
+    fn validate(
+        &mut self,
+        graph_iri: &str,
+        subject_iri: &str,
+        shape: &OrmSchemaShape,
+    ) -> Result<bool, NgError> {
+        let key = (
+            graph_iri.to_string(),
+            subject_iri.to_string(),
+            shape.iri.clone(),
+        );
+        if let Some(res) = self.results.get(&key) {
+            return Ok(res.as_ref().map_or(true, |r| r.valid));
+        }
+        self.results.insert(key.clone(), None);
+        self.order.push(key.clone());
+
+        let quads = self
+            .quads
+            .get(&(graph_iri.to_string(), subject_iri.to_string()))
+            .cloned()
+            .unwrap_or_default();
+
+        // Nested objects are validated first, the closure only reads their result.
+        let mut nested_valid: HashMap<(SubjectIri, ShapeIri), bool> = HashMap::new();
+        for p_schema in shape.predicates.iter() {
+            for dt in p_schema.dataTypes.iter() {
+                let Some(child_shape_iri) = dt.shape.as_ref() else {
+                    continue;
+                };
+                let child_shape = self.schema.get(child_shape_iri).cloned().ok_or_else(|| {
+                    log_err!("Shape not found in schema: {}", child_shape_iri);
+                    NgError::from(VerifierError::InvalidOrmSchema)
+                })?;
+                for quad in quads
+                    .iter()
+                    .filter(|q| q.predicate.as_str() == p_schema.iri)
+                {
+                    if let Term::NamedNode(child) = &quad.object {
+                        let valid = self.validate_nested(child.as_str(), &child_shape)?;
+                        nested_valid
+                            .insert((child.as_str().to_string(), child_shape_iri.clone()), valid);
+                    }
+                }
+            }
+        }
+
+        let violations = predicate_violations(shape, &quads, |child, child_shape| {
+            *nested_valid
+                .get(&(child.to_string(), child_shape.to_string()))
+                .unwrap_or(&false)
+        });
+
+        let valid = violations.is_empty();
+        self.results.insert(
+            key,
+            Some(OrmSubjectValidation {
+                graph: graph_iri.to_string(),
+                subject: subject_iri.to_string(),
+                shape: shape.iri.clone(),
+                valid,
+                violations,
+            }),
+        );
+        Ok(valid)
+    }
+
+    /// A nested object can live in any graph of the scope. It is valid if it is valid in one of them.
+    fn validate_nested(
+        &mut self,
+        subject_iri: &str,
+        shape: &OrmSchemaShape,
+    ) -> Result<bool, NgError> {
+        let mut graphs: Vec<GraphIri> = self
+            .quads
+            .keys()
+            .filter(|(_, s)| s == subject_iri)
+            .map(|(g, _)| g.clone())
+            .collect();
+        if graphs.is_empty() {
+            let quads = self.verifier.query_sparql_select(
+                subject_quads_sparql(subject_iri, self.filter_graphs.as_ref())?,
+                None,
+            )?;
+            for quad in quads {
+                let Some(key) = graph_subject_key(&quad) else {
+                    continue;
+                };
+                if !graphs.contains(&key.0) {
+                    graphs.push(key.0.clone());
+                }
+                self.quads.entry(key).or_default().push(quad);
+            }
+        }
+        let mut valid = false;
+        for graph_iri in graphs {
+            valid |= self.validate(&graph_iri, subject_iri, shape)?;
+        }
+        Ok(valid)
+    }
+}
+
+impl Verifier {
+    /// Validates the graph of a document (or of the whole user site) against a shape,
+    /// and returns a report listing each subject with the predicates it violates.
+    ///
+    /// The subjects of the root shape are all the subjects that have at least one of its predicates.
+    /// Objects of shape-valued predicates are validated recursively against their nested shape.
+    pub(crate) fn validate_graph_against_shape(
+        &self,
+        nuri: &NuriV0,
+        shape_type: &OrmShapeType,
+    ) -> Result<OrmValidationReport, NgError> {
+        let root_shape = shape_type.schema.get(&shape_type.shape).ok_or_else(|| {
+            NgError::OrmError("shape_type.shape must be present in shape_type.schema".into())
+        })?;
+        let graph = nuri_to_string(nuri);
+        let filter_graphs = if graph == "did:ng:i" {
+            None
+        } else {
+            Some(vec![graph])
+        };
+
+        let mut focus_nodes: Vec<(GraphIri, SubjectIri)> = vec![];
+        let mut quads: HashMap<(GraphIri, SubjectIri), Vec<Quad>> = HashMap::new();
+        if !root_shape.predicates.is_empty() {
+            for quad in self.query_sparql_select(
+                focus_nodes_sparql(root_shape, filter_graphs.as_ref())?,
+                None,
+            )? {
+                let Some(key) = graph_subject_key(&quad) else {
+                    continue;
+                };
+                if !quads.contains_key(&key) {
+                    focus_nodes.push(key.clone());
+                }
+                quads.entry(key).or_default().push(quad);
+            }
+        }
+
+        let mut validator = ShapeValidator {
+            verifier: self,
+            schema: &shape_type.schema,
+            filter_graphs,
+            quads,
+            results: HashMap::new(),
+            order: vec![],
+        };
+        for (graph_iri, subject_iri) in focus_nodes.iter() {
+            validator.validate(graph_iri, subject_iri, root_shape)?;
+        }
+
+        let ShapeValidator {
+            mut results, order, ..
+        } = validator;
+        let root_keys: HashSet<(GraphIri, SubjectIri)> = focus_nodes.into_iter().collect();
+        let (mut subjects, nested): (Vec<_>, Vec<_>) = order
+            .into_iter()
+            .filter_map(|key| results.remove(&key).flatten())
+            .partition(|v| {
+                v.shape == shape_type.shape
+                    && root_keys.contains(&(v.graph.clone(), v.subject.clone()))
+            });
+        let conforms = subjects.iter().all(|s| s.valid);
+        subjects.extend(nested);
+
+        Ok(OrmValidationReport {
+            shape: shape_type.shape.clone(),
+            conforms,
+            subjects,
+        })
+    }
+}
+
+/// Checks the values of a subject against each predicate of the shape,
+/// with the same checks as the ORM validation of tracked objects.
+/// `child_is_valid(object_iri, shape_iri)` tells if a nested object conforms to its shape.
+pub(crate) fn predicate_violations<F>(
+    shape: &OrmSchemaShape,
+    quads: &[Quad],
+    child_is_valid: F,
+) -> Vec<OrmPredicateViolation>
+where
+    F: Fn(&str, &str) -> bool,
+{
+    let mut violations = vec![];
+    for p_schema in shape.predicates.iter() {
+        let values: Vec<&Term> = quads
+            .iter()
+            .filter(|q| q.predicate.as_str() == p_schema.iri)
+            .map(|q| &q.object)
+            .collect();
+        let present: Vec<BasicType> = values
+            .iter()
+            .map(|v| oxrdf_term_to_orm_basic_type(v))
+            .collect();
+        let count = values.len() as i32;
+
+        let kind = match check_predicate(p_schema, count, Some(present.as_slice()), present.iter())
+        {
+            PredicateCheck::Valid => continue,
+            PredicateCheck::Invalid(kind) => kind,
+            PredicateCheck::NestedShape => {
+                let valid_children = values
+                    .iter()
+                    .filter(|v| match v {
+                        Term::NamedNode(child) => p_schema
+                            .dataTypes
+                            .iter()
+                            .filter_map(|dt| dt.shape.as_ref())
+                            .any(|s| child_is_valid(child.as_str(), s)),
+                        _ => false,
+                    })
+                    .count() as i32;
+                if nested_cardinality_satisfied(
+                    p_schema.minCardinality,
+                    p_schema.maxCardinality,
+                    valid_children,
+                ) {
+                    continue;
+                }
+                OrmViolationKind::shape
+            }
+        };
+        violations.push(OrmPredicateViolation {
+            predicate: p_schema.iri.clone(),
+            readablePredicate: p_schema.readablePredicate.clone(),
+            kind,
+            count,
+            minCardinality: p_schema.minCardinality,
+            maxCardinality: p_schema.maxCardinality,
+        });
+    }
+    violations
+}
+
+fn graph_subject_key(quad: &Quad) -> Option<(GraphIri, SubjectIri)> {
+    match (&quad.graph_name, &quad.subject) {
+        (GraphName::NamedNode(g), Subject::NamedNode(s)) => {
+            Some((g.as_str().to_string(), s.as_str().to_string()))
+        }
+        _ => None,
+    }
+}
+
+/// Checks the IRI and writes it between angle brackets, so it can be put in a SPARQL query.
+fn sparql_iri(iri: &str) -> Result<String, NgError> {
+    NamedNodeRef::new(iri)
+        .map(|node| node.to_string())
+        .map_err(|e| NgError::OrmError(format!("invalid IRI {iri}: {e}")))
+}
+
+/// All the triples of the subjects that have at least one predicate of the shape.
+fn focus_nodes_sparql(
+    shape: &OrmSchemaShape,
+    filter_graphs: Option<&Vec<String>>,
+) -> Result<String, NgError> {
+    let predicates = shape
+        .predicates
+        .iter()
+        .map(|p| sparql_iri(&p.iri))
+        .collect::<Result<Vec<_>, _>>()?
+        .join(", ");
+    Ok(format!(
+        "SELECT DISTINCT ?s ?p ?o ?g\nWHERE {{\n  GRAPH ?g {{\n  ?s ?p ?o .\n  ?s ?fp ?fo .\n  }}\n  FILTER(isIRI(?s))\n  FILTER(?fp IN ({predicates}))\n{}}}",
+        graph_filter(filter_graphs)?
+    ))
+}
+
+fn subject_quads_sparql(
+    subject_iri: &str,
+    filter_graphs: Option<&Vec<String>>,
+) -> Result<String, NgError> {
+    Ok(format!(
+        "SELECT DISTINCT ?s ?p ?o ?g\nWHERE {{\n  GRAPH ?g {{\n  ?s ?p ?o .\n  }}\n  FILTER(?s = {})\n{}}}",
+        sparql_iri(subject_iri)?,
+        graph_filter(filter_graphs)?
+    ))
+}
+
+fn graph_filter(filter_graphs: Option<&Vec<String>>) -> Result<String, NgError> {
+    Ok(match filter_graphs {
+        Some(graphs) if !graphs.is_empty() => format!(
+            "  FILTER(?g IN ({}))\n",
+            graphs
+                .iter()
+                .map(|g| sparql_iri(g))
+                .collect::<Result<Vec<_>, _>>()?
+                .join(", ")
+        ),
+        _ => String::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ng_oxigraph::oxrdf::{Literal, NamedNode};
+    use std::sync::Arc;
+
+    fn mk_quad(s: &str, p: &str, o: Term) -> Quad {
+        Quad {
+            subject: NamedNode::new_unchecked(s).into(),
+            predicate: NamedNode::new_unchecked(p),
+            object: o,
+            graph_name: NamedNode::new_unchecked("did:ng:o:g").into(),
+        }
+    }
+
+    fn mk_shape() -> OrmSchemaShape {
+        OrmSchemaShape {
+            iri: "did:ng:x:shape".to_string(),
+            predicates: vec![
+                Arc::new(OrmSchemaPredicate {
+                    dataTypes: vec![OrmSchemaDataType {
+                        valType: OrmSchemaValType::string,
+                        literals: None,
+                        shape: None,
+                    }],
+                    iri: "did:ng:x:title".to_string(),
+                    readablePredicate: "title".to_string(),
+                    maxCardinality: 1,
+                    minCardinality: 1,
+                    extra: None,
+                }),
+                Arc::new(OrmSchemaPredicate {
+                    dataTypes: vec![OrmSchemaDataType {
+                        valType: OrmSchemaValType::shape,
+                        literals: None,
+                        shape: Some("did:ng:x:child".to_string()),
+                    }],
+                    iri: "did:ng:x:item".to_string(),
+                    readablePredicate: "item".to_string(),
+                    maxCardinality: -1,
+                    minCardinality: 0,
+                    extra: None,
+                }),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_predicate_violations_cardinality() {
+        let shape = mk_shape();
+        let quads = vec![
+            mk_quad("did:ng:o:s", "did:ng:x:title", Literal::from("a").into()),
+            mk_quad("did:ng:o:s", "did:ng:x:title", Literal::from("b").into()),
+        ];
+        let violations = predicate_violations(&shape, &quads, |_, _| true);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].kind, OrmViolationKind::maxCardinality);
+        assert_eq!(violations[0].count, 2);
+
+        let violations = predicate_violations(&shape, &[], |_, _| true);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].kind, OrmViolationKind::minCardinality);
+    }
+
+    #[test]
+    fn test_sparql_iri() {
+        assert_eq!(sparql_iri("did:ng:o:s").unwrap(), "<did:ng:o:s>");
+        assert!(sparql_iri("did:ng:o:s> } DELETE WHERE { ?s ?p ?o").is_err());
+        assert!(
+            subject_quads_sparql("did:ng:o:s", Some(&vec!["did:ng:o:g> ".to_string()])).is_err()
+        );
+    }
+
+    #[test]
+    fn test_predicate_violations_nested_and_datatype() {
+        let shape = mk_shape();
+        let quads = vec![
+            mk_quad("did:ng:o:s", "did:ng:x:title", Literal::from(3).into()),
+            mk_quad(
+                "did:ng:o:s",
+                "did:ng:x:item",
+                NamedNode::new_unchecked("did:ng:o:c1").into(),
+            ),
+            mk_quad(
+                "did:ng:o:s",
+                "did:ng:x:item",
+                NamedNode::new_unchecked("did:ng:o:c2").into(),
+            ),
+        ];
+        let violations = predicate_violations(&shape, &quads, |_, _| false);
+        let kinds: Vec<OrmViolationKind> = violations.into_iter().map(|v| v.kind).collect();
+        assert_eq!(kinds, vec![OrmViolationKind::dataType]);
+
+        // like in the ORM objects, the invalid children are ignored when enough children are valid
+        let mut shape = shape;
+        Arc::make_mut(&mut shape.predicates[1]).minCardinality = 1;
+        let violations = predicate_violations(&shape, &quads, |_, _| false);
+        let kinds: Vec<OrmViolationKind> = violations.into_iter().map(|v| v.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![OrmViolationKind::dataType, OrmViolationKind::shape]
+        );
+        let violations = predicate_violations(&shape, &quads, |child, _| child == "did:ng:o:c1");
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].kind, OrmViolationKind::dataType);
+
+        let violations = predicate_violations(&shape, &quads[1..], |_, _| true);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].kind, OrmViolationKind::minCardinality);
+    }
+}
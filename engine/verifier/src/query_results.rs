@@ -0,0 +1,132 @@
+// Copyright (c) 2022-2025 Niko Bonnieure, Par le Peuple, NextGraph.org developers
+// All rights reserved.
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE2 or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Serialization of SPARQL query results in the format requested by the app
+
+use std::io::Write;
+
+use ng_oxigraph::oxigraph::sparql::results::*;
+use ng_oxigraph::oxigraph::sparql::{QueryResults, QuerySolutionIter, QueryTripleIter};
+use ng_oxigraph::oxrdfio::{RdfFormat, RdfSerializer, ToWriteQuadWriter};
+
+use crate::graph_export::{ChunkSender, ChunkWriter};
+
+/// Writes query results one solution (or triple) at a time, so they can be streamed.
+///
+/// SELECT and ASK results use a SPARQL results format (defaults to JSON),
+/// CONSTRUCT and DESCRIBE results use an RDF format (defaults to N-Triples).
+pub(crate) enum QueryResultsWriter<W: Write> {
+    Solutions(ToWriteSolutionsWriter<W>, QuerySolutionIter),
+    Graph(ToWriteQuadWriter<W>, QueryTripleIter),
+    Boolean(W),
+}
+
+impl<W: Write> QueryResultsWriter<W> {
+    pub(crate) fn new(
+        results: QueryResults,
+        format: Option<&str>,
+        write: W,
+    ) -> Result<Self, String> {
+        Ok(match results {
+            QueryResults::Graph(triples) => {
+                let format = match format {
+                    None => RdfFormat::NTriples,
+                    Some(f) => RdfFormat::from_media_type(f)
+                        .ok_or(format!("Unsupported RDF format {f}"))?,
+                };
+                Self::Graph(
+                    RdfSerializer::from_format(format).serialize_to_write(write),
+                    triples,
+                )
+            }
+            results => {
+                let format = match format {
+                    None => QueryResultsFormat::Json,
+                    Some(f) => QueryResultsFormat::from_media_type(f)
+                        .ok_or(format!("Unsupported query results format {f}"))?,
+                };
+                let serializer = QueryResultsSerializer::from_format(format);
+                match results {
+                    QueryResults::Boolean(b) => Self::Boolean(
+                        serializer
+                            .serialize_boolean_to_write(write, b)
+                            .map_err(|_| "QueryResult serializer error")?,
+                    ),
+                    QueryResults::Solutions(solutions) => Self::Solutions(
+                        serializer
+                            .serialize_solutions_to_write(write, solutions.variables().to_vec())
+                            .map_err(|_| "QueryResult serializer error")?,
+                        solutions,
+                    ),
+                    QueryResults::Graph(_) => unreachable!(),
+                }
+            }
+        })
+    }
+
+    /// Writes the next solution or triple. Returns false when all the results have been written.
+    pub(crate) fn write_next(&mut self) -> Result<bool, String> {
+        match self {
+            Self::Solutions(writer, solutions) => match solutions.next() {
+                None => Ok(false),
+                Some(solution) => {
+                    writer
+                        .write(&solution.map_err(|e| e.to_string())?)
+                        .map_err(|_| "QueryResult serializer error")?;
+                    Ok(true)
+                }
+            },
+            Self::Graph(writer, triples) => match triples.next() {
+                None => Ok(false),
+                Some(triple) => {
+                    writer
+                        .write_triple(&triple.map_err(|e| e.to_string())?)
+                        .map_err(|_| "QueryResult serializer error")?;
+                    Ok(true)
+                }
+            },
+            Self::Boolean(_) => Ok(false),
+        }
+    }
+
+    pub(crate) fn finish(self) -> Result<W, String> {
+        match self {
+            Self::Solutions(writer, _) => writer
+                .finish()
+                .map_err(|_| "QueryResult serializer error".to_string()),
+            Self::Graph(writer, _) => writer
+                .finish()
+                .map_err(|_| "QueryResult serializer error".to_string()),
+            Self::Boolean(write) => Ok(write),
+        }
+    }
+
+    pub(crate) fn write_all(mut self) -> Result<W, String> {
+        while self.write_next()? {}
+        self.finish()
+    }
+}
+
+/// Serializes all the results in a single buffer.
+pub(crate) fn query_results_to_vec(
+    results: QueryResults,
+    format: Option<&str>,
+) -> Result<Vec<u8>, String> {
+    QueryResultsWriter::new(results, format, Vec::new())?.write_all()
+}
+
+impl ChunkWriter for QueryResultsWriter<ChunkSender> {
+    fn write_next(&mut self) -> Result<bool, String> {
+        QueryResultsWriter::write_next(self)
+    }
+
+    fn finish(self) -> Result<ChunkSender, String> {
+        QueryResultsWriter::finish(self)
+    }
+}
@@ -9,19 +9,22 @@
 
 //! Processor for each type of AppRequest
 
+use std::cmp::min;
 use std::collections::HashSet;
 use std::sync::Arc;
 
-use futures::channel::mpsc;
+use futures::channel::{mpsc, oneshot};
 use futures::SinkExt;
 use futures::StreamExt;
 use ng_net::types::InboxPost;
 use ng_net::types::NgQRCode;
 use ng_net::types::NgQRCodeProfileSharingV0;
-use ng_oxigraph::oxigraph::sparql::{results::*, Query, QueryResults};
+use ng_oxigraph::oxigraph::sparql::{results::*, Query, QueryOptions, QueryResults};
 use ng_oxigraph::oxrdf::{Literal, NamedNode, Quad, Term};
 use ng_oxigraph::oxsdatatypes::DateTime;
+use ng_oxigraph::spargebra;
 
+use ng_repo::block_storage::store_max_value_size;
 use ng_repo::errors::*;
 use ng_repo::file::{RandomAccessFile, ReadFile};
 #[allow(unused_imports)]
@@ -35,9 +38,16 @@ use ng_repo::types::*;
 use ng_repo::PublicKeySet;
 
 use ng_net::app_protocol::*;
+use ng_net::permissions::{AccessMode, AppAccess};
 use ng_net::utils::ResultSend;
 use ng_net::utils::{spawn_and_log_error, Receiver, Sender};
 
+use crate::graph_export::{end_of_stream, spawn_chunk_writer};
+use crate::query_results::*;
+use crate::sparql_service::{
+    parse_sparql_query, parse_sparql_update, query_graphs, update_graphs, QueryGraphs,
+};
+use crate::text_index::NG_TEXT_SCORE;
 use crate::types::*;
 use crate::verifier::*;
 
@@ -61,7 +71,22 @@ impl Verifier {
                             self.open_for_target(&nuri.target, true).await?;
                         }
                     }
-                    self.start_orm(graph_scope, subject_scope, shape_type).await
+                    self.start_orm(graph_scope, subject_scope, shape_type, None)
+                        .await
+                }
+                Some(AppRequestPayload::V0(AppRequestPayloadV0::OrmResume((
+                    shape_type,
+                    graph_scope,
+                    subject_scope,
+                    known_heads,
+                )))) => {
+                    for nuri in graph_scope.iter() {
+                        if nuri.is_valid_for_sparql_update() {
+                            self.open_for_target(&nuri.target, true).await?;
+                        }
+                    }
+                    self.start_orm(graph_scope, subject_scope, shape_type, Some(known_heads))
+                        .await
                 }
                 _ => return Err(NgError::InvalidArgument),
             },
@@ -76,6 +101,58 @@ impl Verifier {
                         .create_branch_subscription(repo_id, branch_id, store_repo)
                         .await?)
                 }
+                AppFetchContentV0::ReadQueryStream => {
+                    let (sparql, base, format) = match payload {
+                        Some(AppRequestPayload::V0(AppRequestPayloadV0::Query(query))) => {
+                            query.into_parts()
+                        }
+                        _ => return Err(NgError::InvalidPayload),
+                    };
+                    let (tx, rx) = mpsc::unbounded::<AppResponse>();
+                    let prepared = parse_sparql_query(&sparql, base.as_deref())
+                        .and_then(|query| self.prepare_sparql_query(&nuri, query));
+                    match prepared {
+                        Err(VerifierError::SparqlError(s)) => end_of_stream(Err(s), tx.clone()),
+                        Err(e) => end_of_stream(Err(e.to_string()), tx.clone()),
+                        Ok((query, options)) => {
+                            // the query is evaluated in the task, so the first results are sent
+                            // before the last ones are computed
+                            let store = self.graph_dataset.as_ref().unwrap().clone();
+                            spawn_chunk_writer(
+                                move |chunks| {
+                                    let results = store
+                                        .query_opt(query, options)
+                                        .map_err(|e| e.to_string())?;
+                                    QueryResultsWriter::new(results, format.as_deref(), chunks)
+                                },
+                                tx.clone(),
+                            );
+                        }
+                    }
+                    let fnonce = Box::new(move || {
+                        tx.close_channel();
+                    });
+                    Ok((rx, fnonce as CancelFn))
+                }
+                AppFetchContentV0::RdfExport => {
+                    let content_type = match payload {
+                        Some(AppRequestPayload::V0(AppRequestPayloadV0::RdfExport(
+                            content_type,
+                        ))) => content_type,
+                        _ => return Err(NgError::InvalidPayload),
+                    };
+                    if nuri.target != NuriTargetV0::UserSite {
+                        self.open_for_target(&nuri.target, false).await?;
+                    }
+                    let (tx, rx) = mpsc::unbounded::<AppResponse>();
+                    if let Err(e) = self.rdf_export(&nuri, &content_type, tx.clone()) {
+                        end_of_stream(Err(e.to_string()), tx.clone());
+                    }
+                    let fnonce = Box::new(move || {
+                        tx.close_channel();
+                    });
+                    Ok((rx, fnonce as CancelFn))
+                }
                 _ => unimplemented!(),
             },
             AppRequestCommandV0::FileGet => {
@@ -84,19 +161,31 @@ impl Verifier {
                 }
                 let (repo_id, _, store_repo) = self.resolve_target(&nuri.target)?;
                 let obj = nuri.objects.get(0).unwrap();
+                let range = match payload {
+                    None => None,
+                    Some(AppRequestPayload::V0(AppRequestPayloadV0::FileGetRange((
+                        offset,
+                        length,
+                    )))) => Some((offset as usize, length as usize)),
+                    _ => return Err(NgError::InvalidPayload),
+                };
                 let repo = self.get_repo(&repo_id, &store_repo)?;
-                if let Some(mut stream) = self
-                    .fetch_blocks_if_needed(&obj.id, &repo_id, &store_repo)
-                    .await?
-                {
-                    // TODO: start opening the file and running the sending_loop after we received 10 (3 mandatory and 7 depths max) blocks.
-                    // for files below 10MB we wont see a difference, but for big files, we can start sending out some AppResponse earlier.
-                    while let Some(block) = stream.next().await {
-                        repo.store.put(&block)?;
-                    }
-                }
-                let file =
-                    RandomAccessFile::open(obj.id, obj.key.clone(), Arc::clone(&repo.store))?;
+                let store = Arc::clone(&repo.store);
+                let (start, end) = match range {
+                    Some((offset, length)) => (offset, offset.saturating_add(length)),
+                    None => (0, usize::MAX),
+                };
+                // only the blocks of the first window are fetched before we start sending. The next ones are prefetched while sending.
+                // With a range, players and viewers can seek in big files without downloading all of them.
+                let file = self
+                    .open_file_for_range(
+                        obj,
+                        start,
+                        FILE_READ_AHEAD_BLOCKS * store_max_value_size(),
+                        &repo_id,
+                        &store_repo,
+                    )
+                    .await?;
 
                 let (mut tx, rx) = mpsc::unbounded::<AppResponse>();
                 tx.send(AppResponse::V0(AppResponseV0::FileMeta(FileMetaV0 {
@@ -106,45 +195,110 @@ impl Verifier {
                 .await
                 .map_err(|_| NgError::InternalError)?;
 
+                /// the stream ends with an error instead of EndOfStream, so the app knows the file is incomplete
+                async fn send_error(mut tx: Sender<AppResponse>, error: NgError) -> ResultSend<()> {
+                    let _ = tx.send(AppResponse::error(error.to_string())).await;
+                    tx.close_channel();
+                    Ok(())
+                }
+
                 async fn sending_loop(
                     file: Arc<RandomAccessFile>,
                     mut tx: Sender<AppResponse>,
+                    start: usize,
+                    end: usize,
+                    store: Arc<Store>,
+                    user: UserId,
+                    remote: BrokerPeerId,
                 ) -> ResultSend<()> {
-                    let mut pos = 0;
-                    loop {
-                        let res = file.read(pos, 1048564);
-
-                        if res.is_err() {
-                            //log_info!("ERR={:?}", res.unwrap_err());
-                            let _ = tx.send(AppResponse::V0(AppResponseV0::EndOfStream)).await;
-                            tx.close_channel();
-                            break;
-                        }
-                        let res = res.unwrap();
+                    let end = min(end, file.meta().total_size() as usize);
+                    let window = FILE_READ_AHEAD_BLOCKS * file.meta().chunk_size() as usize;
+                    let mut pos = start;
+                    while pos < end {
+                        let next = min(end, pos + window);
+                        // the next window is fetched from the broker while the current one is decrypted and sent
+                        let prefetch = if next < end {
+                            let (sender, receiver) = oneshot::channel::<Result<(), NgError>>();
+                            let file = Arc::clone(&file);
+                            let store = Arc::clone(&store);
+                            let user = user.clone();
+                            let remote = remote.clone();
+                            let size = min(window, end - next);
+                            spawn_and_log_error(async move {
+                                let res =
+                                    fetch_file_range(&file, next, size, &store, user, remote).await;
+                                let _ = sender.send(res);
+                                Ok(())
+                            });
+                            Some(receiver)
+                        } else {
+                            None
+                        };
+
+                        let res = match file.read_range(pos, next - pos) {
+                            Ok(res) if res.len() > 0 => res,
+                            // the file is shorter than its meta says
+                            Ok(_) => return send_error(tx, FileError::EndOfFile.into()).await,
+                            Err(e) => return send_error(tx, e.into()).await,
+                        };
                         //log_info!("reading={} {}", pos, res.len());
                         pos += res.len();
-                        if let Err(_) = tx
-                            .send(AppResponse::V0(AppResponseV0::FileBinary(res)))
-                            .await
-                        {
-                            break;
+                        for chunk in res.chunks(1048564) {
+                            if let Err(_) = tx
+                                .send(AppResponse::V0(AppResponseV0::FileBinary(chunk.to_vec())))
+                                .await
+                            {
+                                return Ok(());
+                            }
+                        }
+
+                        if let Some(receiver) = prefetch {
+                            match receiver.await {
+                                Ok(Ok(())) => {}
+                                Ok(Err(e)) => return send_error(tx, e).await,
+                                Err(_) => return send_error(tx, NgError::InternalError).await,
+                            }
                         }
                     }
+                    let _ = tx.send(AppResponse::V0(AppResponseV0::EndOfStream)).await;
+                    tx.close_channel();
                     Ok(())
                 }
 
-                spawn_and_log_error(sending_loop(Arc::new(file), tx.clone()));
+                spawn_and_log_error(sending_loop(
+                    Arc::new(file),
+                    tx.clone(),
+                    start,
+                    end,
+                    store,
+                    self.user_id().clone(),
+                    self.connected_broker.clone(),
+                ));
                 let fnonce = Box::new(move || {
                     //log_debug!("FileGet cancelled");
                     tx.close_channel();
                 });
                 Ok((rx, fnonce as CancelFn))
             }
+            AppRequestCommandV0::GraphImport => {
+                let run = self.graph_import_start(nuri, payload).await?;
+                let (mut tx, rx) = mpsc::unbounded::<AppResponse>();
+                let response = self.import_graph(run, &mut tx).await;
+                tx.send(response)
+                    .await
+                    .map_err(|_| NgError::InternalError)?;
+                tx.send(AppResponse::V0(AppResponseV0::EndOfStream))
+                    .await
+                    .map_err(|_| NgError::InternalError)?;
+                tx.close_channel();
+                let fnonce = Box::new(move || {});
+                Ok((rx, fnonce as CancelFn))
+            }
             _ => unimplemented!(),
         }
     }
 
-    fn resolve_target(
+    pub(crate) fn resolve_target(
         &self,
         target: &NuriTargetV0,
     ) -> Result<(RepoId, BranchId, StoreRepo), NgError> {
@@ -236,6 +390,63 @@ impl Verifier {
         }
     }
 
+    /// adds a member to the repo, with an AddMember commit on the root branch, followed by an AddPermission commit for each permission.
+    /// The editors receive the RepoWriteCapSecret at the next refresh of the capabilities.
+    pub(crate) async fn add_member(
+        &mut self,
+        repo_id: &RepoId,
+        store_repo: &StoreRepo,
+        member: UserId,
+        permissions: Vec<PermissionV0>,
+    ) -> Result<(), VerifierError> {
+        let repo = self.get_repo(repo_id, store_repo)?;
+        if repo.is_owner(&member)
+            || permissions
+                .iter()
+                .any(|p| *p == PermissionV0::Create || *p == PermissionV0::Owner)
+        {
+            return Err(VerifierError::InvalidArgument);
+        }
+        let body = CommitBodyV0::AddMember(AddMember::V0(AddMemberV0 {
+            member,
+            metadata: vec![],
+        }));
+        self.new_commit(body, repo_id, repo_id, store_repo, &vec![], vec![], vec![])
+            .await?;
+        for permission in permissions {
+            let body = CommitBodyV0::AddPermission(AddPermission::V0(AddPermissionV0 {
+                member,
+                permission,
+                metadata: vec![],
+            }));
+            self.new_commit(body, repo_id, repo_id, store_repo, &vec![], vec![], vec![])
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// removes a member from the repo. The RemoveMember commit is signed by the owners,
+    /// and the capabilities of the repo are refreshed so that the removed member cannot read the new commits.
+    pub(crate) async fn remove_member(
+        &mut self,
+        repo_id: &RepoId,
+        store_repo: &StoreRepo,
+        member: UserId,
+        banned: bool,
+    ) -> Result<(), VerifierError> {
+        let repo = self.get_repo(repo_id, store_repo)?;
+        if repo.is_owner(&member) {
+            return Err(VerifierError::PermissionDenied);
+        }
+        let removed = RemoveMemberV0 {
+            member,
+            banned,
+            metadata: vec![],
+        };
+        self.refresh_caps(repo_id, store_repo, &[member], &[removed])
+            .await
+    }
+
     fn resolve_header_branch(
         &self,
         target: &NuriTargetV0,
@@ -294,7 +505,16 @@ impl Verifier {
         Ok((repo_id, branch, store_repo))
     }
 
-    pub fn handle_query_results(results: QueryResults) -> Result<AppResponse, String> {
+    /// Without a format, SELECT results are serialized in SPARQL-JSON, and CONSTRUCT results as a serde_bare list of triples.
+    pub fn handle_query_results(
+        results: QueryResults,
+        format: Option<&str>,
+    ) -> Result<AppResponse, String> {
+        if format.is_some() {
+            return Ok(AppResponse::V0(AppResponseV0::QueryResult(
+                query_results_to_vec(results, format)?,
+            )));
+        }
         Ok(match results {
             QueryResults::Solutions(solutions) => {
                 let serializer = QueryResultsSerializer::from_format(QueryResultsFormat::Json);
@@ -653,27 +873,85 @@ impl Verifier {
             .await
     }
 
-    pub(crate) async fn sparql_query(
+    fn prepare_sparql_query(
         &self,
         nuri: &NuriV0,
-        sparql: String,
-        base: Option<String>,
-    ) -> Result<QueryResults, VerifierError> {
+        query: spargebra::Query,
+    ) -> Result<(Query, QueryOptions), VerifierError> {
         //log_debug!("query={}", query);
-        let store = self.graph_dataset.as_ref().unwrap();
-        let mut parsed = Query::parse(&sparql, base.as_deref())
-            .map_err(|e| VerifierError::SparqlError(e.to_string()))?;
+        let service_handler = self.sparql_service_handler(&query)?;
+        let mut parsed = Query::from(query);
         let dataset = parsed.dataset_mut();
         //log_debug!("DEFAULTS {:?}", dataset.default_graph_graphs());
         if dataset.has_no_default_dataset() {
             //log_info!("DEFAULT GRAPH AS UNION");
             dataset.set_default_graph_as_union();
         }
-        store
-            .query(parsed, self.resolve_target_for_sparql(&nuri.target, false)?)
+        let mut options = QueryOptions::default().with_service_handler(service_handler);
+        if let Some(text_score) = self.text_score_function() {
+            options =
+                options.with_custom_function(NamedNode::new_unchecked(NG_TEXT_SCORE), text_score);
+        }
+        options.set_default_graph(self.resolve_target_for_sparql(&nuri.target, false)?);
+        Ok((parsed, options))
+    }
+
+    pub(crate) async fn sparql_query(
+        &self,
+        nuri: &NuriV0,
+        sparql: String,
+        base: Option<String>,
+    ) -> Result<QueryResults, VerifierError> {
+        self.evaluate_sparql_query(nuri, parse_sparql_query(&sparql, base.as_deref())?)
+    }
+
+    pub(crate) fn evaluate_sparql_query(
+        &self,
+        nuri: &NuriV0,
+        query: spargebra::Query,
+    ) -> Result<QueryResults, VerifierError> {
+        let (query, options) = self.prepare_sparql_query(nuri, query)?;
+        self.graph_dataset
+            .as_ref()
+            .unwrap()
+            .query_opt(query, options)
             .map_err(|e| VerifierError::SparqlError(e.to_string()))
     }
 
+    /// Evaluates the query until the end of its results, and returns the JSON explanation of its plan,
+    /// with the duration and the number of results of each operator.
+    pub(crate) fn sparql_query_explain(
+        &self,
+        nuri: &NuriV0,
+        query: spargebra::Query,
+    ) -> Result<String, VerifierError> {
+        let (query, options) = self.prepare_sparql_query(nuri, query)?;
+        let (results, explanation) = self
+            .graph_dataset
+            .as_ref()
+            .unwrap()
+            .explain_query_opt(query, options, true)
+            .map_err(|e| VerifierError::SparqlError(e.to_string()))?;
+        match results.map_err(|e| VerifierError::SparqlError(e.to_string()))? {
+            QueryResults::Solutions(solutions) => {
+                for solution in solutions {
+                    solution.map_err(|e| VerifierError::SparqlError(e.to_string()))?;
+                }
+            }
+            QueryResults::Graph(triples) => {
+                for triple in triples {
+                    triple.map_err(|e| VerifierError::SparqlError(e.to_string()))?;
+                }
+            }
+            QueryResults::Boolean(_) => {}
+        }
+        let mut json = Vec::new();
+        explanation
+            .write_in_json(&mut json)
+            .map_err(|e| VerifierError::OtherError(e.to_string()))?;
+        String::from_utf8(json).map_err(|_| VerifierError::InternalError)
+    }
+
     pub(crate) async fn doc_create(
         &mut self,
         nuri: NuriV0,
@@ -958,12 +1236,124 @@ impl Verifier {
         )?;
 
         let crdt: &BranchCrdt = &branch.crdt.clone();
-        self.process_discrete(patch, &crdt, &branch_id, commit_id, commit_info, from_orm)
-            .await?;
+        self.process_discrete(
+            patch,
+            &crdt,
+            &branch_id,
+            &repo_id,
+            commit_id,
+            commit_info,
+            from_orm,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Checks an AppRequest of an app session against the grants of the session.
+    /// The type of the request is the class of the target document, or of the document to create.
+    /// The graphs that a read query reaches besides its target (GRAPH, FROM and SERVICE) are checked too,
+    /// and so are the graphs written and read by an update. The imports are restricted to their target
+    /// by the caller, with [GraphImportRun::restrict_to_target](crate::graph_import::GraphImportRun::restrict_to_target).
+    pub fn check_app_access(
+        &self,
+        access: &mut AppAccess,
+        request: &AppRequest,
+    ) -> Result<(), NgError> {
+        let AppRequest::V0(req) = request;
+        let mode = AccessMode::required_by(&req.command);
+        let subscription = match req.command {
+            AppRequestCommandV0::Fetch(AppFetchContentV0::Subscribe)
+            | AppRequestCommandV0::OrmStartGraph
+            | AppRequestCommandV0::OrmStartDiscrete => true,
+            _ => false,
+        };
+        let (access_type, target) = match (&req.command, &req.payload) {
+            (
+                AppRequestCommandV0::Create,
+                Some(AppRequestPayload::V0(AppRequestPayloadV0::Create(doc_create))),
+            ) => match doc_create.class {
+                BranchCrdt::None => (None, None),
+                _ => (Some(doc_create.class.class().clone()), None),
+            },
+            (AppRequestCommandV0::InboxPost, _)
+            | (AppRequestCommandV0::QrCodeProfile, _)
+            | (AppRequestCommandV0::QrCodeProfileImport, _) => (Some("Contact".to_string()), None),
+            _ => self.app_access_target(&req.nuri.target),
+        };
+        // the grants are checked on a copy, so that none is used up when the request is denied
+        let mut checked = access.clone();
+        checked.check(&mode, access_type.as_ref(), target.as_ref(), subscription)?;
+
+        // a query can also read the graphs of its GRAPH, FROM and SERVICE clauses,
+        // and an update can write in the graphs of its quads and templates
+        match (&req.command, &req.payload) {
+            (
+                AppRequestCommandV0::Fetch(
+                    AppFetchContentV0::ReadQuery
+                    | AppFetchContentV0::ReadQueryStream
+                    | AppFetchContentV0::ReadQueryExplain,
+                ),
+                Some(AppRequestPayload::V0(AppRequestPayloadV0::Query(query))),
+            ) => {
+                let (sparql, base, _) = query.clone().into_parts();
+                let graphs = query_graphs(&parse_sparql_query(&sparql, base.as_deref())?);
+                self.check_app_access_graphs(&mut checked, &AccessMode::Read, &graphs)?;
+            }
+            (
+                AppRequestCommandV0::Fetch(AppFetchContentV0::WriteQuery),
+                Some(AppRequestPayload::V0(AppRequestPayloadV0::Query(query))),
+            ) => {
+                let (sparql, base, _) = query.clone().into_parts();
+                let graphs = update_graphs(&parse_sparql_update(&sparql, base.as_deref())?);
+                self.check_app_access_graphs(&mut checked, &AccessMode::Write, &graphs.written)?;
+                self.check_app_access_graphs(&mut checked, &AccessMode::Read, &graphs.read)?;
+            }
+            _ => {}
+        }
+        *access = checked;
+        Ok(())
+    }
 
+    /// Checks the graphs and services that a query or an update reaches besides its target
+    fn check_app_access_graphs(
+        &self,
+        checked: &mut AppAccess,
+        mode: &AccessMode,
+        graphs: &QueryGraphs,
+    ) -> Result<(), NgError> {
+        if graphs.any_graph {
+            // GRAPH ?g can reach any document of the user
+            checked.check(mode, None, None, false)?;
+        }
+        for name in graphs.graphs.iter().chain(graphs.services.iter()) {
+            // the names that aren't the ones of a document need an access to the whole user site
+            let (access_type, target) = NuriV0::new_from(name)
+                .map(|nuri| self.app_access_target(&nuri.target))
+                .unwrap_or((None, None));
+            checked.check(mode, access_type.as_ref(), target.as_ref(), false)?;
+        }
         Ok(())
     }
 
+    /// The type (the class of the document) and the target of the grant needed to access a NuriTargetV0
+    fn app_access_target(&self, target: &NuriTargetV0) -> (Option<String>, Option<String>) {
+        match target {
+            NuriTargetV0::Repo(repo_id) => {
+                let class = self
+                    .repos
+                    .get(repo_id)
+                    .and_then(|repo| repo.main_branch())
+                    .and_then(|branch| match branch.crdt {
+                        BranchCrdt::None => None,
+                        _ => Some(branch.crdt.class().clone()),
+                    });
+                (class, Some(NuriV0::repo_id(repo_id)))
+            }
+            _ => (None, None),
+        }
+    }
+
     pub(crate) async fn process(
         &mut self,
         command: &AppRequestCommandV0,
@@ -1224,6 +1614,40 @@ impl Verifier {
                     return Err(NgError::InvalidPayload);
                 }
             }
+            AppRequestCommandV0::MemberUpdate => {
+                let (repo_id, _, store_repo) = self.resolve_target(&nuri.target)?;
+                let res = match payload {
+                    Some(AppRequestPayload::V0(AppRequestPayloadV0::AddMember(add))) => {
+                        self.add_member(&repo_id, &store_repo, add.member, add.permissions)
+                            .await
+                    }
+                    Some(AppRequestPayload::V0(AppRequestPayloadV0::RemoveMember(remove))) => {
+                        self.remove_member(&repo_id, &store_repo, remove.member, remove.banned)
+                            .await
+                    }
+                    _ => return Err(NgError::InvalidPayload),
+                };
+                return match res {
+                    Ok(()) => Ok(AppResponse::ok()),
+                    Err(e) => Ok(AppResponse::error(e.to_string())),
+                };
+            }
+            AppRequestCommandV0::RefreshCaps => {
+                let exclude = match payload {
+                    Some(AppRequestPayload::V0(AppRequestPayloadV0::RefreshCaps(refresh))) => {
+                        refresh.exclude
+                    }
+                    _ => return Err(NgError::InvalidPayload),
+                };
+                let (repo_id, _, store_repo) = self.resolve_target(&nuri.target)?;
+                return match self
+                    .refresh_caps(&repo_id, &store_repo, &exclude, &[])
+                    .await
+                {
+                    Ok(()) => Ok(AppResponse::ok()),
+                    Err(e) => Ok(AppResponse::error(e.to_string())),
+                };
+            }
             AppRequestCommandV0::Create => {
                 return if let Some(AppRequestPayload::V0(AppRequestPayloadV0::Create(doc_create))) =
                     payload
@@ -1284,18 +1708,33 @@ impl Verifier {
                         _ => return Err(NgError::InvalidResponse),
                     };
                 }
+                AppFetchContentV0::ReadQueryExplain => {
+                    return if let Some(AppRequestPayload::V0(AppRequestPayloadV0::Query(query))) =
+                        payload
+                    {
+                        let (sparql, base, _) = query.into_parts();
+                        let explanation = parse_sparql_query(&sparql, base.as_deref())
+                            .and_then(|query| self.sparql_query_explain(&nuri, query));
+                        Ok(match explanation {
+                            Err(VerifierError::SparqlError(s)) => AppResponse::error(s),
+                            Err(e) => AppResponse::error(e.to_string()),
+                            Ok(json) => AppResponse::V0(AppResponseV0::QueryExplanation(json)),
+                        })
+                    } else {
+                        Err(NgError::InvalidPayload)
+                    };
+                }
                 AppFetchContentV0::ReadQuery => {
-                    if let Some(AppRequestPayload::V0(AppRequestPayloadV0::Query(DocQuery::V0 {
-                        sparql,
-                        base,
-                    }))) = payload
+                    if let Some(AppRequestPayload::V0(AppRequestPayloadV0::Query(query))) = payload
                     {
-                        let results = self.sparql_query(&nuri, sparql, base).await;
+                        let (sparql, base, format) = query.into_parts();
+                        let results = parse_sparql_query(&sparql, base.as_deref())
+                            .and_then(|query| self.evaluate_sparql_query(&nuri, query));
                         return Ok(match results {
                             Err(VerifierError::SparqlError(s)) => AppResponse::error(s),
                             Err(e) => AppResponse::error(e.to_string()),
                             Ok(qr) => {
-                                let res = Self::handle_query_results(qr);
+                                let res = Self::handle_query_results(qr, format.as_deref());
                                 match res {
                                     Ok(ok) => ok,
                                     Err(s) => AppResponse::error(s),
@@ -1310,10 +1749,10 @@ impl Verifier {
                     if !nuri.is_valid_for_sparql_update() {
                         return Err(NgError::InvalidNuri);
                     }
-                    return if let Some(AppRequestPayload::V0(AppRequestPayloadV0::Query(
-                        DocQuery::V0 { sparql, base },
-                    ))) = payload
+                    return if let Some(AppRequestPayload::V0(AppRequestPayloadV0::Query(query))) =
+                        payload
                     {
+                        let (sparql, base, _) = query.into_parts();
                         Ok(
                             match self
                                 .process_sparql_update(
@@ -1340,8 +1779,17 @@ impl Verifier {
                     return if let Some(AppRequestPayload::V0(AppRequestPayloadV0::Update(update))) =
                         payload
                     {
-                        //TODO: deal with update.graph
                         //TODO: verify that update.heads are the same as what the Verifier knows
+                        if let Some(graph) = update.graph {
+                            self.open_for_target(&nuri.target, true).await?;
+                            let commits = match self.process_graph_update(&nuri, graph).await {
+                                Err(e) => return Ok(AppResponse::error(e.to_string())),
+                                Ok(commits) => commits,
+                            };
+                            if update.discrete.is_none() {
+                                return Ok(AppResponse::commits(commits));
+                            }
+                        }
                         if let Some(discrete) = update.discrete {
                             let patch: DiscreteTransaction = discrete.into();
                             return match self.create_discrete_transaction(patch, &nuri, None).await
@@ -1356,19 +1804,44 @@ impl Verifier {
                         Err(NgError::InvalidPayload)
                     };
                 }
-                AppFetchContentV0::RdfDump => {
-                    let store = self.graph_dataset.as_ref().unwrap();
-
-                    let results = store.iter();
-
-                    let vec: Vec<String> = results
-                        .map(|q| match q {
-                            Ok(o) => o.to_string(),
-                            Err(e) => e.to_string(),
+                AppFetchContentV0::TextSearch => {
+                    return if let Some(AppRequestPayload::V0(AppRequestPayloadV0::TextSearch(
+                        search,
+                    ))) = payload
+                    {
+                        Ok(match self.text_search(&nuri, &search.query, search.limit) {
+                            Err(e) => AppResponse::error(e.to_string()),
+                            Ok(results) => {
+                                AppResponse::V0(AppResponseV0::TextSearchResults(results))
+                            }
                         })
-                        .collect();
-
-                    return Ok(AppResponse::V0(AppResponseV0::Text(vec.join("\n"))));
+                    } else {
+                        Err(NgError::InvalidPayload)
+                    };
+                }
+                AppFetchContentV0::Validate => {
+                    return if let Some(AppRequestPayload::V0(AppRequestPayloadV0::Validate(
+                        shape_type,
+                    ))) = payload
+                    {
+                        match nuri.target {
+                            NuriTargetV0::UserSite => {}
+                            NuriTargetV0::Repo(_) => {
+                                self.open_for_target(&nuri.target, true).await?;
+                            }
+                            _ => return Err(NgError::InvalidNuri),
+                        }
+                        Ok(
+                            match self.validate_graph_against_shape(&nuri, &shape_type) {
+                                Err(e) => AppResponse::error(e.to_string()),
+                                Ok(report) => {
+                                    AppResponse::V0(AppResponseV0::ValidationReport(report))
+                                }
+                            },
+                        )
+                    } else {
+                        Err(NgError::InvalidPayload)
+                    };
                 }
                 AppFetchContentV0::CurrentHeads => {
                     if nuri.target.is_repo_id() {
@@ -1476,7 +1949,13 @@ impl Verifier {
                     AppRequestPayloadV0::RandomAccessFilePut(content_type) => {
                         let (repo_id, _, store_repo) = self.resolve_target(&nuri.target)?;
                         let repo = self.get_repo(&repo_id, &store_repo)?;
-                        let id = self.start_upload(content_type, Arc::clone(&repo.store));
+                        let id = self.start_upload(content_type, false, Arc::clone(&repo.store))?;
+                        return Ok(AppResponse::V0(AppResponseV0::FileUploading(id)));
+                    }
+                    AppRequestPayloadV0::RandomAccessFilePutContentDefined(content_type) => {
+                        let (repo_id, _, store_repo) = self.resolve_target(&nuri.target)?;
+                        let repo = self.get_repo(&repo_id, &store_repo)?;
+                        let id = self.start_upload(content_type, true, Arc::clone(&repo.store))?;
                         return Ok(AppResponse::V0(AppResponseV0::FileUploading(id)));
                     }
                     AppRequestPayloadV0::RandomAccessFilePutChunk((id, chunk)) => {
@@ -1487,6 +1966,10 @@ impl Verifier {
                             return Ok(AppResponse::V0(AppResponseV0::FileUploaded(reference)));
                         }
                     }
+                    AppRequestPayloadV0::RandomAccessFileResume(id) => {
+                        let pos = self.resume_upload(id)?;
+                        return Ok(AppResponse::V0(AppResponseV0::FileUploadResumed(pos)));
+                    }
                     _ => return Err(NgError::InvalidPayload),
                 },
             },
@@ -1496,3 +1979,208 @@ impl Verifier {
         Ok(AppResponse::V0(AppResponseV0::Ok))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ng_net::permissions::{AccessGrantV0, AccessRequestV0, AccessScope};
+
+    fn sparql_request(command: AppFetchContentV0, repo_id: &RepoId, sparql: &str) -> AppRequest {
+        AppRequest::new(
+            AppRequestCommandV0::Fetch(command),
+            NuriV0::new_repo_target_from_id(repo_id),
+            Some(AppRequestPayload::new_sparql_query(
+                sparql.to_string(),
+                None,
+            )),
+        )
+    }
+
+    fn query_request(repo_id: &RepoId, sparql: &str) -> AppRequest {
+        sparql_request(AppFetchContentV0::ReadQuery, repo_id, sparql)
+    }
+
+    fn update_request(repo_id: &RepoId, sparql: &str) -> AppRequest {
+        sparql_request(AppFetchContentV0::WriteQuery, repo_id, sparql)
+    }
+
+    /// an access that can use the document `repo_id` once, with the given mode
+    fn once(repo_id: &RepoId, mode: AccessMode) -> AppAccess {
+        let request = AccessRequestV0 {
+            id: "once".to_string(),
+            mode,
+            types: vec!["any".to_string()],
+            scopes: vec![AccessScope::Once],
+            optional: false,
+            depends_on: None,
+        };
+        let grant = AccessGrantV0::new(
+            &request,
+            "any".to_string(),
+            AccessScope::Once,
+            Some(NuriV0::repo_id(repo_id)),
+            PubKey::nil(),
+        );
+        AppAccess::new("https://app.example".to_string(), vec![grant])
+    }
+
+    /// an access that can read the document `repo_id` once
+    fn read_once(repo_id: &RepoId) -> AppAccess {
+        once(repo_id, AccessMode::Read)
+    }
+
+    #[test]
+    pub fn test_check_app_access_graph() {
+        let verifier = Verifier::new_dummy();
+        let repo_id = PubKey::Ed25519PubKey([1; 32]);
+        let other_id = PubKey::Ed25519PubKey([2; 32]);
+        let mut access = read_once(&repo_id);
+
+        // GRAPH ?g reads all the documents of the user
+        let request = query_request(&repo_id, "SELECT * WHERE { GRAPH ?g { ?s ?p ?o } }");
+        assert_eq!(
+            verifier.check_app_access(&mut access, &request),
+            Err(NgError::PermissionDenied)
+        );
+        // a named graph of another document, or a FROM of another document
+        let request = query_request(
+            &repo_id,
+            &format!(
+                "SELECT * WHERE {{ GRAPH <{}> {{ ?s ?p ?o }} }}",
+                NuriV0::repo_graph_name(&other_id, &OverlayId::Outer([3; 32]))
+            ),
+        );
+        assert_eq!(
+            verifier.check_app_access(&mut access, &request),
+            Err(NgError::PermissionDenied)
+        );
+        let request = query_request(
+            &repo_id,
+            &format!(
+                "SELECT * FROM <{}> WHERE {{ ?s ?p ?o }}",
+                NuriV0::repo_id(&other_id)
+            ),
+        );
+        assert_eq!(
+            verifier.check_app_access(&mut access, &request),
+            Err(NgError::PermissionDenied)
+        );
+
+        // the denied requests didn't use up the grant
+        let request = query_request(&repo_id, "SELECT * WHERE { ?s ?p ?o }");
+        assert_eq!(verifier.check_app_access(&mut access, &request), Ok(()));
+        assert_eq!(
+            verifier.check_app_access(&mut access, &request),
+            Err(NgError::PermissionDenied)
+        );
+    }
+
+    #[test]
+    pub fn test_check_app_access_service() {
+        let verifier = Verifier::new_dummy();
+        let repo_id = PubKey::Ed25519PubKey([1; 32]);
+        let other_id = PubKey::Ed25519PubKey([2; 32]);
+
+        let mut access = read_once(&repo_id);
+        let request = query_request(
+            &repo_id,
+            &format!(
+                "SELECT * WHERE {{ ?s ?p ?o OPTIONAL {{ SERVICE <{}> {{ ?s ?p ?x }} }} }}",
+                NuriV0::repo_id(&other_id)
+            ),
+        );
+        assert_eq!(
+            verifier.check_app_access(&mut access, &request),
+            Err(NgError::PermissionDenied)
+        );
+        let request = query_request(
+            &repo_id,
+            "SELECT * WHERE { SERVICE <did:ng:i> { ?s ?p ?o } }",
+        );
+        assert_eq!(
+            verifier.check_app_access(&mut access, &request),
+            Err(NgError::PermissionDenied)
+        );
+
+        // with a grant on the document of the SERVICE too
+        let mut access = read_once(&repo_id);
+        access.grants.append(&mut read_once(&other_id).grants);
+        let request = query_request(
+            &repo_id,
+            &format!(
+                "SELECT * WHERE {{ SERVICE <{}> {{ ?s ?p ?o }} }}",
+                NuriV0::repo_id(&other_id)
+            ),
+        );
+        assert_eq!(verifier.check_app_access(&mut access, &request), Ok(()));
+        assert!(access.grants.is_empty());
+    }
+
+    #[test]
+    pub fn test_check_app_access_update() {
+        let verifier = Verifier::new_dummy();
+        let repo_id = PubKey::Ed25519PubKey([1; 32]);
+        let other_id = PubKey::Ed25519PubKey([2; 32]);
+        let other_graph = NuriV0::repo_graph_name(&other_id, &OverlayId::Outer([3; 32]));
+        let mut access = once(&repo_id, AccessMode::Write);
+
+        // the quads and the templates can write in the graph of another document
+        for sparql in [
+            format!("INSERT DATA {{ GRAPH <{other_graph}> {{ <s:a> <s:p> <s:o> }} }}"),
+            format!("DELETE DATA {{ GRAPH <{other_graph}> {{ <s:a> <s:p> <s:o> }} }}"),
+            format!("INSERT {{ GRAPH <{other_graph}> {{ ?s ?p ?o }} }} WHERE {{ ?s ?p ?o }}"),
+            format!("WITH <{other_graph}> DELETE {{ ?s ?p ?o }} WHERE {{ ?s ?p ?o }}"),
+            format!("CLEAR GRAPH <{other_graph}>"),
+            format!("DROP GRAPH <{other_graph}>"),
+        ] {
+            assert_eq!(
+                verifier.check_app_access(&mut access, &update_request(&repo_id, &sparql)),
+                Err(NgError::PermissionDenied),
+                "{sparql}"
+            );
+        }
+
+        // and a variable graph name, or ALL, can reach all the documents of the user
+        for sparql in [
+            "DELETE { GRAPH ?g { ?s ?p ?o } } WHERE { GRAPH ?g { ?s ?p ?o } }",
+            "CLEAR ALL",
+            "LOAD <http://example.com/data.ttl>",
+        ] {
+            assert_eq!(
+                verifier.check_app_access(&mut access, &update_request(&repo_id, sparql)),
+                Err(NgError::PermissionDenied),
+                "{sparql}"
+            );
+        }
+
+        // the WHERE clause can read another document
+        let request = update_request(
+            &repo_id,
+            &format!("INSERT {{ ?s ?p ?o }} WHERE {{ GRAPH <{other_graph}> {{ ?s ?p ?o }} }}"),
+        );
+        assert_eq!(
+            verifier.check_app_access(&mut access, &request),
+            Err(NgError::PermissionDenied)
+        );
+
+        // the denied updates didn't use up the grant
+        let request = update_request(&repo_id, "INSERT DATA { <s:a> <s:p> <s:o> }");
+        assert_eq!(verifier.check_app_access(&mut access, &request), Ok(()));
+        assert_eq!(
+            verifier.check_app_access(&mut access, &request),
+            Err(NgError::PermissionDenied)
+        );
+
+        // with a grant on the other document too
+        let mut access = once(&repo_id, AccessMode::Write);
+        access
+            .grants
+            .append(&mut once(&other_id, AccessMode::Write).grants);
+        let request = update_request(
+            &repo_id,
+            &format!("INSERT DATA {{ <s:a> <s:p> <s:o> . GRAPH <{other_graph}> {{ <s:a> <s:p> <s:o> }} }}"),
+        );
+        assert_eq!(verifier.check_app_access(&mut access, &request), Ok(()));
+        assert!(access.grants.is_empty());
+    }
+}
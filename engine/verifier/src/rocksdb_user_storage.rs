@@ -9,18 +9,19 @@
 
 //! RocksDb Backend for UserStorage trait
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 
 use either::Either::{Left, Right};
 
 use ng_net::app_protocol::{
-    AppTabBranchInfo, AppTabDocInfo, AppTabInfo, AppTabStoreInfo, FileName, NuriV0,
+    AppTabBranchInfo, AppTabDocInfo, AppTabDocMember, AppTabInfo, AppTabStoreInfo, FileName, NuriV0,
 };
+use ng_net::types::WalletRecoveryShare;
 use ng_repo::block_storage::BlockStorage;
 use ng_repo::log::*;
-use ng_repo::repo::{BranchInfo, Repo};
+use ng_repo::repo::{BranchInfo, Repo, UserInfo};
 use ng_repo::store::Store;
 use ng_repo::{errors::StorageError, types::*};
 
@@ -28,6 +29,9 @@ use ng_storage_rocksdb::kcv_storage::RocksDbKCVStorage;
 
 use crate::user_storage::branch::*;
 use crate::user_storage::repo::*;
+use crate::user_storage::text_index::*;
+use crate::user_storage::upload::*;
+use crate::user_storage::wallet_share::*;
 use crate::user_storage::*;
 
 pub(crate) struct RocksDbUserStorage {
@@ -97,6 +101,46 @@ impl UserStorage for RocksDbUserStorage {
         RepoStorage::update_certificate(repo_id, certificate, &self.user_storage)
     }
 
+    fn update_repo_caps(
+        &self,
+        repo_id: &RepoId,
+        read_cap: &ReadCap,
+        write_cap: Option<&RepoWriteCapSecret>,
+    ) -> Result<(), StorageError> {
+        RepoStorage::update_caps(repo_id, read_cap, write_cap, &self.user_storage)
+    }
+
+    fn update_repo_members(
+        &self,
+        repo_id: &RepoId,
+        members: &HashMap<Digest, UserInfo>,
+    ) -> Result<(), StorageError> {
+        RepoStorage::update_members(repo_id, members, &self.user_storage)
+    }
+
+    fn update_repo_refresh_secret(
+        &self,
+        repo_id: &RepoId,
+        refresh_id: &ObjectId,
+        refresh_secret: &RefreshSecretV0,
+        write_cap: Option<&RepoWriteCapSecret>,
+    ) -> Result<(), StorageError> {
+        RepoStorage::update_refresh_secret(
+            repo_id,
+            refresh_id,
+            refresh_secret,
+            write_cap,
+            &self.user_storage,
+        )
+    }
+
+    fn get_repo_refresh_secret(
+        &self,
+        repo_id: &RepoId,
+    ) -> Result<(ObjectId, RefreshSecretV0, Option<RepoWriteCapSecret>), StorageError> {
+        RepoStorage::open(repo_id, &self.user_storage)?.get_refresh_secret()
+    }
+
     fn get_signer_cap(&self, repo_id: &RepoId) -> Result<SignerCap, StorageError> {
         RepoStorage::open(repo_id, &self.user_storage)?.get_signer_cap()
     }
@@ -148,12 +192,115 @@ impl UserStorage for RocksDbUserStorage {
     fn branch_get_all_files(&self, branch: &BranchId) -> Result<Vec<FileName>, StorageError> {
         BranchStorage::get_all_files(&branch, &self.user_storage)
     }
+    fn branch_remove_file(
+        &self,
+        branch: &BranchId,
+        file_id: &ObjectId,
+    ) -> Result<Option<FileName>, StorageError> {
+        let branch = BranchStorage::new(&branch, &self.user_storage)?;
+        branch.remove_file(file_id)
+    }
+    fn file_is_referenced(&self, file_id: &ObjectId) -> Result<bool, StorageError> {
+        BranchStorage::is_file_referenced(file_id, &self.user_storage)
+    }
+    fn file_get_all_referenced(&self) -> Result<Vec<FileName>, StorageError> {
+        BranchStorage::get_all_referenced_files(&self.user_storage)
+    }
+
+    fn upload_create(
+        &self,
+        upload_id: u32,
+        store_repo: &StoreRepo,
+        meta: &RandomAccessFileMeta,
+    ) -> Result<(), StorageError> {
+        UploadStorage::new(&self.user_storage).create(upload_id, store_repo, meta)
+    }
+
+    fn upload_get_all_ids(&self) -> Result<HashSet<u32>, StorageError> {
+        UploadStorage::new(&self.user_storage).get_all_ids()
+    }
+
+    fn upload_remove_expired(&self, max_age: Timestamp) -> Result<Vec<u32>, StorageError> {
+        UploadStorage::new(&self.user_storage).remove_expired(max_age)
+    }
+
+    fn upload_add_blocks(
+        &self,
+        upload_id: u32,
+        first_index: usize,
+        blocks: &[(BlockId, BlockKey)],
+    ) -> Result<(), StorageError> {
+        UploadStorage::new(&self.user_storage).add_blocks(upload_id, first_index, blocks)
+    }
+
+    fn upload_load(
+        &self,
+        upload_id: u32,
+    ) -> Result<(StoreRepo, RandomAccessFileMeta, Vec<(BlockId, BlockKey)>), StorageError> {
+        UploadStorage::new(&self.user_storage).load(upload_id)
+    }
+
+    fn upload_remove(&self, upload_id: u32) -> Result<(), StorageError> {
+        UploadStorage::new(&self.user_storage).remove(upload_id)
+    }
+
+    fn wallet_share_save(
+        &self,
+        share: &WalletRecoveryShare,
+        from_overlay: &OverlayId,
+    ) -> Result<(), StorageError> {
+        WalletShareStorage::new(&self.user_storage).save(share, from_overlay)
+    }
+
+    fn wallet_share_load(
+        &self,
+        wallet_id: &PubKey,
+    ) -> Result<(WalletRecoveryShare, OverlayId), StorageError> {
+        WalletShareStorage::new(&self.user_storage).load(wallet_id)
+    }
+
+    fn text_index_get_document(
+        &self,
+        repo_id: &RepoId,
+    ) -> Result<HashMap<String, String>, StorageError> {
+        TextIndexStorage::new(&self.user_storage).get_document(repo_id)
+    }
+
+    fn text_index_get_text(
+        &self,
+        repo_id: &RepoId,
+        source: &String,
+    ) -> Result<Option<String>, StorageError> {
+        TextIndexStorage::new(&self.user_storage).get_text(repo_id, source)
+    }
+
+    fn text_index_get_postings(&self, term: &String) -> Result<HashMap<RepoId, u32>, StorageError> {
+        TextIndexStorage::new(&self.user_storage).get_postings(term)
+    }
+
+    fn text_index_save(
+        &self,
+        repo_id: &RepoId,
+        texts: &HashMap<String, Option<String>>,
+        deltas: &HashMap<String, i64>,
+    ) -> Result<(), StorageError> {
+        TextIndexStorage::new(&self.user_storage).save(repo_id, texts, deltas)
+    }
+
+    fn text_index_get_version(&self) -> Result<u8, StorageError> {
+        TextIndexStorage::new(&self.user_storage).get_version()
+    }
+
+    fn text_index_set_version(&self, version: u8) -> Result<(), StorageError> {
+        TextIndexStorage::new(&self.user_storage).set_version(version)
+    }
 
     fn branch_get_tab_info(
         &self,
         branch: &BranchId,
         repo: &RepoId,
         store: &StoreRepo,
+        user: &UserId,
     ) -> Result<AppTabInfo, StorageError> {
         let branch_info = BranchStorage::load(branch, &self.user_storage)?;
 
@@ -166,13 +313,27 @@ impl UserStorage for RocksDbUserStorage {
 
         let root_branch_info = BranchStorage::load(repo, &self.user_storage)?;
 
+        let repo_storage = RepoStorage::open(repo, &self.user_storage)?;
+        let members: Vec<AppTabDocMember> = repo_storage
+            .get_members()
+            .unwrap_or_default()
+            .values()
+            .map(|m| m.into())
+            .collect();
+        let authors = members
+            .iter()
+            .filter_map(|m| m.can_edit.then(|| m.id.clone()))
+            .collect();
+        let is_member = members.iter().any(|m| m.id == user.to_string());
+
         let doc_tab_info = AppTabDocInfo {
             nuri: Some(format!("o:{}", repo.to_string())),
             is_store: Some(store.repo_id() == repo),
-            is_member: Some(root_branch_info.read_cap.unwrap().readcap_nuri()), // TODO
-            authors: None,                                                      // TODO
-            inbox: None,                                                        // TODO
-            can_edit: Some(true),
+            is_member: is_member.then(|| root_branch_info.read_cap.unwrap().readcap_nuri()),
+            authors: Some(authors),
+            members: Some(members),
+            inbox: None, // TODO
+            can_edit: Some(repo_storage.get_write_cap().is_ok()),
             title: None,
             icon: None,
             description: None,
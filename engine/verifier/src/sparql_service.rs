@@ -0,0 +1,510 @@
+// Copyright (c) 2022-2025 Niko Bonnieure, Par le Peuple, NextGraph.org developers
+// All rights reserved.
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE2 or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Resolution of SPARQL SERVICE clauses targeting NextGraph documents (`SERVICE <did:ng:...>`)
+
+use std::collections::HashMap;
+
+use ng_oxigraph::oxigraph::sparql::{EvaluationError, Query, QueryResults, ServiceHandler};
+use ng_oxigraph::oxigraph::store::Store as GraphStore;
+use ng_oxigraph::oxrdf::NamedNode;
+use ng_oxigraph::spargebra;
+use ng_oxigraph::spargebra::algebra::{
+    AggregateExpression, Expression, GraphPattern, GraphTarget, OrderExpression,
+};
+use ng_oxigraph::spargebra::term::{GraphName, GraphNamePattern, NamedNodePattern};
+
+use ng_repo::errors::*;
+#[allow(unused_imports)]
+use ng_repo::log::*;
+use ng_repo::types::*;
+
+use ng_net::app_protocol::*;
+
+use crate::graph_export::ExportScope;
+use crate::verifier::*;
+
+/// Evaluates the SERVICE clauses of a query against the graphs of other documents of the user.
+///
+/// The service names are resolved before the evaluation starts, as the Verifier isn't available
+/// while the query is being evaluated.
+/// A value of None means the union of all the graphs of the user.
+pub(crate) struct NuriServiceHandler {
+    store: GraphStore,
+    graphs: HashMap<String, Option<String>>,
+}
+
+impl ServiceHandler for NuriServiceHandler {
+    type Error = EvaluationError;
+
+    fn handle(
+        &self,
+        service_name: NamedNode,
+        mut query: Query,
+    ) -> Result<QueryResults, Self::Error> {
+        match self.graphs.get(service_name.as_str()) {
+            None => Err(EvaluationError::UnsupportedService(service_name)),
+            Some(graph) => {
+                if graph.is_none() {
+                    query.dataset_mut().set_default_graph_as_union();
+                }
+                self.store.query(query, graph.clone())
+            }
+        }
+    }
+}
+
+/// The graphs that a query reads, besides the default graph it is evaluated on
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct QueryGraphs {
+    /// the `did:ng:` names of the SERVICE clauses
+    pub services: Vec<String>,
+    /// the names of the GRAPH patterns, and of the FROM and FROM NAMED clauses
+    pub graphs: Vec<String>,
+    /// a GRAPH pattern has a variable name, so it can range over all the named graphs of the user.
+    /// FROM NAMED isn't taken into account, as it doesn't apply inside the SERVICE clauses
+    pub any_graph: bool,
+}
+
+fn add_name(names: &mut Vec<String>, name: &str) {
+    if !names.iter().any(|n| n == name) {
+        names.push(name.to_string());
+    }
+}
+
+fn collect_graphs(pattern: &GraphPattern, graphs: &mut QueryGraphs) {
+    match pattern {
+        GraphPattern::Service { name, inner, .. } => {
+            if let NamedNodePattern::NamedNode(name) = name {
+                if name.as_str().starts_with("did:ng:") {
+                    add_name(&mut graphs.services, name.as_str());
+                }
+            }
+            collect_graphs(inner, graphs);
+        }
+        GraphPattern::Graph { name, inner } => {
+            match name {
+                NamedNodePattern::NamedNode(name) => add_name(&mut graphs.graphs, name.as_str()),
+                NamedNodePattern::Variable(_) => graphs.any_graph = true,
+            }
+            collect_graphs(inner, graphs);
+        }
+        GraphPattern::Join { left, right }
+        | GraphPattern::Lateral { left, right }
+        | GraphPattern::Union { left, right }
+        | GraphPattern::Minus { left, right } => {
+            collect_graphs(left, graphs);
+            collect_graphs(right, graphs);
+        }
+        GraphPattern::LeftJoin {
+            left,
+            right,
+            expression,
+        } => {
+            collect_graphs(left, graphs);
+            collect_graphs(right, graphs);
+            if let Some(expression) = expression {
+                collect_expression_graphs(expression, graphs);
+            }
+        }
+        GraphPattern::Filter { expr, inner }
+        | GraphPattern::Extend {
+            inner,
+            expression: expr,
+            ..
+        } => {
+            collect_expression_graphs(expr, graphs);
+            collect_graphs(inner, graphs);
+        }
+        GraphPattern::OrderBy { inner, expression } => {
+            for order in expression {
+                match order {
+                    OrderExpression::Asc(e) | OrderExpression::Desc(e) => {
+                        collect_expression_graphs(e, graphs)
+                    }
+                }
+            }
+            collect_graphs(inner, graphs);
+        }
+        GraphPattern::Group {
+            inner, aggregates, ..
+        } => {
+            for (_, aggregate) in aggregates {
+                if let AggregateExpression::FunctionCall { expr, .. } = aggregate {
+                    collect_expression_graphs(expr, graphs);
+                }
+            }
+            collect_graphs(inner, graphs);
+        }
+        GraphPattern::Project { inner, .. }
+        | GraphPattern::Distinct { inner }
+        | GraphPattern::Reduced { inner }
+        | GraphPattern::Slice { inner, .. } => collect_graphs(inner, graphs),
+        GraphPattern::Bgp { .. } | GraphPattern::Path { .. } | GraphPattern::Values { .. } => {}
+    }
+}
+
+/// Collects the graphs and services of the EXISTS and NOT EXISTS patterns of the expression
+fn collect_expression_graphs(expression: &Expression, graphs: &mut QueryGraphs) {
+    match expression {
+        Expression::Exists(pattern) => collect_graphs(pattern, graphs),
+        Expression::Or(a, b)
+        | Expression::And(a, b)
+        | Expression::Equal(a, b)
+        | Expression::SameTerm(a, b)
+        | Expression::Greater(a, b)
+        | Expression::GreaterOrEqual(a, b)
+        | Expression::Less(a, b)
+        | Expression::LessOrEqual(a, b)
+        | Expression::Add(a, b)
+        | Expression::Subtract(a, b)
+        | Expression::Multiply(a, b)
+        | Expression::Divide(a, b) => {
+            collect_expression_graphs(a, graphs);
+            collect_expression_graphs(b, graphs);
+        }
+        Expression::UnaryPlus(e) | Expression::UnaryMinus(e) | Expression::Not(e) => {
+            collect_expression_graphs(e, graphs)
+        }
+        Expression::In(e, list) => {
+            collect_expression_graphs(e, graphs);
+            for e in list {
+                collect_expression_graphs(e, graphs);
+            }
+        }
+        Expression::If(a, b, c) => {
+            collect_expression_graphs(a, graphs);
+            collect_expression_graphs(b, graphs);
+            collect_expression_graphs(c, graphs);
+        }
+        Expression::Coalesce(list) | Expression::FunctionCall(_, list) => {
+            for e in list {
+                collect_expression_graphs(e, graphs);
+            }
+        }
+        Expression::NamedNode(_)
+        | Expression::Literal(_)
+        | Expression::Variable(_)
+        | Expression::Bound(_) => {}
+    }
+}
+
+/// Returns the graphs and the services used by the query
+pub(crate) fn query_graphs(query: &spargebra::Query) -> QueryGraphs {
+    let mut graphs = QueryGraphs::default();
+    match query {
+        spargebra::Query::Select {
+            pattern, dataset, ..
+        }
+        | spargebra::Query::Construct {
+            pattern, dataset, ..
+        }
+        | spargebra::Query::Describe {
+            pattern, dataset, ..
+        }
+        | spargebra::Query::Ask {
+            pattern, dataset, ..
+        } => {
+            if let Some(dataset) = dataset {
+                for name in dataset.default.iter().chain(dataset.named.iter().flatten()) {
+                    add_name(&mut graphs.graphs, name.as_str());
+                }
+            }
+            collect_graphs(pattern, &mut graphs);
+        }
+    }
+    graphs
+}
+
+/// The graphs that a SPARQL update modifies, besides the default graph it is evaluated on,
+/// and the ones that its WHERE and USING clauses read
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct UpdateGraphs {
+    /// the names of the graphs of the quads, of the templates, and of the LOAD, CLEAR, CREATE and DROP operations.
+    /// `any_graph` is set when a template has a variable graph name, or when an operation targets all the named graphs.
+    /// LOAD also sets it, as it fetches a source outside of the user site
+    pub written: QueryGraphs,
+    pub read: QueryGraphs,
+}
+
+fn add_written_name(graphs: &mut QueryGraphs, graph_name: &GraphName) {
+    if let GraphName::NamedNode(name) = graph_name {
+        add_name(&mut graphs.graphs, name.as_str());
+    }
+}
+
+fn add_written_pattern(graphs: &mut QueryGraphs, graph_name: &GraphNamePattern) {
+    match graph_name {
+        GraphNamePattern::NamedNode(name) => add_name(&mut graphs.graphs, name.as_str()),
+        GraphNamePattern::Variable(_) => graphs.any_graph = true,
+        GraphNamePattern::DefaultGraph => {}
+    }
+}
+
+fn add_written_target(graphs: &mut QueryGraphs, target: &GraphTarget) {
+    match target {
+        GraphTarget::NamedNode(name) => add_name(&mut graphs.graphs, name.as_str()),
+        GraphTarget::NamedGraphs | GraphTarget::AllGraphs => graphs.any_graph = true,
+        GraphTarget::DefaultGraph => {}
+    }
+}
+
+/// Returns the graphs written and read by the operations of the update
+pub(crate) fn update_graphs(update: &spargebra::Update) -> UpdateGraphs {
+    let mut graphs = UpdateGraphs::default();
+    for operation in &update.operations {
+        match operation {
+            spargebra::GraphUpdateOperation::InsertData { data } => {
+                for quad in data {
+                    add_written_name(&mut graphs.written, &quad.graph_name);
+                }
+            }
+            spargebra::GraphUpdateOperation::DeleteData { data } => {
+                for quad in data {
+                    add_written_name(&mut graphs.written, &quad.graph_name);
+                }
+            }
+            spargebra::GraphUpdateOperation::DeleteInsert {
+                delete,
+                insert,
+                using,
+                pattern,
+            } => {
+                for quad in delete {
+                    add_written_pattern(&mut graphs.written, &quad.graph_name);
+                }
+                for quad in insert {
+                    add_written_pattern(&mut graphs.written, &quad.graph_name);
+                }
+                if let Some(using) = using {
+                    for name in using.default.iter().chain(using.named.iter().flatten()) {
+                        add_name(&mut graphs.read.graphs, name.as_str());
+                    }
+                }
+                collect_graphs(pattern, &mut graphs.read);
+            }
+            spargebra::GraphUpdateOperation::Load { destination, .. } => {
+                graphs.written.any_graph = true;
+                add_written_name(&mut graphs.written, destination);
+            }
+            spargebra::GraphUpdateOperation::Clear { graph, .. }
+            | spargebra::GraphUpdateOperation::Drop { graph, .. } => {
+                add_written_target(&mut graphs.written, graph)
+            }
+            spargebra::GraphUpdateOperation::Create { graph, .. } => {
+                add_name(&mut graphs.written.graphs, graph.as_str())
+            }
+        }
+    }
+    graphs
+}
+
+pub(crate) fn parse_sparql_update(
+    sparql: &str,
+    base: Option<&str>,
+) -> Result<spargebra::Update, VerifierError> {
+    spargebra::Update::parse(sparql, base).map_err(|e| VerifierError::SparqlError(e.to_string()))
+}
+
+/// Returns the `did:ng:` names used in the SERVICE clauses of the query
+fn service_names(query: &spargebra::Query) -> Vec<String> {
+    query_graphs(query).services
+}
+
+pub(crate) fn parse_sparql_query(
+    sparql: &str,
+    base: Option<&str>,
+) -> Result<spargebra::Query, VerifierError> {
+    spargebra::Query::parse(sparql, base).map_err(|e| VerifierError::SparqlError(e.to_string()))
+}
+
+impl Verifier {
+    fn service_graph(&self, nuri: &NuriV0) -> Result<Option<String>, VerifierError> {
+        match self.resolve_export_scope(nuri)? {
+            ExportScope::UserSite => Ok(None),
+            ExportScope::Graph(graph) | ExportScope::View(graph) => Ok(Some(graph)),
+            ExportScope::Graphs(_) => Err(VerifierError::NotImplemented),
+        }
+    }
+
+    /// Resolves the SERVICE names of the query to the graphs of the documents that are loaded.
+    ///
+    /// A query never loads a document: a SERVICE that targets a document that isn't open in the verifier
+    /// is an error. The access of an app to the documents of the SERVICE clauses is checked with the request.
+    pub(crate) fn sparql_service_handler(
+        &self,
+        query: &spargebra::Query,
+    ) -> Result<NuriServiceHandler, VerifierError> {
+        let mut graphs = HashMap::new();
+        for name in service_names(query) {
+            let graph = NuriV0::new_from(&name)
+                .map_err(VerifierError::from)
+                .and_then(|nuri| self.service_graph(&nuri))
+                .map_err(|e| {
+                    VerifierError::SparqlError(format!(
+                        "SERVICE <{name}> is not a document that is open: {e}"
+                    ))
+                })?;
+            graphs.insert(name, graph);
+        }
+        Ok(NuriServiceHandler {
+            store: self.graph_dataset.as_ref().unwrap().clone(),
+            graphs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ng_oxigraph::oxigraph::storage_ng::REPO_IN_MAIN;
+    use ng_oxigraph::oxigraph::store::StorageError;
+    use ng_oxigraph::oxrdf::{GraphNameRef, LiteralRef, NamedNodeRef, QuadRef};
+
+    fn names(sparql: &str) -> Vec<String> {
+        service_names(&parse_sparql_query(sparql, None).unwrap())
+    }
+
+    #[test]
+    pub fn test_service_names() {
+        assert_eq!(
+            names("SELECT * WHERE { SERVICE <did:ng:o:a> { ?s ?p ?o } SERVICE <https://example.com/sparql> { ?s ?p ?o } }"),
+            vec!["did:ng:o:a"]
+        );
+        // duplicates are removed, and variables are ignored
+        assert_eq!(
+            names("SELECT * WHERE { SERVICE <did:ng:o:a> { ?s ?p ?o } SERVICE ?x { ?s ?p ?o } SERVICE <did:ng:o:a> { ?o ?p ?s } }"),
+            vec!["did:ng:o:a"]
+        );
+        assert_eq!(
+            names("SELECT * WHERE { ?s ?p ?o OPTIONAL { SERVICE <did:ng:o:a> { ?s ?p ?x } } MINUS { SERVICE <did:ng:o:b> { ?s ?p ?o } } }"),
+            vec!["did:ng:o:a", "did:ng:o:b"]
+        );
+        assert_eq!(
+            names("SELECT * WHERE { ?s ?p ?o LATERAL { SERVICE <did:ng:o:a> { ?s ?p ?x } } VALUES ?s { <did:ng:o:c> } }"),
+            vec!["did:ng:o:a"]
+        );
+        assert_eq!(
+            names("SELECT * WHERE { { SELECT ?s WHERE { SERVICE <did:ng:o:a> { ?s ?p ?o } } } GRAPH ?g { SERVICE <did:ng:o:b> { ?s ?p ?o } } }"),
+            vec!["did:ng:o:a", "did:ng:o:b"]
+        );
+    }
+
+    #[test]
+    pub fn test_service_names_in_expressions() {
+        assert_eq!(
+            names("SELECT * WHERE { ?s ?p ?o FILTER (?o > 1 && NOT EXISTS { SERVICE <did:ng:o:a> { ?s ?p ?x } }) }"),
+            vec!["did:ng:o:a"]
+        );
+        assert_eq!(
+            names("SELECT * WHERE { ?s ?p ?o OPTIONAL { ?s ?q ?v FILTER EXISTS { SERVICE <did:ng:o:a> { ?v ?p ?x } } } }"),
+            vec!["did:ng:o:a"]
+        );
+        assert_eq!(
+            names("SELECT * WHERE { ?s ?p ?o BIND (IF(EXISTS { SERVICE <did:ng:o:a> { ?s ?p ?x } }, 1, 0) AS ?b) }"),
+            vec!["did:ng:o:a"]
+        );
+        assert_eq!(
+            names("SELECT ?s (COUNT(?o) AS ?c) WHERE { ?s ?p ?o } GROUP BY ?s HAVING (SUM(IF(EXISTS { SERVICE <did:ng:o:a> { ?s ?p ?x } }, 1, 0)) > 0) ORDER BY DESC(EXISTS { SERVICE <did:ng:o:b> { ?s ?p ?x } })"),
+            vec!["did:ng:o:b", "did:ng:o:a"]
+        );
+        assert_eq!(
+            names("ASK { ?s ?p ?o FILTER (?o IN (1, COALESCE(?x, EXISTS { SERVICE <did:ng:o:a> { ?s ?p ?x } }))) }"),
+            vec!["did:ng:o:a"]
+        );
+    }
+
+    #[test]
+    pub fn test_query_graphs() {
+        let graphs = |sparql: &str| query_graphs(&parse_sparql_query(sparql, None).unwrap());
+        assert_eq!(
+            graphs("SELECT * WHERE { ?s ?p ?o }"),
+            QueryGraphs::default()
+        );
+        assert!(graphs("SELECT * WHERE { GRAPH ?g { ?s ?p ?o } }").any_graph);
+        // the GRAPH patterns inside a SERVICE are found too
+        let inner = graphs("SELECT * WHERE { SERVICE <did:ng:o:a> { GRAPH ?g { ?s ?p ?o } } }");
+        assert!(inner.any_graph);
+        assert_eq!(inner.services, vec!["did:ng:o:a"]);
+        assert_eq!(
+            graphs("SELECT * FROM <did:ng:o:a> FROM NAMED <did:ng:o:b> WHERE { GRAPH <did:ng:o:c> { ?s ?p ?o } FILTER EXISTS { GRAPH <did:ng:o:a> { ?s ?p ?x } } }"),
+            QueryGraphs {
+                services: vec![],
+                graphs: vec![
+                    "did:ng:o:a".to_string(),
+                    "did:ng:o:b".to_string(),
+                    "did:ng:o:c".to_string()
+                ],
+                any_graph: false,
+            }
+        );
+    }
+
+    #[test]
+    pub fn test_service_handler() {
+        let store = GraphStore::new().unwrap();
+        let graph =
+            NuriV0::repo_graph_name(&PubKey::Ed25519PubKey([1; 32]), &OverlayId::Outer([2; 32]));
+        store
+            .ng_transaction(|mut transaction| {
+                transaction.insert(
+                    QuadRef::new(
+                        NamedNodeRef::new_unchecked("did:ng:x:s"),
+                        NamedNodeRef::new_unchecked("did:ng:x:p"),
+                        LiteralRef::new_simple_literal("in g"),
+                        GraphNameRef::NamedNode(NamedNodeRef::new_unchecked(&graph)),
+                    ),
+                    REPO_IN_MAIN,
+                    false,
+                )?;
+                Ok::<_, StorageError>(())
+            })
+            .unwrap();
+        let handler = NuriServiceHandler {
+            store,
+            graphs: HashMap::from([
+                ("did:ng:o:a".to_string(), Some(graph.clone())),
+                ("did:ng:o:b".to_string(), None),
+            ]),
+        };
+        let count = |name: &str| match handler.handle(
+            NamedNode::new_unchecked(name),
+            Query::parse("SELECT * WHERE { ?s ?p ?o }", None).unwrap(),
+        ) {
+            Ok(QueryResults::Solutions(solutions)) => Ok(solutions.count()),
+            Ok(_) => panic!("expected solutions"),
+            Err(e) => Err(e),
+        };
+        assert_eq!(count("did:ng:o:a").unwrap(), 1);
+        // the union of all the graphs
+        assert_eq!(count("did:ng:o:b").unwrap(), 1);
+        assert!(matches!(
+            count("did:ng:o:c"),
+            Err(EvaluationError::UnsupportedService(_))
+        ));
+    }
+
+    #[test]
+    pub fn test_service_not_open() {
+        let verifier = Verifier::new_dummy();
+        let graph =
+            NuriV0::repo_graph_name(&PubKey::Ed25519PubKey([1; 32]), &OverlayId::Outer([2; 32]));
+        let query = parse_sparql_query(
+            &format!("SELECT * WHERE {{ SERVICE <{graph}> {{ ?s ?p ?o }} }}"),
+            None,
+        )
+        .unwrap();
+        // the document is not loaded by the query
+        assert!(matches!(
+            verifier.sparql_service_handler(&query),
+            Err(VerifierError::SparqlError(_))
+        ));
+        assert!(verifier.repos.is_empty());
+    }
+}
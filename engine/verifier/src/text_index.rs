@@ -0,0 +1,644 @@
+// Copyright (c) 2022-2025 Niko Bonnieure, Par le Peuple, NextGraph.org developers
+// All rights reserved.
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE2 or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Full-text index of the string literals and rich-text content of documents
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use ng_oxigraph::oxrdf::vocab::xsd;
+use ng_oxigraph::oxrdf::{GraphNameRef, Literal, NamedNodeRef, Term, Triple};
+use yrs::updates::decoder::Decode;
+use yrs::{GetString, Transact};
+
+use ng_repo::errors::*;
+#[allow(unused_imports)]
+use ng_repo::log::*;
+use ng_repo::types::*;
+
+use ng_net::app_protocol::*;
+
+use crate::orm::discrete::yrs_orm::YXML_ROOT;
+use crate::types::GraphTransaction;
+use crate::user_storage::UserStorage;
+use crate::verifier::*;
+
+/// SPARQL extension function `<did:ng:x:ng#text_score>(?graph, "some words")`.
+/// Returns the score of the document graph for the words, or an unbound value if it doesn't contain all of them.
+pub const NG_TEXT_SCORE: &str = "did:ng:x:ng#text_score";
+
+const DEFAULT_SEARCH_LIMIT: usize = 50;
+
+/// Version of the text index. When the stored version is older, all the documents are indexed again when the verifier is loaded.
+const TEXT_INDEX_VERSION: u8 = 2;
+
+/// Longer words are not indexed
+const MAX_TERM_LEN: usize = 64;
+
+/// Number of characters kept on each side of the matching word in a snippet
+const SNIPPET_CONTEXT: usize = 60;
+
+/// Splits a text into lowercase words
+pub(crate) fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().nth(1).is_some() && word.len() <= MAX_TERM_LEN)
+        .map(|word| word.to_lowercase())
+}
+
+fn is_text_literal(literal: &Literal) -> bool {
+    literal.language().is_some() || literal.datatype() == xsd::STRING
+}
+
+/// The text of the triple, if its object is a string literal
+fn triple_text(triple: &Triple) -> Option<String> {
+    match &triple.object {
+        Term::Literal(l) if is_text_literal(l) => Some(l.value().to_string()),
+        _ => None,
+    }
+}
+
+/// Removes the markup of an XML serialization, keeping only the text
+fn strip_markup(xml: &str) -> String {
+    let mut text = String::with_capacity(xml.len());
+    let mut in_tag = false;
+    for c in xml.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Returns the text of a YText or YXml state, or None for the other CRDTs
+fn discrete_text(crdt: &BranchCrdt, state: &[u8]) -> Option<String> {
+    if !matches!(crdt, BranchCrdt::YText(_) | BranchCrdt::YXml(_)) {
+        return None;
+    }
+    let doc = yrs::Doc::new();
+    {
+        let mut txn = doc.transact_mut();
+        txn.apply_update(yrs::Update::decode_v1(state).ok()?);
+        txn.commit();
+    }
+    if let BranchCrdt::YText(_) = crdt {
+        let root = doc.get_or_insert_text("ng");
+        let txn = doc.transact();
+        Some(root.get_string(&txn))
+    } else {
+        let root = doc.get_or_insert_xml_fragment(YXML_ROOT);
+        let txn = doc.transact();
+        Some(strip_markup(&root.get_string(&txn)))
+    }
+}
+
+/// Extracts the text around the first word that is one of the terms
+fn snippet(text: &str, terms: &HashSet<String>) -> Option<String> {
+    let mut word_start = None;
+    for (pos, c) in text
+        .char_indices()
+        .chain(std::iter::once((text.len(), ' ')))
+    {
+        if c.is_alphanumeric() {
+            word_start.get_or_insert(pos);
+        } else if let Some(start) = word_start.take() {
+            if !terms.contains(&text[start..pos].to_lowercase()) {
+                continue;
+            }
+            let from = text[..start]
+                .char_indices()
+                .rev()
+                .nth(SNIPPET_CONTEXT - 1)
+                .map_or(0, |(p, _)| p);
+            let to = text[pos..]
+                .char_indices()
+                .nth(SNIPPET_CONTEXT)
+                .map_or(text.len(), |(p, _)| pos + p);
+            let mut snippet = String::with_capacity(to - from + 6);
+            if from > 0 {
+                snippet.push('…');
+            }
+            snippet.push_str(text[from..to].trim());
+            if to < text.len() {
+                snippet.push('…');
+            }
+            return Some(snippet.split_whitespace().collect::<Vec<&str>>().join(" "));
+        }
+    }
+    None
+}
+
+/// Scores the documents that contain all the terms, with a tf-idf sum over the terms
+fn rank(
+    storage: &Arc<Box<dyn UserStorage>>,
+    terms: &HashSet<String>,
+    total_docs: usize,
+) -> Result<HashMap<RepoId, f64>, StorageError> {
+    let mut scores: Option<HashMap<RepoId, f64>> = None;
+    for term in terms {
+        let postings = storage.text_index_get_postings(term)?;
+        let idf = (1.0 + total_docs as f64 / (postings.len() as f64).max(1.0)).ln();
+        scores = Some(
+            postings
+                .into_iter()
+                .filter_map(|(repo_id, tf)| {
+                    let previous = match scores.as_ref() {
+                        None => 0.0,
+                        Some(scores) => *scores.get(&repo_id)?,
+                    };
+                    Some((repo_id, previous + (1.0 + (tf as f64).ln()) * idf))
+                })
+                .collect(),
+        );
+    }
+    Ok(scores.unwrap_or_default())
+}
+
+impl Verifier {
+    /// Applies changes to the texts of a document (None removes the text of a source), and updates the postings of the terms.
+    /// Only the texts of the changed sources are read and tokenized.
+    fn text_index_update(
+        &self,
+        repo_id: &RepoId,
+        changes: Vec<(String, Option<String>)>,
+    ) -> Result<(), VerifierError> {
+        let storage = match self.user_storage() {
+            Some(storage) => storage,
+            None => return Ok(()),
+        };
+        let mut texts: HashMap<String, Option<String>> = HashMap::new();
+        let mut deltas: HashMap<String, i64> = HashMap::new();
+        for (source, text) in changes {
+            let text = text.filter(|text| !text.is_empty());
+            let previous = match texts.get(&source) {
+                Some(previous) => previous.clone(),
+                None => storage.text_index_get_text(repo_id, &source)?,
+            };
+            if previous == text {
+                continue;
+            }
+            for term in tokenize(previous.as_deref().unwrap_or_default()) {
+                *deltas.entry(term).or_insert(0) -= 1;
+            }
+            for term in tokenize(text.as_deref().unwrap_or_default()) {
+                *deltas.entry(term).or_insert(0) += 1;
+            }
+            texts.insert(source, text);
+        }
+        if texts.is_empty() {
+            return Ok(());
+        }
+        deltas.retain(|_, delta| *delta != 0);
+        storage.text_index_save(repo_id, &texts, &deltas)?;
+        Ok(())
+    }
+
+    /// Indexes the documents that were saved before the text index existed, or with an older version of it.
+    /// The documents already indexed are left unchanged, as only the sources that differ are updated.
+    pub(crate) fn text_index_backfill(&self) -> Result<(), VerifierError> {
+        let storage = match self.user_storage() {
+            Some(storage) => storage,
+            None => return Ok(()),
+        };
+        if storage.text_index_get_version()? >= TEXT_INDEX_VERSION {
+            return Ok(());
+        }
+        log_info!("indexing the text of {} repos", self.repos.len());
+        for repo in self.repos.values() {
+            let mut changes = vec![];
+            if let Some(graph_dataset) = self.graph_dataset.as_ref() {
+                let overlay_id = repo.store.overlay_id;
+                let mut graph_names = vec![NuriV0::repo_graph_name(&repo.id, &overlay_id)];
+                if let Some(header) = repo.header_branch() {
+                    graph_names.push(NuriV0::branch_repo_graph_name(
+                        &header.id,
+                        &repo.id,
+                        &overlay_id,
+                    ));
+                }
+                for graph_name in graph_names {
+                    for quad in graph_dataset.quads_for_pattern(
+                        None,
+                        None,
+                        None,
+                        Some(GraphNameRef::NamedNode(NamedNodeRef::new_unchecked(
+                            &graph_name,
+                        ))),
+                    ) {
+                        let triple = Triple::from(
+                            quad.map_err(|e| VerifierError::OxigraphError(e.to_string()))?,
+                        );
+                        if let Some(text) = triple_text(&triple) {
+                            changes.push((triple.to_string(), Some(text)));
+                        }
+                    }
+                }
+            }
+            for branch in repo.branches.values() {
+                match storage.branch_get_discrete_state(&branch.id) {
+                    Ok(state) => {
+                        if let Some(text) = discrete_text(&branch.crdt, &state) {
+                            changes.push((format!("b:{}", branch.id), Some(text)));
+                        }
+                    }
+                    Err(StorageError::NoDiscreteState) => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            if !changes.is_empty() {
+                self.text_index_update(&repo.id, changes)?;
+            }
+        }
+        storage.text_index_set_version(TEXT_INDEX_VERSION)?;
+        Ok(())
+    }
+
+    /// Indexes the string literals inserted and removed in the main or header branch of a document
+    pub(crate) fn text_index_graph_transaction(
+        &self,
+        repo_id: &RepoId,
+        transaction: &GraphTransaction,
+    ) {
+        let mut changes = vec![];
+        for triple in transaction.removes.iter() {
+            if triple_text(triple).is_some() {
+                changes.push((triple.to_string(), None));
+            }
+        }
+        for triple in transaction.inserts.iter() {
+            if let Some(text) = triple_text(triple) {
+                changes.push((triple.to_string(), Some(text)));
+            }
+        }
+        if changes.is_empty() {
+            return;
+        }
+        if let Err(e) = self.text_index_update(repo_id, changes) {
+            log_err!("cannot update the text index of {repo_id}: {e}");
+        }
+    }
+
+    /// Indexes the text of a YText or YXml branch, from its new state
+    pub(crate) fn text_index_discrete_state(
+        &self,
+        repo_id: &RepoId,
+        branch_id: &BranchId,
+        crdt: &BranchCrdt,
+        state: &[u8],
+    ) {
+        let text = match discrete_text(crdt, state) {
+            Some(text) => text,
+            None => return,
+        };
+        if let Err(e) =
+            self.text_index_update(repo_id, vec![(format!("b:{branch_id}"), Some(text))])
+        {
+            log_err!("cannot update the text index of {repo_id}: {e}");
+        }
+    }
+
+    /// Searches the documents containing all the words of the query.
+    ///
+    /// The nuri restricts the search to a store, unless it targets the entire user site.
+    pub(crate) fn text_search(
+        &self,
+        nuri: &NuriV0,
+        query: &str,
+        limit: Option<u32>,
+    ) -> Result<Vec<TextSearchResult>, VerifierError> {
+        let storage = self.user_storage().ok_or(VerifierError::NotImplemented)?;
+        let store_repo = match nuri.target {
+            NuriTargetV0::UserSite | NuriTargetV0::None => None,
+            _ => Some(self.resolve_target(&nuri.target)?.2),
+        };
+        let terms: HashSet<String> = tokenize(query).collect();
+        if terms.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut ranked: Vec<(RepoId, f64)> = rank(&storage, &terms, self.repos.len())?
+            .into_iter()
+            .filter(|(repo_id, _)| match self.repos.get(repo_id) {
+                None => false,
+                Some(repo) => store_repo
+                    .as_ref()
+                    .map_or(true, |store_repo| repo.store.get_store_repo() == store_repo),
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+        ranked.truncate(limit.map_or(DEFAULT_SEARCH_LIMIT, |l| l as usize));
+
+        ranked
+            .into_iter()
+            .map(|(repo_id, score)| {
+                let document = storage.text_index_get_document(&repo_id)?;
+                let mut sources: Vec<&String> = document.keys().collect();
+                sources.sort();
+                let snippet = sources
+                    .into_iter()
+                    .find_map(|source| snippet(&document[source], &terms))
+                    .unwrap_or_default();
+                let overlay_id = self.repos.get(&repo_id).unwrap().store.overlay_id;
+                Ok(TextSearchResult {
+                    nuri: NuriV0::repo_graph_name(&repo_id, &overlay_id),
+                    score,
+                    snippet,
+                })
+            })
+            .collect()
+    }
+
+    /// The evaluator of the NG_TEXT_SCORE SPARQL function.
+    /// The scores of each distinct query string are computed once per SPARQL query.
+    pub(crate) fn text_score_function(
+        &self,
+    ) -> Option<impl Fn(&[Term]) -> Option<Term> + Send + Sync + 'static> {
+        let storage = self.user_storage()?;
+        let total_docs = self.repos.len();
+        let cache: Mutex<HashMap<String, HashMap<RepoId, f64>>> = Mutex::new(HashMap::new());
+        Some(move |args: &[Term]| {
+            let (graph, words) = match args {
+                [Term::NamedNode(graph), Term::Literal(words)] => (graph, words.value()),
+                _ => return None,
+            };
+            let repo_id = match NuriV0::new_from(&graph.as_str().to_string()).ok()?.target {
+                NuriTargetV0::Repo(repo_id) => repo_id,
+                _ => return None,
+            };
+            let mut cache = cache.lock().unwrap();
+            if !cache.contains_key(words) {
+                let terms = tokenize(words).collect();
+                let scores = rank(&storage, &terms, total_docs).ok()?;
+                cache.insert(words.to_string(), scores);
+            }
+            let score = *cache.get(words)?.get(&repo_id)?;
+            Some(Literal::from(score).into())
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ng_oxigraph::oxigraph::storage_ng::REPO_IN_MAIN;
+    use ng_oxigraph::oxigraph::store::{StorageError as GraphStorageError, Store as GraphStore};
+    use ng_oxigraph::oxrdf::{LiteralRef, NamedNode, QuadRef};
+    use ng_repo::repo::Repo;
+    use ng_repo::store::Store;
+
+    use crate::user_storage::InMemoryUserStorage;
+
+    fn verifier_with_repos(count: u8) -> (Verifier, Vec<RepoId>, OverlayId) {
+        let mut verifier = Verifier::new_dummy();
+        verifier.user_storage = Some(Arc::new(
+            Box::new(InMemoryUserStorage::new()) as Box<dyn UserStorage>
+        ));
+        let store = Store::dummy_public_v0();
+        let overlay_id = store.overlay_id;
+        let repo_ids: Vec<RepoId> = (1..=count)
+            .map(|i| PubKey::Ed25519PubKey([i; 32]))
+            .collect();
+        for repo_id in repo_ids.iter() {
+            let repo = Repo::new_with_member(repo_id, repo_id, &[], Arc::clone(&store));
+            verifier.repos.insert(*repo_id, repo);
+        }
+        (verifier, repo_ids, overlay_id)
+    }
+
+    fn texts(changes: &[(&str, Option<&str>)]) -> Vec<(String, Option<String>)> {
+        changes
+            .iter()
+            .map(|(source, text)| (source.to_string(), text.map(|t| t.to_string())))
+            .collect()
+    }
+
+    #[test]
+    pub fn test_text_search() {
+        let (verifier, repos, overlay_id) = verifier_with_repos(3);
+        let (a, b, c) = (repos[0], repos[1], repos[2]);
+        verifier
+            .text_index_update(
+                &a,
+                texts(&[
+                    ("s1", Some("The quick brown fox")),
+                    ("s2", Some("jumps over the lazy dog")),
+                ]),
+            )
+            .unwrap();
+        verifier
+            .text_index_update(
+                &b,
+                texts(&[("s1", Some("A lazy afternoon, lazy and quiet"))]),
+            )
+            .unwrap();
+        verifier
+            .text_index_update(&c, texts(&[("s1", Some("nothing relevant"))]))
+            .unwrap();
+
+        let user_site = NuriV0::new_entire_user_site();
+        let results = verifier.text_search(&user_site, "Lazy", None).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].nuri, NuriV0::repo_graph_name(&b, &overlay_id));
+        assert_eq!(results[1].nuri, NuriV0::repo_graph_name(&a, &overlay_id));
+        assert!(results[0].score > results[1].score);
+        assert_eq!(results[1].snippet, "jumps over the lazy dog");
+
+        // all the words must be found in the document
+        let results = verifier.text_search(&user_site, "lazy fox", None).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].nuri, NuriV0::repo_graph_name(&a, &overlay_id));
+
+        assert_eq!(
+            verifier
+                .text_search(&user_site, "lazy", Some(1))
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(verifier
+            .text_search(&user_site, "", None)
+            .unwrap()
+            .is_empty());
+        assert!(verifier
+            .text_search(&user_site, "a", None)
+            .unwrap()
+            .is_empty());
+        assert!(verifier
+            .text_search(&user_site, "cat", None)
+            .unwrap()
+            .is_empty());
+
+        // removing a source only updates the postings of its own terms
+        let storage = verifier.user_storage().unwrap();
+        assert_eq!(
+            storage.text_index_get_postings(&"the".to_string()).unwrap()[&a],
+            2
+        );
+        verifier
+            .text_index_update(&a, texts(&[("s2", None)]))
+            .unwrap();
+        assert_eq!(
+            storage.text_index_get_postings(&"the".to_string()).unwrap()[&a],
+            1
+        );
+        let results = verifier.text_search(&user_site, "lazy", None).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].nuri, NuriV0::repo_graph_name(&b, &overlay_id));
+
+        // removing and inserting the same source in one update leaves the postings unchanged
+        verifier
+            .text_index_update(
+                &b,
+                texts(&[
+                    ("s1", None),
+                    ("s1", Some("A lazy afternoon, lazy and quiet")),
+                ]),
+            )
+            .unwrap();
+        assert_eq!(
+            storage
+                .text_index_get_postings(&"lazy".to_string())
+                .unwrap()[&b],
+            2
+        );
+
+        // replacing a text
+        verifier
+            .text_index_update(&a, texts(&[("s1", Some("a slow brown turtle"))]))
+            .unwrap();
+        assert!(verifier
+            .text_search(&user_site, "fox", None)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            verifier
+                .text_search(&user_site, "turtle", None)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(!storage
+            .text_index_get_postings(&"the".to_string())
+            .unwrap()
+            .contains_key(&a));
+
+        // an empty text removes the source, and the document when it was the last one
+        verifier
+            .text_index_update(&a, texts(&[("s1", Some(""))]))
+            .unwrap();
+        assert!(storage.text_index_get_document(&a).unwrap().is_empty());
+        assert!(storage
+            .text_index_get_postings(&"brown".to_string())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    pub fn test_text_score_function() {
+        let (verifier, repos, overlay_id) = verifier_with_repos(3);
+        let (a, b, c) = (repos[0], repos[1], repos[2]);
+        verifier
+            .text_index_update(&a, texts(&[("s1", Some("one lazy dog"))]))
+            .unwrap();
+        verifier
+            .text_index_update(&b, texts(&[("s1", Some("lazy lazy lazy cat"))]))
+            .unwrap();
+
+        let text_score = verifier.text_score_function().unwrap();
+        let score = |repo_id: &RepoId, words: &str| {
+            let graph = NamedNode::new_unchecked(NuriV0::repo_graph_name(repo_id, &overlay_id));
+            text_score(&[graph.into(), Literal::new_simple_literal(words).into()]).map(|term| {
+                match term {
+                    Term::Literal(l) => l.value().parse::<f64>().unwrap(),
+                    _ => panic!("the score should be a literal"),
+                }
+            })
+        };
+        assert!(score(&b, "lazy").unwrap() > score(&a, "lazy").unwrap());
+        assert!(score(&a, "lazy dog").is_some());
+        assert!(score(&b, "lazy dog").is_none());
+        assert!(score(&c, "lazy").is_none());
+
+        // invalid arguments give an unbound value
+        assert!(text_score(&[Literal::new_simple_literal("lazy").into()]).is_none());
+        assert!(text_score(&[
+            NamedNode::new_unchecked("did:ng:x:not_a_graph").into(),
+            Literal::new_simple_literal("lazy").into()
+        ])
+        .is_none());
+    }
+
+    #[test]
+    pub fn test_text_index_backfill() {
+        let (mut verifier, repos, overlay_id) = verifier_with_repos(1);
+        let graph_dataset = GraphStore::new().unwrap();
+        let graph_name = NuriV0::repo_graph_name(&repos[0], &overlay_id);
+        let insert = |text: &str| {
+            graph_dataset
+                .ng_transaction(|mut transaction| {
+                    transaction.insert(
+                        QuadRef::new(
+                            NamedNodeRef::new_unchecked("did:ng:x:s"),
+                            NamedNodeRef::new_unchecked("did:ng:x:p"),
+                            LiteralRef::new_simple_literal(text),
+                            GraphNameRef::NamedNode(NamedNodeRef::new_unchecked(&graph_name)),
+                        ),
+                        REPO_IN_MAIN,
+                        false,
+                    )?;
+                    Ok::<_, GraphStorageError>(())
+                })
+                .unwrap()
+        };
+        insert("written before the index existed");
+        verifier.graph_dataset = Some(graph_dataset.clone());
+
+        let user_site = NuriV0::new_entire_user_site();
+        assert!(verifier
+            .text_search(&user_site, "index", None)
+            .unwrap()
+            .is_empty());
+        verifier.text_index_backfill().unwrap();
+        let results = verifier.text_search(&user_site, "index", None).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].snippet, "written before the index existed");
+
+        // the index is up to date, so it is not rebuilt
+        insert("another text");
+        verifier.text_index_backfill().unwrap();
+        assert!(verifier
+            .text_search(&user_site, "another", None)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    pub fn test_tokenize_and_snippet() {
+        let terms: Vec<String> = tokenize("L'été, à Paris: a NextGraph demo!").collect();
+        assert_eq!(terms, vec!["été", "paris", "nextgraph", "demo"]);
+
+        let terms = HashSet::from(["nextgraph".to_string()]);
+        assert_eq!(
+            snippet("Hello <b>NextGraph</b>", &terms),
+            Some("Hello <b>NextGraph</b>".to_string())
+        );
+        assert_eq!(snippet("nothing here", &terms), None);
+        assert_eq!(strip_markup("<p>a &amp; b</p>"), " a & b ");
+    }
+}
@@ -49,6 +49,30 @@ pub struct GraphQuadsPatch {
 
 const TOKENIZED_COMMIT: &str = "did:ng:_";
 
+fn tokenize_named_node(nn: &mut NamedNode, commit_id: &ObjectId, repo_id: &RepoId) {
+    if nn.as_str().starts_with(TOKENIZED_COMMIT) {
+        let mut str = nn.as_string().clone();
+        let new_iri = NuriV0::tokenized_commit(repo_id, commit_id);
+        str.replace_range(..8, &new_iri);
+        *nn = NamedNode::new_unchecked(str);
+    }
+}
+
+/// Replaces the tokenized commit IRIs of the triple, including inside quoted triples (RDF-STAR)
+fn tokenize_triple(triple: &mut Triple, commit_id: &ObjectId, repo_id: &RepoId) {
+    match &mut triple.subject {
+        Subject::NamedNode(nn) => tokenize_named_node(nn, commit_id, repo_id),
+        Subject::Triple(quoted) => tokenize_triple(quoted, commit_id, repo_id),
+        Subject::BlankNode(_) => {}
+    }
+    tokenize_named_node(&mut triple.predicate, commit_id, repo_id);
+    match &mut triple.object {
+        Term::NamedNode(nn) => tokenize_named_node(nn, commit_id, repo_id),
+        Term::Triple(quoted) => tokenize_triple(quoted, commit_id, repo_id),
+        Term::BlankNode(_) | Term::Literal(_) => {}
+    }
+}
+
 impl GraphTransaction {
     pub(crate) fn as_patch(&self) -> GraphPatch {
         GraphPatch {
@@ -80,28 +104,7 @@ impl GraphTransaction {
     }
     pub(crate) fn tokenize_with_commit_id(&mut self, commit_id: ObjectId, repo_id: &RepoId) {
         for triple in self.inserts.iter_mut() {
-            if let Subject::NamedNode(nn) = &triple.subject {
-                if nn.as_str().starts_with(TOKENIZED_COMMIT) {
-                    let mut str = nn.as_string().clone();
-                    let new_iri = NuriV0::tokenized_commit(repo_id, &commit_id);
-                    str.replace_range(..8, &new_iri);
-                    triple.subject = NamedNode::new_unchecked(str).into();
-                }
-            }
-            if triple.predicate.as_str().starts_with(TOKENIZED_COMMIT) {
-                let mut str = triple.predicate.as_string().clone();
-                let new_iri = NuriV0::tokenized_commit(repo_id, &commit_id);
-                str.replace_range(..8, &new_iri);
-                triple.predicate = NamedNode::new_unchecked(str);
-            }
-            if let Term::NamedNode(nn) = &triple.object {
-                if nn.as_str().starts_with(TOKENIZED_COMMIT) {
-                    let mut str = nn.as_string().clone();
-                    let new_iri = NuriV0::tokenized_commit(repo_id, &commit_id);
-                    str.replace_range(..8, &new_iri);
-                    triple.object = NamedNode::new_unchecked(str).into();
-                }
-            }
+            tokenize_triple(triple, &commit_id, repo_id);
         }
     }
 }
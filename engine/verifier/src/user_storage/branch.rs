@@ -279,6 +279,72 @@ impl<'a> BranchStorage<'a> {
         Ok(res)
     }
 
+    /// removes all the entries of the branch that reference the file. Returns the removed FileName, if any
+    pub fn remove_file(&self, file_id: &ObjectId) -> Result<Option<FileName>, StorageError> {
+        let size = to_vec(&ObjectId::nil())?.len();
+        let key_prefix = to_vec(&self.id)?;
+        let total_size = key_prefix.len() + size;
+        let mut found = None;
+        for file in self.storage.get_all_keys_and_values(
+            Self::PREFIX_FILES,
+            total_size,
+            key_prefix,
+            None,
+            &None,
+        )? {
+            if file.0.len() == total_size + 1 {
+                let filename: FileName = from_slice(&file.1)?;
+                if filename.reference.id == *file_id {
+                    let key = file.0[1..].to_vec();
+                    self.storage.write_transaction(&mut |tx| {
+                        tx.del(Self::PREFIX_FILES, &key, None, &None)?;
+                        Ok(())
+                    })?;
+                    found = Some(filename);
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    /// checks if any branch of the user storage still references the file
+    pub fn is_file_referenced(
+        file_id: &ObjectId,
+        storage: &'a dyn KCVStorage,
+    ) -> Result<bool, StorageError> {
+        let size = to_vec(&ObjectId::nil())?.len();
+        let total_size = to_vec(&BranchId::nil())?.len() + size;
+        for file in
+            storage.get_all_keys_and_values(Self::PREFIX_FILES, total_size, vec![], None, &None)?
+        {
+            if file.0.len() == total_size + 1 {
+                let filename: FileName = from_slice(&file.1)?;
+                if filename.reference.id == *file_id {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// returns the files referenced by all the branches of the user storage
+    pub fn get_all_referenced_files(
+        storage: &'a dyn KCVStorage,
+    ) -> Result<Vec<FileName>, StorageError> {
+        let size = to_vec(&ObjectId::nil())?.len();
+        let total_size = to_vec(&BranchId::nil())?.len() + size;
+        let mut res: Vec<FileName> = vec![];
+        for file in
+            storage.get_all_keys_and_values(Self::PREFIX_FILES, total_size, vec![], None, &None)?
+        {
+            if file.0.len() == total_size + 1 {
+                let filename: FileName = from_slice(&file.1)?;
+                res.push(filename);
+            }
+        }
+        Ok(res)
+    }
+
     pub fn exists(&self) -> bool {
         self.storage
             .get(
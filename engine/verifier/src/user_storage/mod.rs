@@ -16,3 +16,9 @@ pub use storage::*;
 pub mod repo;
 
 pub mod branch;
+
+pub mod text_index;
+
+pub mod upload;
+
+pub mod wallet_share;
@@ -27,6 +27,7 @@ use ng_repo::kcv_storage::KCVStorage;
 use ng_repo::log::*;
 use ng_repo::repo::BranchInfo;
 use ng_repo::repo::Repo;
+use ng_repo::repo::UserInfo;
 use ng_repo::store::Store;
 use ng_repo::types::*;
 
@@ -47,9 +48,11 @@ impl<'a> RepoStorage<'a> {
     const DEFINITION: u8 = b'd';
     const STORE_BRANCH: u8 = b'e';
     const CERTIFICATE: u8 = b'f';
+    const REFRESH_SECRET: u8 = b'g';
     const INHERIT: u8 = b'i';
     const OVERLAY_BRANCH: u8 = b'l';
     const MAIN_BRANCH: u8 = b'm';
+    const MEMBERS: u8 = b'n';
     const OWNERS: u8 = b'o';
     const PINNED: u8 = b'p';
     const QUORUM: u8 = b'q';
@@ -60,7 +63,7 @@ impl<'a> RepoStorage<'a> {
     const WRITE_CAP_SECRET: u8 = b'w';
     const INBOX_CAP: u8 = b'x';
 
-    const ALL_PROPERTIES: [u8; 16] = [
+    const ALL_PROPERTIES: [u8; 18] = [
         Self::SIGNER_CAP,
         Self::INBOX_CAP,
         //Self::SIGNER_CAP_PARTIAL,
@@ -70,6 +73,7 @@ impl<'a> RepoStorage<'a> {
         Self::INHERIT,
         Self::OVERLAY_BRANCH,
         Self::MAIN_BRANCH,
+        Self::MEMBERS,
         Self::OWNERS,
         Self::PINNED,
         Self::QUORUM,
@@ -79,6 +83,7 @@ impl<'a> RepoStorage<'a> {
         Self::USER_BRANCH,
         Self::WRITE_CAP_SECRET,
         Self::CERTIFICATE,
+        Self::REFRESH_SECRET,
     ];
 
     const PREFIX_BRANCHES: u8 = b'b';
@@ -113,6 +118,7 @@ impl<'a> RepoStorage<'a> {
             repo.store.get_store_repo(),
             &repo.repo_def,
             &repo.branches,
+            &repo.members,
             storage,
         )
     }
@@ -194,6 +200,81 @@ impl<'a> RepoStorage<'a> {
         Ok(())
     }
 
+    pub fn update_members(
+        id: &RepoId,
+        members: &HashMap<Digest, UserInfo>,
+        storage: &'a dyn KCVStorage,
+    ) -> Result<(), StorageError> {
+        storage.write_transaction(&mut |tx| {
+            let id_ser = to_vec(id)?;
+            let value = to_vec(members)?;
+            tx.put(Self::PREFIX, &id_ser, Some(Self::MEMBERS), &value, &None)?;
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    pub fn update_caps(
+        id: &RepoId,
+        read_cap: &ReadCap,
+        write_cap: Option<&RepoWriteCapSecret>,
+        storage: &'a dyn KCVStorage,
+    ) -> Result<(), StorageError> {
+        storage.write_transaction(&mut |tx| {
+            let id_ser = to_vec(id)?;
+            let value = to_vec(read_cap)?;
+            tx.put(Self::PREFIX, &id_ser, Some(Self::READ_CAP), &value, &None)?;
+            if let Some(wc) = write_cap {
+                let value = to_vec(wc)?;
+                tx.put(
+                    Self::PREFIX,
+                    &id_ser,
+                    Some(Self::WRITE_CAP_SECRET),
+                    &value,
+                    &None,
+                )?;
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// the refresh secret of the last capabilities refresh of the repo, with the id of its RefreshCap,
+    /// and the renewed RepoWriteCapSecret if we are an editor
+    pub fn update_refresh_secret(
+        id: &RepoId,
+        refresh_id: &ObjectId,
+        refresh_secret: &RefreshSecretV0,
+        write_cap: Option<&RepoWriteCapSecret>,
+        storage: &'a dyn KCVStorage,
+    ) -> Result<(), StorageError> {
+        storage.write_transaction(&mut |tx| {
+            let id_ser = to_vec(id)?;
+            let value = to_vec(&(refresh_id, refresh_secret, write_cap))?;
+            tx.put(
+                Self::PREFIX,
+                &id_ser,
+                Some(Self::REFRESH_SECRET),
+                &value,
+                &None,
+            )?;
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    pub fn get_refresh_secret(
+        &self,
+    ) -> Result<(ObjectId, RefreshSecretV0, Option<RepoWriteCapSecret>), StorageError> {
+        let ser = self.storage.get(
+            Self::PREFIX,
+            &to_vec(&self.id).unwrap(),
+            Some(Self::REFRESH_SECRET),
+            &None,
+        )?;
+        Ok(from_slice(&ser)?)
+    }
+
     pub fn get_signer_cap(&self) -> Result<SignerCap, StorageError> {
         let ser = self.storage.get(
             Self::PREFIX,
@@ -204,6 +285,26 @@ impl<'a> RepoStorage<'a> {
         Ok(from_slice(&ser)?)
     }
 
+    pub fn get_write_cap(&self) -> Result<RepoWriteCapSecret, StorageError> {
+        let ser = self.storage.get(
+            Self::PREFIX,
+            &to_vec(&self.id).unwrap(),
+            Some(Self::WRITE_CAP_SECRET),
+            &None,
+        )?;
+        Ok(from_slice(&ser)?)
+    }
+
+    pub fn get_members(&self) -> Result<HashMap<Digest, UserInfo>, StorageError> {
+        let ser = self.storage.get(
+            Self::PREFIX,
+            &to_vec(&self.id).unwrap(),
+            Some(Self::MEMBERS),
+            &None,
+        )?;
+        Ok(from_slice(&ser)?)
+    }
+
     pub fn get_inbox_cap(&self) -> Result<PrivKey, StorageError> {
         let ser = self.storage.get(
             Self::PREFIX,
@@ -223,6 +324,7 @@ impl<'a> RepoStorage<'a> {
         store_repo: &StoreRepo,
         repo_def: &Repository,
         branches: &HashMap<BranchId, BranchInfo>,
+        members: &HashMap<Digest, UserInfo>,
         storage: &'a dyn KCVStorage,
     ) -> Result<RepoStorage<'a>, StorageError> {
         let repo = RepoStorage {
@@ -251,6 +353,8 @@ impl<'a> RepoStorage<'a> {
             tx.put(Self::PREFIX, &id_ser, Some(Self::STORE_REPO), &value, &None)?;
             let value = to_vec(repo_def)?;
             tx.put(Self::PREFIX, &id_ser, Some(Self::DEFINITION), &value, &None)?;
+            let value = to_vec(members)?;
+            tx.put(Self::PREFIX, &id_ser, Some(Self::MEMBERS), &value, &None)?;
             if let Some(wc) = write_cap {
                 let value = to_vec(wc)?;
                 tx.put(
@@ -360,8 +464,7 @@ impl<'a> RepoStorage<'a> {
             write_cap: prop(Self::WRITE_CAP_SECRET, &props).ok(),
             signer: prop(Self::SIGNER_CAP, &props).ok(),
             inbox: prop(Self::INBOX_CAP, &props).ok(),
-            //TODO: members
-            members: HashMap::new(),
+            members: prop(Self::MEMBERS, &props).unwrap_or_default(),
             branches,
             opened_branches,
             store,
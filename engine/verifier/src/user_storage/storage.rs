@@ -10,15 +10,16 @@
 //! Storage of user application data (RDF, content of rich-text document, etc)
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     sync::{Arc, RwLock},
 };
 
 use ng_net::app_protocol::{AppTabInfo, FileName};
+use ng_net::types::WalletRecoveryShare;
 use ng_repo::{
     block_storage::BlockStorage,
     errors::StorageError,
-    repo::{BranchInfo, Repo},
+    repo::{BranchInfo, Repo, UserInfo},
     store::Store,
     types::*,
 };
@@ -55,6 +56,32 @@ pub trait UserStorage: Send + Sync {
         certificate: &ObjectRef,
     ) -> Result<(), StorageError>;
 
+    fn update_repo_caps(
+        &self,
+        repo_id: &RepoId,
+        read_cap: &ReadCap,
+        write_cap: Option<&RepoWriteCapSecret>,
+    ) -> Result<(), StorageError>;
+
+    fn update_repo_members(
+        &self,
+        repo_id: &RepoId,
+        members: &HashMap<Digest, UserInfo>,
+    ) -> Result<(), StorageError>;
+
+    fn update_repo_refresh_secret(
+        &self,
+        repo_id: &RepoId,
+        refresh_id: &ObjectId,
+        refresh_secret: &RefreshSecretV0,
+        write_cap: Option<&RepoWriteCapSecret>,
+    ) -> Result<(), StorageError>;
+
+    fn get_repo_refresh_secret(
+        &self,
+        repo_id: &RepoId,
+    ) -> Result<(ObjectId, RefreshSecretV0, Option<RepoWriteCapSecret>), StorageError>;
+
     fn get_signer_cap(&self, repo_id: &RepoId) -> Result<SignerCap, StorageError>;
 
     fn get_inbox_cap(&self, repo_id: &RepoId) -> Result<PrivKey, StorageError>;
@@ -68,6 +95,16 @@ pub trait UserStorage: Send + Sync {
 
     fn branch_get_all_files(&self, branch: &BranchId) -> Result<Vec<FileName>, StorageError>;
 
+    fn branch_remove_file(
+        &self,
+        branch: &BranchId,
+        file_id: &ObjectId,
+    ) -> Result<Option<FileName>, StorageError>;
+
+    fn file_is_referenced(&self, file_id: &ObjectId) -> Result<bool, StorageError>;
+
+    fn file_get_all_referenced(&self) -> Result<Vec<FileName>, StorageError>;
+
     fn branch_set_discrete_state(
         &self,
         branch: BranchId,
@@ -81,6 +118,7 @@ pub trait UserStorage: Send + Sync {
         branch: &BranchId,
         repo: &RepoId,
         store: &StoreRepo,
+        user: &UserId,
     ) -> Result<AppTabInfo, StorageError>;
 
     fn update_branch_current_heads(
@@ -89,13 +127,80 @@ pub trait UserStorage: Send + Sync {
         branch_id: &BranchId,
         new_heads: Vec<ObjectRef>,
     ) -> Result<(), StorageError>;
+
+    fn upload_create(
+        &self,
+        upload_id: u32,
+        store_repo: &StoreRepo,
+        meta: &RandomAccessFileMeta,
+    ) -> Result<(), StorageError>;
+
+    fn upload_get_all_ids(&self) -> Result<HashSet<u32>, StorageError>;
+
+    /// removes the checkpoints of the uploads that were not continued for `max_age` minutes. Returns their ids
+    fn upload_remove_expired(&self, max_age: Timestamp) -> Result<Vec<u32>, StorageError>;
+
+    fn upload_add_blocks(
+        &self,
+        upload_id: u32,
+        first_index: usize,
+        blocks: &[(BlockId, BlockKey)],
+    ) -> Result<(), StorageError>;
+
+    fn upload_load(
+        &self,
+        upload_id: u32,
+    ) -> Result<(StoreRepo, RandomAccessFileMeta, Vec<(BlockId, BlockKey)>), StorageError>;
+
+    fn upload_remove(&self, upload_id: u32) -> Result<(), StorageError>;
+
+    fn wallet_share_save(
+        &self,
+        share: &WalletRecoveryShare,
+        from_overlay: &OverlayId,
+    ) -> Result<(), StorageError>;
+
+    fn wallet_share_load(
+        &self,
+        wallet_id: &PubKey,
+    ) -> Result<(WalletRecoveryShare, OverlayId), StorageError>;
+
+    fn text_index_get_document(
+        &self,
+        repo_id: &RepoId,
+    ) -> Result<HashMap<String, String>, StorageError>;
+
+    fn text_index_get_text(
+        &self,
+        repo_id: &RepoId,
+        source: &String,
+    ) -> Result<Option<String>, StorageError>;
+
+    fn text_index_get_postings(&self, term: &String) -> Result<HashMap<RepoId, u32>, StorageError>;
+
+    fn text_index_save(
+        &self,
+        repo_id: &RepoId,
+        texts: &HashMap<String, Option<String>>,
+        deltas: &HashMap<String, i64>,
+    ) -> Result<(), StorageError>;
+
+    fn text_index_get_version(&self) -> Result<u8, StorageError>;
+
+    fn text_index_set_version(&self, version: u8) -> Result<(), StorageError>;
 }
 
 pub(crate) struct InMemoryUserStorage {
     branch_files: RwLock<HashMap<BranchId, Vec<FileName>>>,
     branch_discrete_state: RwLock<HashMap<BranchId, Vec<u8>>>,
     repo_signer_cap: RwLock<HashMap<RepoId, SignerCap>>,
+    repo_refresh_secret:
+        RwLock<HashMap<RepoId, (ObjectId, RefreshSecretV0, Option<RepoWriteCapSecret>)>>,
     repo_inbox_cap: RwLock<HashMap<RepoId, PrivKey>>,
+    text_index_documents: RwLock<HashMap<RepoId, HashMap<String, String>>>,
+    text_index_postings: RwLock<HashMap<String, HashMap<RepoId, u32>>>,
+    text_index_version: RwLock<u8>,
+    wallet_shares: RwLock<HashMap<PubKey, (WalletRecoveryShare, OverlayId)>>,
 }
 
 impl InMemoryUserStorage {
@@ -104,7 +209,12 @@ impl InMemoryUserStorage {
             branch_files: RwLock::new(HashMap::new()),
             branch_discrete_state: RwLock::new(HashMap::new()),
             repo_signer_cap: RwLock::new(HashMap::new()),
+            repo_refresh_secret: RwLock::new(HashMap::new()),
             repo_inbox_cap: RwLock::new(HashMap::new()),
+            text_index_documents: RwLock::new(HashMap::new()),
+            text_index_postings: RwLock::new(HashMap::new()),
+            text_index_version: RwLock::new(0),
+            wallet_shares: RwLock::new(HashMap::new()),
         }
     }
 }
@@ -131,6 +241,36 @@ impl UserStorage for InMemoryUserStorage {
         }
     }
 
+    fn branch_remove_file(
+        &self,
+        branch: &BranchId,
+        file_id: &ObjectId,
+    ) -> Result<Option<FileName>, StorageError> {
+        let mut lock = self.branch_files.write().unwrap();
+        if let Some(file_list) = lock.get_mut(branch) {
+            let removed = file_list
+                .iter()
+                .find(|f| f.reference.id == *file_id)
+                .cloned();
+            file_list.retain(|f| f.reference.id != *file_id);
+            Ok(removed)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn file_is_referenced(&self, file_id: &ObjectId) -> Result<bool, StorageError> {
+        let lock = self.branch_files.read().unwrap();
+        Ok(lock
+            .values()
+            .any(|file_list| file_list.iter().any(|f| f.reference.id == *file_id)))
+    }
+
+    fn file_get_all_referenced(&self) -> Result<Vec<FileName>, StorageError> {
+        let lock = self.branch_files.read().unwrap();
+        Ok(lock.values().flatten().cloned().collect())
+    }
+
     fn branch_set_discrete_state(
         &self,
         branch: BranchId,
@@ -155,6 +295,7 @@ impl UserStorage for InMemoryUserStorage {
         branch: &BranchId,
         repo: &RepoId,
         store: &StoreRepo,
+        user: &UserId,
     ) -> Result<AppTabInfo, StorageError> {
         unimplemented!();
     }
@@ -190,6 +331,46 @@ impl UserStorage for InMemoryUserStorage {
         unimplemented!();
     }
 
+    fn update_repo_caps(
+        &self,
+        repo_id: &RepoId,
+        read_cap: &ReadCap,
+        write_cap: Option<&RepoWriteCapSecret>,
+    ) -> Result<(), StorageError> {
+        unimplemented!();
+    }
+
+    fn update_repo_members(
+        &self,
+        repo_id: &RepoId,
+        members: &HashMap<Digest, UserInfo>,
+    ) -> Result<(), StorageError> {
+        unimplemented!();
+    }
+
+    fn update_repo_refresh_secret(
+        &self,
+        repo_id: &RepoId,
+        refresh_id: &ObjectId,
+        refresh_secret: &RefreshSecretV0,
+        write_cap: Option<&RepoWriteCapSecret>,
+    ) -> Result<(), StorageError> {
+        let mut lock = self.repo_refresh_secret.write().unwrap();
+        lock.insert(
+            *repo_id,
+            (*refresh_id, refresh_secret.clone(), write_cap.cloned()),
+        );
+        Ok(())
+    }
+
+    fn get_repo_refresh_secret(
+        &self,
+        repo_id: &RepoId,
+    ) -> Result<(ObjectId, RefreshSecretV0, Option<RepoWriteCapSecret>), StorageError> {
+        let lock = self.repo_refresh_secret.read().unwrap();
+        lock.get(repo_id).cloned().ok_or(StorageError::NotFound)
+    }
+
     fn update_signer_cap(&self, signer_cap: &SignerCap) -> Result<(), StorageError> {
         let mut lock = self.repo_signer_cap.write().unwrap();
         lock.insert(signer_cap.repo, signer_cap.clone());
@@ -225,4 +406,130 @@ impl UserStorage for InMemoryUserStorage {
     ) -> Result<(), StorageError> {
         unimplemented!();
     }
+
+    fn upload_create(
+        &self,
+        _upload_id: u32,
+        _store_repo: &StoreRepo,
+        _meta: &RandomAccessFileMeta,
+    ) -> Result<(), StorageError> {
+        unimplemented!();
+    }
+
+    fn upload_get_all_ids(&self) -> Result<HashSet<u32>, StorageError> {
+        Ok(HashSet::new())
+    }
+
+    fn upload_remove_expired(&self, _max_age: Timestamp) -> Result<Vec<u32>, StorageError> {
+        Ok(vec![])
+    }
+
+    fn upload_add_blocks(
+        &self,
+        _upload_id: u32,
+        _first_index: usize,
+        _blocks: &[(BlockId, BlockKey)],
+    ) -> Result<(), StorageError> {
+        unimplemented!();
+    }
+
+    fn upload_load(
+        &self,
+        _upload_id: u32,
+    ) -> Result<(StoreRepo, RandomAccessFileMeta, Vec<(BlockId, BlockKey)>), StorageError> {
+        Err(StorageError::NotFound)
+    }
+
+    fn upload_remove(&self, _upload_id: u32) -> Result<(), StorageError> {
+        unimplemented!();
+    }
+
+    fn wallet_share_save(
+        &self,
+        share: &WalletRecoveryShare,
+        from_overlay: &OverlayId,
+    ) -> Result<(), StorageError> {
+        let mut lock = self.wallet_shares.write().unwrap();
+        lock.insert(share.wallet_id, (share.clone(), *from_overlay));
+        Ok(())
+    }
+
+    fn wallet_share_load(
+        &self,
+        wallet_id: &PubKey,
+    ) -> Result<(WalletRecoveryShare, OverlayId), StorageError> {
+        let lock = self.wallet_shares.read().unwrap();
+        lock.get(wallet_id).cloned().ok_or(StorageError::NotFound)
+    }
+
+    fn text_index_get_document(
+        &self,
+        repo_id: &RepoId,
+    ) -> Result<HashMap<String, String>, StorageError> {
+        let lock = self.text_index_documents.read().unwrap();
+        Ok(lock.get(repo_id).cloned().unwrap_or_default())
+    }
+
+    fn text_index_get_text(
+        &self,
+        repo_id: &RepoId,
+        source: &String,
+    ) -> Result<Option<String>, StorageError> {
+        let lock = self.text_index_documents.read().unwrap();
+        Ok(lock
+            .get(repo_id)
+            .and_then(|document| document.get(source))
+            .cloned())
+    }
+
+    fn text_index_get_postings(&self, term: &String) -> Result<HashMap<RepoId, u32>, StorageError> {
+        let lock = self.text_index_postings.read().unwrap();
+        Ok(lock.get(term).cloned().unwrap_or_default())
+    }
+
+    fn text_index_save(
+        &self,
+        repo_id: &RepoId,
+        texts: &HashMap<String, Option<String>>,
+        deltas: &HashMap<String, i64>,
+    ) -> Result<(), StorageError> {
+        let mut lock = self.text_index_documents.write().unwrap();
+        let document = lock.entry(*repo_id).or_default();
+        for (source, text) in texts {
+            match text {
+                Some(text) if !text.is_empty() => {
+                    document.insert(source.clone(), text.clone());
+                }
+                _ => {
+                    document.remove(source);
+                }
+            }
+        }
+        if document.is_empty() {
+            lock.remove(repo_id);
+        }
+        let mut lock = self.text_index_postings.write().unwrap();
+        for (term, delta) in deltas {
+            let docs = lock.entry(term.clone()).or_default();
+            let tf = docs.get(repo_id).map_or(0, |tf| *tf as i64) + delta;
+            if tf > 0 {
+                docs.insert(*repo_id, tf as u32);
+            } else {
+                docs.remove(repo_id);
+            }
+            if docs.is_empty() {
+                lock.remove(term);
+            }
+        }
+        Ok(())
+    }
+
+    fn text_index_get_version(&self) -> Result<u8, StorageError> {
+        Ok(*self.text_index_version.read().unwrap())
+    }
+
+    fn text_index_set_version(&self, version: u8) -> Result<(), StorageError> {
+        *self.text_index_version.write().unwrap() = version;
+        Ok(())
+    }
 }
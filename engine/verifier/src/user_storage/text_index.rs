@@ -0,0 +1,193 @@
+// Copyright (c) 2022-2025 Niko Bonnieure, Par le Peuple, NextGraph.org developers
+// All rights reserved.
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE2 or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Full-text index Storage (Object Key/Col/Value Mapping)
+
+use std::collections::HashMap;
+
+use serde_bare::from_slice;
+use serde_bare::to_vec;
+
+use ng_repo::errors::StorageError;
+use ng_repo::kcv_storage::KCVStorage;
+#[allow(unused_imports)]
+use ng_repo::log::*;
+use ng_repo::types::*;
+
+/// Stores, for each document, one entry per indexed text (by source),
+/// and for each term, one entry per document containing it, with the number of occurrences.
+pub struct TextIndexStorage<'a> {
+    storage: &'a dyn KCVStorage,
+}
+
+impl<'a> TextIndexStorage<'a> {
+    /// Entries of the version 1 of the index, with all the texts of a document, or all the postings of a term, in one value
+    const PREFIX_DOC_V1: u8 = b'x';
+    const PREFIX_TERM_V1: u8 = b'w';
+    /// key is repo_id + digest of the source, value is (source, text)
+    const PREFIX_TEXT: u8 = b't';
+    /// key is term + repo_id, value is the number of occurrences
+    const PREFIX_POSTING: u8 = b'p';
+    const PREFIX_VERSION: u8 = b'y';
+
+    pub fn new(storage: &'a dyn KCVStorage) -> TextIndexStorage<'a> {
+        TextIndexStorage { storage }
+    }
+
+    fn text_key(repo_id: &RepoId, source: &String) -> Result<Vec<u8>, StorageError> {
+        let mut key = to_vec(repo_id)?;
+        key.append(&mut to_vec(&Digest::from(&source.as_bytes().to_vec()))?);
+        Ok(key)
+    }
+
+    fn posting_key(term: &String, repo_id: &RepoId) -> Result<Vec<u8>, StorageError> {
+        let mut key = to_vec(term)?;
+        key.append(&mut to_vec(repo_id)?);
+        Ok(key)
+    }
+
+    pub fn get_document(&self, repo_id: &RepoId) -> Result<HashMap<String, String>, StorageError> {
+        let key_prefix = to_vec(repo_id)?;
+        let total_size = key_prefix.len() + to_vec(&ObjectId::nil())?.len();
+        let mut document = HashMap::new();
+        for (key, value) in self.storage.get_all_keys_and_values(
+            Self::PREFIX_TEXT,
+            total_size,
+            key_prefix,
+            None,
+            &None,
+        )? {
+            if key.len() == total_size + 1 {
+                let (source, text): (String, String) = from_slice(&value)?;
+                document.insert(source, text);
+            }
+        }
+        Ok(document)
+    }
+
+    pub fn get_text(
+        &self,
+        repo_id: &RepoId,
+        source: &String,
+    ) -> Result<Option<String>, StorageError> {
+        match self.storage.get(
+            Self::PREFIX_TEXT,
+            &Self::text_key(repo_id, source)?,
+            None,
+            &None,
+        ) {
+            Ok(ser) => Ok(Some(from_slice::<(String, String)>(&ser)?.1)),
+            Err(StorageError::NotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn get_postings(&self, term: &String) -> Result<HashMap<RepoId, u32>, StorageError> {
+        let key_prefix = to_vec(term)?;
+        let key_prefix_len = key_prefix.len();
+        let total_size = key_prefix_len + to_vec(&RepoId::nil())?.len();
+        let mut postings = HashMap::new();
+        for (key, value) in self.storage.get_all_keys_and_values(
+            Self::PREFIX_POSTING,
+            total_size,
+            key_prefix,
+            None,
+            &None,
+        )? {
+            if key.len() == total_size + 1 {
+                let repo_id: RepoId = from_slice(&key[1 + key_prefix_len..])?;
+                postings.insert(repo_id, from_slice(&value)?);
+            }
+        }
+        Ok(postings)
+    }
+
+    /// The version of the index, or 0 if the documents were never indexed
+    pub fn get_version(&self) -> Result<u8, StorageError> {
+        match self.storage.get(Self::PREFIX_VERSION, &vec![], None, &None) {
+            Ok(ser) => Ok(from_slice(&ser)?),
+            Err(StorageError::NotFound) => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Sets the version, and removes the entries of the version 1 when upgrading from it
+    pub fn set_version(&self, version: u8) -> Result<(), StorageError> {
+        let mut legacy = vec![];
+        if self.get_version()? == 1 {
+            // the keys of version 1 were a serialized term (at most 64 bytes) or repo_id
+            for prefix in [Self::PREFIX_TERM_V1, Self::PREFIX_DOC_V1] {
+                for (key, _) in
+                    self.storage
+                        .get_all_keys_and_values(prefix, 128, vec![], None, &None)?
+                {
+                    legacy.push((prefix, key[1..].to_vec()));
+                }
+            }
+        }
+        self.storage.write_transaction(&mut |tx| {
+            for (prefix, key) in legacy.iter() {
+                tx.del(*prefix, key, None, &None)?;
+            }
+            tx.put(
+                Self::PREFIX_VERSION,
+                &vec![],
+                None,
+                &to_vec(&version)?,
+                &None,
+            )
+        })
+    }
+
+    /// Saves the texts of the sources that changed (None or an empty text removes the source),
+    /// and adds the deltas to the number of occurrences of the terms in the document.
+    /// The postings that drop to zero are deleted.
+    pub fn save(
+        &self,
+        repo_id: &RepoId,
+        texts: &HashMap<String, Option<String>>,
+        deltas: &HashMap<String, i64>,
+    ) -> Result<(), StorageError> {
+        self.storage.write_transaction(&mut |tx| {
+            for (source, text) in texts {
+                let key = Self::text_key(repo_id, source)?;
+                match text {
+                    Some(text) if !text.is_empty() => tx.put(
+                        Self::PREFIX_TEXT,
+                        &key,
+                        None,
+                        &to_vec(&(source, text))?,
+                        &None,
+                    )?,
+                    _ => tx.del(Self::PREFIX_TEXT, &key, None, &None)?,
+                }
+            }
+            for (term, delta) in deltas {
+                let key = Self::posting_key(term, repo_id)?;
+                let tf = match tx.get(Self::PREFIX_POSTING, &key, None, &None) {
+                    Ok(ser) => from_slice::<u32>(&ser)? as i64,
+                    Err(StorageError::NotFound) => 0,
+                    Err(e) => return Err(e),
+                } + delta;
+                if tf > 0 {
+                    tx.put(
+                        Self::PREFIX_POSTING,
+                        &key,
+                        None,
+                        &to_vec(&(tf as u32))?,
+                        &None,
+                    )?;
+                } else {
+                    tx.del(Self::PREFIX_POSTING, &key, None, &None)?;
+                }
+            }
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,184 @@
+// Copyright (c) 2022-2025 Niko Bonnieure, Par le Peuple, NextGraph.org developers
+// All rights reserved.
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE2 or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Upload Storage (Object Key/Col/Value Mapping)
+
+use std::collections::{HashMap, HashSet};
+
+use serde_bare::from_slice;
+use serde_bare::to_vec;
+
+use ng_repo::errors::StorageError;
+use ng_repo::kcv_storage::KCVStorage;
+#[allow(unused_imports)]
+use ng_repo::log::*;
+use ng_repo::types::*;
+use ng_repo::utils::now_timestamp;
+
+/// Checkpoints of the uploads in progress, so they can be resumed after the app was restarted.
+/// For each upload, stores the store and the meta of the file, the leaves that have been written so far,
+/// and the time of the last checkpoint, so that the abandoned uploads can be removed.
+pub struct UploadStorage<'a> {
+    storage: &'a dyn KCVStorage,
+}
+
+impl<'a> UploadStorage<'a> {
+    const PREFIX: u8 = b'u';
+    const PREFIX_BLOCKS: u8 = b'l';
+
+    const LAST_ACTIVITY: u8 = b't';
+
+    pub fn new(storage: &'a dyn KCVStorage) -> UploadStorage<'a> {
+        UploadStorage { storage }
+    }
+
+    pub fn create(
+        &self,
+        upload_id: u32,
+        store_repo: &StoreRepo,
+        meta: &RandomAccessFileMeta,
+    ) -> Result<(), StorageError> {
+        self.storage.write_transaction(&mut |tx| {
+            let upload_id_ser = to_vec(&upload_id)?;
+            let value = to_vec(&(store_repo, meta))?;
+            tx.put(Self::PREFIX, &upload_id_ser, None, &value, &None)?;
+            let now = to_vec(&now_timestamp())?;
+            tx.put(
+                Self::PREFIX,
+                &upload_id_ser,
+                Some(Self::LAST_ACTIVITY),
+                &now,
+                &None,
+            )?;
+            Ok(())
+        })
+    }
+
+    /// returns the ids of all the uploads that have a checkpoint, and the time of their last checkpoint, if known
+    fn get_all(&self) -> Result<HashMap<u32, Option<Timestamp>>, StorageError> {
+        let key_size = to_vec(&0u32)?.len();
+        let mut res = HashMap::new();
+        for upload in
+            self.storage
+                .get_all_keys_and_values(Self::PREFIX, key_size, vec![], None, &None)?
+        {
+            if upload.0.len() < key_size + 1 {
+                continue;
+            }
+            let upload_id: u32 = from_slice(&upload.0[1..key_size + 1])?;
+            let last_activity = res.entry(upload_id).or_insert(None);
+            if upload.0.len() == key_size + 2 && upload.0[key_size + 1] == Self::LAST_ACTIVITY {
+                *last_activity = Some(from_slice(&upload.1)?);
+            }
+        }
+        Ok(res)
+    }
+
+    pub fn get_all_ids(&self) -> Result<HashSet<u32>, StorageError> {
+        Ok(self.get_all()?.into_keys().collect())
+    }
+
+    /// removes the uploads whose last checkpoint is older than `max_age` (in minutes). Returns their ids
+    pub fn remove_expired(&self, max_age: Timestamp) -> Result<Vec<u32>, StorageError> {
+        let now = now_timestamp();
+        let mut removed = vec![];
+        for (upload_id, last_activity) in self.get_all()? {
+            // the checkpoints saved without time are expired too
+            if last_activity.map_or(true, |time| time.saturating_add(max_age) < now) {
+                self.remove(upload_id)?;
+                removed.push(upload_id);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// saves the leaves, the first one being at position `first_index` in the file
+    pub fn add_blocks(
+        &self,
+        upload_id: u32,
+        first_index: usize,
+        blocks: &[(BlockId, BlockKey)],
+    ) -> Result<(), StorageError> {
+        self.storage.write_transaction(&mut |tx| {
+            let upload_id_ser = to_vec(&upload_id)?;
+            for (i, block) in blocks.iter().enumerate() {
+                let mut key = upload_id_ser.clone();
+                key.append(&mut to_vec(&((first_index + i) as u32))?);
+                tx.put(Self::PREFIX_BLOCKS, &key, None, &to_vec(block)?, &None)?;
+            }
+            let now = to_vec(&now_timestamp())?;
+            tx.put(
+                Self::PREFIX,
+                &upload_id_ser,
+                Some(Self::LAST_ACTIVITY),
+                &now,
+                &None,
+            )?;
+            Ok(())
+        })
+    }
+
+    /// returns the store, the meta and the leaves (in order) of an upload
+    pub fn load(
+        &self,
+        upload_id: u32,
+    ) -> Result<(StoreRepo, RandomAccessFileMeta, Vec<(BlockId, BlockKey)>), StorageError> {
+        let upload_id_ser = to_vec(&upload_id)?;
+        let (store_repo, meta): (StoreRepo, RandomAccessFileMeta) = from_slice(
+            &self
+                .storage
+                .get(Self::PREFIX, &upload_id_ser, None, &None)?,
+        )?;
+
+        let total_size = upload_id_ser.len() + to_vec(&0u32)?.len();
+        let mut blocks: Vec<(u32, (BlockId, BlockKey))> = vec![];
+        for block in self.storage.get_all_keys_and_values(
+            Self::PREFIX_BLOCKS,
+            total_size,
+            upload_id_ser.clone(),
+            None,
+            &None,
+        )? {
+            if block.0.len() == total_size + 1 {
+                let index: u32 = from_slice(&block.0[1 + upload_id_ser.len()..])?;
+                blocks.push((index, from_slice(&block.1)?));
+            }
+        }
+        blocks.sort_by_key(|b| b.0);
+        // only the leaves that were saved without gap are usable
+        let contiguous = blocks
+            .iter()
+            .enumerate()
+            .take_while(|(i, b)| *i as u32 == b.0)
+            .count();
+        blocks.truncate(contiguous);
+        Ok((store_repo, meta, blocks.into_iter().map(|b| b.1).collect()))
+    }
+
+    pub fn remove(&self, upload_id: u32) -> Result<(), StorageError> {
+        self.storage.write_transaction(&mut |tx| {
+            let upload_id_ser = to_vec(&upload_id)?;
+            tx.del(Self::PREFIX, &upload_id_ser, None, &None)?;
+            tx.del(
+                Self::PREFIX,
+                &upload_id_ser,
+                Some(Self::LAST_ACTIVITY),
+                &None,
+            )?;
+            tx.del_all_values(
+                Self::PREFIX_BLOCKS,
+                &upload_id_ser,
+                to_vec(&0u32)?.len(),
+                None,
+                &None,
+            )?;
+            Ok(())
+        })
+    }
+}
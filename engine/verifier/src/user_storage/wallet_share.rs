@@ -0,0 +1,64 @@
+// Copyright (c) 2022-2025 Niko Bonnieure, Par le Peuple, NextGraph.org developers
+// All rights reserved.
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE2 or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Wallet Share Storage (Object Key/Col/Value Mapping)
+
+use serde_bare::from_slice;
+use serde_bare::to_vec;
+
+use ng_net::types::WalletRecoveryShare;
+use ng_repo::errors::StorageError;
+use ng_repo::kcv_storage::KCVStorage;
+#[allow(unused_imports)]
+use ng_repo::log::*;
+use ng_repo::types::*;
+
+/// Shares of the master key of the wallets of our contacts, that we keep for their social recovery.
+/// For each wallet ID, stores the share and the overlay of the profile of the contact who sent it.
+pub struct WalletShareStorage<'a> {
+    storage: &'a dyn KCVStorage,
+}
+
+impl<'a> WalletShareStorage<'a> {
+    const PREFIX: u8 = b's';
+
+    pub fn new(storage: &'a dyn KCVStorage) -> WalletShareStorage<'a> {
+        WalletShareStorage { storage }
+    }
+
+    pub fn save(
+        &self,
+        share: &WalletRecoveryShare,
+        from_overlay: &OverlayId,
+    ) -> Result<(), StorageError> {
+        self.storage.write_transaction(&mut |tx| {
+            let value = to_vec(&(share, from_overlay))?;
+            tx.put(
+                Self::PREFIX,
+                &to_vec(&share.wallet_id)?,
+                None,
+                &value,
+                &None,
+            )?;
+            Ok(())
+        })
+    }
+
+    pub fn load(
+        &self,
+        wallet_id: &PubKey,
+    ) -> Result<(WalletRecoveryShare, OverlayId), StorageError> {
+        Ok(from_slice(&self.storage.get(
+            Self::PREFIX,
+            &to_vec(wallet_id)?,
+            None,
+            &None,
+        )?)?)
+    }
+}
@@ -48,7 +48,7 @@ use ng_repo::utils::generate_keypair;
 use ng_repo::{
     block_storage::{store_max_value_size, BlockStorage, HashMapBlockStorage},
     errors::{NgError, ProtocolError, ServerError, StorageError, VerifierError},
-    file::RandomAccessFile,
+    file::{FileError, RandomAccessFile},
     object::Object,
     repo::{BranchInfo, Repo},
     store::Store,
@@ -87,6 +87,12 @@ use crate::user_storage::UserStorage;
 //     fn get_repo(&self, id: &RepoId, store_repo: &StoreRepo) -> Result<&Repo, NgError>;
 // }
 
+/// number of blocks of a file that are fetched (or sent) to the broker at once, and prefetched while the previous ones are read
+pub(crate) const FILE_READ_AHEAD_BLOCKS: usize = 16;
+
+/// the checkpoints of the uploads that were not continued for a week (in minutes) are removed when the verifier is loaded
+const UPLOAD_CHECKPOINT_EXPIRY: Timestamp = 7 * 24 * 60;
+
 pub struct Verifier {
     pub(crate) config: VerifierConfig,
     user_id: UserId,
@@ -98,7 +104,7 @@ pub struct Verifier {
     peer_id: PubKey,
     max_reserved_seq_num: u64,
     last_reservation: SystemTime,
-    stores: HashMap<OverlayId, Arc<Store>>,
+    pub(crate) stores: HashMap<OverlayId, Arc<Store>>,
     inner_to_outer: HashMap<OverlayId, OverlayId>,
     pub(crate) outer: String,
     pub(crate) repos: HashMap<RepoId, Repo>,
@@ -115,6 +121,13 @@ pub struct Verifier {
     branch_subscriptions: HashMap<BranchId, Sender<AppResponse>>,
     pub(crate) orm_subscriptions: HashMap<u64, OrmSubscription>, // subscription id > subscription
     pub(crate) temporary_repo_certificates: HashMap<RepoId, ObjectRef>,
+    /// commits covered by the SyncSignature being verified
+    pub(crate) temporary_signed_commits: HashSet<ObjectId>,
+    /// refresh secrets received in RootCapRefresh or BranchCapRefresh, with the id of their RefreshCap and the renewed RepoWriteCapSecret if any
+    pub(crate) refresh_secrets:
+        HashMap<RepoId, (ObjectId, RefreshSecretV0, Option<RepoWriteCapSecret>)>,
+    /// refresh secrets of the RootCapRefresh that were received before their SyncSignature, only used to open the events of the refresh
+    pub(crate) pending_refresh_secrets: HashMap<RepoId, RefreshSecretV0>,
     pub(crate) orm_subscription_counter: u64,
     pub(crate) discrete_orm_subscriptions: HashMap<u64, DiscreteOrmSubscription>, // subscription id > Subscription
     pub(crate) discrete_orm_states: HashMap<BranchId, (u32, BackendDiscreteState)>, // branch_id > BackendDiscreteState
@@ -181,23 +194,42 @@ impl Verifier {
             .await;
     }
 
-    pub(crate) fn start_upload(&mut self, content_type: String, store: Arc<Store>) -> u32 {
-        let mut first_available: u32 = 0;
-        for upload in self.uploads.keys() {
-            if *upload != first_available + 1 {
-                break;
-            } else {
-                first_available += 1;
-            }
+    /// Starts an upload. The upload id is also checkpointed in user storage (if persistent), so that it can be resumed with resume_upload() after a restart.
+    /// Without user storage, the upload can only be resumed during the session, from the chunks kept in memory.
+    /// With `content_defined`, the file is split with content defined chunking, so that its successive versions share most of their blocks.
+    pub(crate) fn start_upload(
+        &mut self,
+        content_type: String,
+        content_defined: bool,
+        store: Arc<Store>,
+    ) -> Result<u32, NgError> {
+        let user_storage = self.user_storage_if_persistent();
+        let checkpointed = match user_storage.as_ref() {
+            Some(us) => us.upload_get_all_ids()?,
+            None => HashSet::new(),
+        };
+        let mut upload_id: u32 = 1;
+        while self.uploads.contains_key(&upload_id) || checkpointed.contains(&upload_id) {
+            upload_id += 1;
         }
-        first_available += 1;
 
-        let ret = self.uploads.insert(
-            first_available,
-            RandomAccessFile::new_empty(store_max_value_size(), content_type, vec![], store),
-        );
+        let store_repo = *store.get_store_repo();
+        let file = if content_defined {
+            RandomAccessFile::new_empty_content_defined(
+                store_max_value_size(),
+                content_type,
+                vec![],
+                store,
+            )
+        } else {
+            RandomAccessFile::new_empty(store_max_value_size(), content_type, vec![], store)
+        };
+        if let Some(user_storage) = user_storage {
+            user_storage.upload_create(upload_id, &store_repo, file.meta())?;
+        }
+        let ret = self.uploads.insert(upload_id, file);
         assert!(ret.is_none());
-        first_available
+        Ok(upload_id)
     }
 
     pub(crate) fn continue_upload(
@@ -205,11 +237,49 @@ impl Verifier {
         upload_id: u32,
         data: &Vec<u8>,
     ) -> Result<(), NgError> {
+        let user_storage = self.user_storage_if_persistent();
         let file = self
             .uploads
             .get_mut(&upload_id)
             .ok_or(NgError::WrongUploadId)?;
-        Ok(file.write(data)?)
+        let previous_blocks = file.written_blocks().len();
+        file.write(data)?;
+        // the complete chunks are checkpointed
+        if let Some(user_storage) = user_storage {
+            let blocks = &file.written_blocks()[previous_blocks..];
+            if !blocks.is_empty() {
+                user_storage.upload_add_blocks(upload_id, previous_blocks, blocks)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Resumes an upload that was interrupted, from its last checkpointed chunk.
+    /// Returns the position in the file from which the content must be sent again with continue_upload().
+    pub(crate) fn resume_upload(&mut self, upload_id: u32) -> Result<u64, NgError> {
+        let user_storage = match self.user_storage_if_persistent() {
+            Some(user_storage) => user_storage,
+            None => {
+                // the complete chunks of the upload are still in memory
+                let file = self
+                    .uploads
+                    .get_mut(&upload_id)
+                    .ok_or(NgError::WrongUploadId)?;
+                file.discard_remainder();
+                return Ok(file.written_size() as u64);
+            }
+        };
+        let (store_repo, meta, blocks) =
+            user_storage.upload_load(upload_id).map_err(|e| match e {
+                StorageError::NotFound => NgError::WrongUploadId,
+                _ => e.into(),
+            })?;
+        let store = self.get_store(&store_repo)?;
+        // the remainder that was not checkpointed (if the upload is still in memory) is discarded
+        let file = RandomAccessFile::resume(meta, blocks, store);
+        let pos = file.written_size() as u64;
+        self.uploads.insert(upload_id, file);
+        Ok(pos)
     }
 
     pub(crate) fn finish_upload(&mut self, upload_id: u32) -> Result<ObjectRef, NgError> {
@@ -218,6 +288,9 @@ impl Verifier {
             .remove(&upload_id)
             .ok_or(NgError::WrongUploadId)?;
         let _id = file.save()?;
+        if let Some(user_storage) = self.user_storage_if_persistent() {
+            user_storage.upload_remove(upload_id)?;
+        }
         Ok(file.reference().unwrap())
     }
 
@@ -242,11 +315,15 @@ impl Verifier {
             obj.block_ids()
         };
         let found = self.has_blocks(blocks, repo).await?;
-        for block_id in found.missing() {
-            let block = repo.store.get(block_id)?;
-            self.put_blocks(vec![block], repo).await?;
+        for block_ids in found.missing().chunks(FILE_READ_AHEAD_BLOCKS) {
+            let blocks = block_ids
+                .iter()
+                .map(|block_id| repo.store.get(block_id))
+                .collect::<Result<Vec<Block>, StorageError>>()?;
+            self.put_blocks(blocks, repo).await?;
         }
-        Ok(())
+        // the broker keeps the file until we unpin it
+        self.pin_object(&file_ref.id, repo).await
     }
 
     pub(crate) async fn push_app_response(&mut self, branch: &BranchId, response: AppResponse) {
@@ -314,13 +391,22 @@ impl Verifier {
 
         let root_branch_info = repo.branch(&repo.id)?;
 
+        let members: Vec<AppTabDocMember> = repo.members.values().map(|m| m.into()).collect();
+        let authors = members
+            .iter()
+            .filter_map(|m| m.can_edit.then(|| m.id.clone()))
+            .collect();
+        let is_member = members.iter().any(|m| m.id == self.user_id.to_string());
+
         let doc_tab_info = AppTabDocInfo {
             nuri: Some(format!("o:{}", repo.id.to_string())),
             is_store: Some(repo.store.id() == &repo.id),
-            is_member: Some(root_branch_info.read_cap.as_ref().unwrap().readcap_nuri()), // TODO
-            authors: None,                                                               // TODO
-            inbox: None,                                                                 // TODO
-            can_edit: Some(true),
+            is_member: is_member
+                .then(|| root_branch_info.read_cap.as_ref().unwrap().readcap_nuri()),
+            authors: Some(authors),
+            members: Some(members),
+            inbox: None, // TODO
+            can_edit: Some(repo.write_cap.is_some()),
             title,
             icon: None,
             description: about,
@@ -525,6 +611,9 @@ impl Verifier {
             branch_subscriptions: HashMap::new(),
             orm_subscriptions: HashMap::new(),
             temporary_repo_certificates: HashMap::new(),
+            temporary_signed_commits: HashSet::new(),
+            refresh_secrets: HashMap::new(),
+            pending_refresh_secrets: HashMap::new(),
             orm_subscription_counter: 1,
             discrete_orm_subscription_counter: 1,
             discrete_orm_subscriptions: HashMap::new(),
@@ -545,8 +634,9 @@ impl Verifier {
 
             for (store, repos) in stores.iter() {
                 log_debug!("LOADING STORE: {}", store);
-                let repo = user_storage
+                let mut repo = user_storage
                     .load_store(store, Arc::clone(self.block_storage.as_ref().unwrap()))?;
+                Self::load_repo_owners(&mut repo);
                 self.stores.insert(
                     store.overlay_id_for_storage_purpose(),
                     Arc::clone(&repo.store),
@@ -557,15 +647,38 @@ impl Verifier {
 
                 for repo_id in repos {
                     //log_info!("LOADING REPO: {}", repo_id);
-                    let repo = user_storage.load_repo(repo_id, Arc::clone(&store))?;
+                    let mut repo = user_storage.load_repo(repo_id, Arc::clone(&store))?;
+                    Self::load_repo_owners(&mut repo);
                     self.populate_topics(&repo);
                     self.add_repo_without_saving(repo);
+                    if let Ok(refresh_secret) = user_storage.get_repo_refresh_secret(repo_id) {
+                        self.refresh_secrets.insert(*repo_id, refresh_secret);
+                    }
                 }
             }
+            if let Err(e) = self.text_index_backfill() {
+                log_err!("cannot index the text of the documents: {e}");
+            }
+            match user_storage.upload_remove_expired(UPLOAD_CHECKPOINT_EXPIRY) {
+                Ok(expired) if !expired.is_empty() => {
+                    log_info!("removed {} expired upload checkpoints", expired.len())
+                }
+                Ok(_) => {}
+                Err(e) => log_err!("cannot remove the expired upload checkpoints: {e}"),
+            }
         }
         Ok(())
     }
 
+    /// the repos saved before their members were tracked, only know their owners from the RootBranch
+    fn load_repo_owners(repo: &mut Repo) {
+        if repo.members.is_empty() {
+            if let Err(e) = repo.load_owners() {
+                log_err!("cannot load the owners of repo {}: {}", repo.id, e);
+            }
+        }
+    }
+
     fn is_persistent(&self) -> bool {
         self.config.config_type.is_persistent()
     }
@@ -754,6 +867,37 @@ impl Verifier {
             .await
     }
 
+    /// same as new_event, but the key of the commit is encrypted with the given secret instead of the read cap of the branch
+    pub(crate) async fn new_event_with_secret(
+        &mut self,
+        commit: &Commit,
+        additional_blocks: &Vec<BlockId>,
+        repo_id: RepoId,
+        store_repo: &StoreRepo,
+        secret: &ReadCapSecret,
+    ) -> Result<(), NgError> {
+        if self.last_seq_num + 1 >= self.max_reserved_seq_num {
+            self.reserve_more(1).await?;
+        }
+        let publisher = self.config.peer_priv_key.clone();
+        self.last_seq_num += 1;
+        let seq_num = self.last_seq_num;
+        let repo = self.get_repo(&repo_id, store_repo)?;
+
+        let event =
+            Event::new_with_secret(&publisher, seq_num, commit, additional_blocks, repo, secret)?;
+        let past = commit.direct_causal_past();
+        self.send_or_save_event_to_outbox(
+            commit.reference().unwrap(),
+            past,
+            event,
+            repo.store.inner_overlay(),
+            commit.nfiles(),
+        )
+        .await?;
+        Ok(())
+    }
+
     #[allow(dead_code)]
     pub(crate) async fn new_event_with_repo(
         &mut self,
@@ -787,6 +931,7 @@ impl Verifier {
             past,
             event,
             repo.store.inner_overlay(),
+            commit.nfiles(),
         )
         .await?;
         Ok(())
@@ -808,6 +953,7 @@ impl Verifier {
             commit.direct_causal_past(),
             event,
             repo.store.inner_overlay(),
+            commit.nfiles(),
         )
         .await?;
         Ok(())
@@ -1124,6 +1270,7 @@ impl Verifier {
         past: Vec<ObjectRef>,
         event: Event,
         overlay: OverlayId,
+        removed_files: Vec<ObjectId>,
     ) -> Result<(), NgError> {
         //log_info!("========== EVENT {:03}: {}", event.seq_num(), event);
 
@@ -1142,6 +1289,7 @@ impl Verifier {
             let user = self.user_id().clone();
             self.send_event(event, &broker, &Some(user), &connected_broker, overlay)
                 .await?;
+            self.unpin_removed_files(&removed_files, &repo_id).await?;
         } else {
             match &self.config.config_type {
                 VerifierConfigType::JsSaveSession(js) => {
@@ -1389,6 +1537,106 @@ impl Verifier {
         }
     }
 
+    pub(crate) async fn pin_object(&self, id: &ObjectId, repo: &Repo) -> Result<(), NgError> {
+        let overlay = repo.store.overlay_for_read_on_client_protocol();
+
+        let broker = BROKER.read().await;
+        let user = self.user_id().clone();
+        let remote = self.connected_broker.connected_or_err()?;
+
+        let msg = ObjectPin::V0(ObjectPinV0 {
+            id: *id,
+            overlay: Some(overlay),
+        });
+        broker
+            .request::<ObjectPin, ()>(&Some(user), &remote, msg)
+            .await?;
+        Ok(())
+    }
+
+    /// asks the broker to unpin the removed files that are not referenced anymore by any branch.
+    /// Only called by the device that sends the RemoveFile commit, so each file is unpinned once.
+    async fn unpin_removed_files(
+        &self,
+        file_ids: &Vec<ObjectId>,
+        repo_id: &RepoId,
+    ) -> Result<(), NgError> {
+        if file_ids.is_empty() {
+            return Ok(());
+        }
+        let repo = self.repos.get(repo_id).ok_or(NgError::RepoNotFound)?;
+        let overlay = repo.store.overlay_for_read_on_client_protocol();
+
+        let broker = BROKER.read().await;
+        let user = self.user_id().clone();
+        let remote = self.connected_broker.connected_or_err()?;
+
+        for file_id in file_ids {
+            if let Some(user_storage) = self.user_storage.as_ref() {
+                if user_storage.file_is_referenced(file_id)? {
+                    continue;
+                }
+            }
+            let msg = ObjectUnpin::V0(ObjectUnpinV0 {
+                id: *file_id,
+                overlay: Some(overlay),
+            });
+            // the file might have been pinned by another user only. then the broker keeps it
+            if let Err(e) = broker
+                .request::<ObjectUnpin, ()>(&Some(user), &remote, msg)
+                .await
+            {
+                log_info!("could not unpin file {}: {}", file_id, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// the ids of the local blocks of all the files that are still referenced by a branch.
+    pub(crate) fn referenced_blocks(&self, repo: &Repo) -> Result<HashSet<BlockId>, NgError> {
+        let mut referenced = HashSet::new();
+        if let Some(user_storage) = self.user_storage.as_ref() {
+            for file in user_storage.file_get_all_referenced()? {
+                let mut to_visit = vec![file.reference.id];
+                while let Some(block_id) = to_visit.pop() {
+                    if !referenced.insert(block_id) {
+                        continue;
+                    }
+                    match repo.store.get(&block_id) {
+                        Ok(block) => to_visit.extend(block.children().iter()),
+                        Err(StorageError::NotFound) => {}
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+            }
+        }
+        Ok(referenced)
+    }
+
+    /// removes the local blocks of a file that is not referenced anymore by any branch.
+    /// The blocks in `kept`, given by referenced_blocks(), are shared with a file that is still referenced, and are kept.
+    pub(crate) fn free_file(
+        &self,
+        file_id: &ObjectId,
+        repo: &Repo,
+        kept: &HashSet<BlockId>,
+    ) -> Result<(), NgError> {
+        let mut to_visit = vec![*file_id];
+        while let Some(block_id) = to_visit.pop() {
+            if kept.contains(&block_id) {
+                // the whole subtree is used by another file
+                continue;
+            }
+            match repo.store.get(&block_id) {
+                Ok(block) => to_visit.extend(block.children().iter()),
+                Err(StorageError::NotFound) => continue,
+                Err(e) => return Err(e.into()),
+            }
+            repo.store.del(&block_id)?;
+        }
+        Ok(())
+    }
+
     pub(crate) async fn open_branch_<'a>(
         &mut self,
         repo_id: &RepoId,
@@ -1747,12 +1995,36 @@ impl Verifier {
             .ok_or(VerifierError::BranchNotOpened)?;
         let branch = repo.branch(&branch_id)?;
 
-        let commit = event.open(
+        let commit = match event.open(
             &repo.store,
             &repo_id,
             &branch_id,
             &branch.read_cap.as_ref().unwrap().key,
-        )?;
+        ) {
+            Ok(commit) => commit,
+            Err(e) => {
+                // the SyncSignature of a cap refresh is encrypted with the refresh secret
+                let refresh_secrets = self
+                    .refresh_secrets
+                    .get(&repo_id)
+                    .map(|(_, refresh_secret, _)| refresh_secret)
+                    .into_iter()
+                    .chain(self.pending_refresh_secrets.get(&repo_id));
+                let mut opened = Err(e);
+                for refresh_secret in refresh_secrets {
+                    opened = event.open(
+                        &repo.store,
+                        &repo_id,
+                        &branch_id,
+                        refresh_secret.read_secret(),
+                    );
+                    if opened.is_ok() {
+                        break;
+                    }
+                }
+                opened?
+            }
+        };
 
         self.verify_commit(&commit, &branch_id, &repo_id, Arc::clone(&repo.store))
             .await?;
@@ -1800,6 +2072,7 @@ impl Verifier {
                 CommitBodyV0::AddInboxCap(a) => a.verify(commit, self, branch_id, repo_id, store),
                 CommitBodyV0::AddSignerCap(a) => a.verify(commit, self, branch_id, repo_id, store),
                 CommitBodyV0::AddFile(a) => a.verify(commit, self, branch_id, repo_id, store),
+                CommitBodyV0::RemoveFile(a) => a.verify(commit, self, branch_id, repo_id, store),
                 CommitBodyV0::AddRepo(a) => a.verify(commit, self, branch_id, repo_id, store),
                 CommitBodyV0::Snapshot(a) => a.verify(commit, self, branch_id, repo_id, store),
                 CommitBodyV0::AsyncSignature(a) => {
@@ -1808,6 +2081,22 @@ impl Verifier {
                 CommitBodyV0::AsyncTransaction(a) => {
                     Box::pin(self.verify_async_transaction(a, commit, branch_id, repo_id, store))
                 }
+                CommitBodyV0::RootCapRefresh(a) => {
+                    a.verify(commit, self, branch_id, repo_id, store)
+                }
+                CommitBodyV0::BranchCapRefresh(a) => {
+                    a.verify(commit, self, branch_id, repo_id, store)
+                }
+                CommitBodyV0::CapRefreshed(a) => a.verify(commit, self, branch_id, repo_id, store),
+                CommitBodyV0::AddMember(a) => a.verify(commit, self, branch_id, repo_id, store),
+                CommitBodyV0::AddPermission(a) => a.verify(commit, self, branch_id, repo_id, store),
+                CommitBodyV0::RemoveMember(a) => a.verify(commit, self, branch_id, repo_id, store),
+                CommitBodyV0::UpdateRootBranch(a) => {
+                    Box::pin(self.verify_update_root_branch(a, commit, branch_id, repo_id, store))
+                }
+                CommitBodyV0::UpdateBranch(a) => {
+                    Box::pin(self.verify_update_branch(a, commit, branch_id, repo_id, store))
+                }
                 _ => {
                     log_err!("unimplemented verifier {}", commit);
                     return Err(VerifierError::NotImplemented);
@@ -1859,7 +2148,7 @@ impl Verifier {
         Ok(())
     }
 
-    fn user_storage_if_persistent(&self) -> Option<Arc<Box<dyn UserStorage>>> {
+    pub(crate) fn user_storage_if_persistent(&self) -> Option<Arc<Box<dyn UserStorage>>> {
         if self.is_persistent() {
             self.user_storage()
         } else {
@@ -1913,6 +2202,18 @@ impl Verifier {
         Ok(())
     }
 
+    pub(crate) fn update_repo_members(
+        &self,
+        repo_id: &RepoId,
+        store_repo: &StoreRepo,
+    ) -> Result<(), VerifierError> {
+        if let Some(user_storage) = self.user_storage_if_persistent() {
+            let repo = self.get_repo(repo_id, store_repo)?;
+            user_storage.update_repo_members(repo_id, &repo.members)?;
+        }
+        Ok(())
+    }
+
     pub(crate) fn update_inbox_cap_v0(
         &mut self,
         inbox_cap: &AddInboxCapV0,
@@ -2368,37 +2669,79 @@ impl Verifier {
     ) -> Result<Option<Receiver<Block>>, NgError> {
         let repo = self.get_repo(repo_id, store_repo)?;
 
-        let overlay = repo.store.overlay_for_read_on_client_protocol();
-
-        let broker = BROKER.read().await;
-        let user = Some(self.user_id().clone());
-        let remote = &self.connected_broker;
-
         match repo.store.has(id) {
             Err(StorageError::NotFound) => {
-                if remote.is_none() {
-                    return Err(NgError::NotFound);
-                }
-                let msg = BlocksGet::V0(BlocksGetV0 {
-                    ids: vec![*id],
-                    topic: None,
-                    include_children: true,
-                    overlay: Some(overlay),
-                });
-                match broker
-                    .request::<BlocksGet, Block>(&user, &remote.into(), msg)
-                    .await
-                {
-                    Ok(SoS::Stream(blockstream)) => Ok(Some(blockstream)),
-                    Ok(_) => return Err(NgError::InvalidResponse),
-                    Err(e) => return Err(e),
-                }
+                Ok(Some(self.fetch_blocks(vec![*id], true, repo).await?))
             }
             Err(e) => Err(e.into()),
             Ok(()) => Ok(None),
         }
     }
 
+    async fn fetch_blocks(
+        &self,
+        ids: Vec<BlockId>,
+        include_children: bool,
+        repo: &Repo,
+    ) -> Result<Receiver<Block>, NgError> {
+        fetch_blocks_from_broker(
+            ids,
+            include_children,
+            repo.store.overlay_for_read_on_client_protocol(),
+            self.user_id().clone(),
+            self.connected_broker.clone(),
+        )
+        .await
+    }
+
+    /// Opens a RandomAccessFile, fetching from the broker only the blocks that are needed to read the range `pos..pos+size`.
+    pub(crate) async fn open_file_for_range(
+        &self,
+        file_ref: &ObjectRef,
+        pos: usize,
+        size: usize,
+        repo_id: &RepoId,
+        store_repo: &StoreRepo,
+    ) -> Result<RandomAccessFile, NgError> {
+        let repo = self.get_repo(repo_id, store_repo)?;
+
+        // the root block and the meta object are needed to open the file
+        let root_block = match repo.store.get(&file_ref.id) {
+            Ok(block) => block,
+            Err(StorageError::NotFound) => {
+                let mut stream = self.fetch_blocks(vec![file_ref.id], false, repo).await?;
+                while let Some(block) = stream.next().await {
+                    repo.store.put(&block)?;
+                }
+                repo.store.get(&file_ref.id)?
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let meta_id = *root_block
+            .children()
+            .get(0)
+            .ok_or(NgError::FileError(FileError::NotAFile))?;
+        if let Err(StorageError::NotFound) = repo.store.has(&meta_id) {
+            let mut stream = self.fetch_blocks(vec![meta_id], true, repo).await?;
+            while let Some(block) = stream.next().await {
+                repo.store.put(&block)?;
+            }
+        }
+        let file =
+            RandomAccessFile::open(file_ref.id, file_ref.key.clone(), Arc::clone(&repo.store))?;
+
+        fetch_file_range(
+            &file,
+            pos,
+            size,
+            &repo.store,
+            self.user_id().clone(),
+            self.connected_broker.clone(),
+        )
+        .await?;
+        Ok(file)
+    }
+
     async fn bootstrap_from_remote(&mut self) -> Result<(), NgError> {
         if self.need_bootstrap() {
             let broker = BROKER.read().await;
@@ -2659,28 +3002,28 @@ impl Verifier {
         log_info!("SENDING {} EVENTS FROM OUTBOX", events_to_replay.len());
         for e in events_to_replay {
             let files = e.event.file_ids();
-            if !files.is_empty() || !need_replay {
-                let (repo_id, branch_id) = self
-                    .topics
-                    .get(&(e.overlay, *e.event.topic_id()))
-                    .ok_or(NgError::TopicNotFound)?
-                    .to_owned();
+            let (repo_id, branch_id) = self
+                .topics
+                .get(&(e.overlay, *e.event.topic_id()))
+                .ok_or(NgError::TopicNotFound)?
+                .to_owned();
 
-                let repo = self
-                    .repos
-                    .get(&repo_id)
-                    .ok_or(VerifierError::RepoNotFound)?;
+            let repo = self
+                .repos
+                .get(&repo_id)
+                .ok_or(VerifierError::RepoNotFound)?;
 
-                let branch = repo.branch(&branch_id)?;
+            let branch = repo.branch(&branch_id)?;
 
-                let commit = e.event.open_with_body(
-                    &repo.store,
-                    &repo_id,
-                    &branch_id,
-                    &branch.read_cap.as_ref().unwrap().key,
-                    !need_replay,
-                )?;
+            let commit = e.event.open_with_body(
+                &repo.store,
+                &repo_id,
+                &branch_id,
+                &branch.read_cap.as_ref().unwrap().key,
+                !need_replay,
+            )?;
 
+            if !files.is_empty() || !need_replay {
                 let store_repo = repo.store.get_store_repo().clone();
                 let store = Arc::clone(&repo.store);
                 for block in e.file_blocks {
@@ -2703,6 +3046,7 @@ impl Verifier {
             }
             self.send_event(e.event, &broker, &user, &remote, e.overlay)
                 .await?;
+            self.unpin_removed_files(&commit.nfiles(), &repo_id).await?;
         }
         Ok(())
     }
@@ -2825,6 +3169,9 @@ impl Verifier {
             branch_subscriptions: HashMap::new(),
             orm_subscriptions: HashMap::new(),
             temporary_repo_certificates: HashMap::new(),
+            temporary_signed_commits: HashSet::new(),
+            refresh_secrets: HashMap::new(),
+            pending_refresh_secrets: HashMap::new(),
             orm_subscription_counter: 1,
             discrete_orm_subscription_counter: 1,
             discrete_orm_subscriptions: HashMap::new(),
@@ -3068,6 +3415,69 @@ impl Verifier {
         Ok(repo_id)
     }
 }
+
+async fn fetch_blocks_from_broker(
+    ids: Vec<BlockId>,
+    include_children: bool,
+    overlay: OverlayId,
+    user: UserId,
+    remote: BrokerPeerId,
+) -> Result<Receiver<Block>, NgError> {
+    if remote.is_none() {
+        return Err(NgError::NotFound);
+    }
+    let broker = BROKER.read().await;
+    let msg = BlocksGet::V0(BlocksGetV0 {
+        ids,
+        topic: None,
+        include_children,
+        overlay: Some(overlay),
+    });
+    match broker
+        .request::<BlocksGet, Block>(&Some(user), &remote.into(), msg)
+        .await
+    {
+        Ok(SoS::Stream(blockstream)) => Ok(blockstream),
+        Ok(_) => return Err(NgError::InvalidResponse),
+        Err(e) => return Err(e),
+    }
+}
+
+/// Fetches from the broker the blocks of an opened file that are needed to read the range `pos..pos+size` and are missing locally.
+/// The tree is fetched level by level, only for the branches that cover the range.
+pub(crate) async fn fetch_file_range(
+    file: &RandomAccessFile,
+    pos: usize,
+    size: usize,
+    store: &Store,
+    user: UserId,
+    remote: BrokerPeerId,
+) -> Result<(), NgError> {
+    let overlay = store.overlay_for_read_on_client_protocol();
+    let mut previous_missing = vec![];
+    loop {
+        let missing = file.missing_blocks_in_range(pos, size)?;
+        if missing.is_empty() {
+            return Ok(());
+        }
+        if missing == previous_missing {
+            return Err(NgError::NotFound);
+        }
+        let mut stream = fetch_blocks_from_broker(
+            missing.clone(),
+            false,
+            overlay,
+            user.clone(),
+            remote.clone(),
+        )
+        .await?;
+        while let Some(block) = stream.next().await {
+            store.put(&block)?;
+        }
+        previous_missing = missing;
+    }
+}
+
 #[cfg(test)]
 mod test {
 
@@ -3106,4 +3516,38 @@ mod test {
 
         assert_eq!(verifier.last_seq_num, 5);
     }
+
+    #[test]
+    pub fn test_resume_upload_in_memory() {
+        let store = Store::dummy_public_v0();
+        let mut verifier = Verifier::new_dummy();
+        assert!(!verifier.is_persistent());
+
+        let id = verifier
+            .start_upload("text/plain".to_string(), false, Arc::clone(&store))
+            .unwrap();
+        let chunk_size = verifier.uploads[&id].meta().chunk_size() as usize;
+        let content: Vec<u8> = (0..chunk_size * 3 + 100).map(|i| i as u8).collect();
+        verifier
+            .continue_upload(id, &content[..chunk_size * 2 + 10].to_vec())
+            .unwrap();
+
+        // the connection was interrupted: the chunk that was not complete must be sent again
+        let pos = verifier.resume_upload(id).unwrap() as usize;
+        assert_eq!(pos, chunk_size * 2);
+        verifier
+            .continue_upload(id, &content[pos..].to_vec())
+            .unwrap();
+        let reference = verifier.finish_upload(id).unwrap();
+
+        let file = RandomAccessFile::open(reference.id, reference.key, store).unwrap();
+        assert_eq!(file.meta().total_size() as usize, content.len());
+        let mut read = vec![];
+        while read.len() < content.len() {
+            read.extend(file.read(read.len(), content.len() - read.len()).unwrap());
+        }
+        assert_eq!(read, content);
+
+        assert_eq!(verifier.resume_upload(id), Err(NgError::WrongUploadId));
+    }
 }
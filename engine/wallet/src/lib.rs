@@ -16,7 +16,9 @@ pub mod bip39;
 
 pub mod emojis;
 
-pub mod permissions;
+pub use ng_net::permissions;
+
+pub mod shamir;
 
 use std::{collections::HashMap, io::Cursor};
 
@@ -27,7 +29,8 @@ use aes_gcm_siv::{
 use argon2::{Algorithm, Argon2, AssociatedData, ParamsBuilder, Version};
 use chacha20poly1305::XChaCha20Poly1305;
 use image::{imageops::FilterType, io::Reader as ImageReader, ImageOutputFormat};
-use ng_net::types::Locator;
+use ng_net::permissions::AccessGrantV0;
+use ng_net::types::{Locator, WalletRecoveryShare};
 use rand::distributions::{Distribution, Uniform};
 use rand::prelude::*;
 use safe_transmute::transmute_to_bytes;
@@ -518,6 +521,82 @@ fn check_security_img(security_img: &Vec<u8>) -> Result<Vec<u8>, NgWalletError>
     Ok(cursor.into_inner())
 }
 
+fn check_pin(pin: &[u8; 4]) -> Result<(), NgWalletError> {
+    // each digit shouldnt be greater than 9
+    if pin[0] > 9 || pin[1] > 9 || pin[2] > 9 || pin[3] > 9 {
+        return Err(NgWalletError::InvalidPin);
+    }
+
+    // check for same digit doesnt appear 3 times
+    if (pin[0] == pin[1] && pin[0] == pin[2])
+        || (pin[0] == pin[1] && pin[0] == pin[3])
+        || (pin[0] == pin[2] && pin[0] == pin[3])
+        || (pin[1] == pin[2] && pin[1] == pin[3])
+    {
+        return Err(NgWalletError::InvalidPin);
+    }
+
+    // check for ascending series
+    if pin[1] == pin[0] + 1 && pin[2] == pin[1] + 1 && pin[3] == pin[2] + 1 {
+        return Err(NgWalletError::InvalidPin);
+    }
+
+    // check for descending series
+    if pin[3] >= 3 && pin[2] == pin[3] - 1 && pin[1] == pin[2] - 1 && pin[0] == pin[1] - 1 {
+        return Err(NgWalletError::InvalidPin);
+    }
+    Ok(())
+}
+
+fn gen_pazzle(pazzle_length: u8) -> Vec<u8> {
+    let mut ran = thread_rng();
+    let mut category_indices: Vec<u8> = (0..pazzle_length).collect();
+    category_indices.shuffle(&mut ran);
+
+    let between = Uniform::try_from(0..15).unwrap();
+    let mut pazzle = vec![0u8; pazzle_length.into()];
+    for (ix, i) in pazzle.iter_mut().enumerate() {
+        //*i = ran.gen_range(0, 15) + (category_indices[ix] << 4);
+        *i = between.sample(&mut ran) + (category_indices[ix] << 4);
+    }
+    //log_debug!("pazzle {:?}", pazzle);
+    pazzle
+}
+
+fn gen_mnemonic() -> [u16; 12] {
+    let mut ran = thread_rng();
+    let between = Uniform::try_from(0..2048).unwrap();
+    let mut mnemonic = [0u16; 12];
+    for i in &mut mnemonic {
+        //*i = ran.gen_range(0, 2048);
+        *i = between.sample(&mut ran);
+    }
+    //log_debug!("mnemonic {:?}", display_mnemonic(&mnemonic));
+    mnemonic
+}
+
+/// encrypts the master key with a key derived from `pass` and a new random salt
+fn new_login_method(
+    master_key: &[u8; 32],
+    pass: Vec<u8>,
+    master_nonce: u8,
+    wallet_id: WalletId,
+) -> Result<LoginMethod, NgWalletError> {
+    let mut salt = [0u8; 16];
+    getrandom::fill(&mut salt).map_err(|_e| NgWalletError::InternalError)?;
+
+    // pass is zeroized in derive_key_from_pass
+    let mut key = derive_key_from_pass(pass, salt, wallet_id);
+
+    let enc_master_key = enc_master_key(master_key, &key, master_nonce, wallet_id);
+    key.zeroize();
+
+    Ok(LoginMethod {
+        salt,
+        enc_master_key: enc_master_key?,
+    })
+}
+
 /// creates a Wallet from a pin, a security text and image
 /// and returns the Wallet, the pazzle and the mnemonic
 pub fn create_wallet_first_step_v0(
@@ -544,29 +623,7 @@ pub fn create_wallet_first_step_v0(
     }
 
     if let Some(pin) = params.pin {
-        // each digit shouldnt be greater than 9
-        if pin[0] > 9 || pin[1] > 9 || pin[2] > 9 || pin[3] > 9 {
-            return Err(NgWalletError::InvalidPin);
-        }
-
-        // check for same digit doesnt appear 3 times
-        if (pin[0] == pin[1] && pin[0] == pin[2])
-            || (pin[0] == pin[1] && pin[0] == pin[3])
-            || (pin[0] == pin[2] && pin[0] == pin[3])
-            || (pin[1] == pin[2] && pin[1] == pin[3])
-        {
-            return Err(NgWalletError::InvalidPin);
-        }
-
-        // check for ascending series
-        if pin[1] == pin[0] + 1 && pin[2] == pin[1] + 1 && pin[3] == pin[2] + 1 {
-            return Err(NgWalletError::InvalidPin);
-        }
-
-        // check for descending series
-        if pin[3] >= 3 && pin[2] == pin[3] - 1 && pin[1] == pin[2] - 1 && pin[0] == pin[1] - 1 {
-            return Err(NgWalletError::InvalidPin);
-        }
+        check_pin(&pin)?;
     } else if params.pazzle_length > 0 || params.mnemonic {
         return Err(NgWalletError::MnemonicOrPazzleNeedAPin);
     }
@@ -651,33 +708,14 @@ pub async fn create_wallet_second_step_v0(
 
     let wallet_id = params.wallet_privkey.to_pub();
 
-    let mut ran = thread_rng();
-
     let pazzle = if params.pazzle_length > 0 {
-        let mut category_indices: Vec<u8> = (0..params.pazzle_length).collect();
-        category_indices.shuffle(&mut ran);
-
-        let between = Uniform::try_from(0..15).unwrap();
-        let mut pazzle = vec![0u8; params.pazzle_length.into()];
-        for (ix, i) in pazzle.iter_mut().enumerate() {
-            //*i = ran.gen_range(0, 15) + (category_indices[ix] << 4);
-            *i = between.sample(&mut ran) + (category_indices[ix] << 4);
-        }
-        //log_debug!("pazzle {:?}", pazzle);
-        Some(pazzle)
+        Some(gen_pazzle(params.pazzle_length))
     } else {
         None
     };
 
     let mnemonic = if params.mnemonic {
-        let between = Uniform::try_from(0..2048).unwrap();
-        let mut mnemonic = [0u16; 12];
-        for i in &mut mnemonic {
-            //*i = ran.gen_range(0, 2048);
-            *i = between.sample(&mut ran);
-        }
-        //log_debug!("mnemonic {:?}", display_mnemonic(&mnemonic));
-        Some(mnemonic)
+        Some(gen_mnemonic())
     } else {
         None
     };
@@ -756,67 +794,34 @@ pub async fn create_wallet_second_step_v0(
     getrandom::fill(&mut master_key).map_err(|_e| NgWalletError::InternalError)?;
 
     let pazzle_login = if let Some(pazzle) = &pazzle {
-        let mut salt_pazzle = [0u8; 16];
-
-        //log_debug!("salt_pazzle {:?}", salt_pazzle);
-
-        getrandom::fill(&mut salt_pazzle).map_err(|_e| NgWalletError::InternalError)?;
-
-        let mut pazzle_key = derive_key_from_pass(
+        Some(new_login_method(
+            &master_key,
             [pazzle.clone(), params.pin.unwrap().to_vec()].concat(),
-            salt_pazzle,
+            0,
             wallet_id,
-        );
-
-        let enc_master_key_pazzle = enc_master_key(&master_key, &pazzle_key, 0, wallet_id)?;
-        pazzle_key.zeroize();
-        Some(LoginMethod {
-            salt: salt_pazzle,
-            enc_master_key: enc_master_key_pazzle,
-        })
+        )?)
     } else {
         None
     };
 
     let mnemonic_login = if let Some(mnemonic) = mnemonic {
-        let mut salt_mnemonic = [0u8; 16];
-        getrandom::fill(&mut salt_mnemonic).map_err(|_e| NgWalletError::InternalError)?;
-
-        //log_debug!("salt_mnemonic {:?}", salt_mnemonic);
-
-        let mut mnemonic_key = derive_key_from_pass(
+        Some(new_login_method(
+            &master_key,
             [transmute_to_bytes(&mnemonic), &params.pin.unwrap()].concat(),
-            salt_mnemonic,
+            0,
             wallet_id,
-        );
-
-        let enc_master_key_mnemonic = enc_master_key(&master_key, &mnemonic_key, 0, wallet_id)?;
-        mnemonic_key.zeroize();
-
-        Some(LoginMethod {
-            salt: salt_mnemonic,
-            enc_master_key: enc_master_key_mnemonic,
-        })
+        )?)
     } else {
         None
     };
 
     let password = if let Some(password) = &params.password {
-        let mut salt_password = [0u8; 16];
-        getrandom::fill(&mut salt_password).map_err(|_e| NgWalletError::InternalError)?;
-
-        //log_debug!("salt_password {:?}", salt_password);
-
-        let mut password_key =
-            derive_key_from_pass(password.as_bytes().to_vec(), salt_password, wallet_id);
-
-        let enc_master_key_password = enc_master_key(&master_key, &password_key, 0, wallet_id)?;
-        password_key.zeroize();
-
-        Some(LoginMethod {
-            salt: salt_password,
-            enc_master_key: enc_master_key_password,
-        })
+        Some(new_login_method(
+            &master_key,
+            password.as_bytes().to_vec(),
+            0,
+            wallet_id,
+        )?)
     } else {
         None
     };
@@ -904,11 +909,327 @@ pub async fn create_wallet_second_step_v0(
     ))
 }
 
+/// Applies a change to the login methods of a wallet, encrypts its log again and signs its content again.
+///
+/// The master key doesn't change, so master_nonce stays the same. The timestamp is bumped, so that this version
+/// of the wallet replaces the previous one. As the timestamp is part of the associated data of the encrypted log,
+/// the log is encrypted again, with a new random peer_id, so the nonce can start again at 0.
+fn update_login_methods(
+    wallet: &Wallet,
+    opened_wallet: &SensitiveWallet,
+    update: impl FnOnce(&mut WalletContentV0, &[u8; 32], WalletId) -> Result<(), NgWalletError>,
+) -> Result<Wallet, NgWalletError> {
+    verify(&wallet.content_as_bytes(), wallet.sig(), wallet.id())
+        .map_err(|_e| NgWalletError::InvalidSignature)?;
+
+    let wallet_privkey = opened_wallet.privkey();
+    if wallet_privkey.to_pub() != wallet.id() {
+        return Err(NgWalletError::InvalidSignature);
+    }
+    let master_key = opened_wallet
+        .master_key()
+        .ok_or(NgWalletError::MasterKeyNotAvailable)?;
+    let log = match opened_wallet {
+        SensitiveWallet::V0(v0) => v0
+            .log
+            .as_ref()
+            .ok_or(NgWalletError::MasterKeyNotAvailable)?,
+    };
+
+    let updated = match wallet {
+        Wallet::V0(v0) => {
+            let mut content = v0.content.clone();
+            update(&mut content, master_key, v0.id)?;
+
+            if content.pazzle.is_none() && content.mnemonic.is_none() && content.password.is_none()
+            {
+                return Err(NgWalletError::NoLoginMethod);
+            }
+            Wallet::V0(WalletV0 {
+                id: v0.id,
+                content,
+                sig: v0.sig,
+            })
+        }
+        _ => return Err(NgWalletError::InternalError),
+    };
+
+    // encrypt() sets the new timestamp, and signs the content
+    let (_, peer_id) = generate_keypair();
+    updated.encrypt(
+        &WalletLog::V0(log.clone()),
+        master_key,
+        peer_id,
+        0,
+        wallet_privkey,
+    )
+}
+
+/// Adds a password to a wallet that was opened, or replaces its current password.
+///
+/// Only the password LoginMethod is encrypted again. Returns the updated wallet, that should replace the previous one.
+pub fn set_wallet_password(
+    wallet: &Wallet,
+    opened_wallet: &SensitiveWallet,
+    mut pass: String,
+) -> Result<Wallet, NgWalletError> {
+    let mut password = pass.trim().to_string();
+    pass.zeroize();
+    if password.is_empty() {
+        return Err(NgWalletError::LoginMethodNotSupported);
+    }
+    let res = update_login_methods(wallet, opened_wallet, |content, master_key, wallet_id| {
+        content.password = Some(new_login_method(
+            master_key,
+            password.as_bytes().to_vec(),
+            content.master_nonce,
+            wallet_id,
+        )?);
+        Ok(())
+    });
+    password.zeroize();
+    res
+}
+
+/// Generates a new mnemonic for a wallet that was opened, replacing the previous one if any.
+///
+/// Only the mnemonic LoginMethod is encrypted again. Returns the updated wallet and the new mnemonic.
+pub fn regenerate_wallet_mnemonic(
+    wallet: &Wallet,
+    opened_wallet: &SensitiveWallet,
+    mut pin: [u8; 4],
+) -> Result<(Wallet, [u16; 12]), NgWalletError> {
+    check_pin(&pin)?;
+    let mnemonic = gen_mnemonic();
+    let res = update_login_methods(wallet, opened_wallet, |content, master_key, wallet_id| {
+        content.mnemonic = Some(new_login_method(
+            master_key,
+            [transmute_to_bytes(&mnemonic), &pin].concat(),
+            content.master_nonce,
+            wallet_id,
+        )?);
+        Ok(())
+    });
+    pin.zeroize();
+    Ok((res?, mnemonic))
+}
+
+/// Generates a new pazzle for a wallet that was opened, replacing the previous one if any.
+///
+/// Only the pazzle LoginMethod is encrypted again. Returns the updated wallet and the new pazzle.
+pub fn regenerate_wallet_pazzle(
+    wallet: &Wallet,
+    opened_wallet: &SensitiveWallet,
+    mut pin: [u8; 4],
+) -> Result<(Wallet, Vec<u8>), NgWalletError> {
+    check_pin(&pin)?;
+    // a wallet whose pazzle was removed gets one of the default length
+    let pazzle_length = match wallet.pazzle_length() {
+        0 => 9,
+        pazzle_length => pazzle_length,
+    };
+    let pazzle = gen_pazzle(pazzle_length);
+    let res = update_login_methods(wallet, opened_wallet, |content, master_key, wallet_id| {
+        content.pazzle = Some(new_login_method(
+            master_key,
+            [pazzle.clone(), pin.to_vec()].concat(),
+            content.master_nonce,
+            wallet_id,
+        )?);
+        content.pazzle_length = pazzle_length;
+        Ok(())
+    });
+    pin.zeroize();
+    Ok((res?, pazzle))
+}
+
+/// Removes (disables) a login method of a wallet that was opened. At least one login method must remain.
+pub fn remove_wallet_login_method(
+    wallet: &Wallet,
+    opened_wallet: &SensitiveWallet,
+    method: LoginMethodType,
+) -> Result<Wallet, NgWalletError> {
+    update_login_methods(wallet, opened_wallet, |content, _, _| {
+        match method {
+            LoginMethodType::Pazzle => {
+                content.pazzle = None;
+                content.pazzle_length = 0;
+            }
+            LoginMethodType::Mnemonic => content.mnemonic = None,
+            LoginMethodType::Password => content.password = None,
+        }
+        Ok(())
+    })
+}
+
+/// Splits the master key of an opened wallet into `count` shares, of which `threshold` will be needed to open the wallet
+/// with [open_wallet_with_recovery_shares]. The shares are meant to be given to some contacts of the user.
+pub fn split_wallet_master_key(
+    opened_wallet: &SensitiveWallet,
+    threshold: u8,
+    count: u8,
+) -> Result<Vec<WalletRecoveryShare>, NgWalletError> {
+    let master_key = opened_wallet
+        .master_key()
+        .ok_or(NgWalletError::MasterKeyNotAvailable)?;
+    let wallet_privkey = opened_wallet.privkey();
+    let mut shares = shamir::split(master_key, threshold, count)?;
+    let res = shares
+        .iter()
+        .map(|(index, share)| {
+            WalletRecoveryShare::new(&wallet_privkey, threshold, *index, *share)
+                .map_err(|_| NgWalletError::InternalError)
+        })
+        .collect();
+    shares.iter_mut().for_each(|(_, share)| share.zeroize());
+    res
+}
+
+/// Opens a wallet with the shares of its master key that were returned by the contacts of the user (social recovery).
+///
+/// A password should then be set on the wallet with [set_wallet_password], as the master key is available.
+pub fn open_wallet_with_recovery_shares(
+    wallet: &Wallet,
+    shares: Vec<WalletRecoveryShare>,
+) -> Result<SensitiveWallet, NgWalletError> {
+    verify(&wallet.content_as_bytes(), wallet.sig(), wallet.id())
+        .map_err(|_e| NgWalletError::InvalidSignature)?;
+
+    match wallet {
+        Wallet::V0(v0) => {
+            let threshold = shares
+                .first()
+                .ok_or(NgWalletError::InvalidRecoveryShares)?
+                .threshold;
+            if shares.len() < threshold as usize
+                || shares.iter().any(|s| {
+                    s.wallet_id != v0.id || s.threshold != threshold || s.verify().is_err()
+                })
+            {
+                return Err(NgWalletError::InvalidRecoveryShares);
+            }
+            let mut points: Vec<(u8, [u8; 32])> =
+                shares.iter().map(|s| (s.index, s.share)).collect();
+            let master_key = shamir::combine(&points);
+            points.iter_mut().for_each(|(_, share)| share.zeroize());
+
+            // the decryption fails if the shares were wrong
+            Ok(SensitiveWallet::V0(dec_encrypted_block(
+                v0.content.encrypted.clone(),
+                master_key?,
+                v0.content.peer_id,
+                v0.content.nonce,
+                v0.content.timestamp,
+                v0.id,
+            )?))
+        }
+        _ => Err(NgWalletError::InternalError),
+    }
+}
+
+/// Adds operations to the log of a wallet that was opened, and encrypts the log again.
+///
+/// The log is encrypted with a new random peer_id, so the nonce can start again at 0.
+/// Returns the updated wallet, that should replace the previous one.
+fn add_wallet_operations(
+    wallet: &Wallet,
+    opened_wallet: &mut SensitiveWallet,
+    ops: Vec<WalletOperation>,
+) -> Result<Wallet, NgWalletError> {
+    verify(&wallet.content_as_bytes(), wallet.sig(), wallet.id())
+        .map_err(|_e| NgWalletError::InvalidSignature)?;
+
+    let wallet_privkey = opened_wallet.privkey();
+    if wallet_privkey.to_pub() != wallet.id() {
+        return Err(NgWalletError::InvalidSignature);
+    }
+    let mut master_key = *opened_wallet
+        .master_key()
+        .ok_or(NgWalletError::MasterKeyNotAvailable)?;
+
+    let res = match opened_wallet {
+        SensitiveWallet::V0(v0) => {
+            let log = v0
+                .log
+                .as_mut()
+                .ok_or(NgWalletError::MasterKeyNotAvailable)?;
+            for op in ops {
+                log.add(op);
+            }
+            let (_, peer_id) = generate_keypair();
+            wallet.encrypt(
+                &WalletLog::V0(log.clone()),
+                &master_key,
+                peer_id,
+                0,
+                wallet_privkey,
+            )
+        }
+    };
+    master_key.zeroize();
+    res
+}
+
+/// Saves in a wallet that was opened, the grants given to an app (identified by its nuri or origin).
+///
+/// Returns the updated wallet, that should replace the previous one.
+pub fn add_access_grants(
+    wallet: &Wallet,
+    opened_wallet: &mut SensitiveWallet,
+    app: &String,
+    grants: Vec<AccessGrantV0>,
+) -> Result<Wallet, NgWalletError> {
+    let ops = grants
+        .iter()
+        .map(|grant| WalletOperation::AddAccessGrantV0((app.clone(), grant.clone())))
+        .collect();
+    let res = add_wallet_operations(wallet, opened_wallet, ops)?;
+    match opened_wallet {
+        SensitiveWallet::V0(v0) => v0
+            .access_grants
+            .entry(app.clone())
+            .or_default()
+            .extend(grants),
+    }
+    Ok(res)
+}
+
+/// Revokes a grant given to an app, in a wallet that was opened.
+///
+/// Returns the updated wallet, that should replace the previous one.
+pub fn remove_access_grant(
+    wallet: &Wallet,
+    opened_wallet: &mut SensitiveWallet,
+    app: &String,
+    grant_id: &String,
+) -> Result<Wallet, NgWalletError> {
+    let res = add_wallet_operations(
+        wallet,
+        opened_wallet,
+        vec![WalletOperation::RemoveAccessGrantV0((
+            app.clone(),
+            grant_id.clone(),
+        ))],
+    )?;
+    match opened_wallet {
+        SensitiveWallet::V0(v0) => {
+            if let Some(grants) = v0.access_grants.get_mut(app) {
+                grants.retain(|g| g.id != *grant_id);
+                if grants.is_empty() {
+                    v0.access_grants.remove(app);
+                }
+            }
+        }
+    }
+    Ok(res)
+}
+
 #[cfg(test)]
 mod test {
     use crate::emojis::display_pazzle_one;
 
     use super::*;
+    use ng_net::permissions::AppManifest;
     use ng_net::types::BootstrapContentV0;
     use std::fs::File;
     use std::io::BufReader;
@@ -1042,4 +1363,222 @@ mod test {
             log_debug!("encrypted part {:?}", _w);
         }
     }
+    #[async_std::test]
+    async fn change_wallet_password() {
+        let res = create_wallet_first_step_v0(CreateWalletV0::new(
+            None,
+            "know yourself".to_string(),
+            None,
+            0,
+            Some("first password".to_string()),
+            false,
+            false,
+            false,
+            BootstrapContentV0::new_localhost(PubKey::nil()),
+            None,
+            None,
+            false,
+            "test".to_string(),
+        ))
+        .expect("create_wallet_first_step_v0");
+
+        let mut verifier = Verifier::new_dummy();
+        let (res, _, _) = create_wallet_second_step_v0(res, &mut verifier)
+            .await
+            .expect("create_wallet_second_step_v0");
+
+        let opened = open_wallet_with_password(&res.wallet, "first password".to_string())
+            .expect("open with password");
+
+        let wallet = set_wallet_password(&res.wallet, &opened, " second password ".to_string())
+            .expect("set_wallet_password");
+
+        let opened_again = open_wallet_with_password(&wallet, "second password".to_string())
+            .expect("open with new password");
+        assert_eq!(opened_again.master_key(), opened.master_key());
+
+        // the only login method cannot be removed
+        assert_eq!(
+            remove_wallet_login_method(&wallet, &opened, LoginMethodType::Password).unwrap_err(),
+            NgWalletError::NoLoginMethod
+        );
+    }
+
+    #[async_std::test]
+    async fn regenerate_wallet_mnemonic_and_pazzle() {
+        let res = create_wallet_first_step_v0(CreateWalletV0::new(
+            None,
+            "know yourself".to_string(),
+            None,
+            0,
+            Some("password".to_string()),
+            false,
+            false,
+            false,
+            BootstrapContentV0::new_localhost(PubKey::nil()),
+            None,
+            None,
+            false,
+            "test".to_string(),
+        ))
+        .expect("create_wallet_first_step_v0");
+
+        let mut verifier = Verifier::new_dummy();
+        let (res, _, _) = create_wallet_second_step_v0(res, &mut verifier)
+            .await
+            .expect("create_wallet_second_step_v0");
+
+        let timestamp = |wallet: &Wallet| match wallet {
+            Wallet::V0(v0) => v0.content.timestamp,
+            _ => panic!("wallet is empty"),
+        };
+
+        let opened = open_wallet_with_password(&res.wallet, "password".to_string())
+            .expect("open with password");
+
+        // the timestamps are in minutes, so the previous version is backdated to check that the timestamp is bumped
+        let backdated = |wallet: &Wallet| match wallet {
+            Wallet::V0(v0) => {
+                let mut content = v0.content.clone();
+                content.timestamp -= 1;
+                let sig = sign(&opened.privkey(), &v0.id, &to_vec(&content).unwrap()).unwrap();
+                Wallet::V0(WalletV0 {
+                    id: v0.id,
+                    content,
+                    sig,
+                })
+            }
+            _ => panic!("wallet is empty"),
+        };
+
+        let pin = [5, 2, 9, 1];
+        let previous = backdated(&res.wallet);
+        let (wallet, mnemonic) =
+            regenerate_wallet_mnemonic(&previous, &opened, pin).expect("regenerate mnemonic");
+        assert!(timestamp(&wallet) > timestamp(&previous));
+
+        let opened_mnemonic =
+            open_wallet_with_mnemonic(&wallet, mnemonic, pin).expect("open with mnemonic");
+        assert_eq!(opened_mnemonic.master_key(), opened.master_key());
+        assert!(open_wallet_with_mnemonic(&wallet, mnemonic, [5, 2, 9, 3]).is_err());
+
+        let (wallet_2, new_mnemonic) =
+            regenerate_wallet_mnemonic(&wallet, &opened, pin).expect("regenerate mnemonic again");
+        assert_ne!(new_mnemonic, mnemonic);
+        assert!(open_wallet_with_mnemonic(&wallet_2, mnemonic, pin).is_err());
+        open_wallet_with_mnemonic(&wallet_2, new_mnemonic, pin).expect("open with new mnemonic");
+
+        let previous = backdated(&wallet_2);
+        let (wallet_3, pazzle) =
+            regenerate_wallet_pazzle(&previous, &opened, pin).expect("regenerate pazzle");
+        assert!(timestamp(&wallet_3) > timestamp(&previous));
+        assert_eq!(pazzle.len(), 9);
+        let opened_pazzle =
+            open_wallet_with_pazzle(&wallet_3, pazzle.clone(), pin).expect("open with pazzle");
+        assert_eq!(opened_pazzle.master_key(), opened.master_key());
+
+        let (wallet_4, new_pazzle) =
+            regenerate_wallet_pazzle(&wallet_3, &opened, pin).expect("regenerate pazzle again");
+        assert!(open_wallet_with_pazzle(&wallet_4, pazzle, pin).is_err());
+        open_wallet_with_pazzle(&wallet_4, new_pazzle, pin).expect("open with new pazzle");
+
+        // the other login methods are kept
+        open_wallet_with_mnemonic(&wallet_4, new_mnemonic, pin).expect("open with mnemonic");
+        open_wallet_with_password(&wallet_4, "password".to_string()).expect("open with password");
+    }
+
+    #[async_std::test]
+    async fn recover_wallet_with_shares() {
+        let res = create_wallet_first_step_v0(CreateWalletV0::new(
+            None,
+            "know yourself".to_string(),
+            None,
+            0,
+            Some("password".to_string()),
+            false,
+            false,
+            false,
+            BootstrapContentV0::new_localhost(PubKey::nil()),
+            None,
+            None,
+            false,
+            "test".to_string(),
+        ))
+        .expect("create_wallet_first_step_v0");
+
+        let mut verifier = Verifier::new_dummy();
+        let (res, _, _) = create_wallet_second_step_v0(res, &mut verifier)
+            .await
+            .expect("create_wallet_second_step_v0");
+
+        let opened = open_wallet_with_password(&res.wallet, "password".to_string())
+            .expect("open with password");
+
+        let shares = split_wallet_master_key(&opened, 2, 3).expect("split_wallet_master_key");
+
+        let recovered = open_wallet_with_recovery_shares(&res.wallet, shares[1..3].to_vec())
+            .expect("open with shares");
+        assert_eq!(recovered.master_key(), opened.master_key());
+
+        assert_eq!(
+            open_wallet_with_recovery_shares(&res.wallet, shares[0..1].to_vec()).unwrap_err(),
+            NgWalletError::InvalidRecoveryShares
+        );
+
+        // a share that was modified is rejected, as it is signed by the wallet
+        let mut modified = shares[1..3].to_vec();
+        modified[0].share[0] ^= 1;
+        assert_eq!(
+            open_wallet_with_recovery_shares(&res.wallet, modified).unwrap_err(),
+            NgWalletError::InvalidRecoveryShares
+        );
+    }
+
+    #[async_std::test]
+    async fn access_grants_persisted_in_wallet() {
+        let res = create_wallet_first_step_v0(CreateWalletV0::new(
+            None,
+            "know yourself".to_string(),
+            None,
+            0,
+            Some("password".to_string()),
+            false,
+            false,
+            false,
+            BootstrapContentV0::new_localhost(PubKey::nil()),
+            None,
+            None,
+            false,
+            "test".to_string(),
+        ))
+        .expect("create_wallet_first_step_v0");
+
+        let mut verifier = Verifier::new_dummy();
+        let (res, _, _) = create_wallet_second_step_v0(res, &mut verifier)
+            .await
+            .expect("create_wallet_second_step_v0");
+
+        let mut opened = open_wallet_with_password(&res.wallet, "password".to_string())
+            .expect("open with password");
+
+        let app = "https://app.example.com".to_string();
+        let manifest = AppManifest::new_for_origin_all_access_v0(app.clone());
+        let grants = manifest
+            .grant(&[], opened.personal_identity())
+            .expect("grant");
+        let grant_id = grants[0].id.clone();
+
+        let wallet =
+            add_access_grants(&res.wallet, &mut opened, &app, grants).expect("add_access_grants");
+        let reopened =
+            open_wallet_with_password(&wallet, "password".to_string()).expect("open with password");
+        assert_eq!(reopened.access_grants(&app).len(), 1);
+
+        let wallet = remove_access_grant(&wallet, &mut opened, &app, &grant_id)
+            .expect("remove_access_grant");
+        assert!(opened.access_grants(&app).is_empty());
+        let reopened =
+            open_wallet_with_password(&wallet, "password".to_string()).expect("open with password");
+        assert!(reopened.access_grants(&app).is_empty());
+    }
 }
@@ -0,0 +1,125 @@
+// Copyright (c) 2022-2025 Niko Bonnieure, Par le Peuple, NextGraph.org developers
+// All rights reserved.
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE2 or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Shamir secret sharing over GF(256), used for the social recovery of the master key of a wallet
+
+use rand::prelude::*;
+use zeroize::Zeroize;
+
+use crate::types::NgWalletError;
+
+/// multiplication in GF(256), with the reduction polynomial of AES (x^8 + x^4 + x^3 + x + 1)
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80;
+        a <<= 1;
+        if carry != 0 {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+/// inverse in GF(256): a^254, as a^255 = 1
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1u8;
+    let mut base = a;
+    let mut exp = 254u8;
+    while exp != 0 {
+        if exp & 1 != 0 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exp >>= 1;
+    }
+    result
+}
+
+/// Splits a secret into `count` shares, any `threshold` of them being enough to rebuild the secret.
+///
+/// Each byte of the secret is the constant term of a random polynomial of degree `threshold - 1`.
+/// The shares are the values of those polynomials at x = 1..=count.
+pub fn split(
+    secret: &[u8; 32],
+    threshold: u8,
+    count: u8,
+) -> Result<Vec<(u8, [u8; 32])>, NgWalletError> {
+    if threshold < 2 || count < threshold {
+        return Err(NgWalletError::InvalidRecoveryShares);
+    }
+    let mut rng = rand::thread_rng();
+    let mut shares: Vec<(u8, [u8; 32])> = (1..=count).map(|x| (x, [0u8; 32])).collect();
+    let mut coefficients = vec![0u8; threshold as usize];
+    for (i, byte) in secret.iter().enumerate() {
+        coefficients[0] = *byte;
+        rng.fill_bytes(&mut coefficients[1..]);
+        for (x, share) in shares.iter_mut() {
+            let mut y = 0u8;
+            for c in coefficients.iter().rev() {
+                y = gf_mul(y, *x) ^ c;
+            }
+            share[i] = y;
+        }
+    }
+    coefficients.zeroize();
+    Ok(shares)
+}
+
+/// Rebuilds the secret with a Lagrange interpolation at x = 0.
+///
+/// With less shares than the threshold, the result is a wrong secret, which cannot be detected here.
+pub fn combine(shares: &[(u8, [u8; 32])]) -> Result<[u8; 32], NgWalletError> {
+    if shares.is_empty() {
+        return Err(NgWalletError::InvalidRecoveryShares);
+    }
+    for (j, (xj, _)) in shares.iter().enumerate() {
+        if *xj == 0 || shares[j + 1..].iter().any(|(xm, _)| xm == xj) {
+            return Err(NgWalletError::InvalidRecoveryShares);
+        }
+    }
+    let mut secret = [0u8; 32];
+    for (j, (xj, share)) in shares.iter().enumerate() {
+        let mut basis = 1u8;
+        for (m, (xm, _)) in shares.iter().enumerate() {
+            if m != j {
+                basis = gf_mul(basis, gf_mul(*xm, gf_inv(xm ^ xj)));
+            }
+        }
+        for (s, y) in secret.iter_mut().zip(share.iter()) {
+            *s ^= gf_mul(basis, *y);
+        }
+    }
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_and_combine() {
+        let secret: [u8; 32] = rand::thread_rng().gen();
+        let shares = split(&secret, 3, 5).expect("split");
+        assert_eq!(shares.len(), 5);
+
+        assert_eq!(combine(&shares[0..3]).unwrap(), secret);
+        assert_eq!(combine(&shares[2..5]).unwrap(), secret);
+        assert_eq!(combine(&[shares[4], shares[0], shares[3]]).unwrap(), secret);
+        assert_eq!(combine(&shares).unwrap(), secret);
+
+        assert_ne!(combine(&shares[0..2]).unwrap(), secret);
+        assert!(combine(&[shares[0], shares[0], shares[1]]).is_err());
+        assert!(split(&secret, 4, 3).is_err());
+    }
+}
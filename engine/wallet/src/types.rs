@@ -21,6 +21,7 @@ use ng_repo::log::*;
 use ng_repo::types::*;
 use ng_repo::utils::{encrypt_in_place, generate_keypair};
 
+use ng_net::permissions::AccessGrantV0;
 use ng_net::types::*;
 
 use ng_verifier::site::SiteV0;
@@ -282,6 +283,7 @@ impl From<&CreateWalletIntermediaryV0> for SensitiveWalletV0 {
             brokers: HashMap::new(),
             overlay_core_overrides: HashMap::new(),
             third_parties: HashMap::new(),
+            access_grants: HashMap::new(),
             log: None,
             master_key: None,
             client: None,
@@ -470,6 +472,11 @@ pub struct SensitiveWalletV0 {
     #[zeroize(skip)]
     pub third_parties: HashMap<String, serde_bytes::ByteBuf>,
 
+    /// grants given to the third-party apps, by app (nuri or origin of the app)
+    #[zeroize(skip)]
+    #[serde(default)]
+    pub access_grants: HashMap<String, Vec<AccessGrantV0>>,
+
     #[zeroize(skip)]
     pub log: Option<WalletLogV0>,
 
@@ -502,6 +509,12 @@ impl SensitiveWallet {
             Self::V0(v0) => v0.wallet_privkey.clone(),
         }
     }
+    /// only available if the wallet was opened with one of its login methods (not right after its creation)
+    pub fn master_key(&self) -> Option<&[u8; 32]> {
+        match self {
+            Self::V0(v0) => v0.master_key.as_ref(),
+        }
+    }
     pub fn id(&self) -> String {
         match self {
             Self::V0(v0) => v0.wallet_id.clone(),
@@ -537,6 +550,21 @@ impl SensitiveWallet {
             },
         }
     }
+    /// the grants given to an app. empty if the app was never granted anything
+    pub fn access_grants(&self, app: &String) -> &[AccessGrantV0] {
+        match self {
+            Self::V0(v0) => v0
+                .access_grants
+                .get(app)
+                .map(|g| g.as_slice())
+                .unwrap_or(&[]),
+        }
+    }
+    pub fn all_access_grants(&self) -> &HashMap<String, Vec<AccessGrantV0>> {
+        match self {
+            Self::V0(v0) => &v0.access_grants,
+        }
+    }
     pub fn set_client(&mut self, client: ClientV0) {
         match self {
             Self::V0(v0) => v0.client = Some(client),
@@ -671,6 +699,14 @@ pub struct LoginMethod {
     pub enc_master_key: [u8; 48],
 }
 
+/// Kind of a LoginMethod of a wallet
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LoginMethodType {
+    Pazzle,
+    Mnemonic,
+    Password,
+}
+
 /// Wallet content Version 0
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct WalletContentV0 {
@@ -811,6 +847,16 @@ impl WalletLogV0 {
                             let _ = wallet.third_parties.insert(key.to_string(), value.clone());
                         }
                     }
+                    WalletOperation::AddAccessGrantV0((app, grant)) => {
+                        if self.is_first_and_not_deleted_afterwards(op, "RemoveAccessGrantV0") {
+                            wallet
+                                .access_grants
+                                .entry(app.clone())
+                                .or_default()
+                                .push(grant.clone());
+                        }
+                    }
+                    WalletOperation::RemoveAccessGrantV0(_) => {}
                     WalletOperation::RemoveThirdPartyDataV0(_) => {} // WalletOperation::SetSiteRBDRefV0((site, store_type, rbdr)) => {
                                                                      //     if self.is_last_occurrence(op.0, &op.1) != 0 {
                                                                      //         let _ = wallet.sites.get_mut(&site.to_string()).and_then(|site| {
@@ -959,6 +1005,10 @@ pub enum WalletOperation {
     RemoveSiteBootstrapV0((PubKey, PubKey)),
     AddThirdPartyDataV0((String, serde_bytes::ByteBuf)),
     RemoveThirdPartyDataV0(String),
+    /// app and grant
+    AddAccessGrantV0((String, AccessGrantV0)),
+    /// app and grant ID
+    RemoveAccessGrantV0((String, String)),
     //SetSiteRBDRefV0((PubKey, SiteStoreType, ObjectRef)),
     //SetSiteRepoSecretV0((PubKey, SiteStoreType, RepoWriteCapSecret)),
 }
@@ -1028,6 +1078,16 @@ impl WalletOperation {
             Self::RemoveThirdPartyDataV0(t) => {
                 t.hash(&mut s);
                 (s.finish(), "RemoveThirdPartyDataV0")
+            }
+            Self::AddAccessGrantV0(t) => {
+                t.0.hash(&mut s);
+                t.1.id.hash(&mut s);
+                (s.finish(), "AddAccessGrantV0")
+            }
+            Self::RemoveAccessGrantV0(t) => {
+                t.0.hash(&mut s);
+                t.1.hash(&mut s);
+                (s.finish(), "RemoveAccessGrantV0")
             } // Self::SetSiteRBDRefV0(t) => {
               //     t.0.hash(&mut s);
               //     t.1.hash(&mut s);
@@ -1086,6 +1146,7 @@ impl From<&WalletOpCreateV0> for SensitiveWalletV0 {
             //clients: HashMap::new(),
             overlay_core_overrides: HashMap::new(),
             third_parties: HashMap::new(),
+            access_grants: HashMap::new(),
             log: None,
             master_key: None,
             client: None, //Some(op.client.clone()),
@@ -1460,6 +1521,9 @@ pub enum NgWalletError {
     MnemonicOrPazzleNeedAPin,
     NoLoginMethod,
     LoginMethodNotSupported,
+    /// the wallet must be opened with one of its login methods, so that the master key is available
+    MasterKeyNotAvailable,
+    InvalidRecoveryShares,
 }
 
 impl From<NgWalletError> for NgError {
@@ -101,6 +101,7 @@ export const worker_ready = new Promise<void>((resolve) => {
 const streamed_api: Record<string, number> = {
     doc_subscribe: 2,
     orm_start_graph: 4,
+    orm_resume_graph: 5,
     orm_start_discrete: 2,
     file_get: 3,
     app_request_stream: 1,
@@ -44,10 +44,11 @@ use wasm_bindgen_futures::JsFuture;
 use ng_repo::errors::{NgError, ProtocolError};
 use ng_repo::log::*;
 use ng_repo::types::*;
-use ng_repo::utils::{decode_key, decode_priv_key};
+use ng_repo::utils::{decode_digest, decode_key, decode_priv_key};
 
 use ng_net::app_protocol::*;
 use ng_net::broker::*;
+use ng_net::permissions::AppManifest;
 use ng_net::types::{
     BindAddress, BootstrapContentV0, ClientInfo, ClientInfoV0, ClientType, CreateAccountBSP,
     InboxPost, IP,
@@ -448,6 +449,65 @@ pub async fn discrete_update(
     }
 }
 
+/// Streams the results of a SPARQL query to the callback, as FileBinary chunks of the serialization in `format`
+/// (iana media type, defaults to SPARQL-JSON for SELECT and ASK, and N-Triples for CONSTRUCT), followed by an EndOfStream.
+#[wasm_bindgen]
+pub async fn sparql_query_stream(
+    session_id: JsValue,
+    sparql: String,
+    base: JsValue,
+    format: JsValue,
+    nuri: JsValue,
+    callback: &js_sys::Function,
+) -> Result<JsValue, String> {
+    let session_id: u64 = serde_wasm_bindgen::from_value::<u64>(session_id)
+        .map_err(|_| "Invalid session_id".to_string())?;
+    let nuri = if nuri.is_string() {
+        NuriV0::new_from(&nuri.as_string().unwrap()).map_err(|e| e.to_string())?
+    } else {
+        NuriV0::new_entire_user_site()
+    };
+    let base = base.as_string();
+    let format = format.as_string();
+
+    let request = AppRequest::V0(AppRequestV0 {
+        command: AppRequestCommandV0::new_read_query_stream(),
+        nuri,
+        payload: Some(AppRequestPayload::new_sparql_query_with_format(
+            sparql, base, format,
+        )),
+        session_id,
+    });
+
+    app_request_stream_(request, callback).await
+}
+
+/// Evaluates a SPARQL query and returns the explanation of its plan, with the duration and the number of results of each operator.
+#[wasm_bindgen]
+pub async fn sparql_query_explain(
+    session_id: JsValue,
+    sparql: String,
+    base: JsValue,
+    nuri: JsValue,
+) -> Result<JsValue, JsValue> {
+    let session_id: u64 = serde_wasm_bindgen::from_value::<u64>(session_id)
+        .map_err(|_| "Invalid session_id".to_string())?;
+    let nuri = if nuri.is_string() {
+        Some(NuriV0::new_from(&nuri.as_string().unwrap()).map_err(|e| e.to_string())?)
+    } else {
+        None
+    };
+
+    let json = nextgraph::local_broker::doc_sparql_query_explain(
+        session_id,
+        sparql,
+        base.as_string(),
+        nuri,
+    )
+    .await?;
+    js_sys::JSON::parse(&json)
+}
+
 #[wasm_bindgen]
 pub async fn sparql_update(
     session_id: JsValue,
@@ -614,22 +674,7 @@ pub async fn rdf_dump(session_id: JsValue) -> Result<String, String> {
     let session_id: u64 = serde_wasm_bindgen::from_value::<u64>(session_id)
         .map_err(|_| "Invalid session_id".to_string())?;
 
-    let request = AppRequest::V0(AppRequestV0 {
-        command: AppRequestCommandV0::new_rdf_dump(),
-        nuri: NuriV0::new_entire_user_site(),
-        payload: None,
-        session_id,
-    });
-
-    let res = nextgraph::local_broker::app_request(request)
-        .await
-        .map_err(|e: NgError| e.to_string())?;
-
-    let AppResponse::V0(res) = res;
-    match res {
-        AppResponseV0::Text(s) => Ok(s),
-        _ => Err("invalid response".to_string()),
-    }
+    nextgraph::local_broker::rdf_dump(session_id).await
 }
 
 /// from_profile_nuri = did:ng:a or did:ng:b
@@ -1683,15 +1728,61 @@ pub async fn file_get(
     file_get_(session_id, nuri, branch_nuri, callback).await
 }
 
+/// Gets only the `length` bytes of the file starting at `offset`. Only the blocks of that range are fetched from the broker.
+#[wasm_bindgen]
+pub async fn file_get_range(
+    session_id: JsValue,
+    nuri: String,
+    branch_nuri: String,
+    offset: JsValue,
+    length: JsValue,
+    callback: &js_sys::Function,
+) -> Result<JsValue, String> {
+    let session_id: u64 = serde_wasm_bindgen::from_value::<u64>(session_id)
+        .map_err(|_| "Deserialization error of session_id".to_string())?;
+    let offset: u64 = serde_wasm_bindgen::from_value::<u64>(offset)
+        .map_err(|_| "Deserialization error of offset".to_string())?;
+    let length: u64 = serde_wasm_bindgen::from_value::<u64>(length)
+        .map_err(|_| "Deserialization error of length".to_string())?;
+    let nuri =
+        NuriV0::new_from(&nuri).map_err(|e| format!("error with nuri: {}", e.to_string()))?;
+
+    let branch_nuri = if branch_nuri.is_empty() {
+        NuriV0::new_private_store_target()
+    } else {
+        NuriV0::new_from(&branch_nuri)
+            .map_err(|e| format!("error with branch_nuri: {}", e.to_string()))?
+    };
+
+    file_get_range_(
+        session_id,
+        nuri,
+        branch_nuri,
+        Some(AppRequestPayload::new_file_get_range(offset, length)),
+        callback,
+    )
+    .await
+}
+
 async fn file_get_(
+    session_id: u64,
+    nuri: NuriV0,
+    branch_nuri: NuriV0,
+    callback: &js_sys::Function,
+) -> Result<JsValue, String> {
+    file_get_range_(session_id, nuri, branch_nuri, None, callback).await
+}
+
+async fn file_get_range_(
     session_id: u64,
     mut nuri: NuriV0,
     branch_nuri: NuriV0,
+    payload: Option<AppRequestPayload>,
     callback: &js_sys::Function,
 ) -> Result<JsValue, String> {
     nuri.copy_target_from(&branch_nuri);
 
-    let mut request = AppRequest::new(AppRequestCommandV0::FileGet, nuri, None);
+    let mut request = AppRequest::new(AppRequestCommandV0::FileGet, nuri, payload);
     request.set_session_id(session_id);
 
     app_request_stream_(request, callback).await
@@ -1743,13 +1834,21 @@ pub async fn upload_done(
     Ok(serde_wasm_bindgen::to_value(&filename).unwrap())
 }
 
-async fn do_upload_start(session_id: u64, nuri: NuriV0, mimetype: String) -> Result<u32, String> {
+async fn do_upload_start(
+    session_id: u64,
+    nuri: NuriV0,
+    mimetype: String,
+    content_defined: bool,
+) -> Result<u32, String> {
+    let payload = if content_defined {
+        AppRequestPayloadV0::RandomAccessFilePutContentDefined(mimetype)
+    } else {
+        AppRequestPayloadV0::RandomAccessFilePut(mimetype)
+    };
     let mut request = AppRequest::new(
         AppRequestCommandV0::FilePut,
         nuri,
-        Some(AppRequestPayload::V0(
-            AppRequestPayloadV0::RandomAccessFilePut(mimetype),
-        )),
+        Some(AppRequestPayload::V0(payload)),
     );
     request.set_session_id(session_id);
 
@@ -1777,11 +1876,72 @@ pub async fn upload_start(
         NuriV0::new_from(&nuri).map_err(|e| format!("error with nuri: {}", e.to_string()))?
     };
 
-    let upload_id = do_upload_start(session_id, branch_nuri, mimetype).await?;
+    let upload_id = do_upload_start(session_id, branch_nuri, mimetype, false).await?;
+
+    Ok(serde_wasm_bindgen::to_value(&upload_id).unwrap())
+}
+
+/// Same as upload_start, but the file is split with content defined chunking,
+/// so that the successive versions of a big file share most of their blocks.
+#[wasm_bindgen]
+pub async fn upload_start_content_defined(
+    session_id: JsValue,
+    nuri: String,
+    mimetype: String,
+) -> Result<JsValue, String> {
+    let session_id: u64 = serde_wasm_bindgen::from_value::<u64>(session_id)
+        .map_err(|_| "Deserialization error of session_id".to_string())?;
+    let branch_nuri = if nuri.is_empty() {
+        NuriV0::new_private_store_target()
+    } else {
+        NuriV0::new_from(&nuri).map_err(|e| format!("error with nuri: {}", e.to_string()))?
+    };
+
+    let upload_id = do_upload_start(session_id, branch_nuri, mimetype, true).await?;
 
     Ok(serde_wasm_bindgen::to_value(&upload_id).unwrap())
 }
 
+/// Resumes an upload that was interrupted (app restarted, tab reloaded).
+/// Returns the position in the file from which the chunks must be sent again with upload_chunk.
+#[wasm_bindgen]
+pub async fn upload_resume(
+    session_id: JsValue,
+    upload_id: JsValue,
+    nuri: String,
+) -> Result<JsValue, String> {
+    let session_id: u64 = serde_wasm_bindgen::from_value::<u64>(session_id)
+        .map_err(|_| "Deserialization error of session_id".to_string())?;
+    let upload_id: u32 = serde_wasm_bindgen::from_value::<u32>(upload_id)
+        .map_err(|_| "Deserialization error of upload_id".to_string())?;
+    let branch_nuri = if nuri.is_empty() {
+        NuriV0::new_private_store_target()
+    } else {
+        NuriV0::new_from(&nuri).map_err(|e| format!("error with nuri: {}", e.to_string()))?
+    };
+
+    let mut request = AppRequest::new(
+        AppRequestCommandV0::FilePut,
+        branch_nuri,
+        Some(AppRequestPayload::V0(
+            AppRequestPayloadV0::RandomAccessFileResume(upload_id),
+        )),
+    );
+    request.set_session_id(session_id);
+
+    let response = nextgraph::local_broker::app_request(request)
+        .await
+        .map_err(|e: NgError| e.to_string())?;
+
+    match response {
+        AppResponse::V0(AppResponseV0::FileUploadResumed(pos)) => {
+            Ok(serde_wasm_bindgen::to_value(&pos).unwrap())
+        }
+        AppResponse::V0(AppResponseV0::Error(e)) => Err(e),
+        _ => Err("invalid response".to_string()),
+    }
+}
+
 #[cfg(wasmpack_target = "nodejs")]
 #[wasm_bindgen]
 pub async fn file_put_to_private_store(
@@ -1794,7 +1954,7 @@ pub async fn file_put_to_private_store(
     let session_id: u64 = serde_wasm_bindgen::from_value::<u64>(session_id)
         .map_err(|_| "Deserialization error of session_id".to_string())?;
 
-    let upload_id = do_upload_start(session_id, target.clone(), mimetype).await?;
+    let upload_id = do_upload_start(session_id, target.clone(), mimetype, false).await?;
     let target_for_chunk = target.clone();
     let cb_chunk = Closure::new(move |chunk| {
         let chunk_res = serde_wasm_bindgen::from_value::<serde_bytes::ByteBuf>(chunk);
@@ -1992,6 +2152,88 @@ pub async fn orm_start_graph(
     app_request_stream_(request, callback).await
 }
 
+/// Not to be used by frontend directly.
+/// Restarts an ORM subscription after a reconnect, for a client holding the state at `known_heads`.
+/// The first message is a `GraphOrmResumed` with the patches since those heads,
+/// or a `GraphOrmInitial` if the subscription could not be resumed.
+#[wasm_bindgen]
+pub async fn orm_resume_graph(
+    graph_scope: Array,
+    subject_scope: Array,
+    shapeType: JsValue,
+    known_heads: Array,
+    session_id: JsValue,
+    callback: &js_sys::Function,
+) -> Result<JsValue, String> {
+    let graph_scope: Vec<String> = graph_scope.iter().map(|s| s.as_string().unwrap()).collect();
+    let subject_scope: Vec<String> = subject_scope
+        .iter()
+        .map(|s| s.as_string().unwrap())
+        .collect();
+
+    let shape_type: OrmShapeType = serde_wasm_bindgen::from_value::<OrmShapeType>(shapeType)
+        .map_err(|e| format!("Deserialization error of shapeType {e}"))?;
+    let session_id: u64 =
+        serde_wasm_bindgen::from_value::<u64>(session_id.clone()).map_err(|_| {
+            format!(
+                "Deserialization error of session_id {:?} orm_resume_graph",
+                session_id
+            )
+        })?;
+    let mut heads: Vec<ObjectId> = Vec::with_capacity(known_heads.length() as usize);
+    for head in known_heads.iter() {
+        let head = head
+            .as_string()
+            .ok_or("Deserialization error of known_heads".to_string())?;
+        heads.push(decode_digest(&head).map_err(|e| e.to_string())?);
+    }
+
+    let graph_nuris: Vec<NuriV0> = if graph_scope.is_empty() {
+        vec![NuriV0::new_entire_user_site()]
+    } else {
+        let mut graph_nuris = vec![];
+        for gs in graph_scope {
+            if gs.is_empty() || gs == "did:ng:i" {
+                graph_nuris = vec![NuriV0::new_entire_user_site()];
+                break;
+            }
+            graph_nuris.push(
+                NuriV0::new_from(&gs).map_err(|_| "Deserialization error of scope".to_string())?,
+            );
+        }
+        graph_nuris
+    };
+
+    let mut request =
+        AppRequest::new_orm_resume_graph(graph_nuris, subject_scope, shape_type, heads);
+    request.set_session_id(session_id);
+    app_request_stream_(request, callback).await
+}
+
+/// Validates the graph of a document against a shape, without subscribing.
+/// Returns a report with the validity and the violations of each subject.
+#[wasm_bindgen]
+pub async fn validate_shape(
+    session_id: JsValue,
+    nuri: JsValue,
+    shapeType: JsValue,
+) -> Result<JsValue, String> {
+    let session_id: u64 = serde_wasm_bindgen::from_value::<u64>(session_id)
+        .map_err(|_| "Invalid session_id".to_string())?;
+    let shape_type: OrmShapeType = serde_wasm_bindgen::from_value::<OrmShapeType>(shapeType)
+        .map_err(|e| format!("Deserialization error of shapeType {e}"))?;
+    let nuri = if nuri.is_string() {
+        Some(nuri.as_string().unwrap())
+    } else {
+        None
+    };
+
+    let report = nextgraph::local_broker::doc_validate_shape(session_id, nuri, shape_type)
+        .await
+        .map_err(|e: NgError| e.to_string())?;
+    Ok(serde_wasm_bindgen::to_value(&report).unwrap())
+}
+
 /// Not to be used by frontend directly.
 /// Use a useShape hook or OrmSubscription to establish ORM subscriptions
 #[wasm_bindgen]
@@ -2265,6 +2507,167 @@ pub async fn wallet_close(wallet_name: String) -> Result<(), String> {
         .map_err(|e: NgError| e.to_string())
 }
 
+#[wasm_bindgen]
+pub async fn wallet_set_password(wallet_name: String, password: String) -> Result<(), String> {
+    nextgraph::local_broker::wallet_set_password(&wallet_name, password)
+        .await
+        .map_err(|e: NgError| e.to_string())
+}
+
+#[wasm_bindgen]
+pub async fn wallet_regenerate_mnemonic(
+    wallet_name: String,
+    pin: JsValue,
+) -> Result<JsValue, String> {
+    let pin = serde_wasm_bindgen::from_value::<[u8; 4]>(pin)
+        .map_err(|_| "Deserialization error of pin".to_string())?;
+    let mnemonic = nextgraph::local_broker::wallet_regenerate_mnemonic(&wallet_name, pin)
+        .await
+        .map_err(|e: NgError| e.to_string())?;
+    Ok(serde_wasm_bindgen::to_value(&mnemonic).unwrap())
+}
+
+#[wasm_bindgen]
+pub async fn wallet_regenerate_pazzle(
+    wallet_name: String,
+    pin: JsValue,
+) -> Result<JsValue, String> {
+    let pin = serde_wasm_bindgen::from_value::<[u8; 4]>(pin)
+        .map_err(|_| "Deserialization error of pin".to_string())?;
+    let pazzle = nextgraph::local_broker::wallet_regenerate_pazzle(&wallet_name, pin)
+        .await
+        .map_err(|e: NgError| e.to_string())?;
+    Ok(serde_wasm_bindgen::to_value(&pazzle).unwrap())
+}
+
+/// `method` is one of "Pazzle", "Mnemonic" or "Password"
+#[wasm_bindgen]
+pub async fn wallet_remove_login_method(
+    wallet_name: String,
+    method: JsValue,
+) -> Result<(), String> {
+    let method = serde_wasm_bindgen::from_value::<LoginMethodType>(method)
+        .map_err(|_| "Deserialization error of method".to_string())?;
+    nextgraph::local_broker::wallet_remove_login_method(&wallet_name, method)
+        .await
+        .map_err(|e: NgError| e.to_string())
+}
+
+/// `contacts` is an array of [profile_nuri, inbox_nuri]
+#[wasm_bindgen]
+pub async fn wallet_social_recovery_setup(
+    session_id: JsValue,
+    contacts: JsValue,
+    threshold: u8,
+) -> Result<(), String> {
+    let session_id: u64 = serde_wasm_bindgen::from_value::<u64>(session_id)
+        .map_err(|_| "Deserialization error of session_id".to_string())?;
+    let contacts = serde_wasm_bindgen::from_value::<Vec<(String, String)>>(contacts)
+        .map_err(|_| "Deserialization error of contacts".to_string())?;
+    nextgraph::local_broker::wallet_social_recovery_setup(session_id, contacts, threshold)
+        .await
+        .map_err(|e: NgError| e.to_string())
+}
+
+/// returns [request_code, verification_code, key]
+#[wasm_bindgen]
+pub fn wallet_social_recovery_request(wallet: JsValue) -> Result<JsValue, String> {
+    let encrypted_wallet = serde_wasm_bindgen::from_value::<Wallet>(wallet)
+        .map_err(|_| "Deserialization error of wallet".to_string())?;
+    let res = nextgraph::local_broker::wallet_social_recovery_request(&encrypted_wallet);
+    Ok(serde_wasm_bindgen::to_value(&res).unwrap())
+}
+
+#[wasm_bindgen]
+pub async fn wallet_social_recovery_answer(
+    session_id: JsValue,
+    request_code: String,
+    verification_code: String,
+) -> Result<String, String> {
+    let session_id: u64 = serde_wasm_bindgen::from_value::<u64>(session_id)
+        .map_err(|_| "Deserialization error of session_id".to_string())?;
+    nextgraph::local_broker::wallet_social_recovery_answer(
+        session_id,
+        request_code,
+        verification_code,
+    )
+    .await
+    .map_err(|e: NgError| e.to_string())
+}
+
+#[wasm_bindgen]
+pub fn wallet_open_with_recovery_shares(
+    wallet: JsValue,
+    key: JsValue,
+    response_codes: JsValue,
+) -> Result<JsValue, JsValue> {
+    let encrypted_wallet = serde_wasm_bindgen::from_value::<Wallet>(wallet)
+        .map_err(|_| "Deserialization error of wallet")?;
+    let key = serde_wasm_bindgen::from_value::<PrivKey>(key)
+        .map_err(|_| "Deserialization error of key")?;
+    let response_codes = serde_wasm_bindgen::from_value::<Vec<String>>(response_codes)
+        .map_err(|_| "Deserialization error of response_codes")?;
+    let res = nextgraph::local_broker::wallet_open_with_recovery_shares(
+        &encrypted_wallet,
+        key,
+        response_codes,
+    );
+    match res {
+        Ok(r) => Ok(r
+            .serialize(&serde_wasm_bindgen::Serializer::new().serialize_maps_as_objects(true))
+            .unwrap()),
+        Err(e) => Err(e.to_string().into()),
+    }
+}
+
+/// `accepted` is the list of the IDs of the optional access requests of the manifest that the user accepted.
+/// returns the grants
+#[wasm_bindgen]
+pub async fn app_grant_access(
+    wallet_name: String,
+    manifest: JsValue,
+    accepted: JsValue,
+) -> Result<JsValue, String> {
+    let manifest = serde_wasm_bindgen::from_value::<AppManifest>(manifest)
+        .map_err(|_| "Deserialization error of manifest".to_string())?;
+    let accepted = serde_wasm_bindgen::from_value::<Vec<String>>(accepted)
+        .map_err(|_| "Deserialization error of accepted".to_string())?;
+    let grants = nextgraph::local_broker::app_grant_access(&wallet_name, manifest, accepted)
+        .await
+        .map_err(|e: NgError| e.to_string())?;
+    Ok(serde_wasm_bindgen::to_value(&grants).unwrap())
+}
+
+#[wasm_bindgen]
+pub async fn app_revoke_access(
+    wallet_name: String,
+    app: String,
+    grant_id: String,
+) -> Result<(), String> {
+    nextgraph::local_broker::app_revoke_access(&wallet_name, app, grant_id)
+        .await
+        .map_err(|e: NgError| e.to_string())
+}
+
+#[wasm_bindgen]
+pub async fn app_session_bind(session_id: JsValue, app: String) -> Result<(), String> {
+    let session_id: u64 = serde_wasm_bindgen::from_value::<u64>(session_id)
+        .map_err(|_| "Deserialization error of session_id".to_string())?;
+    nextgraph::local_broker::app_session_bind(session_id, app)
+        .await
+        .map_err(|e: NgError| e.to_string())
+}
+
+#[wasm_bindgen]
+pub async fn app_list_access(wallet_name: String) -> Result<JsValue, String> {
+    let res = nextgraph::local_broker::app_list_access(&wallet_name)
+        .await
+        .map_err(|e: NgError| e.to_string())?;
+    Ok(res
+        .serialize(&serde_wasm_bindgen::Serializer::new().serialize_maps_as_objects(true))
+        .unwrap())
+}
+
 #[wasm_bindgen]
 pub async fn user_connect(
     client_info: JsValue,
@@ -313,6 +313,11 @@ export class OrmSubscription<T extends BaseType> {
         const data = message?.V0;
         if (data?.GraphOrmInitial) {
             this.handleInitialResponse(data.GraphOrmInitial);
+        } else if (data?.GraphOrmResumed) {
+            const [patches, subscriptionId] = data.GraphOrmResumed;
+            this.subscriptionId = subscriptionId;
+            this.onBackendUpdate(patches);
+            this.resolveReady();
         } else if (data?.GraphOrmUpdate) {
             this.onBackendUpdate(data.GraphOrmUpdate);
         } else {
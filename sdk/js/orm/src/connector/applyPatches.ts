@@ -16,7 +16,7 @@ export type Patch = {
     path: string;
     valType?: string & {};
     value?: unknown;
-} & (SetAddPatch | SetRemovePatch | RemovePatch | LiteralAddPatch);
+} & (SetAddPatch | SetRemovePatch | RemovePatch | LiteralAddPatch | TextPatch);
 
 /** @ignore */
 export interface SetAddPatch {
@@ -63,6 +63,26 @@ export interface LiteralAddPatch {
     value: string | number | boolean | object;
 }
 
+/** @ignore */
+export interface TextPatch {
+    /** Mutation kind applied to the text. */
+    op: "add" | "remove";
+    /** The last path segment is the (UTF-16) offset in the text at the rest of the path. */
+    valType: "text";
+    /** The string to insert (`add`) or the number of characters to delete (`remove`). */
+    value: string | number;
+}
+
+/** Inserts or deletes characters of `text` at `offset`, as described by a text patch. */
+function spliceText(text: unknown, offset: number, patch: Patch): string {
+    const current = typeof text === "string" ? text : "";
+    const before = current.slice(0, offset);
+    if (patch.op === "add") {
+        return before + String(patch.value) + current.slice(offset);
+    }
+    return before + current.slice(offset + Number(patch.value));
+}
+
 function isPrimitive(v: unknown): v is string | number | boolean {
     return (
         typeof v === "string" || typeof v === "number" || typeof v === "boolean"
@@ -121,6 +141,7 @@ function findInSetBySegment(set: Set<any>, seg: string): any | undefined {
  * @param currentState The object before the patch
  * @param patches An array of patches to apply to the object.
  * @param ensurePathExists If true, create nested objects along the path if the path does not exist.
+ * @returns The resulting state. It is `currentState` itself, unless it is a text that was patched.
  *
  * Note: When creating new objects, this function pre-scans upcoming patches to find `@id` and `@graph`
  *       values that will be assigned to the object. This prevents the signal library's propGenerator
@@ -132,7 +153,8 @@ export function applyPatches(
     patches: Patch[],
     ormType: "set" | "discrete",
     ensurePathExists: boolean = false
-) {
+): any {
+    let root: any = currentState;
     for (let patchIndex = 0; patchIndex < patches.length; patchIndex++) {
         const patch = patches[patchIndex];
         if (!patch.path.startsWith("/")) continue;
@@ -142,13 +164,28 @@ export function applyPatches(
             .filter(Boolean)
             .map(decodePathSegment);
 
+        // Text deltas: the last segment is the offset in the text found at the rest of the path.
+        let textOffset: number | undefined = undefined;
+        if (patch.valType === "text") {
+            textOffset = Number(pathParts.pop());
+            if (!Number.isInteger(textOffset) || textOffset < 0) {
+                console.warn("[applyPatches] Invalid text offset", patch);
+                continue;
+            }
+            if (pathParts.length === 0) {
+                // The whole document is a text.
+                root = spliceText(root, textOffset, patch);
+                continue;
+            }
+        }
+
         if (pathParts.length === 0) {
             // Actually, this should mean replace..
             console.warn("[applyPatches] No path specified for patch", patch);
             continue;
         }
         const lastKey = pathParts[pathParts.length - 1];
-        let parentVal: any = currentState;
+        let parentVal: any = root;
         let parentMissing = false;
         // Traverse only intermediate segments (to leaf object at path)
         for (let i = 0; i < pathParts.length - 1; i++) {
@@ -273,6 +310,11 @@ export function applyPatches(
             continue;
         }
 
+        if (textOffset !== undefined) {
+            parentVal[key] = spliceText(parentVal[key], textOffset, patch);
+            continue;
+        }
+
         // Handle primitive set additions
         if (patch.op === "add" && patch.valType === "set") {
             const existing = parentVal[key];
@@ -384,6 +426,7 @@ export function applyPatches(
             continue;
         }
     }
+    return root;
 }
 
 /**
@@ -395,15 +438,15 @@ export function applyPatchesToDeepSignal(
     currentState: object,
     patch: Patch[],
     ormType: "set" | "discrete"
-) {
-    batch(() => {
+): any {
+    return batch(() =>
         applyPatches(
             currentState as Record<string, any>,
             patch,
             ormType,
             false
-        );
-    });
+        )
+    );
 }
 
 function decodePathSegment(segment: string): string {
@@ -901,3 +901,140 @@ describe("applyDiff - array operations", () => {
         expect(obj).toEqual([1, 3, 4, 5]);
     });
 });
+
+describe("applyDiff - text operations", () => {
+    test("inserts and deletes text in a text document", () => {
+        let text: any = "hello world";
+        text = applyPatches(
+            text,
+            [
+                { op: "remove", valType: "text", path: "/5", value: 6 },
+                { op: "add", valType: "text", path: "/5", value: ", there" },
+                { op: "add", valType: "text", path: "/0", value: "Oh " },
+            ],
+            "discrete"
+        );
+        expect(text).toBe("Oh hello, there");
+    });
+    test("offsets count UTF-16 code units", () => {
+        const text = applyPatches(
+            "a😀b" as any,
+            [{ op: "add", valType: "text", path: "/3", value: "c" }],
+            "discrete"
+        );
+        expect(text).toBe("a😀cb");
+    });
+    test("edits a nested text in place", () => {
+        const obj: any = { note: { body: "abc" } };
+        const result = applyPatches(
+            obj,
+            [
+                {
+                    op: "add",
+                    valType: "text",
+                    path: "/note/body/3",
+                    value: "d",
+                },
+                {
+                    op: "remove",
+                    valType: "text",
+                    path: "/note/body/0",
+                    value: 1,
+                },
+            ],
+            "discrete"
+        );
+        expect(result).toBe(obj);
+        expect(obj.note.body).toBe("bcd");
+    });
+    test("ignores invalid offsets", () => {
+        const obj: any = { body: "abc" };
+        applyPatches(
+            obj,
+            [{ op: "add", valType: "text", path: "/body/x", value: "d" }],
+            "discrete"
+        );
+        expect(obj.body).toBe("abc");
+    });
+});
+
+describe("applyDiff - XML operations", () => {
+    const doc = () => [
+        {
+            type: "paragraph",
+            attrs: { align: "left" },
+            content: [{ type: "text", text: "Hello" }],
+        },
+    ];
+    test("inserts and removes nodes", () => {
+        const state: any = doc();
+        applyPatches(
+            state,
+            [
+                {
+                    op: "add",
+                    path: "/0/content/1",
+                    value: { type: "bold", attrs: {}, content: [] },
+                },
+                {
+                    op: "add",
+                    path: "/-",
+                    value: { type: "paragraph", attrs: {}, content: [] },
+                },
+            ],
+            "discrete"
+        );
+        expect(state[0].content[1].type).toBe("bold");
+        expect(state.length).toBe(2);
+
+        applyPatches(
+            state,
+            [{ op: "remove", path: "/0/content/0" }],
+            "discrete"
+        );
+        expect(state[0].content).toEqual([
+            { type: "bold", attrs: {}, content: [] },
+        ]);
+    });
+    test("sets and removes attributes", () => {
+        const state: any = doc();
+        applyPatches(
+            state,
+            [
+                { op: "add", path: "/0/attrs/align", value: "right" },
+                { op: "add", path: "/0/attrs/id", value: "p1" },
+            ],
+            "discrete"
+        );
+        expect(state[0].attrs).toEqual({ align: "right", id: "p1" });
+
+        applyPatches(
+            state,
+            [{ op: "remove", path: "/0/attrs/align" }],
+            "discrete"
+        );
+        expect(state[0].attrs).toEqual({ id: "p1" });
+    });
+    test("edits the text of a text node", () => {
+        const state: any = doc();
+        applyPatches(
+            state,
+            [
+                {
+                    op: "add",
+                    valType: "text",
+                    path: "/0/content/0/text/5",
+                    value: " world",
+                },
+                {
+                    op: "remove",
+                    valType: "text",
+                    path: "/0/content/0/text/0",
+                    value: 1,
+                },
+            ],
+            "discrete"
+        );
+        expect(state[0].content[0].text).toBe("ello world");
+    });
+});
@@ -0,0 +1,77 @@
+// Copyright (c) 2022-2025 Niko Bonnieure, Par le Peuple, NextGraph.org developers
+// All rights reserved.
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE2 or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+use std::env::{args, current_dir};
+use std::io::{Error, ErrorKind};
+
+use nextgraph::local_broker::{
+    init_local_broker, session_start, session_stop, wallet_close, wallet_get,
+    wallet_open_with_pazzle, wallet_was_opened, LocalBrokerConfig, SessionConfig,
+};
+use nextgraph::sparql_endpoint::sparql_endpoint_start;
+
+fn usage() -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        "usage: sparql_endpoint <wallet_name> <pazzle as comma separated numbers> <4 digits PIN> [port]",
+    )
+}
+
+#[async_std::main]
+async fn main() -> std::io::Result<()> {
+    let args: Vec<String> = args().collect();
+    if args.len() < 4 {
+        return Err(usage());
+    }
+    let wallet_name = args[1].clone();
+    let pazzle = args[2]
+        .split(',')
+        .map(|n| n.trim().parse::<u8>())
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| usage())?;
+    let pin: [u8; 4] = args[3]
+        .chars()
+        .map(|c| c.to_digit(10).map(|d| d as u8))
+        .collect::<Option<Vec<u8>>>()
+        .and_then(|pin| pin.try_into().ok())
+        .ok_or_else(usage)?;
+    let port = match args.get(4) {
+        None => 0,
+        Some(port) => port.parse::<u16>().map_err(|_| usage())?,
+    };
+
+    // the wallet must have been saved before in the `.ng/example` folder, see the `persistent` example
+    let mut current_path = current_dir()?;
+    current_path.push(".ng");
+    current_path.push("example");
+    init_local_broker(Box::new(move || {
+        LocalBrokerConfig::BasePath(current_path.clone())
+    }))
+    .await;
+
+    let wallet = wallet_get(&wallet_name).await?;
+    let opened_wallet = wallet_open_with_pazzle(&wallet, pazzle, pin)?;
+    let user_id = opened_wallet.personal_identity();
+    let _client = wallet_was_opened(opened_wallet).await?;
+    let session = session_start(SessionConfig::new_save(&user_id, &wallet_name)).await?;
+
+    let endpoint = sparql_endpoint_start(session.session_id, port).await?;
+    println!("SPARQL endpoint: http://{}/sparql", endpoint.addr());
+    println!("Graph Store endpoint: http://{}/store", endpoint.addr());
+    println!("Authorization: Bearer {}", endpoint.token());
+    println!("Press Enter to stop");
+    let mut line = String::new();
+    async_std::io::stdin().read_line(&mut line).await?;
+
+    endpoint.stop().await;
+    session_stop(&user_id).await?;
+    wallet_close(&wallet_name).await?;
+
+    Ok(())
+}
@@ -79,6 +79,9 @@
 
 pub mod local_broker;
 
+#[cfg(all(not(target_family = "wasm"), not(docsrs)))]
+pub mod sparql_endpoint;
+
 pub mod repo {
     pub use ng_repo::*;
 }
@@ -18,8 +18,9 @@ use async_std::sync::{Arc, Condvar, Mutex, RwLock};
 use futures::channel::mpsc;
 use futures::{SinkExt, StreamExt};
 use lazy_static::lazy_static;
-use ng_net::orm::{OrmPatches, OrmShapeType};
-use ng_oxigraph::oxrdf::Quad;
+use ng_net::orm::{OrmPatches, OrmShapeType, OrmValidationReport};
+use ng_oxigraph::oxrdf::{Quad, Triple};
+use ng_oxigraph::oxrdfio::{RdfFormat, RdfSerializer};
 use once_cell::sync::Lazy;
 use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str};
 use qrcode::{render::svg, QrCode};
@@ -39,10 +40,12 @@ use ng_repo::utils::{derive_key, encrypt_in_place, generate_keypair};
 use ng_net::app_protocol::*;
 use ng_net::broker::*;
 use ng_net::connection::{AppConfig, ClientConfig, IConnect, NoiseFSM, StartConfig};
+use ng_net::permissions::{AccessGrantV0, AppAccess, AppManifest, SignedAppAccess};
 use ng_net::types::*;
 use ng_net::utils::{spawn_and_log_error, Receiver, ResultSend, Sender};
 use ng_net::{actor::*, actors::admin::*};
 
+use ng_verifier::graph_import::GraphImportRun;
 use ng_verifier::orm::graph::types::ShapeIri;
 use ng_verifier::types::*;
 use ng_verifier::verifier::Verifier;
@@ -50,7 +53,9 @@ use ng_verifier::verifier::Verifier;
 use ng_wallet::bip39::encode_mnemonic;
 use ng_wallet::emojis::{display_pazzle, encode_pazzle};
 use ng_wallet::{
-    create_wallet_first_step_v0, create_wallet_second_step_v0, display_mnemonic, types::*,
+    add_access_grants, create_wallet_first_step_v0, create_wallet_second_step_v0, display_mnemonic,
+    regenerate_wallet_mnemonic, regenerate_wallet_pazzle, remove_access_grant,
+    remove_wallet_login_method, set_wallet_password, split_wallet_master_key, types::*,
 };
 
 #[cfg(not(target_family = "wasm"))]
@@ -392,6 +397,8 @@ struct Session {
     #[allow(dead_code)]
     last_wallet_nonce: u64,
     verifier: Verifier,
+    /// set when the session was bound to a third-party app with [app_session_bind]
+    app_access: Option<AppAccess>,
 }
 
 impl SessionConfig {
@@ -999,6 +1006,51 @@ impl LocalBroker {
         Ok((session_id, session))
     }
 
+    /// Starts a session on the remote broker of a headless LocalBroker.
+    /// `access` are the grants of the third-party app that the session is for, signed by the user with [app_sign_access].
+    async fn start_headless_session(
+        &mut self,
+        user_id: UserId,
+        access: Option<SignedAppAccess>,
+    ) -> Result<SessionInfo, NgError> {
+        self.err_if_not_headless()?;
+        // establish the connection if not already there?
+
+        self.connect_remote_broker().await?;
+
+        let session = HeadlessSession {
+            user_id: user_id.clone(),
+        };
+        let mut session_info = self.add_headless_session(session)?;
+
+        let request = AppSessionStart::V0(AppSessionStartV0 {
+            session_id: session_info.session_id,
+            credentials: None,
+            user_id,
+            detach: true,
+            access,
+        });
+
+        let res = self.send_request_headless(request).await;
+
+        if res.is_err() {
+            let _ = self.remove_headless_session(&session_info.user);
+            return Err(res.unwrap_err());
+        }
+
+        if let Ok(AppResponse::V0(AppResponseV0::SessionStart(AppSessionStartResponse::V0(
+            response,
+        )))) = res
+        {
+            session_info.private_store_id = NuriV0::to_store_nuri_string(&response.private_store);
+            session_info.protected_store_id =
+                NuriV0::to_store_nuri_string(&response.protected_store);
+            session_info.public_store_id = NuriV0::to_store_nuri_string(&response.public_store);
+        }
+
+        Ok(session_info)
+    }
+
     #[allow(dead_code)]
     fn get_remote_session(&self, session_id: u64) -> Result<&RemoteSession, NgError> {
         let _ = Self::is_remote_session(session_id)
@@ -1441,6 +1493,7 @@ impl LocalBroker {
                     peer_key: session.peer_key.clone(),
                     last_wallet_nonce: session.last_wallet_nonce,
                     verifier,
+                    app_access: None,
                 };
                 Ok(session)
             }
@@ -2311,6 +2364,199 @@ pub async fn wallet_was_opened(wallet: SensitiveWallet) -> Result<ClientV0, NgEr
     broker.wallet_was_opened(wallet).await
 }
 
+/// Changes the login methods of a wallet that is opened, then replaces and saves its encrypted Wallet.
+async fn wallet_update_login_methods<T>(
+    wallet_name: &String,
+    update: impl FnOnce(&Wallet, &SensitiveWallet) -> Result<(Wallet, T), NgWalletError>,
+) -> Result<T, NgError> {
+    let mut broker = match LOCAL_BROKER.get() {
+        None | Some(Err(_)) => return Err(NgError::LocalBrokerNotInitialized),
+        Some(Ok(broker)) => broker.write().await,
+    };
+    let opened_wallet = broker
+        .opened_wallets
+        .get(wallet_name)
+        .ok_or(NgError::WalletNotFound)?;
+    let lws = broker
+        .wallets
+        .get(wallet_name)
+        .ok_or(NgError::WalletNotFound)?;
+    let (wallet, res) = update(&lws.wallet, &opened_wallet.wallet)?;
+    let in_memory = lws.in_memory;
+    broker.wallets.get_mut(wallet_name).unwrap().wallet = wallet;
+    if !in_memory {
+        LocalBroker::wallet_save(&mut broker).await?;
+    }
+    Ok(res)
+}
+
+/// Adds a password to an opened wallet, or changes its password.
+///
+/// The wallet must have been opened with one of its login methods (with [wallet_open_with_pazzle_words] for example), and [wallet_was_opened].
+pub async fn wallet_set_password(wallet_name: &String, password: String) -> Result<(), NgError> {
+    wallet_update_login_methods(wallet_name, |wallet, opened_wallet| {
+        Ok((set_wallet_password(wallet, opened_wallet, password)?, ()))
+    })
+    .await
+}
+
+/// Generates a new mnemonic for an opened wallet, that replaces the previous one if any. Returns the words of the new mnemonic.
+///
+/// The pin will have to be entered together with the mnemonic when opening the wallet.
+pub async fn wallet_regenerate_mnemonic(
+    wallet_name: &String,
+    pin: [u8; 4],
+) -> Result<Vec<String>, NgError> {
+    wallet_update_login_methods(wallet_name, |wallet, opened_wallet| {
+        let (wallet, mnemonic) = regenerate_wallet_mnemonic(wallet, opened_wallet, pin)?;
+        Ok((wallet, display_mnemonic(&mnemonic)))
+    })
+    .await
+}
+
+/// Generates a new pazzle for an opened wallet, that replaces the previous one if any. Returns the new pazzle.
+///
+/// The pin will have to be entered together with the pazzle when opening the wallet.
+pub async fn wallet_regenerate_pazzle(
+    wallet_name: &String,
+    pin: [u8; 4],
+) -> Result<Vec<u8>, NgError> {
+    wallet_update_login_methods(wallet_name, |wallet, opened_wallet| {
+        Ok(regenerate_wallet_pazzle(wallet, opened_wallet, pin)?)
+    })
+    .await
+}
+
+/// Removes a login method (pazzle, mnemonic or password) of an opened wallet. At least one login method must remain.
+pub async fn wallet_remove_login_method(
+    wallet_name: &String,
+    method: LoginMethodType,
+) -> Result<(), NgError> {
+    wallet_update_login_methods(wallet_name, |wallet, opened_wallet| {
+        Ok((
+            remove_wallet_login_method(wallet, opened_wallet, method)?,
+            (),
+        ))
+    })
+    .await
+}
+
+/// Splits the master key of the wallet of a session into shares, and sends one share to each contact, for a social recovery of the wallet.
+///
+/// `contacts` are pairs of profile nuri and inbox nuri. `threshold` of those contacts will be needed to recover the wallet.
+/// The wallet must have been opened with one of its login methods.
+pub async fn wallet_social_recovery_setup(
+    session_id: u64,
+    contacts: Vec<(String, String)>,
+    threshold: u8,
+) -> Result<(), NgError> {
+    if contacts.len() > u8::MAX as usize {
+        return Err(NgError::InvalidArgument);
+    }
+    let broker = match LOCAL_BROKER.get() {
+        None | Some(Err(_)) => return Err(NgError::LocalBrokerNotInitialized),
+        Some(Ok(broker)) => broker.read().await,
+    };
+
+    match &broker.config {
+        LocalBrokerConfig::Headless(_) => return Err(NgError::LocalBrokerIsHeadless),
+        _ => {
+            let (real_session_id, is_remote) = broker.get_real_session_id_for_mut(session_id)?;
+            if is_remote {
+                return Err(NgError::NotImplemented);
+            }
+            let session = broker.opened_sessions_list[real_session_id]
+                .as_ref()
+                .ok_or(NgError::SessionNotFound)?;
+            let opened_wallet = broker
+                .opened_wallets
+                .get(&session.config.wallet_name())
+                .ok_or(NgError::WalletNotFound)?;
+            let shares =
+                split_wallet_master_key(&opened_wallet.wallet, threshold, contacts.len() as u8)?;
+            session
+                .verifier
+                .send_wallet_recovery_shares(shares, contacts)
+                .await?;
+            Ok(())
+        }
+    }
+}
+
+/// Starts the social recovery of a wallet that lost all its login methods.
+///
+/// Returns the request to give to the contacts (as a TextCode), the verification code to read to each of them by another channel,
+/// and the private key that will be needed to open their responses with [wallet_open_with_recovery_shares].
+pub fn wallet_social_recovery_request(wallet: &Wallet) -> (String, String, PrivKey) {
+    let (key, pub_key) = generate_keypair();
+    let request = NgQRCodeWalletShareRequestV0 {
+        wallet_id: wallet.id(),
+        key: pub_key,
+    };
+    let verification_code = request.verification_code();
+    (
+        NgQRCode::WalletShareRequestV0(request).to_code(),
+        verification_code,
+        key,
+    )
+}
+
+/// Answers the social recovery request of a contact, if we hold a share of their wallet. Returns the response as a TextCode.
+///
+/// `verification_code` must have been given by the contact by another channel (in person, or on the phone),
+/// so we know the request really comes from them.
+pub async fn wallet_social_recovery_answer(
+    session_id: u64,
+    request_code: String,
+    verification_code: String,
+) -> Result<String, NgError> {
+    let request = match NgQRCode::from_code(request_code)? {
+        NgQRCode::WalletShareRequestV0(request) => request,
+        _ => return Err(NgError::InvalidArgument),
+    };
+    let broker = match LOCAL_BROKER.get() {
+        None | Some(Err(_)) => return Err(NgError::LocalBrokerNotInitialized),
+        Some(Ok(broker)) => broker.read().await,
+    };
+
+    match &broker.config {
+        LocalBrokerConfig::Headless(_) => return Err(NgError::LocalBrokerIsHeadless),
+        _ => {
+            let (real_session_id, is_remote) = broker.get_real_session_id_for_mut(session_id)?;
+            if is_remote {
+                return Err(NgError::NotImplemented);
+            }
+            let session = broker.opened_sessions_list[real_session_id]
+                .as_ref()
+                .ok_or(NgError::SessionNotFound)?;
+            let response = session
+                .verifier
+                .answer_wallet_recovery_request(&request, &verification_code)?;
+            Ok(NgQRCode::WalletShareResponseV0(response).to_code())
+        }
+    }
+}
+
+/// Opens a wallet with the responses of the contacts to a [wallet_social_recovery_request], and the key that was returned by it.
+///
+/// If you are opening a wallet that is already known to the LocalBroker, you must then call [wallet_was_opened].
+/// Otherwise, if you are importing, then you must call [wallet_import].
+/// A new password should then be set with [wallet_set_password].
+pub fn wallet_open_with_recovery_shares(
+    wallet: &Wallet,
+    key: PrivKey,
+    response_codes: Vec<String>,
+) -> Result<SensitiveWallet, NgError> {
+    let shares = response_codes
+        .into_iter()
+        .map(|code| match NgQRCode::from_code(code)? {
+            NgQRCode::WalletShareResponseV0(response) => response.open(&key),
+            _ => Err(NgError::InvalidArgument),
+        })
+        .collect::<Result<Vec<_>, NgError>>()?;
+    Ok(ng_wallet::open_wallet_with_recovery_shares(wallet, shares)?)
+}
+
 /// Starts a session with the LocalBroker. The type of verifier is selected at this moment.
 ///
 /// The session is valid even if there is no internet. The local data will be used in this case.
@@ -2326,36 +2572,7 @@ pub async fn session_start(config: SessionConfig) -> Result<SessionInfo, NgError
         LocalBrokerConfig::Headless(_) => {
             match config {
                 SessionConfig::HeadlessV0(user_id) => {
-
-                    broker.err_if_not_headless()?;
-                    // establish the connection if not already there?
-
-                    broker.connect_remote_broker().await?;
-
-                    let session = HeadlessSession { user_id: user_id.clone() };
-                    let mut session_info = broker.add_headless_session(session)?;
-
-                    let request = AppSessionStart::V0(AppSessionStartV0{
-                        session_id: session_info.session_id,
-                        credentials: None,
-                        user_id,
-                        detach: true
-                    });
-
-                    let res = broker.send_request_headless(request).await;
-
-                    if res.is_err() {
-                        let _ = broker.remove_headless_session(&session_info.user);
-                        return Err(res.unwrap_err())
-                    }
-
-                    if let Ok(AppResponse::V0(AppResponseV0::SessionStart(AppSessionStartResponse::V0(response)))) = res {
-                        session_info.private_store_id = NuriV0::to_store_nuri_string(&response.private_store);
-                        session_info.protected_store_id = NuriV0::to_store_nuri_string(&response.protected_store);
-                        session_info.public_store_id = NuriV0::to_store_nuri_string(&response.public_store);
-                    }
-
-                    Ok(session_info)
+                    broker.start_headless_session(user_id, None).await
                 },
                 _ => panic!("don't call session_start with a SessionConfig different from HeadlessV0 when the LocalBroker is configured for Headless")
             }
@@ -2795,23 +3012,57 @@ pub async fn doc_fetch_repo_subscribe(
 //     session.verifier.doc_fetch_private(true).await
 // }
 
-pub async fn rdf_dump(session_id: u64) -> Result<String, String> {
+/// Exports the graph targeted by the nuri in the RDF format given as a media type (text/turtle, application/trig, application/n-quads, application/rdf+xml...).
+///
+/// The nuri can be a document (optionally with a branch or a set of heads), a store with `entire_store`, or the entire user site.
+/// The serialization is received as `FileBinary` chunks, followed by an `EndOfStream`.
+pub async fn rdf_export(
+    session_id: u64,
+    nuri: NuriV0,
+    content_type: String,
+) -> Result<(Receiver<AppResponse>, CancelFn), NgError> {
     let request = AppRequest::V0(AppRequestV0 {
-        command: AppRequestCommandV0::new_rdf_dump(),
-        nuri: NuriV0::new_entire_user_site(),
-        payload: None,
+        command: AppRequestCommandV0::new_rdf_export(),
+        nuri,
+        payload: Some(AppRequestPayload::new_rdf_export(content_type)),
         session_id,
     });
+    app_request_stream(request).await
+}
 
-    let res = app_request(request)
-        .await
-        .map_err(|e: NgError| e.to_string())?;
+/// Dumps the entire user site in N-Quads.
+pub async fn rdf_dump(session_id: u64) -> Result<String, String> {
+    let (mut stream, _cancel) = rdf_export(
+        session_id,
+        NuriV0::new_entire_user_site(),
+        RdfFormat::NQuads.media_type().to_string(),
+    )
+    .await
+    .map_err(|e: NgError| e.to_string())?;
+
+    let mut dump = vec![];
+    while let Some(AppResponse::V0(res)) = stream.next().await {
+        match res {
+            AppResponseV0::FileBinary(chunk) => dump.extend(chunk),
+            AppResponseV0::EndOfStream => break,
+            AppResponseV0::Error(e) => return Err(e),
+            _ => return Err("invalid response".to_string()),
+        }
+    }
+    String::from_utf8(dump).map_err(|_| "invalid response".to_string())
+}
 
-    let AppResponse::V0(res) = res;
-    match res {
-        AppResponseV0::Text(s) => Ok(s),
-        _ => Err("invalid response".to_string()),
+/// Serializes the triples of an [`AppResponseV0::Graph`] response in the given RDF format (JSON-LD, Turtle...).
+pub fn graph_response_to_rdf(graph: &[u8], format: RdfFormat) -> Result<Vec<u8>, NgError> {
+    let triples: Vec<Triple> =
+        serde_bare::from_slice(graph).map_err(|_| NgError::SerializationError)?;
+    let mut writer = RdfSerializer::from_format(format).serialize_to_write(Vec::new());
+    for triple in &triples {
+        writer
+            .write_triple(triple)
+            .map_err(|_| NgError::SerializationError)?;
     }
+    writer.finish().map_err(|_| NgError::SerializationError)
 }
 
 pub async fn doc_sparql_update(
@@ -2844,6 +3095,158 @@ pub async fn doc_sparql_update(
     }
 }
 
+/// Runs a SPARQL query on the nuri (or on the entire user site if None), and streams the results.
+///
+/// The results are serialized in `format` (iana media type, defaults to SPARQL-JSON or N-Triples),
+/// and received as `FileBinary` chunks, followed by an `EndOfStream`.
+pub async fn doc_sparql_query_stream(
+    session_id: u64,
+    sparql: String,
+    base: Option<String>,
+    format: Option<String>,
+    nuri: Option<NuriV0>,
+) -> Result<(Receiver<AppResponse>, CancelFn), NgError> {
+    let request = AppRequest::V0(AppRequestV0 {
+        command: AppRequestCommandV0::new_read_query_stream(),
+        nuri: nuri.unwrap_or_else(NuriV0::new_entire_user_site),
+        payload: Some(AppRequestPayload::new_sparql_query_with_format(
+            sparql, base, format,
+        )),
+        session_id,
+    });
+    app_request_stream(request).await
+}
+
+/// Evaluates a SPARQL query on the nuri (or on the entire user site if None), and returns the JSON explanation of its plan,
+/// with the duration and the number of results of each operator. The results themselves are discarded.
+pub async fn doc_sparql_query_explain(
+    session_id: u64,
+    sparql: String,
+    base: Option<String>,
+    nuri: Option<NuriV0>,
+) -> Result<String, String> {
+    let request = AppRequest::V0(AppRequestV0 {
+        command: AppRequestCommandV0::new_read_query_explain(),
+        nuri: nuri.unwrap_or_else(NuriV0::new_entire_user_site),
+        payload: Some(AppRequestPayload::new_sparql_query(sparql, base)),
+        session_id,
+    });
+
+    let res = app_request(request)
+        .await
+        .map_err(|e: NgError| e.to_string())?;
+    match res {
+        AppResponse::V0(AppResponseV0::Error(e)) => Err(e),
+        AppResponse::V0(AppResponseV0::QueryExplanation(json)) => Ok(json),
+        _ => Err(NgError::InvalidResponse.to_string()),
+    }
+}
+
+/// Searches the documents of a store (or of the entire user site if None) containing all the words of the query.
+///
+/// Returns the Nuris of the documents, ranked by decreasing score, with a snippet of the matching text.
+pub async fn doc_text_search(
+    session_id: u64,
+    query: String,
+    limit: Option<u32>,
+    nuri: Option<NuriV0>,
+) -> Result<Vec<TextSearchResult>, String> {
+    let request = AppRequest::V0(AppRequestV0 {
+        command: AppRequestCommandV0::new_text_search(),
+        nuri: nuri.unwrap_or_else(NuriV0::new_entire_user_site),
+        payload: Some(AppRequestPayload::new_text_search(query, limit)),
+        session_id,
+    });
+
+    let res = app_request(request)
+        .await
+        .map_err(|e: NgError| e.to_string())?;
+    match res {
+        AppResponse::V0(AppResponseV0::Error(e)) => Err(e),
+        AppResponse::V0(AppResponseV0::TextSearchResults(results)) => Ok(results),
+        _ => Err(NgError::InvalidResponse.to_string()),
+    }
+}
+
+/// Renews the read caps, the topics and the write cap of a document. The excluded users keep access to its history,
+/// but cannot read nor write the commits added after the refresh.
+pub async fn doc_refresh_caps(
+    session_id: u64,
+    nuri: String,
+    exclude: Vec<UserId>,
+) -> Result<(), String> {
+    let nuri = NuriV0::new_from(&nuri).map_err(|e| e.to_string())?;
+    let request = AppRequest::V0(AppRequestV0 {
+        command: AppRequestCommandV0::new_refresh_caps(),
+        nuri,
+        payload: Some(AppRequestPayload::new_refresh_caps(exclude)),
+        session_id,
+    });
+
+    let res = app_request(request)
+        .await
+        .map_err(|e: NgError| e.to_string())?;
+    match res {
+        AppResponse::V0(AppResponseV0::Error(e)) => Err(e),
+        AppResponse::V0(AppResponseV0::Ok) => Ok(()),
+        _ => Err(NgError::InvalidResponse.to_string()),
+    }
+}
+
+async fn doc_member_update(
+    session_id: u64,
+    nuri: String,
+    payload: AppRequestPayload,
+) -> Result<(), String> {
+    let nuri = NuriV0::new_from(&nuri).map_err(|e| e.to_string())?;
+    let request = AppRequest::V0(AppRequestV0 {
+        command: AppRequestCommandV0::new_member_update(),
+        nuri,
+        payload: Some(payload),
+        session_id,
+    });
+
+    let res = app_request(request)
+        .await
+        .map_err(|e: NgError| e.to_string())?;
+    match res {
+        AppResponse::V0(AppResponseV0::Error(e)) => Err(e),
+        AppResponse::V0(AppResponseV0::Ok) => Ok(()),
+        _ => Err(NgError::InvalidResponse.to_string()),
+    }
+}
+
+/// Adds a member to a document. Without permissions, the member is a reader.
+pub async fn doc_add_member(
+    session_id: u64,
+    nuri: String,
+    member: UserId,
+    permissions: Vec<PermissionV0>,
+) -> Result<(), String> {
+    doc_member_update(
+        session_id,
+        nuri,
+        AppRequestPayload::new_add_member(member, permissions),
+    )
+    .await
+}
+
+/// Removes a member from a document. Only the owners can do it, as the removal is signed by them,
+/// and followed by a refresh of the capabilities of the document.
+pub async fn doc_remove_member(
+    session_id: u64,
+    nuri: String,
+    member: UserId,
+    banned: bool,
+) -> Result<(), String> {
+    doc_member_update(
+        session_id,
+        nuri,
+        AppRequestPayload::new_remove_member(member, banned),
+    )
+    .await
+}
+
 pub async fn get_broker() -> Result<async_std::sync::RwLockWriteGuard<'static, LocalBroker>, NgError>
 {
     let broker = match LOCAL_BROKER.get() {
@@ -2903,6 +3306,22 @@ pub async fn orm_start_graph(
     app_request_stream(request).await
 }
 
+/// Restarts an orm subscription after a reconnect, for a client that holds the state at `known_heads`.
+/// The first response is a `GraphOrmResumed` with the patches since those heads,
+/// or a `GraphOrmInitial` with the full state if the subscription could not be resumed.
+pub async fn orm_resume_graph(
+    graph_scope: Vec<NuriV0>,
+    subject_scope: Vec<String>,
+    shape_type: OrmShapeType,
+    known_heads: Vec<ObjectId>,
+    session_id: u64,
+) -> Result<(Receiver<AppResponse>, CancelFn), NgError> {
+    let mut request =
+        AppRequest::new_orm_resume_graph(graph_scope, subject_scope, shape_type, known_heads);
+    request.set_session_id(session_id);
+    app_request_stream(request).await
+}
+
 pub async fn orm_start_discrete(
     nuri: NuriV0,
     session_id: u64,
@@ -2966,6 +3385,30 @@ pub async fn doc_query_quads_for_shape_type(
         .query_quads_for_shape(&nuris, schema, shape, filter_subjects)
 }
 
+/// Validates the graph of a document (or of the whole user site if `nuri` is None) against a shape.
+pub async fn doc_validate_shape(
+    session_id: u64,
+    nuri: Option<String>,
+    shape_type: OrmShapeType,
+) -> Result<OrmValidationReport, NgError> {
+    let nuri = match nuri {
+        Some(n) => NuriV0::new_from(&n)?,
+        None => NuriV0::new_entire_user_site(),
+    };
+    let request = AppRequest::V0(AppRequestV0 {
+        command: AppRequestCommandV0::new_validate(),
+        nuri,
+        payload: Some(AppRequestPayload::new_validate(shape_type)),
+        session_id,
+    });
+
+    match app_request(request).await? {
+        AppResponse::V0(AppResponseV0::ValidationReport(report)) => Ok(report),
+        AppResponse::V0(AppResponseV0::Error(e)) => Err(NgError::OrmError(e)),
+        _ => Err(NgError::InvalidResponse),
+    }
+}
+
 pub async fn doc_create(
     session_id: u64,
     crdt: String,
@@ -3042,6 +3485,9 @@ pub async fn app_request(request: AppRequest) -> Result<AppResponse, NgError> {
                 let session = broker.opened_sessions_list[real_session_id]
                     .as_mut()
                     .ok_or(NgError::SessionNotFound)?;
+                if let Some(access) = session.app_access.as_mut() {
+                    session.verifier.check_app_access(access, &request)?;
+                }
                 session.verifier.app_request(request).await
             }
         }
@@ -3071,12 +3517,314 @@ pub async fn app_request_stream(
                 let session = broker.opened_sessions_list[real_session_id]
                     .as_mut()
                     .ok_or(NgError::SessionNotFound)?;
-                session.verifier.app_request_stream(request).await
+                if let Some(access) = session.app_access.as_mut() {
+                    session.verifier.check_app_access(access, &request)?;
+                }
+                match request {
+                    AppRequest::V0(AppRequestV0 {
+                        command: AppRequestCommandV0::GraphImport,
+                        nuri,
+                        payload,
+                        ..
+                    }) => {
+                        let mut run = session.verifier.graph_import_start(nuri, payload).await?;
+                        if session.app_access.is_some() {
+                            run.restrict_to_target();
+                        }
+                        Ok(spawn_graph_import(real_session_id, run))
+                    }
+                    _ => session.verifier.app_request_stream(request).await,
+                }
+            }
+        }
+    }
+}
+
+/// Commits the batches of a graph import in a spawned task, locking the local broker for one batch at a time,
+/// so the GraphImportProgress responses are streamed while the import runs.
+fn spawn_graph_import(
+    session_id: usize,
+    mut run: GraphImportRun,
+) -> (Receiver<AppResponse>, CancelFn) {
+    let (mut tx, rx) = mpsc::unbounded::<AppResponse>();
+    let mut tx_cancel = tx.clone();
+    spawn_and_log_error(async move {
+        let response = loop {
+            let res = match LOCAL_BROKER.get() {
+                None | Some(Err(_)) => Err(NgError::LocalBrokerNotInitialized),
+                Some(Ok(broker)) => {
+                    let mut broker = broker.write().await;
+                    match broker
+                        .opened_sessions_list
+                        .get_mut(session_id)
+                        .and_then(|session| session.as_mut())
+                    {
+                        None => Err(NgError::SessionNotFound),
+                        Some(session) => session
+                            .verifier
+                            .graph_import_next_batch(&mut run)
+                            .await
+                            .map_err(|e| e.into()),
+                    }
+                }
+            };
+            match res {
+                Err(e) => break AppResponse::error(e.to_string()),
+                Ok(false) => break run.into_response(),
+                Ok(true) => {
+                    if tx.send(run.progress()).await.is_err() {
+                        // cancelled
+                        return Ok(());
+                    }
+                }
+            }
+        };
+        let _ = tx.send(response).await;
+        let _ = tx.send(AppResponse::V0(AppResponseV0::EndOfStream)).await;
+        tx.close_channel();
+        Ok(())
+    });
+    let fnonce = Box::new(move || {
+        tx_cancel.close_channel();
+    });
+    (rx, fnonce as CancelFn)
+}
+
+/// Changes the access grants saved in an opened wallet, then replaces and saves its encrypted Wallet.
+async fn wallet_update_access_grants<T>(
+    broker: &mut LocalBroker,
+    wallet_name: &String,
+    update: impl FnOnce(&Wallet, &mut SensitiveWallet) -> Result<(Wallet, T), NgWalletError>,
+) -> Result<T, NgError> {
+    let opened_wallet = broker
+        .opened_wallets
+        .get_mut(wallet_name)
+        .ok_or(NgError::WalletNotFound)?;
+    let lws = broker
+        .wallets
+        .get_mut(wallet_name)
+        .ok_or(NgError::WalletNotFound)?;
+    let (wallet, res) = update(&lws.wallet, &mut opened_wallet.wallet)?;
+    lws.wallet = wallet;
+    if !lws.in_memory {
+        LocalBroker::wallet_save(broker).await?;
+    }
+    Ok(res)
+}
+
+/// Grants to a third-party app the mandatory access requests of its manifest, and the optional ones whose ID is in `accepted`.
+///
+/// The grants are saved in the wallet, and returned. They apply to the sessions bound to the app with [app_session_bind].
+pub async fn app_grant_access(
+    wallet_name: &String,
+    manifest: AppManifest,
+    accepted: Vec<String>,
+) -> Result<Vec<AccessGrantV0>, NgError> {
+    let mut broker = match LOCAL_BROKER.get() {
+        None | Some(Err(_)) => return Err(NgError::LocalBrokerNotInitialized),
+        Some(Ok(broker)) => broker.write().await,
+    };
+    let app = manifest.app_id()?;
+    let grantee = broker
+        .opened_wallets
+        .get(wallet_name)
+        .ok_or(NgError::WalletNotFound)?
+        .wallet
+        .personal_identity();
+    let grants = manifest.grant(&accepted, grantee)?;
+    wallet_update_access_grants(&mut broker, wallet_name, |wallet, opened_wallet| {
+        Ok((
+            add_access_grants(wallet, opened_wallet, &app, grants.clone())?,
+            (),
+        ))
+    })
+    .await?;
+    Ok(grants)
+}
+
+/// Revokes a grant given to a third-party app. It is removed from the wallet, and stops applying right away to the sessions bound to the app.
+pub async fn app_revoke_access(
+    wallet_name: &String,
+    app: String,
+    grant_id: String,
+) -> Result<(), NgError> {
+    let mut broker = match LOCAL_BROKER.get() {
+        None | Some(Err(_)) => return Err(NgError::LocalBrokerNotInitialized),
+        Some(Ok(broker)) => broker.write().await,
+    };
+    if !broker
+        .opened_wallets
+        .get(wallet_name)
+        .ok_or(NgError::WalletNotFound)?
+        .wallet
+        .access_grants(&app)
+        .iter()
+        .any(|g| g.id == grant_id)
+    {
+        return Err(NgError::NotFound);
+    }
+    wallet_update_access_grants(&mut broker, wallet_name, |wallet, opened_wallet| {
+        Ok((
+            remove_access_grant(wallet, opened_wallet, &app, &grant_id)?,
+            (),
+        ))
+    })
+    .await?;
+    for session in broker.opened_sessions_list.iter_mut().flatten() {
+        if session.config.wallet_name() == *wallet_name {
+            if let Some(access) = session.app_access.as_mut() {
+                if access.app == app {
+                    access.revoke(&grant_id);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Binds a session to a third-party app (identified by the nuri or the origin of the app), that was granted access with [app_grant_access].
+///
+/// The grants are loaded from the opened wallet, for the identity of the app. Only the local sessions can be bound,
+/// the sessions on a broker are bound when they start, see [app_sign_access].
+/// All the AppRequests of the session are then checked against the grants of the app.
+/// The grants with a scope of Once* are removed from the wallet, as they are only valid for this session.
+pub async fn app_session_bind(session_id: u64, app: String) -> Result<(), NgError> {
+    let mut broker = match LOCAL_BROKER.get() {
+        None | Some(Err(_)) => return Err(NgError::LocalBrokerNotInitialized),
+        Some(Ok(broker)) => broker.write().await,
+    };
+
+    match &broker.config {
+        LocalBrokerConfig::Headless(_) => return Err(NgError::LocalBrokerIsHeadless),
+        _ => {
+            let (real_session_id, is_remote) = broker.get_real_session_id_for_mut(session_id)?;
+            if is_remote {
+                // the remote sessions are bound to the grants when they start, with a SignedAppAccess
+                return Err(NgError::NotImplemented);
             }
+            let wallet_name = broker.opened_sessions_list[real_session_id]
+                .as_ref()
+                .ok_or(NgError::SessionNotFound)?
+                .config
+                .wallet_name();
+            let grants = broker
+                .opened_wallets
+                .get(&wallet_name)
+                .ok_or(NgError::WalletNotFound)?
+                .wallet
+                .access_grants(&app)
+                .to_vec();
+            if grants.is_empty() {
+                return Err(NgError::PermissionDenied);
+            }
+            let once: Vec<String> = grants
+                .iter()
+                .filter(|g| g.scope.is_once())
+                .map(|g| g.id.clone())
+                .collect();
+            if !once.is_empty() {
+                wallet_update_access_grants(&mut broker, &wallet_name, |wallet, opened_wallet| {
+                    let mut wallet = wallet.clone();
+                    for grant_id in once.iter() {
+                        wallet = remove_access_grant(&wallet, opened_wallet, &app, grant_id)?;
+                    }
+                    Ok((wallet, ()))
+                })
+                .await?;
+            }
+            broker.opened_sessions_list[real_session_id]
+                .as_mut()
+                .ok_or(NgError::SessionNotFound)?
+                .app_access = Some(AppAccess::new(app, grants));
+            Ok(())
         }
     }
 }
 
+/// Signs the grants of a third-party app that are saved in an opened wallet, for the peer of the app.
+///
+/// The app sends them when it opens a session on a broker (see [session_headless_start_for_app]), and the broker checks
+/// all the AppRequests of the session against them. The grants with a scope of Once* are removed from the wallet,
+/// as they are given to the app.
+pub async fn app_sign_access(
+    wallet_name: &String,
+    app: String,
+    peer: DirectPeerId,
+) -> Result<SignedAppAccess, NgError> {
+    let mut broker = match LOCAL_BROKER.get() {
+        None | Some(Err(_)) => return Err(NgError::LocalBrokerNotInitialized),
+        Some(Ok(broker)) => broker.write().await,
+    };
+    let (grants, user_privkey) = {
+        let opened_wallet = &broker
+            .opened_wallets
+            .get(wallet_name)
+            .ok_or(NgError::WalletNotFound)?
+            .wallet;
+        let grants = opened_wallet.access_grants(&app).to_vec();
+        if grants.is_empty() {
+            return Err(NgError::PermissionDenied);
+        }
+        let user_privkey = match opened_wallet {
+            SensitiveWallet::V0(v0) => v0
+                .sites
+                .get(&opened_wallet.personal_identity().to_string())
+                .and_then(|site| site.get_individual_user_priv_key())
+                .ok_or(NgError::NotFound)?,
+        };
+        (grants, user_privkey)
+    };
+    let once: Vec<String> = grants
+        .iter()
+        .filter(|g| g.scope.is_once())
+        .map(|g| g.id.clone())
+        .collect();
+    if !once.is_empty() {
+        wallet_update_access_grants(&mut broker, wallet_name, |wallet, opened_wallet| {
+            let mut wallet = wallet.clone();
+            for grant_id in once.iter() {
+                wallet = remove_access_grant(&wallet, opened_wallet, &app, grant_id)?;
+            }
+            Ok((wallet, ()))
+        })
+        .await?;
+    }
+    SignedAppAccess::new(AppAccess::new(app, grants), peer, &user_privkey)
+}
+
+/// Starts a session on the broker of a headless LocalBroker, for a third-party app.
+///
+/// `access` are the grants of the app, signed by the user with [app_sign_access] for the peer of this LocalBroker
+/// (the public key of the `client_peer_key` of its HeadlessConfig).
+/// The broker checks all the AppRequests of the session against them.
+pub async fn session_headless_start_for_app(
+    user_id: UserId,
+    access: SignedAppAccess,
+) -> Result<SessionInfo, NgError> {
+    let mut broker = match LOCAL_BROKER.get() {
+        None | Some(Err(_)) => return Err(NgError::LocalBrokerNotInitialized),
+        Some(Ok(broker)) => broker.write().await,
+    };
+    broker.start_headless_session(user_id, Some(access)).await
+}
+
+/// Lists the grants given to the third-party apps, by app, that are saved in an opened wallet.
+pub async fn app_list_access(
+    wallet_name: &String,
+) -> Result<HashMap<String, Vec<AccessGrantV0>>, NgError> {
+    let broker = match LOCAL_BROKER.get() {
+        None | Some(Err(_)) => return Err(NgError::LocalBrokerNotInitialized),
+        Some(Ok(broker)) => broker.read().await,
+    };
+    Ok(broker
+        .opened_wallets
+        .get(wallet_name)
+        .ok_or(NgError::WalletNotFound)?
+        .wallet
+        .all_access_grants()
+        .clone())
+}
+
 /// retrieves the ID of one of the 3 stores of a the personal Site (3P: public, protected, or private)
 pub async fn personal_site_store(
     session_id: u64,
@@ -3372,7 +4120,7 @@ mod test {
             .expect("");
     }
 
-    async fn import_session_for_test() -> (UserId, String) {
+    async fn import_session_for_test() -> (UserId, String, u64) {
         let wallet_file = read("tests/wallet.ngw").expect("read wallet file");
         let opened_wallet_file = read("tests/opened_wallet.ngw").expect("read opened_wallet file");
         let opened_wallet: SensitiveWallet =
@@ -3391,16 +4139,16 @@ mod test {
             .await
             .expect("wallet_import");
 
-        let _session = session_start(SessionConfig::new_in_memory(&user_id, &wallet_name))
+        let session = session_start(SessionConfig::new_in_memory(&user_id, &wallet_name))
             .await
             .expect("");
 
-        (user_id, wallet_name)
+        (user_id, wallet_name, session.session_id)
     }
 
     #[async_std::test]
     async fn import_wallet() {
-        let (user_id, wallet_name) = import_session_for_test().await;
+        let (user_id, wallet_name, _) = import_session_for_test().await;
 
         let status = user_connect(&user_id).await.expect("user_connect");
 
@@ -3417,6 +4165,51 @@ mod test {
         wallet_close(&wallet_name).await.expect("wallet_close");
     }
 
+    #[async_std::test]
+    async fn app_session_grants_from_wallet() {
+        let (user_id, wallet_name, session_id) = import_session_for_test().await;
+        let app = "https://app.example.com".to_string();
+
+        // an app without grants in the wallet cannot bind a session
+        assert_eq!(
+            app_session_bind(session_id, app.clone()).await,
+            Err(NgError::PermissionDenied)
+        );
+
+        let manifest = AppManifest::new_for_origin_all_access_v0(app.clone());
+        let grants = app_grant_access(&wallet_name, manifest, vec![])
+            .await
+            .expect("app_grant_access");
+        app_session_bind(session_id, app.clone())
+            .await
+            .expect("app_session_bind");
+
+        let mut request = AppRequest::new(
+            AppRequestCommandV0::Fetch(AppFetchContentV0::ReadQuery),
+            NuriV0::new_entire_user_site(),
+            Some(AppRequestPayload::new_sparql_query(
+                "SELECT * WHERE { GRAPH ?g { ?s ?p ?o } }".to_string(),
+                None,
+            )),
+        );
+        request.set_session_id(session_id);
+        assert!(app_request(request.clone()).await.is_ok());
+
+        // once the grants are revoked in the wallet, the session is denied
+        for grant in grants {
+            app_revoke_access(&wallet_name, app.clone(), grant.id)
+                .await
+                .expect("app_revoke_access");
+        }
+        assert_eq!(
+            app_request(request).await.unwrap_err(),
+            NgError::PermissionDenied
+        );
+
+        session_stop(&user_id).await.expect("session_stop");
+        wallet_close(&wallet_name).await.expect("wallet_close");
+    }
+
     #[async_std::test]
     async fn recovery_pdf() {
         let wallet_file = read("tests/wallet.ngw").expect("read wallet file");
@@ -0,0 +1,878 @@
+// Copyright (c) 2022-2025 Niko Bonnieure, Par le Peuple, NextGraph.org developers
+// All rights reserved.
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE2 or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Local HTTP endpoint implementing the SPARQL 1.1 Protocol and Graph Store Protocol for a session
+//!
+//! The endpoint is bound to localhost, and each request must carry the token of the endpoint
+//! in an `Authorization: Bearer <token>` header.
+//!
+//! - `/sparql` : queries with GET `?query=` or POST (`application/sparql-query` or form-urlencoded `query=`),
+//!   updates with POST (`application/sparql-update` or form-urlencoded `update=`).
+//!   An optional `nuri` parameter selects the target. Queries default to the entire user site, updates to the private store.
+//! - `/store` : GET, PUT, POST and DELETE on `?graph=<nuri of a document>`, or on `?default` for the private store.
+//!
+//! The results are negotiated with the Accept header, and streamed with a chunked transfer encoding.
+//!
+//! Browser apps can call the endpoint: CORS preflight requests are answered without a token,
+//! and all the responses allow any origin, as the token is never sent implicitly by browsers.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use async_std::io::{ReadExt, WriteExt};
+use async_std::net::{TcpListener, TcpStream};
+use async_std::task;
+use futures::StreamExt;
+
+use ng_oxigraph::oxigraph::sparql::results::QueryResultsFormat;
+use ng_oxigraph::oxrdfio::RdfFormat;
+use ng_oxigraph::spargebra::Query;
+
+use ng_net::app_protocol::*;
+use ng_net::utils::Receiver;
+use ng_repo::errors::NgError;
+#[allow(unused_imports)]
+use ng_repo::log::*;
+use ng_repo::utils::random_key;
+use ng_verifier::types::CancelFn;
+
+use crate::local_broker::{app_request, app_request_stream};
+
+/// Maximum size of the head of an HTTP request
+const MAX_HEAD_SIZE: usize = 65536;
+
+/// Maximum size of the body of an HTTP request (queries, updates and uploaded graphs)
+const MAX_BODY_SIZE: usize = 256 * 1024 * 1024;
+
+/// Added to all the responses
+const CORS_ALLOW_ORIGIN: &str = "Access-Control-Allow-Origin: *\r\n";
+
+/// A running SPARQL endpoint. It stops when [`SparqlEndpoint::stop`] is called.
+pub struct SparqlEndpoint {
+    addr: SocketAddr,
+    token: String,
+    task: task::JoinHandle<()>,
+}
+
+impl SparqlEndpoint {
+    /// The local address the endpoint is listening on
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The bearer token that clients must send in the Authorization header
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub async fn stop(self) {
+        let _ = self.task.cancel().await;
+    }
+}
+
+/// Starts a SPARQL endpoint on `127.0.0.1:port` (0 for a random port) for the given session.
+pub async fn sparql_endpoint_start(session_id: u64, port: u16) -> Result<SparqlEndpoint, NgError> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .await
+        .map_err(|_| NgError::IoError)?;
+    let addr = listener.local_addr().map_err(|_| NgError::IoError)?;
+    let token = base64_url::encode(&random_key());
+
+    let listener_token = token.clone();
+    let task = task::spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(stream) = incoming.next().await {
+            if let Ok(stream) = stream {
+                let token = listener_token.clone();
+                task::spawn(async move {
+                    if let Err(e) = handle_connection(stream, session_id, &token).await {
+                        log_debug!("SPARQL endpoint connection error {}", e);
+                    }
+                });
+            }
+        }
+    });
+    log_info!("SPARQL endpoint listening on http://{}/sparql", addr);
+    Ok(SparqlEndpoint { addr, token, task })
+}
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    params: Vec<(String, String)>,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+impl HttpRequest {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(|s| s.as_str())
+    }
+
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// the media type of the body, without its parameters
+    fn content_type(&self) -> Option<&str> {
+        self.header("content-type")
+            .map(|c| c.split(';').next().unwrap_or("").trim())
+    }
+}
+
+#[derive(Debug)]
+struct HttpError(u16, String);
+
+impl HttpError {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        Self(status, message.into())
+    }
+}
+
+/// Reads a request. The token is checked as soon as the head is parsed, so the body of a request
+/// without a valid token is never read. CORS preflight requests don't need a token.
+async fn read_request(
+    stream: &mut TcpStream,
+    token: &str,
+) -> Result<Option<HttpRequest>, HttpError> {
+    let mut buf = Vec::with_capacity(4096);
+    let mut chunk = [0u8; 4096];
+    let (head_len, method, path, headers) = loop {
+        let n = stream
+            .read(&mut chunk)
+            .await
+            .map_err(|_| HttpError::new(400, "Read error"))?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        let mut parsed_headers = [httparse::EMPTY_HEADER; 64];
+        let mut req = httparse::Request::new(&mut parsed_headers);
+        match req.parse(&buf) {
+            Ok(httparse::Status::Complete(head_len)) => {
+                let mut headers = HashMap::new();
+                for h in req.headers.iter() {
+                    headers.insert(
+                        h.name.to_lowercase(),
+                        String::from_utf8_lossy(h.value).to_string(),
+                    );
+                }
+                break (
+                    head_len,
+                    req.method.unwrap_or("").to_string(),
+                    req.path.unwrap_or("/").to_string(),
+                    headers,
+                );
+            }
+            Ok(httparse::Status::Partial) => {
+                if buf.len() > MAX_HEAD_SIZE {
+                    return Err(HttpError::new(431, "Request head too large"));
+                }
+            }
+            Err(_) => return Err(HttpError::new(400, "Invalid HTTP request")),
+        }
+    };
+
+    let authorized = headers
+        .get("authorization")
+        .and_then(|a| a.strip_prefix("Bearer "))
+        .map_or(false, |t| token_matches(t.trim(), token));
+    if method != "OPTIONS" && !authorized {
+        return Err(HttpError::new(401, "Missing or invalid token"));
+    }
+
+    if headers.contains_key("transfer-encoding") {
+        return Err(HttpError::new(501, "Chunked requests are not supported"));
+    }
+    let content_length = match headers.get("content-length") {
+        None => 0,
+        Some(l) => l
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| HttpError::new(400, "Invalid Content-Length"))?,
+    };
+    if content_length > MAX_BODY_SIZE {
+        return Err(HttpError::new(413, "Request body too large"));
+    }
+    let mut body = buf.split_off(head_len);
+    if body.len() < content_length {
+        let already = body.len();
+        body.resize(content_length, 0);
+        stream
+            .read_exact(&mut body[already..])
+            .await
+            .map_err(|_| HttpError::new(400, "Incomplete body"))?;
+    }
+    body.truncate(content_length);
+
+    let url = url::Url::parse(&format!("http://localhost{path}"))
+        .map_err(|_| HttpError::new(400, "Invalid path"))?;
+    let params = url.query_pairs().into_owned().collect();
+    Ok(Some(HttpRequest {
+        method,
+        path: url.path().to_string(),
+        params,
+        headers,
+        body,
+    }))
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        406 => "Not Acceptable",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        431 => "Request Header Fields Too Large",
+        501 => "Not Implemented",
+        _ => "Internal Server Error",
+    }
+}
+
+async fn write_response(
+    stream: &mut TcpStream,
+    status: u16,
+    content_type: &str,
+    body: &[u8],
+) -> std::io::Result<()> {
+    let mut head = format!(
+        "HTTP/1.1 {status} {}\r\nContent-Length: {}\r\nConnection: close\r\n{CORS_ALLOW_ORIGIN}",
+        reason(status),
+        body.len()
+    );
+    if status == 401 {
+        head.push_str("WWW-Authenticate: Bearer\r\n");
+    }
+    if !body.is_empty() {
+        head.push_str(&format!("Content-Type: {content_type}\r\n"));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body).await?;
+    stream.flush().await
+}
+
+/// Answers a CORS preflight request. Browsers send them without the Authorization header.
+async fn write_preflight(stream: &mut TcpStream) -> std::io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 204 No Content\r\nContent-Length: 0\r\nConnection: close\r\n{CORS_ALLOW_ORIGIN}\
+         Access-Control-Allow-Methods: GET, POST, PUT, DELETE, OPTIONS\r\n\
+         Access-Control-Allow-Headers: Authorization, Content-Type, Accept\r\n\
+         Access-Control-Max-Age: 86400\r\n\r\n"
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.flush().await
+}
+
+/// Compares the token in a time that doesn't depend on the position of the first differing byte
+fn token_matches(provided: &str, token: &str) -> bool {
+    let (provided, token) = (provided.as_bytes(), token.as_bytes());
+    provided.len() == token.len()
+        && provided
+            .iter()
+            .zip(token.iter())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+async fn write_error(stream: &mut TcpStream, error: HttpError) -> std::io::Result<()> {
+    write_response(
+        stream,
+        error.0,
+        "text/plain; charset=utf-8",
+        error.1.as_bytes(),
+    )
+    .await
+}
+
+/// Forwards the FileBinary chunks of the stream as a chunked 200 response.
+/// An error received before any chunk is returned as an HttpError instead.
+/// If the stream fails after the first chunk, the connection is closed without the last (empty) chunk,
+/// so that the client doesn't take the truncated response for a complete one.
+async fn stream_response(
+    stream: &mut TcpStream,
+    content_type: &str,
+    mut receiver: Receiver<AppResponse>,
+    cancel: CancelFn,
+) -> Result<(), HttpError> {
+    let mut started = false;
+    let mut complete = false;
+    while let Some(AppResponse::V0(response)) = receiver.next().await {
+        let chunk = match response {
+            AppResponseV0::FileBinary(chunk) => chunk,
+            AppResponseV0::EndOfStream => {
+                complete = true;
+                break;
+            }
+            AppResponseV0::Error(e) if !started => return Err(HttpError::new(400, e)),
+            _ => {
+                log_debug!("SPARQL endpoint: stream interrupted");
+                break;
+            }
+        };
+        if !started {
+            started = true;
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n{CORS_ALLOW_ORIGIN}\r\n"
+            );
+            if stream.write_all(head.as_bytes()).await.is_err() {
+                cancel();
+                return Ok(());
+            }
+        }
+        let mut data = format!("{:x}\r\n", chunk.len()).into_bytes();
+        data.extend_from_slice(&chunk);
+        data.extend_from_slice(b"\r\n");
+        if stream.write_all(&data).await.is_err() {
+            cancel();
+            return Ok(());
+        }
+    }
+    if !started {
+        let _ = write_response(stream, 200, content_type, &[]).await;
+        return Ok(());
+    }
+    if !complete {
+        cancel();
+        let _ = stream.flush().await;
+        let _ = stream.shutdown(std::net::Shutdown::Both);
+        return Ok(());
+    }
+    let _ = stream.write_all(b"0\r\n\r\n").await;
+    let _ = stream.flush().await;
+    Ok(())
+}
+
+/// Picks the first media type of the Accept header that is supported, or the default one.
+fn negotiate(
+    accept: Option<&str>,
+    default: &str,
+    supported: impl Fn(&str) -> bool,
+) -> Result<String, HttpError> {
+    let accept = match accept {
+        None => return Ok(default.to_string()),
+        Some(a) if a.trim().is_empty() => return Ok(default.to_string()),
+        Some(a) => a,
+    };
+    let mut media_types: Vec<(f32, &str)> = accept
+        .split(',')
+        .map(|m| {
+            let mut parts = m.split(';');
+            let media_type = parts.next().unwrap_or("").trim();
+            let q = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (q, media_type)
+        })
+        .filter(|(q, _)| *q > 0.0)
+        .collect();
+    media_types.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    for (_, media_type) in media_types {
+        if media_type == "*/*"
+            || (media_type.ends_with("/*")
+                && default.starts_with(&media_type[..media_type.len() - 1]))
+        {
+            return Ok(default.to_string());
+        }
+        if supported(media_type) {
+            return Ok(media_type.to_string());
+        }
+    }
+    Err(HttpError::new(406, "No acceptable format"))
+}
+
+/// relative IRIs are resolved against the document, when the target is a document
+fn repo_base(nuri: &Option<NuriV0>) -> Option<String> {
+    nuri.as_ref()
+        .filter(|n| n.target.is_repo_id())
+        .map(|n| n.repo())
+}
+
+fn nuri_param(request: &HttpRequest, name: &str) -> Result<Option<NuriV0>, HttpError> {
+    request
+        .param(name)
+        .map(|n| NuriV0::new_from(&n.to_string()).map_err(|e| HttpError::new(400, e.to_string())))
+        .transpose()
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    session_id: u64,
+    token: &str,
+) -> std::io::Result<()> {
+    let request = match read_request(&mut stream, token).await {
+        Ok(None) => return Ok(()),
+        Ok(Some(request)) => request,
+        Err(e) => return write_error(&mut stream, e).await,
+    };
+    if request.method == "OPTIONS" {
+        return write_preflight(&mut stream).await;
+    }
+    let res = match request.path.as_str() {
+        "/sparql" => handle_sparql(&mut stream, request, session_id).await,
+        "/store" => handle_graph_store(&mut stream, request, session_id).await,
+        _ => Err(HttpError::new(404, "Not found")),
+    };
+    match res {
+        Err(e) => write_error(&mut stream, e).await,
+        Ok(()) => Ok(()),
+    }
+}
+
+async fn handle_sparql(
+    stream: &mut TcpStream,
+    request: HttpRequest,
+    session_id: u64,
+) -> Result<(), HttpError> {
+    let mut form = vec![];
+    let (query, update) = match (request.method.as_str(), request.content_type()) {
+        ("GET", _) => (request.param("query").map(|q| q.to_string()), None),
+        ("POST", Some("application/sparql-query")) => (
+            Some(
+                String::from_utf8(request.body.clone())
+                    .map_err(|_| HttpError::new(400, "Invalid UTF-8"))?,
+            ),
+            None,
+        ),
+        ("POST", Some("application/sparql-update")) => (
+            None,
+            Some(
+                String::from_utf8(request.body.clone())
+                    .map_err(|_| HttpError::new(400, "Invalid UTF-8"))?,
+            ),
+        ),
+        ("POST", Some("application/x-www-form-urlencoded")) => {
+            form = url::form_urlencoded::parse(&request.body)
+                .into_owned()
+                .collect::<Vec<(String, String)>>();
+            let get = |name: &str| form.iter().find(|(k, _)| k == name).map(|(_, v)| v.clone());
+            (get("query"), get("update"))
+        }
+        ("POST", _) => return Err(HttpError::new(415, "Unsupported Content-Type")),
+        _ => return Err(HttpError::new(405, "Method not allowed")),
+    };
+    let nuri = match form.iter().find(|(k, _)| k == "nuri") {
+        Some((_, n)) => Some(NuriV0::new_from(n).map_err(|e| HttpError::new(400, e.to_string()))?),
+        None => nuri_param(&request, "nuri")?,
+    };
+
+    match (query, update) {
+        (Some(query), None) => {
+            let base = repo_base(&nuri);
+            let parsed = Query::parse(&query, base.as_deref())
+                .map_err(|e| HttpError::new(400, e.to_string()))?;
+            let format = match parsed {
+                Query::Select { .. } | Query::Ask { .. } => negotiate(
+                    request.header("accept"),
+                    QueryResultsFormat::Json.media_type(),
+                    |m| QueryResultsFormat::from_media_type(m).is_some(),
+                )?,
+                Query::Construct { .. } | Query::Describe { .. } => negotiate(
+                    request.header("accept"),
+                    RdfFormat::Turtle.media_type(),
+                    |m| RdfFormat::from_media_type(m).is_some(),
+                )?,
+            };
+            let req = AppRequest::V0(AppRequestV0 {
+                command: AppRequestCommandV0::new_read_query_stream(),
+                nuri: nuri.unwrap_or_else(NuriV0::new_entire_user_site),
+                payload: Some(AppRequestPayload::new_sparql_query_with_format(
+                    query,
+                    base,
+                    Some(format.clone()),
+                )),
+                session_id,
+            });
+            let (receiver, cancel) = app_request_stream(req)
+                .await
+                .map_err(|e| HttpError::new(500, e.to_string()))?;
+            stream_response(stream, &format, receiver, cancel).await
+        }
+        (None, Some(update)) => {
+            let base = repo_base(&nuri);
+            sparql_update(
+                session_id,
+                nuri.unwrap_or_else(NuriV0::new_private_store_target),
+                update,
+                base,
+            )
+            .await?;
+            write_response(stream, 204, "", &[])
+                .await
+                .map_err(|_| HttpError::new(500, "Write error"))
+        }
+        _ => Err(HttpError::new(
+            400,
+            "Exactly one query or update is expected",
+        )),
+    }
+}
+
+async fn sparql_update(
+    session_id: u64,
+    nuri: NuriV0,
+    update: String,
+    base: Option<String>,
+) -> Result<(), HttpError> {
+    let req = AppRequest::V0(AppRequestV0 {
+        command: AppRequestCommandV0::new_write_query(),
+        nuri,
+        payload: Some(AppRequestPayload::new_sparql_query(update, base)),
+        session_id,
+    });
+    match app_request(req).await {
+        Err(e) => Err(HttpError::new(500, e.to_string())),
+        Ok(AppResponse::V0(AppResponseV0::Error(e))) => Err(HttpError::new(400, e)),
+        Ok(_) => Ok(()),
+    }
+}
+
+async fn handle_graph_store(
+    stream: &mut TcpStream,
+    request: HttpRequest,
+    session_id: u64,
+) -> Result<(), HttpError> {
+    let nuri = match (nuri_param(&request, "graph")?, request.param("default")) {
+        (Some(nuri), None) => nuri,
+        (None, Some(_)) => NuriV0::new_private_store_target(),
+        _ => return Err(HttpError::new(400, "Expected either ?graph= or ?default")),
+    };
+    let clear = "DELETE WHERE { ?s ?p ?o }".to_string();
+
+    match request.method.as_str() {
+        "GET" => {
+            let format = negotiate(
+                request.header("accept"),
+                RdfFormat::Turtle.media_type(),
+                |m| RdfFormat::from_media_type(m).is_some(),
+            )?;
+            let req = AppRequest::V0(AppRequestV0 {
+                command: AppRequestCommandV0::new_rdf_export(),
+                nuri,
+                payload: Some(AppRequestPayload::new_rdf_export(format.clone())),
+                session_id,
+            });
+            let (receiver, cancel) = app_request_stream(req)
+                .await
+                .map_err(|e| HttpError::new(500, e.to_string()))?;
+            return stream_response(stream, &format, receiver, cancel).await;
+        }
+        "DELETE" => {
+            sparql_update(session_id, nuri, clear, None).await?;
+        }
+        "PUT" | "POST" => {
+            let content_type = request
+                .content_type()
+                .filter(|c| RdfFormat::from_media_type(c).is_some())
+                .ok_or_else(|| HttpError::new(415, "Unsupported Content-Type"))?
+                .to_string();
+            if request.method == "PUT" {
+                sparql_update(session_id, nuri.clone(), clear, None).await?;
+            }
+            let base = repo_base(&Some(nuri.clone()));
+            let req = AppRequest::V0(AppRequestV0 {
+                command: AppRequestCommandV0::new_graph_import(),
+                nuri,
+                payload: Some(AppRequestPayload::new_graph_import(
+                    content_type,
+                    base,
+                    DocGraphImportSource::Inline(request.body),
+                )),
+                session_id,
+            });
+            let (mut receiver, _cancel) = app_request_stream(req)
+                .await
+                .map_err(|e| HttpError::new(500, e.to_string()))?;
+            while let Some(AppResponse::V0(response)) = receiver.next().await {
+                match response {
+                    AppResponseV0::Error(e) => return Err(HttpError::new(400, e)),
+                    AppResponseV0::EndOfStream => break,
+                    _ => {}
+                }
+            }
+        }
+        _ => return Err(HttpError::new(405, "Method not allowed")),
+    }
+    write_response(stream, 204, "", &[])
+        .await
+        .map_err(|_| HttpError::new(500, "Write error"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::SinkExt;
+
+    fn supported(m: &str) -> bool {
+        QueryResultsFormat::from_media_type(m).is_some()
+    }
+
+    #[test]
+    pub fn test_negotiate() {
+        let json = QueryResultsFormat::Json.media_type();
+        assert_eq!(negotiate(None, json, supported).unwrap(), json);
+        assert_eq!(negotiate(Some(" "), json, supported).unwrap(), json);
+        assert_eq!(negotiate(Some("*/*"), json, supported).unwrap(), json);
+        assert_eq!(
+            negotiate(Some("application/*"), json, supported).unwrap(),
+            json
+        );
+        assert_eq!(
+            negotiate(Some("text/csv"), json, supported).unwrap(),
+            "text/csv"
+        );
+        // the highest quality that is supported wins, whatever the order
+        assert_eq!(
+            negotiate(
+                Some("text/html, text/csv;q=0.5, application/sparql-results+xml;q=0.8"),
+                json,
+                supported
+            )
+            .unwrap(),
+            "application/sparql-results+xml"
+        );
+        // q=0 means not acceptable
+        assert_eq!(
+            negotiate(Some("text/csv;q=0, */*;q=0.1"), json, supported).unwrap(),
+            json
+        );
+        assert_eq!(
+            negotiate(Some("text/html"), json, supported).unwrap_err().0,
+            406
+        );
+        assert_eq!(
+            negotiate(Some("text/csv;q=0"), json, supported)
+                .unwrap_err()
+                .0,
+            406
+        );
+    }
+
+    #[test]
+    pub fn test_token_matches() {
+        assert!(token_matches("abcdef", "abcdef"));
+        assert!(!token_matches("abcdeg", "abcdef"));
+        assert!(!token_matches("abcde", "abcdef"));
+        assert!(!token_matches("", "abcdef"));
+    }
+
+    /// Sends `raw` on a local connection, and parses it with read_request, with the token `abc`
+    async fn read_raw(raw: &[u8]) -> Result<Option<HttpRequest>, HttpError> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        client.write_all(raw).await.unwrap();
+        // so that reading past the end of `raw` fails instead of waiting
+        client.shutdown(std::net::Shutdown::Write).unwrap();
+        let (mut server, _) = listener.accept().await.unwrap();
+        read_request(&mut server, "abc").await
+    }
+
+    #[async_std::test]
+    pub async fn test_read_request() {
+        let request = read_raw(
+            b"POST /sparql?nuri=did%3Ang%3Ai&default HTTP/1.1\r\nHost: localhost\r\n\
+              Content-Type: application/sparql-query; charset=utf-8\r\n\
+              Authorization: Bearer abc\r\nContent-Length: 8\r\n\r\nASK {}\n\n",
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/sparql");
+        assert_eq!(request.param("nuri"), Some("did:ng:i"));
+        assert_eq!(request.param("default"), Some(""));
+        assert_eq!(request.param("graph"), None);
+        assert_eq!(request.header("authorization"), Some("Bearer abc"));
+        assert_eq!(request.content_type(), Some("application/sparql-query"));
+        assert_eq!(request.body, b"ASK {}\n\n");
+
+        // the body is truncated to the Content-Length
+        let request = read_raw(
+            b"PUT /store HTTP/1.1\r\nAuthorization: Bearer abc\r\nContent-Length: 2\r\n\r\nabcd",
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(request.body, b"ab");
+        assert_eq!(request.content_type(), None);
+
+        assert!(read_raw(b"").await.unwrap().is_none());
+    }
+
+    #[async_std::test]
+    pub async fn test_read_invalid_requests() {
+        assert_eq!(read_raw(b"NOT HTTP\r\n\r\n").await.unwrap_err().0, 400);
+        // the token is checked before the body is read
+        assert_eq!(
+            read_raw(b"POST /sparql HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc")
+                .await
+                .unwrap_err()
+                .0,
+            401
+        );
+        assert_eq!(
+            read_raw(
+                format!(
+                    "POST /sparql HTTP/1.1\r\nAuthorization: Bearer abd\r\nContent-Length: {}\r\n\r\n",
+                    MAX_BODY_SIZE
+                )
+                .as_bytes()
+            )
+            .await
+            .unwrap_err()
+            .0,
+            401
+        );
+        assert_eq!(
+            read_raw(
+                b"POST /sparql HTTP/1.1\r\nAuthorization: Bearer abc\r\nContent-Length: x\r\n\r\n"
+            )
+            .await
+            .unwrap_err()
+            .0,
+            400
+        );
+        assert_eq!(
+            read_raw(b"POST /sparql HTTP/1.1\r\nAuthorization: Bearer abc\r\nTransfer-Encoding: chunked\r\n\r\n")
+                .await
+                .unwrap_err()
+                .0,
+            501
+        );
+        assert_eq!(
+            read_raw(
+                format!(
+                    "POST /sparql HTTP/1.1\r\nAuthorization: Bearer abc\r\nContent-Length: {}\r\n\r\n",
+                    MAX_BODY_SIZE + 1
+                )
+                .as_bytes()
+            )
+            .await
+            .unwrap_err()
+            .0,
+            413
+        );
+        // the body is shorter than its Content-Length
+        assert_eq!(
+            read_raw(b"POST /sparql HTTP/1.1\r\nAuthorization: Bearer abc\r\nContent-Length: 10\r\n\r\nabc")
+                .await
+                .unwrap_err()
+                .0,
+            400
+        );
+    }
+
+    /// Sends `raw` to handle_connection, and returns the whole response
+    async fn exchange(raw: &[u8]) -> String {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        client.write_all(raw).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        handle_connection(server, 0, "secret").await.unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[async_std::test]
+    pub async fn test_preflight_and_authorization() {
+        let response = exchange(
+            b"OPTIONS /sparql HTTP/1.1\r\nOrigin: http://localhost:1421\r\n\
+              Access-Control-Request-Method: POST\r\n\
+              Access-Control-Request-Headers: authorization\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 204 No Content\r\n"));
+        assert!(response.contains("Access-Control-Allow-Origin: *\r\n"));
+        assert!(response
+            .contains("Access-Control-Allow-Headers: Authorization, Content-Type, Accept\r\n"));
+
+        let response = exchange(b"GET /sparql?query=ASK%7B%7D HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+        assert!(response.contains("WWW-Authenticate: Bearer\r\n"));
+        assert!(response.contains("Access-Control-Allow-Origin: *\r\n"));
+
+        let response = exchange(
+            b"GET /sparql?query=ASK%7B%7D HTTP/1.1\r\nAuthorization: Bearer secreT\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+
+        // authorized, but an unknown path
+        let response =
+            exchange(b"GET /other HTTP/1.1\r\nAuthorization: Bearer secret\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    /// Runs stream_response on the responses, and returns what the client received
+    async fn streamed(responses: Vec<AppResponseV0>) -> Result<String, HttpError> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (mut server, _) = listener.accept().await.unwrap();
+        let (mut tx, rx) = futures::channel::mpsc::unbounded::<AppResponse>();
+        for response in responses {
+            tx.send(AppResponse::V0(response)).await.unwrap();
+        }
+        tx.close_channel();
+        stream_response(&mut server, "text/plain", rx, Box::new(|| {})).await?;
+        drop(server);
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        Ok(response)
+    }
+
+    #[async_std::test]
+    pub async fn test_stream_response() {
+        let response = streamed(vec![
+            AppResponseV0::FileBinary(b"abc".to_vec()),
+            AppResponseV0::EndOfStream,
+        ])
+        .await
+        .unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("\r\n\r\n3\r\nabc\r\n0\r\n\r\n"));
+
+        // a failure in the middle of the stream doesn't end the chunked body
+        let response = streamed(vec![
+            AppResponseV0::FileBinary(b"abc".to_vec()),
+            AppResponseV0::Error("failed".to_string()),
+        ])
+        .await
+        .unwrap();
+        assert!(response.ends_with("\r\n\r\n3\r\nabc\r\n"));
+        let response = streamed(vec![AppResponseV0::FileBinary(b"abc".to_vec())])
+            .await
+            .unwrap();
+        assert!(response.ends_with("\r\n\r\n3\r\nabc\r\n"));
+
+        // an error before the first chunk is an error response
+        assert_eq!(
+            streamed(vec![AppResponseV0::Error("failed".to_string())])
+                .await
+                .unwrap_err()
+                .0,
+            400
+        );
+    }
+}